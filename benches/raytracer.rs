@@ -0,0 +1,121 @@
+// Run with `cargo bench`. Criterion drives the timing, so this target is
+// built with `harness = false` and `criterion` as a dev-dependency.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use glam::DVec3;
+use raytracer::environment::SkyGradient;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::mesh::Mesh;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::qbvh::{BvhBuildStrategy, Qbvh};
+use raytracer::ray::Ray;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::sampler::{IndependentSampler, Sampler};
+use raytracer::scene::presets::{self, RandomSpheresParams};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn grey() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.5,
+    )))))
+}
+
+// Small spheres scattered through a unit cube.
+fn spheres(count: usize) -> HittableList {
+    let mut sampler = IndependentSampler::new(1);
+    sampler.start_pixel(0, 0, 0);
+    let material = grey();
+    (0..count)
+        .map(|_| {
+            let center = DVec3::new(sampler.next_1d(), sampler.next_1d(), sampler.next_1d());
+            Arc::new(Sphere::new(center, 0.005, material.clone())) as Arc<dyn Hittable>
+        })
+        .collect()
+}
+
+// A rippled `n` x `n` grid over the unit square, two triangles per cell.
+fn rippled_grid(n: usize) -> Vec<Triangle> {
+    let material = grey();
+    let at = |i: usize, j: usize| {
+        let (x, z) = (i as f64 / n as f64, j as f64 / n as f64);
+        DVec3::new(x, 0.05 * (20.0 * x).sin() * (20.0 * z).cos(), z)
+    };
+    let mut triangles = Vec::with_capacity(2 * n * n);
+    for i in 0..n {
+        for j in 0..n {
+            let [a, b, c, d] = [at(i, j), at(i + 1, j), at(i + 1, j + 1), at(i, j + 1)];
+            triangles.push(Triangle::new([a, b, c], material.clone()));
+            triangles.push(Triangle::new([a, c, d], material.clone()));
+        }
+    }
+    triangles
+}
+
+fn bvh_build(c: &mut Criterion) {
+    let objects = spheres(20_000);
+    let mut group = c.benchmark_group("bvh_build");
+    for (name, strategy) in [
+        ("midpoint", BvhBuildStrategy::Midpoint),
+        ("sah", BvhBuildStrategy::default()),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || objects.clone(),
+                |objects| Qbvh::with_strategy(objects, strategy),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn mesh_intersection(c: &mut Criterion) {
+    let mesh = Mesh::new(rippled_grid(256));
+    let mut sampler = IndependentSampler::new(2);
+    sampler.start_pixel(0, 0, 0);
+    // Rays from above at scattered points of the mesh, slightly tilted.
+    let rays: Vec<Ray> = (0..1024)
+        .map(|_| {
+            let (x, z) = sampler.next_2d();
+            let target = DVec3::new(x, 0.0, z);
+            Ray::new(
+                target + DVec3::new(0.1, 1.0, 0.2),
+                DVec3::new(-0.1, -1.0, -0.2),
+            )
+        })
+        .collect();
+    c.bench_function("mesh_intersection", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| mesh.hit(black_box(ray), Interval::after(0.0)).is_some())
+                .count()
+        })
+    });
+}
+
+fn small_frame(c: &mut Criterion) {
+    let (camera, world) = presets::random_spheres(&RandomSpheresParams {
+        spheres: 100,
+        ..RandomSpheresParams::default()
+    });
+    let renderer = Renderer::new(world, Arc::new(SkyGradient::default()));
+    let settings = RenderSettings {
+        width: 64,
+        height: 36,
+        samples_per_pixel: 4,
+        max_depth: 8,
+        ..RenderSettings::default()
+    };
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    group.bench_function("random_spheres_64x36", |b| {
+        b.iter(|| renderer.render(&camera, &settings))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bvh_build, mesh_intersection, small_frame);
+criterion_main!(benches);
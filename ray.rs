@@ -4,11 +4,51 @@ use glam::DVec3;
 pub struct Ray {
     pub origin: DVec3,
     pub direction: DVec3,
+    // Instant within the shutter interval, in [0, 1]; only moving objects
+    // look at it.
+    pub time: f64,
+    // Wavelength in nanometres carried by spectral renders; `None` for RGB
+    // rays.
+    pub wavelength: Option<f64>,
+    pub kind: RayKind,
+}
+
+// What a ray is traced for, so that objects can hide from some of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RayKind {
+    // From the camera, and on through surfaces it passes straight through.
+    #[default]
+    Camera,
+    // Bounces off surfaces and scattering in media, and photons.
+    Indirect,
+    // Testing for what blocks the light from a light sample.
+    Shadow,
 }
 
 impl Ray {
     pub fn new(origin: DVec3, direction: DVec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+            wavelength: None,
+            kind: RayKind::Camera,
+        }
+    }
+
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+        self
     }
 
     pub fn at(&self, t: f64) -> DVec3 {
@@ -1,155 +1,5294 @@
+pub mod presets;
+
+use crate::accelerator::{Accelerator, UniformGrid};
+use crate::albedo_lut::AlbedoLut;
 use crate::bvh::BvhNode;
-use crate::camera::Camera;
-use crate::hittable::{Hittable, HittableList};
-use crate::material::{Dielectric, Lambertian, Metal};
+use crate::camera::{
+    ApertureShape, Camera, CameraProjection, LensEffects, PhysicalExposure, ShutterCurve, Stereo,
+    StereoLayout,
+};
+use crate::color::{blackbody, parse_css};
+use crate::color_space::{ColorSpace, DecodedTexture};
+use crate::environment::{Environment, EnvironmentMap, SkyGradient, SolidBackground, SunSky};
+use crate::error::RenderError;
+use crate::hittable::{hit_surface, Hittable, HittableList, AABB};
+use crate::ies::IesProfile;
+use crate::interval::Interval;
+use crate::lights::{
+    sphere_emission, triangle_emission, AnalyticLight, DirectionalLight, Emitter, LightSet,
+    PointLight, Portal, SpotLight,
+};
+use crate::lut::Lut;
+use crate::material::{
+    AnisotropicMetal, ComplexIor, Conductor, Cutout, Dielectric, DiffuseLight, Dispersion, Hair,
+    Identified, Lambertian, Layered, Medium, Metal, Mix, NormalMapped, Principled, ShadowCatcher,
+    Subsurface, ThinFilm, Volume, LAMBDA_D,
+};
+use crate::mipmap::{MipmappedTexture, NearestTexture, ReloadingTexture, TextureWatch};
+use crate::objects::capsule;
+use crate::objects::cone::Cone;
+use crate::objects::csg::{Csg, CsgOperation};
+use crate::objects::cuboid::Cuboid;
+use crate::objects::curve::{self, Curve, CurveShape};
+use crate::objects::cylinder::Cylinder;
+use crate::objects::disk::Disk;
+use crate::objects::dop;
+use crate::objects::heightfield::Heightfield;
 use crate::objects::mesh::Mesh;
+use crate::objects::motion::MotionTransformed;
+use crate::objects::obj::{self, Displacement, ObjOptions, Shading};
+use crate::objects::point_cloud::{self, PointCloud, Splat};
+use crate::objects::quadric::Quadric;
+use crate::objects::raymarch::{JuliaSet, Mandelbulb, MengerSponge, RayMarched};
+use crate::objects::sdf::{SdfExpr, SdfObject};
 use crate::objects::sphere::Sphere;
+use crate::objects::tagged::Tagged;
+use crate::objects::torus::Torus;
+use crate::objects::transform::{transform_box, Transformed};
+use crate::objects::triangle::Triangle;
+use crate::objects::visibility::Visibility;
+use crate::output::{scene_hash, OutputOptions};
+use crate::path::CatmullRom;
+use crate::procedural::{BrickTexture, GradientShape, GradientTexture, MarbleTexture, WoodTexture};
+use crate::qbvh::{BvhBuildStrategy, Qbvh};
+use crate::ray::Ray;
+use crate::renderer::{luminance, name_id, RenderSettings};
+use crate::sample_map::{SampleMap, SampleRegion};
+use crate::scatter::{self, ScatterSettings};
 use crate::texture::{CheckerTexture, ImageTexture, SolidColor, Texture};
-use glam::DVec3;
-use serde::Deserialize;
+use crate::uv_transform::UvTransform;
+use crate::volume::DensityGrid;
+use crate::wireframe::write_boxes_obj;
+use glam::{DAffine3, DMat3, DVec2, DVec3, DVec4};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
+use std::path::Path;
 use std::sync::Arc;
 
 
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SceneConfig {
-    pub aspect_ratio: Option<f64>,
+    // Replaces `render.width` and `render.height` when set.
+    pub resolution: Option<ResolutionPreset>,
     pub camera: CameraDef,
-    pub objects: Vec<ObjectDef>,
+    pub objects: Vec<SceneObjectDef>,
+    // Other scene files whose objects are merged into this one when it is
+    // loaded.
+    #[serde(default)]
+    pub include: Vec<IncludeDef>,
+    #[serde(default)]
+    pub lights: Vec<LightDef>,
+    // Openings that light from the background comes in through, such as
+    // the windows of an interior; see `Portal`.
+    #[serde(default)]
+    pub portals: Vec<PortalDef>,
+    #[serde(default)]
+    pub paths: HashMap<String, PathDef>,
+    // Meshes placed by `instance` objects. Each is loaded and given its own
+    // BVH once, however many instances share it.
+    #[serde(default)]
+    pub meshes: HashMap<String, MeshDef>,
+    // Materials and textures that objects can refer to by name instead of
+    // repeating the definition. Each is built once and shared.
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialDef>,
+    #[serde(default)]
+    pub textures: HashMap<String, TextureDef>,
+    // Bakes GGX albedo tables when the scene loads, with which principled
+    // and anisotropic metal materials put back the energy rough microfacet
+    // lobes lose; see `AlbedoLut`.
+    #[serde(default)]
+    pub energy_compensation: bool,
+    pub background: Option<EnvironmentDef>,
+    // Units and up axis of the mesh files the scene loads, unless a mesh
+    // gives its own. Meshes are scaled to metres and turned to Y up as they
+    // load, so that models from CAD and modelling tools can be mixed
+    // without exporting them again. Meshes of included files follow the
+    // including scene.
+    #[serde(default)]
+    pub units: Units,
+    #[serde(default)]
+    pub up_axis: UpAxis,
+    #[serde(default)]
+    pub settle: SettleDef,
+    #[serde(default)]
+    pub accelerator: AcceleratorDef,
+    #[serde(default)]
+    pub render: RenderSettings,
+    // Spends fewer samples or pixels on parts of the image; see
+    // `SampleMap`.
+    pub sample_map: Option<SampleMapDef>,
+    #[serde(default)]
+    pub output: OutputOptions,
 }
 
-#[derive(Deserialize)]
-pub struct CameraDef {
-    lookfrom: DVec3,
-    lookat: DVec3,
-    vup: DVec3,
-    vfov: f64,
-    aperture: f64,
-    focus_dist: f64,
+#[derive(Deserialize, Serialize)]
+pub struct SampleMapDef {
+    // Greyscale image stretched over the frame, white for every sample.
+    pub mask: Option<String>,
+    #[serde(default)]
+    pub regions: Vec<SampleRegion>,
 }
 
-#[derive(Deserialize)]
-#[serde(tag = "type")]
-enum ObjectDef {
-    #[serde(rename = "sphere")]
-    Sphere(SphereDef),
-    #[serde(rename = "mesh")]
-    Mesh(MeshDef),
+// Objects from the scene file at `path`, together with the `materials`,
+// `textures`, `meshes` and `paths` entries they use; the file's camera,
+// lights and settings are ignored, and its own includes are followed.
+// `path` is relative to the file the include is in. Library entries of the
+// including file win over included ones of the same name, and included
+// files may only share a name for the same entry. Without a transform the
+// objects are added as they are, keeping their names, and a file already
+// added that way isn't added again; with one they become a single unnamed
+// group.
+#[derive(Deserialize, Serialize)]
+pub struct IncludeDef {
+    pub path: String,
+    pub transform: Option<TransformDef>,
 }
 
+// The parts of a scene file that an include takes.
 #[derive(Deserialize)]
-struct SphereDef {
-    center: DVec3,
-    radius: f64,
-    material: MaterialDef,
+struct SceneFragment {
+    // Library entries taken from includes, as "materials.gold" and so on,
+    // which others may only define again alike.
+    #[serde(skip)]
+    included: HashSet<String>,
+    #[serde(default)]
+    objects: Vec<SceneObjectDef>,
+    #[serde(default)]
+    include: Vec<IncludeDef>,
+    #[serde(default)]
+    paths: HashMap<String, PathDef>,
+    #[serde(default)]
+    meshes: HashMap<String, MeshDef>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDef>,
+    #[serde(default)]
+    textures: HashMap<String, TextureDef>,
 }
 
-#[derive(Deserialize)]
-struct MeshDef {
-    path: String,
-    material: MaterialDef,
+impl SceneConfig {
+    // Merges in the objects of every include, recursively, leaving the
+    // list empty. `file` is the scene's own file, which its include paths
+    // are relative to; without one they are relative to the working
+    // directory.
+    fn resolve_includes(&mut self, file: Option<&Path>) -> Result<(), Box<dyn Error>> {
+        if self.include.is_empty() {
+            return Ok(());
+        }
+        let mut stack = Vec::new();
+        if let Some(file) = file {
+            stack.push(std::fs::canonicalize(file)?);
+        }
+        let dir = file.and_then(Path::parent).unwrap_or(Path::new(""));
+        let mut fragment = SceneFragment {
+            included: HashSet::new(),
+            objects: std::mem::take(&mut self.objects),
+            include: std::mem::take(&mut self.include),
+            paths: std::mem::take(&mut self.paths),
+            meshes: std::mem::take(&mut self.meshes),
+            materials: std::mem::take(&mut self.materials),
+            textures: std::mem::take(&mut self.textures),
+        };
+        fragment.resolve_includes(dir, &mut stack, &mut HashSet::new())?;
+        self.objects = fragment.objects;
+        self.paths = fragment.paths;
+        self.meshes = fragment.meshes;
+        self.materials = fragment.materials;
+        self.textures = fragment.textures;
+        Ok(())
+    }
 }
 
-#[derive(Deserialize)]
+impl SceneFragment {
+    // Follows the includes of the file in `dir`. `stack` holds the files
+    // being read, to catch files that include themselves, and `added` the
+    // files already added untransformed where these objects will end up.
+    fn resolve_includes(
+        &mut self,
+        dir: &Path,
+        stack: &mut Vec<std::path::PathBuf>,
+        added: &mut HashSet<std::path::PathBuf>,
+    ) -> Result<(), Box<dyn Error>> {
+        for include in std::mem::take(&mut self.include) {
+            let in_include =
+                |e: &dyn std::fmt::Display| format!("include '{}': {}", include.path, e);
+            let path = dir.join(&include.path);
+            let source = std::fs::read_to_string(&path).map_err(|e| in_include(&e))?;
+            let canonical = std::fs::canonicalize(&path)?;
+            if stack.contains(&canonical) {
+                return Err(in_include(&"the file includes itself").into());
+            }
+            if include.transform.is_none() && !added.insert(canonical.clone()) {
+                continue;
+            }
+            let mut fragment: SceneFragment = SceneFormat::from_path(&include.path)
+                .parse_as(&source)
+                .map_err(|e| in_include(&e))?;
+            // A transformed include is a copy placed elsewhere, which
+            // repeats whatever it includes.
+            let mut placed = HashSet::new();
+            let added = match include.transform {
+                None => &mut *added,
+                Some(_) => &mut placed,
+            };
+            let parent = path.parent().unwrap_or(Path::new(""));
+            stack.push(canonical);
+            fragment.resolve_includes(parent, stack, added)?;
+            stack.pop();
+            self.merge(fragment, include.transform)
+                .map_err(|e| in_include(&e))?;
+        }
+        Ok(())
+    }
+
+    fn merge(
+        &mut self,
+        fragment: SceneFragment,
+        transform: Option<TransformDef>,
+    ) -> Result<(), Box<dyn Error>> {
+        let included = &mut self.included;
+        merge_entries("paths", &mut self.paths, fragment.paths, included)?;
+        merge_entries("meshes", &mut self.meshes, fragment.meshes, included)?;
+        merge_entries(
+            "materials",
+            &mut self.materials,
+            fragment.materials,
+            included,
+        )?;
+        merge_entries("textures", &mut self.textures, fragment.textures, included)?;
+        match transform {
+            None => self.objects.extend(fragment.objects),
+            Some(transform) => self.objects.push(SceneObjectDef {
+                name: None,
+                visible_to_camera: true,
+                casts_shadows: true,
+                visible_in_reflections: true,
+                object: ObjectDef::Group(GroupDef {
+                    objects: fragment.objects.into_iter().map(|o| o.object).collect(),
+                    transform: Some(transform),
+                }),
+            }),
+        }
+        Ok(())
+    }
+}
+
+// Adds the library entries of an included file to those of the file that
+// includes it, whose own entries win. `included` names the entries taken
+// from includes, which two files can only share if they define them alike.
+fn merge_entries<T: Serialize>(
+    kind: &str,
+    entries: &mut HashMap<String, T>,
+    from: HashMap<String, T>,
+    included: &mut HashSet<String>,
+) -> Result<(), Box<dyn Error>> {
+    for (name, def) in from {
+        let key = format!("{kind}.{name}");
+        match entries.get(&name) {
+            None => {
+                entries.insert(name, def);
+                included.insert(key);
+            }
+            Some(existing) if included.contains(&key) => {
+                if serde_json::to_value(existing)? != serde_json::to_value(&def)? {
+                    return Err(format!("{key} is defined differently by another include").into());
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+// A colour written as linear RGB, as a CSS hex string or name such as
+// "#ffcc00" or "tomato" (sRGB encoded, like a colour picker's values), or
+// as `{ color_temperature: 3200 }` for a black body at that many kelvin.
+#[derive(Serialize, Clone)]
+#[serde(untagged)]
+pub enum ColorDef {
+    Rgb(DVec3),
+    Css(String),
+    Temperature(TemperatureDef),
+}
+
+// Scaled by `intensity`, since the black-body colour's largest channel is
+// 1.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct TemperatureDef {
+    pub color_temperature: f64,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+}
+
+impl ColorDef {
+    pub fn rgb(&self) -> DVec3 {
+        match self {
+            ColorDef::Rgb(rgb) => *rgb,
+            // Names are checked when the scene is read.
+            ColorDef::Css(text) => parse_css(text).unwrap_or_default(),
+            ColorDef::Temperature(t) => t.intensity * blackbody(t.color_temperature),
+        }
+    }
+}
+
+// Arrays are RGB, strings CSS colours and maps temperatures; written by
+// hand so an unknown colour name is reported as such.
+impl<'de> Deserialize<'de> for ColorDef {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ColorVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ColorVisitor {
+            type Value = ColorDef;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an RGB triple, a CSS colour or a color_temperature")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<Self::Value, E> {
+                match parse_css(text) {
+                    Some(_) => Ok(ColorDef::Css(text.to_string())),
+                    None => Err(E::custom(format!("unknown colour '{text}'"))),
+                }
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let deserializer = serde::de::value::SeqAccessDeserializer::new(seq);
+                DVec3::deserialize(deserializer).map(ColorDef::Rgb)
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let deserializer = serde::de::value::MapAccessDeserializer::new(map);
+                TemperatureDef::deserialize(deserializer).map(ColorDef::Temperature)
+            }
+        }
+
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+// Angles and fractions may be written with a unit: "40deg", "40°" or
+// "0.7rad" for angles, "25%" for fractions. Bare numbers keep their
+// meaning (degrees and plain fractions), and numbers in strings may use
+// scientific notation like any other. A unit of the wrong kind is an
+// error rather than being ignored.
+#[derive(Clone, Copy)]
+enum Quantity {
+    Angle,
+    Fraction,
+}
+
+impl Quantity {
+    // The value of `text` in degrees or as a fraction.
+    fn parse(self, text: &str) -> Result<f64, String> {
+        let text = text.trim();
+        let units: &[(&str, f64)] = match self {
+            Quantity::Angle => &[
+                ("deg", 1.0),
+                ("°", 1.0),
+                ("rad", 180.0 / std::f64::consts::PI),
+            ],
+            Quantity::Fraction => &[("%", 0.01)],
+        };
+        let (number, scale) = units
+            .iter()
+            .find_map(|&(unit, scale)| Some((text.strip_suffix(unit)?, scale)))
+            .unwrap_or((text, 1.0));
+        match number.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value * scale),
+            _ => Err(format!("expected {}, found '{text}'", self.expecting())),
+        }
+    }
+
+    fn expecting(self) -> &'static str {
+        match self {
+            Quantity::Angle => "an angle such as 40, '40deg' or '0.7rad'",
+            Quantity::Fraction => "a fraction such as 0.25 or '25%'",
+        }
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for Quantity {
+    type Value = f64;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(Quantity::expecting(*self))
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<f64, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<f64, E> {
+        Ok(value as f64)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<f64, E> {
+        Ok(value as f64)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<f64, E> {
+        self.parse(text).map_err(E::custom)
+    }
+}
+
+fn degrees<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    deserializer.deserialize_any(Quantity::Angle)
+}
+
+fn fraction<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    deserializer.deserialize_any(Quantity::Fraction)
+}
+
+// An angle in a list of them.
+struct Degrees(f64);
+
+impl<'de> Deserialize<'de> for Degrees {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        degrees(deserializer).map(Degrees)
+    }
+}
+
+// Two angles, such as a lens tilt about the camera's two axes.
+fn degrees2<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<DVec2, D::Error> {
+    let [x, y] = <[Degrees; 2]>::deserialize(deserializer)?;
+    Ok(DVec2::new(x.0, y.0))
+}
+
+// Three angles, such as XYZ Euler rotations.
+fn degrees3<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<DVec3, D::Error> {
+    let [x, y, z] = <[Degrees; 3]>::deserialize(deserializer)?;
+    Ok(DVec3::new(x.0, y.0, z.0))
+}
+
+// Analytic lights; angles are in degrees. Point and spot lights take an
+// optional `radius` for soft shadows and an `ies` photometric file for the
+// shape of their light; see `PointLight`.
+#[derive(Deserialize, Serialize)]
 #[serde(tag = "type")]
-enum MaterialDef {
-    #[serde(rename = "lambertian")]
-    Lambertian { texture: TextureDef },
-    #[serde(rename = "metal")]
-    Metal {
-        texture: TextureDef,
-        fuzz: f64,
+pub enum LightDef {
+    #[serde(rename = "point")]
+    Point {
+        position: DVec3,
+        intensity: ColorDef,
+        #[serde(default)]
+        radius: f64,
+        ies: Option<String>,
+    },
+    #[serde(rename = "spot")]
+    Spot {
+        position: DVec3,
+        target: DVec3,
+        intensity: ColorDef,
+        #[serde(deserialize_with = "degrees")]
+        cone_angle: f64,
+        #[serde(default = "default_cone_delta", deserialize_with = "degrees")]
+        cone_delta: f64,
+        #[serde(default)]
+        radius: f64,
+        ies: Option<String>,
+    },
+    #[serde(rename = "directional")]
+    Directional {
+        direction: DVec3,
+        irradiance: ColorDef,
     },
-    #[serde(rename = "dielectric")]
-    Dielectric { index_of_refraction: f64 },
 }
 
-#[derive(Deserialize)]
+// The parallelogram with a corner at `corner` and sides `edge1` and
+// `edge2`, with `edge1 × edge2` pointing out of the scene.
+#[derive(Deserialize, Serialize)]
+pub struct PortalDef {
+    pub corner: DVec3,
+    pub edge1: DVec3,
+    pub edge2: DVec3,
+}
+
+fn default_cone_delta() -> f64 {
+    5.0
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolutionPreset {
+    #[serde(rename = "720p")]
+    Hd720,
+    #[serde(rename = "1080p")]
+    Hd1080,
+    #[serde(rename = "1440p")]
+    Qhd,
+    #[serde(rename = "4k")]
+    Uhd4k,
+    // 1080 pixels square, for social media and contact sheets.
+    #[serde(rename = "square")]
+    Square,
+}
+
+impl ResolutionPreset {
+    pub fn dimensions(self) -> (u32, u32) {
+        match self {
+            ResolutionPreset::Hd720 => (1280, 720),
+            ResolutionPreset::Hd1080 => (1920, 1080),
+            ResolutionPreset::Qhd => (2560, 1440),
+            ResolutionPreset::Uhd4k => (3840, 2160),
+            ResolutionPreset::Square => (1080, 1080),
+        }
+    }
+}
+
+// Index over the top-level objects; see `Accelerator`.
+#[derive(Deserialize, Serialize, Clone, Copy)]
 #[serde(tag = "type")]
-enum TextureDef {
-    #[serde(rename = "solid_color")]
-    SolidColor { color: DVec3 },
-    #[serde(rename = "checker")]
-    Checker {
-        scale: f64,
-        even: Box<TextureDef>,
-        odd: Box<TextureDef>,
+pub enum AcceleratorDef {
+    #[serde(rename = "bvh")]
+    Bvh {
+        #[serde(default)]
+        bounds: BoundsDef,
     },
-    #[serde(rename = "image")]
-    Image { path: String },
+    #[serde(rename = "qbvh")]
+    Qbvh {
+        #[serde(default)]
+        strategy: BvhStrategyDef,
+        #[serde(default)]
+        bounds: BoundsDef,
+    },
+    #[serde(rename = "grid")]
+    Grid {
+        #[serde(default)]
+        bounds: BoundsDef,
+    },
+    // Every object is tested against every ray.
+    #[serde(rename = "list")]
+    List,
+    // Intel Embree's hierarchy over the objects' boxes, with the `embree`
+    // feature; see `EmbreeScene`.
+    #[serde(rename = "embree")]
+    Embree,
 }
 
-// --- Scene Construction Logic ---
+impl Default for AcceleratorDef {
+    fn default() -> Self {
+        AcceleratorDef::Bvh {
+            bounds: BoundsDef::default(),
+        }
+    }
+}
 
-pub struct Scene;
+impl AcceleratorDef {
+    fn bounds(&self) -> BoundsDef {
+        match self {
+            AcceleratorDef::Bvh { bounds }
+            | AcceleratorDef::Qbvh { bounds, .. }
+            | AcceleratorDef::Grid { bounds } => *bounds,
+            AcceleratorDef::List | AcceleratorDef::Embree => BoundsDef::Aabb,
+        }
+    }
 
-impl Scene {
-    pub fn from_file(
-        path: &str,
-    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>), Box<dyn Error>> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let scene_def: SceneConfig = serde_json::from_reader(reader)?;
+    // The four-wide BVH traverses in f32, so it is built around the camera
+    // at `origin` to keep boxes tight near it in scenes far from the world
+    // origin.
+    fn build(
+        &self,
+        objects: HittableList,
+        origin: DVec3,
+    ) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+        let objects = self.bounds().apply(objects);
+        Ok(match *self {
+            AcceleratorDef::Bvh { .. } => Arc::new(BvhNode::build(objects)),
+            AcceleratorDef::Qbvh { strategy, .. } => {
+                Arc::new(Qbvh::with_origin(objects, strategy.into(), origin))
+            }
+            AcceleratorDef::Grid { .. } => Arc::new(UniformGrid::build(objects)),
+            AcceleratorDef::List => Arc::new(HittableList::build(objects)),
+            #[cfg(feature = "embree")]
+            AcceleratorDef::Embree => Arc::new(crate::embree::EmbreeScene::new(objects)?),
+            #[cfg(not(feature = "embree"))]
+            AcceleratorDef::Embree => {
+                return Err("the \"embree\" accelerator requires the `embree` feature".into())
+            }
+        })
+    }
+}
 
-        let aspect_ratio = scene_def.aspect_ratio.unwrap_or(16.0 / 9.0);
+// Bounding volumes of the primitives at the leaves of the scene's
+// hierarchies: the top level, rope strands, instances and meshes loaded
+// triangle by triangle. 14-DOPs cut the corners that boxes leave around
+// long diagonal triangles, strands and rotated objects, so fewer rays get
+// as far as testing them, for a few more slab tests per primitive.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+pub enum BoundsDef {
+    #[default]
+    #[serde(rename = "box")]
+    Aabb,
+    #[serde(rename = "dop14")]
+    Dop14,
+}
 
-        let camera = Camera::new(
-            scene_def.camera.lookfrom,
-            scene_def.camera.lookat,
-            scene_def.camera.vup,
-            scene_def.camera.vfov,
-            aspect_ratio,
-            scene_def.camera.aperture,
-            scene_def.camera.focus_dist,
-        );
+impl BoundsDef {
+    fn apply(self, primitives: HittableList) -> HittableList {
+        match self {
+            BoundsDef::Aabb => primitives,
+            BoundsDef::Dop14 => dop::tighten(primitives),
+        }
+    }
+}
 
-        let mut objects = HittableList::new();
-        for obj_def in &scene_def.objects {
-            objects.push(parse_object(obj_def));
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(tag = "type")]
+pub enum BvhStrategyDef {
+    #[serde(rename = "midpoint")]
+    Midpoint,
+    #[serde(rename = "sah")]
+    Sah {
+        #[serde(default = "default_sah_bins")]
+        bins: usize,
+    },
+}
+
+impl Default for BvhStrategyDef {
+    fn default() -> Self {
+        BvhStrategyDef::Sah {
+            bins: default_sah_bins(),
         }
+    }
+}
 
-        let world = Arc::new(BvhNode::new(objects));
+fn default_sah_bins() -> usize {
+    12
+}
 
-        Ok((scene_def, camera, world))
+impl From<BvhStrategyDef> for BvhBuildStrategy {
+    fn from(def: BvhStrategyDef) -> Self {
+        match def {
+            BvhStrategyDef::Midpoint => BvhBuildStrategy::Midpoint,
+            BvhStrategyDef::Sah { bins } => BvhBuildStrategy::Sah { bins },
+        }
     }
 }
 
-fn parse_object(obj_def: &ObjectDef) -> Arc<dyn Hittable> {
-    match obj_def {
-        ObjectDef::Sphere(s) => Arc::new(Sphere::new(
-            s.center,
-            s.radius,
-            parse_material(&s.material),
-        )),
-        ObjectDef::Mesh(m) => Arc::new(Mesh::new(&m.path, parse_material(&m.material))),
+#[derive(Deserialize, Serialize)]
+pub struct SettleDef {
+    #[serde(default)]
+    pub ground_height: f64,
+    #[serde(default = "default_settle_steps")]
+    pub max_steps: usize,
+}
+
+impl Default for SettleDef {
+    fn default() -> Self {
+        Self {
+            ground_height: 0.0,
+            max_steps: default_settle_steps(),
+        }
+    }
+}
+
+fn default_settle_steps() -> usize {
+    2000
+}
+
+impl SceneConfig {
+    pub fn environment(&self) -> Result<Arc<dyn Environment>, Box<dyn Error>> {
+        let environment: Arc<dyn Environment> = match &self.background {
+            None => Arc::new(SkyGradient::default()),
+            Some(EnvironmentDef::Solid { color }) => Arc::new(SolidBackground::new(color.rgb())),
+            Some(EnvironmentDef::Gradient { horizon, zenith }) => {
+                Arc::new(SkyGradient::new(horizon.rgb(), zenith.rgb()))
+            }
+            Some(EnvironmentDef::Hdr { path, intensity }) => {
+                Arc::new(EnvironmentMap::new(path, *intensity)?)
+            }
+            Some(EnvironmentDef::Sky {
+                elevation,
+                azimuth,
+                turbidity,
+                intensity,
+            }) => Arc::new(SunSky::new(
+                elevation.to_radians(),
+                azimuth.to_radians(),
+                *turbidity,
+                *intensity,
+            )),
+        };
+        Ok(environment)
+    }
+
+    // How the scene's `mesh` objects read the file at `path`: with the
+    // options of the first that does, or otherwise just in the scene's
+    // units and axes.
+    pub fn mesh_options(&self, path: &str) -> Result<ObjOptions, Box<dyn Error>> {
+        fn find<'a>(objects: &'a [ObjectDef], path: &str) -> Option<&'a MeshDef> {
+            objects.iter().find_map(|object| match object {
+                ObjectDef::Mesh(m) if m.path == path => Some(m),
+                ObjectDef::Group(g) => find(&g.objects, path),
+                ObjectDef::Node(n) => find(&n.children, path),
+                _ => None,
+            })
+        }
+        let import = MeshImport::of(self);
+        let found = self
+            .objects
+            .iter()
+            .find_map(|o| find(std::slice::from_ref(&o.object), path))
+            .or_else(|| self.meshes.values().find(|m| m.path == path));
+        match found {
+            Some(def) => {
+                let library = MaterialLibrary::new(&self.materials, &self.textures);
+                def.obj_options(import, &library)
+            }
+            None => Ok(ObjOptions {
+                conversion: import.conversion(),
+                ..ObjOptions::default()
+            }),
+        }
+    }
+
+    pub fn sample_map(&self) -> Result<Option<SampleMap>, Box<dyn Error>> {
+        let Some(def) = &self.sample_map else {
+            return Ok(None);
+        };
+        let map = SampleMap::new(def.regions.clone());
+        Ok(Some(match &def.mask {
+            Some(path) => map.load_mask(path)?,
+            None => map,
+        }))
     }
 }
 
-fn parse_material(mat_def: &MaterialDef) -> Arc<dyn crate::material::Material> {
-    match mat_def {
-        MaterialDef::Lambertian { texture } => {
-            Arc::new(Lambertian::new(parse_texture(texture)))
+#[cfg(feature = "gpu")]
+impl SceneConfig {
+    pub fn to_gpu_scene(&self) -> Result<crate::gpu::GpuScene, Box<dyn Error>> {
+        // Geometry is uploaded in f32, relative to the camera.
+        let mut gpu_scene = crate::gpu::GpuScene::with_origin(self.camera.lookfrom);
+        gpu_scene.set_environment(&*self.environment()?);
+        let library = MaterialLibrary::new(&self.materials, &self.textures);
+        for obj_def in &self.objects {
+            match &obj_def.object {
+                ObjectDef::Sphere(s) => {
+                    let material = gpu_scene.add_material(gpu_material(&s.material, &library)?);
+                    gpu_scene.add_sphere(s.center, s.radius, material);
+                }
+                ObjectDef::Mesh(m) => {
+                    let material = gpu_scene.add_material(gpu_material(&m.material, &library)?);
+                    let fallback = library.material(&m.material)?;
+                    let import = MeshImport::of(self);
+                    for triangle in mesh_triangles(m, 0, fallback, import, &library)? {
+                        gpu_scene.add_triangle(triangle.vertices(), material);
+                    }
+                }
+                _ => return Err("the GPU backend only supports spheres and meshes".into()),
+            }
         }
-        MaterialDef::Metal { texture, fuzz } => {
-            Arc::new(Metal::new(parse_texture(texture), *fuzz))
+        Ok(gpu_scene)
+    }
+}
+
+// Textures are not available on the GPU, so each one is reduced to a single
+// representative color.
+#[cfg(feature = "gpu")]
+fn gpu_material(
+    mat_def: &MaterialRef,
+    library: &MaterialLibrary,
+) -> Result<crate::gpu::GpuMaterial, Box<dyn Error>> {
+    use crate::gpu::GpuMaterial;
+
+    fn flat_color(
+        tex_def: &TextureRef,
+        library: &MaterialLibrary,
+    ) -> Result<DVec3, Box<dyn Error>> {
+        Ok(match library.texture_def(tex_def)? {
+            TextureDef::SolidColor { color } => color.rgb(),
+            TextureDef::Image { .. } => DVec3::splat(0.5),
+            // The average of what the texture blends.
+            def => {
+                let inputs = def.inputs();
+                let mut sum = DVec3::ZERO;
+                for (_, input) in &inputs {
+                    sum += flat_color(input, library)?;
+                }
+                sum / inputs.len() as f64
+            }
+        })
+    }
+
+    let material = match library.material_def(mat_def)? {
+        // The GPU has no shadow catchers; they render as the ground they
+        // stand in for.
+        MaterialDef::Lambertian { texture } | MaterialDef::ShadowCatcher { texture } => {
+            GpuMaterial::Lambertian {
+                albedo: flat_color(texture, library)?,
+            }
         }
+        MaterialDef::Hair { color, .. } => GpuMaterial::Lambertian {
+            albedo: flat_color(color, library)?,
+        },
+        MaterialDef::Metal { texture, fuzz, .. } => GpuMaterial::Metal {
+            albedo: flat_color(texture, library)?,
+            fuzz: *fuzz,
+        },
         MaterialDef::Dielectric {
             index_of_refraction,
-        } => Arc::new(Dielectric::new(*index_of_refraction)),
+            abbe_number,
+            cauchy,
+            sellmeier,
+            ..
+        } => GpuMaterial::Dielectric {
+            index_of_refraction: dielectric(
+                *index_of_refraction,
+                *abbe_number,
+                *cauchy,
+                *sellmeier,
+            )
+            .index_at(LAMBDA_D),
+        },
+        // The GPU has no microfacet model; pick the closest basic material.
+        MaterialDef::Principled {
+            base_color,
+            metallic,
+            roughness,
+            ior,
+            transmission,
+            ..
+        } => {
+            if *transmission > 0.5 {
+                GpuMaterial::Dielectric {
+                    index_of_refraction: *ior,
+                }
+            } else if *metallic > 0.5 {
+                GpuMaterial::Metal {
+                    albedo: flat_color(base_color, library)?,
+                    fuzz: *roughness,
+                }
+            } else {
+                GpuMaterial::Lambertian {
+                    albedo: flat_color(base_color, library)?,
+                }
+            }
+        }
+        MaterialDef::NormalMapped { material, .. }
+        | MaterialDef::Cutout { material, .. }
+        | MaterialDef::Layered { base: material, .. } => gpu_material(material, library)?,
+        // Whichever half the blend mostly shows.
+        MaterialDef::Mix { a, b, factor } => {
+            let weight = match factor {
+                FactorDef::Constant(weight) => *weight,
+                FactorDef::Texture(texture) => luminance(flat_color(texture, library)?),
+            };
+            gpu_material(if weight < 0.5 { a } else { b }, library)?
+        }
+        MaterialDef::AnisotropicMetal {
+            texture,
+            roughness_u,
+            roughness_v,
+            ..
+        } => GpuMaterial::Metal {
+            albedo: flat_color(texture, library)?,
+            fuzz: 0.5 * (roughness_u + roughness_v),
+        },
+        MaterialDef::Conductor {
+            metal,
+            eta,
+            k,
+            roughness,
+        } => GpuMaterial::Metal {
+            albedo: conductor_ior(*metal, *eta, *k)?.reflectance(1.0, None),
+            fuzz: *roughness,
+        },
+        MaterialDef::Subsurface {
+            sigma_a, sigma_s, ..
+        }
+        | MaterialDef::Volume {
+            sigma_a, sigma_s, ..
+        } => GpuMaterial::Lambertian {
+            albedo: Medium::new(*sigma_a, *sigma_s).single_scattering_albedo(),
+        },
+        // The GPU path tracer has no emission; lights render black.
+        MaterialDef::DiffuseLight { .. } => GpuMaterial::Lambertian {
+            albedo: DVec3::ZERO,
+        },
+    };
+    Ok(material)
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum EnvironmentDef {
+    #[serde(rename = "solid")]
+    Solid { color: ColorDef },
+    #[serde(rename = "gradient")]
+    Gradient { horizon: ColorDef, zenith: ColorDef },
+    #[serde(rename = "hdr")]
+    Hdr {
+        path: String,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    // Daylight from the Preetham model; angles are in degrees, with the
+    // azimuth measured clockwise from -Z.
+    #[serde(rename = "sky")]
+    Sky {
+        #[serde(deserialize_with = "degrees")]
+        elevation: f64,
+        #[serde(default, deserialize_with = "degrees")]
+        azimuth: f64,
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PathDef {
+    pub points: Vec<DVec3>,
+    #[serde(default)]
+    pub closed: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CameraDef {
+    pub lookfrom: DVec3,
+    pub lookat: DVec3,
+    pub vup: DVec3,
+    #[serde(deserialize_with = "degrees")]
+    pub vfov: f64,
+    pub aperture: AnimatedValue,
+    pub focus_dist: AnimatedValue,
+    // Name of an object to keep in focus; overrides `focus_dist`.
+    pub focus_target: Option<String>,
+    pub path: Option<String>,
+    #[serde(default)]
+    pub look_along_path: bool,
+    pub aperture_blades: Option<u32>,
+    #[serde(default, deserialize_with = "degrees")]
+    pub aperture_rotation: f64,
+    #[serde(default = "default_scale")]
+    pub anamorphic_squeeze: f64,
+    #[serde(default)]
+    pub projection: ProjectionDef,
+    #[serde(default = "default_shutter")]
+    pub shutter: (f64, f64),
+    #[serde(default)]
+    pub shutter_curve: ShutterCurveDef,
+    // Lens shift in image widths and heights, and lens tilt in degrees;
+    // see `Camera::with_shift` and `Camera::with_tilt`.
+    #[serde(default)]
+    pub shift: DVec2,
+    #[serde(default, deserialize_with = "degrees2")]
+    pub tilt: DVec2,
+    // Name of an object to aim at; overrides `lookat` and `look_along_path`.
+    pub track_target: Option<String>,
+    // Keyframed transform of the camera, applied to `lookfrom`, `lookat`
+    // and `vup` after `path`.
+    pub animation: Option<AnimationDef>,
+    // Moves the camera along its line of sight until every object is in
+    // view, focused on the middle of them; see `Camera::frame_bounds`.
+    // Only the direction from `lookat` to `lookfrom` is kept.
+    #[serde(default)]
+    pub auto_frame: bool,
+    // Measures `focus_dist` when the scene loads; overrides it and
+    // `focus_target`.
+    pub focus: Option<FocusDef>,
+    // Exposes the film as a real camera would, for scenes lit in physical
+    // units; see `PhysicalExposure`.
+    pub exposure: Option<ExposureDef>,
+    #[serde(default)]
+    pub lens_effects: LensEffectsDef,
+}
+
+// See `LensEffects`.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct LensEffectsDef {
+    pub distortion: f64,
+    pub chromatic_aberration: f64,
+    pub vignetting: f64,
+}
+
+impl From<&LensEffectsDef> for LensEffects {
+    fn from(def: &LensEffectsDef) -> Self {
+        LensEffects {
+            distortion: def.distortion,
+            chromatic_aberration: def.chromatic_aberration,
+            vignetting: def.vignetting,
+        }
     }
 }
 
-fn parse_texture(tex_def: &TextureDef) -> Arc<dyn Texture> {
-    match tex_def {
-        TextureDef::SolidColor { color } => Arc::new(SolidColor::new(*color)),
-        TextureDef::Checker { scale, even, odd } => Arc::new(CheckerTexture::new(
-            *scale,
-            parse_texture(even),
-            parse_texture(odd),
+#[derive(Deserialize, Serialize)]
+pub struct ExposureDef {
+    #[serde(default = "default_iso")]
+    pub iso: f64,
+    pub f_stop: f64,
+    // Seconds the film is exposed for; by default the length of `shutter`.
+    pub shutter_speed: Option<f64>,
+    // Sizes the lens aperture from `f_stop`, so that stopping down both
+    // darkens the image and deepens the depth of field, as on a real lens;
+    // `aperture` is then ignored. Turned off, `aperture` sets the depth of
+    // field alone and `f_stop` only the brightness.
+    #[serde(default = "default_true")]
+    pub couple_aperture: bool,
+    // Height of the film in world units, which `vfov` turns into the focal
+    // length the f-number divides; 24 mm, a full-frame sensor, by default.
+    #[serde(default = "default_sensor_height")]
+    pub sensor_height: f64,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusDef {
+    // On the first surface in the middle of the image.
+    Auto,
+    // On the named object, where a ray at its centre meets it.
+    Object(String),
+}
+
+// Margin `auto_frame` leaves around the scene, as a fraction of its size.
+const AUTO_FRAME_PADDING: f64 = 0.05;
+
+// Either a plain number or keyframes in time order, linearly interpolated
+// and held constant before the first and after the last key.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum AnimatedValue {
+    Constant(f64),
+    Keyframes(Vec<KeyframeDef>),
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct KeyframeDef {
+    pub time: f64,
+    pub value: f64,
+}
+
+impl AnimatedValue {
+    fn at(&self, time: f64) -> f64 {
+        let keys = match self {
+            AnimatedValue::Constant(value) => return *value,
+            AnimatedValue::Keyframes(keys) => keys,
+        };
+        let next = keys.partition_point(|k| k.time <= time);
+        match (next.checked_sub(1).map(|i| &keys[i]), keys.get(next)) {
+            (Some(a), Some(b)) if b.time > a.time => {
+                let f = (time - a.time) / (b.time - a.time);
+                a.value + f * (b.value - a.value)
+            }
+            (Some(a), _) => a.value,
+            (None, Some(b)) => b.value,
+            (None, None) => 0.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(tag = "type")]
+pub enum ProjectionDef {
+    #[default]
+    #[serde(rename = "perspective")]
+    Perspective,
+    #[serde(rename = "orthographic")]
+    Orthographic { height: f64 },
+    #[serde(rename = "fisheye")]
+    Fisheye {
+        #[serde(deserialize_with = "degrees")]
+        fov: f64,
+    },
+    #[serde(rename = "equirectangular")]
+    Equirectangular,
+    #[serde(rename = "omni_stereo")]
+    OmniStereo {
+        #[serde(default = "default_ipd")]
+        ipd: f64,
+    },
+    #[serde(rename = "stereo")]
+    Stereo {
+        #[serde(default = "default_ipd")]
+        ipd: f64,
+        convergence: Option<f64>,
+        #[serde(default)]
+        layout: StereoLayoutDef,
+    },
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum StereoLayoutDef {
+    #[default]
+    SideBySide,
+    TopBottom,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(tag = "type")]
+pub enum ShutterCurveDef {
+    #[default]
+    #[serde(rename = "box")]
+    Box,
+    #[serde(rename = "triangle")]
+    Triangle,
+    #[serde(rename = "ramp")]
+    Ramp { open: f64, close: f64 },
+}
+
+impl From<&ShutterCurveDef> for ShutterCurve {
+    fn from(def: &ShutterCurveDef) -> Self {
+        match *def {
+            ShutterCurveDef::Box => ShutterCurve::Box,
+            ShutterCurveDef::Triangle => ShutterCurve::Triangle,
+            ShutterCurveDef::Ramp { open, close } => ShutterCurve::Ramp { open, close },
+        }
+    }
+}
+
+impl From<&ProjectionDef> for CameraProjection {
+    fn from(def: &ProjectionDef) -> Self {
+        match *def {
+            ProjectionDef::Perspective => CameraProjection::Perspective,
+            ProjectionDef::Orthographic { height } => CameraProjection::Orthographic { height },
+            ProjectionDef::Fisheye { fov } => CameraProjection::Fisheye { fov },
+            ProjectionDef::Equirectangular => CameraProjection::Equirectangular,
+            ProjectionDef::OmniStereo { ipd } => CameraProjection::OmniStereo { ipd },
+            ProjectionDef::Stereo {
+                ipd,
+                convergence,
+                layout,
+            } => CameraProjection::Stereo(Stereo {
+                ipd,
+                convergence,
+                layout: match layout {
+                    StereoLayoutDef::SideBySide => StereoLayout::SideBySide,
+                    StereoLayoutDef::TopBottom => StereoLayout::TopBottom,
+                },
+            }),
+        }
+    }
+}
+
+impl CameraDef {
+    // Diameter of the lens aperture at `time`: the focal length over the
+    // f-number when `exposure` couples them, `aperture` otherwise.
+    fn aperture_at(&self, time: f64) -> f64 {
+        match &self.exposure {
+            Some(exposure) if exposure.couple_aperture => {
+                let half_angle = 0.5 * self.vfov.to_radians();
+                let focal_length = 0.5 * exposure.sensor_height / half_angle.tan();
+                focal_length / exposure.f_stop
+            }
+            _ => self.aperture.at(time),
+        }
+    }
+
+    fn physical_exposure(&self) -> Option<PhysicalExposure> {
+        self.exposure.as_ref().map(|exposure| PhysicalExposure {
+            iso: exposure.iso,
+            f_stop: exposure.f_stop,
+            shutter_speed: exposure
+                .shutter_speed
+                .unwrap_or(self.shutter.1 - self.shutter.0),
+        })
+    }
+
+    fn aperture_shape(&self) -> ApertureShape {
+        match self.aperture_blades {
+            Some(blades) => ApertureShape::Polygon {
+                blades,
+                rotation: self.aperture_rotation,
+            },
+            None => ApertureShape::Circular,
+        }
+    }
+}
+
+// A top-level scene object, optionally named so the camera can refer to it.
+// Turning off `visible_to_camera` leaves an object that still blocks and
+// bounces light, such as a light blocker out of shot; turning off
+// `casts_shadows` lets light samples through it, and
+// `visible_in_reflections` hides it from bounced rays.
+#[derive(Deserialize, Serialize)]
+pub struct SceneObjectDef {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub object: ObjectDef,
+    #[serde(default = "default_true")]
+    pub visible_to_camera: bool,
+    #[serde(default = "default_true")]
+    pub casts_shadows: bool,
+    #[serde(default = "default_true")]
+    pub visible_in_reflections: bool,
+}
+
+impl SceneObjectDef {
+    // `object` behind a `Visibility` if any of the flags are off.
+    fn with_visibility(&self, object: Arc<dyn Hittable>) -> Arc<dyn Hittable> {
+        if self.visible_to_camera && self.casts_shadows && self.visible_in_reflections {
+            return object;
+        }
+        Arc::new(
+            Visibility::new(object)
+                .with_camera(self.visible_to_camera)
+                .with_shadows(self.casts_shadows)
+                .with_reflections(self.visible_in_reflections),
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ObjectDef {
+    #[serde(rename = "sphere")]
+    Sphere(SphereDef),
+    #[serde(rename = "mesh")]
+    Mesh(MeshDef),
+    #[serde(rename = "instance")]
+    Instance(InstanceDef),
+    #[serde(rename = "cylinder")]
+    Cylinder(CylinderDef),
+    #[serde(rename = "cone")]
+    Cone(ConeDef),
+    #[serde(rename = "disk")]
+    Disk(DiskDef),
+    #[serde(rename = "box")]
+    Cuboid(CuboidDef),
+    #[serde(rename = "torus")]
+    Torus(TorusDef),
+    #[serde(rename = "quadric")]
+    Quadric(QuadricDef),
+    #[serde(rename = "heightfield")]
+    Heightfield(HeightfieldDef),
+    #[serde(rename = "rope")]
+    Rope(RopeDef),
+    #[serde(rename = "curve")]
+    Curve(CurveDef),
+    #[serde(rename = "hair")]
+    Hair(HairDef),
+    #[serde(rename = "point_cloud")]
+    PointCloud(PointCloudDef),
+    #[serde(rename = "follow_path")]
+    FollowPath(FollowPathDef),
+    #[serde(rename = "drop")]
+    Drop(DropDef),
+    #[serde(rename = "scatter")]
+    Scatter(ScatterDef),
+    #[serde(rename = "fractal")]
+    Fractal(FractalDef),
+    #[serde(rename = "sdf")]
+    Sdf(SdfDef),
+    #[serde(rename = "union")]
+    Union(CsgDef),
+    #[serde(rename = "intersection")]
+    Intersection(CsgDef),
+    #[serde(rename = "difference")]
+    Difference(DifferenceDef),
+    #[serde(rename = "motion")]
+    Motion(MotionDef),
+    #[serde(rename = "group")]
+    Group(GroupDef),
+    #[serde(rename = "animated")]
+    Animated(AnimatedDef),
+    #[serde(rename = "node")]
+    Node(NodeDef),
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SphereDef {
+    pub center: DVec3,
+    pub radius: f64,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MeshDef {
+    // An OBJ, STL or PLY file.
+    pub path: String,
+    pub material: MaterialRef,
+    // Keeps the materials of an OBJ file's MTL library, failing to load
+    // when the library it names can't be read. A PLY file's vertex colours
+    // tint the material either way.
+    #[serde(default = "default_true")]
+    pub use_mtl: bool,
+    // Turns closed meshes whose faces all wind inwards, as some exporters
+    // write them, so glass refracts into them rather than out.
+    #[serde(default)]
+    pub orient_outward: bool,
+    // Keeps the mesh's BVH in a `.qbvh` file beside it, reused while the
+    // mesh is unchanged, so later renders of large meshes skip the build.
+    #[serde(default)]
+    pub cache_bvh: bool,
+    #[serde(default)]
+    pub shading: Shading,
+    // Averages vertex normals from the faces when the file has none.
+    #[serde(default)]
+    pub generate_normals: bool,
+    // Levels of Loop subdivision, each splitting every triangle in four and
+    // smoothing the surface, applied before any displacement.
+    #[serde(default)]
+    pub subdivision: u32,
+    pub displacement: Option<DisplacementDef>,
+    // Coarser versions of the mesh, read with the same options, for
+    // objects far enough from the camera that its detail is lost; by
+    // increasing distance.
+    #[serde(default)]
+    pub lods: Vec<LodDef>,
+    // Override the scene's `units` and `up_axis` for this file.
+    pub units: Option<Units>,
+    pub up_axis: Option<UpAxis>,
+}
+
+// Length unit of a mesh file.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Units {
+    #[default]
+    #[serde(rename = "m", alias = "metres", alias = "meters")]
+    Metres,
+    #[serde(rename = "cm", alias = "centimetres", alias = "centimeters")]
+    Centimetres,
+    #[serde(rename = "mm", alias = "millimetres", alias = "millimeters")]
+    Millimetres,
+    #[serde(rename = "in", alias = "inches")]
+    Inches,
+    #[serde(rename = "ft", alias = "feet")]
+    Feet,
+}
+
+impl Units {
+    pub fn metres(self) -> f64 {
+        match self {
+            Units::Metres => 1.0,
+            Units::Centimetres => 0.01,
+            Units::Millimetres => 0.001,
+            Units::Inches => 0.0254,
+            Units::Feet => 0.3048,
+        }
+    }
+}
+
+// Axis pointing up in a mesh file: Y in most modelling tools and game
+// engines and in OBJ files, Z in CAD, 3D printing and Blender itself.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpAxis {
+    #[default]
+    #[serde(rename = "y", alias = "Y")]
+    Y,
+    #[serde(rename = "z", alias = "Z")]
+    Z,
+}
+
+// The units and up axis mesh files are read in.
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct MeshImport {
+    pub units: Units,
+    pub up_axis: UpAxis,
+}
+
+impl MeshImport {
+    fn of(config: &SceneConfig) -> Self {
+        Self {
+            units: config.units,
+            up_axis: config.up_axis,
+        }
+    }
+
+    // With `units` and `up_axis` in place of the scene's where an object
+    // sets them.
+    fn overridden(self, units: Option<Units>, up_axis: Option<UpAxis>) -> Self {
+        Self {
+            units: units.unwrap_or(self.units),
+            up_axis: up_axis.unwrap_or(self.up_axis),
+        }
+    }
+
+    // The map from the file's coordinates to the scene's, if they differ.
+    // Z-up files are turned as Blender's exporters turn them: X stays, and
+    // Z becomes Y, and Y becomes -Z.
+    fn conversion(self) -> Option<DMat3> {
+        let axes = match self.up_axis {
+            UpAxis::Y => DMat3::IDENTITY,
+            UpAxis::Z => DMat3::from_cols(DVec3::X, DVec3::NEG_Z, DVec3::Y),
+        };
+        let conversion = self.units.metres() * axes;
+        (conversion != DMat3::IDENTITY).then_some(conversion)
+    }
+}
+
+// A level of detail of a mesh, drawn in place of the finer levels once
+// the object is at least `distance` from the camera.
+#[derive(Deserialize, Serialize)]
+pub struct LodDef {
+    pub path: String,
+    pub distance: f64,
+}
+
+// Moves a mesh's vertices along its normals by the grey levels of a
+// texture, for detail such as terrain or brickwork that the file itself
+// doesn't model. It needs vertices to move, so usually goes with
+// `subdivision`.
+#[derive(Deserialize, Serialize)]
+pub struct DisplacementDef {
+    // Read at the vertices' texture coordinates, or at their positions for
+    // meshes without any, as solid textures are.
+    pub texture: TextureRef,
+    // The distance white moves a vertex by.
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+impl MeshDef {
+    // The file of level of detail `level`, 0 being the mesh itself.
+    fn level_path(&self, level: usize) -> &str {
+        match level {
+            0 => &self.path,
+            _ => &self.lods[level - 1].path,
+        }
+    }
+
+    // The level of detail for an object `distance` from the camera.
+    fn level_at(&self, distance: f64) -> usize {
+        self.lods
+            .iter()
+            .take_while(|lod| lod.distance <= distance)
+            .count()
+    }
+
+    // The scene's `import`, as this mesh overrides it.
+    fn import(&self, import: MeshImport) -> MeshImport {
+        import.overridden(self.units, self.up_axis)
+    }
+
+    // Everything that decides the triangles of level `level` but their
+    // material: the file and how it is read.
+    fn geometry_key(&self, level: usize, import: MeshImport) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string(&(
+            self.level_path(level),
+            self.use_mtl,
+            self.orient_outward,
+            self.shading,
+            self.generate_normals,
+            self.subdivision,
+            &self.displacement,
+            self.import(import),
+        ))?)
+    }
+
+    fn obj_options(
+        &self,
+        import: MeshImport,
+        library: &MaterialLibrary,
+    ) -> Result<ObjOptions, Box<dyn Error>> {
+        let displacement = match &self.displacement {
+            Some(def) => Some(Displacement {
+                height: library.data_texture(&def.texture)?,
+                scale: def.scale,
+            }),
+            None => None,
+        };
+        Ok(ObjOptions {
+            orient_outward: self.orient_outward,
+            shading: self.shading,
+            generate_normals: self.generate_normals,
+            subdivision: self.subdivision,
+            displacement,
+            conversion: self.import(import).conversion(),
+            ignore_mtl: !self.use_mtl,
+        })
+    }
+}
+
+// Triangles of level of detail `level` of the mesh `def`, all with
+// `material` unless it uses the materials of its MTL file, in the scene's
+// units and axes by `import`.
+fn mesh_triangles(
+    def: &MeshDef,
+    level: usize,
+    material: Arc<dyn crate::material::Material>,
+    import: MeshImport,
+    library: &MaterialLibrary,
+) -> Result<Vec<Triangle>, Box<dyn Error>> {
+    let options = def.obj_options(import, library)?;
+    let path = def.level_path(level);
+    let (triangles, _) = obj::load_with(path, material, &options)?;
+    Ok(triangles)
+}
+
+// Copies of a mesh from `SceneConfig::meshes`, one per transform.
+#[derive(Deserialize, Serialize)]
+pub struct InstanceDef {
+    pub mesh: String,
+    pub transforms: Vec<TransformDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CylinderDef {
+    pub start: DVec3,
+    pub end: DVec3,
+    pub radius: f64,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ConeDef {
+    pub base: DVec3,
+    pub apex: DVec3,
+    pub radius: f64,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DiskDef {
+    pub center: DVec3,
+    pub normal: DVec3,
+    pub radius: f64,
+    pub material: MaterialRef,
+}
+
+// Axis-aligned; any two opposite corners.
+#[derive(Deserialize, Serialize)]
+pub struct CuboidDef {
+    pub min: DVec3,
+    pub max: DVec3,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TorusDef {
+    pub center: DVec3,
+    #[serde(default = "default_up")]
+    pub axis: DVec3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: MaterialRef,
+}
+
+fn default_up() -> DVec3 {
+    DVec3::Y
+}
+
+// `coefficients` are A to J of A x² + B y² + C z² + D xy + E xz + F yz +
+// G x + H y + I z + J = 0; the surface is clipped to the box `min`-`max`.
+#[derive(Deserialize, Serialize)]
+pub struct QuadricDef {
+    pub coefficients: [f64; 10],
+    pub min: DVec3,
+    pub max: DVec3,
+    pub material: MaterialRef,
+}
+
+// A grayscale image as terrain, `size` wide along X and Z and centred on
+// `center`; white pixels rise `height` above it.
+#[derive(Deserialize, Serialize)]
+pub struct HeightfieldDef {
+    pub path: String,
+    pub size: DVec2,
+    pub height: f64,
+    #[serde(default)]
+    pub center: DVec3,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RopeDef {
+    pub points: Vec<DVec3>,
+    pub radius: f64,
+    pub material: MaterialRef,
+}
+
+// A cubic Bezier strand; see `Curve`.
+#[derive(Deserialize, Serialize)]
+pub struct CurveDef {
+    pub control_points: [DVec3; 4],
+    pub widths: [f64; 2],
+    #[serde(default)]
+    pub shape: CurveShape,
+    pub material: MaterialRef,
+}
+
+// The strands of a .hair file, with the thicknesses it gives scaled by
+// `width_scale`.
+#[derive(Deserialize, Serialize)]
+pub struct HairDef {
+    pub path: String,
+    #[serde(default = "default_scale")]
+    pub width_scale: f64,
+    #[serde(default)]
+    pub shape: CurveShape,
+    pub material: MaterialRef,
+}
+
+// The points of a .ply or .xyz scan, each drawn as a splat of `radius`.
+// With `point_colors`, points that have a colour tint the base colour of
+// `material` with it, which should be white to show them as scanned.
+#[derive(Deserialize, Serialize)]
+pub struct PointCloudDef {
+    pub path: String,
+    pub radius: f64,
+    #[serde(default)]
+    pub splat: Splat,
+    #[serde(default)]
+    pub point_colors: bool,
+    pub material: MaterialRef,
+    // Override the scene's `units` and `up_axis` for this file; `radius`
+    // is in the scene's units.
+    pub units: Option<Units>,
+    pub up_axis: Option<UpAxis>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FollowPathDef {
+    pub path: String,
+    pub object: Box<ObjectDef>,
+    #[serde(default)]
+    pub orient: bool,
+}
+
+// `keys` are spread evenly over the shutter interval.
+#[derive(Deserialize, Serialize)]
+pub struct MotionDef {
+    pub object: Box<ObjectDef>,
+    pub keys: Vec<TransformDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TransformDef {
+    #[serde(default)]
+    pub translate: DVec3,
+    // XYZ Euler angles in degrees.
+    #[serde(default, deserialize_with = "degrees3")]
+    pub rotate: DVec3,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+impl From<&TransformDef> for DAffine3 {
+    fn from(def: &TransformDef) -> Self {
+        let r = def.rotate * (std::f64::consts::PI / 180.0);
+        DAffine3::from_scale_rotation_translation(
+            DVec3::splat(def.scale),
+            glam::DQuat::from_euler(glam::EulerRot::XYZ, r.x, r.y, r.z),
+            def.translate,
+        )
+    }
+}
+
+// An object moved by a keyframed transform over the animation.
+#[derive(Deserialize, Serialize)]
+pub struct AnimatedDef {
+    pub object: Box<ObjectDef>,
+    pub animation: AnimationDef,
+}
+
+// Transform keys over the [0, 1] animation time, in time order and held
+// before the first and after the last. Components are interpolated
+// separately, so rotations can span more than a turn between two keys.
+#[derive(Deserialize, Serialize)]
+pub struct AnimationDef {
+    pub keys: Vec<TransformKeyDef>,
+    #[serde(default)]
+    pub interpolation: InterpolationDef,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TransformKeyDef {
+    pub time: f64,
+    #[serde(default)]
+    pub translate: DVec3,
+    // XYZ Euler angles in degrees.
+    #[serde(default, deserialize_with = "degrees3")]
+    pub rotate: DVec3,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy)]
+pub enum InterpolationDef {
+    #[default]
+    #[serde(rename = "linear")]
+    Linear,
+    // Cubic Hermite curves with Catmull-Rom tangents: smooth through every
+    // key, easing in and out at the ends.
+    #[serde(rename = "cubic")]
+    Cubic,
+}
+
+// Motion keys per frame for keyframed objects.
+const ANIMATION_MOTION_STEPS: usize = 4;
+
+impl AnimationDef {
+    // The transform at `time`, or keys across the time to the next frame
+    // when `frame_span` is non-zero, which rays sample through their
+    // shutter time.
+    fn keys(&self, time: f64, frame_span: f64) -> Vec<DAffine3> {
+        if frame_span > 0.0 {
+            (0..=ANIMATION_MOTION_STEPS)
+                .map(|step| {
+                    let offset = step as f64 / ANIMATION_MOTION_STEPS as f64;
+                    self.transform_at(time + offset * frame_span)
+                })
+                .collect()
+        } else {
+            vec![self.transform_at(time)]
+        }
+    }
+
+    fn transform_at(&self, time: f64) -> DAffine3 {
+        let transform = TransformDef {
+            translate: self.interpolate(time, |k| k.translate),
+            rotate: self.interpolate(time, |k| k.rotate),
+            scale: self.interpolate(time, |k| DVec3::splat(k.scale)).x,
+        };
+        (&transform).into()
+    }
+
+    fn interpolate(&self, time: f64, value: impl Fn(&TransformKeyDef) -> DVec3) -> DVec3 {
+        let keys = &self.keys;
+        let next = keys.partition_point(|k| k.time <= time);
+        let (i, j) = match (next.checked_sub(1), keys.get(next)) {
+            (Some(i), Some(_)) if keys[next].time > keys[i].time => (i, next),
+            (Some(i), _) => return value(&keys[i]),
+            (None, Some(b)) => return value(b),
+            (None, None) => return DVec3::ZERO,
+        };
+        let (a, b) = (value(&keys[i]), value(&keys[j]));
+        let span = keys[j].time - keys[i].time;
+        let f = (time - keys[i].time) / span;
+        match self.interpolation {
+            InterpolationDef::Linear => a.lerp(b, f),
+            InterpolationDef::Cubic => {
+                // Slope at key `k` from its neighbours; one-sided at the
+                // ends, which ease in and out.
+                let slope = |k: usize| {
+                    let (before, after) = (k.saturating_sub(1), (k + 1).min(keys.len() - 1));
+                    let dt = keys[after].time - keys[before].time;
+                    if dt > 0.0 {
+                        (value(&keys[after]) - value(&keys[before])) / dt
+                    } else {
+                        DVec3::ZERO
+                    }
+                };
+                let (m0, m1) = (slope(i) * span, slope(j) * span);
+                let (f2, f3) = (f * f, f * f * f);
+                (2.0 * f3 - 3.0 * f2 + 1.0) * a
+                    + (f3 - 2.0 * f2 + f) * m0
+                    + (3.0 * f2 - 2.0 * f3) * b
+                    + (f3 - f2) * m1
+            }
+        }
+    }
+}
+
+// Objects placed together, with one transform for the whole group.
+#[derive(Deserialize, Serialize)]
+pub struct GroupDef {
+    pub objects: Vec<ObjectDef>,
+    pub transform: Option<TransformDef>,
+}
+
+// A node of a transform hierarchy, such as a car whose wheels and mirrors
+// move with its body. `children`, which may be nodes themselves, are placed
+// in the node's frame: `transform`, or `animation` if it is keyframed,
+// relative to its parent's. World transforms are composed when the scene
+// is built, so each object ends up with a single one.
+#[derive(Deserialize, Serialize)]
+pub struct NodeDef {
+    pub children: Vec<ObjectDef>,
+    pub transform: Option<TransformDef>,
+    pub animation: Option<AnimationDef>,
+}
+
+impl NodeDef {
+    fn local_keys(&self, ctx: &ParseContext) -> Vec<DAffine3> {
+        match (&self.animation, &self.transform) {
+            (Some(animation), _) => animation.keys(ctx.time, ctx.frame_span),
+            (None, Some(transform)) => vec![transform.into()],
+            (None, None) => vec![DAffine3::IDENTITY],
+        }
+    }
+}
+
+// Children of a union or intersection, combined left to right.
+#[derive(Deserialize, Serialize)]
+pub struct CsgDef {
+    pub objects: Vec<ObjectDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DifferenceDef {
+    pub object: Box<ObjectDef>,
+    pub subtract: Vec<ObjectDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DropDef {
+    pub object: Box<ObjectDef>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ScatterDef {
+    pub target: String,
+    pub prototype: Box<ObjectDef>,
+    pub count: usize,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default = "default_true")]
+    pub align_to_normal: bool,
+    #[serde(default = "default_true")]
+    pub random_rotation: bool,
+    #[serde(default = "default_scale_range")]
+    pub scale_range: (f64, f64),
+    pub density: Option<TextureRef>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FractalDef {
+    pub fractal: FractalShapeDef,
+    #[serde(default)]
+    pub center: DVec3,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u32,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum FractalShapeDef {
+    #[serde(rename = "mandelbulb")]
+    Mandelbulb {
+        #[serde(default = "default_mandelbulb_power")]
+        power: f64,
+        #[serde(default = "default_fractal_iterations")]
+        iterations: u32,
+    },
+    #[serde(rename = "menger")]
+    Menger {
+        #[serde(default = "default_menger_iterations")]
+        iterations: u32,
+    },
+    #[serde(rename = "julia")]
+    Julia {
+        c: [f64; 4],
+        #[serde(default = "default_fractal_iterations")]
+        iterations: u32,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SdfDef {
+    pub shape: SdfShapeDef,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u32,
+    pub material: MaterialRef,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SdfShapeDef {
+    #[serde(rename = "sphere")]
+    Sphere { center: DVec3, radius: f64 },
+    #[serde(rename = "box")]
+    Cuboid { min: DVec3, max: DVec3 },
+    #[serde(rename = "torus")]
+    Torus {
+        center: DVec3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    #[serde(rename = "capsule")]
+    Capsule {
+        start: DVec3,
+        end: DVec3,
+        radius: f64,
+    },
+    #[serde(rename = "union")]
+    Union { shapes: Vec<SdfShapeDef> },
+    #[serde(rename = "intersection")]
+    Intersection { shapes: Vec<SdfShapeDef> },
+    #[serde(rename = "difference")]
+    Difference {
+        shape: Box<SdfShapeDef>,
+        subtract: Vec<SdfShapeDef>,
+    },
+    #[serde(rename = "smooth_union")]
+    SmoothUnion {
+        shapes: Vec<SdfShapeDef>,
+        #[serde(default = "default_blend")]
+        k: f64,
+    },
+}
+
+fn default_blend() -> f64 {
+    0.25
+}
+
+fn default_shutter() -> (f64, f64) {
+    (0.0, 1.0)
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_iso() -> f64 {
+    100.0
+}
+
+// A full-frame sensor, in metres.
+fn default_sensor_height() -> f64 {
+    0.024
+}
+
+// Average adult interpupillary distance, for scenes in metres.
+fn default_ipd() -> f64 {
+    0.064
+}
+
+fn default_max_steps() -> u32 {
+    256
+}
+
+fn default_mandelbulb_power() -> f64 {
+    8.0
+}
+
+fn default_fractal_iterations() -> u32 {
+    12
+}
+
+fn default_menger_iterations() -> u32 {
+    4
+}
+
+fn default_scale_range() -> (f64, f64) {
+    (1.0, 1.0)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum MaterialDef {
+    #[serde(rename = "lambertian")]
+    Lambertian { texture: TextureRef },
+    #[serde(rename = "metal")]
+    Metal {
+        texture: TextureRef,
+        #[serde(deserialize_with = "fraction")]
+        fuzz: f64,
+        thin_film: Option<ThinFilmDef>,
+    },
+    // At most one of `abbe_number`, `cauchy` and `sellmeier` gives the
+    // dispersion; with `cauchy` or `sellmeier` the index of refraction only
+    // matters to the GPU backend. `roughness` frosts the glass and
+    // `absorption` tints it; the GPU backend renders it smooth and clear.
+    #[serde(rename = "dielectric")]
+    Dielectric {
+        #[serde(default = "default_ior")]
+        index_of_refraction: f64,
+        abbe_number: Option<f64>,
+        cauchy: Option<CauchyDef>,
+        sellmeier: Option<SellmeierDef>,
+        thin_film: Option<ThinFilmDef>,
+        #[serde(default, deserialize_with = "fraction")]
+        roughness: f64,
+        absorption: Option<AbsorptionDef>,
+    },
+    #[serde(rename = "principled")]
+    Principled {
+        base_color: TextureRef,
+        #[serde(default, deserialize_with = "fraction")]
+        metallic: f64,
+        #[serde(default = "default_roughness", deserialize_with = "fraction")]
+        roughness: f64,
+        #[serde(default = "default_ior")]
+        ior: f64,
+        #[serde(default = "default_specular", deserialize_with = "fraction")]
+        specular: f64,
+        #[serde(default, deserialize_with = "fraction")]
+        transmission: f64,
+    },
+    #[serde(rename = "normal_mapped")]
+    NormalMapped {
+        material: Box<MaterialRef>,
+        normal_map: TextureRef,
+        #[serde(default = "default_scale")]
+        strength: f64,
+    },
+    // Cuts `material` away wherever `alpha` is darker than `threshold`, so
+    // that rays and shadows pass through, as for leaves drawn on cards.
+    #[serde(rename = "cutout")]
+    Cutout {
+        material: Box<MaterialRef>,
+        alpha: TextureRef,
+        #[serde(default = "default_alpha_threshold", deserialize_with = "fraction")]
+        threshold: f64,
+    },
+    // Blends `a` into `b` by `factor`, a number or the grey level of a
+    // texture: 0 is all `a` and 1 all `b`.
+    #[serde(rename = "mix")]
+    Mix {
+        a: Box<MaterialRef>,
+        b: Box<MaterialRef>,
+        factor: FactorDef,
+    },
+    // A clear coat of index `coat_ior` over `base`.
+    #[serde(rename = "layered")]
+    Layered {
+        base: Box<MaterialRef>,
+        #[serde(default = "default_ior")]
+        coat_ior: f64,
+        #[serde(default = "default_coat_roughness", deserialize_with = "fraction")]
+        coat_roughness: f64,
+    },
+    #[serde(rename = "anisotropic_metal")]
+    AnisotropicMetal {
+        texture: TextureRef,
+        #[serde(deserialize_with = "fraction")]
+        roughness_u: f64,
+        #[serde(deserialize_with = "fraction")]
+        roughness_v: f64,
+        // Degrees around the normal.
+        #[serde(default, deserialize_with = "degrees")]
+        rotation: f64,
+    },
+    // A metal coloured by its complex index of refraction rather than a
+    // tint: one of the measured `metal`s, or `eta` and `k` of its own for
+    // the red, green and blue channels.
+    #[serde(rename = "conductor")]
+    Conductor {
+        metal: Option<MeasuredMetal>,
+        eta: Option<DVec3>,
+        k: Option<DVec3>,
+        #[serde(default, deserialize_with = "fraction")]
+        roughness: f64,
+    },
+    // Emits `color` times `texture` as radiance; either may be left out.
+    // With `power`, in watts, the radiance is scaled by the light's area and
+    // the average of its texture, so that a light gives out that much from
+    // each side, tinted by `color`, whatever its size and texture. Only
+    // spheres and meshes know their area for this.
+    #[serde(rename = "diffuse_light")]
+    DiffuseLight {
+        color: Option<ColorDef>,
+        texture: Option<TextureRef>,
+        power: Option<f64>,
+    },
+    // Fibres for `curve` and `hair` objects; see `Hair`. Roughnesses are
+    // in radians.
+    #[serde(rename = "hair")]
+    Hair {
+        color: TextureRef,
+        #[serde(default = "default_hair_ior")]
+        ior: f64,
+        #[serde(default = "default_hair_roughness")]
+        longitudinal_roughness: f64,
+        #[serde(default = "default_hair_roughness")]
+        azimuthal_roughness: f64,
+        #[serde(default = "default_cuticle_angle", deserialize_with = "degrees")]
+        cuticle_angle: f64,
+    },
+    // Ground for compositing onto a photograph, seen by the camera only
+    // through the shadows on it; `texture` is the albedo it bounces light
+    // onto objects with. See `ShadowCatcher`.
+    #[serde(rename = "shadow_catcher")]
+    ShadowCatcher { texture: TextureRef },
+    // Coefficients are per world unit.
+    #[serde(rename = "subsurface")]
+    Subsurface {
+        sigma_a: DVec3,
+        sigma_s: DVec3,
+        #[serde(default = "default_ior")]
+        ior: f64,
+    },
+    // Clouds, smoke and fog inside an invisible boundary. Coefficients are
+    // per world unit, scaled through space by `density` when it is given.
+    #[serde(rename = "volume")]
+    Volume {
+        sigma_a: DVec3,
+        sigma_s: DVec3,
+        density: Option<DensityGridDef>,
+    },
+}
+
+// See `DensityGrid::load`. `min` and `max` place the grid in the world,
+// overriding the bounds a .vol file records; raw grids otherwise fill the
+// unit cube.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DensityGridDef {
+    pub path: String,
+    pub resolution: Option<[usize; 3]>,
+    pub min: Option<DVec3>,
+    pub max: Option<DVec3>,
+}
+
+impl DensityGridDef {
+    fn load(&self) -> Result<DensityGrid, Box<dyn Error>> {
+        let mut grid = DensityGrid::load(&self.path, self.resolution)?;
+        if let Some(min) = self.min {
+            grid.bounds.min = min;
+        }
+        if let Some(max) = self.max {
+            grid.bounds.max = max;
+        }
+        Ok(grid)
+    }
+}
+
+// Coefficients for wavelengths in micrometres; see `Dispersion`.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct CauchyDef {
+    pub a: f64,
+    pub b: f64,
+    #[serde(default)]
+    pub c: f64,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct SellmeierDef {
+    pub b: [f64; 3],
+    pub c: [f64; 3],
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum MeasuredMetal {
+    #[serde(rename = "gold")]
+    Gold,
+    #[serde(rename = "silver")]
+    Silver,
+    #[serde(rename = "copper")]
+    Copper,
+    #[serde(rename = "aluminium", alias = "aluminum")]
+    Aluminium,
+}
+
+impl MeasuredMetal {
+    pub fn ior(self) -> ComplexIor {
+        match self {
+            MeasuredMetal::Gold => ComplexIor::GOLD,
+            MeasuredMetal::Silver => ComplexIor::SILVER,
+            MeasuredMetal::Copper => ComplexIor::COPPER,
+            MeasuredMetal::Aluminium => ComplexIor::ALUMINIUM,
+        }
+    }
+}
+
+// The index of a `conductor`: its own `eta` and `k` when it gives them,
+// otherwise that of its measured `metal`.
+fn conductor_ior(
+    metal: Option<MeasuredMetal>,
+    eta: Option<DVec3>,
+    k: Option<DVec3>,
+) -> Result<ComplexIor, Box<dyn Error>> {
+    match (metal, eta, k) {
+        (_, Some(eta), Some(k)) => Ok(ComplexIor { eta, k }),
+        (Some(metal), None, None) => Ok(metal.ior()),
+        _ => Err("a conductor needs a metal, or both eta and k".into()),
+    }
+}
+
+// Iridescent coating; the GPU backend ignores it.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct ThinFilmDef {
+    // In nanometres.
+    pub thickness: f64,
+    #[serde(default = "default_film_ior")]
+    pub index_of_refraction: f64,
+}
+
+impl ThinFilmDef {
+    fn film(self) -> ThinFilm {
+        ThinFilm {
+            thickness: self.thickness,
+            index_of_refraction: self.index_of_refraction,
+        }
+    }
+}
+
+// Soapy water.
+fn default_film_ior() -> f64 {
+    1.33
+}
+
+// Tint of light travelling through a dielectric: it keeps `color` of
+// itself for every `1 / density` world units it crosses, so a larger
+// density deepens the colour without changing its hue.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct AbsorptionDef {
+    pub color: DVec3,
+    #[serde(default = "default_density")]
+    pub density: f64,
+}
+
+impl AbsorptionDef {
+    // Absorption coefficients per world unit, by Beer-Lambert's law.
+    fn coefficients(self) -> DVec3 {
+        -self.density * DVec3::from_array(self.color.to_array().map(f64::ln))
+    }
+}
+
+fn default_density() -> f64 {
+    1.0
+}
+
+fn dielectric(
+    index_of_refraction: f64,
+    abbe_number: Option<f64>,
+    cauchy: Option<CauchyDef>,
+    sellmeier: Option<SellmeierDef>,
+) -> Dielectric {
+    let dispersion = match (abbe_number, cauchy, sellmeier) {
+        (_, _, Some(SellmeierDef { b, c })) => Some(Dispersion::Sellmeier { b, c }),
+        (_, Some(CauchyDef { a, b, c }), None) => Some(Dispersion::Cauchy { a, b, c }),
+        (abbe_number, None, None) => abbe_number.map(Dispersion::Abbe),
+    };
+    Dielectric {
+        dispersion,
+        ..Dielectric::new(index_of_refraction)
+    }
+}
+
+fn default_roughness() -> f64 {
+    0.5
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+fn default_specular() -> f64 {
+    0.5
+}
+
+fn default_alpha_threshold() -> f64 {
+    0.5
+}
+
+fn default_coat_roughness() -> f64 {
+    0.03
+}
+
+fn default_hair_ior() -> f64 {
+    1.55
+}
+
+fn default_hair_roughness() -> f64 {
+    0.3
+}
+
+fn default_cuticle_angle() -> f64 {
+    2.0
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FactorDef {
+    Constant(f64),
+    Texture(TextureRef),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum TextureDef {
+    #[serde(rename = "solid_color")]
+    SolidColor { color: ColorDef },
+    #[serde(rename = "checker")]
+    Checker {
+        scale: f64,
+        even: Box<TextureRef>,
+        odd: Box<TextureRef>,
+    },
+    #[serde(rename = "image")]
+    Image {
+        path: String,
+        #[serde(default)]
+        filter: TextureFilterDef,
+        // Reads the image's alpha channel, as grey, instead of its colour;
+        // always filtered bilinearly.
+        #[serde(default)]
+        alpha: bool,
+        // What the colours are decoded from. By default `srgb` where the
+        // texture gives a colour and `linear` where it gives data: normal
+        // maps, displacement, cut-out alpha, mix factors and scatter
+        // density. Alpha is always linear.
+        color_space: Option<ColorSpace>,
+    },
+    // Tiles, shifts and rotates the UVs of `texture`. Rotation is in
+    // degrees.
+    #[serde(rename = "uv_transform")]
+    UvTransform {
+        texture: Box<TextureRef>,
+        #[serde(default = "default_uv_scale")]
+        scale: DVec2,
+        #[serde(default)]
+        offset: DVec2,
+        #[serde(default, deserialize_with = "degrees")]
+        rotation: f64,
+    },
+    // Blends from `start` at `from` to `end` at `to`, by position.
+    #[serde(rename = "linear_gradient")]
+    LinearGradient {
+        start: Box<TextureRef>,
+        end: Box<TextureRef>,
+        from: DVec3,
+        to: DVec3,
+    },
+    // Blends from `start` at `center` to `end` at `radius` from it.
+    #[serde(rename = "radial_gradient")]
+    RadialGradient {
+        start: Box<TextureRef>,
+        end: Box<TextureRef>,
+        center: DVec3,
+        radius: f64,
+    },
+    // See `MarbleTexture`.
+    #[serde(rename = "marble")]
+    Marble {
+        base: Box<TextureRef>,
+        vein: Box<TextureRef>,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default = "default_marble_turbulence")]
+        turbulence: f64,
+    },
+    // See `WoodTexture`.
+    #[serde(rename = "wood")]
+    Wood {
+        light: Box<TextureRef>,
+        dark: Box<TextureRef>,
+        ring_spacing: f64,
+        #[serde(default)]
+        distortion: f64,
+    },
+    // See `BrickTexture`.
+    #[serde(rename = "bricks")]
+    Bricks {
+        brick: Box<TextureRef>,
+        mortar: Box<TextureRef>,
+        size: DVec2,
+        mortar_width: f64,
+    },
+}
+
+fn default_uv_scale() -> DVec2 {
+    DVec2::ONE
+}
+
+fn default_marble_turbulence() -> f64 {
+    5.0
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy)]
+pub enum TextureFilterDef {
+    #[default]
+    #[serde(rename = "nearest")]
+    Nearest,
+    // Bilinear filtering over a mip pyramid; smooths magnified textures.
+    #[serde(rename = "bilinear")]
+    Bilinear,
+}
+
+// A material or texture written out in place, or the name of an entry in
+// the scene's `materials` or `textures` library.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Reference<T> {
+    Named(String),
+    Inline(T),
+}
+
+pub type MaterialRef = Reference<MaterialDef>;
+pub type TextureRef = Reference<TextureDef>;
+
+// Encoded images, by the `path` of the `image` textures that read them, for
+// scenes loaded where there are no files; see `Scene::from_json_str`.
+pub type ImageFiles = HashMap<String, Vec<u8>>;
+
+// Strings are names and maps are definitions. Written by hand rather than
+// as an untagged enum so that a malformed definition still reports its own
+// error and path instead of "did not match any variant".
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Reference<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ReferenceVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for ReferenceVisitor<T> {
+            type Value = Reference<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a library name or an inline definition")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                Ok(Reference::Named(name.to_string()))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let deserializer = serde::de::value::MapAccessDeserializer::new(map);
+                T::deserialize(deserializer).map(Reference::Inline)
+            }
+        }
+
+        deserializer.deserialize_any(ReferenceVisitor(std::marker::PhantomData))
+    }
+}
+
+impl<T> Reference<T> {
+    // Library names used by this reference, directly or inside an inline
+    // definition whose own references `inline` lists.
+    fn names<'a>(&'a self, inline: fn(&T) -> Vec<&str>) -> Vec<&'a str> {
+        match self {
+            Reference::Named(name) => vec![name.as_str()],
+            Reference::Inline(def) => inline(def),
+        }
+    }
+}
+
+impl MaterialDef {
+    fn material_names(&self) -> Vec<&str> {
+        match self {
+            MaterialDef::NormalMapped { material, .. }
+            | MaterialDef::Cutout { material, .. }
+            | MaterialDef::Layered { base: material, .. } => {
+                material.names(MaterialDef::material_names)
+            }
+            MaterialDef::Mix { a, b, .. } => {
+                let mut names = a.names(MaterialDef::material_names);
+                names.extend(b.names(MaterialDef::material_names));
+                names
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl TextureDef {
+    // The textures this one is made from, by the fields that hold them.
+    fn inputs(&self) -> Vec<(&'static str, &TextureRef)> {
+        match self {
+            TextureDef::SolidColor { .. } | TextureDef::Image { .. } => Vec::new(),
+            TextureDef::Checker { even, odd, .. } => vec![("even", &**even), ("odd", &**odd)],
+            TextureDef::UvTransform { texture, .. } => vec![("texture", &**texture)],
+            TextureDef::LinearGradient { start, end, .. }
+            | TextureDef::RadialGradient { start, end, .. } => {
+                vec![("start", &**start), ("end", &**end)]
+            }
+            TextureDef::Marble { base, vein, .. } => vec![("base", &**base), ("vein", &**vein)],
+            TextureDef::Wood { light, dark, .. } => vec![("light", &**light), ("dark", &**dark)],
+            TextureDef::Bricks { brick, mortar, .. } => {
+                vec![("brick", &**brick), ("mortar", &**mortar)]
+            }
+        }
+    }
+
+    fn texture_names(&self) -> Vec<&str> {
+        self.inputs()
+            .into_iter()
+            .flat_map(|(_, input)| input.names(TextureDef::texture_names))
+            .collect()
+    }
+}
+
+// What a slot reads from its texture, which decides how image textures
+// that don't give a `color_space` are decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum TextureUsage {
+    Color,
+    Data,
+}
+
+impl TextureUsage {
+    fn color_space(self) -> ColorSpace {
+        match self {
+            TextureUsage::Color => ColorSpace::Srgb,
+            TextureUsage::Data => ColorSpace::Linear,
+        }
+    }
+}
+
+// Builds the materials and textures of a scene. Library entries are built
+// on first use and then shared, so every object naming "gold" holds the
+// same `Arc`. Image textures are shared the same way by their definition,
+// so a file read by many inline textures alike is decoded once.
+pub(crate) struct MaterialLibrary<'a> {
+    material_defs: &'a HashMap<String, MaterialDef>,
+    texture_defs: &'a HashMap<String, TextureDef>,
+    materials: RefCell<HashMap<String, Arc<dyn crate::material::Material>>>,
+    textures: RefCell<HashMap<(String, TextureUsage), Arc<dyn Texture>>>,
+    // By the JSON of their `TextureDef::Image`.
+    images: RefCell<HashMap<(String, TextureUsage), Arc<dyn Texture>>>,
+    energy_compensation: Option<Arc<AlbedoLut>>,
+    // Set once any material built is index-matched.
+    index_matched: Cell<bool>,
+    // Where bilinear image textures are added, reloading, when the scene
+    // is being watched.
+    texture_watch: Option<TextureWatch>,
+    // Encoded images read in place of the files at their paths.
+    in_memory: Option<&'a ImageFiles>,
+}
+
+impl<'a> MaterialLibrary<'a> {
+    pub(crate) fn new(
+        material_defs: &'a HashMap<String, MaterialDef>,
+        texture_defs: &'a HashMap<String, TextureDef>,
+    ) -> Self {
+        Self {
+            material_defs,
+            texture_defs,
+            materials: RefCell::new(HashMap::new()),
+            textures: RefCell::new(HashMap::new()),
+            images: RefCell::new(HashMap::new()),
+            energy_compensation: None,
+            index_matched: Cell::new(false),
+            texture_watch: None,
+            in_memory: None,
+        }
+    }
+
+    pub(crate) fn with_energy_compensation(mut self, enabled: bool) -> Self {
+        self.energy_compensation = enabled.then(AlbedoLut::shared);
+        self
+    }
+
+    pub(crate) fn with_texture_watch(mut self, watch: Option<&TextureWatch>) -> Self {
+        self.texture_watch = watch.cloned();
+        self
+    }
+
+    pub(crate) fn with_images(mut self, images: Option<&'a ImageFiles>) -> Self {
+        self.in_memory = images;
+        self
+    }
+
+    // The encoded image given in memory for `path`, if there is one.
+    fn in_memory(&self, path: &str) -> Option<&'a [u8]> {
+        self.in_memory?.get(path).map(Vec::as_slice)
+    }
+
+    // The image at `path` with bilinear filtering, as `read` makes it,
+    // added to the texture watch if there is one, or as `decode` makes it
+    // from the bytes given in memory for `path`.
+    fn image(
+        &self,
+        path: &str,
+        read: impl Fn(&str) -> Result<MipmappedTexture, RenderError> + Send + Sync + 'static,
+        decode: impl FnOnce(&[u8]) -> Result<MipmappedTexture, RenderError>,
+    ) -> Result<Arc<dyn Texture>, RenderError> {
+        if let Some(bytes) = self.in_memory(path) {
+            return Ok(Arc::new(decode(bytes)?));
+        }
+        match &self.texture_watch {
+            Some(watch) => {
+                let texture = Arc::new(ReloadingTexture::new(path, read)?);
+                watch.add(texture.clone());
+                Ok(texture)
+            }
+            None => Ok(Arc::new(read(path)?)),
+        }
+    }
+
+    fn material_def<'b>(&'b self, def: &'b MaterialRef) -> Result<&'b MaterialDef, Box<dyn Error>> {
+        match def {
+            Reference::Inline(def) => Ok(def),
+            Reference::Named(name) => self
+                .material_defs
+                .get(name)
+                .ok_or_else(|| format!("unknown material '{}'", name).into()),
+        }
+    }
+
+    fn texture_def<'b>(&'b self, def: &'b TextureRef) -> Result<&'b TextureDef, Box<dyn Error>> {
+        match def {
+            Reference::Inline(def) => Ok(def),
+            Reference::Named(name) => self
+                .texture_defs
+                .get(name)
+                .ok_or_else(|| format!("unknown texture '{}'", name).into()),
+        }
+    }
+
+    // Library references must not form cycles; `Validator` rejects them
+    // before anything is built.
+    pub(crate) fn material(
+        &self,
+        def: &MaterialRef,
+    ) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let Reference::Named(name) = def else {
+            return Ok(self.identified(def, parse_material(self.material_def(def)?, self)?));
+        };
+        if let Some(material) = self.materials.borrow().get(name) {
+            return Ok(material.clone());
+        }
+        let material = self.identified(def, parse_material(self.material_def(def)?, self)?);
+        self.materials
+            .borrow_mut()
+            .insert(name.clone(), material.clone());
+        Ok(material)
+    }
+
+    // `material` with the stable ID of `def` for the material ID pass and
+    // matte: from the name of a library material, or from the definition
+    // itself of an inline one, so that identical inline materials share
+    // their ID.
+    fn identified(
+        &self,
+        def: &MaterialRef,
+        material: Arc<dyn crate::material::Material>,
+    ) -> Arc<dyn crate::material::Material> {
+        let id = match def {
+            Reference::Named(name) => name_id(name),
+            Reference::Inline(def) => name_id(&serde_json::to_string(def).unwrap_or_default()),
+        };
+        if material.is_index_matched() {
+            self.index_matched.set(true);
+        }
+        Arc::new(Identified::new(material, id))
+    }
+
+    // `def` for a slot that reads a colour from it.
+    pub(crate) fn texture(&self, def: &TextureRef) -> Result<Arc<dyn Texture>, Box<dyn Error>> {
+        self.texture_as(def, TextureUsage::Color)
+    }
+
+    // `def` for a slot that reads data from it, such as a normal map.
+    pub(crate) fn data_texture(
+        &self,
+        def: &TextureRef,
+    ) -> Result<Arc<dyn Texture>, Box<dyn Error>> {
+        self.texture_as(def, TextureUsage::Data)
+    }
+
+    fn texture_as(
+        &self,
+        def: &TextureRef,
+        usage: TextureUsage,
+    ) -> Result<Arc<dyn Texture>, Box<dyn Error>> {
+        let Reference::Named(name) = def else {
+            return parse_texture(self.texture_def(def)?, self, usage);
+        };
+        let key = (name.clone(), usage);
+        if let Some(texture) = self.textures.borrow().get(&key) {
+            return Ok(texture.clone());
+        }
+        let texture = parse_texture(self.texture_def(def)?, self, usage)?;
+        self.textures.borrow_mut().insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    // The material `def` for an emitter with `area` of surface. Lights
+    // given in watts get their own material, with radiance for that area.
+    // The material `def` for an emitter, with any power shared out over
+    // it. `area` gives the emitter's area weighted by the luminance of a
+    // light on it, so a texture's average rather than its value at any one
+    // point decides how bright it is.
+    fn emitter_material(
+        &self,
+        def: &MaterialRef,
+        area: impl FnOnce(&dyn crate::material::Material) -> f64,
+    ) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        match self.material_def(def)? {
+            MaterialDef::DiffuseLight {
+                color,
+                texture,
+                power: Some(power),
+            } => {
+                let white = diffuse_light(&None, texture, 1.0, self)?;
+                let area = area(&white);
+                let scale = if area > 0.0 {
+                    power / (std::f64::consts::PI * area)
+                } else {
+                    0.0
+                };
+                let light = Arc::new(diffuse_light(color, texture, scale, self)?);
+                Ok(self.identified(def, light))
+            }
+            _ => self.material(def),
+        }
+    }
+
+    fn is_emissive(&self, def: &MaterialRef) -> bool {
+        match self.material_def(def) {
+            Ok(MaterialDef::DiffuseLight { .. }) => true,
+            Ok(MaterialDef::NormalMapped { material, .. }) => self.is_emissive(material),
+            Ok(MaterialDef::Cutout { material, .. }) => self.is_emissive(material),
+            Ok(MaterialDef::Layered { base, .. }) => self.is_emissive(base),
+            Ok(MaterialDef::Mix { a, b, .. }) => self.is_emissive(a) || self.is_emissive(b),
+            _ => false,
+        }
+    }
+}
+
+// --- Scene Formats and Validation ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    Json,
+    Yaml,
+    Toml,
+    Ron,
+}
+
+impl SceneFormat {
+    // Picks the format from the file extension; anything unrecognised is
+    // read as JSON.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("yaml" | "yml") => SceneFormat::Yaml,
+            Some("toml") => SceneFormat::Toml,
+            Some("ron") => SceneFormat::Ron,
+            _ => SceneFormat::Json,
+        }
+    }
+
+    // Syntax and type errors name the field they occurred in, such as
+    // `objects[2].material.type: unknown variant ...`.
+    pub fn parse(self, source: &str) -> Result<SceneConfig, Box<dyn Error>> {
+        self.parse_as(source)
+    }
+
+    fn parse_as<T: serde::de::DeserializeOwned>(self, source: &str) -> Result<T, Box<dyn Error>> {
+        let config = match self {
+            SceneFormat::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(source);
+                let config = serde_path_to_error::deserialize(&mut deserializer)?;
+                deserializer.end()?;
+                config
+            }
+            SceneFormat::Yaml => {
+                serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(source))?
+            }
+            SceneFormat::Toml => serde_path_to_error::deserialize(toml::Deserializer::new(source))?,
+            SceneFormat::Ron => {
+                let mut deserializer = ron::Deserializer::from_str(source)?;
+                let config = serde_path_to_error::deserialize(&mut deserializer)?;
+                deserializer.end()?;
+                config
+            }
+        };
+        Ok(config)
+    }
+
+    pub fn write(self, config: &SceneConfig) -> Result<String, Box<dyn Error>> {
+        Ok(match self {
+            SceneFormat::Json => serde_json::to_string_pretty(config)?,
+            SceneFormat::Yaml => serde_yaml::to_string(config)?,
+            SceneFormat::Toml => toml::to_string_pretty(config)?,
+            SceneFormat::Ron => ron::ser::to_string_pretty(config, Default::default())?,
+        })
+    }
+}
+
+// Values that parse but can't be rendered, each with the path of the field
+// at fault.
+#[derive(Debug)]
+pub struct SceneValidationError {
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for SceneValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid scene:")?;
+        for problem in &self.problems {
+            write!(f, "\n  {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SceneValidationError {}
+
+#[derive(Default)]
+struct Validator {
+    problems: Vec<String>,
+    // Images given in memory, which need no file.
+    in_memory: HashSet<String>,
+}
+
+impl Validator {
+    fn finish(self) -> Result<(), SceneValidationError> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SceneValidationError {
+                problems: self.problems,
+            })
+        }
+    }
+
+    fn problem(&mut self, field: String, message: &str) {
+        self.problems.push(format!("{field}: {message}"));
+    }
+
+    fn positive(&mut self, value: f64, field: String) {
+        if !(value > 0.0) {
+            self.problem(field, "must be positive");
+        }
+    }
+
+    fn file(&mut self, path: &str, field: String) {
+        if !Path::new(path).exists() {
+            self.problem(field, &format!("file '{path}' not found"));
+        }
+    }
+
+    fn config(&mut self, config: &SceneConfig) {
+        self.positive(
+            config.render.pixel_aspect_ratio,
+            "render.pixel_aspect_ratio".into(),
+        );
+        if let Some(crop) = config.render.crop {
+            if crop.width == 0 || crop.height == 0 {
+                self.problem("render.crop".into(), "must not be empty");
+            }
+        }
+        let clamp = config.render.sample_clamp;
+        if let Some(limit) = clamp.direct {
+            self.positive(limit, "render.sample_clamp.direct".into());
+        }
+        if let Some(limit) = clamp.indirect {
+            self.positive(limit, "render.sample_clamp.indirect".into());
+        }
+        if !(config.camera.vfov > 0.0 && config.camera.vfov < 180.0) {
+            self.problem("camera.vfov".into(), "must be between 0 and 180 degrees");
+        }
+        if let Some(path) = &config.camera.path {
+            if !config.paths.contains_key(path) {
+                self.problem("camera.path".into(), &format!("unknown path '{path}'"));
+            }
+        }
+        match &config.background {
+            Some(EnvironmentDef::Hdr { path, .. }) => self.file(path, "background.path".into()),
+            Some(EnvironmentDef::Sky {
+                elevation,
+                turbidity,
+                ..
+            }) => {
+                if !(-90.0..=90.0).contains(elevation) {
+                    self.problem(
+                        "background.elevation".into(),
+                        "must be between -90 and 90 degrees",
+                    );
+                }
+                // The range the model was fitted over.
+                if !(2.0..=10.0).contains(turbidity) {
+                    self.problem("background.turbidity".into(), "must be between 2 and 10");
+                }
+            }
+            _ => {}
+        }
+        if let Some(sample_map) = &config.sample_map {
+            if let Some(path) = &sample_map.mask {
+                self.file(path, "sample_map.mask".into());
+            }
+            for (i, region) in sample_map.regions.iter().enumerate() {
+                let field = |name: &str| format!("sample_map.regions[{i}].{name}");
+                if !(region.samples >= 0.0) {
+                    self.problem(field("samples"), "must not be negative");
+                }
+                if !(region.resolution > 0.0 && region.resolution <= 1.0) {
+                    self.problem(field("resolution"), "must be in (0, 1]");
+                }
+            }
+        }
+        for (i, portal) in config.portals.iter().enumerate() {
+            if portal.edge1.cross(portal.edge2).length_squared() == 0.0 {
+                self.problem(format!("portals[{i}]"), "must have a non-zero area");
+            }
+        }
+        // The LUT is only read when the image is saved, so a malformed one
+        // is caught here rather than after the render.
+        if let Some(path) = &config.output.lut {
+            if Path::new(path).exists() {
+                if let Err(e) = Lut::load(path) {
+                    self.problem("output.lut".into(), &e.to_string());
+                }
+            } else {
+                self.file(path, "output.lut".into());
+            }
+        }
+        if config.camera.focus.is_some() && config.camera.focus_target.is_some() {
+            self.problem(
+                "camera.focus".into(),
+                "give only one of focus and focus_target",
+            );
+        }
+        if let Some(animation) = &config.camera.animation {
+            self.animation(animation, "camera.animation".into());
+        }
+        if let Some(exposure) = &config.camera.exposure {
+            self.positive(exposure.iso, "camera.exposure.iso".into());
+            self.positive(exposure.f_stop, "camera.exposure.f_stop".into());
+            let shutter = config.camera.shutter;
+            let speed = exposure.shutter_speed.unwrap_or(shutter.1 - shutter.0);
+            self.positive(speed, "camera.exposure.shutter_speed".into());
+            self.positive(
+                exposure.sensor_height,
+                "camera.exposure.sensor_height".into(),
+            );
+        }
+        for (i, light) in config.lights.iter().enumerate() {
+            let (LightDef::Point { radius, ies, .. } | LightDef::Spot { radius, ies, .. }) = light
+            else {
+                continue;
+            };
+            if !(*radius >= 0.0) {
+                self.problem(format!("lights[{i}].radius"), "must not be negative");
+            }
+            if let Some(path) = ies {
+                if Path::new(path).exists() {
+                    if let Err(e) = IesProfile::load(path) {
+                        self.problem(format!("lights[{i}].ies"), &e.to_string());
+                    }
+                } else {
+                    self.file(path, format!("lights[{i}].ies"));
+                }
+            }
+        }
+        let lens = &config.camera.lens_effects;
+        if !(lens.distortion > -1.0) {
+            self.problem(
+                "camera.lens_effects.distortion".into(),
+                "must be greater than -1",
+            );
+        }
+        if !(lens.chromatic_aberration.abs() < 1.0) {
+            self.problem(
+                "camera.lens_effects.chromatic_aberration".into(),
+                "must be between -1 and 1",
+            );
+        }
+        if !(0.0..=1.0).contains(&lens.vignetting) {
+            self.problem(
+                "camera.lens_effects.vignetting".into(),
+                "must be between 0 and 1",
+            );
+        }
+        let mut names: Vec<&String> = config.meshes.keys().collect();
+        names.sort();
+        for name in names {
+            let mesh = &config.meshes[name];
+            self.mesh(mesh, config, &format!("meshes.{name}"));
+            self.material(&mesh.material, config, format!("meshes.{name}.material"));
+        }
+        let mut names: Vec<&String> = config.materials.keys().collect();
+        names.sort();
+        for name in names {
+            let field = format!("materials.{name}");
+            if refers_to_itself(&config.materials, name, MaterialDef::material_names) {
+                self.problem(field.clone(), "refers to itself");
+            }
+            self.material_def(&config.materials[name], config, field);
+        }
+        let mut names: Vec<&String> = config.textures.keys().collect();
+        names.sort();
+        for name in names {
+            let field = format!("textures.{name}");
+            if refers_to_itself(&config.textures, name, TextureDef::texture_names) {
+                self.problem(field.clone(), "refers to itself");
+            }
+            self.texture_def(&config.textures[name], config, field);
+        }
+        for (index, object) in config.objects.iter().enumerate() {
+            self.object(&object.object, config, format!("objects[{index}]"));
+        }
+    }
+
+    fn object(&mut self, def: &ObjectDef, config: &SceneConfig, field: String) {
+        let material = match def {
+            ObjectDef::Sphere(s) => {
+                // Negative radii are allowed: they turn the normals inwards
+                // for hollow glass shells.
+                if s.radius == 0.0 {
+                    self.problem(format!("{field}.radius"), "must be non-zero");
+                }
+                Some(&s.material)
+            }
+            ObjectDef::Mesh(m) => {
+                self.mesh(m, config, &field);
+                Some(&m.material)
+            }
+            ObjectDef::Instance(i) => {
+                if !config.meshes.contains_key(&i.mesh) {
+                    self.problem(
+                        format!("{field}.mesh"),
+                        &format!("unknown mesh '{}'", i.mesh),
+                    );
+                }
+                if i.transforms.is_empty() {
+                    self.problem(
+                        format!("{field}.transforms"),
+                        "needs at least one transform",
+                    );
+                }
+                None
+            }
+            ObjectDef::Cylinder(c) => {
+                self.positive(c.radius, format!("{field}.radius"));
+                Some(&c.material)
+            }
+            ObjectDef::Cone(c) => {
+                self.positive(c.radius, format!("{field}.radius"));
+                Some(&c.material)
+            }
+            ObjectDef::Disk(d) => {
+                self.positive(d.radius, format!("{field}.radius"));
+                if d.normal == DVec3::ZERO {
+                    self.problem(format!("{field}.normal"), "must be non-zero");
+                }
+                Some(&d.material)
+            }
+            ObjectDef::Cuboid(b) => Some(&b.material),
+            ObjectDef::Torus(t) => {
+                self.positive(t.major_radius, format!("{field}.major_radius"));
+                self.positive(t.minor_radius, format!("{field}.minor_radius"));
+                Some(&t.material)
+            }
+            ObjectDef::Quadric(q) => Some(&q.material),
+            ObjectDef::Heightfield(h) => {
+                self.file(&h.path, format!("{field}.path"));
+                Some(&h.material)
+            }
+            ObjectDef::Rope(r) => {
+                self.positive(r.radius, format!("{field}.radius"));
+                Some(&r.material)
+            }
+            ObjectDef::Curve(c) => {
+                if c.widths.iter().any(|&width| width < 0.0) || c.widths == [0.0; 2] {
+                    self.problem(format!("{field}.widths"), "must be positive");
+                }
+                Some(&c.material)
+            }
+            ObjectDef::Hair(h) => {
+                self.file(&h.path, format!("{field}.path"));
+                self.positive(h.width_scale, format!("{field}.width_scale"));
+                Some(&h.material)
+            }
+            ObjectDef::PointCloud(p) => {
+                self.file(&p.path, format!("{field}.path"));
+                self.positive(p.radius, format!("{field}.radius"));
+                Some(&p.material)
+            }
+            ObjectDef::Fractal(f) => {
+                self.positive(f.scale, format!("{field}.scale"));
+                Some(&f.material)
+            }
+            ObjectDef::Sdf(d) => Some(&d.material),
+            ObjectDef::FollowPath(f) => {
+                if !config.paths.contains_key(&f.path) {
+                    self.problem(
+                        format!("{field}.path"),
+                        &format!("unknown path '{}'", f.path),
+                    );
+                }
+                self.object(&f.object, config, format!("{field}.object"));
+                None
+            }
+            ObjectDef::Drop(d) => {
+                self.object(&d.object, config, format!("{field}.object"));
+                None
+            }
+            ObjectDef::Motion(m) => {
+                if m.keys.is_empty() {
+                    self.problem(format!("{field}.keys"), "needs at least one key");
+                }
+                self.object(&m.object, config, format!("{field}.object"));
+                None
+            }
+            ObjectDef::Scatter(s) => {
+                self.file(&s.target, format!("{field}.target"));
+                if let Some(density) = &s.density {
+                    self.texture(density, config, format!("{field}.density"));
+                }
+                self.object(&s.prototype, config, format!("{field}.prototype"));
+                None
+            }
+            ObjectDef::Union(c) | ObjectDef::Intersection(c) => {
+                if c.objects.is_empty() {
+                    self.problem(format!("{field}.objects"), "needs at least one object");
+                }
+                for (index, child) in c.objects.iter().enumerate() {
+                    self.object(child, config, format!("{field}.objects[{index}]"));
+                }
+                None
+            }
+            ObjectDef::Difference(d) => {
+                self.object(&d.object, config, format!("{field}.object"));
+                for (index, child) in d.subtract.iter().enumerate() {
+                    self.object(child, config, format!("{field}.subtract[{index}]"));
+                }
+                None
+            }
+            ObjectDef::Animated(a) => {
+                self.animation(&a.animation, format!("{field}.animation"));
+                self.object(&a.object, config, format!("{field}.object"));
+                None
+            }
+            ObjectDef::Group(g) => {
+                if g.objects.is_empty() {
+                    self.problem(format!("{field}.objects"), "needs at least one object");
+                }
+                for (index, child) in g.objects.iter().enumerate() {
+                    self.object(child, config, format!("{field}.objects[{index}]"));
+                }
+                None
+            }
+            ObjectDef::Node(n) => {
+                if let Some(animation) = &n.animation {
+                    if n.transform.is_some() {
+                        self.problem(format!("{field}.transform"), "can't be used with animation");
+                    }
+                    self.animation(animation, format!("{field}.animation"));
+                }
+                if n.children.is_empty() {
+                    self.problem(format!("{field}.children"), "needs at least one object");
+                }
+                for (index, child) in n.children.iter().enumerate() {
+                    self.object(child, config, format!("{field}.children[{index}]"));
+                }
+                None
+            }
+        };
+        if let Some(material) = material {
+            self.material(material, config, format!("{field}.material"));
+        }
+    }
+
+    fn mesh(&mut self, def: &MeshDef, config: &SceneConfig, field: &str) {
+        self.file(&def.path, format!("{field}.path"));
+        for (index, lod) in def.lods.iter().enumerate() {
+            self.file(&lod.path, format!("{field}.lods[{index}].path"));
+            self.positive(lod.distance, format!("{field}.lods[{index}].distance"));
+        }
+        if def.lods.windows(2).any(|l| l[1].distance <= l[0].distance) {
+            self.problem(format!("{field}.lods"), "must be in increasing distance");
+        }
+        if let Some(displacement) = &def.displacement {
+            let field = format!("{field}.displacement.texture");
+            self.texture(&displacement.texture, config, field);
+        }
+    }
+
+    fn animation(&mut self, def: &AnimationDef, field: String) {
+        if def.keys.is_empty() {
+            self.problem(format!("{field}.keys"), "needs at least one key");
+        }
+        if def.keys.windows(2).any(|k| k[1].time < k[0].time) {
+            self.problem(format!("{field}.keys"), "must be in time order");
+        }
+    }
+
+    fn material(&mut self, def: &MaterialRef, config: &SceneConfig, field: String) {
+        match def {
+            Reference::Named(name) if !config.materials.contains_key(name) => {
+                self.problem(field, &format!("unknown material '{name}'"))
+            }
+            Reference::Named(_) => {}
+            Reference::Inline(def) => self.material_def(def, config, field),
+        }
+    }
+
+    fn material_def(&mut self, def: &MaterialDef, config: &SceneConfig, field: String) {
+        match def {
+            MaterialDef::Metal {
+                texture, thin_film, ..
+            } => {
+                self.texture(texture, config, format!("{field}.texture"));
+                self.thin_film(thin_film, format!("{field}.thin_film"));
+            }
+            MaterialDef::Lambertian { texture }
+            | MaterialDef::AnisotropicMetal { texture, .. }
+            | MaterialDef::ShadowCatcher { texture } => {
+                self.texture(texture, config, format!("{field}.texture"))
+            }
+            MaterialDef::Principled { base_color, .. } => {
+                self.texture(base_color, config, format!("{field}.base_color"))
+            }
+            MaterialDef::NormalMapped {
+                material,
+                normal_map,
+                ..
+            } => {
+                self.material(material, config, format!("{field}.material"));
+                self.texture(normal_map, config, format!("{field}.normal_map"));
+            }
+            MaterialDef::Cutout {
+                material, alpha, ..
+            } => {
+                self.material(material, config, format!("{field}.material"));
+                self.texture(alpha, config, format!("{field}.alpha"));
+            }
+            MaterialDef::Mix { a, b, factor } => {
+                self.material(a, config, format!("{field}.a"));
+                self.material(b, config, format!("{field}.b"));
+                match factor {
+                    FactorDef::Constant(weight) if !(0.0..=1.0).contains(weight) => {
+                        self.problem(format!("{field}.factor"), "must be between 0 and 1")
+                    }
+                    FactorDef::Constant(_) => {}
+                    FactorDef::Texture(texture) => {
+                        self.texture(texture, config, format!("{field}.factor"))
+                    }
+                }
+            }
+            MaterialDef::Layered { base, coat_ior, .. } => {
+                self.material(base, config, format!("{field}.base"));
+                self.positive(*coat_ior, format!("{field}.coat_ior"));
+            }
+            MaterialDef::Hair {
+                color,
+                ior,
+                longitudinal_roughness,
+                azimuthal_roughness,
+                ..
+            } => {
+                self.texture(color, config, format!("{field}.color"));
+                self.positive(*ior, format!("{field}.ior"));
+                self.positive(
+                    *longitudinal_roughness,
+                    format!("{field}.longitudinal_roughness"),
+                );
+                self.positive(*azimuthal_roughness, format!("{field}.azimuthal_roughness"));
+            }
+            MaterialDef::Dielectric {
+                index_of_refraction,
+                abbe_number,
+                cauchy,
+                sellmeier,
+                thin_film,
+                absorption,
+                ..
+            } => {
+                self.positive(*index_of_refraction, format!("{field}.index_of_refraction"));
+                self.thin_film(thin_film, format!("{field}.thin_film"));
+                self.absorption(absorption, format!("{field}.absorption"));
+                let given = [abbe_number.is_some(), cauchy.is_some(), sellmeier.is_some()];
+                if given.into_iter().filter(|&g| g).count() > 1 {
+                    self.problem(field, "give only one of abbe_number, cauchy and sellmeier");
+                }
+            }
+            MaterialDef::Volume {
+                density: Some(density),
+                ..
+            } => self.file(&density.path, format!("{field}.density.path")),
+            MaterialDef::DiffuseLight { texture, power, .. } => {
+                if let Some(texture) = texture {
+                    self.texture(texture, config, format!("{field}.texture"));
+                }
+                if let Some(power) = power {
+                    self.positive(*power, format!("{field}.power"));
+                }
+            }
+            MaterialDef::Conductor { metal, eta, k, .. } => match (metal, eta, k) {
+                (Some(_), None, None) => {}
+                (None, Some(eta), Some(k)) => {
+                    if eta.cmple(DVec3::ZERO).any() {
+                        self.problem(format!("{field}.eta"), "must be positive");
+                    }
+                    if k.cmplt(DVec3::ZERO).any() {
+                        self.problem(format!("{field}.k"), "must not be negative");
+                    }
+                }
+                _ => self.problem(field, "give either metal, or both eta and k"),
+            },
+            MaterialDef::Subsurface { .. } | MaterialDef::Volume { .. } => {}
+        }
+    }
+
+    fn thin_film(&mut self, def: &Option<ThinFilmDef>, field: String) {
+        if let Some(film) = def {
+            if film.thickness < 0.0 {
+                self.problem(format!("{field}.thickness"), "must not be negative");
+            }
+            let index_field = format!("{field}.index_of_refraction");
+            self.positive(film.index_of_refraction, index_field);
+        }
+    }
+
+    fn absorption(&mut self, def: &Option<AbsorptionDef>, field: String) {
+        if let Some(absorption) = def {
+            let color = absorption.color;
+            if !(color.cmpgt(DVec3::ZERO).all() && color.cmple(DVec3::ONE).all()) {
+                self.problem(format!("{field}.color"), "must be in (0, 1]");
+            }
+            if !(absorption.density >= 0.0) {
+                self.problem(format!("{field}.density"), "must not be negative");
+            }
+        }
+    }
+
+    fn texture(&mut self, def: &TextureRef, config: &SceneConfig, field: String) {
+        match def {
+            Reference::Named(name) if !config.textures.contains_key(name) => {
+                self.problem(field, &format!("unknown texture '{name}'"))
+            }
+            Reference::Named(_) => {}
+            Reference::Inline(def) => self.texture_def(def, config, field),
+        }
+    }
+
+    fn texture_def(&mut self, def: &TextureDef, config: &SceneConfig, field: String) {
+        match def {
+            TextureDef::Image { path, .. } if !self.in_memory.contains(path) => {
+                self.file(path, format!("{field}.path"))
+            }
+            TextureDef::LinearGradient { from, to, .. } => {
+                if from == to {
+                    self.problem(format!("{field}.to"), "must differ from `from`");
+                }
+            }
+            TextureDef::RadialGradient { radius, .. } => {
+                self.positive(*radius, format!("{field}.radius"))
+            }
+            TextureDef::Marble { scale, .. } => self.positive(*scale, format!("{field}.scale")),
+            TextureDef::Wood { ring_spacing, .. } => {
+                self.positive(*ring_spacing, format!("{field}.ring_spacing"))
+            }
+            TextureDef::Bricks {
+                size, mortar_width, ..
+            } => {
+                self.positive(size.min_element(), format!("{field}.size"));
+                if !(*mortar_width >= 0.0) {
+                    self.problem(format!("{field}.mortar_width"), "must not be negative");
+                }
+            }
+            _ => {}
+        }
+        for (name, input) in def.inputs() {
+            self.texture(input, config, format!("{field}.{name}"));
+        }
+    }
+}
+
+// Whether library entry `name` leads back to itself through the names its
+// definition, and the definitions it names, refer to.
+fn refers_to_itself<T>(
+    library: &HashMap<String, T>,
+    name: &str,
+    references: fn(&T) -> Vec<&str>,
+) -> bool {
+    let mut pending = references(&library[name]);
+    let mut seen = HashSet::new();
+    while let Some(next) = pending.pop() {
+        if next == name {
+            return true;
+        }
+        if seen.insert(next) {
+            if let Some(def) = library.get(next) {
+                pending.extend(references(def));
+            }
+        }
+    }
+    false
+}
+
+// --- Scene Construction Logic ---
+
+pub struct Scene;
+
+struct ParseContext<'a> {
+    paths: HashMap<String, CatmullRom>,
+    mesh_defs: &'a HashMap<String, MeshDef>,
+    // Bottom-level BVHs of the instanced meshes built so far, by name and
+    // level of detail.
+    meshes: RefCell<HashMap<(String, usize), Arc<dyn Hittable>>>,
+    // The triangles of every mesh file read so far, by
+    // `MeshDef::geometry_key`, with `unassigned` where the object's own
+    // material goes, so objects that read a file alike share one copy
+    // whatever their materials.
+    geometry: RefCell<HashMap<String, Arc<Vec<Triangle>>>>,
+    unassigned: Arc<dyn crate::material::Material>,
+    // All the meshes built so far, by their geometry key and the JSON of
+    // their material, so objects alike share one BVH too.
+    hierarchies: RefCell<HashMap<(String, String), Arc<dyn Hittable>>>,
+    library: MaterialLibrary<'a>,
+    time: f64,
+    // Animation time between this frame and the next, over which keyframed
+    // objects move while the shutter is open; 0 for a still.
+    frame_span: f64,
+    bounds: BoundsDef,
+    // Where the camera is, which meshes pick their level of detail by.
+    eye: DVec3,
+    // The world transform of the object being parsed, composed from the
+    // groups, nodes and other placements around it, for judging how far
+    // from the eye its meshes end up.
+    placement: Cell<DAffine3>,
+    import: MeshImport,
+    // The emitters found so far that the renderer can sample directly; see
+    // `parse_emitter`.
+    lights: RefCell<LightSet>,
+}
+
+impl<'a> ParseContext<'a> {
+    fn new(
+        config: &'a SceneConfig,
+        time: f64,
+        frame_span: f64,
+        textures: Option<&TextureWatch>,
+        images: Option<&'a ImageFiles>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut curves = HashMap::new();
+        for (name, path_def) in &config.paths {
+            if path_def.points.len() < 2 {
+                return Err(format!("path '{}' needs at least two points", name).into());
+            }
+            curves.insert(
+                name.clone(),
+                CatmullRom::new(path_def.points.clone(), path_def.closed),
+            );
+        }
+        let eye = match &config.camera.path {
+            Some(name) => curves
+                .get(name)
+                .ok_or_else(|| format!("unknown path '{}'", name))?
+                .position_at(time),
+            None => config.camera.lookfrom,
+        };
+        Ok(Self {
+            paths: curves,
+            mesh_defs: &config.meshes,
+            meshes: RefCell::new(HashMap::new()),
+            geometry: RefCell::new(HashMap::new()),
+            unassigned: Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE)))),
+            hierarchies: RefCell::new(HashMap::new()),
+            library: MaterialLibrary::new(&config.materials, &config.textures)
+                .with_energy_compensation(config.energy_compensation)
+                .with_texture_watch(textures)
+                .with_images(images),
+            time,
+            frame_span,
+            bounds: config.accelerator.bounds(),
+            eye,
+            placement: Cell::new(DAffine3::IDENTITY),
+            import: MeshImport::of(config),
+            lights: RefCell::new(LightSet::new()),
+        })
+    }
+
+    // Runs `parse` for an object placed by `transform` within the current
+    // placement.
+    fn placed<T>(&self, transform: DAffine3, parse: impl FnOnce() -> T) -> T {
+        let outer = self.placement.replace(self.placement.get() * transform);
+        let parsed = parse();
+        self.placement.set(outer);
+        parsed
+    }
+
+    // BVH over `primitives`, with the bounds the accelerator asks for.
+    fn hierarchy(&self, primitives: HittableList) -> Arc<dyn Hittable> {
+        Arc::new(BvhNode::new(self.bounds.apply(primitives)))
+    }
+
+    // The mesh `def` at the level of detail for its distance from the
+    // camera, judged by the bounds of its coarsest level.
+    fn mesh_at_distance(
+        &self,
+        def: &MeshDef,
+        material: Arc<dyn crate::material::Material>,
+    ) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+        if def.lods.is_empty() {
+            return self.mesh_hierarchy(def, 0, material);
+        }
+        let coarsest = self.mesh_hierarchy(def, def.lods.len(), material.clone())?;
+        match self.level(def, &*coarsest) {
+            level if level == def.lods.len() => Ok(coarsest),
+            level => self.mesh_hierarchy(def, level, material),
+        }
+    }
+
+    // The level of detail of `def` for an object whose coarsest level is
+    // `coarsest`, by the distance from the camera to its bounds where the
+    // current placement puts them.
+    fn level(&self, def: &MeshDef, coarsest: &dyn Hittable) -> usize {
+        coarsest.bounding_box().map_or(0, |bounds| {
+            let bounds = transform_box(&bounds, &self.placement.get());
+            def.level_at(bounds.distance(self.eye))
+        })
+    }
+
+    // BVH over the triangles of level of detail `level` of the mesh `def`,
+    // all with `material`, the one `def` names, unless `use_mtl` lets them
+    // keep those of its MTL file. Built on first use; later calls for the
+    // same file, read alike, with the same material share it.
+    fn mesh_hierarchy(
+        &self,
+        def: &MeshDef,
+        level: usize,
+        material: Arc<dyn crate::material::Material>,
+    ) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+        let geometry = def.geometry_key(level, self.import)?;
+        let key = (geometry.clone(), serde_json::to_string(&def.material)?);
+        if let Some(mesh) = self.hierarchies.borrow().get(&key) {
+            return Ok(mesh.clone());
+        }
+        let triangles = self
+            .mesh_geometry(def, level, &geometry)?
+            .iter()
+            .map(|triangle| {
+                let mut triangle = triangle.clone();
+                if Arc::ptr_eq(&triangle.material, &self.unassigned) {
+                    triangle.material = material.clone();
+                }
+                triangle
+            })
+            .collect();
+        let mesh = self.build_mesh_hierarchy(def, level, triangles, &geometry)?;
+        self.hierarchies.borrow_mut().insert(key, mesh.clone());
+        Ok(mesh)
+    }
+
+    // The triangles of level `level` of `def`, read on first use.
+    fn mesh_geometry(
+        &self,
+        def: &MeshDef,
+        level: usize,
+        geometry: &str,
+    ) -> Result<Arc<Vec<Triangle>>, Box<dyn Error>> {
+        if let Some(triangles) = self.geometry.borrow().get(geometry) {
+            return Ok(triangles.clone());
+        }
+        let unassigned = self.unassigned.clone();
+        let triangles = mesh_triangles(def, level, unassigned, self.import, &self.library)?;
+        let triangles = Arc::new(triangles);
+        self.geometry
+            .borrow_mut()
+            .insert(geometry.to_string(), triangles.clone());
+        Ok(triangles)
+    }
+
+    // The triangles are kept in the flat buffers of a `Mesh` unless the
+    // accelerator asks for bounds tighter than boxes.
+    fn build_mesh_hierarchy(
+        &self,
+        def: &MeshDef,
+        level: usize,
+        triangles: Vec<Triangle>,
+        geometry: &str,
+    ) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+        if def.cache_bvh {
+            let objects = triangles
+                .into_iter()
+                .map(|t| Arc::new(t) as Arc<dyn Hittable>)
+                .collect();
+            // The options change the triangles as much as the file does.
+            let mut source = std::fs::read(def.level_path(level))?;
+            source.extend(geometry.as_bytes());
+            let path = format!("{}.qbvh", def.level_path(level));
+            return Ok(Arc::new(Qbvh::cached(
+                self.bounds.apply(objects),
+                BvhBuildStrategy::default(),
+                Path::new(&path),
+                scene_hash(&source),
+            )));
+        }
+        if self.bounds == BoundsDef::Aabb {
+            return Ok(Arc::new(Mesh::new(triangles)));
+        }
+        Ok(self.hierarchy(
+            triangles
+                .into_iter()
+                .map(|t| Arc::new(t) as Arc<dyn Hittable>)
+                .collect(),
+        ))
+    }
+
+    fn mesh_def(&self, name: &str) -> Result<&'a MeshDef, Box<dyn Error>> {
+        self.mesh_defs
+            .get(name)
+            .ok_or_else(|| format!("unknown mesh '{}'", name).into())
+    }
+
+    // Loads level of detail `level` of the named mesh on first use; later
+    // calls share it.
+    fn mesh(&self, name: &str, level: usize) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+        let key = (name.to_string(), level);
+        if let Some(mesh) = self.meshes.borrow().get(&key) {
+            return Ok(mesh.clone());
+        }
+        let def = self.mesh_def(name)?;
+        let material = self.library.material(&def.material)?;
+        let mesh = self.mesh_hierarchy(def, level, material)?;
+        self.meshes.borrow_mut().insert(key, mesh.clone());
+        Ok(mesh)
+    }
+
+    fn path(&self, name: &str) -> Result<&CatmullRom, Box<dyn Error>> {
+        self.paths
+            .get(name)
+            .ok_or_else(|| format!("unknown path '{}'", name).into())
+    }
+}
+
+impl Scene {
+    pub fn from_file(
+        path: &str,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), RenderError> {
+        Self::from_file_at(path, 0.0)
+    }
+
+    // `time` is the normalized animation time in [0, 1] used to place
+    // cameras and objects that follow a path.
+    pub fn from_file_at(
+        path: &str,
+        time: f64,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), RenderError> {
+        Self::read_config(path)
+            .and_then(|config| Self::from_config(config, time, 0.0, None, None))
+            .map_err(|e| RenderError::scene(path, e))
+    }
+
+    // Like `from_file`, adding the scene's bilinear and alpha image
+    // textures to `textures` so that they can be read again when their
+    // files change; see `TextureWatch::reload_changed`.
+    pub fn from_file_watching(
+        path: &str,
+        textures: &TextureWatch,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), RenderError> {
+        Self::read_config(path)
+            .and_then(|config| Self::from_config(config, 0.0, 0.0, Some(textures), None))
+            .map_err(|e| RenderError::scene(path, e))
+    }
+
+    // Frame `frame` of an animation of `frames` frames spread evenly over
+    // the [0, 1] animation time. Keyframed objects are also given motion
+    // blur over the time to the next frame, spread across the shutter.
+    pub fn from_file_at_frame(
+        path: &str,
+        frame: u32,
+        frames: u32,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), RenderError> {
+        let frame_span = if frames > 1 {
+            1.0 / (frames - 1) as f64
+        } else {
+            0.0
+        };
+        let time = frame as f64 * frame_span;
+        Self::read_config(path)
+            .and_then(|config| Self::from_config(config, time, frame_span, None, None))
+            .map_err(|e| RenderError::scene(path, e))
+    }
+
+    // Saves `config` in the format named by the extension of `path`, for
+    // scenes built or modified in code that should be rendered again later.
+    pub fn to_file(config: &SceneConfig, path: &str) -> Result<(), Box<dyn Error>> {
+        let text = SceneFormat::from_path(path).write(config)?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    // Builds a scene from text in an explicit format, for scenes that don't
+    // come from a file or whose extension doesn't name the format.
+    pub fn from_source_at(
+        source: &str,
+        format: SceneFormat,
+        time: f64,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), Box<dyn Error>> {
+        Self::from_config_at(format.parse(source)?, time)
+    }
+
+    // Builds a scene from JSON text, for callers with no filesystem to
+    // read one from, such as a page rendering into a canvas. Its `image`
+    // textures are read from `images`.
+    pub fn from_json_str(
+        source: &str,
+        images: &ImageFiles,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), Box<dyn Error>> {
+        Self::from_config_with_images(SceneFormat::Json.parse(source)?, 0.0, images)
+    }
+
+    // Builds a scene from a configuration parsed, and perhaps changed, in
+    // code.
+    pub fn from_config_at(
+        mut config: SceneConfig,
+        time: f64,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), Box<dyn Error>> {
+        config.resolve_includes(None)?;
+        Self::from_config(config, time, 0.0, None, None)
+    }
+
+    // Like `from_config_at`, reading the `image` textures whose paths are
+    // in `images` from there rather than from files.
+    pub fn from_config_with_images(
+        mut config: SceneConfig,
+        time: f64,
+        images: &ImageFiles,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), Box<dyn Error>> {
+        config.resolve_includes(None)?;
+        Self::from_config(config, time, 0.0, None, Some(images))
+    }
+
+    fn from_config(
+        scene_def: SceneConfig,
+        time: f64,
+        frame_span: f64,
+        textures: Option<&TextureWatch>,
+        images: Option<&ImageFiles>,
+    ) -> Result<(SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>), Box<dyn Error>> {
+        let (scene_def, camera, objects, lights) =
+            Self::load(scene_def, time, frame_span, textures, images)?;
+        let world = scene_def.accelerator.build(objects, camera.origin)?;
+        Ok((scene_def, camera, world, Arc::new(lights)))
+    }
+
+    // Writes the bounds of every top-level object and the BVH boxes `depth`
+    // levels below the root to an OBJ wireframe at `output`. Node boxes come
+    // from the four-wide BVH; scenes using another accelerator get the same
+    // hierarchy built with the default SAH strategy.
+    pub fn export_bounds(path: &str, depth: usize, output: &Path) -> Result<(), Box<dyn Error>> {
+        let (scene_def, _, objects, _) =
+            Self::load(Self::read_config(path)?, 0.0, 0.0, None, None)?;
+        let objects = scene_def.accelerator.bounds().apply(objects);
+        let object_boxes = objects.iter().filter_map(|o| o.bounding_box()).collect();
+        let strategy = match scene_def.accelerator {
+            AcceleratorDef::Qbvh { strategy, .. } => strategy.into(),
+            _ => BvhBuildStrategy::default(),
+        };
+        let bvh = Qbvh::with_strategy(objects, strategy);
+        write_boxes_obj(
+            output,
+            &[
+                ("objects", object_boxes),
+                ("bvh", bvh.boxes_at_depth(depth)),
+            ],
+        )
+    }
+
+    fn read_config(path: &str) -> Result<SceneConfig, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        let mut config = SceneFormat::from_path(path).parse(&source)?;
+        config.resolve_includes(Some(Path::new(path)))?;
+        Ok(config)
+    }
+
+    fn load(
+        mut scene_def: SceneConfig,
+        time: f64,
+        frame_span: f64,
+        textures: Option<&TextureWatch>,
+        images: Option<&ImageFiles>,
+    ) -> Result<(SceneConfig, Camera, HittableList, LightSet), Box<dyn Error>> {
+        let mut validator = Validator {
+            in_memory: images.map_or_else(HashSet::new, |i| i.keys().cloned().collect()),
+            ..Validator::default()
+        };
+        validator.config(&scene_def);
+        validator.finish()?;
+        if let Some(preset) = scene_def.resolution {
+            (scene_def.render.width, scene_def.render.height) = preset.dimensions();
+        }
+        // The camera always matches the displayed image, so shapes keep
+        // their proportions once non-square pixels are unsqueezed.
+        let aspect_ratio = scene_def.render.aspect_ratio();
+
+        let ctx = ParseContext::new(&scene_def, time, frame_span, textures, images)?;
+
+        let mut objects = HittableList::new();
+        let mut dropped = Vec::new();
+        // Index into `objects` of every named object once dropped objects
+        // have been appended after settling.
+        let mut named = HashMap::new();
+        let mut dropped_names = Vec::new();
+        for (index, obj_def) in scene_def.objects.iter().enumerate() {
+            // Named objects take their IDs from their names, which last
+            // through edits to the scene; the rest are numbered from 1, so
+            // the ID pass can keep 0 for misses.
+            let name = obj_def.name.as_deref();
+            let id = name.map_or(index as u32 + 1, name_id);
+            match &obj_def.object {
+                ObjectDef::Drop(d) => {
+                    let object = Arc::new(Tagged::new(parse_object(&d.object, &ctx)?, id));
+                    let object = obj_def.with_visibility(object);
+                    dropped.push((&*d.object, object));
+                    dropped_names.push(name);
+                }
+                object_def => {
+                    if let Some(name) = name {
+                        if named.insert(name, objects.len()).is_some() {
+                            return Err(format!("duplicate object name '{}'", name).into());
+                        }
+                    }
+                    let object = parse_placed(object_def, &ctx)?;
+                    objects.push(obj_def.with_visibility(Arc::new(Tagged::new(object, id))));
+                }
+            }
+        }
+        let mut lights = ctx.lights.take();
+        if !ctx.library.index_matched.get() {
+            lights.mark_without_media();
+        }
+        for light_def in &scene_def.lights {
+            lights.add_analytic(parse_light(light_def)?);
+        }
+        for portal in &scene_def.portals {
+            lights.add_portal(Portal {
+                corner: portal.corner,
+                edge1: portal.edge1,
+                edge2: portal.edge2,
+            });
+        }
+        if !dropped.is_empty() {
+            settle_dropped(&mut objects, dropped, &scene_def.settle)?;
+            let first_dropped = objects.len() - dropped_names.len();
+            for (offset, name) in dropped_names.into_iter().enumerate() {
+                if let Some(name) = name {
+                    if named.insert(name, first_dropped + offset).is_some() {
+                        return Err(format!("duplicate object name '{}'", name).into());
+                    }
+                }
+            }
+        }
+
+        let (mut lookfrom, mut lookat) = match &scene_def.camera.path {
+            Some(name) => {
+                let camera_path = ctx.path(name)?;
+                let lookfrom = camera_path.position_at(time);
+                let lookat = if scene_def.camera.look_along_path {
+                    lookfrom + camera_path.tangent_at(time)
+                } else {
+                    scene_def.camera.lookat
+                };
+                (lookfrom, lookat)
+            }
+            None => (scene_def.camera.lookfrom, scene_def.camera.lookat),
+        };
+        // Keyframes move the whole rig, so a rotation about the origin
+        // orbits the camera around it.
+        let mut vup = scene_def.camera.vup;
+        if let Some(animation) = &scene_def.camera.animation {
+            let rig = animation.transform_at(time);
+            lookfrom = rig.transform_point3(lookfrom);
+            lookat = rig.transform_point3(lookat);
+            vup = rig.transform_vector3(vup);
+        }
+        let target_center = |option: &str, target: &str| -> Result<DVec3, Box<dyn Error>> {
+            let index = *named
+                .get(target)
+                .ok_or_else(|| format!("unknown {} '{}'", option, target))?;
+            let bbox = objects[index]
+                .bounding_box()
+                .ok_or_else(|| format!("{} '{}' has no bounding box", option, target))?;
+            Ok((bbox.min + bbox.max) / 2.0)
+        };
+        if let Some(target) = &scene_def.camera.track_target {
+            lookat = target_center("track_target", target)?;
+        }
+        let mut focus_dist = scene_def.camera.focus_dist.at(time);
+        if let Some(target) = &scene_def.camera.focus_target {
+            // Distance to the target's centre measured along the view axis,
+            // since the focal plane is perpendicular to it.
+            let view = (lookat - lookfrom).normalize();
+            focus_dist = (target_center("focus_target", target)? - lookfrom)
+                .dot(view)
+                .max(1e-3);
+        }
+
+        let camera = Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            scene_def.camera.vfov,
+            aspect_ratio,
+            scene_def.camera.aperture_at(time),
+            focus_dist,
+        )
+        .with_aperture_shape(scene_def.camera.aperture_shape())
+        .with_anamorphic_squeeze(scene_def.camera.anamorphic_squeeze)
+        .with_projection((&scene_def.camera.projection).into())
+        .with_shutter(scene_def.camera.shutter.0, scene_def.camera.shutter.1)
+        .with_shutter_curve((&scene_def.camera.shutter_curve).into())
+        .with_shift(scene_def.camera.shift)
+        .with_tilt(scene_def.camera.tilt * (std::f64::consts::PI / 180.0))
+        .with_lens_effects((&scene_def.camera.lens_effects).into());
+        let camera = match scene_def.camera.physical_exposure() {
+            Some(exposure) => camera.with_exposure(exposure),
+            None => camera,
+        };
+        let camera = if scene_def.camera.auto_frame {
+            let bounds = objects
+                .bounding_box()
+                .ok_or("auto_frame needs every object to have a bounding box")?;
+            camera.frame_bounds(&bounds, AUTO_FRAME_PADDING)
+        } else {
+            camera
+        };
+        let camera = match &scene_def.camera.focus {
+            Some(FocusDef::Auto) => {
+                let ray = camera.generate_ray(0.5, 0.5, DVec2::ZERO, camera.shutter.0);
+                let rec = hit_surface(&objects, &ray, Interval::after(1e-3))
+                    .ok_or("focus 'auto' finds nothing in the middle of the image")?;
+                camera.focused_on(rec.point)
+            }
+            Some(FocusDef::Object(target)) => {
+                let center = target_center("focus object", target)?;
+                let ray = Ray::new(camera.origin, center - camera.origin);
+                // Hollow objects may have nothing at their centre.
+                let object = &objects[named[target]];
+                let point = hit_surface(object.as_ref(), &ray, Interval::after(1e-3))
+                    .map_or(center, |rec| rec.point);
+                camera.focused_on(point)
+            }
+            None => camera,
+        };
+
+        Ok((scene_def, camera, objects, lights))
+    }
+
+    // Summarises a scene without rendering it; see `SceneInspection`.
+    pub fn inspect(path: &str) -> Result<SceneInspection, Box<dyn Error>> {
+        let scene_def = Self::read_config(path)?;
+        let ctx = ParseContext::new(&scene_def, 0.0, 0.0, None, None)?;
+
+        let mut inspector = Inspector::default();
+        let mut names: Vec<&String> = scene_def.textures.keys().collect();
+        names.sort();
+        for name in names {
+            let description = inspector.texture_def(&scene_def.textures[name]);
+            inspector.line(0, format!("texture '{name}': {description}"));
+        }
+        let mut names: Vec<&String> = scene_def.materials.keys().collect();
+        names.sort();
+        for name in names {
+            let description = inspector.material_def(&scene_def.materials[name]);
+            inspector.line(0, format!("material '{name}': {description}"));
+        }
+        for (index, obj_def) in scene_def.objects.iter().enumerate() {
+            let label = match &obj_def.name {
+                Some(name) => format!("#{} '{}'", index + 1, name),
+                None => format!("#{}", index + 1),
+            };
+            inspector.object(&obj_def.object, &ctx, &label, 0);
+        }
+        for light_def in &scene_def.lights {
+            let kind = match light_def {
+                LightDef::Point { .. } => "point",
+                LightDef::Spot { .. } => "spot",
+                LightDef::Directional { .. } => "directional",
+            };
+            inspector.line(0, format!("{kind} light"));
+        }
+        if !scene_def.portals.is_empty() {
+            inspector.line(0, format!("{} portals", scene_def.portals.len()));
+        }
+        match &scene_def.background {
+            Some(EnvironmentDef::Hdr { path, .. }) => {
+                inspector.line(0, format!("background: hdr '{path}'"));
+                // Texels plus the sampling CDFs.
+                inspector.image(path, std::mem::size_of::<DVec3>() + 8);
+            }
+            Some(EnvironmentDef::Solid { .. }) => inspector.line(0, "background: solid".into()),
+            Some(EnvironmentDef::Sky { .. }) => inspector.line(0, "background: sky".into()),
+            Some(EnvironmentDef::Gradient { .. }) | None => {
+                inspector.line(0, "background: gradient".into())
+            }
+        }
+        if let Some(path) = &scene_def.output.lut {
+            inspector.line(0, format!("lut: '{path}'"));
+            inspector.asset(path);
+        }
+        Ok(inspector.finish())
+    }
+}
+
+// What `Scene::inspect` found: the object tree with materials, textures,
+// bounds and triangle counts, the files the scene reads and any of them
+// that don't exist, and a rough estimate of the memory the scene needs
+// once loaded.
+pub struct SceneInspection {
+    pub tree: String,
+    pub assets: Vec<String>,
+    pub missing_assets: Vec<String>,
+    pub triangles: usize,
+    pub estimated_bytes: usize,
+}
+
+impl std::fmt::Display for SceneInspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tree)?;
+        writeln!(f, "triangles: {}", self.triangles)?;
+        writeln!(
+            f,
+            "estimated memory: {:.1} MiB",
+            self.estimated_bytes as f64 / (1024.0 * 1024.0)
+        )?;
+        if self.missing_assets.is_empty() {
+            writeln!(f, "missing assets: none")
+        } else {
+            writeln!(f, "missing assets:")?;
+            for asset in &self.missing_assets {
+                writeln!(f, "  {asset}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inspector {
+    tree: String,
+    assets: Vec<String>,
+    missing_assets: Vec<String>,
+    triangles: usize,
+    estimated_bytes: usize,
+    // Triangles of each instanced mesh already counted.
+    instanced: HashMap<String, usize>,
+    // Images, with their bytes per texel, and inline mesh definitions, as
+    // JSON, already counted; objects that share them share their memory.
+    images: HashSet<(String, usize)>,
+    meshes: HashSet<String>,
+}
+
+// A primitive plus its share of the BVH: about two nodes per leaf.
+fn primitive_bytes(size: usize) -> usize {
+    let pointer = std::mem::size_of::<Arc<dyn Hittable>>();
+    let node = std::mem::size_of::<AABB>() + 2 * pointer;
+    size + pointer + 2 * node
+}
+
+impl Inspector {
+    fn finish(self) -> SceneInspection {
+        SceneInspection {
+            tree: self.tree,
+            assets: self.assets,
+            missing_assets: self.missing_assets,
+            triangles: self.triangles,
+            estimated_bytes: self.estimated_bytes,
+        }
+    }
+
+    fn line(&mut self, depth: usize, text: String) {
+        self.tree.push_str(&"  ".repeat(depth));
+        self.tree.push_str(&text);
+        self.tree.push('\n');
+    }
+
+    // Records `path` as missing unless it exists; returns whether it does.
+    fn asset(&mut self, path: &str) -> bool {
+        let exists = Path::new(path).exists();
+        if !self.assets.iter().any(|p| p == path) {
+            self.assets.push(path.to_string());
+        }
+        if !exists && !self.missing_assets.iter().any(|p| p == path) {
+            self.missing_assets.push(path.to_string());
+        }
+        exists
+    }
+
+    fn image(&mut self, path: &str, bytes_per_texel: usize) {
+        if self.asset(path) && self.images.insert((path.to_string(), bytes_per_texel)) {
+            if let Ok((width, height)) = image::image_dimensions(path) {
+                self.estimated_bytes += width as usize * height as usize * bytes_per_texel;
+            }
+        }
+    }
+
+    // Writes the subtree for `def` and returns the triangles it renders.
+    fn object(&mut self, def: &ObjectDef, ctx: &ParseContext, label: &str, depth: usize) -> usize {
+        let missing_before = self.missing_assets.len();
+        let (kind, material) = match def {
+            ObjectDef::Sphere(s) => ("sphere", Some(&s.material)),
+            ObjectDef::Mesh(m) => ("mesh", Some(&m.material)),
+            ObjectDef::Instance(_) => ("instance", None),
+            ObjectDef::Cylinder(c) => ("cylinder", Some(&c.material)),
+            ObjectDef::Cone(c) => ("cone", Some(&c.material)),
+            ObjectDef::Disk(d) => ("disk", Some(&d.material)),
+            ObjectDef::Cuboid(b) => ("box", Some(&b.material)),
+            ObjectDef::Torus(t) => ("torus", Some(&t.material)),
+            ObjectDef::Quadric(q) => ("quadric", Some(&q.material)),
+            ObjectDef::Heightfield(h) => ("heightfield", Some(&h.material)),
+            ObjectDef::Rope(r) => ("rope", Some(&r.material)),
+            ObjectDef::Curve(c) => ("curve", Some(&c.material)),
+            ObjectDef::Hair(h) => ("hair", Some(&h.material)),
+            ObjectDef::PointCloud(p) => ("point_cloud", Some(&p.material)),
+            ObjectDef::Fractal(f) => ("fractal", Some(&f.material)),
+            ObjectDef::Sdf(d) => ("sdf", Some(&d.material)),
+            ObjectDef::Union(_) => ("union", None),
+            ObjectDef::Intersection(_) => ("intersection", None),
+            ObjectDef::Difference(_) => ("difference", None),
+            ObjectDef::Group(_) => ("group", None),
+            ObjectDef::Animated(_) => ("animated", None),
+            ObjectDef::Node(_) => ("node", None),
+            ObjectDef::FollowPath(_) => ("follow_path", None),
+            ObjectDef::Drop(_) => ("drop", None),
+            ObjectDef::Scatter(_) => ("scatter", None),
+            ObjectDef::Motion(_) => ("motion", None),
+        };
+        self.line(depth, format!("{label} {kind}"));
+        if let Some(material) = material {
+            let description = self.material(material);
+            self.line(depth + 1, format!("material: {description}"));
+        }
+
+        let triangles = match def {
+            ObjectDef::Mesh(m) => {
+                let count = self.mesh_triangles(m, ctx, depth);
+                let key = serde_json::to_string(m).unwrap_or_default();
+                if self.meshes.insert(key) {
+                    self.estimated_bytes +=
+                        count * primitive_bytes(std::mem::size_of::<Triangle>());
+                }
+                count
+            }
+            ObjectDef::Instance(i) => self.instance(i, ctx, depth),
+            ObjectDef::Heightfield(h) => {
+                // A height, a normal and a cell's height range per sample.
+                let per_sample = 3 * std::mem::size_of::<f64>() + std::mem::size_of::<DVec3>();
+                self.image(&h.path, per_sample);
+                image::image_dimensions(&h.path).map_or(0, |(columns, rows)| {
+                    2 * columns.saturating_sub(1) as usize * rows.saturating_sub(1) as usize
+                })
+            }
+            ObjectDef::Rope(r) => {
+                let capsule = std::mem::size_of::<capsule::Capsule>();
+                self.estimated_bytes += r.points.len() * primitive_bytes(capsule);
+                0
+            }
+            ObjectDef::Hair(h) => {
+                self.asset(&h.path);
+                // One curve per segment, about a twelfth of the file's
+                // bytes each.
+                let segments = std::fs::metadata(&h.path).map_or(0, |m| m.len() as usize / 12);
+                self.estimated_bytes += segments * primitive_bytes(std::mem::size_of::<Curve>());
+                0
+            }
+            ObjectDef::PointCloud(p) => {
+                if self.asset(&p.path) {
+                    // A position, a normal and a material index per point,
+                    // with its share of the hierarchy.
+                    let points = point_cloud::read(&p.path).map_or(0, |points| points.len());
+                    let per_point = 2 * std::mem::size_of::<DVec3>() + std::mem::size_of::<u32>();
+                    self.estimated_bytes += points * primitive_bytes(per_point);
+                    self.line(depth + 1, format!("points: {points}"));
+                }
+                0
+            }
+            ObjectDef::FollowPath(f) => self.object(&f.object, ctx, "object", depth + 1),
+            ObjectDef::Drop(d) => self.object(&d.object, ctx, "object", depth + 1),
+            ObjectDef::Motion(m) => self.object(&m.object, ctx, "object", depth + 1),
+            ObjectDef::Animated(a) => self.object(&a.object, ctx, "object", depth + 1),
+            ObjectDef::Union(c) | ObjectDef::Intersection(c) => c
+                .objects
+                .iter()
+                .map(|child| self.object(child, ctx, "object", depth + 1))
+                .sum(),
+            ObjectDef::Group(g) => g
+                .objects
+                .iter()
+                .map(|child| self.object(child, ctx, "object", depth + 1))
+                .sum(),
+            ObjectDef::Node(n) => n
+                .children
+                .iter()
+                .map(|child| self.object(child, ctx, "child", depth + 1))
+                .sum(),
+            ObjectDef::Difference(d) => {
+                let kept = self.object(&d.object, ctx, "object", depth + 1);
+                let cut: usize = d
+                    .subtract
+                    .iter()
+                    .map(|child| self.object(child, ctx, "subtract", depth + 1))
+                    .sum();
+                kept + cut
+            }
+            ObjectDef::Scatter(s) => {
+                self.asset(&s.target);
+                if let Some(density) = &s.density {
+                    let description = self.texture(density);
+                    self.line(depth + 1, format!("density: {description}"));
+                }
+                let label = format!("prototype x{}", s.count);
+                // The prototype is shared; each instance only adds a
+                // transform and a BVH leaf.
+                let prototype = self.object(&s.prototype, ctx, &label, depth + 1);
+                let instance = std::mem::size_of::<Transformed>();
+                self.estimated_bytes += s.count * primitive_bytes(instance);
+                prototype * s.count
+            }
+            _ => {
+                // Analytic primitives are all about this size.
+                self.estimated_bytes += primitive_bytes(std::mem::size_of::<Cylinder>());
+                0
+            }
+        };
+        if triangles > 0 {
+            self.line(depth + 1, format!("triangles: {triangles}"));
+        }
+
+        if depth == 0 {
+            self.triangles += triangles;
+            // Bounds need the object built, which only works once its
+            // assets are found.
+            if self.missing_assets.len() == missing_before {
+                match parse_object(def, ctx).map(|object| object.bounding_box()) {
+                    Ok(Some(bbox)) => {
+                        self.line(depth + 1, format!("bounds: {} to {}", bbox.min, bbox.max))
+                    }
+                    Ok(None) => self.line(depth + 1, "bounds: unbounded".into()),
+                    Err(e) => self.line(depth + 1, format!("error: {e}")),
+                }
+            }
+        }
+        triangles
+    }
+
+    // Instances only add a transform each; the mesh they share is counted
+    // towards memory the first time it appears.
+    fn instance(&mut self, def: &InstanceDef, ctx: &ParseContext, depth: usize) -> usize {
+        let count = def.transforms.len();
+        self.line(depth + 1, format!("mesh: '{}' x{count}", def.mesh));
+        let mesh = match ctx.mesh_def(&def.mesh) {
+            Ok(mesh) => mesh,
+            Err(e) => {
+                self.line(depth + 1, format!("error: {e}"));
+                return 0;
+            }
+        };
+        let triangles = match self.instanced.get(&def.mesh) {
+            Some(&triangles) => triangles,
+            None => {
+                let triangles = self.mesh_triangles(mesh, ctx, depth);
+                self.estimated_bytes +=
+                    triangles * primitive_bytes(std::mem::size_of::<Triangle>());
+                self.instanced.insert(def.mesh.clone(), triangles);
+                triangles
+            }
+        };
+        self.estimated_bytes += count * primitive_bytes(std::mem::size_of::<Transformed>());
+        triangles * count
+    }
+
+    // The triangles of the finest level of detail, which the estimate is
+    // kept to as the camera may come close enough to need it.
+    fn mesh_triangles(&mut self, def: &MeshDef, ctx: &ParseContext, depth: usize) -> usize {
+        for lod in &def.lods {
+            self.asset(&lod.path);
+            let line = format!("lod: '{}' from {}", lod.path, lod.distance);
+            self.line(depth + 1, line);
+        }
+        if !self.asset(&def.path) {
+            return 0;
+        }
+        let fallback = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+        let loaded = def
+            .obj_options(ctx.import, &ctx.library)
+            .and_then(|options| obj::load_with(&def.path, fallback, &options).map_err(Into::into));
+        match loaded {
+            Ok((triangles, cleanup)) => {
+                if !cleanup.is_empty() {
+                    self.line(depth + 1, format!("cleanup: {cleanup}"));
+                }
+                triangles.len()
+            }
+            Err(e) => {
+                self.line(depth + 1, format!("error: {e}"));
+                0
+            }
+        }
+    }
+
+    // Library entries are described once by `Scene::inspect`; references
+    // to them show just the name.
+    fn material(&mut self, def: &MaterialRef) -> String {
+        match def {
+            Reference::Named(name) => format!("'{name}'"),
+            Reference::Inline(def) => self.material_def(def),
+        }
+    }
+
+    fn material_def(&mut self, def: &MaterialDef) -> String {
+        match def {
+            MaterialDef::Lambertian { texture } => format!("lambertian({})", self.texture(texture)),
+            MaterialDef::Metal { texture, .. } => format!("metal({})", self.texture(texture)),
+            MaterialDef::Dielectric { .. } => "dielectric".into(),
+            MaterialDef::Principled { base_color, .. } => {
+                format!("principled({})", self.texture(base_color))
+            }
+            MaterialDef::NormalMapped {
+                material,
+                normal_map,
+                ..
+            } => {
+                let inner = self.material(material);
+                let normals = self.texture(normal_map);
+                format!("normal_mapped({inner}, normals: {normals})")
+            }
+            MaterialDef::Cutout {
+                material, alpha, ..
+            } => {
+                let inner = self.material(material);
+                let alpha = self.texture(alpha);
+                format!("cutout({inner}, alpha: {alpha})")
+            }
+            MaterialDef::Mix { a, b, factor } => {
+                let (a, b) = (self.material(a), self.material(b));
+                let factor = match factor {
+                    FactorDef::Constant(weight) => weight.to_string(),
+                    FactorDef::Texture(texture) => self.texture(texture),
+                };
+                format!("mix({a}, {b}, factor: {factor})")
+            }
+            MaterialDef::Layered { base, .. } => format!("layered({})", self.material(base)),
+            MaterialDef::Hair { color, .. } => format!("hair({})", self.texture(color)),
+            MaterialDef::AnisotropicMetal { texture, .. } => {
+                format!("anisotropic_metal({})", self.texture(texture))
+            }
+            MaterialDef::Conductor {
+                metal: Some(metal), ..
+            } => format!("conductor({metal:?})").to_lowercase(),
+            MaterialDef::Conductor { .. } => "conductor".into(),
+            MaterialDef::DiffuseLight {
+                texture: Some(texture),
+                ..
+            } => format!("diffuse_light({})", self.texture(texture)),
+            MaterialDef::DiffuseLight { .. } => "diffuse_light".into(),
+            MaterialDef::ShadowCatcher { texture } => {
+                format!("shadow_catcher({})", self.texture(texture))
+            }
+            MaterialDef::Subsurface { .. } => "subsurface".into(),
+            MaterialDef::Volume { .. } => "volume".into(),
+        }
+    }
+
+    fn texture(&mut self, def: &TextureRef) -> String {
+        match def {
+            Reference::Named(name) => format!("'{name}'"),
+            Reference::Inline(def) => self.texture_def(def),
+        }
+    }
+
+    fn texture_def(&mut self, def: &TextureDef) -> String {
+        match def {
+            TextureDef::SolidColor { color } => format!("solid {}", color.rgb()),
+            TextureDef::Checker { even, odd, .. } => {
+                let even = self.texture(even);
+                format!("checker({even}, {})", self.texture(odd))
+            }
+            TextureDef::Image {
+                path,
+                filter,
+                alpha,
+                ..
+            } => {
+                let bytes = match (filter, alpha) {
+                    (TextureFilterDef::Nearest, false) => std::mem::size_of::<DVec3>(),
+                    // The mip pyramid adds a third.
+                    _ => std::mem::size_of::<DVec3>() * 4 / 3,
+                };
+                self.image(path, bytes);
+                if *alpha {
+                    format!("image alpha '{path}'")
+                } else {
+                    format!("image '{path}'")
+                }
+            }
+            TextureDef::UvTransform { texture, .. } => {
+                format!("uv_transform({})", self.texture(texture))
+            }
+            TextureDef::LinearGradient { .. } => self.blend("linear_gradient", def),
+            TextureDef::RadialGradient { .. } => self.blend("radial_gradient", def),
+            TextureDef::Marble { .. } => self.blend("marble", def),
+            TextureDef::Wood { .. } => self.blend("wood", def),
+            TextureDef::Bricks { .. } => self.blend("bricks", def),
+        }
+    }
+
+    // `kind(first, second)`, for textures that blend others.
+    fn blend(&mut self, kind: &str, def: &TextureDef) -> String {
+        let inputs: Vec<String> = def
+            .inputs()
+            .into_iter()
+            .map(|(_, input)| self.texture(input))
+            .collect();
+        format!("{kind}({})", inputs.join(", "))
+    }
+}
+
+#[cfg(feature = "physics")]
+fn settle_dropped(
+    objects: &mut HittableList,
+    dropped: Vec<(&ObjectDef, Arc<dyn Hittable>)>,
+    settle: &SettleDef,
+) -> Result<(), Box<dyn Error>> {
+    use crate::physics::{self, BodyShape, DropBody, SettleSettings};
+
+    let obstacles: Vec<_> = objects.iter().filter_map(|o| o.bounding_box()).collect();
+    let mut bodies = Vec::with_capacity(dropped.len());
+    for (def, object) in &dropped {
+        let bbox = object
+            .bounding_box()
+            .ok_or("dropped objects must have a bounding box")?;
+        let center = (bbox.min + bbox.max) / 2.0;
+        let shape = match def {
+            ObjectDef::Sphere(s) => BodyShape::Ball(s.radius),
+            _ => BodyShape::Cuboid((bbox.max - bbox.min) / 2.0),
+        };
+        bodies.push(DropBody { center, shape });
+    }
+
+    let settings = SettleSettings {
+        ground_height: settle.ground_height,
+        max_steps: settle.max_steps,
+    };
+    let transforms = physics::settle(&bodies, &obstacles, &settings);
+    for ((_, object), transform) in dropped.into_iter().zip(transforms) {
+        objects.push(Arc::new(Transformed::new(object, transform)));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "physics"))]
+fn settle_dropped(
+    _objects: &mut HittableList,
+    _dropped: Vec<(&ObjectDef, Arc<dyn Hittable>)>,
+    _settle: &SettleDef,
+) -> Result<(), Box<dyn Error>> {
+    Err("\"drop\" objects require the `physics` feature".into())
+}
+
+// Emissive spheres and meshes at the top level, or in groups and nodes
+// that don't move, are registered in the context's lights so the renderer
+// can sample them directly. Emitters nested inside other objects are still
+// visible but only found by BSDF sampling. The object is built where it is
+// defined, but its lights and their power are measured where the current
+// placement puts it in the world.
+fn parse_emitter(
+    obj_def: &ObjectDef,
+    ctx: &ParseContext,
+) -> Result<Option<Arc<dyn Hittable>>, Box<dyn Error>> {
+    let placement = ctx.placement.get();
+    let object: Arc<dyn Hittable> = match obj_def {
+        ObjectDef::Sphere(s) if ctx.library.is_emissive(&s.material) => {
+            // A sphere stretched unevenly is sized as if scaled evenly to
+            // the same volume, and left to BSDF sampling.
+            let scale = placement.matrix3.determinant().abs().cbrt();
+            let radius = s.radius * scale;
+            let area = 4.0 * std::f64::consts::PI * radius * radius;
+            let material = ctx.library.emitter_material(&s.material, |light| {
+                area * luminance(sphere_emission(s.center, s.radius, light))
+            })?;
+            let sphere = Arc::new(Sphere::new(s.center, s.radius, material.clone()));
+            let center = placement.transform_point3(s.center);
+            if !is_similarity(&placement)
+                || sphere_emission(s.center, s.radius, &*material) == DVec3::ZERO
+            {
+                return Ok(Some(sphere));
+            }
+            let index = ctx.lights.borrow_mut().add_sphere(center, radius, material);
+            Arc::new(Emitter::new(sphere, index))
+        }
+        ObjectDef::Mesh(m) if ctx.library.is_emissive(&m.material) => {
+            // The triangles come first, as the material may depend on their
+            // area; those left with `unset` take it.
+            let unset: Arc<dyn crate::material::Material> =
+                Arc::new(DiffuseLight::new(DVec3::ZERO));
+            let triangles = mesh_triangles(m, 0, unset.clone(), ctx.import, &ctx.library)?;
+            let material = ctx.library.emitter_material(&m.material, |light| {
+                triangles
+                    .iter()
+                    .filter(|t| Arc::ptr_eq(&t.material, &unset))
+                    .map(|t| {
+                        let [a, b, c] = t.vertices().map(|p| placement.transform_point3(p));
+                        let area = 0.5 * (b - a).cross(c - a).length();
+                        area * luminance(triangle_emission(t, light))
+                    })
+                    .sum()
+            })?;
+            let mut list = HittableList::new();
+            for mut triangle in triangles {
+                if Arc::ptr_eq(&triangle.material, &unset) {
+                    triangle.material = material.clone();
+                }
+                if triangle_emission(&triangle, &*triangle.material) == DVec3::ZERO {
+                    list.push(Arc::new(triangle));
+                } else {
+                    let index = ctx
+                        .lights
+                        .borrow_mut()
+                        .add_placed_triangle(&triangle, placement);
+                    list.push(Arc::new(Emitter::new(Arc::new(triangle), index)));
+                }
+            }
+            ctx.hierarchy(list)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(object))
+}
+
+// Whether `transform` keeps shapes as they are apart from their size.
+fn is_similarity(transform: &DAffine3) -> bool {
+    let m = transform.matrix3;
+    let scale = m.x_axis.length();
+    let tolerance = 1e-9 * scale;
+    (m.y_axis.length() - scale).abs() <= tolerance
+        && (m.z_axis.length() - scale).abs() <= tolerance
+        && m.x_axis.dot(m.y_axis).abs() <= tolerance * scale
+        && m.y_axis.dot(m.z_axis).abs() <= tolerance * scale
+        && m.z_axis.dot(m.x_axis).abs() <= tolerance * scale
+}
+
+fn diffuse_light(
+    color: &Option<ColorDef>,
+    texture: &Option<TextureRef>,
+    scale: f64,
+    library: &MaterialLibrary,
+) -> Result<DiffuseLight, Box<dyn Error>> {
+    Ok(DiffuseLight {
+        color: color.as_ref().map_or(DVec3::ONE, ColorDef::rgb) * scale,
+        texture: texture.as_ref().map(|t| library.texture(t)).transpose()?,
+    })
+}
+
+// Parses an object of a group or node.
+type ParseChild = fn(&ObjectDef, &ParseContext) -> Result<Arc<dyn Hittable>, Box<dyn Error>>;
+
+// `obj_def` where the current placement puts it in the world, with its
+// emitters registered for light sampling, including those of the groups
+// and nodes in it that don't move.
+fn parse_placed(
+    obj_def: &ObjectDef,
+    ctx: &ParseContext,
+) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+    match obj_def {
+        ObjectDef::Node(n) => parse_node_object(n, ctx, parse_placed),
+        ObjectDef::Group(g) => parse_group(g, ctx, parse_placed),
+        _ => match parse_emitter(obj_def, ctx)? {
+            Some(emitter) => Ok(emitter),
+            None => parse_object(obj_def, ctx),
+        },
+    }
+}
+
+fn parse_node_object(
+    node: &NodeDef,
+    ctx: &ParseContext,
+    parse_child: ParseChild,
+) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+    let mut objects = HittableList::new();
+    parse_node(node, &[DAffine3::IDENTITY], ctx, &mut objects, parse_child)?;
+    Ok(Arc::new(BvhNode::new(objects)))
+}
+
+fn parse_group(
+    group: &GroupDef,
+    ctx: &ParseContext,
+    parse_child: ParseChild,
+) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+    let transform = group.transform.as_ref().map(DAffine3::from);
+    let objects = ctx.placed(transform.unwrap_or(DAffine3::IDENTITY), || {
+        group
+            .objects
+            .iter()
+            .map(|child| parse_child(child, ctx))
+            .collect::<Result<HittableList, _>>()
+    })?;
+    let group: Arc<dyn Hittable> = Arc::new(BvhNode::new(objects));
+    Ok(match transform {
+        Some(transform) => Arc::new(Transformed::new(group, transform)),
+        None => group,
+    })
+}
+
+// Adds the objects under `node` to `objects`, each transformed by the
+// composition of the nodes above it and parsed by `parse_child`, or by
+// `parse_object` once they move. `parent` is the parent's world transform,
+// or its keys over the frame when any ancestor is keyframed.
+fn parse_node(
+    node: &NodeDef,
+    parent: &[DAffine3],
+    ctx: &ParseContext,
+    objects: &mut HittableList,
+    parse_child: ParseChild,
+) -> Result<(), Box<dyn Error>> {
+    let local = node.local_keys(ctx);
+    let world: Vec<DAffine3> = (0..parent.len().max(local.len()))
+        .map(|i| parent[i.min(parent.len() - 1)] * local[i.min(local.len() - 1)])
+        .collect();
+    let parse_child = match world.as_slice() {
+        [_] => parse_child,
+        _ => parse_object,
+    };
+    for child in &node.children {
+        if let ObjectDef::Node(n) = child {
+            parse_node(n, &world, ctx, objects, parse_child)?;
+            continue;
+        }
+        let object = ctx.placed(world[0], || parse_child(child, ctx))?;
+        let object: Arc<dyn Hittable> = match world.as_slice() {
+            [transform] => Arc::new(Transformed::new(object, *transform)),
+            keys => Arc::new(MotionTransformed::new(object, keys)),
+        };
+        objects.push(object);
+    }
+    Ok(())
+}
+
+fn parse_object(
+    obj_def: &ObjectDef,
+    ctx: &ParseContext,
+) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+    let object: Arc<dyn Hittable> = match obj_def {
+        ObjectDef::Sphere(s) => Arc::new(Sphere::new(
+            s.center,
+            s.radius,
+            ctx.library.material(&s.material)?,
+        )),
+        ObjectDef::Mesh(m) => ctx.mesh_at_distance(m, ctx.library.material(&m.material)?)?,
+        ObjectDef::Instance(i) => {
+            // Each copy picks its own level of detail, by where the
+            // transform puts the mesh's coarsest level.
+            let def = ctx.mesh_def(&i.mesh)?;
+            let coarsest = ctx.mesh(&i.mesh, def.lods.len())?;
+            let mut instances = HittableList::new();
+            for t in &i.transforms {
+                let placed = Transformed::new(coarsest.clone(), t.into());
+                let level = ctx.level(def, &placed);
+                let mesh = ctx.mesh(&i.mesh, level)?;
+                instances.push(Arc::new(Transformed::new(mesh, t.into())));
+            }
+            match instances.len() {
+                0 => return Err("instance needs at least one transform".into()),
+                1 => instances.pop().unwrap(),
+                // The top level of the two-level hierarchy: a BVH over
+                // instance bounds, each pointing into the shared mesh BVH.
+                _ => ctx.hierarchy(instances),
+            }
+        }
+        ObjectDef::Cylinder(c) => Arc::new(Cylinder::new(
+            c.start,
+            c.end,
+            c.radius,
+            ctx.library.material(&c.material)?,
+        )),
+        ObjectDef::Cone(c) => Arc::new(Cone::new(
+            c.base,
+            c.apex,
+            c.radius,
+            ctx.library.material(&c.material)?,
+        )),
+        ObjectDef::Disk(d) => {
+            if d.normal == DVec3::ZERO {
+                return Err("disk normal must be non-zero".into());
+            }
+            Arc::new(Disk::new(
+                d.center,
+                d.normal,
+                d.radius,
+                ctx.library.material(&d.material)?,
+            ))
+        }
+        ObjectDef::Cuboid(b) => Arc::new(Cuboid::new(
+            b.min,
+            b.max,
+            ctx.library.material(&b.material)?,
+        )),
+        ObjectDef::Torus(t) => {
+            if t.axis == DVec3::ZERO {
+                return Err("torus axis must be non-zero".into());
+            }
+            Arc::new(Torus::new(
+                t.center,
+                t.axis,
+                t.major_radius,
+                t.minor_radius,
+                ctx.library.material(&t.material)?,
+            ))
+        }
+        ObjectDef::Heightfield(h) => {
+            let material = ctx.library.material(&h.material)?;
+            let terrain = Heightfield::from_image(&h.path, h.size, h.height, material)?;
+            let transform = DAffine3::from_translation(h.center);
+            Arc::new(Transformed::new(Arc::new(terrain), transform))
+        }
+        ObjectDef::Quadric(q) => Arc::new(Quadric::new(
+            q.coefficients,
+            AABB::new(q.min.min(q.max), q.min.max(q.max)),
+            ctx.library.material(&q.material)?,
+        )),
+        ObjectDef::Rope(r) => {
+            if r.points.is_empty() {
+                return Err("rope needs at least one point".into());
+            }
+            let segments =
+                capsule::polyline(&r.points, r.radius, ctx.library.material(&r.material)?);
+            ctx.hierarchy(segments)
+        }
+        ObjectDef::Curve(c) => Arc::new(Curve::new(
+            c.control_points,
+            c.widths,
+            c.shape,
+            ctx.library.material(&c.material)?,
+        )),
+        ObjectDef::Hair(h) => {
+            let material = ctx.library.material(&h.material)?;
+            let mut curves = HittableList::new();
+            for mut strand in curve::read_hair(&h.path)? {
+                for width in &mut strand.widths {
+                    *width *= h.width_scale;
+                }
+                curves.extend(strand.curves(h.shape, material.clone()));
+            }
+            if curves.is_empty() {
+                return Err(format!("{} has no strands", h.path).into());
+            }
+            ctx.hierarchy(curves)
+        }
+        ObjectDef::PointCloud(p) => {
+            let mut points = point_cloud::read(&p.path)?;
+            if points.is_empty() {
+                return Err(format!("{} has no points", p.path).into());
+            }
+            let import = ctx.import.overridden(p.units, p.up_axis);
+            if let Some(conversion) = import.conversion() {
+                point_cloud::convert(&mut points, conversion);
+            }
+            if !p.point_colors {
+                for point in &mut points {
+                    point.color = None;
+                }
+            }
+            let material = ctx.library.material(&p.material)?;
+            Arc::new(PointCloud::new(&points, p.radius, p.splat, material))
+        }
+        ObjectDef::FollowPath(f) => {
+            let follow = ctx.path(&f.path)?;
+            let rotation = if f.orient {
+                let forward = follow.tangent_at(ctx.time);
+                let up = if forward.y.abs() > 0.999 {
+                    DVec3::X
+                } else {
+                    DVec3::Y
+                };
+                let right = up.cross(forward).normalize();
+                DMat3::from_cols(right, forward.cross(right), forward)
+            } else {
+                DMat3::IDENTITY
+            };
+            let transform = DAffine3::from_mat3_translation(rotation, follow.position_at(ctx.time));
+            let object = ctx.placed(transform, || parse_object(&f.object, ctx))?;
+            Arc::new(Transformed::new(object, transform))
+        }
+        ObjectDef::Drop(d) => parse_object(&d.object, ctx)?,
+        ObjectDef::Motion(m) => {
+            if m.keys.is_empty() {
+                return Err("motion needs at least one key".into());
+            }
+            let keys: Vec<DAffine3> = m.keys.iter().map(DAffine3::from).collect();
+            let object = ctx.placed(keys[0], || parse_object(&m.object, ctx))?;
+            Arc::new(MotionTransformed::new(object, &keys))
+        }
+        ObjectDef::Scatter(s) => {
+            let fallback = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+            let options = ObjOptions {
+                conversion: ctx.import.conversion(),
+                ignore_mtl: true,
+                ..ObjOptions::default()
+            };
+            let (target, _) = obj::load_with(&s.target, fallback, &options)?;
+            let settings = ScatterSettings {
+                count: s.count,
+                seed: s.seed,
+                align_to_normal: s.align_to_normal,
+                random_rotation: s.random_rotation,
+                scale_range: s.scale_range,
+                density: s
+                    .density
+                    .as_ref()
+                    .map(|d| ctx.library.data_texture(d))
+                    .transpose()?,
+            };
+            let prototype = parse_object(&s.prototype, ctx)?;
+            Arc::new(BvhNode::new(scatter::scatter_on_surface(
+                &target, prototype, &settings,
+            )))
+        }
+        ObjectDef::Fractal(f) => {
+            let material = ctx.library.material(&f.material)?;
+            let fractal: Arc<dyn Hittable> = match &f.fractal {
+                FractalShapeDef::Mandelbulb { power, iterations } => {
+                    let field = Mandelbulb {
+                        power: *power,
+                        iterations: *iterations,
+                    };
+                    Arc::new(RayMarched {
+                        max_steps: f.max_steps,
+                        ..RayMarched::new(field, material)
+                    })
+                }
+                FractalShapeDef::Menger { iterations } => {
+                    let field = MengerSponge {
+                        iterations: *iterations,
+                    };
+                    Arc::new(RayMarched {
+                        max_steps: f.max_steps,
+                        ..RayMarched::new(field, material)
+                    })
+                }
+                FractalShapeDef::Julia { c, iterations } => {
+                    let field = JuliaSet {
+                        c: DVec4::from_array(*c),
+                        iterations: *iterations,
+                    };
+                    Arc::new(RayMarched {
+                        max_steps: f.max_steps,
+                        ..RayMarched::new(field, material)
+                    })
+                }
+            };
+            let transform = DAffine3::from_scale_rotation_translation(
+                DVec3::splat(f.scale),
+                glam::DQuat::IDENTITY,
+                f.center,
+            );
+            Arc::new(Transformed::new(fractal, transform))
+        }
+        ObjectDef::Animated(a) => {
+            let keys = a.animation.keys(ctx.time, ctx.frame_span);
+            let object = ctx.placed(keys[0], || parse_object(&a.object, ctx))?;
+            match keys.as_slice() {
+                [transform] => Arc::new(Transformed::new(object, *transform)),
+                keys => Arc::new(MotionTransformed::new(object, keys)),
+            }
+        }
+        ObjectDef::Node(n) => parse_node_object(n, ctx, parse_object)?,
+        ObjectDef::Group(g) => parse_group(g, ctx, parse_object)?,
+        ObjectDef::Union(c) => parse_csg(&c.objects, ctx, CsgOperation::Union)?,
+        ObjectDef::Intersection(c) => parse_csg(&c.objects, ctx, CsgOperation::Intersection)?,
+        ObjectDef::Difference(d) => {
+            let mut object = parse_object(&d.object, ctx)?;
+            for cut in &d.subtract {
+                object = Arc::new(Csg::difference(object, parse_object(cut, ctx)?));
+            }
+            object
+        }
+        ObjectDef::Sdf(d) => Arc::new(SdfObject {
+            max_steps: d.max_steps,
+            ..SdfObject::new(parse_sdf(&d.shape)?, ctx.library.material(&d.material)?)
+        }),
+    };
+    Ok(object)
+}
+
+fn parse_csg(
+    objects: &[ObjectDef],
+    ctx: &ParseContext,
+    operation: CsgOperation,
+) -> Result<Arc<dyn Hittable>, Box<dyn Error>> {
+    let (first, rest) = objects
+        .split_first()
+        .ok_or("CSG operations need at least one object")?;
+    let mut object = parse_object(first, ctx)?;
+    for child in rest {
+        object = Arc::new(Csg::new(operation, object, parse_object(child, ctx)?));
+    }
+    Ok(object)
+}
+
+fn parse_sdf(shape: &SdfShapeDef) -> Result<SdfExpr, Box<dyn Error>> {
+    // Folds a list of operands left to right with a binary operation.
+    fn fold(
+        shapes: &[SdfShapeDef],
+        op: impl Fn(Box<SdfExpr>, Box<SdfExpr>) -> SdfExpr,
+    ) -> Result<SdfExpr, Box<dyn Error>> {
+        let (first, rest) = shapes
+            .split_first()
+            .ok_or("SDF operations need at least one shape")?;
+        rest.iter().try_fold(parse_sdf(first)?, |expr, shape| {
+            Ok(op(Box::new(expr), Box::new(parse_sdf(shape)?)))
+        })
+    }
+
+    let expr = match shape {
+        SdfShapeDef::Sphere { center, radius } => SdfExpr::Sphere {
+            center: *center,
+            radius: *radius,
+        },
+        SdfShapeDef::Cuboid { min, max } => SdfExpr::Cuboid {
+            center: 0.5 * (*min + *max),
+            half_extents: 0.5 * (*max - *min).abs(),
+        },
+        SdfShapeDef::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        } => SdfExpr::Torus {
+            center: *center,
+            major_radius: *major_radius,
+            minor_radius: *minor_radius,
+        },
+        SdfShapeDef::Capsule { start, end, radius } => SdfExpr::Capsule {
+            start: *start,
+            end: *end,
+            radius: *radius,
+        },
+        SdfShapeDef::Union { shapes } => fold(shapes, SdfExpr::Union)?,
+        SdfShapeDef::Intersection { shapes } => fold(shapes, SdfExpr::Intersection)?,
+        SdfShapeDef::Difference { shape, subtract } => {
+            subtract.iter().try_fold(parse_sdf(shape)?, |expr, cut| {
+                Ok::<_, Box<dyn Error>>(SdfExpr::Difference(
+                    Box::new(expr),
+                    Box::new(parse_sdf(cut)?),
+                ))
+            })?
+        }
+        SdfShapeDef::SmoothUnion { shapes, k } => {
+            fold(shapes, |a, b| SdfExpr::SmoothUnion(a, b, *k))?
+        }
+    };
+    Ok(expr)
+}
+
+fn parse_light(light_def: &LightDef) -> Result<AnalyticLight, Box<dyn Error>> {
+    let profile = |ies: &Option<String>| -> Result<_, Box<dyn Error>> {
+        Ok(match ies {
+            Some(path) => Some(Arc::new(IesProfile::load(path)?)),
+            None => None,
+        })
+    };
+    Ok(match light_def {
+        LightDef::Point {
+            position,
+            intensity,
+            radius,
+            ies,
+        } => AnalyticLight::Point(PointLight {
+            position: *position,
+            intensity: intensity.rgb(),
+            radius: *radius,
+            profile: profile(ies)?,
+        }),
+        LightDef::Spot {
+            position,
+            target,
+            intensity,
+            cone_angle,
+            cone_delta,
+            radius,
+            ies,
+        } => AnalyticLight::Spot(SpotLight {
+            position: *position,
+            direction: (*target - *position).normalize_or_zero(),
+            intensity: intensity.rgb(),
+            cone_angle: cone_angle.to_radians(),
+            cone_delta: cone_delta.to_radians(),
+            radius: *radius,
+            profile: profile(ies)?,
+        }),
+        LightDef::Directional {
+            direction,
+            irradiance,
+        } => AnalyticLight::Directional(DirectionalLight {
+            direction: *direction,
+            irradiance: irradiance.rgb(),
+        }),
+    })
+}
+
+pub(crate) fn parse_material(
+    mat_def: &MaterialDef,
+    library: &MaterialLibrary,
+) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+    let material: Arc<dyn crate::material::Material> = match mat_def {
+        MaterialDef::Lambertian { texture } => Arc::new(Lambertian::new(library.texture(texture)?)),
+        MaterialDef::Metal {
+            texture,
+            fuzz,
+            thin_film,
+        } => Arc::new(Metal {
+            thin_film: thin_film.map(ThinFilmDef::film),
+            ..Metal::new(library.texture(texture)?, *fuzz)
+        }),
+        MaterialDef::Dielectric {
+            index_of_refraction,
+            abbe_number,
+            cauchy,
+            sellmeier,
+            thin_film,
+            roughness,
+            absorption,
+        } => Arc::new(Dielectric {
+            thin_film: thin_film.map(ThinFilmDef::film),
+            roughness: *roughness,
+            absorption: absorption.map_or(DVec3::ZERO, AbsorptionDef::coefficients),
+            ..dielectric(*index_of_refraction, *abbe_number, *cauchy, *sellmeier)
+        }),
+        MaterialDef::Principled {
+            base_color,
+            metallic,
+            roughness,
+            ior,
+            specular,
+            transmission,
+        } => Arc::new(Principled {
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            ior: *ior,
+            specular: specular.max(0.0),
+            transmission: transmission.clamp(0.0, 1.0),
+            energy_compensation: library.energy_compensation.clone(),
+            ..Principled::new(library.texture(base_color)?)
+        }),
+        MaterialDef::NormalMapped {
+            material,
+            normal_map,
+            strength,
+        } => Arc::new(NormalMapped {
+            strength: *strength,
+            ..NormalMapped::new(
+                library.material(material)?,
+                library.data_texture(normal_map)?,
+            )
+        }),
+        MaterialDef::Cutout {
+            material,
+            alpha,
+            threshold,
+        } => Arc::new(Cutout {
+            threshold: *threshold,
+            ..Cutout::new(library.material(material)?, library.data_texture(alpha)?)
+        }),
+        MaterialDef::Mix { a, b, factor } => {
+            let factor: Arc<dyn Texture> = match factor {
+                FactorDef::Constant(weight) => Arc::new(SolidColor::new(DVec3::splat(*weight))),
+                FactorDef::Texture(texture) => library.data_texture(texture)?,
+            };
+            Arc::new(Mix::new(library.material(a)?, library.material(b)?, factor))
+        }
+        MaterialDef::Layered {
+            base,
+            coat_ior,
+            coat_roughness,
+        } => Arc::new(Layered {
+            coat_ior: *coat_ior,
+            coat_roughness: *coat_roughness,
+            ..Layered::new(library.material(base)?)
+        }),
+        MaterialDef::Hair {
+            color,
+            ior,
+            longitudinal_roughness,
+            azimuthal_roughness,
+            cuticle_angle,
+        } => Arc::new(Hair {
+            ior: *ior,
+            longitudinal_roughness: *longitudinal_roughness,
+            azimuthal_roughness: *azimuthal_roughness,
+            cuticle_angle: cuticle_angle.to_radians(),
+            ..Hair::new(library.texture(color)?)
+        }),
+        MaterialDef::AnisotropicMetal {
+            texture,
+            roughness_u,
+            roughness_v,
+            rotation,
+        } => Arc::new(AnisotropicMetal {
+            rotation: rotation.to_radians(),
+            energy_compensation: library.energy_compensation.clone(),
+            ..AnisotropicMetal::new(library.texture(texture)?, *roughness_u, *roughness_v)
+        }),
+        MaterialDef::Conductor {
+            metal,
+            eta,
+            k,
+            roughness,
+        } => Arc::new(Conductor {
+            energy_compensation: library.energy_compensation.clone(),
+            ..Conductor::new(conductor_ior(*metal, *eta, *k)?, *roughness)
+        }),
+        MaterialDef::DiffuseLight { power: Some(_), .. } => {
+            return Err("diffuse_light power only works on spheres and meshes".into())
+        }
+        MaterialDef::DiffuseLight { color, texture, .. } => {
+            Arc::new(diffuse_light(color, texture, 1.0, library)?)
+        }
+        MaterialDef::ShadowCatcher { texture } => {
+            Arc::new(ShadowCatcher::new(library.texture(texture)?))
+        }
+        MaterialDef::Subsurface {
+            sigma_a,
+            sigma_s,
+            ior,
+        } => Arc::new(Subsurface {
+            ior: *ior,
+            ..Subsurface::new(sigma_a.max(DVec3::ZERO), sigma_s.max(DVec3::ZERO))
+        }),
+        MaterialDef::Volume {
+            sigma_a,
+            sigma_s,
+            density,
+        } => {
+            let density = match density {
+                Some(def) => Some(Arc::new(def.load()?)),
+                None => None,
+            };
+            Arc::new(Volume::new(Medium {
+                density,
+                ..Medium::new(sigma_a.max(DVec3::ZERO), sigma_s.max(DVec3::ZERO))
+            }))
+        }
+    };
+    Ok(material)
+}
+
+// `tex_def` for a slot reading it as `usage`, which the textures it blends
+// are read as too.
+fn parse_texture(
+    tex_def: &TextureDef,
+    library: &MaterialLibrary,
+    usage: TextureUsage,
+) -> Result<Arc<dyn Texture>, Box<dyn Error>> {
+    let image_key = match tex_def {
+        TextureDef::Image { .. } => Some((serde_json::to_string(tex_def)?, usage)),
+        _ => None,
+    };
+    if let Some(key) = &image_key {
+        if let Some(texture) = library.images.borrow().get(key) {
+            return Ok(texture.clone());
+        }
+    }
+    let texture: Arc<dyn Texture> = match tex_def {
+        TextureDef::SolidColor { color } => Arc::new(SolidColor::new(color.rgb())),
+        TextureDef::Checker { scale, even, odd } => Arc::new(CheckerTexture::new(
+            *scale,
+            library.texture_as(even, usage)?,
+            library.texture_as(odd, usage)?,
+        )),
+        TextureDef::Image {
+            path, alpha: true, ..
+        } => library.image(
+            path,
+            MipmappedTexture::alpha,
+            MipmappedTexture::alpha_from_bytes,
+        )?,
+        TextureDef::Image {
+            path,
+            filter: TextureFilterDef::Nearest,
+            color_space,
+            ..
+        } => {
+            let color_space = color_space.unwrap_or(usage.color_space());
+            match library.in_memory(path) {
+                Some(bytes) => Arc::new(NearestTexture::new(MipmappedTexture::from_bytes(
+                    bytes,
+                    color_space,
+                )?)),
+                None => {
+                    // `ImageTexture` can't report a file it fails to read,
+                    // and hands out its code values undecoded.
+                    image::image_dimensions(path).map_err(|e| RenderError::image(path, e))?;
+                    let texture = Arc::new(ImageTexture::new(path));
+                    Arc::new(DecodedTexture::new(texture, color_space))
+                }
+            }
+        }
+        TextureDef::Image {
+            path,
+            filter: TextureFilterDef::Bilinear,
+            color_space,
+            ..
+        } => {
+            let color_space = color_space.unwrap_or(usage.color_space());
+            library.image(
+                path,
+                move |path| MipmappedTexture::new(path, color_space),
+                |bytes| MipmappedTexture::from_bytes(bytes, color_space),
+            )?
+        }
+        TextureDef::UvTransform {
+            texture,
+            scale,
+            offset,
+            rotation,
+        } => Arc::new(UvTransform {
+            scale: *scale,
+            offset: *offset,
+            rotation: rotation.to_radians(),
+            ..UvTransform::new(library.texture_as(texture, usage)?)
+        }),
+        TextureDef::LinearGradient {
+            start,
+            end,
+            from,
+            to,
+        } => Arc::new(GradientTexture::new(
+            library.texture_as(start, usage)?,
+            library.texture_as(end, usage)?,
+            GradientShape::Linear {
+                from: *from,
+                to: *to,
+            },
+        )),
+        TextureDef::RadialGradient {
+            start,
+            end,
+            center,
+            radius,
+        } => Arc::new(GradientTexture::new(
+            library.texture_as(start, usage)?,
+            library.texture_as(end, usage)?,
+            GradientShape::Radial {
+                center: *center,
+                radius: *radius,
+            },
+        )),
+        TextureDef::Marble {
+            base,
+            vein,
+            scale,
+            turbulence,
+        } => Arc::new(MarbleTexture {
+            scale: *scale,
+            turbulence: *turbulence,
+            ..MarbleTexture::new(
+                library.texture_as(base, usage)?,
+                library.texture_as(vein, usage)?,
+            )
+        }),
+        TextureDef::Wood {
+            light,
+            dark,
+            ring_spacing,
+            distortion,
+        } => Arc::new(WoodTexture {
+            distortion: *distortion,
+            ..WoodTexture::new(
+                library.texture_as(light, usage)?,
+                library.texture_as(dark, usage)?,
+                *ring_spacing,
+            )
+        }),
+        TextureDef::Bricks {
+            brick,
+            mortar,
+            size,
+            mortar_width,
+        } => Arc::new(BrickTexture::new(
+            library.texture_as(brick, usage)?,
+            library.texture_as(mortar, usage)?,
+            *size,
+            *mortar_width,
         )),
-        TextureDef::Image { path } => Arc::new(ImageTexture::new(path)),
+    };
+    if let Some(key) = image_key {
+        library.images.borrow_mut().insert(key, texture.clone());
     }
+    Ok(texture)
 }
@@ -0,0 +1,230 @@
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable, HittableList, AABB};
+use crate::interval::Interval;
+use crate::qbvh::Qbvh;
+use crate::ray::Ray;
+use glam::DVec3;
+
+// Spatial index over a scene's top-level objects. The integrator only sees
+// the `Hittable` an accelerator builds, so backends can be swapped by the
+// scene's `accelerator` setting, or compared in benchmarks, without
+// touching it.
+pub trait Accelerator: Hittable {
+    fn build(objects: HittableList) -> Self
+    where
+        Self: Sized;
+
+    // Takes `objects`, which replace the ones built over one for one and in
+    // the same order, after they moved. Backends that can keep their
+    // structure and only update its bounds do, which is much faster than a
+    // build.
+    fn refit(&mut self, objects: HittableList);
+}
+
+// Tests every object: no build cost, for a handful of objects and as a
+// baseline in benchmarks.
+impl Accelerator for HittableList {
+    fn build(objects: HittableList) -> Self {
+        objects
+    }
+
+    fn refit(&mut self, objects: HittableList) {
+        *self = objects;
+    }
+}
+
+impl Accelerator for BvhNode {
+    fn build(objects: HittableList) -> Self {
+        BvhNode::new(objects)
+    }
+
+    fn refit(&mut self, objects: HittableList) {
+        *self = BvhNode::new(objects);
+    }
+}
+
+impl Accelerator for Qbvh {
+    fn build(objects: HittableList) -> Self {
+        Qbvh::new(objects)
+    }
+
+    fn refit(&mut self, objects: HittableList) {
+        Qbvh::refit(self, objects);
+    }
+}
+
+// About this many cells per object, as in PBRT's grid.
+const CELLS_PER_OBJECT: f64 = 3.0;
+const MAX_RESOLUTION: f64 = 128.0;
+
+// Uniform grid of cells, each listing the objects whose boxes overlap it,
+// walked cell by cell along the ray. Quick to build and good for many
+// evenly spread objects of similar size; objects much larger than a cell
+// are listed in many cells and tested repeatedly.
+pub struct UniformGrid {
+    objects: HittableList,
+    unbounded: Vec<usize>,
+    bounds: AABB,
+    resolution: [usize; 3],
+    cell_size: DVec3,
+    // Indices into `objects` per cell, x varying fastest.
+    cells: Vec<Vec<usize>>,
+}
+
+impl UniformGrid {
+    pub fn new(objects: HittableList) -> Self {
+        let boxes: Vec<Option<AABB>> = objects.iter().map(|o| o.bounding_box()).collect();
+        let unbounded = (0..objects.len()).filter(|&i| boxes[i].is_none()).collect();
+        let Some(bounds) = boxes
+            .iter()
+            .flatten()
+            .copied()
+            .reduce(AABB::surrounding_box)
+        else {
+            return Self {
+                objects,
+                unbounded,
+                bounds: AABB::default(),
+                resolution: [0; 3],
+                cell_size: DVec3::ONE,
+                cells: Vec::new(),
+            };
+        };
+
+        let bounded = boxes.iter().flatten().count() as f64;
+        let extent = (bounds.max - bounds.min).max(DVec3::splat(1e-9));
+        let per_unit = (CELLS_PER_OBJECT * bounded).cbrt() / extent.max_element();
+        let resolution = [0, 1, 2]
+            .map(|axis| (extent[axis] * per_unit).round().clamp(1.0, MAX_RESOLUTION) as usize);
+        let cells = DVec3::new(
+            resolution[0] as f64,
+            resolution[1] as f64,
+            resolution[2] as f64,
+        );
+        let mut grid = Self {
+            objects,
+            unbounded,
+            bounds: AABB::new(bounds.min, bounds.min + extent),
+            resolution,
+            cell_size: extent / cells,
+            cells: vec![Vec::new(); resolution.iter().product()],
+        };
+        for (index, object_box) in boxes.iter().enumerate() {
+            let Some(object_box) = object_box else {
+                continue;
+            };
+            let (lo, hi) = (grid.cell_of(object_box.min), grid.cell_of(object_box.max));
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        let cell = grid.cell_index([x, y, z]);
+                        grid.cells[cell].push(index);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    fn cell_of(&self, point: DVec3) -> [usize; 3] {
+        let cell = ((point - self.bounds.min) / self.cell_size).floor();
+        [0, 1, 2].map(|axis| (cell[axis].max(0.0) as usize).min(self.resolution[axis] - 1))
+    }
+
+    fn cell_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+
+    // Interval over which `ray` is inside the grid's bounds.
+    fn clip(&self, ray: &Ray, interval: Interval) -> Option<(f64, f64)> {
+        let mut t_min = interval.min;
+        let mut t_max = interval.max;
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction[axis];
+            let t0 = (self.bounds.min[axis] - ray.origin[axis]) * inv_d;
+            let t1 = (self.bounds.max[axis] - ray.origin[axis]) * inv_d;
+            t_min = t0.min(t1).max(t_min);
+            t_max = t0.max(t1).min(t_max);
+        }
+        (t_min <= t_max).then_some((t_min, t_max))
+    }
+}
+
+impl Hittable for UniformGrid {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let mut closest = interval.max;
+        let mut result = None;
+        for &index in &self.unbounded {
+            if let Some(rec) = self.objects[index].hit(ray, interval.with_max(closest)) {
+                closest = rec.t;
+                result = Some(rec);
+            }
+        }
+        if self.cells.is_empty() {
+            return result;
+        }
+        let Some((t_enter, t_exit)) = self.clip(ray, interval.with_max(closest)) else {
+            return result;
+        };
+
+        // Walk the cells in order along the ray (Amanatides and Woo),
+        // tracking the parameter at which it crosses into the next cell
+        // along each axis.
+        let mut cell = self.cell_of(ray.at(t_enter));
+        let mut next = [f64::INFINITY; 3];
+        let mut delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            let d = ray.direction[axis];
+            if d == 0.0 {
+                continue;
+            }
+            let edge = cell[axis] + (d > 0.0) as usize;
+            let plane = self.bounds.min[axis] + edge as f64 * self.cell_size[axis];
+            next[axis] = (plane - ray.origin[axis]) / d;
+            delta[axis] = self.cell_size[axis] / d.abs();
+        }
+        loop {
+            for &index in &self.cells[self.cell_index(cell)] {
+                if let Some(rec) = self.objects[index].hit(ray, interval.with_max(closest)) {
+                    closest = rec.t;
+                    result = Some(rec);
+                }
+            }
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            // Nothing in later cells can be nearer than a hit in this one.
+            if closest <= next[axis] || next[axis] > t_exit {
+                return result;
+            }
+            if ray.direction[axis] > 0.0 {
+                cell[axis] += 1;
+                if cell[axis] == self.resolution[axis] {
+                    return result;
+                }
+            } else {
+                if cell[axis] == 0 {
+                    return result;
+                }
+                cell[axis] -= 1;
+            }
+            next[axis] += delta[axis];
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        (self.unbounded.is_empty() && !self.cells.is_empty()).then_some(self.bounds)
+    }
+}
+
+impl Accelerator for UniformGrid {
+    fn build(objects: HittableList) -> Self {
+        UniformGrid::new(objects)
+    }
+
+    fn refit(&mut self, objects: HittableList) {
+        *self = UniformGrid::new(objects);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::material::{sample_ggx, smith_g1};
+use glam::DVec3;
+use std::f64::consts::PI;
+use std::sync::{Arc, OnceLock};
+
+// Entries along each axis of the table.
+const SIZE: usize = 32;
+// Stratified samples along each axis of the hemisphere per entry.
+const SAMPLES: usize = 32;
+
+// Directional albedo of a GGX reflection lobe with a Fresnel term of 1,
+// tabulated by the cosine of the incoming direction and by roughness
+// (alpha), and its cosine-weighted average over the hemisphere. A single
+// GGX lobe only accounts for light that leaves the microsurface after one
+// bounce, so rough metals come out darker than they should; the tables
+// give Kulla and Conty's lobe for the rest, which `multiple_scattering`
+// adds back.
+pub struct AlbedoLut {
+    // Rows of cosines, one per roughness.
+    albedo: Vec<f64>,
+    average: Vec<f64>,
+}
+
+impl AlbedoLut {
+    // Integrates the tables, with entries at evenly spaced cosines and
+    // roughnesses from 0 to 1, by sampling the GGX distribution.
+    pub fn bake() -> Self {
+        let mut albedo = Vec::with_capacity(SIZE * SIZE);
+        let mut average = Vec::with_capacity(SIZE);
+        for j in 0..SIZE {
+            let alpha = (j as f64 / (SIZE - 1) as f64).max(1e-3);
+            let row: Vec<f64> = (0..SIZE)
+                .map(|i| single_scattering(i as f64 / (SIZE - 1) as f64, alpha))
+                .collect();
+            // 2 times the integral of E(mu) mu over the cosines, by the
+            // trapezium rule.
+            let step = 1.0 / (SIZE - 1) as f64;
+            let integral: f64 = row
+                .windows(2)
+                .enumerate()
+                .map(|(i, pair)| {
+                    let (mu0, mu1) = (i as f64 * step, (i + 1) as f64 * step);
+                    0.5 * step * (pair[0] * mu0 + pair[1] * mu1)
+                })
+                .sum();
+            average.push((2.0 * integral).min(1.0));
+            albedo.extend(row);
+        }
+        Self { albedo, average }
+    }
+
+    // The tables baked once per process and shared by every scene.
+    pub fn shared() -> Arc<Self> {
+        static SHARED: OnceLock<Arc<AlbedoLut>> = OnceLock::new();
+        SHARED.get_or_init(|| Arc::new(Self::bake())).clone()
+    }
+
+    // Fraction of light arriving at `cosine` to the normal that a single
+    // bounce reflects.
+    pub fn albedo(&self, cosine: f64, alpha: f64) -> f64 {
+        let (j, t) = grid_position(alpha);
+        let row = |j: usize| {
+            let (i, s) = grid_position(cosine);
+            let at = |i: usize| self.albedo[j * SIZE + i];
+            at(i) + s * (at((i + 1).min(SIZE - 1)) - at(i))
+        };
+        row(j) + t * (row((j + 1).min(SIZE - 1)) - row(j))
+    }
+
+    // `albedo` averaged over the hemisphere, weighted by cosine.
+    pub fn average(&self, alpha: f64) -> f64 {
+        let (j, t) = grid_position(alpha);
+        let at = |j: usize| self.average[j];
+        at(j) + t * (at((j + 1).min(SIZE - 1)) - at(j))
+    }
+
+    // BSDF of the light that bounces more than once between microfacets,
+    // for directions at `cos_i` and `cos_o` to the normal and a Fresnel
+    // reflectance of `f0` at normal incidence. It reflects the energy that
+    // the single-bounce lobe loses, tinted by the Fresnel term averaged
+    // over the hemisphere (for Schlick's approximation) once per bounce.
+    pub fn multiple_scattering(&self, cos_i: f64, cos_o: f64, alpha: f64, f0: DVec3) -> DVec3 {
+        let average = self.average(alpha);
+        if average >= 1.0 - 1e-6 {
+            return DVec3::ZERO;
+        }
+        let fresnel = f0 + (DVec3::ONE - f0) / 21.0;
+        let tint = fresnel * fresnel * average / (DVec3::ONE - fresnel * (1.0 - average));
+        let missing = (1.0 - self.albedo(cos_i, alpha)) * (1.0 - self.albedo(cos_o, alpha));
+        tint * missing / (PI * (1.0 - average))
+    }
+}
+
+// The entry at or below `x` in [0, 1] and the fraction of the way to the
+// next.
+fn grid_position(x: f64) -> (usize, f64) {
+    let scaled = x.clamp(0.0, 1.0) * (SIZE - 1) as f64;
+    let i = (scaled as usize).min(SIZE - 2);
+    (i, scaled - i as f64)
+}
+
+// Directional albedo for a view direction at `cosine` to the normal. With
+// microfacet normals drawn from D(m)(m.n), the weight of each reflection is
+// G |v.m| / (v.n m.n).
+fn single_scattering(cosine: f64, alpha: f64) -> f64 {
+    let cosine = cosine.max(1e-3);
+    let view = DVec3::new((1.0 - cosine * cosine).sqrt(), 0.0, cosine);
+    let mut total = 0.0;
+    for a in 0..SAMPLES {
+        for b in 0..SAMPLES {
+            let sample = (
+                (a as f64 + 0.5) / SAMPLES as f64,
+                (b as f64 + 0.5) / SAMPLES as f64,
+            );
+            let m = sample_ggx(DVec3::Z, alpha, sample);
+            let cos_vm = view.dot(m);
+            let light = 2.0 * cos_vm * m - view;
+            if cos_vm <= 0.0 || light.z <= 0.0 {
+                continue;
+            }
+            let g = smith_g1(cosine, alpha) * smith_g1(light.z, alpha);
+            total += g * cos_vm / (cosine * m.z);
+        }
+    }
+    (total / (SAMPLES * SAMPLES) as f64).min(1.0)
+}
@@ -0,0 +1,66 @@
+use crate::output::{self, RenderMetadata};
+use crate::renderer::{self, Renderer};
+use crate::scene::Scene;
+use crate::temporal::TemporalAccumulator;
+use std::error::Error;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+// Renders the frames in `frame_range` of an animation of `frames` frames
+// spread evenly over the scene's [0, 1] animation time, so a long sequence
+// can be split between machines. The run of `#` in `output` is replaced by
+// the zero-padded frame number. `on_frame` is called with each frame and
+// the file it was saved to.
+pub fn render_animation(
+    path: &str,
+    frames: u32,
+    frame_range: Range<u32>,
+    output: &str,
+    mut on_frame: impl FnMut(u32, &Path),
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let digits = output.matches('#').count();
+    let placeholder = "#".repeat(digits);
+    if digits == 0 || !output.contains(&placeholder) {
+        return Err("output needs one run of '#' for the frame number".into());
+    }
+    if frame_range.end > frames {
+        return Err(format!("frame range ends after the last of {frames} frames").into());
+    }
+    let scene = std::fs::read(path)?;
+    let mut temporal: Option<TemporalAccumulator> = None;
+    let mut written = Vec::new();
+    for frame in frame_range {
+        let (config, camera, world, lights) = Scene::from_file_at_frame(path, frame, frames)?;
+        let mut settings = config.render.for_frame(frame);
+        if temporal.is_none() {
+            temporal = settings.temporal.map(TemporalAccumulator::new);
+        }
+        // Reprojection needs the depth of every frame.
+        settings.aovs.depth |= temporal.is_some();
+        let mut renderer = Renderer::new(world, config.environment()?).with_lights(lights);
+        if let Some(sample_map) = config.sample_map()? {
+            renderer = renderer.with_sample_map(Arc::new(sample_map));
+        }
+        let start = Instant::now();
+        let image = match &mut temporal {
+            Some(temporal) => {
+                let mut image =
+                    temporal.accumulate(&camera, &renderer.render_passes(&camera, &settings));
+                renderer::finish(&mut image, &settings);
+                image
+            }
+            None => renderer.render(&camera, &settings),
+        };
+        let metadata = RenderMetadata::new(&settings, start.elapsed())
+            .with_scene(&scene)
+            .with_camera(&camera);
+        let frame_path =
+            PathBuf::from(output.replacen(&placeholder, &format!("{frame:0digits$}"), 1));
+        output::save(&image, &frame_path, &config.output, Some(&metadata))?;
+        on_frame(frame, &frame_path);
+        written.push(frame_path);
+    }
+    Ok(written)
+}
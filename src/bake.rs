@@ -0,0 +1,279 @@
+use crate::hittable::{Hittable, AABB, DEFAULT_EPSILON};
+use crate::interval::Interval;
+use crate::lights::cosine_direction;
+use crate::material::Lambertian;
+use crate::objects::obj;
+use crate::objects::triangle::Triangle;
+use crate::ray::{Ray, RayKind};
+use crate::renderer::{ImageBuffer, RenderSettings, Renderer};
+use crate::sampler::{IndependentSampler, Sampler};
+use crate::scene::Scene;
+use crate::texture::SolidColor;
+use glam::{DVec2, DVec3};
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BakeMode {
+    // White where the surface is open to the sky, darker where nearby
+    // geometry hides it, as `Integrator::AmbientOcclusion` shades it: only
+    // surfaces within `distance` world units block, or any without it.
+    AmbientOcclusion { distance: Option<f64> },
+    // Light arriving at the surface, cosine weighted, from everything the
+    // scene lights it with: the environment, emitters, analytic lights and
+    // light bouncing off other surfaces. A Lambertian surface of albedo
+    // `a` reflects `a / π` times it.
+    Irradiance,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BakeOptions {
+    // Size of the texture atlas the mesh's UVs are laid out on.
+    pub width: u32,
+    pub height: u32,
+    pub mode: BakeMode,
+    // Hemisphere rays per texel.
+    pub samples: u32,
+    // Texels each UV island is grown by past its edges, so that filtering
+    // and mipmapping near seams don't pull in the empty space between
+    // islands.
+    pub padding: u32,
+    pub seed: u64,
+}
+
+impl Default for BakeOptions {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            mode: BakeMode::AmbientOcclusion { distance: None },
+            samples: 64,
+            padding: 4,
+            seed: 0,
+        }
+    }
+}
+
+// A texel centre covered by the mesh, and the surface under it.
+struct Texel {
+    index: usize,
+    point: DVec3,
+    normal: DVec3,
+}
+
+// Bakes the lighting of `triangles`, placed in the world of `renderer`,
+// into a texture laid out by their UVs: each texel the UVs cover traces
+// rays over the hemisphere above the surface point it maps to. Texels
+// outside the islands and their padding are black, with an alpha of 0.
+// `settings` supplies the depth and other path tracing settings for
+// irradiance. The triangles should be in the world too, for the mesh to
+// shadow itself.
+pub fn bake(
+    triangles: &[Triangle],
+    renderer: &Renderer,
+    settings: &RenderSettings,
+    options: &BakeOptions,
+) -> ImageBuffer {
+    let (width, height) = (options.width.max(1), options.height.max(1));
+    let bounds = triangles
+        .iter()
+        .filter_map(|triangle| triangle.bounding_box())
+        .reduce(AABB::surrounding_box);
+    let epsilon = bounds.map_or(DEFAULT_EPSILON, |b| b.epsilon());
+    let texels = rasterize(triangles, width, height);
+
+    let texel_value = |texel: &Texel| {
+        let mut sampler = IndependentSampler::new(options.seed);
+        let (x, y) = (
+            (texel.index % width as usize) as u32,
+            (texel.index / width as usize) as u32,
+        );
+        let origin = texel.point + epsilon * texel.normal;
+        let mut total = DVec3::ZERO;
+        for sample in 0..options.samples {
+            sampler.start_pixel(x, y, sample);
+            let direction = cosine_direction(texel.normal, sampler.next_2d());
+            let ray = Ray::new(origin, direction).with_kind(RayKind::Indirect);
+            total += match options.mode {
+                BakeMode::AmbientOcclusion { distance } => {
+                    let interval = Interval::new(epsilon, distance.unwrap_or(f64::INFINITY));
+                    if renderer.world.hit_any(&ray, interval) {
+                        DVec3::ZERO
+                    } else {
+                        DVec3::ONE
+                    }
+                }
+                // Cosine-weighted directions make each ray's estimate π L.
+                BakeMode::Irradiance => {
+                    PI * renderer.ray_color(&ray, settings, &mut sampler)
+                        + analytic_irradiance(renderer, origin, texel.normal, epsilon, &mut sampler)
+                }
+            };
+        }
+        let value = total / options.samples.max(1) as f64;
+        if value.is_finite() {
+            value
+        } else {
+            DVec3::ZERO
+        }
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let values: Vec<DVec3> = texels.par_iter().map(texel_value).collect();
+    #[cfg(target_arch = "wasm32")]
+    let values: Vec<DVec3> = texels.iter().map(texel_value).collect();
+
+    let mut image = ImageBuffer::new(width, height);
+    let mut coverage = vec![0.0; (width * height) as usize];
+    for (texel, value) in texels.iter().zip(values) {
+        image.pixels[texel.index] = value;
+        coverage[texel.index] = 1.0;
+    }
+    for _ in 0..options.padding {
+        dilate(&mut image, &mut coverage);
+    }
+    image.alpha = Some(coverage);
+    image
+}
+
+// Bakes `mesh`, an OBJ, STL or PLY file read as a scene's `mesh` objects
+// read it, in the world of the scene at `scene`, which should include it.
+pub fn bake_scene(
+    scene: &str,
+    mesh: &str,
+    options: &BakeOptions,
+) -> Result<ImageBuffer, Box<dyn Error>> {
+    let (config, _, world, lights) = Scene::from_file(scene)?;
+    // Only the shape is baked, so the material is never seen.
+    let texture = Arc::new(SolidColor::new(DVec3::splat(0.5)));
+    let import = config.mesh_options(mesh)?;
+    let (triangles, _) = obj::load_with(mesh, Arc::new(Lambertian::new(texture)), &import)?;
+    if triangles.is_empty() {
+        return Err(format!("'{mesh}' has no triangles to bake").into());
+    }
+    let renderer = Renderer::new(world, config.environment()?).with_lights(lights);
+    Ok(bake(&triangles, &renderer, &config.render, options))
+}
+
+// The texels whose centres fall inside a triangle in UV space. The UV
+// square maps onto the whole atlas, with v running up it; where triangles
+// overlap in UV space, the last one wins.
+fn rasterize(triangles: &[Triangle], width: u32, height: u32) -> Vec<Texel> {
+    let mut owner = vec![usize::MAX; (width * height) as usize];
+    let mut texels = Vec::new();
+    let size = DVec2::new(width as f64, height as f64);
+    for triangle in triangles {
+        let corners = triangle.uvs.map(|uv| DVec2::new(uv.x, 1.0 - uv.y) * size);
+        let area = (corners[1] - corners[0]).perp_dot(corners[2] - corners[0]);
+        if area.abs() < 1e-12 {
+            continue;
+        }
+        let vertices = triangle.vertices();
+        let geometric = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+        let lower = corners[0]
+            .min(corners[1])
+            .min(corners[2])
+            .floor()
+            .max(DVec2::ZERO);
+        let upper = corners[0].max(corners[1]).max(corners[2]).ceil().min(size);
+        for y in lower.y as u32..upper.y as u32 {
+            for x in lower.x as u32..upper.x as u32 {
+                let centre = DVec2::new(x as f64 + 0.5, y as f64 + 0.5);
+                let b1 = (centre - corners[0]).perp_dot(corners[2] - corners[0]) / area;
+                let b2 = (corners[1] - corners[0]).perp_dot(centre - corners[0]) / area;
+                let b0 = 1.0 - b1 - b2;
+                if b0 < -1e-9 || b1 < -1e-9 || b2 < -1e-9 {
+                    continue;
+                }
+                let weights = [b0, b1, b2];
+                let point = (0..3).map(|i| weights[i] * vertices[i]).sum::<DVec3>();
+                let normal = match triangle.normals() {
+                    Some(normals) => (0..3).map(|i| weights[i] * normals[i]).sum::<DVec3>(),
+                    None => geometric,
+                };
+                let Some(normal) = normal.try_normalize() else {
+                    continue;
+                };
+                let index = (y * width + x) as usize;
+                let texel = Texel {
+                    index,
+                    point,
+                    normal,
+                };
+                match owner[index] {
+                    usize::MAX => {
+                        owner[index] = texels.len();
+                        texels.push(texel);
+                    }
+                    previous => texels[previous] = texel,
+                }
+            }
+        }
+    }
+    texels
+}
+
+// Irradiance at `point` from the analytic lights, which hemisphere rays
+// can never find, with a shadow ray to each. Lights with a radius are
+// sampled once per call, so their soft shadows average out over the
+// texel's samples.
+fn analytic_irradiance(
+    renderer: &Renderer,
+    point: DVec3,
+    normal: DVec3,
+    epsilon: f64,
+    sampler: &mut dyn Sampler,
+) -> DVec3 {
+    let mut irradiance = DVec3::ZERO;
+    for light in renderer.lights.analytic() {
+        let Some(illumination) = light.illuminate(point, sampler) else {
+            continue;
+        };
+        let cosine = normal.dot(illumination.direction);
+        if cosine <= 0.0 {
+            continue;
+        }
+        let shadow = Ray::new(point, illumination.direction).with_kind(RayKind::Shadow);
+        let reach = Interval::new(epsilon, illumination.distance * (1.0 - 1e-9));
+        if !renderer.world.hit_any(&shadow, reach) {
+            irradiance += cosine * illumination.radiance;
+        }
+    }
+    irradiance
+}
+
+// Grows the covered texels by one, giving each uncovered texel next to any
+// the mean of its covered neighbours.
+fn dilate(image: &mut ImageBuffer, coverage: &mut [f64]) {
+    let (width, height) = (image.width as i64, image.height as i64);
+    let mut grown = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let index = (y * width + x) as usize;
+            if coverage[index] > 0.0 {
+                continue;
+            }
+            let mut sum = DVec3::ZERO;
+            let mut count = 0;
+            for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                let (nx, ny) = (x + dx, y + dy);
+                if (0..width).contains(&nx) && (0..height).contains(&ny) {
+                    let neighbour = (ny * width + nx) as usize;
+                    if coverage[neighbour] > 0.0 {
+                        sum += image.pixels[neighbour];
+                        count += 1;
+                    }
+                }
+            }
+            if count > 0 {
+                grown.push((index, sum / count as f64));
+            }
+        }
+    }
+    for (index, value) in grown {
+        image.pixels[index] = value;
+        coverage[index] = 1.0;
+    }
+}
@@ -0,0 +1,705 @@
+use raytracer::animation::render_animation;
+use raytracer::bake::{bake_scene, BakeMode, BakeOptions};
+use raytracer::camera::Camera;
+use raytracer::checkpoint::RenderCheckpoint;
+use raytracer::contact_sheet::{
+    render_contact_sheet, render_sweep, ContactSheetOptions, SweepAxis,
+};
+use raytracer::distributed;
+use raytracer::environment::SkyGradient;
+use raytracer::generators::{self, CityParams, SweepParams, TerrainParams};
+#[cfg(feature = "gpu")]
+use raytracer::gpu::GpuDevices;
+use raytracer::metrics::{self, RenderProgress};
+use raytracer::output::{self, OutputOptions, RenderMetadata, ToneMapper};
+use raytracer::renderer::{self, ImageBuffer, RenderPasses, RenderSettings, Renderer};
+use raytracer::scene::{Scene, SceneConfig};
+use raytracer::stats::RenderStats;
+use std::error::Error;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage:
+  raytracer render <scene.json> (<output> | -o <output>) [--spp <samples>] [--threads <count>]
+                   [--watch] [--metrics <address>] [--workers <host[:port]>,...]
+                   [--gpu <default|all>[+cpu]]      (with the `gpu` feature)
+  raytracer animate <scene.json> <frames> <output-####.png> [first-last]
+  raytracer preview <scene.json>            (with the `preview` feature)
+  raytracer worker <scene.json> [address]
+  raytracer inspect <scene.json>
+  raytracer sheet <directory> <output.png> [--size <pixels>] [--spp <samples>] [--columns <count>]
+  raytracer sweep <scene.json> <output.png> --vary <field>=<value>,... [--vary <field>=<value>,...]
+                  [--size <pixels>] [--spp <samples>]
+  raytracer bounds <scene.json> <out.obj> [depth]
+  raytracer bake <scene.json> <mesh.obj> <output> [--size <pixels>] [--spp <samples>]
+                 [--irradiance] [--distance <units>]
+  raytracer generate (city | terrain | sweep) <output> [--seed <seed>] [--size <width>]
+                     [--spp <samples>]";
+
+// BVH levels exported by `bounds` when no depth is given.
+const DEFAULT_BOUNDS_DEPTH: usize = 3;
+
+// How often `render --watch` checks the scene file for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+// How often local renders save their checkpoint while they run, for when
+// the process is killed without warning.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(300);
+
+// The local render in progress, saved if the process is interrupted.
+static UNFINISHED: Mutex<Option<Unfinished>> = Mutex::new(None);
+
+struct Unfinished {
+    checkpoint: Arc<RenderCheckpoint>,
+    checkpoint_path: PathBuf,
+    output: PathBuf,
+    settings: RenderSettings,
+    options: OutputOptions,
+}
+
+#[derive(Default)]
+struct RenderArgs {
+    path: String,
+    output: String,
+    // Overrides the scene's samples per pixel.
+    samples: Option<u32>,
+    threads: Option<usize>,
+    watch: bool,
+    metrics_address: Option<String>,
+    // Render hosts to farm tiles out to instead of rendering locally.
+    workers: Vec<String>,
+    // The GPUs to render on instead, and whether the CPU renderer takes
+    // tiles alongside them.
+    #[cfg(feature = "gpu")]
+    gpu: Option<(GpuDevices, bool)>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, rest @ ..] if command == "render" => match parse_render_args(rest) {
+            Some(args) => render(&args),
+            None => usage(),
+        },
+        [command, path, frames, output] if command == "animate" => match frames.parse() {
+            Ok(frames) if frames > 0 => animate(path, frames, 0..frames, output),
+            _ => usage(),
+        },
+        [command, path, frames, output, range] if command == "animate" => {
+            match (frames.parse(), parse_frame_range(range)) {
+                (Ok(frames), Some(range)) if frames > 0 => animate(path, frames, range, output),
+                _ => usage(),
+            }
+        }
+        #[cfg(feature = "preview")]
+        [command, path] if command == "preview" => preview(path),
+        [command, path] if command == "worker" => {
+            worker(path, &format!("0.0.0.0:{}", distributed::DEFAULT_PORT))
+        }
+        [command, path, address] if command == "worker" => worker(path, address),
+        [command, path] if command == "inspect" => inspect(path),
+        [command, rest @ ..] if command == "sheet" => match parse_sheet_args(rest) {
+            Some((dir, output, options)) => sheet(&dir, &output, &options),
+            None => usage(),
+        },
+        [command, rest @ ..] if command == "sweep" => match parse_sweep_args(rest) {
+            Some((path, output, axes, options)) => sweep(&path, &output, &axes, &options),
+            None => usage(),
+        },
+        [command, path, output] if command == "bounds" => {
+            bounds(path, output, DEFAULT_BOUNDS_DEPTH)
+        }
+        [command, path, output, depth] if command == "bounds" => match depth.parse() {
+            Ok(depth) => bounds(path, output, depth),
+            Err(_) => usage(),
+        },
+        [command, rest @ ..] if command == "bake" => match parse_bake_args(rest) {
+            Some((path, mesh, output, options)) => bake(&path, &mesh, &output, &options),
+            None => usage(),
+        },
+        [command, rest @ ..] if command == "generate" => match parse_generate_args(rest) {
+            Some((generator, output, seed, settings)) => {
+                generate(&generator, &output, seed, &settings)
+            }
+            None => usage(),
+        },
+        _ => usage(),
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::from(2)
+}
+
+// Flags may come before or after the scene; the output is either the
+// second positional argument or `-o`.
+fn parse_render_args(args: &[String]) -> Option<RenderArgs> {
+    let mut parsed = RenderArgs::default();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => parsed.output = args.next()?.clone(),
+            "--spp" => parsed.samples = Some(args.next()?.parse().ok().filter(|&n| n > 0)?),
+            "--threads" => parsed.threads = Some(args.next()?.parse().ok().filter(|&n| n > 0)?),
+            "--watch" => parsed.watch = true,
+            "--metrics" => parsed.metrics_address = Some(args.next()?.clone()),
+            "--workers" => {
+                parsed.workers = args.next()?.split(',').map(str::to_string).collect();
+            }
+            #[cfg(feature = "gpu")]
+            "--gpu" => parsed.gpu = Some(parse_gpu_devices(args.next()?)?),
+            flag if flag.starts_with('-') => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match (positional.as_slice(), parsed.output.is_empty()) {
+        ([path], false) => parsed.path = path.clone(),
+        ([path, output], true) => {
+            parsed.path = path.clone();
+            parsed.output = output.clone();
+        }
+        _ => return None,
+    }
+    // GPU renders are local.
+    #[cfg(feature = "gpu")]
+    if parsed.gpu.is_some() && !parsed.workers.is_empty() {
+        return None;
+    }
+    Some(parsed)
+}
+
+// `default` or `all`, optionally followed by `+cpu`.
+#[cfg(feature = "gpu")]
+fn parse_gpu_devices(devices: &str) -> Option<(GpuDevices, bool)> {
+    let (devices, cpu) = match devices.strip_suffix("+cpu") {
+        Some(devices) => (devices, true),
+        None => (devices, false),
+    };
+    let devices = match devices {
+        "default" => GpuDevices::Default,
+        "all" => GpuDevices::AllGpus,
+        _ => return None,
+    };
+    Some((devices, cpu))
+}
+
+fn render(args: &RenderArgs) -> ExitCode {
+    match try_render(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// With a metrics address, progress is served over HTTP for the length of
+// the render so farm monitoring can scrape it. In watch mode the scene is
+// rendered again whenever its file changes, until interrupted; a scene
+// that fails to load is reported and the previous image is kept.
+fn try_render(args: &RenderArgs) -> Result<(), Box<dyn Error>> {
+    save_on_interrupt()?;
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+    let progress = match &args.metrics_address {
+        Some(address) => {
+            let progress = Arc::new(RenderProgress::new());
+            let bound = metrics::serve(address.as_str(), progress.clone())?;
+            eprintln!("serving metrics on http://{bound}/metrics");
+            Some(progress)
+        }
+        None => None,
+    };
+    if !args.watch {
+        return render_once(args, progress);
+    }
+
+    let mut rendered = None;
+    loop {
+        let modified = std::fs::metadata(&args.path)?.modified()?;
+        if rendered != Some(modified) {
+            rendered = Some(modified);
+            match render_once(args, progress.clone()) {
+                Ok(()) => eprintln!("rendered {}", args.output),
+                Err(e) => eprintln!("error: {e}"),
+            }
+            eprintln!("watching {} for changes", args.path);
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+fn render_once(
+    args: &RenderArgs,
+    progress: Option<Arc<RenderProgress>>,
+) -> Result<(), Box<dyn Error>> {
+    let (mut config, camera, world, lights) = Scene::from_file(&args.path)?;
+    if let Some(samples) = args.samples {
+        config.render.samples_per_pixel = samples;
+    }
+    let mut renderer = Renderer::new(world, config.environment()?).with_lights(lights);
+    if let Some(progress) = progress {
+        renderer = renderer.with_progress(progress);
+    }
+    if let Some(sample_map) = config.sample_map()? {
+        renderer = renderer.with_sample_map(Arc::new(sample_map));
+    }
+    let scene = std::fs::read(&args.path)?;
+    let start = Instant::now();
+    #[cfg(feature = "gpu")]
+    if let Some((devices, cpu)) = args.gpu {
+        let cpu = cpu.then_some(&renderer);
+        let gpu_scene = config.to_gpu_scene()?;
+        let image = gpu_scene.render_on(&camera, &config.render, devices, cpu)?;
+        return save_render(args, &config, &camera, &scene, start, image);
+    }
+    let passes = if args.workers.is_empty() {
+        // Edited meshes and textures make checkpointed tiles stale too.
+        let assets = Scene::inspect(&args.path)?.assets;
+        render_resumable(renderer, &camera, &config, &scene, &assets, &args.output)?
+    } else {
+        let report = |message: &str| eprintln!("warning: {message}");
+        distributed::render(
+            &scene,
+            &config.render,
+            &args.workers,
+            renderer.progress.as_deref(),
+            report,
+        )?
+    };
+    if RenderStats::enabled() {
+        let stats = passes.stats;
+        eprintln!(
+            "rays: {}, shadow rays: {}, BVH node tests: {}, triangle tests: {}",
+            stats.rays, stats.shadow_rays, stats.node_tests, stats.triangle_tests
+        );
+    }
+    save_render(args, &config, &camera, &scene, start, passes.beauty)
+}
+
+// Finishes the image rendered of `scene` since `start` and saves it to
+// the output.
+fn save_render(
+    args: &RenderArgs,
+    config: &SceneConfig,
+    camera: &Camera,
+    scene: &[u8],
+    start: Instant,
+    mut image: ImageBuffer,
+) -> Result<(), Box<dyn Error>> {
+    let metadata = RenderMetadata::new(&config.render, start.elapsed())
+        .with_scene(scene)
+        .with_camera(camera);
+    renderer::finish(&mut image, &config.render);
+    output::save(
+        &image,
+        Path::new(&args.output),
+        &config.output,
+        Some(&metadata),
+    )
+}
+
+// Renders locally, picking up where an interrupted render of the same
+// scene and settings left off. Finished tiles are checkpointed beside the
+// output; see `save_on_interrupt`.
+fn render_resumable(
+    renderer: Renderer,
+    camera: &Camera,
+    config: &SceneConfig,
+    scene: &[u8],
+    assets: &[String],
+    output: &str,
+) -> Result<RenderPasses, Box<dyn Error>> {
+    let settings = &config.render;
+    let mut checkpoint_path = PathBuf::from(output);
+    checkpoint_path.as_mut_os_string().push(".checkpoint");
+    let checkpoint = Arc::new(RenderCheckpoint::load(
+        &checkpoint_path,
+        scene,
+        assets,
+        settings,
+    )?);
+    if checkpoint.tiles_done() > 0 {
+        eprintln!(
+            "resuming from {}: {} tiles already rendered",
+            checkpoint_path.display(),
+            checkpoint.tiles_done()
+        );
+    }
+    let renderer = renderer.with_checkpoint(checkpoint.clone());
+    *unfinished() = Some(Unfinished {
+        checkpoint: checkpoint.clone(),
+        checkpoint_path: checkpoint_path.clone(),
+        output: PathBuf::from(output),
+        settings: *settings,
+        options: config.output.clone(),
+    });
+    let passes = std::thread::scope(|scope| {
+        let (done, finished) = mpsc::channel::<()>();
+        let (checkpoint, path) = (&checkpoint, &checkpoint_path);
+        scope.spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(CHECKPOINT_INTERVAL) {
+                if let Err(e) = checkpoint.save(path) {
+                    eprintln!("warning: couldn't save checkpoint: {e}");
+                }
+            }
+        });
+        let passes = renderer.render_passes(camera, settings);
+        drop(done);
+        passes
+    });
+    *unfinished() = None;
+    match std::fs::remove_file(&checkpoint_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(passes),
+    }
+}
+
+fn unfinished() -> std::sync::MutexGuard<'static, Option<Unfinished>> {
+    UNFINISHED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// On Ctrl-C, SIGTERM or a panic, saves the checkpoint of the local render
+// in progress and what it has rendered so far as the output image, with
+// unfinished tiles black, so interrupted renders leave something usable
+// behind. Rendering again resumes from the checkpoint.
+fn save_on_interrupt() -> Result<(), Box<dyn Error>> {
+    ctrlc::set_handler(|| {
+        save_unfinished();
+        std::process::exit(130);
+    })?;
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        save_unfinished();
+    }));
+    Ok(())
+}
+
+fn save_unfinished() {
+    // Taken, so that a second signal or a panic while saving doesn't save
+    // again.
+    let Some(unfinished) = unfinished().take() else {
+        return;
+    };
+    eprintln!(
+        "interrupted; saving {} and {}",
+        unfinished.output.display(),
+        unfinished.checkpoint_path.display()
+    );
+    if let Err(e) = unfinished.checkpoint.save(&unfinished.checkpoint_path) {
+        eprintln!("error: couldn't save checkpoint: {e}");
+    }
+    let image = unfinished.checkpoint.passes(&unfinished.settings).beauty;
+    if let Err(e) = output::save(&image, &unfinished.output, &unfinished.options, None) {
+        eprintln!("error: couldn't save partial image: {e}");
+    }
+}
+
+fn animate(path: &str, frames: u32, range: Range<u32>, output: &str) -> ExitCode {
+    let progress = |frame: u32, frame_path: &Path| {
+        eprintln!("frame {}/{frames}: {}", frame + 1, frame_path.display())
+    };
+    match render_animation(path, frames, range, output, progress) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Parses an inclusive `first-last` range of frame numbers.
+fn parse_frame_range(range: &str) -> Option<Range<u32>> {
+    let (first, last) = range.split_once('-')?;
+    let (first, last): (u32, u32) = (first.parse().ok()?, last.parse().ok()?);
+    (first <= last).then_some(first..last + 1)
+}
+
+// Opens an interactive window on the scene; see `raytracer::preview`.
+#[cfg(feature = "preview")]
+fn preview(path: &str) -> ExitCode {
+    let textures = raytracer::mipmap::TextureWatch::new();
+    let result = Scene::from_file_watching(path, &textures)
+        .map_err(Into::into)
+        .and_then(|(config, camera, world, lights)| {
+            let renderer = Renderer::new(world, config.environment()?).with_lights(lights);
+            let (settings, output) = (&config.render, &config.output);
+            raytracer::preview::run(&renderer, &camera, settings, output, Some(&textures))
+        });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Renders tiles for `render --workers` on other machines until killed.
+fn worker(path: &str, address: &str) -> ExitCode {
+    match distributed::serve(address, path) {
+        Ok(bound) => {
+            eprintln!("rendering tiles of {path} on {bound}");
+            loop {
+                std::thread::park();
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Prints the scene summary; fails when assets are missing so scripts can
+// check a scene before queueing a long render.
+fn inspect(path: &str) -> ExitCode {
+    match Scene::inspect(path) {
+        Ok(inspection) => {
+            print!("{inspection}");
+            if inspection.missing_assets.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// The directory and output of `sheet`, then its flags in any order.
+fn parse_sheet_args(args: &[String]) -> Option<(String, String, ContactSheetOptions)> {
+    let mut options = ContactSheetOptions::default();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut count = || args.next()?.parse().ok().filter(|&n| n > 0);
+        match arg.as_str() {
+            "--size" => options.cell_size = count()?,
+            "--spp" => options.samples_per_pixel = count()?,
+            "--columns" => options.columns = count()?,
+            flag if flag.starts_with('-') => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match positional.as_slice() {
+        [dir, output] => Some((dir.clone(), output.clone(), options)),
+        _ => None,
+    }
+}
+
+// The scene and output of `sweep`, the fields it varies across and then
+// down the sheet, and its flags in any order.
+fn parse_sweep_args(
+    args: &[String],
+) -> Option<(String, String, Vec<SweepAxis>, ContactSheetOptions)> {
+    let mut options = ContactSheetOptions::default();
+    let mut axes = Vec::new();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--vary" => axes.push(parse_sweep_axis(args.next()?)?),
+            "--size" => options.cell_size = args.next()?.parse().ok().filter(|&n| n > 0)?,
+            "--spp" => options.samples_per_pixel = args.next()?.parse().ok().filter(|&n| n > 0)?,
+            flag if flag.starts_with('-') => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match positional.as_slice() {
+        [path, output] if (1..=2).contains(&axes.len()) => {
+            Some((path.clone(), output.clone(), axes, options))
+        }
+        _ => None,
+    }
+}
+
+// `field=a,b,c`, with each value read as JSON and otherwise as a string,
+// so that `material=gold,silver` needs no quotes.
+fn parse_sweep_axis(arg: &str) -> Option<SweepAxis> {
+    let (field, values) = arg.split_once('=')?;
+    let values = values
+        .split(',')
+        .map(|value| serde_json::from_str(value).unwrap_or_else(|_| value.into()))
+        .collect();
+    Some(SweepAxis {
+        field: field.to_string(),
+        values,
+    })
+}
+
+// Renders the scene at `path` with every combination of the values of
+// `axes`, the first across the sheet and the second down it, for tuning
+// materials and settings by eye.
+fn sweep(path: &str, output: &str, axes: &[SweepAxis], options: &ContactSheetOptions) -> ExitCode {
+    let result = render_sweep(path, &axes[0], axes.get(1), options)
+        .and_then(|image| output::save(&image, Path::new(output), &OutputOptions::default(), None));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Renders a thumbnail of every scene in `dir` onto one image and lists the
+// scenes in the order shown, for browsing scene and material libraries.
+fn sheet(dir: &str, output: &str, options: &ContactSheetOptions) -> ExitCode {
+    let skipped = |path: &Path, e: Box<dyn Error>| {
+        eprintln!("skipping {}: {e}", path.display());
+    };
+    let result = render_contact_sheet(Path::new(dir), options, skipped).and_then(|sheet| {
+        output::save(
+            &sheet.image,
+            Path::new(output),
+            &OutputOptions::default(),
+            None,
+        )?;
+        Ok(sheet.scenes)
+    });
+    match result {
+        Ok(scenes) => {
+            for (index, scene) in scenes.iter().enumerate() {
+                println!("{}: {}", index + 1, scene.display());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Writes object and BVH node boxes as an OBJ wireframe.
+fn bounds(path: &str, output: &str, depth: usize) -> ExitCode {
+    match Scene::export_bounds(path, depth, Path::new(output)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Ambient occlusion unless `--irradiance` is given; `--distance` limits
+// the occluders to those that near.
+fn parse_bake_args(args: &[String]) -> Option<(String, String, String, BakeOptions)> {
+    let mut options = BakeOptions::default();
+    let mut irradiance = false;
+    let mut distance = None;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let size = args.next()?.parse().ok().filter(|&n| n > 0)?;
+                (options.width, options.height) = (size, size);
+            }
+            "--spp" => options.samples = args.next()?.parse().ok().filter(|&n| n > 0)?,
+            "--irradiance" => irradiance = true,
+            "--distance" => distance = Some(args.next()?.parse().ok().filter(|&d: &f64| d > 0.0)?),
+            flag if flag.starts_with('-') => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    options.mode = match (irradiance, distance) {
+        (false, distance) => BakeMode::AmbientOcclusion { distance },
+        (true, None) => BakeMode::Irradiance,
+        (true, Some(_)) => return None,
+    };
+    match positional.as_slice() {
+        [path, mesh, output] => Some((path.clone(), mesh.clone(), output.clone(), options)),
+        _ => None,
+    }
+}
+
+// Baked maps hold values to be read back, not pictures, so they're saved
+// without tone mapping; irradiance above 1 needs EXR or HDR output.
+fn bake(path: &str, mesh: &str, output: &str, options: &BakeOptions) -> ExitCode {
+    let output_options = OutputOptions {
+        tone_mapper: ToneMapper::Linear,
+        ..OutputOptions::default()
+    };
+    let result = bake_scene(path, mesh, options)
+        .and_then(|image| output::save(&image, Path::new(output), &output_options, None));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// The generator and output of `generate`, then its flags in any order.
+// Images are 16:9, `--size` pixels wide.
+fn parse_generate_args(args: &[String]) -> Option<(String, String, u64, RenderSettings)> {
+    let mut settings = RenderSettings::default();
+    let mut seed = 0;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = args.next()?.parse().ok()?,
+            "--size" => {
+                let width: u32 = args.next()?.parse().ok().filter(|&n| n > 0)?;
+                (settings.width, settings.height) = (width, (width * 9 / 16).max(1));
+            }
+            "--spp" => settings.samples_per_pixel = args.next()?.parse().ok().filter(|&n| n > 0)?,
+            flag if flag.starts_with('-') => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match positional.as_slice() {
+        [generator, output] if ["city", "terrain", "sweep"].contains(&generator.as_str()) => {
+            Some((generator.clone(), output.clone(), seed, settings))
+        }
+        _ => None,
+    }
+}
+
+// Renders one of the procedural scenes of `raytracer::generators` under
+// the default sky, for benchmarks and demos. The material sweep takes no
+// seed.
+fn generate(generator: &str, output: &str, seed: u64, settings: &RenderSettings) -> ExitCode {
+    let aspect_ratio = settings.aspect_ratio();
+    let (camera, world) = match generator {
+        "city" => generators::menger_city(&CityParams {
+            seed,
+            aspect_ratio,
+            ..CityParams::default()
+        }),
+        "terrain" => generators::fbm_terrain(&TerrainParams {
+            seed,
+            aspect_ratio,
+            ..TerrainParams::default()
+        }),
+        _ => generators::material_sweep(&SweepParams {
+            aspect_ratio,
+            ..SweepParams::default()
+        }),
+    };
+    let image = Renderer::new(world, Arc::new(SkyGradient::default())).render(&camera, settings);
+    match output::save(&image, Path::new(output), &OutputOptions::default(), None) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::renderer::{luminance, ImageBuffer};
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// Light scattered by a camera's lens and sensor around bright parts of the
+// image, applied to the scene-referred beauty image before it is tone
+// mapped: only what is brighter than `threshold` spreads, so highlights
+// glow and the rest stays sharp. The light is moved rather than added,
+// keeping the image's total, less what spreads past its edges.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    // Luminance above which pixels bloom; only the excess spreads.
+    pub threshold: f64,
+    // Fraction of the excess spread into the glow.
+    pub intensity: f64,
+    // Standard deviation of the glow's Gaussian, as a fraction of the
+    // image height, so it looks the same at any resolution.
+    pub radius: f64,
+    pub glare: Option<GlareSettings>,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.1,
+            radius: 0.01,
+            glare: None,
+        }
+    }
+}
+
+// Diffraction spikes: streaks from the excess of bright pixels, as the
+// blades of an aperture draw around the sun or a street light.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GlareSettings {
+    // Streaks around each pixel, evenly spaced; a six-bladed aperture
+    // draws six.
+    pub spikes: u32,
+    // Length of the streaks as a fraction of the image height. They fade
+    // exponentially, to about 2% at the end.
+    pub length: f64,
+    // Fraction of the excess spread into the streaks.
+    pub intensity: f64,
+    // Angle of the first streak anticlockwise from the right, in degrees.
+    pub rotation: f64,
+}
+
+impl Default for GlareSettings {
+    fn default() -> Self {
+        Self {
+            spikes: 6,
+            length: 0.1,
+            intensity: 0.05,
+            rotation: 0.0,
+        }
+    }
+}
+
+// Blooms `image` in place. Its alpha is left as it is, so with a
+// transparent background the glow only shows over what was rendered.
+pub fn apply(image: &mut ImageBuffer, settings: &BloomSettings) {
+    let (width, height) = (image.width as usize, image.height as usize);
+    if width == 0 || height == 0 {
+        return;
+    }
+    let bright: Vec<DVec3> = image
+        .pixels
+        .iter()
+        .map(|&color| {
+            let l = luminance(color);
+            if l > settings.threshold && l.is_finite() {
+                color * ((l - settings.threshold) / l)
+            } else {
+                DVec3::ZERO
+            }
+        })
+        .collect();
+    if bright.iter().all(|&c| c == DVec3::ZERO) {
+        return;
+    }
+
+    let sigma = settings.radius * height as f64;
+    let glow = gaussian_blur(&bright, width, height, sigma);
+    for ((pixel, glow), excess) in image.pixels.iter_mut().zip(&glow).zip(&bright) {
+        *pixel += settings.intensity * (*glow - *excess);
+    }
+    if let Some(glare) = &settings.glare {
+        let streaks = streaks(&bright, width, height, glare);
+        for ((pixel, streak), excess) in image.pixels.iter_mut().zip(&streaks).zip(&bright) {
+            *pixel += glare.intensity * (*streak - *excess);
+        }
+    }
+}
+
+// Separable Gaussian blur with a standard deviation of `sigma` pixels.
+// Light blurred past the edges is lost rather than folded back in.
+fn gaussian_blur(pixels: &[DVec3], width: usize, height: usize, sigma: f64) -> Vec<DVec3> {
+    if !(sigma > 0.0) {
+        return pixels.to_vec();
+    }
+    let reach = (3.0 * sigma).ceil() as i64;
+    let mut kernel: Vec<f64> = (-reach..=reach)
+        .map(|i| (-0.5 * (i as f64 / sigma).powi(2)).exp())
+        .collect();
+    let total: f64 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|k| *k /= total);
+
+    let blur = |source: &[DVec3], dx: usize, dy: usize| {
+        let mut blurred = vec![DVec3::ZERO; source.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = DVec3::ZERO;
+                for (k, weight) in kernel.iter().enumerate() {
+                    let offset = k as i64 - reach;
+                    let sx = x as i64 + offset * dx as i64;
+                    let sy = y as i64 + offset * dy as i64;
+                    if (0..width as i64).contains(&sx) && (0..height as i64).contains(&sy) {
+                        sum += *weight * source[sy as usize * width + sx as usize];
+                    }
+                }
+                blurred[y * width + x] = sum;
+            }
+        }
+        blurred
+    };
+    blur(&blur(pixels, 1, 0), 0, 1)
+}
+
+// The streaks `settings` draws from `pixels`, each pixel's light shared
+// between them.
+fn streaks(pixels: &[DVec3], width: usize, height: usize, settings: &GlareSettings) -> Vec<DVec3> {
+    let steps = (settings.length * height as f64).round().max(1.0) as usize;
+    let spikes = settings.spikes.max(1);
+    let falloff = |i: usize| (-4.0 * i as f64 / steps as f64).exp();
+    let total = spikes as f64 * (1..=steps).map(falloff).sum::<f64>();
+    let directions: Vec<(f64, f64)> = (0..spikes)
+        .map(|k| {
+            let angle = settings.rotation.to_radians() + 2.0 * PI * k as f64 / spikes as f64;
+            // Rows run down the image.
+            (angle.cos(), -angle.sin())
+        })
+        .collect();
+
+    let mut streaks = vec![DVec3::ZERO; pixels.len()];
+    for (index, &source) in pixels.iter().enumerate() {
+        if source == DVec3::ZERO {
+            continue;
+        }
+        let (x, y) = ((index % width) as f64, (index / width) as f64);
+        for &(dx, dy) in &directions {
+            for i in 1..=steps {
+                let sx = (x + i as f64 * dx).round();
+                let sy = (y + i as f64 * dy).round();
+                if sx < 0.0 || sy < 0.0 || sx >= width as f64 || sy >= height as f64 {
+                    break;
+                }
+                streaks[sy as usize * width + sx as usize] += falloff(i) / total * source;
+            }
+        }
+    }
+    streaks
+}
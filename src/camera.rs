@@ -1,15 +1,209 @@
+use crate::hittable::AABB;
 use crate::ray::Ray;
-use glam::DVec3;
-use rand::Rng;
+use crate::sampler::Sampler;
+use glam::{DVec2, DVec3};
+use std::f64::consts::PI;
 
+#[derive(Clone, Copy, Debug)]
+pub enum ApertureShape {
+    Circular,
+    // Regular polygon with `blades` sides, rotated by `rotation` degrees.
+    Polygon { blades: u32, rotation: f64 },
+}
+
+impl ApertureShape {
+    pub fn hexagonal() -> Self {
+        ApertureShape::Polygon {
+            blades: 6,
+            rotation: 0.0,
+        }
+    }
+
+    // Maps a point of the unit square onto the aperture; (0, 0) lands on
+    // the lens centre.
+    fn sample(&self, lens_sample: DVec2) -> DVec3 {
+        match *self {
+            ApertureShape::Circular => sample_disk(lens_sample),
+            ApertureShape::Polygon { blades, rotation } if blades >= 3 => {
+                let wedge = 2.0 * PI / blades as f64;
+                let scaled = lens_sample.x * blades as f64;
+                let i = scaled.floor().min((blades - 1) as f64);
+                let start = rotation.to_radians() + i * wedge;
+                let a = DVec3::new(start.cos(), start.sin(), 0.0);
+                let b = DVec3::new((start + wedge).cos(), (start + wedge).sin(), 0.0);
+
+                let (mut s, mut t) = (scaled - i, lens_sample.y);
+                if s + t > 1.0 {
+                    s = 1.0 - s;
+                    t = 1.0 - t;
+                }
+                s * a + t * b
+            }
+            ApertureShape::Polygon { .. } => sample_disk(lens_sample),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum CameraProjection {
+    Perspective,
+    // `height` is the world-space height of the view volume.
+    Orthographic { height: f64 },
+    // Equidistant fisheye; `fov` is the field of view in degrees across the
+    // shorter image axis.
+    Fisheye { fov: f64 },
+    Equirectangular,
+    // Omni-directional stereo for VR: equirectangular panoramas for the
+    // left eye, in the top half of the image, and the right eye below, as
+    // YouTube's top-bottom stereo layout expects. Each eye's rays start
+    // on a circle of diameter `ipd`, the distance between the eyes in
+    // scene units, tangent to the direction they look in, so every view
+    // direction is seen in stereo.
+    OmniStereo { ipd: f64 },
+    // A perspective view for each eye; see `Stereo`.
+    Stereo(Stereo),
+}
+
+// Two perspective views, the eyes `ipd` apart along the camera's right
+// axis, laid out in one image by `layout`. Their axes stay parallel, with
+// their frames sharing the window at `convergence`, so objects at that
+// distance appear at the screen and nearer ones in front of it; `None`
+// converges at the focus distance. Each eye keeps the vertical field of
+// view over its half of the image.
+#[derive(Clone, Copy, Debug)]
+pub struct Stereo {
+    pub ipd: f64,
+    pub convergence: Option<f64>,
+    pub layout: StereoLayout,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoLayout {
+    // Left eye in the left half.
+    SideBySide,
+    // Left eye in the top half.
+    TopBottom,
+}
+
+// How the shutter opens over the exposure, as a trapezoid in time. Rays
+// are distributed in time proportionally to the open fraction, so ramps
+// soften the start and end of motion-blur streaks.
+#[derive(Clone, Copy, Debug)]
+pub enum ShutterCurve {
+    // Opens and closes instantly.
+    Box,
+    // Fully open only at the middle of the exposure.
+    Triangle,
+    // `open` and `close` are the fractions of the exposure spent opening and
+    // closing; the shutter is fully open in between.
+    Ramp { open: f64, close: f64 },
+}
+
+impl ShutterCurve {
+    // Maps a uniform sample to a time in [0, 1] following the curve.
+    pub fn sample(&self, u: f64) -> f64 {
+        let (a, b) = match *self {
+            ShutterCurve::Box => return u,
+            ShutterCurve::Triangle => (0.5, 0.5),
+            ShutterCurve::Ramp { open, close } => {
+                let open = open.clamp(0.0, 1.0);
+                (open, close.clamp(0.0, 1.0 - open))
+            }
+        };
+        let height = 1.0 / (1.0 - 0.5 * (a + b));
+        let opening = 0.5 * height * a;
+        let fully_open = height * (1.0 - a - b);
+        if u < opening {
+            (2.0 * a * u / height).sqrt()
+        } else if u < opening + fully_open {
+            a + (u - opening) / height
+        } else {
+            1.0 - (2.0 * b * (1.0 - u) / height).sqrt()
+        }
+    }
+
+    // Fraction of the exposure interval during which light is collected.
+    pub fn efficiency(&self) -> f64 {
+        match *self {
+            ShutterCurve::Box => 1.0,
+            ShutterCurve::Triangle => 0.5,
+            ShutterCurve::Ramp { open, close } => {
+                let open = open.clamp(0.0, 1.0);
+                1.0 - 0.5 * (open + close.clamp(0.0, 1.0 - open))
+            }
+        }
+    }
+}
+
+// The exposure settings of a real camera, for scenes lit in physical
+// units: radiance read as nits (cd/m²), as lights given in watts or lux
+// make it. The film is scaled as a sensor of speed `iso` would record it
+// through the lens at `f_stop` for `shutter_speed` seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicalExposure {
+    pub iso: f64,
+    pub f_stop: f64,
+    pub shutter_speed: f64,
+}
+
+impl PhysicalExposure {
+    // Exposure value at ISO 100 of these settings: 0 for f/1 at 1 s, one
+    // higher for each halving of the light they let in.
+    pub fn ev100(&self) -> f64 {
+        (self.f_stop * self.f_stop / self.shutter_speed).log2() - (self.iso / 100.0).log2()
+    }
+
+    // Factor the film is scaled by: one over the luminance that just
+    // saturates the sensor, 1.2 × 2^EV100 nits by the saturation-based
+    // speed of ISO 12232, so that brighter surfaces clip.
+    pub fn scale(&self) -> f64 {
+        1.0 / (1.2 * self.ev100().exp2())
+    }
+}
+
+// Flaws of a real lens that make renders look photographed, applied to
+// the rays `get_ray` draws; all are off at 0. The ideal projection of
+// `generate_ray` and `project` is left as it is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LensEffects {
+    // Radial distortion: lines off centre bow outwards below 0, as through
+    // a wide-angle lens (barrel), and inwards above 0 (pincushion). The
+    // corners of the image move by about this fraction of their distance
+    // from its centre.
+    pub distortion: f64,
+    // Lateral chromatic aberration: the red image is this fraction larger
+    // than the green one and the blue image this fraction smaller, so edges
+    // towards the corners fringe with colour. Each camera ray carries one
+    // channel, drawn at random.
+    pub chromatic_aberration: f64,
+    // Darkening towards the corners: 1 follows the cos⁴ law of the angle
+    // off the line of sight, as an ideal lens does; 0 is none, and values
+    // between blend the two.
+    pub vignetting: f64,
+}
+
+#[derive(Clone)]
 pub struct Camera {
-    origin: DVec3,
-    lower_left_corner: DVec3,
-    horizontal: DVec3,
-    vertical: DVec3,
-    u: DVec3,
-    v: DVec3,
-    lens_radius: f64,
+    pub(crate) origin: DVec3,
+    pub(crate) lower_left_corner: DVec3,
+    pub(crate) horizontal: DVec3,
+    pub(crate) vertical: DVec3,
+    pub(crate) u: DVec3,
+    pub(crate) v: DVec3,
+    pub(crate) w: DVec3,
+    pub(crate) aspect_ratio: f64,
+    pub(crate) projection: CameraProjection,
+    pub(crate) lens_radius: f64,
+    pub(crate) aperture_shape: ApertureShape,
+    pub(crate) anamorphic_squeeze: f64,
+    pub(crate) shutter: (f64, f64),
+    pub(crate) shutter_curve: ShutterCurve,
+    // Lens shift, in image widths and heights; see `with_shift`.
+    pub(crate) shift: DVec2,
+    // Tangents of the focal plane's tilt angles; see `with_tilt`.
+    pub(crate) tilt: DVec2,
+    pub(crate) exposure: Option<PhysicalExposure>,
+    pub(crate) lens_effects: LensEffects,
 }
 
 impl Camera {
@@ -44,27 +238,450 @@ impl Camera {
             vertical,
             u,
             v,
+            w,
+            aspect_ratio,
+            projection: CameraProjection::Perspective,
             lens_radius,
+            aperture_shape: ApertureShape::Circular,
+            anamorphic_squeeze: 1.0,
+            shutter: (0.0, 1.0),
+            shutter_curve: ShutterCurve::Box,
+            shift: DVec2::ZERO,
+            tilt: DVec2::ZERO,
+            exposure: None,
+            lens_effects: LensEffects::default(),
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk();
-        let offset = self.u * rd.x + self.v * rd.y; // retest
+    pub fn with_aperture_shape(mut self, shape: ApertureShape) -> Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    // Squeeze factors above 1 narrow the aperture horizontally, producing the
+    // vertically stretched oval bokeh of anamorphic lenses.
+    pub fn with_anamorphic_squeeze(mut self, squeeze: f64) -> Self {
+        self.anamorphic_squeeze = squeeze.max(1e-3);
+        self
+    }
+
+    pub fn with_projection(mut self, projection: CameraProjection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    // Open and close times within the frame's [0, 1] motion interval; rays
+    // are spread between them according to the shutter curve.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter = (open, close);
+        self
+    }
+
+    pub fn with_shutter_curve(mut self, curve: ShutterCurve) -> Self {
+        self.shutter_curve = curve;
+        self
+    }
+
+    // Slides the image across the focal plane without turning the camera,
+    // by `shift.x` image widths to the right and `shift.y` heights up, as a
+    // shift lens does. Looking level with the image shifted up keeps the
+    // verticals of a tall building parallel. Perspective and orthographic
+    // views only.
+    pub fn with_shift(mut self, shift: DVec2) -> Self {
+        self.shift = shift;
+        self
+    }
+
+    // Tilts the plane in focus, in radians, as a tilt lens does for
+    // Scheimpflug focus: by `tilt.x` about the vertical axis, so that it
+    // recedes to the right, and by `tilt.y` about the horizontal one, so
+    // that it recedes towards the top, as for a table top or the ground in
+    // focus from near to far. It still passes through the focus distance
+    // on the line of sight. Perspective views only.
+    pub fn with_tilt(mut self, tilt: DVec2) -> Self {
+        self.tilt = DVec2::new(tilt.x.tan(), tilt.y.tan());
+        self
+    }
 
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-        )
+    // Scales the film as `exposure` would expose it; see `film_scale`. The
+    // aperture the rays pass through is left as it is, so depth of field
+    // can be set apart from brightness.
+    pub fn with_exposure(mut self, exposure: PhysicalExposure) -> Self {
+        self.exposure = Some(exposure);
+        self
+    }
+
+    pub fn with_lens_effects(mut self, effects: LensEffects) -> Self {
+        self.lens_effects = effects;
+        self
+    }
+
+    // Factor every sample reaching the film is scaled by: 1 without a
+    // physical exposure, and otherwise its scale, less the light a shutter
+    // that takes time to open and close keeps out.
+    pub fn film_scale(&self) -> f64 {
+        self.exposure.map_or(1.0, |exposure| {
+            exposure.scale() * self.shutter_curve.efficiency()
+        })
+    }
+
+    // The same camera moved to `lookfrom`, aimed at `lookat` and focused on
+    // it. The field of view, lens, projection and shutter are kept.
+    pub fn looking_at(&self, lookfrom: DVec3, lookat: DVec3, vup: DVec3) -> Self {
+        let focus_dist = (lookfrom - lookat).length();
+        let scale = focus_dist / self.focus_distance();
+        let w = (lookfrom - lookat).normalize();
+        let u = vup.cross(w).normalize();
+        let v = w.cross(u);
+        let horizontal = scale * self.horizontal.length() * u;
+        let vertical = scale * self.vertical.length() * v;
+        Self {
+            origin: lookfrom,
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0 - focus_dist * w,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            ..self.clone()
+        }
+    }
+
+    // The same camera moved along its line of sight until all of `bounds`
+    // is in view, aimed at and focused on their centre. `padding` widens
+    // the margin around them, as a fraction of their size. Orthographic
+    // views are resized to fit instead; panoramas see everything and are
+    // just placed outside the bounds.
+    pub fn frame_bounds(&self, bounds: &AABB, padding: f64) -> Self {
+        let center = (bounds.min + bounds.max) / 2.0;
+        // The sphere around the box, which fits whichever way it turns.
+        let radius = ((bounds.max - bounds.min).length() / 2.0 * (1.0 + padding)).max(1e-9);
+        let half_fov = match self.projection {
+            CameraProjection::Perspective | CameraProjection::Stereo(_) => {
+                let narrowest = self.horizontal.length().min(self.vertical.length());
+                (narrowest / 2.0 / self.focus_distance()).atan()
+            }
+            CameraProjection::Fisheye { fov } => (fov.to_radians() / 2.0).min(PI / 2.0),
+            _ => PI / 6.0,
+        };
+        let distance = radius / half_fov.sin();
+        let mut camera = self.looking_at(center + distance * self.w, center, self.v);
+        if let CameraProjection::Orthographic { .. } = camera.projection {
+            camera.projection = CameraProjection::Orthographic {
+                height: 2.0 * radius * (1.0 / self.aspect_ratio).max(1.0),
+            };
+        }
+        camera
+    }
+
+    // The same camera focused on the plane through `point`, square to the
+    // line of sight. The field of view is kept.
+    pub fn focused_on(&self, point: DVec3) -> Self {
+        let focus_dist = (self.origin - point).dot(self.w).max(1e-3);
+        let scale = focus_dist / self.focus_distance();
+        let horizontal = scale * self.horizontal;
+        let vertical = scale * self.vertical;
+        Self {
+            lower_left_corner: self.origin
+                - horizontal / 2.0
+                - vertical / 2.0
+                - focus_dist * self.w,
+            horizontal,
+            vertical,
+            ..self.clone()
+        }
+    }
+
+    // Distance from the origin to the plane in focus.
+    pub fn focus_distance(&self) -> f64 {
+        (self.origin - self.lower_left_corner).dot(self.w)
+    }
+
+    // The cone the rays through one pixel of an image `height` pixels high
+    // fill, as its width at the eye and how much wider it grows per unit
+    // of distance, for choosing how finely to filter textures.
+    pub fn pixel_cone(&self, height: u32) -> (f64, f64) {
+        let height = height.max(1) as f64;
+        match self.projection {
+            CameraProjection::Perspective | CameraProjection::Stereo(_) => {
+                (0.0, self.vertical.length() / self.focus_distance() / height)
+            }
+            CameraProjection::Orthographic { height: view } => (view / height, 0.0),
+            CameraProjection::Fisheye { fov } => {
+                let shorter = height.min(height * self.aspect_ratio);
+                (0.0, fov.to_radians() / shorter)
+            }
+            CameraProjection::Equirectangular => (0.0, PI / height),
+            // Each eye's panorama fills half of the image.
+            CameraProjection::OmniStereo { .. } => (0.0, 2.0 * PI / height),
+        }
+    }
+
+    // Ray through the image at (`s`, `t`), at a random point of the lens
+    // and time the shutter is open, and the weight of the light it brings
+    // back to the film, which the lens effects darken and split by colour.
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> (Ray, DVec3) {
+        let lens_sample = sampler.next_2d();
+        let (open, close) = self.shutter;
+        let time = open + self.shutter_curve.sample(sampler.next_1d()) * (close - open);
+        let effects = self.lens_effects;
+        if effects == LensEffects::default() {
+            let ray = self.generate_ray(s, t, DVec2::new(lens_sample.0, lens_sample.1), time);
+            return (ray, DVec3::ONE);
+        }
+
+        let channel = if effects.chromatic_aberration != 0.0 {
+            Some(((sampler.next_1d() * 3.0) as usize).min(2))
+        } else {
+            None
+        };
+        let film = self.distort(s, t, channel);
+        let ray = self.generate_ray(
+            film.x,
+            film.y,
+            DVec2::new(lens_sample.0, lens_sample.1),
+            time,
+        );
+        let cosine = ray.direction.normalize().dot(-self.w).max(0.0);
+        let vignetting = 1.0 - effects.vignetting + effects.vignetting * cosine.powi(4);
+        let weight = match channel {
+            Some(channel) => 3.0 * DVec3::AXES[channel],
+            None => DVec3::ONE,
+        };
+        (ray, vignetting * weight)
+    }
+
+    // Where on the ideal image the lens sees the light landing at NDC
+    // (`px`, `py`) come from, for colour `channel` (0 red, 1 green, 2
+    // blue) or for all of them. Distances from the centre are measured in
+    // half-diagonals of the image, whatever its aspect ratio.
+    pub fn distort(&self, px: f64, py: f64, channel: Option<usize>) -> DVec2 {
+        let effects = self.lens_effects;
+        let aspect = DVec2::new(self.aspect_ratio, 1.0);
+        let offset = (DVec2::new(px, py) - 0.5) * aspect;
+        let r2 = offset.length_squared() / (0.25 * aspect.length_squared());
+        let magnification = match channel {
+            Some(0) => 1.0 + effects.chromatic_aberration,
+            Some(2) => 1.0 - effects.chromatic_aberration,
+            _ => 1.0,
+        };
+        let scale = 1.0 / ((1.0 + effects.distortion * r2).max(1e-3) * magnification.max(1e-3));
+        DVec2::splat(0.5) + scale * offset / aspect
+    }
+
+    // Ray through normalized device coordinates (`px`, `py`), where (0, 0)
+    // is the bottom-left corner of the image and (1, 1) the top-right, with
+    // the pixel grid spanning [0, 1] regardless of aspect ratio.
+    // `lens_sample` is a point in the unit square mapped onto the aperture,
+    // (0, 0) being the lens centre; projections without a lens ignore it.
+    // `time` is stamped on the ray for moving objects; the camera itself
+    // doesn't move during a frame.
+    pub fn generate_ray(&self, px: f64, py: f64, lens_sample: DVec2, time: f64) -> Ray {
+        let ray = match self.projection {
+            CameraProjection::Perspective => self.perspective_ray(px, py, lens_sample),
+            CameraProjection::Orthographic { height } => {
+                let width = height * self.aspect_ratio;
+                let origin = self.origin
+                    + (px - 0.5 + self.shift.x) * width * self.u
+                    + (py - 0.5 + self.shift.y) * height * self.v;
+                Ray::new(origin, -self.w)
+            }
+            CameraProjection::Fisheye { fov } => {
+                let x = (2.0 * px - 1.0) * self.aspect_ratio.max(1.0);
+                let y = (2.0 * py - 1.0) / self.aspect_ratio.min(1.0);
+                let r = (x * x + y * y).sqrt();
+                let theta = r * fov.to_radians() / 2.0;
+                let phi = y.atan2(x);
+                let direction = theta.sin() * (phi.cos() * self.u + phi.sin() * self.v)
+                    - theta.cos() * self.w;
+                Ray::new(self.origin, direction)
+            }
+            CameraProjection::Equirectangular => {
+                Ray::new(self.origin, self.panorama_direction(px, py))
+            }
+            CameraProjection::OmniStereo { ipd } => {
+                let (eye, py) = if py >= 0.5 {
+                    (-0.5, 2.0 * py - 1.0)
+                } else {
+                    (0.5, 2.0 * py)
+                };
+                let phi = (px - 0.5) * 2.0 * PI;
+                let right = phi.cos() * self.u + phi.sin() * self.w;
+                Ray::new(
+                    self.origin + eye * ipd * right,
+                    self.panorama_direction(px, py),
+                )
+            }
+            CameraProjection::Stereo(stereo) => {
+                let (eye, ndc) = stereo.eye(px, py);
+                self.stereo_ray(&stereo, eye * stereo.ipd, ndc, lens_sample)
+            }
+        };
+        ray.with_time(time)
+    }
+
+    // Inverse of `generate_ray` through the lens centre: the NDC position at
+    // which `point` appears, or `None` if it is behind a perspective camera
+    // or sits exactly at the camera origin. Omni-directional stereo cameras
+    // give the position in the left eye's half, and `None` for points
+    // between the eyes.
+    pub fn project(&self, point: DVec3) -> Option<DVec2> {
+        let d = point - self.origin;
+        let (x, y, z) = (d.dot(self.u), d.dot(self.v), -d.dot(self.w));
+        match self.projection {
+            CameraProjection::Perspective => {
+                if z <= 0.0 {
+                    return None;
+                }
+                let k = self.focus_distance() / z;
+                let centred = DVec2::new(
+                    0.5 + k * x / self.horizontal.length(),
+                    0.5 + k * y / self.vertical.length(),
+                );
+                Some(centred - self.shift)
+            }
+            CameraProjection::Orthographic { height } => {
+                let width = height * self.aspect_ratio;
+                Some(DVec2::new(0.5 + x / width, 0.5 + y / height) - self.shift)
+            }
+            CameraProjection::Fisheye { fov } => {
+                let length = d.length();
+                if length == 0.0 {
+                    return None;
+                }
+                let theta = (z / length).clamp(-1.0, 1.0).acos();
+                let r = theta / (fov.to_radians() / 2.0);
+                let phi = y.atan2(x);
+                let s = (r * phi.cos() / self.aspect_ratio.max(1.0) + 1.0) / 2.0;
+                let t = (r * phi.sin() * self.aspect_ratio.min(1.0) + 1.0) / 2.0;
+                Some(DVec2::new(s, t))
+            }
+            CameraProjection::Equirectangular => {
+                let length = d.length();
+                if length == 0.0 {
+                    return None;
+                }
+                let theta = (y / length).clamp(-1.0, 1.0).asin();
+                let phi = x.atan2(z);
+                Some(DVec2::new(phi / (2.0 * PI) + 0.5, theta / PI + 0.5))
+            }
+            CameraProjection::OmniStereo { ipd } => {
+                // The left eye sees the point along the ray that starts
+                // half the eye distance to the left of its direction and
+                // passes through it.
+                let radius = 0.5 * ipd;
+                let horizontal = (x * x + z * z).sqrt();
+                if horizontal <= radius {
+                    return None;
+                }
+                let phi = x.atan2(z) + (radius / horizontal).asin();
+                let theta = y.atan2((horizontal * horizontal - radius * radius).sqrt());
+                Some(DVec2::new(
+                    (phi / (2.0 * PI) + 0.5).rem_euclid(1.0),
+                    0.5 + 0.5 * (theta / PI + 0.5),
+                ))
+            }
+            CameraProjection::Stereo(stereo) => {
+                // From the left eye onto the shared window, whose centre
+                // is half the eye distance to its right.
+                let x = x + 0.5 * stereo.ipd;
+                if z <= 0.0 {
+                    return None;
+                }
+                let (horizontal, vertical) = self.stereo_window(stereo.layout);
+                let on_window = DVec2::new(x - 0.5 * stereo.ipd * z / self.convergence(&stereo), y);
+                let eye = DVec2::new(
+                    0.5 + self.focus_distance() * on_window.x / (z * horizontal.length()),
+                    0.5 + self.focus_distance() * on_window.y / (z * vertical.length()),
+                );
+                Some(match stereo.layout {
+                    StereoLayout::SideBySide => DVec2::new(0.5 * eye.x, eye.y),
+                    StereoLayout::TopBottom => DVec2::new(eye.x, 0.5 + 0.5 * eye.y),
+                })
+            }
+        }
+    }
+
+    // World-space point `distance` along the lens-centre ray through
+    // (`px`, `py`).
+    pub fn unproject(&self, px: f64, py: f64, distance: f64) -> DVec3 {
+        let ray = self.generate_ray(px, py, DVec2::ZERO, 0.0);
+        ray.origin + distance * ray.direction.normalize()
+    }
+
+    // Direction at NDC (`px`, `py`) of an equirectangular panorama centred
+    // on the view direction.
+    fn panorama_direction(&self, px: f64, py: f64) -> DVec3 {
+        let phi = (px - 0.5) * 2.0 * PI;
+        let theta = (py - 0.5) * PI;
+        theta.cos() * (phi.sin() * self.u - phi.cos() * self.w) + theta.sin() * self.v
+    }
+
+    fn convergence(&self, stereo: &Stereo) -> f64 {
+        stereo.convergence.unwrap_or(self.focus_distance())
+    }
+
+    // Width and height of an eye's frame on the focal plane: the camera's
+    // frame reshaped to the eye's half of the image at the same height.
+    fn stereo_window(&self, layout: StereoLayout) -> (DVec3, DVec3) {
+        match layout {
+            StereoLayout::SideBySide => (0.5 * self.horizontal, self.vertical),
+            StereoLayout::TopBottom => (2.0 * self.horizontal, self.vertical),
+        }
+    }
+
+    // Ray for the eye `offset` along the right axis, through `ndc` of its
+    // frame, aimed through the lens at the point of the focal plane on the
+    // line from the eye through the shared window.
+    fn stereo_ray(&self, stereo: &Stereo, offset: f64, ndc: DVec2, lens_sample: DVec2) -> Ray {
+        let focus_dist = self.focus_distance();
+        let convergence = self.convergence(stereo);
+        let (horizontal, vertical) = self.stereo_window(stereo.layout);
+        let eye = self.origin + offset * self.u;
+        let on_focal_plane =
+            -focus_dist * self.w + (ndc.x - 0.5) * horizontal + (ndc.y - 0.5) * vertical;
+        let window = self.origin + convergence / focus_dist * on_focal_plane;
+        let focus = eye + (window - eye) * (focus_dist / convergence);
+
+        let rd = self.lens_radius * self.aperture_shape.sample(lens_sample);
+        let lens = self.u * (rd.x / self.anamorphic_squeeze) + self.v * rd.y;
+        Ray::new(eye + lens, focus - eye - lens)
+    }
+
+    fn perspective_ray(&self, s: f64, t: f64, lens_sample: DVec2) -> Ray {
+        let rd = self.lens_radius * self.aperture_shape.sample(lens_sample);
+        let offset = self.u * (rd.x / self.anamorphic_squeeze) + self.v * rd.y; // retest
+
+        let (s, t) = (s + self.shift.x, t + self.shift.y);
+        let mut focus = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        if self.tilt != DVec2::ZERO {
+            // Where the ray through the lens centre meets the tilted plane.
+            let normal = self.w + self.tilt.x * self.u + self.tilt.y * self.v;
+            let centre = focus - self.origin;
+            let along = -self.focus_distance() * self.w.dot(normal) / centre.dot(normal);
+            if along > 0.0 {
+                focus = self.origin + along * centre;
+            }
+        }
+        Ray::new(self.origin + offset, focus - self.origin - offset)
     }
 }
 
-fn random_in_unit_disk() -> DVec3 {
-    let mut rng = rand::thread_rng();
-    loop {
-        let p = DVec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
-        if p.length_squared() < 1.0 {
-            return p;
+impl Stereo {
+    // The eye that sees NDC (`px`, `py`), -0.5 for the left and 0.5 for the
+    // right, and the position within its half of the image.
+    fn eye(&self, px: f64, py: f64) -> (f64, DVec2) {
+        match self.layout {
+            StereoLayout::SideBySide if px < 0.5 => (-0.5, DVec2::new(2.0 * px, py)),
+            StereoLayout::SideBySide => (0.5, DVec2::new(2.0 * px - 1.0, py)),
+            StereoLayout::TopBottom if py >= 0.5 => (-0.5, DVec2::new(px, 2.0 * py - 1.0)),
+            StereoLayout::TopBottom => (0.5, DVec2::new(px, 2.0 * py)),
         }
     }
 }
+
+fn sample_disk(sample: DVec2) -> DVec3 {
+    let r = sample.x.sqrt();
+    let phi = 2.0 * PI * sample.y;
+    DVec3::new(r * phi.cos(), r * phi.sin(), 0.0)
+}
@@ -0,0 +1,138 @@
+use crate::output::scene_hash;
+use crate::renderer::{assemble, RenderPasses, RenderSettings, RenderedTile, Tile};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// The tiles of a render finished so far, collected as they finish so that
+// an interrupted render can still be saved as a partial image, and picked
+// up again later without redoing them. Renderers given one skip the tiles
+// it already holds.
+pub struct RenderCheckpoint {
+    // Identifies the scene, its assets and the settings the tiles were
+    // rendered with.
+    key: u64,
+    // By the corner they start at.
+    tiles: Mutex<HashMap<(u32, u32), (Tile, RenderedTile)>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CheckpointFile {
+    key: u64,
+    tiles: Vec<(Tile, RenderedTile)>,
+}
+
+impl RenderCheckpoint {
+    // An empty checkpoint for rendering the scene file contents `scene`
+    // with `settings`. `assets` are the files the scene reads, such as
+    // meshes and images, whose contents are part of what the tiles show.
+    pub fn new(scene: &[u8], assets: &[String], settings: &RenderSettings) -> Self {
+        Self {
+            key: checkpoint_key(scene, assets, settings),
+            tiles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The checkpoint `save` left at `path` if it was for the same scene,
+    // assets and settings, or an empty one if there is none or it was for
+    // another render.
+    pub fn load(
+        path: &Path,
+        scene: &[u8],
+        assets: &[String],
+        settings: &RenderSettings,
+    ) -> Result<Self, Box<dyn Error>> {
+        let checkpoint = Self::new(scene, assets, settings);
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(checkpoint),
+            Err(e) => return Err(e.into()),
+        };
+        let file: CheckpointFile = serde_json::from_slice(&bytes)?;
+        if file.key == checkpoint.key {
+            *checkpoint.lock() = file
+                .tiles
+                .into_iter()
+                .map(|(tile, rendered)| ((tile.x0, tile.y0), (tile, rendered)))
+                .collect();
+        }
+        Ok(checkpoint)
+    }
+
+    // Writes the tiles finished so far to `path`, replacing it in one step
+    // so that an interruption mid-write leaves the previous checkpoint.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let bytes = {
+            let tiles = self.lock();
+            serde_json::to_vec(&CheckpointFileRef {
+                key: self.key,
+                tiles: tiles.values().collect(),
+            })?
+        };
+        let mut partial = PathBuf::from(path);
+        partial.as_mut_os_string().push(".partial");
+        std::fs::write(&partial, bytes)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn tiles_done(&self) -> usize {
+        self.lock().len()
+    }
+
+    // The render as far as it got; pixels of unfinished tiles are black.
+    pub fn passes(&self, settings: &RenderSettings) -> RenderPasses {
+        let tiles = self.lock();
+        let mut done: Vec<(Tile, &RenderedTile)> = tiles
+            .values()
+            .map(|(tile, rendered)| (*tile, rendered))
+            .collect();
+        done.sort_by_key(|(tile, _)| (tile.y0, tile.x0));
+        assemble(settings, done)
+    }
+
+    pub(crate) fn finished(&self, tile: &Tile) -> Option<RenderedTile> {
+        let tiles = self.lock();
+        let (_, rendered) = tiles.get(&(tile.x0, tile.y0))?;
+        Some(rendered.clone())
+    }
+
+    pub(crate) fn record(&self, tile: Tile, rendered: &RenderedTile) {
+        let mut rendered = rendered.clone();
+        // JSON can't carry them.
+        rendered.clear_non_finite();
+        self.lock().insert((tile.x0, tile.y0), (tile, rendered));
+    }
+
+    // Panics elsewhere in the render mustn't stop what was finished from
+    // being saved.
+    fn lock(&self) -> MutexGuard<'_, HashMap<(u32, u32), (Tile, RenderedTile)>> {
+        self.tiles.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// `CheckpointFile` as written, borrowing the tiles rather than copying them.
+#[derive(Serialize)]
+struct CheckpointFileRef<'a> {
+    key: u64,
+    tiles: Vec<&'a (Tile, RenderedTile)>,
+}
+
+// Each asset adds its path and the hash of its contents; those that can't
+// be read add a marker instead, so that a render resumed once they are back
+// starts over.
+fn checkpoint_key(scene: &[u8], assets: &[String], settings: &RenderSettings) -> u64 {
+    let mut bytes = scene.to_vec();
+    for asset in assets {
+        bytes.extend(asset.as_bytes());
+        match std::fs::read(asset) {
+            Ok(contents) => bytes.extend(scene_hash(&contents).to_le_bytes()),
+            Err(_) => bytes.push(0),
+        }
+    }
+    bytes.extend(serde_json::to_vec(settings).unwrap_or_default());
+    scene_hash(&bytes)
+}
@@ -0,0 +1,64 @@
+use crate::color::srgb_to_linear;
+use crate::texture::Texture;
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// How the code values of an image relate to linear light, for decoding
+// textures on load. Colour images are almost always sRGB; data such as
+// normal, roughness and height maps is usually stored linear.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    #[serde(rename = "srgb")]
+    Srgb,
+    // BT.709 video, whose curve has a longer linear toe than sRGB's.
+    #[serde(rename = "rec709")]
+    Rec709,
+    #[serde(rename = "linear")]
+    Linear,
+}
+
+impl ColorSpace {
+    // The linear value of code value `c`, in [0, 1].
+    pub fn decode(self, c: f64) -> f64 {
+        match self {
+            ColorSpace::Srgb => srgb_to_linear(c),
+            ColorSpace::Rec709 => {
+                if c < 0.081 {
+                    c / 4.5
+                } else {
+                    ((c + 0.099) / 1.099).powf(1.0 / 0.45)
+                }
+            }
+            ColorSpace::Linear => c,
+        }
+    }
+
+    pub fn decode_rgb(self, c: DVec3) -> DVec3 {
+        DVec3::from_array(c.to_array().map(|c| self.decode(c)))
+    }
+}
+
+// Decodes what another texture looks up, for textures that hand out their
+// code values as they are. Only exact for unfiltered lookups: blending
+// should happen after decoding, as `MipmappedTexture` does it.
+pub struct DecodedTexture {
+    pub texture: Arc<dyn Texture>,
+    pub color_space: ColorSpace,
+}
+
+impl DecodedTexture {
+    pub fn new(texture: Arc<dyn Texture>, color_space: ColorSpace) -> Self {
+        Self {
+            texture,
+            color_space,
+        }
+    }
+}
+
+impl Texture for DecodedTexture {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        self.color_space.decode_rgb(self.texture.value(u, v, p))
+    }
+}
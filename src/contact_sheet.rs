@@ -0,0 +1,215 @@
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::lights::LightSet;
+use crate::output::exposure_scale;
+use crate::renderer::{AovSelection, ImageBuffer, RenderSettings, Renderer};
+use crate::scene::{Scene, SceneConfig, SceneFormat};
+use crate::stamp::stamp_image;
+use glam::DVec3;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Files read as scenes: the extensions `SceneFormat` knows.
+const SCENE_EXTENSIONS: [&str; 5] = ["json", "yaml", "yml", "toml", "ron"];
+// Behind and between the thumbnails.
+const BACKGROUND: DVec3 = DVec3::splat(0.02);
+
+#[derive(Clone, Copy, Debug)]
+pub struct ContactSheetOptions {
+    // Each thumbnail is fitted, at its scene's aspect ratio, inside a
+    // square cell this many pixels across.
+    pub cell_size: u32,
+    pub samples_per_pixel: u32,
+    // Caps each scene's own `max_depth`.
+    pub max_depth: u32,
+    pub columns: u32,
+    // Pixels around each cell.
+    pub gap: u32,
+}
+
+impl Default for ContactSheetOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 160,
+            samples_per_pixel: 8,
+            max_depth: 8,
+            columns: 6,
+            gap: 4,
+        }
+    }
+}
+
+pub struct ContactSheet {
+    pub image: ImageBuffer,
+    // The scenes shown, row by row.
+    pub scenes: Vec<PathBuf>,
+}
+
+// Renders a quick thumbnail of every scene file directly inside `dir`, in
+// file name order, and lays them out in rows. Files that don't load, such
+// as fragments meant to be included by other scenes, are passed to
+// `skipped` and left out.
+pub fn render_contact_sheet(
+    dir: &Path,
+    options: &ContactSheetOptions,
+    mut skipped: impl FnMut(&Path, Box<dyn Error>),
+) -> Result<ContactSheet, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|e| e.to_str());
+        if extension.is_some_and(|e| SCENE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str())) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut thumbnails = Vec::with_capacity(paths.len());
+    let mut scenes = Vec::with_capacity(paths.len());
+    for path in paths {
+        let scene = path
+            .to_str()
+            .ok_or_else(|| "scene path isn't valid UTF-8".into())
+            .and_then(|path| Scene::from_file(path).map_err(Into::into));
+        match scene.and_then(|scene| thumbnail(scene, options)) {
+            Ok(image) => {
+                thumbnails.push(image);
+                scenes.push(path);
+            }
+            Err(e) => skipped(&path, e),
+        }
+    }
+
+    let columns = options.columns.clamp(1, (thumbnails.len() as u32).max(1));
+    let image = lay_out(&thumbnails, columns, options);
+    Ok(ContactSheet { image, scenes })
+}
+
+// One scene field varied along an axis of a sweep: its path, with object
+// keys and list indices separated by dots as in `materials.gold.roughness`
+// or `objects.2.material.ior`, and the values it takes.
+pub struct SweepAxis {
+    pub field: String,
+    pub values: Vec<serde_json::Value>,
+}
+
+impl SweepAxis {
+    // The field's name without the path to it, for labels.
+    fn name(&self) -> &str {
+        self.field.rsplit('.').next().unwrap_or(&self.field)
+    }
+}
+
+// Renders the scene at `path` once for every value of `columns` and, if
+// given, every combination with the values of `rows`, for comparing
+// materials and settings side by side. The renders are laid out in that
+// grid, each labelled with its values; `options.columns` is ignored.
+pub fn render_sweep(
+    path: &str,
+    columns: &SweepAxis,
+    rows: Option<&SweepAxis>,
+    options: &ContactSheetOptions,
+) -> Result<ImageBuffer, Box<dyn Error>> {
+    let source = std::fs::read_to_string(path)?;
+    let config = SceneFormat::from_path(path).parse(&source)?;
+    let base = serde_json::to_value(&config)?;
+    let row_values = rows.map_or(vec![None], |axis| axis.values.iter().map(Some).collect());
+
+    let mut thumbnails = Vec::with_capacity(row_values.len() * columns.values.len());
+    for row_value in &row_values {
+        for column_value in &columns.values {
+            let mut scene = base.clone();
+            let mut label = Vec::new();
+            let settings = std::iter::once((columns, column_value)).chain(rows.zip(*row_value));
+            for (axis, value) in settings {
+                set_field(&mut scene, &axis.field, value.clone())?;
+                label.push(format!("{}={value}", axis.name()));
+            }
+            let source = serde_json::to_string(&scene)?;
+            let mut image = thumbnail(
+                Scene::from_source_at(&source, SceneFormat::Json, 0.0)?,
+                options,
+            )?;
+            stamp_image(&mut image, &label.join(" ").replace('"', ""));
+            thumbnails.push(image);
+        }
+    }
+    Ok(lay_out(&thumbnails, columns.values.len() as u32, options))
+}
+
+// Replaces the value at `field` in `scene`, which must already be there.
+fn set_field(
+    scene: &mut serde_json::Value,
+    field: &str,
+    value: serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    let mut target = scene;
+    for key in field.split('.') {
+        target = match target {
+            serde_json::Value::Array(items) => {
+                key.parse().ok().and_then(|i: usize| items.get_mut(i))
+            }
+            serde_json::Value::Object(fields) => fields.get_mut(key),
+            _ => None,
+        }
+        .ok_or_else(|| format!("scene has no field '{field}'"))?;
+    }
+    *target = value;
+    Ok(())
+}
+
+// Thumbnails in rows of `columns` cells, each centred in its cell.
+fn lay_out(thumbnails: &[ImageBuffer], columns: u32, options: &ContactSheetOptions) -> ImageBuffer {
+    let count = thumbnails.len() as u32;
+    let cell = options.cell_size.max(1);
+    let columns = columns.max(1);
+    let rows = count.div_ceil(columns);
+    let pitch = cell + options.gap;
+    let mut image = ImageBuffer::new(columns * pitch + options.gap, rows * pitch + options.gap);
+    image.pixels.fill(BACKGROUND);
+    for (index, thumbnail) in thumbnails.iter().enumerate() {
+        let (column, row) = (index as u32 % columns, index as u32 / columns);
+        let x0 = options.gap + column * pitch + (cell - thumbnail.width) / 2;
+        let y0 = options.gap + row * pitch + (cell - thumbnail.height) / 2;
+        for y in 0..thumbnail.height {
+            for x in 0..thumbnail.width {
+                image.set(x0 + x, y0 + y, thumbnail.get(x, y));
+            }
+        }
+    }
+    image
+}
+
+// A loaded scene with its own camera, lighting and exposure, at thumbnail
+// size and sample count.
+fn thumbnail(
+    (config, camera, world, lights): (SceneConfig, Camera, Arc<dyn Hittable>, Arc<LightSet>),
+    options: &ContactSheetOptions,
+) -> Result<ImageBuffer, Box<dyn Error>> {
+    let cell = options.cell_size.max(1) as f64;
+    let aspect_ratio = config.render.aspect_ratio();
+    let (width, height) = if aspect_ratio >= 1.0 {
+        (cell, cell / aspect_ratio)
+    } else {
+        (cell * aspect_ratio, cell)
+    };
+    let settings = RenderSettings {
+        width: (width.round() as u32).max(1),
+        height: (height.round() as u32).max(1),
+        pixel_aspect_ratio: 1.0,
+        samples_per_pixel: options.samples_per_pixel.max(1),
+        max_depth: config.render.max_depth.min(options.max_depth),
+        adaptive: None,
+        aovs: AovSelection::default(),
+        temporal: None,
+        ..config.render
+    };
+    let renderer = Renderer::new(world, config.environment()?).with_lights(lights);
+    let mut image = renderer.render(&camera, &settings);
+    let scale = exposure_scale(config.output.exposure);
+    for pixel in &mut image.pixels {
+        *pixel *= scale;
+    }
+    Ok(image)
+}
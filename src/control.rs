@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+// How often paused render threads look for a cancellation.
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+// Pauses and resumes renders from another thread, such as an embedding
+// GUI's. Pausing stops new tiles from starting; tiles already running
+// finish, then the render threads sleep without using the CPU until
+// `resume`. Nothing rendered so far is lost, and the render returns as if
+// it had never paused. Tiles start in rows from the top, or nearest the
+// focus when there is one, such as where a GUI's user is looking.
+#[derive(Default)]
+pub struct RenderControl {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    focus: Mutex<Option<(u32, u32)>>,
+}
+
+impl RenderControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    // Starts the tiles nearest pixel (`x`, `y`) first, from the next tile
+    // on; `None` goes back to rows from the top.
+    pub fn set_focus(&self, focus: Option<(u32, u32)>) {
+        *self.focus.lock().unwrap() = focus;
+    }
+
+    pub fn focus(&self) -> Option<(u32, u32)> {
+        *self.focus.lock().unwrap()
+    }
+
+    // Blocks the calling thread while the render is paused, unless
+    // `cancellation` is cancelled meanwhile.
+    pub(crate) fn wait_while_paused(&self, cancellation: Option<&CancellationToken>) {
+        let mut paused = self.paused.lock().unwrap();
+        let Some(cancellation) = cancellation else {
+            drop(self.resumed.wait_while(paused, |paused| *paused).unwrap());
+            return;
+        };
+        while *paused && !cancellation.is_cancelled() {
+            paused = self.resumed.wait_timeout(paused, CANCEL_POLL).unwrap().0;
+        }
+    }
+}
+
+// Stops a render early from another thread, such as when the user closes
+// a GUI's preview or presses a CLI's stop key. Tiles already running
+// finish and no new ones start, so the render returns soon with the image
+// as far as it got: unrendered tiles are black, and transparent when the
+// background is. Clones share the same state, so one can be handed to the
+// renderer and another kept to cancel with.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,90 @@
+use crate::renderer::{ImageBuffer, RenderPasses};
+use glam::{DVec3, Vec3};
+use std::error::Error;
+
+// Wraps Intel Open Image Denoise's ray tracing filter. The albedo and normal
+// passes are optional guides; OIDN only accepts normals together with albedo.
+pub struct Denoiser {
+    device: oidn::Device,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self {
+            device: oidn::Device::new(),
+        }
+    }
+
+    pub fn denoise(
+        &self,
+        beauty: &ImageBuffer,
+        albedo: Option<&ImageBuffer>,
+        normal: Option<&ImageBuffer>,
+    ) -> Result<ImageBuffer, Box<dyn Error>> {
+        let (width, height) = (beauty.width, beauty.height);
+        for guide in albedo.iter().chain(normal.iter()) {
+            if guide.width != width || guide.height != height {
+                return Err("denoiser guide passes must match the beauty image size".into());
+            }
+        }
+        if normal.is_some() && albedo.is_none() {
+            return Err("the normal pass can only be used together with albedo".into());
+        }
+
+        let color = to_f32(beauty);
+        let albedo = albedo.map(to_f32);
+        let normal = normal.map(to_f32);
+        let mut output = vec![0.0f32; color.len()];
+
+        let mut filter = oidn::RayTracing::new(&self.device);
+        filter
+            .hdr(true)
+            .image_dimensions(width as usize, height as usize);
+        match (&albedo, &normal) {
+            (Some(albedo), Some(normal)) => {
+                filter.albedo_normal(albedo, normal);
+            }
+            (Some(albedo), None) => {
+                filter.albedo(albedo);
+            }
+            _ => {}
+        }
+        filter
+            .filter(&color, &mut output)
+            .map_err(|e| format!("OIDN filter error: {:?}", e))?;
+        if let Err((_, message)) = self.device.get_error() {
+            return Err(format!("OIDN error: {}", message).into());
+        }
+
+        let mut image = ImageBuffer::new(width, height);
+        for (pixel, rgb) in image.pixels.iter_mut().zip(output.chunks_exact(3)) {
+            *pixel = Vec3::from_slice(rgb).as_dvec3();
+        }
+        // Coverage isn't noisy the way colour is.
+        image.alpha = beauty.alpha.clone();
+        Ok(image)
+    }
+
+    // Denoises the beauty pass using whichever guide passes were rendered.
+    pub fn denoise_passes(&self, passes: &RenderPasses) -> Result<ImageBuffer, Box<dyn Error>> {
+        let normal = passes.albedo.as_ref().and(passes.normal.as_ref());
+        self.denoise(&passes.beauty, passes.albedo.as_ref(), normal)
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_f32(image: &ImageBuffer) -> Vec<f32> {
+    image
+        .pixels
+        .iter()
+        .flat_map(|c| {
+            let c = if c.is_finite() { *c } else { DVec3::ZERO };
+            c.as_vec3().to_array()
+        })
+        .collect()
+}
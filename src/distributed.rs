@@ -0,0 +1,277 @@
+use crate::irradiance_cache::IrradianceCache;
+use crate::metrics::RenderProgress;
+use crate::output::scene_hash;
+use crate::renderer::{self, RenderPasses, RenderSettings, RenderedTile, Renderer, Tile};
+use crate::scene::Scene;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+
+// Port `raytracer worker` listens on when none is given.
+pub const DEFAULT_PORT: u16 = 7878;
+
+// Messages between a coordinator and a worker, one JSON object per line.
+// On connecting the worker loads its copy of the scene and says hello (or
+// sends an error); the coordinator then sends jobs, each answered with its
+// tiles in the order asked for, until it hangs up.
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+enum Message {
+    #[serde(rename = "hello")]
+    Hello { threads: usize, scene_hash: u64 },
+    #[serde(rename = "job")]
+    Job {
+        settings: RenderSettings,
+        tiles: Vec<Tile>,
+    },
+    #[serde(rename = "rendered")]
+    Rendered { tiles: Vec<RenderedTile> },
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.writer, message)?;
+        self.writer.write_all(b"\n")?;
+        Ok(self.writer.flush()?)
+    }
+
+    // `None` once the other end has hung up.
+    fn receive(&mut self) -> Result<Option<Message>, Box<dyn Error>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
+
+// Renders tiles of the scene at `path` for any coordinator that connects,
+// on a background thread; see `render`. The scene is loaded again for each
+// connection, so edits are picked up between renders. Returns the bound
+// address, which tells callers the port when `address` asks for port 0.
+pub fn serve(address: impl ToSocketAddrs, path: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    // Fail now rather than on the first connection if the path is wrong.
+    std::fs::metadata(path)?;
+    let listener = TcpListener::bind(address)?;
+    let bound = listener.local_addr()?;
+    let path = path.to_string();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let path = path.clone();
+            // A coordinator that hangs up mid-job only affects itself.
+            std::thread::spawn(move || {
+                if let Ok(mut connection) = Connection::new(stream) {
+                    if let Err(e) = work(&mut connection, &path) {
+                        let message = e.to_string();
+                        let _ = connection.send(&Message::Error { message });
+                    }
+                }
+            });
+        }
+    });
+    Ok(bound)
+}
+
+fn work(connection: &mut Connection, path: &str) -> Result<(), Box<dyn Error>> {
+    let hash = scene_hash(&std::fs::read(path)?);
+    let (config, camera, world, lights) = Scene::from_file(path)?;
+    let mut renderer = Renderer::new(world, config.environment()?).with_lights(lights);
+    if let Some(sample_map) = config.sample_map()? {
+        renderer = renderer.with_sample_map(Arc::new(sample_map));
+    }
+    connection.send(&Message::Hello {
+        threads: rayon::current_num_threads(),
+        scene_hash: hash,
+    })?;
+    // Each worker builds its own irradiance cache over the tiles it is
+    // given, so cached renders differ slightly from a local one. Photon
+    // maps depend only on the seed, so every worker traces the same one.
+    let mut cache = None;
+    let mut photons = None;
+    while let Some(message) = connection.receive()? {
+        let Message::Job { settings, tiles } = message else {
+            return Err("expected a job".into());
+        };
+        let cache =
+            cache.get_or_insert_with(|| settings.irradiance_cache.map(IrradianceCache::new));
+        let photons = photons.get_or_insert_with(|| renderer.photon_map(&settings));
+        let tiles = renderer
+            .render_tiles(&camera, &settings, cache.as_ref(), photons.as_ref(), tiles)
+            .into_iter()
+            .map(|(_, mut rendered)| {
+                rendered.clear_non_finite();
+                rendered
+            })
+            .collect();
+        connection.send(&Message::Rendered { tiles })?;
+    }
+    Ok(())
+}
+
+// Tiles waiting to be rendered, by their index in `split_tiles` order, and
+// the ones done so far.
+struct Schedule {
+    queue: VecDeque<(usize, Tile)>,
+    in_flight: usize,
+    rendered: Vec<(usize, Tile, RenderedTile)>,
+}
+
+// Renders the scene loaded from `scene`, whose camera and objects the
+// workers must share, by sending tiles to the workers at `workers` (see
+// `serve`) and merging what they send back. Each worker is kept busy with
+// as many tiles at a time as it has threads. A worker that fails or drops
+// its connection is reported through `report` and its tiles go to the
+// others; the render fails only if no worker is left to finish it. Only
+// the main scene file is compared, so workers must also have the same
+// includes and assets.
+pub fn render(
+    scene: &[u8],
+    settings: &RenderSettings,
+    workers: &[String],
+    progress: Option<&RenderProgress>,
+    report: impl Fn(&str) + Sync,
+) -> Result<RenderPasses, Box<dyn Error>> {
+    let tiles = renderer::split_tiles(settings);
+    let tile_count = tiles.len();
+    let schedule = Mutex::new(Schedule {
+        queue: tiles.into_iter().enumerate().collect(),
+        in_flight: 0,
+        rendered: Vec::with_capacity(tile_count),
+    });
+    let changed = Condvar::new();
+    if let Some(progress) = progress {
+        let (x0, y0, x1, y1) = settings.render_window();
+        progress.start((x1 - x0) as u64 * (y1 - y0) as u64, tile_count as u64);
+    }
+    let hash = scene_hash(scene);
+
+    std::thread::scope(|scope| {
+        for worker in workers {
+            let (schedule, changed, report) = (&schedule, &changed, &report);
+            scope.spawn(move || {
+                if let Err(e) = drive(worker, hash, settings, schedule, changed, progress) {
+                    report(&format!("worker {worker}: {e}"));
+                }
+            });
+        }
+    });
+
+    let mut rendered = schedule.into_inner().unwrap().rendered;
+    if rendered.len() < tile_count {
+        return Err(format!(
+            "no worker left to render the remaining {} of {tile_count} tiles",
+            tile_count - rendered.len()
+        )
+        .into());
+    }
+    // Films are summed in tile order, as in a local render.
+    rendered.sort_by_key(|(index, _, _)| *index);
+    let passes = renderer::assemble(
+        settings,
+        rendered
+            .into_iter()
+            .map(|(_, tile, rendered)| (tile, rendered))
+            .collect(),
+    );
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    Ok(passes)
+}
+
+// Feeds tiles to one worker until none are left. Tiles of a job that
+// fails are put back for the other workers.
+fn drive(
+    worker: &str,
+    hash: u64,
+    settings: &RenderSettings,
+    schedule: &Mutex<Schedule>,
+    changed: &Condvar,
+    progress: Option<&RenderProgress>,
+) -> Result<(), Box<dyn Error>> {
+    let address = if worker.contains(':') {
+        worker.to_string()
+    } else {
+        format!("{worker}:{DEFAULT_PORT}")
+    };
+    let mut connection = Connection::new(TcpStream::connect(address)?)?;
+    let threads = match connection.receive()? {
+        Some(Message::Hello { scene_hash, .. }) if scene_hash != hash => {
+            return Err("its scene file differs from this one".into());
+        }
+        Some(Message::Hello { threads, .. }) => threads.max(1),
+        Some(Message::Error { message }) => return Err(message.into()),
+        _ => return Err("expected a hello".into()),
+    };
+
+    loop {
+        let batch: Vec<(usize, Tile)> = {
+            let mut schedule = schedule.lock().unwrap();
+            // Tiles may still come back from a worker that fails.
+            while schedule.queue.is_empty() && schedule.in_flight > 0 {
+                schedule = changed.wait(schedule).unwrap();
+            }
+            let count = threads.min(schedule.queue.len());
+            schedule.in_flight += count;
+            schedule.queue.drain(..count).collect()
+        };
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let result = run_job(&mut connection, settings, &batch);
+        let mut schedule = schedule.lock().unwrap();
+        schedule.in_flight -= batch.len();
+        changed.notify_all();
+        match result {
+            Ok(tiles) => {
+                for ((index, tile), rendered) in batch.into_iter().zip(tiles) {
+                    if let Some(progress) = progress {
+                        progress.add_tile(tile.pixels(), rendered.camera_rays);
+                    }
+                    schedule.rendered.push((index, tile, rendered));
+                }
+            }
+            Err(e) => {
+                for tile in batch.into_iter().rev() {
+                    schedule.queue.push_front(tile);
+                }
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn run_job(
+    connection: &mut Connection,
+    settings: &RenderSettings,
+    batch: &[(usize, Tile)],
+) -> Result<Vec<RenderedTile>, Box<dyn Error>> {
+    connection.send(&Message::Job {
+        settings: *settings,
+        tiles: batch.iter().map(|(_, tile)| *tile).collect(),
+    })?;
+    match connection.receive()? {
+        Some(Message::Rendered { tiles }) if tiles.len() == batch.len() => Ok(tiles),
+        Some(Message::Error { message }) => Err(message.into()),
+        None => Err("connection closed".into()),
+        _ => Err("unexpected reply".into()),
+    }
+}
@@ -0,0 +1,521 @@
+use crate::accelerator::Accelerator;
+use crate::hittable::{HitRecord, Hittable, HittableList, AABB};
+use crate::interval::Interval;
+use crate::objects::mesh::Mesh;
+use crate::ray::Ray;
+use glam::DAffine3;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_uint};
+use std::ptr;
+use std::sync::Arc;
+
+// The few entry points of Embree 4 the backend uses, as declared in
+// `rtcore_device.h`, `rtcore_scene.h`, `rtcore_geometry.h` and
+// `rtcore_ray.h`. The structs mirror the C layouts, fields that only
+// Embree reads included.
+#[allow(dead_code)]
+mod ffi {
+    use std::ffi::c_void;
+    use std::os::raw::{c_char, c_int, c_uint};
+
+    pub type Device = *mut c_void;
+    pub type Scene = *mut c_void;
+    pub type Geometry = *mut c_void;
+
+    pub const GEOMETRY_TYPE_TRIANGLE: c_int = 0;
+    pub const GEOMETRY_TYPE_USER: c_int = 120;
+    pub const GEOMETRY_TYPE_INSTANCE: c_int = 121;
+    pub const BUFFER_TYPE_INDEX: c_int = 0;
+    pub const BUFFER_TYPE_VERTEX: c_int = 1;
+    pub const FORMAT_UINT3: c_int = 0x5003;
+    pub const FORMAT_FLOAT3: c_int = 0x9003;
+    pub const FORMAT_FLOAT3X4_COLUMN_MAJOR: c_int = 0x9244;
+    pub const SCENE_FLAG_ROBUST: c_int = 4;
+    pub const BUILD_QUALITY_HIGH: c_int = 2;
+    pub const ERROR_NONE: c_int = 0;
+    pub const INVALID_GEOMETRY_ID: c_uint = c_uint::MAX;
+
+    #[repr(C, align(16))]
+    pub struct Bounds {
+        pub lower: [f32; 3],
+        pub align0: f32,
+        pub upper: [f32; 3],
+        pub align1: f32,
+    }
+
+    #[repr(C, align(16))]
+    pub struct RtcRay {
+        pub org: [f32; 3],
+        pub tnear: f32,
+        pub dir: [f32; 3],
+        pub time: f32,
+        pub tfar: f32,
+        pub mask: c_uint,
+        pub id: c_uint,
+        pub flags: c_uint,
+    }
+
+    #[repr(C, align(16))]
+    pub struct RtcHit {
+        pub ng: [f32; 3],
+        pub u: f32,
+        pub v: f32,
+        pub prim_id: c_uint,
+        pub geom_id: c_uint,
+        pub inst_id: [c_uint; 1],
+        pub inst_prim_id: [c_uint; 1],
+    }
+
+    #[repr(C)]
+    pub struct RayHit {
+        pub ray: RtcRay,
+        pub hit: RtcHit,
+    }
+
+    #[repr(C)]
+    pub struct BoundsArguments {
+        pub user: *mut c_void,
+        pub prim_id: c_uint,
+        pub time_step: c_uint,
+        pub bounds: *mut Bounds,
+    }
+
+    // With one ray at a time, as `rtcIntersect1` and `rtcOccluded1` pass
+    // them, `ray` points to a single `RayHit` or `RtcRay`.
+    #[repr(C)]
+    pub struct QueryArguments {
+        pub valid: *mut c_int,
+        pub user: *mut c_void,
+        pub prim_id: c_uint,
+        pub context: *mut c_void,
+        pub ray: *mut c_void,
+        pub n: c_uint,
+        pub geom_id: c_uint,
+    }
+
+    pub type BoundsFunction = unsafe extern "C" fn(*const BoundsArguments);
+    pub type QueryFunction = unsafe extern "C" fn(*const QueryArguments);
+
+    #[link(name = "embree4")]
+    extern "C" {
+        pub fn rtcNewDevice(config: *const c_char) -> Device;
+        pub fn rtcGetDeviceError(device: Device) -> c_int;
+        pub fn rtcReleaseDevice(device: Device);
+        pub fn rtcNewScene(device: Device) -> Scene;
+        pub fn rtcSetSceneBuildQuality(scene: Scene, quality: c_int);
+        pub fn rtcSetSceneFlags(scene: Scene, flags: c_int);
+        pub fn rtcCommitScene(scene: Scene);
+        pub fn rtcReleaseScene(scene: Scene);
+        pub fn rtcNewGeometry(device: Device, kind: c_int) -> Geometry;
+        pub fn rtcSetGeometryUserPrimitiveCount(geometry: Geometry, count: c_uint);
+        pub fn rtcSetGeometryUserData(geometry: Geometry, user: *mut c_void);
+        pub fn rtcSetGeometryBoundsFunction(
+            geometry: Geometry,
+            bounds: BoundsFunction,
+            user: *mut c_void,
+        );
+        pub fn rtcSetGeometryIntersectFunction(geometry: Geometry, intersect: QueryFunction);
+        pub fn rtcSetGeometryOccludedFunction(geometry: Geometry, occluded: QueryFunction);
+        pub fn rtcSetNewGeometryBuffer(
+            geometry: Geometry,
+            kind: c_int,
+            slot: c_uint,
+            format: c_int,
+            stride: usize,
+            count: usize,
+        ) -> *mut c_void;
+        pub fn rtcSetGeometryInstancedScene(geometry: Geometry, scene: Scene);
+        pub fn rtcSetGeometryTransform(
+            geometry: Geometry,
+            time_step: c_uint,
+            format: c_int,
+            transform: *const c_void,
+        );
+        pub fn rtcCommitGeometry(geometry: Geometry);
+        pub fn rtcAttachGeometry(scene: Scene, geometry: Geometry) -> c_uint;
+        pub fn rtcReleaseGeometry(geometry: Geometry);
+        pub fn rtcIntersect1(scene: Scene, rayhit: *mut RayHit, args: *mut c_void);
+        pub fn rtcOccluded1(scene: Scene, ray: *mut RtcRay, args: *mut c_void);
+    }
+}
+
+// The query being traced on this thread: Embree only sees the f32 copy of
+// the ray, so its callbacks test objects against the f64 original, and
+// keep the closest hit found so far by object and distance.
+#[derive(Clone, Copy)]
+struct Query {
+    ray: Ray,
+    interval: Interval,
+    closest: Option<(usize, f64)>,
+}
+
+thread_local! {
+    static QUERY: Cell<Option<Query>> = const { Cell::new(None) };
+}
+
+// Embree intersects meshes itself, as triangle geometries, with those
+// placed by a transform instanced from one scene per mesh. Analytic
+// objects go into one user geometry with a primitive per object, for
+// which Embree builds the hierarchy over their boxes and calls back into
+// Rust for every object a ray reaches. Records and shading stay in Rust:
+// the object Embree finds closest is hit again in f64 for its record.
+pub struct EmbreeScene {
+    device: ffi::Device,
+    scene: ffi::Scene,
+    // The scenes of the meshes instanced by a transform, one per mesh.
+    instanced: Vec<ffi::Scene>,
+    // The object each geometry of the top scene was built from, by
+    // geometry id; `None` for the user geometry of analytic objects.
+    geometries: Vec<Option<Arc<dyn Hittable>>>,
+    // Boxed so the callbacks can keep a pointer to them.
+    analytic: Box<HittableList>,
+    // Tested directly, as Embree needs a box for every primitive.
+    unbounded: HittableList,
+    bounds: Option<AABB>,
+    triangles: usize,
+}
+
+// Committed Embree scenes may be traced from any number of threads, and the
+// objects they call back into are `Send + Sync` themselves.
+unsafe impl Send for EmbreeScene {}
+unsafe impl Sync for EmbreeScene {}
+
+impl EmbreeScene {
+    pub fn new(objects: HittableList) -> Result<Self, String> {
+        let (bounded, unbounded): (HittableList, HittableList) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+        let bounds = if unbounded.is_empty() {
+            bounded.bounding_box()
+        } else {
+            None
+        };
+        // SAFETY: the device, scenes and geometries are used as the Embree
+        // API documents; the meshes whose buffers are copied are kept in
+        // `geometries`, and the analytic objects behind the user data
+        // outlive the scene, which `drop` releases before them.
+        unsafe {
+            let device = ffi::rtcNewDevice(ptr::null::<c_char>());
+            if device.is_null() {
+                return Err("could not create an Embree device".into());
+            }
+            let scene = ffi::rtcNewScene(device);
+            ffi::rtcSetSceneBuildQuality(scene, ffi::BUILD_QUALITY_HIGH);
+            ffi::rtcSetSceneFlags(scene, ffi::SCENE_FLAG_ROBUST);
+            let mut built = Self {
+                device,
+                scene,
+                instanced: Vec::new(),
+                geometries: Vec::new(),
+                analytic: Box::new(HittableList::new()),
+                unbounded,
+                bounds,
+                triangles: 0,
+            };
+            let mut mesh_scenes = HashMap::new();
+            for object in bounded {
+                // Meshes that shadow rays may pass through stay with the
+                // analytic objects, whose occlusion their materials decide.
+                let (mesh, transform) = placed_mesh(&*object);
+                let geometry = match mesh.filter(|mesh| !mesh.is_see_through()) {
+                    None => {
+                        built.analytic.push(object);
+                        continue;
+                    }
+                    Some(mesh) if transform == DAffine3::IDENTITY => {
+                        built.triangles += mesh.triangle_count();
+                        triangle_geometry(device, mesh)
+                    }
+                    Some(mesh) => {
+                        let instanced = *mesh_scenes
+                            .entry(mesh as *const Mesh)
+                            .or_insert_with(|| built.instance_scene(mesh));
+                        let geometry = ffi::rtcNewGeometry(device, ffi::GEOMETRY_TYPE_INSTANCE);
+                        ffi::rtcSetGeometryInstancedScene(geometry, instanced);
+                        let columns = transform.to_cols_array().map(|x| x as f32);
+                        ffi::rtcSetGeometryTransform(
+                            geometry,
+                            0,
+                            ffi::FORMAT_FLOAT3X4_COLUMN_MAJOR,
+                            columns.as_ptr() as *const c_void,
+                        );
+                        ffi::rtcCommitGeometry(geometry);
+                        geometry
+                    }
+                };
+                built.attach(geometry, Some(object));
+            }
+            if !built.analytic.is_empty() {
+                let user = &mut *built.analytic as *mut HittableList as *mut c_void;
+                let geometry = ffi::rtcNewGeometry(device, ffi::GEOMETRY_TYPE_USER);
+                ffi::rtcSetGeometryUserPrimitiveCount(geometry, built.analytic.len() as c_uint);
+                ffi::rtcSetGeometryUserData(geometry, user);
+                ffi::rtcSetGeometryBoundsFunction(geometry, object_bounds, user);
+                ffi::rtcSetGeometryIntersectFunction(geometry, intersect);
+                ffi::rtcSetGeometryOccludedFunction(geometry, occluded);
+                ffi::rtcCommitGeometry(geometry);
+                built.attach(geometry, None);
+            }
+            ffi::rtcCommitScene(scene);
+            let error = ffi::rtcGetDeviceError(device);
+            if error != ffi::ERROR_NONE {
+                return Err(format!("Embree failed to build the scene (error {error})"));
+            }
+            Ok(built)
+        }
+    }
+
+    // The triangles Embree intersects itself, counting each instanced mesh
+    // once.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles
+    }
+
+    // Attaches a committed geometry to the top scene, built from `object`.
+    unsafe fn attach(&mut self, geometry: ffi::Geometry, object: Option<Arc<dyn Hittable>>) {
+        let id = ffi::rtcAttachGeometry(self.scene, geometry) as usize;
+        ffi::rtcReleaseGeometry(geometry);
+        if self.geometries.len() <= id {
+            self.geometries.resize(id + 1, None);
+        }
+        self.geometries[id] = object;
+    }
+
+    // A committed scene of the mesh alone, for instances to place.
+    unsafe fn instance_scene(&mut self, mesh: &Mesh) -> ffi::Scene {
+        let scene = ffi::rtcNewScene(self.device);
+        ffi::rtcSetSceneBuildQuality(scene, ffi::BUILD_QUALITY_HIGH);
+        ffi::rtcSetSceneFlags(scene, ffi::SCENE_FLAG_ROBUST);
+        let geometry = triangle_geometry(self.device, mesh);
+        ffi::rtcAttachGeometry(scene, geometry);
+        ffi::rtcReleaseGeometry(geometry);
+        ffi::rtcCommitScene(scene);
+        self.instanced.push(scene);
+        self.triangles += mesh.triangle_count();
+        scene
+    }
+
+    // Runs `trace` with `query` set for the callbacks, and returns what
+    // they left of it.
+    fn traced(&self, query: Query, trace: impl FnOnce()) -> Query {
+        let outer = QUERY.with(|q| q.replace(Some(query)));
+        trace();
+        QUERY.with(|q| q.replace(outer)).unwrap_or(query)
+    }
+}
+
+impl Drop for EmbreeScene {
+    fn drop(&mut self) {
+        // SAFETY: all were created in `new` and are released once.
+        unsafe {
+            ffi::rtcReleaseScene(self.scene);
+            for &scene in &self.instanced {
+                ffi::rtcReleaseScene(scene);
+            }
+            ffi::rtcReleaseDevice(self.device);
+        }
+    }
+}
+
+// The mesh an object is, possibly behind transforms, and the transform
+// that places it.
+fn placed_mesh(object: &dyn Hittable) -> (Option<&Mesh>, DAffine3) {
+    match object.as_instance() {
+        Some((inner, transform)) => {
+            let (mesh, inner_transform) = placed_mesh(inner);
+            (mesh, transform * inner_transform)
+        }
+        None => (object.as_mesh(), DAffine3::IDENTITY),
+    }
+}
+
+// A committed triangle geometry over the mesh's vertices and faces, in the
+// mesh's own space.
+unsafe fn triangle_geometry(device: ffi::Device, mesh: &Mesh) -> ffi::Geometry {
+    let geometry = ffi::rtcNewGeometry(device, ffi::GEOMETRY_TYPE_TRIANGLE);
+    let vertices = ffi::rtcSetNewGeometryBuffer(
+        geometry,
+        ffi::BUFFER_TYPE_VERTEX,
+        0,
+        ffi::FORMAT_FLOAT3,
+        3 * std::mem::size_of::<f32>(),
+        mesh.vertex_count(),
+    ) as *mut [f32; 3];
+    for (i, position) in mesh.positions().enumerate() {
+        *vertices.add(i) = position.as_vec3().to_array();
+    }
+    let indices = ffi::rtcSetNewGeometryBuffer(
+        geometry,
+        ffi::BUFFER_TYPE_INDEX,
+        0,
+        ffi::FORMAT_UINT3,
+        3 * std::mem::size_of::<u32>(),
+        mesh.triangle_count(),
+    ) as *mut [u32; 3];
+    for (i, corners) in mesh.face_corners().enumerate() {
+        *indices.add(i) = corners;
+    }
+    ffi::rtcCommitGeometry(geometry);
+    geometry
+}
+
+// Slack on f32 distances, as the four-wide BVH allows its far bound.
+const SLACK: f32 = f32::EPSILON * 4.0;
+
+// The interval is widened so that the f32 copy never loses a hit the f64
+// ray has.
+fn rtc_ray(ray: &Ray, interval: Interval) -> ffi::RtcRay {
+    ffi::RtcRay {
+        org: ray.origin.as_vec3().to_array(),
+        tnear: (interval.min.max(0.0) as f32) * (1.0 - SLACK),
+        dir: ray.direction.as_vec3().to_array(),
+        time: ray.time as f32,
+        tfar: (interval.max as f32) * (1.0 + SLACK),
+        mask: u32::MAX,
+        id: 0,
+        flags: 0,
+    }
+}
+
+impl Hittable for EmbreeScene {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let unbounded = self.unbounded.hit(ray, interval);
+        let interval = interval.with_max(unbounded.as_ref().map_or(interval.max, |rec| rec.t));
+        let mut rayhit = ffi::RayHit {
+            ray: rtc_ray(ray, interval),
+            hit: ffi::RtcHit {
+                ng: [0.0; 3],
+                u: 0.0,
+                v: 0.0,
+                prim_id: ffi::INVALID_GEOMETRY_ID,
+                geom_id: ffi::INVALID_GEOMETRY_ID,
+                inst_id: [ffi::INVALID_GEOMETRY_ID],
+                inst_prim_id: [ffi::INVALID_GEOMETRY_ID],
+            },
+        };
+        let query = Query {
+            ray: *ray,
+            interval,
+            closest: None,
+        };
+        // SAFETY: `rayhit` is a valid ray and hit for the committed scene.
+        let query = self.traced(query, || unsafe {
+            ffi::rtcIntersect1(self.scene, &mut rayhit, ptr::null_mut())
+        });
+        // The callbacks only keep which analytic object is closest; its
+        // record is found again here, where it can borrow the object.
+        let analytic = query.closest.and_then(|(index, t)| {
+            let reach = interval.with_max(t + t.abs() * 4.0 * f64::EPSILON);
+            self.analytic[index].hit(ray, reach)
+        });
+        // A mesh Embree found closer is hit again in f64 along the whole
+        // interval, as its f32 distance may fall either side of the f64 one.
+        let top = match rayhit.hit.inst_id[0] {
+            ffi::INVALID_GEOMETRY_ID => rayhit.hit.geom_id,
+            instance => instance,
+        };
+        let mesh = self
+            .geometries
+            .get(top as usize)
+            .and_then(Option::as_ref)
+            .and_then(|object| object.hit(ray, interval));
+        [mesh, analytic, unbounded]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        if self.unbounded.hit_any(ray, interval) {
+            return true;
+        }
+        let mut shadow = rtc_ray(ray, interval);
+        let query = Query {
+            ray: *ray,
+            interval,
+            closest: None,
+        };
+        // SAFETY: `shadow` is a valid ray for the committed scene.
+        self.traced(query, || unsafe {
+            ffi::rtcOccluded1(self.scene, &mut shadow, ptr::null_mut())
+        });
+        // Embree marks occluded rays with a far distance of -infinity.
+        shadow.tfar == f32::NEG_INFINITY
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.bounds
+    }
+}
+
+// For benchmarks beside the other backends; panics where `new` would fail.
+impl Accelerator for EmbreeScene {
+    fn build(objects: HittableList) -> Self {
+        Self::new(objects).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn refit(&mut self, objects: HittableList) {
+        *self = Self::build(objects);
+    }
+}
+
+// SAFETY for the callbacks: Embree passes back the `HittableList` of
+// analytic objects given to the user geometry as its user data, and a primitive index below its length.
+unsafe extern "C" fn object_bounds(args: *const ffi::BoundsArguments) {
+    let args = &*args;
+    let objects = &*(args.user as *const HittableList);
+    let bounds = objects[args.prim_id as usize]
+        .bounding_box()
+        .unwrap_or_default();
+    // Rounded outwards, as the four-wide BVH rounds its f32 boxes.
+    let lower = bounds.min.as_vec3() - bounds.min.abs().as_vec3() * 1e-6 - 1e-7;
+    let upper = bounds.max.as_vec3() + bounds.max.abs().as_vec3() * 1e-6 + 1e-7;
+    *args.bounds = ffi::Bounds {
+        lower: lower.to_array(),
+        align0: 0.0,
+        upper: upper.to_array(),
+        align1: 0.0,
+    };
+}
+
+unsafe extern "C" fn intersect(args: *const ffi::QueryArguments) {
+    let args = &*args;
+    if *args.valid == 0 {
+        return;
+    }
+    let objects = &*(args.user as *const HittableList);
+    let index = args.prim_id as usize;
+    let Some(mut query) = QUERY.with(Cell::get) else {
+        return;
+    };
+    let closest = query.closest.map_or(query.interval.max, |(_, t)| t);
+    let Some(rec) = objects[index].hit(&query.ray, query.interval.with_max(closest)) else {
+        return;
+    };
+    query.closest = Some((index, rec.t));
+    QUERY.with(|q| q.set(Some(query)));
+    // Embree culls what lies beyond the far distance, so it is kept at or
+    // past the f64 hit.
+    let rayhit = &mut *(args.ray as *mut ffi::RayHit);
+    rayhit.ray.tfar = (rec.t as f32) * (1.0 + SLACK);
+    rayhit.hit.prim_id = args.prim_id;
+    rayhit.hit.geom_id = args.geom_id;
+    rayhit.hit.inst_id = [ffi::INVALID_GEOMETRY_ID];
+    rayhit.hit.ng = rec.normal.as_vec3().to_array();
+}
+
+unsafe extern "C" fn occluded(args: *const ffi::QueryArguments) {
+    let args = &*args;
+    if *args.valid == 0 {
+        return;
+    }
+    let objects = &*(args.user as *const HittableList);
+    let Some(query) = QUERY.with(Cell::get) else {
+        return;
+    };
+    if objects[args.prim_id as usize].hit_any(&query.ray, query.interval) {
+        (*(args.ray as *mut ffi::RtcRay)).tfar = f32::NEG_INFINITY;
+    }
+}
@@ -0,0 +1,392 @@
+use crate::color::XYZ_TO_SRGB;
+use crate::sampler::Sampler;
+use glam::DVec3;
+use std::error::Error;
+use std::f64::consts::PI;
+
+pub trait Environment: Send + Sync {
+    fn value(&self, direction: DVec3) -> DVec3;
+
+    // Environments that can be importance sampled return a direction and its
+    // solid-angle pdf; the rest are only reached by BSDF sampling.
+    fn sample(&self, _sampler: &mut dyn Sampler) -> Option<(DVec3, f64)> {
+        None
+    }
+
+    fn pdf(&self, _direction: DVec3) -> f64 {
+        0.0
+    }
+}
+
+pub struct SolidBackground {
+    pub color: DVec3,
+}
+
+impl SolidBackground {
+    pub fn new(color: DVec3) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for SolidBackground {
+    fn value(&self, _direction: DVec3) -> DVec3 {
+        self.color
+    }
+}
+
+pub struct SkyGradient {
+    pub horizon: DVec3,
+    pub zenith: DVec3,
+}
+
+impl SkyGradient {
+    pub fn new(horizon: DVec3, zenith: DVec3) -> Self {
+        Self { horizon, zenith }
+    }
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        Self::new(DVec3::ONE, DVec3::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for SkyGradient {
+    fn value(&self, direction: DVec3) -> DVec3 {
+        let t = 0.5 * (direction.normalize().y + 1.0);
+        (1.0 - t) * self.horizon + t * self.zenith
+    }
+}
+
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<DVec3>,
+    intensity: f64,
+    marginal_cdf: Vec<f64>,
+    conditional_cdfs: Vec<Vec<f64>>,
+    total_weight: f64,
+}
+
+impl EnvironmentMap {
+    pub fn new(path: &str, intensity: f64) -> Result<Self, Box<dyn Error>> {
+        let image = image::open(path)?.into_rgb32f();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image
+            .pixels()
+            .map(|p| DVec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+        Ok(Self::from_pixels(width, height, pixels, intensity))
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<DVec3>, intensity: f64) -> Self {
+        let mut conditional_cdfs = Vec::with_capacity(height);
+        let mut row_weights = Vec::with_capacity(height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            let mut cdf = Vec::with_capacity(width);
+            let mut sum = 0.0;
+            for x in 0..width {
+                sum += luminance(pixels[y * width + x]) * sin_theta;
+                cdf.push(sum);
+            }
+            row_weights.push(sum);
+            conditional_cdfs.push(cdf);
+        }
+
+        let mut marginal_cdf = Vec::with_capacity(height);
+        let mut total_weight = 0.0;
+        for w in &row_weights {
+            total_weight += w;
+            marginal_cdf.push(total_weight);
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+            intensity,
+            marginal_cdf,
+            conditional_cdfs,
+            total_weight,
+        }
+    }
+
+    fn texel(&self, direction: DVec3) -> (usize, usize) {
+        let d = direction.normalize();
+        let u = d.x.atan2(-d.z) / (2.0 * PI) + 0.5;
+        let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        (x, y)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn value(&self, direction: DVec3) -> DVec3 {
+        let (x, y) = self.texel(direction);
+        self.intensity * self.pixels[y * self.width + x]
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(DVec3, f64)> {
+        if self.total_weight <= 0.0 {
+            return None;
+        }
+        let target = sampler.next_1d() * self.total_weight;
+        let y = self
+            .marginal_cdf
+            .partition_point(|&c| c < target)
+            .min(self.height - 1);
+        let row = &self.conditional_cdfs[y];
+        let target = sampler.next_1d() * row[self.width - 1];
+        let x = row.partition_point(|&c| c < target).min(self.width - 1);
+
+        let (jx, jy) = sampler.next_2d();
+        let u = (x as f64 + jx) / self.width as f64;
+        let v = (y as f64 + jy) / self.height as f64;
+        let direction = map_direction(u, v);
+
+        let pdf = self.pdf(direction);
+        if pdf > 0.0 {
+            Some((direction, pdf))
+        } else {
+            None
+        }
+    }
+
+    fn pdf(&self, direction: DVec3) -> f64 {
+        let (x, y) = self.texel(direction);
+        let sin_theta = (PI * (y as f64 + 0.5) / self.height as f64).sin();
+        if sin_theta <= 0.0 || self.total_weight <= 0.0 {
+            return 0.0;
+        }
+        let weight = luminance(self.pixels[y * self.width + x]) * sin_theta;
+        let pdf_image = weight / self.total_weight * (self.width * self.height) as f64;
+        pdf_image / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// Direction at (`u`, `v`) of an equirectangular map, with -Z in the middle.
+fn map_direction(u: f64, v: f64) -> DVec3 {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    DVec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+// Preetham, Shirley and Smits' analytic daylight: the clear sky through
+// air of the given turbidity, from 2 for very clear air to 10 for haze,
+// plus the sun's disc, dimmed and reddened by the air it passes through.
+// Radiance is in units of 10^4 cd/m^2 times `intensity`, which puts a
+// clear zenith around 1 and the sun around 10^5. Below the horizon it is
+// black; outdoor scenes give it a ground. Sky and sun are importance
+// sampled separately: the sky from a coarse map baked from the model and
+// the sun uniformly over its disc.
+pub struct SunSky {
+    sky: Preetham,
+    intensity: f64,
+    sun_radiance: DVec3,
+    sky_map: EnvironmentMap,
+}
+
+struct Preetham {
+    sun: DVec3,
+    // Radiance at the zenith in xyY, and the Perez coefficients of each of
+    // Y, x and y.
+    zenith: DVec3,
+    perez: [[f64; 5]; 3],
+}
+
+// Angular radius of the sun, in radians.
+const SUN_RADIUS: f64 = 0.00465;
+// Illuminance of the sun at the top of the atmosphere, in 10^4 lux.
+const SUN_ILLUMINANCE: f64 = 12.8;
+// Size of the map the sky is sampled from.
+const SKY_MAP_WIDTH: usize = 128;
+const SKY_MAP_HEIGHT: usize = 64;
+// Share of samples aimed at the sun while it is up.
+const SUN_SAMPLES: f64 = 0.5;
+
+impl SunSky {
+    // The sun at `elevation` above the horizon and `azimuth` clockwise
+    // from -Z seen from above, both in radians.
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64, intensity: f64) -> Self {
+        let sun = DVec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        let t = turbidity;
+        let theta_s = (PI / 2.0 - elevation).clamp(0.0, PI / 2.0);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let angles = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+            let row = |r: [f64; 4]| r.iter().zip(angles).map(|(a, b)| a * b).sum::<f64>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith = DVec3::new(
+            // kcd/m^2 to 10^4 cd/m^2.
+            0.1 * luminance,
+            chromaticity([
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ]),
+            chromaticity([
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ]),
+        );
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let solid_angle = 2.0 * PI * (1.0 - SUN_RADIUS.cos());
+        let sun_radiance =
+            sun_transmittance(PI / 2.0 - elevation, t) * SUN_ILLUMINANCE / solid_angle;
+
+        let sky = Preetham { sun, zenith, perez };
+        let mut pixels = Vec::with_capacity(SKY_MAP_WIDTH * SKY_MAP_HEIGHT);
+        for y in 0..SKY_MAP_HEIGHT {
+            for x in 0..SKY_MAP_WIDTH {
+                let u = (x as f64 + 0.5) / SKY_MAP_WIDTH as f64;
+                let v = (y as f64 + 0.5) / SKY_MAP_HEIGHT as f64;
+                pixels.push(sky.radiance(map_direction(u, v)));
+            }
+        }
+        Self {
+            sky,
+            intensity,
+            sun_radiance,
+            sky_map: EnvironmentMap::from_pixels(SKY_MAP_WIDTH, SKY_MAP_HEIGHT, pixels, 1.0),
+        }
+    }
+
+    fn sun_visible(&self) -> bool {
+        self.sky.sun.y > -SUN_RADIUS
+    }
+
+    fn in_sun(&self, direction: DVec3) -> bool {
+        self.sun_visible() && direction.normalize().dot(self.sky.sun) >= SUN_RADIUS.cos()
+    }
+}
+
+impl Preetham {
+    fn radiance(&self, direction: DVec3) -> DVec3 {
+        let d = direction.normalize();
+        if d.y <= 0.0 {
+            return DVec3::ZERO;
+        }
+        let cos_gamma = d.dot(self.sun).clamp(-1.0, 1.0);
+        let cos_sun = self.sun.y.max(0.0);
+        let perez = |[a, b, c, d, e]: [f64; 5], cos_theta: f64, cos_gamma: f64| {
+            let gamma = cos_gamma.acos();
+            (1.0 + a * (b / cos_theta.max(1e-3)).exp())
+                * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+        };
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * perez(self.perez[i], d.y, cos_gamma)
+                / perez(self.perez[i], 1.0, cos_sun)
+        });
+        if y <= 0.0 {
+            return DVec3::ZERO;
+        }
+        let xyz = DVec3::new(x / y, 1.0, (1.0 - x - y) / y) * luminance;
+        (XYZ_TO_SRGB * xyz).max(DVec3::ZERO)
+    }
+}
+
+impl Environment for SunSky {
+    fn value(&self, direction: DVec3) -> DVec3 {
+        let mut radiance = self.sky.radiance(direction);
+        if self.in_sun(direction) && direction.y > 0.0 {
+            radiance += self.sun_radiance;
+        }
+        self.intensity * radiance
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(DVec3, f64)> {
+        if !self.sun_visible() {
+            return self.sky_map.sample(sampler);
+        }
+        let direction = if sampler.next_1d() < SUN_SAMPLES {
+            let (u1, u2) = sampler.next_2d();
+            let cosine = 1.0 - u1 * (1.0 - SUN_RADIUS.cos());
+            let r = (1.0 - cosine * cosine).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+            let sun = self.sky.sun;
+            let (tangent, bitangent) = sun.any_orthonormal_pair();
+            r * phi.cos() * tangent + r * phi.sin() * bitangent + cosine * sun
+        } else {
+            self.sky_map.sample(sampler)?.0
+        };
+        let pdf = self.pdf(direction);
+        (pdf > 0.0).then_some((direction, pdf))
+    }
+
+    fn pdf(&self, direction: DVec3) -> f64 {
+        if !self.sun_visible() {
+            return self.sky_map.pdf(direction);
+        }
+        let sun = if self.in_sun(direction) {
+            1.0 / (2.0 * PI * (1.0 - SUN_RADIUS.cos()))
+        } else {
+            0.0
+        };
+        SUN_SAMPLES * sun + (1.0 - SUN_SAMPLES) * self.sky_map.pdf(direction)
+    }
+}
+
+// Fraction of sunlight at the top of the atmosphere that reaches the
+// ground with the sun `theta` from the zenith, from Rayleigh and aerosol
+// scattering at a red, green and blue wavelength, following the appendix
+// of Preetham et al. without ozone and water vapour absorption.
+fn sun_transmittance(theta: f64, turbidity: f64) -> DVec3 {
+    let degrees = theta.to_degrees();
+    if degrees >= 93.0 {
+        return DVec3::ZERO;
+    }
+    // Kasten's relative optical air mass.
+    let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    // In micrometres.
+    let wavelengths = DVec3::new(0.68, 0.55, 0.44);
+    let transmit = |lambda: f64| {
+        let rayleigh = (-0.008735 * lambda.powf(-4.08) * air_mass).exp();
+        let aerosol = (-beta * lambda.powf(-1.3) * air_mass).exp();
+        rayleigh * aerosol
+    };
+    DVec3::new(
+        transmit(wavelengths.x),
+        transmit(wavelengths.y),
+        transmit(wavelengths.z),
+    )
+}
+
+fn luminance(c: DVec3) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
@@ -0,0 +1,121 @@
+use crate::scene::SceneValidationError;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Why a scene, or a texture or mesh it refers to, couldn't be loaded. Each
+// carries the path of the file at fault.
+#[derive(Debug)]
+pub enum RenderError {
+    // The file couldn't be read at all, e.g. because it doesn't exist.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    // An image in a format that isn't supported, or that is corrupt.
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    // A mesh file that doesn't parse as its format.
+    Mesh { path: PathBuf, message: String },
+    // A scene file that doesn't parse, or refers to things it doesn't
+    // define.
+    Scene { path: PathBuf, message: String },
+    // A scene file that parses, with values that can't be rendered.
+    Invalid {
+        path: PathBuf,
+        source: SceneValidationError,
+    },
+}
+
+impl RenderError {
+    pub fn io(path: impl AsRef<Path>, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    // Image errors that come from reading the file are reported as such.
+    pub fn image(path: impl AsRef<Path>, source: image::ImageError) -> Self {
+        match source {
+            image::ImageError::IoError(source) => Self::io(path, source),
+            source => Self::Image {
+                path: path.as_ref().to_path_buf(),
+                source,
+            },
+        }
+    }
+
+    pub fn mesh(path: impl AsRef<Path>, error: impl fmt::Display) -> Self {
+        Self::Mesh {
+            path: path.as_ref().to_path_buf(),
+            message: error.to_string(),
+        }
+    }
+
+    // Whatever went wrong while building the scene at `path`. Errors from
+    // the textures and meshes it loads keep their own path and kind.
+    pub fn scene(path: impl AsRef<Path>, error: Box<dyn Error>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let error = match error.downcast::<RenderError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<SceneValidationError>() {
+            Ok(source) => {
+                return Self::Invalid {
+                    path,
+                    source: *source,
+                }
+            }
+            Err(error) => error,
+        };
+        match error.downcast::<std::io::Error>() {
+            Ok(source) => Self::Io {
+                path,
+                source: *source,
+            },
+            Err(error) => Self::Scene {
+                path,
+                message: error.to_string(),
+            },
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. }
+            | Self::Image { path, .. }
+            | Self::Mesh { path, .. }
+            | Self::Scene { path, .. }
+            | Self::Invalid { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            Self::Io { source, .. } => write!(f, "{path}: {source}"),
+            Self::Image { source, .. } => write!(f, "{path}: {source}"),
+            Self::Mesh { message, .. } | Self::Scene { message, .. } => {
+                write!(f, "{path}: {message}")
+            }
+            Self::Invalid { source, .. } => write!(f, "{path}: {source}"),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Image { source, .. } => Some(source),
+            Self::Invalid { source, .. } => Some(source),
+            Self::Mesh { .. } | Self::Scene { .. } => None,
+        }
+    }
+}
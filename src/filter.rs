@@ -0,0 +1,201 @@
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+
+// Reconstruction filter for turning samples into pixels. The box filter
+// averages the samples taken in each pixel; the wider filters splat every
+// sample onto all pixels within `radius`, which softens aliasing at the
+// cost of a little sharpness. Radii are in pixels.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum PixelFilter {
+    #[default]
+    #[serde(rename = "box")]
+    Box,
+    #[serde(rename = "tent")]
+    Tent {
+        #[serde(default = "default_tent_radius")]
+        radius: f64,
+    },
+    // Truncated at `radius` and shifted so it reaches zero there.
+    #[serde(rename = "gaussian")]
+    Gaussian {
+        #[serde(default = "default_gaussian_radius")]
+        radius: f64,
+        #[serde(default = "default_gaussian_alpha")]
+        alpha: f64,
+    },
+    // Mitchell-Netravali with B = C = 1/3; its negative lobes sharpen
+    // edges slightly.
+    #[serde(rename = "mitchell")]
+    Mitchell {
+        #[serde(default = "default_mitchell_radius")]
+        radius: f64,
+    },
+}
+
+fn default_tent_radius() -> f64 {
+    1.0
+}
+
+fn default_gaussian_radius() -> f64 {
+    1.5
+}
+
+fn default_gaussian_alpha() -> f64 {
+    2.0
+}
+
+fn default_mitchell_radius() -> f64 {
+    2.0
+}
+
+impl PixelFilter {
+    pub fn is_box(&self) -> bool {
+        matches!(self, PixelFilter::Box)
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            PixelFilter::Box => 0.5,
+            PixelFilter::Tent { radius }
+            | PixelFilter::Gaussian { radius, .. }
+            | PixelFilter::Mitchell { radius } => radius.max(0.5),
+        }
+    }
+
+    // Pixels a sample can reach beyond the one it was taken in, on each
+    // side.
+    pub fn margin(&self) -> u32 {
+        (self.radius() - 0.5).ceil().max(0.0) as u32
+    }
+
+    // Weight of a sample `dx`, `dy` pixels from a pixel centre.
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, d: f64) -> f64 {
+        let radius = self.radius();
+        let d = d.abs();
+        if d > radius {
+            return 0.0;
+        }
+        match *self {
+            PixelFilter::Box => 1.0,
+            PixelFilter::Tent { .. } => radius - d,
+            PixelFilter::Gaussian { alpha, .. } => {
+                ((-alpha * d * d).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            PixelFilter::Mitchell { .. } => {
+                let x = 2.0 * d / radius;
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let value = if x < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b)
+                } else {
+                    (-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                };
+                value / 6.0
+            }
+        }
+    }
+}
+
+// Weighted sample sums over a rectangle of pixels: one tile plus the
+// filter's margin on each side, clipped to the image. Tiles fill their own
+// film independently and are then added into the image film one after the
+// other in a fixed order, so the sums, and the image, don't depend on how
+// tiles were scheduled across threads.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Film {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    sums: Vec<(DVec3, f64)>,
+}
+
+impl Film {
+    pub fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        let (width, height) = (x1 - x0, y1 - y0);
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            sums: vec![(DVec3::ZERO, 0.0); (width * height) as usize],
+        }
+    }
+
+    // The film for a tile spanning `x0..x1` by `y0..y1`, grown by the
+    // filter's margin.
+    pub fn for_tile(
+        filter: &PixelFilter,
+        (x0, y0, x1, y1): (u32, u32, u32, u32),
+        image_width: u32,
+        image_height: u32,
+    ) -> Self {
+        let margin = filter.margin();
+        Self::new(
+            x0.saturating_sub(margin),
+            y0.saturating_sub(margin),
+            (x1 + margin).min(image_width),
+            (y1 + margin).min(image_height),
+        )
+    }
+
+    // Adds `color` sampled at image position (`sx`, `sy`), where pixel
+    // (x, y) covers [x, x + 1) by [y, y + 1).
+    pub fn splat(&mut self, filter: &PixelFilter, sx: f64, sy: f64, color: DVec3) {
+        let radius = filter.radius();
+        let first = |s: f64, origin: u32| ((s - 0.5 - radius).ceil().max(origin as f64)) as u32;
+        let (x_start, y_start) = (first(sx, self.x0), first(sy, self.y0));
+        let x_end = ((sx - 0.5 + radius).floor() + 1.0).clamp(0.0, (self.x0 + self.width) as f64);
+        let y_end = ((sy - 0.5 + radius).floor() + 1.0).clamp(0.0, (self.y0 + self.height) as f64);
+        for y in y_start..y_end as u32 {
+            for x in x_start..x_end as u32 {
+                let weight = filter.evaluate(sx - (x as f64 + 0.5), sy - (y as f64 + 0.5));
+                if weight != 0.0 {
+                    let index = ((y - self.y0) * self.width + (x - self.x0)) as usize;
+                    self.sums[index].0 += weight * color;
+                    self.sums[index].1 += weight;
+                }
+            }
+        }
+    }
+
+    // Adds the sums of `other`, which must lie within this film.
+    pub fn add(&mut self, other: &Film) {
+        for y in 0..other.height {
+            let src = (y * other.width) as usize;
+            let dst = ((other.y0 + y - self.y0) * self.width + (other.x0 - self.x0)) as usize;
+            for (sum, add) in self.sums[dst..dst + other.width as usize]
+                .iter_mut()
+                .zip(&other.sums[src..src + other.width as usize])
+            {
+                sum.0 += add.0;
+                sum.1 += add.1;
+            }
+        }
+    }
+
+    // Zeroes sums that have become infinite or NaN, which JSON can't carry.
+    pub fn clear_non_finite(&mut self) {
+        for sum in &mut self.sums {
+            if !sum.0.is_finite() || !sum.1.is_finite() {
+                *sum = (DVec3::ZERO, 0.0);
+            }
+        }
+    }
+
+    // Filtered colour of pixel (`x`, `y`), or `None` if no sample weighs on
+    // it.
+    pub fn resolve(&self, x: u32, y: u32) -> Option<DVec3> {
+        let (sum, weight) = self.sums[((y - self.y0) * self.width + (x - self.x0)) as usize];
+        (weight.abs() > 1e-12).then(|| sum / weight)
+    }
+}
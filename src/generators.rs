@@ -0,0 +1,288 @@
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::hittable::{Hittable, HittableList};
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::objects::raymarch::{MengerSponge, RayMarched};
+use crate::objects::sphere::Sphere;
+use crate::objects::transform::Transformed;
+use crate::objects::triangle::Triangle;
+use crate::texture::{CheckerTexture, SolidColor};
+use glam::{DAffine3, DVec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+pub struct CityParams {
+    pub seed: u64,
+    pub blocks: u32,
+    pub max_floors: u32,
+    pub aspect_ratio: f64,
+}
+
+impl Default for CityParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            blocks: 8,
+            max_floors: 4,
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+}
+
+pub struct TerrainParams {
+    pub seed: u64,
+    pub resolution: u32,
+    pub size: f64,
+    pub height: f64,
+    pub octaves: u32,
+    pub water_level: Option<f64>,
+    pub aspect_ratio: f64,
+}
+
+impl Default for TerrainParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            resolution: 128,
+            size: 20.0,
+            height: 4.0,
+            octaves: 6,
+            water_level: Some(0.8),
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+}
+
+pub struct SweepParams {
+    pub rows: u32,
+    pub columns: u32,
+    pub aspect_ratio: f64,
+}
+
+impl Default for SweepParams {
+    fn default() -> Self {
+        Self {
+            rows: 2,
+            columns: 7,
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+}
+
+// Blocks of stacked Menger-sponge towers on a checkered ground plane.
+pub fn menger_city(params: &CityParams) -> (Camera, Arc<dyn Hittable>) {
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    let mut world = HittableList::new();
+
+    let half = params.blocks as f64;
+    world.push(Arc::new(Sphere::new(
+        DVec3::new(0.0, -10000.0, 0.0),
+        10000.0,
+        Arc::new(Lambertian::new(Arc::new(CheckerTexture::new(
+            0.5,
+            Arc::new(SolidColor::new(DVec3::splat(0.2))),
+            Arc::new(SolidColor::new(DVec3::splat(0.6))),
+        )))),
+    )));
+
+    let mut towers = HittableList::new();
+    for i in 0..params.blocks {
+        for j in 0..params.blocks {
+            let floors = rng.gen_range(1..=params.max_floors.max(1));
+            let shade = rng.gen_range(0.4..0.9);
+            let material: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+                DVec3::new(shade, shade * 0.95, shade * 0.9),
+            ))));
+            let sponge: Arc<dyn Hittable> = Arc::new(RayMarched::new(
+                MengerSponge {
+                    iterations: rng.gen_range(2..=3),
+                },
+                material,
+            ));
+            let x = 2.0 * i as f64 - half + 1.0;
+            let z = 2.0 * j as f64 - half + 1.0;
+            for floor in 0..floors {
+                let center = DVec3::new(x, 0.4 + 0.8 * floor as f64, z);
+                let transform =
+                    DAffine3::from_translation(center) * DAffine3::from_scale(DVec3::splat(0.4));
+                towers.push(Arc::new(Transformed::new(sponge.clone(), transform)));
+            }
+        }
+    }
+    world.push(Arc::new(BvhNode::new(towers)));
+
+    let camera = Camera::new(
+        DVec3::new(half * 1.2, half * 0.9, half * 1.6),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        params.aspect_ratio,
+        0.0,
+        1.0,
+    );
+    (camera, Arc::new(BvhNode::new(world)))
+}
+
+// fBm value-noise heightfield tessellated into triangles, with an optional
+// glassy water plane.
+pub fn fbm_terrain(params: &TerrainParams) -> (Camera, Arc<dyn Hittable>) {
+    let n = params.resolution.max(2) as usize;
+    let step = params.size / (n - 1) as f64;
+    let origin = -0.5 * params.size;
+    let ground: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::new(0.35, 0.45, 0.25),
+    ))));
+
+    let heights: Vec<f64> = (0..n * n)
+        .map(|i| {
+            let (x, z) = ((i % n) as f64, (i / n) as f64);
+            params.height
+                * fbm(
+                    x * 4.0 / n as f64,
+                    z * 4.0 / n as f64,
+                    params.octaves,
+                    params.seed,
+                )
+        })
+        .collect();
+    let vertex = |i: usize, j: usize| {
+        DVec3::new(
+            origin + i as f64 * step,
+            heights[j * n + i],
+            origin + j as f64 * step,
+        )
+    };
+
+    let mut triangles = HittableList::new();
+    for j in 0..n - 1 {
+        for i in 0..n - 1 {
+            let (a, b, c, d) = (
+                vertex(i, j),
+                vertex(i + 1, j),
+                vertex(i, j + 1),
+                vertex(i + 1, j + 1),
+            );
+            triangles.push(Arc::new(Triangle::new([a, c, b], ground.clone())));
+            triangles.push(Arc::new(Triangle::new([b, c, d], ground.clone())));
+        }
+    }
+
+    let mut world = HittableList::new();
+    world.push(Arc::new(BvhNode::new(triangles)));
+    if let Some(level) = params.water_level {
+        let water: Arc<dyn Material> = Arc::new(Dielectric::new(1.33));
+        let h = params.size;
+        let corners = [
+            DVec3::new(-h, level, -h),
+            DVec3::new(h, level, -h),
+            DVec3::new(-h, level, h),
+            DVec3::new(h, level, h),
+        ];
+        world.push(Arc::new(Triangle::new(
+            [corners[0], corners[2], corners[1]],
+            water.clone(),
+        )));
+        world.push(Arc::new(Triangle::new(
+            [corners[1], corners[2], corners[3]],
+            water,
+        )));
+    }
+
+    let camera = Camera::new(
+        DVec3::new(0.0, params.height * 2.5, params.size * 0.75),
+        DVec3::new(0.0, params.height * 0.3, 0.0),
+        DVec3::Y,
+        45.0,
+        params.aspect_ratio,
+        0.0,
+        1.0,
+    );
+    (camera, Arc::new(BvhNode::new(world)))
+}
+
+// Rows alternate between metals with increasing fuzz and dielectrics with
+// increasing index of refraction.
+pub fn material_sweep(params: &SweepParams) -> (Camera, Arc<dyn Hittable>) {
+    let mut world = HittableList::new();
+    world.push(Arc::new(Sphere::new(
+        DVec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+            0.5,
+        ))))),
+    )));
+
+    let columns = params.columns.max(1);
+    for row in 0..params.rows {
+        for column in 0..columns {
+            let t = if columns > 1 {
+                column as f64 / (columns - 1) as f64
+            } else {
+                0.0
+            };
+            let material: Arc<dyn Material> = if row % 2 == 0 {
+                Arc::new(Metal::new(
+                    Arc::new(SolidColor::new(DVec3::new(0.9, 0.8, 0.6))),
+                    t,
+                ))
+            } else {
+                Arc::new(Dielectric::new(1.0 + t * 1.5))
+            };
+            let center = DVec3::new(
+                (column as f64 - (columns - 1) as f64 / 2.0) * 1.1,
+                0.5,
+                -(row as f64) * 1.1,
+            );
+            world.push(Arc::new(Sphere::new(center, 0.5, material)));
+        }
+    }
+
+    let camera = Camera::new(
+        DVec3::new(0.0, 2.0, 6.0 + columns as f64 * 0.5),
+        DVec3::new(0.0, 0.5, -(params.rows as f64 - 1.0) * 0.55),
+        DVec3::Y,
+        35.0,
+        params.aspect_ratio,
+        0.0,
+        1.0,
+    );
+    (camera, Arc::new(BvhNode::new(world)))
+}
+
+fn fbm(x: f64, z: f64, octaves: u32, seed: u64) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0;
+    for octave in 0..octaves {
+        sum += amplitude * value_noise(x * frequency, z * frequency, seed + octave as u64);
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum
+}
+
+fn value_noise(x: f64, z: f64, seed: u64) -> f64 {
+    let (xi, zi) = (x.floor() as i64, z.floor() as i64);
+    let (xf, zf) = (x - x.floor(), z - z.floor());
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (u, v) = (smooth(xf), smooth(zf));
+
+    let a = lattice(xi, zi, seed);
+    let b = lattice(xi + 1, zi, seed);
+    let c = lattice(xi, zi + 1, seed);
+    let d = lattice(xi + 1, zi + 1, seed);
+    let top = a + (b - a) * u;
+    let bottom = c + (d - c) * u;
+    top + (bottom - top) * v
+}
+
+fn lattice(x: i64, z: i64, seed: u64) -> f64 {
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ seed.wrapping_mul(0x1656_67B1_9E37_79F9);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
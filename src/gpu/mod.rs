@@ -0,0 +1,622 @@
+use crate::camera::Camera;
+use crate::environment::{Environment, SkyGradient};
+use crate::filter::PixelFilter;
+use crate::irradiance_cache::IrradianceCache;
+use crate::renderer::{self, CropWindow, ImageBuffer, RenderSettings, Renderer};
+use bytemuck::{Pod, Zeroable};
+use glam::DVec3;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::sync::Mutex;
+use wgpu::util::DeviceExt;
+
+const LEAF_SIZE: usize = 4;
+// Set in `count` of interior nodes, whose low bits hold the split axis.
+const INTERIOR: u32 = 1 << 31;
+// Width and height of the tiles devices take in turn.
+const TILE_SIZE: u32 = 64;
+// Size of the latitude-longitude map the environment is baked into.
+const ENVIRONMENT_WIDTH: u32 = 512;
+const ENVIRONMENT_HEIGHT: u32 = 256;
+
+// Errors that can cross from a device's thread.
+type WorkerError = Box<dyn Error + Send + Sync>;
+
+// The adapters that share a frame, each taking the next tile as soon as
+// it finishes its last, so faster devices render more of the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GpuDevices {
+    // The one adapter wgpu picks by default.
+    #[default]
+    Default,
+    // Every GPU of the best available backend.
+    AllGpus,
+}
+
+impl GpuDevices {
+    // Software adapters, such as lavapipe or WARP, are only taken when
+    // wgpu picks one by default: the CPU renderer makes better use of the
+    // cores; see `GpuScene::render_on`.
+    fn adapters(self, instance: &wgpu::Instance) -> Vec<wgpu::Adapter> {
+        if self == GpuDevices::Default {
+            let adapter = pollster::block_on(
+                instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+            );
+            return adapter.into_iter().collect();
+        }
+        let is_gpu = |adapter: &wgpu::Adapter| {
+            use wgpu::DeviceType::{DiscreteGpu, IntegratedGpu, VirtualGpu};
+            matches!(
+                adapter.get_info().device_type,
+                DiscreteGpu | IntegratedGpu | VirtualGpu
+            )
+        };
+        let all = instance.enumerate_adapters(wgpu::Backends::PRIMARY);
+        // A GPU shows up once per backend that drives it, so only the
+        // first backend's are taken.
+        let backend = all.iter().find(|a| is_gpu(a)).map(|a| a.get_info().backend);
+        all.into_iter()
+            .filter(|adapter| is_gpu(adapter) && Some(adapter.get_info().backend) == backend)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum GpuMaterial {
+    Lambertian { albedo: DVec3 },
+    Metal { albedo: DVec3, fuzz: f64 },
+    Dielectric { index_of_refraction: f64 },
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct MaterialData {
+    albedo: [f32; 3],
+    param: f32,
+    kind: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct SphereData {
+    center_radius: [f32; 4],
+    material: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct TriangleData {
+    v0: [f32; 4],
+    v1: [f32; 4],
+    v2: [f32; 4],
+    material: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct NodeData {
+    bmin: [f32; 3],
+    left_first: u32,
+    bmax: [f32; 3],
+    count: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Params {
+    origin: [f32; 4],
+    lower_left: [f32; 4],
+    horizontal: [f32; 4],
+    vertical: [f32; 4],
+    u: [f32; 4],
+    v: [f32; 4],
+    width: u32,
+    height: u32,
+    samples: u32,
+    max_depth: u32,
+    lens_radius: f32,
+    sphere_count: u32,
+    seed: u32,
+    // The tile being rendered, in pixels from the top-left.
+    tile_x: u32,
+    tile_y: u32,
+    tile_width: u32,
+    tile_height: u32,
+    _pad: u32,
+}
+
+// Flattened, GPU-friendly copy of a scene. Only spheres and triangles with
+// untextured Lambertian/Metal/Dielectric materials are supported. Positions
+// are stored in f32 relative to `origin`, and the camera is moved by the
+// same amount when rendering; an origin near the camera keeps nearby
+// geometry precise in scenes far from the world origin.
+#[derive(Default)]
+pub struct GpuScene {
+    origin: DVec3,
+    materials: Vec<MaterialData>,
+    spheres: Vec<SphereData>,
+    triangles: Vec<TriangleData>,
+    // Baked by `set_environment`; empty for the default sky gradient.
+    environment: Vec<[f32; 4]>,
+}
+
+impl GpuScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_origin(origin: DVec3) -> Self {
+        Self {
+            origin,
+            ..Self::default()
+        }
+    }
+
+    pub fn add_material(&mut self, material: GpuMaterial) -> u32 {
+        let (albedo, param, kind) = match material {
+            GpuMaterial::Lambertian { albedo } => (albedo, 0.0, 0),
+            GpuMaterial::Metal { albedo, fuzz } => (albedo, fuzz.clamp(0.0, 1.0), 1),
+            GpuMaterial::Dielectric {
+                index_of_refraction,
+            } => (DVec3::ONE, index_of_refraction, 2),
+        };
+        self.materials.push(MaterialData {
+            albedo: albedo.as_vec3().to_array(),
+            param: param as f32,
+            kind,
+            _pad: [0; 3],
+        });
+        (self.materials.len() - 1) as u32
+    }
+
+    // Sets what rays that leave the scene see. The environment is baked
+    // into a latitude-longitude map, laid out as `EnvironmentMap` reads
+    // HDR images, that the shader looks up without filtering.
+    pub fn set_environment(&mut self, environment: &dyn Environment) {
+        self.environment = bake_environment(environment);
+    }
+
+    pub fn add_sphere(&mut self, center: DVec3, radius: f64, material: u32) {
+        self.spheres.push(SphereData {
+            center_radius: (center - self.origin).extend(radius).as_vec4().to_array(),
+            material,
+            _pad: [0; 3],
+        });
+    }
+
+    pub fn add_triangle(&mut self, vertices: [DVec3; 3], material: u32) {
+        let [v0, v1, v2] = vertices.map(|v| (v - self.origin).extend(0.0).as_vec4().to_array());
+        self.triangles.push(TriangleData {
+            v0,
+            v1,
+            v2,
+            material,
+            _pad: [0; 3],
+        });
+    }
+
+    fn primitive_bounds(&self, index: usize) -> (DVec3, DVec3) {
+        if index < self.spheres.len() {
+            let s = self.spheres[index].center_radius;
+            let center = DVec3::new(s[0] as f64, s[1] as f64, s[2] as f64);
+            let r = DVec3::splat(s[3] as f64);
+            (center - r, center + r)
+        } else {
+            let t = &self.triangles[index - self.spheres.len()];
+            let [a, b, c] =
+                [t.v0, t.v1, t.v2].map(|v| DVec3::new(v[0] as f64, v[1] as f64, v[2] as f64));
+            (a.min(b).min(c), a.max(b).max(c))
+        }
+    }
+
+    // The nodes and primitive indices of the hierarchy, and its depth in
+    // interior nodes.
+    fn build_bvh(&self) -> (Vec<NodeData>, Vec<u32>, usize) {
+        let count = self.spheres.len() + self.triangles.len();
+        let bounds: Vec<(DVec3, DVec3)> = (0..count).map(|i| self.primitive_bounds(i)).collect();
+        let mut prims: Vec<u32> = (0..count as u32).collect();
+        let mut nodes = vec![NodeData::zeroed()];
+        let depth = if count > 0 {
+            build_node(&bounds, &mut prims, 0, count, 0, &mut nodes)
+        } else {
+            0
+        };
+        (nodes, prims, depth)
+    }
+
+    pub fn render(
+        &self,
+        camera: &Camera,
+        settings: &RenderSettings,
+    ) -> Result<ImageBuffer, Box<dyn Error>> {
+        self.render_on(camera, settings, GpuDevices::Default, None)
+    }
+
+    // Renders across `devices`, one thread driving each. Every pixel's
+    // random numbers depend only on its position, so the GPUs' share of
+    // the image is the same however the tiles fall. With `cpu`, which
+    // should hold the same scene, the CPU renderer takes tiles too, each
+    // spread over its thread pool, and renders the whole frame when there
+    // is no GPU. Its tiles are shaded in full, with textures and every
+    // material, and box-filtered like the GPUs'. Tiles a failing device
+    // had taken go back to the others; the render fails only if some are
+    // left over.
+    pub fn render_on(
+        &self,
+        camera: &Camera,
+        settings: &RenderSettings,
+        devices: GpuDevices,
+        cpu: Option<&Renderer>,
+    ) -> Result<ImageBuffer, Box<dyn Error>> {
+        let instance = wgpu::Instance::default();
+        let adapters = devices.adapters(&instance);
+        if adapters.is_empty() && cpu.is_none() {
+            return Err("no compatible GPU adapter found".into());
+        }
+        let (nodes, prims, depth) = self.build_bvh();
+        // Traversal pops a node and pushes its two children, so the stack
+        // never holds more than one entry per level, plus one.
+        let shader = format!(
+            "const STACK_SIZE: u32 = {}u;\n\
+             const ENVIRONMENT_WIDTH: u32 = {ENVIRONMENT_WIDTH}u;\n\
+             const ENVIRONMENT_HEIGHT: u32 = {ENVIRONMENT_HEIGHT}u;\n{}",
+            depth + 1,
+            include_str!("path_tracer.wgsl")
+        );
+        let default_sky;
+        let environment = if self.environment.is_empty() {
+            default_sky = bake_environment(&SkyGradient::default());
+            &default_sky
+        } else {
+            &self.environment
+        };
+        let to4 = |v: DVec3| v.extend(0.0).as_vec4().to_array();
+        let params = Params {
+            origin: to4(camera.origin - self.origin),
+            lower_left: to4(camera.lower_left_corner - self.origin),
+            horizontal: to4(camera.horizontal),
+            vertical: to4(camera.vertical),
+            u: to4(camera.u),
+            v: to4(camera.v),
+            width: settings.width,
+            height: settings.height,
+            samples: settings.samples_per_pixel,
+            max_depth: settings.max_depth,
+            lens_radius: camera.lens_radius as f32,
+            sphere_count: self.spheres.len() as u32,
+            seed: (settings.seed ^ (settings.seed >> 32)) as u32,
+            tile_x: 0,
+            tile_y: 0,
+            tile_width: 0,
+            tile_height: 0,
+            _pad: 0,
+        };
+
+        let mut tiles = Vec::new();
+        for y in (0..settings.height).step_by(TILE_SIZE as usize) {
+            for x in (0..settings.width).step_by(TILE_SIZE as usize) {
+                tiles.push(Params {
+                    tile_x: x,
+                    tile_y: y,
+                    tile_width: TILE_SIZE.min(settings.width - x),
+                    tile_height: TILE_SIZE.min(settings.height - y),
+                    ..params
+                });
+            }
+        }
+        // Taken from the end, so the top of the image comes first.
+        tiles.reverse();
+        let tiles = Mutex::new(tiles);
+        let image = Mutex::new(ImageBuffer::new(settings.width, settings.height));
+        let results: Vec<Result<(), WorkerError>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = adapters
+                .into_iter()
+                .map(|adapter| {
+                    let scene = SceneBuffers {
+                        shader: &shader,
+                        nodes: &nodes,
+                        prims: &prims,
+                        environment,
+                    };
+                    let (tiles, image) = (&tiles, &image);
+                    scope.spawn(move || {
+                        pollster::block_on(self.render_tiles(adapter, scene, tiles, image))
+                    })
+                })
+                .collect();
+            if let Some(cpu) = cpu {
+                render_cpu_tiles(cpu, camera, settings, &tiles, &image);
+            }
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("GPU worker panicked"))
+                .collect()
+        });
+        if !tiles.into_inner().unwrap().is_empty() {
+            let error: Box<dyn Error> = match results.into_iter().find_map(Result::err) {
+                Some(error) => error,
+                None => "GPU render left tiles unfinished".into(),
+            };
+            return Err(error);
+        }
+        Ok(image.into_inner().unwrap())
+    }
+
+    // Sets the scene up on `adapter` and renders tiles from `tiles` until
+    // none are left.
+    async fn render_tiles(
+        &self,
+        adapter: wgpu::Adapter,
+        scene: SceneBuffers<'_>,
+        tiles: &Mutex<Vec<Params>>,
+        image: &Mutex<ImageBuffer>,
+    ) -> Result<(), WorkerError> {
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await?;
+
+        let storage = |label: &str, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+        // Zero-sized storage bindings are invalid, so empty arrays get one
+        // dummy element that the shader never indexes.
+        let spheres = non_empty(&self.spheres);
+        let triangles = non_empty(&self.triangles);
+        let materials = non_empty(&self.materials);
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("params"),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sphere_buffer = storage("spheres", bytemuck::cast_slice(&spheres));
+        let triangle_buffer = storage("triangles", bytemuck::cast_slice(&triangles));
+        let material_buffer = storage("materials", bytemuck::cast_slice(&materials));
+        let node_buffer = storage("nodes", bytemuck::cast_slice(scene.nodes));
+        let prim_buffer = storage("prims", bytemuck::cast_slice(&non_empty(scene.prims)));
+        let environment_buffer = storage("environment", bytemuck::cast_slice(scene.environment));
+
+        let output_size = (TILE_SIZE * TILE_SIZE) as u64 * 16;
+        let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("output"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("path_tracer"),
+            source: wgpu::ShaderSource::Wgsl(scene.shader.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("path_tracer"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("scene"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                params_buffer.as_entire_binding(),
+                sphere_buffer.as_entire_binding(),
+                triangle_buffer.as_entire_binding(),
+                material_buffer.as_entire_binding(),
+                node_buffer.as_entire_binding(),
+                prim_buffer.as_entire_binding(),
+                output_buffer.as_entire_binding(),
+                environment_buffer.as_entire_binding(),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(binding, resource)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource,
+            })
+            .collect::<Vec<_>>(),
+        });
+
+        loop {
+            let Some(tile) = tiles.lock().unwrap().pop() else {
+                return Ok(());
+            };
+            queue.write_buffer(&params_buffer, 0, bytemuck::bytes_of(&tile));
+            let tile_size = (tile.tile_width * tile.tile_height) as u64 * 16;
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(
+                    tile.tile_width.div_ceil(8),
+                    tile.tile_height.div_ceil(8),
+                    1,
+                );
+            }
+            encoder.copy_buffer_to_buffer(&output_buffer, 0, &staging_buffer, 0, tile_size);
+            queue.submit(Some(encoder.finish()));
+
+            let slice = staging_buffer.slice(..tile_size);
+            let (sender, receiver) = std::sync::mpsc::channel();
+            slice.map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            device.poll(wgpu::Maintain::Wait);
+            let mapped = match receiver.recv() {
+                Ok(result) => result.map_err(WorkerError::from),
+                Err(error) => Err(error.into()),
+            };
+            if let Err(error) = mapped {
+                tiles.lock().unwrap().push(tile);
+                return Err(error);
+            }
+
+            {
+                let data = slice.get_mapped_range();
+                let pixels: &[[f32; 4]] = bytemuck::cast_slice(&data);
+                let mut image = image.lock().unwrap();
+                for (i, src) in pixels.iter().enumerate() {
+                    let x = tile.tile_x + i as u32 % tile.tile_width;
+                    let y = tile.tile_y + i as u32 / tile.tile_width;
+                    image.pixels[(y * tile.width + x) as usize] =
+                        DVec3::new(src[0] as f64, src[1] as f64, src[2] as f64);
+                }
+            }
+            staging_buffer.unmap();
+        }
+    }
+}
+
+// Renders tiles from `tiles` with the CPU renderer until none are left,
+// on the calling thread and the renderer's thread pool.
+fn render_cpu_tiles(
+    cpu: &Renderer,
+    camera: &Camera,
+    settings: &RenderSettings,
+    tiles: &Mutex<Vec<Params>>,
+    image: &Mutex<ImageBuffer>,
+) {
+    let cache = settings.irradiance_cache.map(IrradianceCache::new);
+    let photons = cpu.photon_map(settings);
+    loop {
+        let Some(tile) = tiles.lock().unwrap().pop() else {
+            return;
+        };
+        let settings = RenderSettings {
+            filter: PixelFilter::Box,
+            crop: Some(CropWindow {
+                x: tile.tile_x,
+                y: tile.tile_y,
+                width: tile.tile_width,
+                height: tile.tile_height,
+            }),
+            ..*settings
+        };
+        let rendered = cpu.render_tiles(
+            camera,
+            &settings,
+            cache.as_ref(),
+            photons.as_ref(),
+            renderer::split_tiles(&settings),
+        );
+        let mut image = image.lock().unwrap();
+        for (part, rendered) in rendered {
+            let mut pixels = rendered.pixels.iter();
+            for y in part.y0..part.y1 {
+                for x in part.x0..part.x1 {
+                    image.set(x, y, pixels.next().unwrap().0);
+                }
+            }
+        }
+    }
+}
+
+// What every device's worker uploads alike: the shader, compiled with the
+// hierarchy's stack size, and the scene's shared buffers.
+#[derive(Clone, Copy)]
+struct SceneBuffers<'a> {
+    shader: &'a str,
+    nodes: &'a [NodeData],
+    prims: &'a [u32],
+    environment: &'a [[f32; 4]],
+}
+
+// `environment` at the centre of each texel of a latitude-longitude map,
+// with u turning about +y from -z and v running down from +y.
+fn bake_environment(environment: &dyn Environment) -> Vec<[f32; 4]> {
+    let (width, height) = (ENVIRONMENT_WIDTH, ENVIRONMENT_HEIGHT);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let phi = 2.0 * PI * ((x as f64 + 0.5) / width as f64 - 0.5);
+            let theta = PI * (y as f64 + 0.5) / height as f64;
+            let direction = DVec3::new(
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                -theta.sin() * phi.cos(),
+            );
+            environment
+                .value(direction)
+                .extend(1.0)
+                .as_vec4()
+                .to_array()
+        })
+        .collect()
+}
+
+fn non_empty<T: Pod>(items: &[T]) -> Vec<T> {
+    if items.is_empty() {
+        vec![T::zeroed()]
+    } else {
+        items.to_vec()
+    }
+}
+
+// Median-split build writing nodes in the layout the shader expects: interior
+// nodes store the index of their left child, whose sibling immediately follows,
+// and the axis they were split along, so rays can visit the nearer child first.
+// Returns the depth of the subtree in interior nodes.
+fn build_node(
+    bounds: &[(DVec3, DVec3)],
+    prims: &mut [u32],
+    start: usize,
+    end: usize,
+    node_index: usize,
+    nodes: &mut Vec<NodeData>,
+) -> usize {
+    let mut min = DVec3::splat(f64::INFINITY);
+    let mut max = DVec3::splat(f64::NEG_INFINITY);
+    for &p in &prims[start..end] {
+        let (lo, hi) = bounds[p as usize];
+        min = min.min(lo);
+        max = max.max(hi);
+    }
+    nodes[node_index].bmin = min.as_vec3().to_array();
+    nodes[node_index].bmax = max.as_vec3().to_array();
+
+    if end - start <= LEAF_SIZE {
+        nodes[node_index].left_first = start as u32;
+        nodes[node_index].count = (end - start) as u32;
+        return 0;
+    }
+
+    let extent = max - min;
+    let axis = if extent.x > extent.y && extent.x > extent.z {
+        0
+    } else if extent.y > extent.z {
+        1
+    } else {
+        2
+    };
+    let centroid = |p: &u32| {
+        let (lo, hi) = bounds[*p as usize];
+        (lo[axis] + hi[axis]) * 0.5
+    };
+    prims[start..end].sort_by(|a, b| centroid(a).total_cmp(&centroid(b)));
+    let mid = (start + end) / 2;
+
+    let left = nodes.len();
+    nodes.push(NodeData::zeroed());
+    nodes.push(NodeData::zeroed());
+    nodes[node_index].left_first = left as u32;
+    nodes[node_index].count = INTERIOR | axis as u32;
+    let left_depth = build_node(bounds, prims, start, mid, left, nodes);
+    let right_depth = build_node(bounds, prims, mid, end, left + 1, nodes);
+    1 + left_depth.max(right_depth)
+}
@@ -0,0 +1,309 @@
+// STACK_SIZE, the BVH traversal stack's length, and ENVIRONMENT_WIDTH and
+// ENVIRONMENT_HEIGHT, the size of `environment_map`, are prepended by
+// `GpuScene::render_on`.
+
+const PI: f32 = 3.14159265;
+
+struct Params {
+    origin: vec4<f32>,
+    lower_left: vec4<f32>,
+    horizontal: vec4<f32>,
+    vertical: vec4<f32>,
+    u: vec4<f32>,
+    v: vec4<f32>,
+    width: u32,
+    height: u32,
+    samples: u32,
+    max_depth: u32,
+    lens_radius: f32,
+    sphere_count: u32,
+    seed: u32,
+    // The tile being rendered; `output` holds just its pixels.
+    tile_x: u32,
+    tile_y: u32,
+    tile_width: u32,
+    tile_height: u32,
+    _pad: u32,
+}
+
+struct Sphere {
+    center_radius: vec4<f32>,
+    material: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+struct Triangle {
+    v0: vec4<f32>,
+    v1: vec4<f32>,
+    v2: vec4<f32>,
+    material: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+// kind: 0 = lambertian, 1 = metal (param = fuzz), 2 = dielectric (param = ior)
+struct Material {
+    albedo: vec3<f32>,
+    param: f32,
+    kind: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+// Flag in `Node.count` marking interior nodes; see `build_node`.
+const INTERIOR: u32 = 0x80000000u;
+
+struct Node {
+    bmin: vec3<f32>,
+    left_first: u32,
+    bmax: vec3<f32>,
+    count: u32,
+}
+
+struct Hit {
+    t: f32,
+    normal: vec3<f32>,
+    material: u32,
+    front_face: bool,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> spheres: array<Sphere>;
+@group(0) @binding(2) var<storage, read> triangles: array<Triangle>;
+@group(0) @binding(3) var<storage, read> materials: array<Material>;
+@group(0) @binding(4) var<storage, read> nodes: array<Node>;
+@group(0) @binding(5) var<storage, read> prims: array<u32>;
+@group(0) @binding(6) var<storage, read_write> output: array<vec4<f32>>;
+// Latitude-longitude map of the scene's environment, row by row from +y.
+@group(0) @binding(7) var<storage, read> environment_map: array<vec4<f32>>;
+
+var<private> rng_state: u32;
+
+fn rand() -> f32 {
+    rng_state = rng_state * 747796405u + 2891336453u;
+    var w = ((rng_state >> ((rng_state >> 28u) + 4u)) ^ rng_state) * 277803737u;
+    w = (w >> 22u) ^ w;
+    return f32(w) / 4294967296.0;
+}
+
+fn random_in_unit_sphere() -> vec3<f32> {
+    loop {
+        let p = vec3<f32>(rand(), rand(), rand()) * 2.0 - 1.0;
+        if dot(p, p) < 1.0 {
+            return p;
+        }
+    }
+}
+
+fn random_in_unit_disk() -> vec2<f32> {
+    loop {
+        let p = vec2<f32>(rand(), rand()) * 2.0 - 1.0;
+        if dot(p, p) < 1.0 {
+            return p;
+        }
+    }
+}
+
+fn hit_sphere(index: u32, ro: vec3<f32>, rd: vec3<f32>, t_min: f32, t_max: f32, hit: ptr<function, Hit>) -> bool {
+    let s = spheres[index];
+    let center = s.center_radius.xyz;
+    let radius = s.center_radius.w;
+    let oc = ro - center;
+    let a = dot(rd, rd);
+    let half_b = dot(oc, rd);
+    let c = dot(oc, oc) - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return false;
+    }
+    let sqrtd = sqrt(discriminant);
+    var root = (-half_b - sqrtd) / a;
+    if root <= t_min || root >= t_max {
+        root = (-half_b + sqrtd) / a;
+        if root <= t_min || root >= t_max {
+            return false;
+        }
+    }
+    let outward = (ro + root * rd - center) / radius;
+    (*hit).t = root;
+    (*hit).front_face = dot(rd, outward) < 0.0;
+    (*hit).normal = select(-outward, outward, (*hit).front_face);
+    (*hit).material = s.material;
+    return true;
+}
+
+fn hit_triangle(index: u32, ro: vec3<f32>, rd: vec3<f32>, t_min: f32, t_max: f32, hit: ptr<function, Hit>) -> bool {
+    let tri = triangles[index];
+    let e1 = tri.v1.xyz - tri.v0.xyz;
+    let e2 = tri.v2.xyz - tri.v0.xyz;
+    let pvec = cross(rd, e2);
+    let det = dot(e1, pvec);
+    if abs(det) < 1e-9 {
+        return false;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ro - tri.v0.xyz;
+    let b1 = dot(tvec, pvec) * inv_det;
+    if b1 < 0.0 || b1 > 1.0 {
+        return false;
+    }
+    let qvec = cross(tvec, e1);
+    let b2 = dot(rd, qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return false;
+    }
+    let t = dot(e2, qvec) * inv_det;
+    if t <= t_min || t >= t_max {
+        return false;
+    }
+    let outward = normalize(cross(e1, e2));
+    (*hit).t = t;
+    (*hit).front_face = dot(rd, outward) < 0.0;
+    (*hit).normal = select(-outward, outward, (*hit).front_face);
+    (*hit).material = tri.material;
+    return true;
+}
+
+fn hit_aabb(node: Node, ro: vec3<f32>, inv_d: vec3<f32>, t_min: f32, t_max: f32) -> bool {
+    let t0 = (node.bmin - ro) * inv_d;
+    let t1 = (node.bmax - ro) * inv_d;
+    let small = min(t0, t1);
+    let big = max(t0, t1);
+    let t_near = max(max(small.x, small.y), max(small.z, t_min));
+    let t_far = min(min(big.x, big.y), min(big.z, t_max));
+    return t_near <= t_far;
+}
+
+fn hit_world(ro: vec3<f32>, rd: vec3<f32>, hit: ptr<function, Hit>) -> bool {
+    let inv_d = 1.0 / rd;
+    var closest = 1e30;
+    var found = false;
+    var stack: array<u32, STACK_SIZE>;
+    var sp = 0u;
+    stack[0] = 0u;
+    sp = 1u;
+
+    while sp > 0u {
+        sp = sp - 1u;
+        let node = nodes[stack[sp]];
+        if !hit_aabb(node, ro, inv_d, 0.001, closest) {
+            continue;
+        }
+        if (node.count & INTERIOR) == 0u {
+            for (var i = 0u; i < node.count; i = i + 1u) {
+                let prim = prims[node.left_first + i];
+                var hit_found = false;
+                if prim < params.sphere_count {
+                    hit_found = hit_sphere(prim, ro, rd, 0.001, closest, hit);
+                } else {
+                    hit_found = hit_triangle(prim - params.sphere_count, ro, rd, 0.001, closest, hit);
+                }
+                if hit_found {
+                    closest = (*hit).t;
+                    found = true;
+                }
+            }
+        } else {
+            // The left child holds the lower half along the split axis, so
+            // it is nearer for rays heading up that axis. Pushing the far
+            // child first pops the near one next, and its hits shrink
+            // `closest` before the far child's box is tested.
+            let axis = node.count & 3u;
+            let near = node.left_first + select(0u, 1u, rd[axis] < 0.0);
+            let far = node.left_first + select(1u, 0u, rd[axis] < 0.0);
+            stack[sp] = far;
+            stack[sp + 1u] = near;
+            sp = sp + 2u;
+        }
+    }
+    return found;
+}
+
+fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
+    var r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+    r0 = r0 * r0;
+    return r0 + (1.0 - r0) * pow(1.0 - cosine, 5.0);
+}
+
+// Radiance of the environment towards `rd`, looked up as
+// `EnvironmentMap::texel` does.
+fn environment(rd: vec3<f32>) -> vec3<f32> {
+    let d = normalize(rd);
+    let u = atan2(d.x, -d.z) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(d.y, -1.0, 1.0)) / PI;
+    let x = min(u32(u * f32(ENVIRONMENT_WIDTH)), ENVIRONMENT_WIDTH - 1u);
+    let y = min(u32(v * f32(ENVIRONMENT_HEIGHT)), ENVIRONMENT_HEIGHT - 1u);
+    return environment_map[y * ENVIRONMENT_WIDTH + x].xyz;
+}
+
+fn ray_color(origin: vec3<f32>, direction: vec3<f32>) -> vec3<f32> {
+    var ro = origin;
+    var rd = direction;
+    var throughput = vec3<f32>(1.0);
+
+    for (var depth = 0u; depth < params.max_depth; depth = depth + 1u) {
+        var hit: Hit;
+        if !hit_world(ro, rd, &hit) {
+            return throughput * environment(rd);
+        }
+
+        let m = materials[hit.material];
+        let point = ro + hit.t * rd;
+        let unit = normalize(rd);
+        var scattered: vec3<f32>;
+        if m.kind == 0u {
+            scattered = hit.normal + normalize(random_in_unit_sphere());
+            if dot(scattered, scattered) < 1e-12 {
+                scattered = hit.normal;
+            }
+            throughput = throughput * m.albedo;
+        } else if m.kind == 1u {
+            scattered = reflect(unit, hit.normal) + m.param * random_in_unit_sphere();
+            if dot(scattered, hit.normal) <= 0.0 {
+                return vec3<f32>(0.0);
+            }
+            throughput = throughput * m.albedo;
+        } else {
+            let ratio = select(m.param, 1.0 / m.param, hit.front_face);
+            let cos_theta = min(dot(-unit, hit.normal), 1.0);
+            let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+            if ratio * sin_theta > 1.0 || reflectance(cos_theta, ratio) > rand() {
+                scattered = reflect(unit, hit.normal);
+            } else {
+                scattered = refract(unit, hit.normal, ratio);
+            }
+        }
+        ro = point;
+        rd = scattered;
+    }
+    return vec3<f32>(0.0);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) tile_id: vec3<u32>) {
+    if tile_id.x >= params.tile_width || tile_id.y >= params.tile_height {
+        return;
+    }
+    let id = vec2<u32>(params.tile_x + tile_id.x, params.tile_y + tile_id.y);
+    let index = id.y * params.width + id.x;
+    rng_state = index * 9781u + params.seed * 6271u + 1u;
+
+    var color = vec3<f32>(0.0);
+    let w = f32(max(params.width, 2u) - 1u);
+    let h = f32(max(params.height, 2u) - 1u);
+    for (var s = 0u; s < params.samples; s = s + 1u) {
+        let px = (f32(id.x) + rand()) / w;
+        let py = (f32(params.height - 1u - id.y) + rand()) / h;
+        let rd2 = params.lens_radius * random_in_unit_disk();
+        let offset = params.u.xyz * rd2.x + params.v.xyz * rd2.y;
+        let origin = params.origin.xyz + offset;
+        let aim = params.lower_left.xyz + px * params.horizontal.xyz + py * params.vertical.xyz;
+        color = color + ray_color(origin, aim - origin);
+    }
+    let tile_index = tile_id.y * params.tile_width + tile_id.x;
+    output[tile_index] = vec4<f32>(color / f32(max(params.samples, 1u)), 1.0);
+}
@@ -1,9 +1,31 @@
+use crate::interval::Interval;
 use crate::material::Material;
-use crate::ray::Ray;
-use glam::DVec3;
-use std::ops::Range;
+use crate::objects::mesh::Mesh;
+use crate::ray::{Ray, RayKind};
+use crate::texture::Texture;
+use glam::{DAffine3, DVec3};
 use std::sync::Arc;
 
+// Offset for rays that don't leave a surface, such as camera rays, and
+// for bounds too large to scale one from.
+pub const DEFAULT_EPSILON: f64 = 0.001;
+
+// Rounding errors in intersections grow with the magnitude of the
+// coordinates involved, so ray offsets are scaled with it. In f64 those
+// errors stay within a few thousand ulps of the coordinates, well inside
+// this bound: a nanometre at a kilometre from the origin, and still under
+// a millimetre at the radius of the Earth.
+const RELATIVE_EPSILON: f64 = 1e-12;
+const MIN_EPSILON: f64 = 1e-12;
+
+fn scaled_epsilon(magnitude: f64) -> f64 {
+    if magnitude.is_finite() {
+        (RELATIVE_EPSILON * magnitude).max(MIN_EPSILON)
+    } else {
+        DEFAULT_EPSILON
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 pub struct AABB {
     pub min: DVec3,
@@ -15,11 +37,49 @@ impl AABB {
         Self { min, max }
     }
 
-    pub fn hit(&self, ray: &Ray, interval: Range<f64>) -> bool {
-        let mut t_min = interval.start;
-        let mut t_max = interval.end;
+    // Extent of the box along axis `a`.
+    pub fn axis(&self, a: usize) -> Interval {
+        Interval::new(self.min[a], self.max[a])
+    }
+
+    // Widened to at least `delta` along every axis, so that flat objects,
+    // such as disks facing down an axis, don't get flat boxes.
+    pub fn padded(&self, delta: f64) -> Self {
+        let mut padded = *self;
+        for a in 0..3 {
+            let axis = self.axis(a);
+            if axis.size() < delta {
+                let axis = axis.expand(delta - axis.size());
+                padded.min[a] = axis.min;
+                padded.max[a] = axis.max;
+            }
+        }
+        padded
+    }
+
+    // Distance from `point` to the nearest point of the box; 0 inside it.
+    pub fn distance(&self, point: DVec3) -> f64 {
+        point.distance(point.clamp(self.min, self.max))
+    }
+
+    // Slab test that never misses a ray grazing the box. Rays parallel to
+    // a slab are checked against it directly rather than through infinite
+    // or NaN bounds, so rays running along a face count as inside. The far
+    // bound is widened by a few ulps (PBRT's 1 + 2γ₃) against rounding, and
+    // flat boxes, such as those of axis-aligned triangles, are hit where a
+    // ray crosses them.
+    pub fn hit(&self, ray: &Ray, interval: Interval) -> bool {
+        const FAR_SLACK: f64 = 1.0 + 3.0 * f64::EPSILON;
+        let mut t_min = interval.min;
+        let mut t_max = interval.max;
 
         for a in 0..3 {
+            if ray.direction[a] == 0.0 {
+                if ray.origin[a] < self.min[a] || ray.origin[a] > self.max[a] {
+                    return false;
+                }
+                continue;
+            }
             let inv_d = 1.0 / ray.direction[a];
             let mut t0 = (self.min[a] - ray.origin[a]) * inv_d;
             let mut t1 = (self.max[a] - ray.origin[a]) * inv_d;
@@ -29,15 +89,21 @@ impl AABB {
             }
 
             t_min = t0.max(t_min);
-            t_max = t1.min(t_max);
+            t_max = (t1 * FAR_SLACK).min(t_max);
 
-            if t_max <= t_min {
+            if t_max < t_min {
                 return false;
             }
         }
         true
     }
 
+    // Ray offset for surfaces within these bounds, from the largest
+    // coordinate they span.
+    pub fn epsilon(&self) -> f64 {
+        scaled_epsilon(self.min.abs().max(self.max.abs()).max_element())
+    }
+
     pub fn surrounding_box(box0: AABB, box1: AABB) -> AABB {
         let min = box0.min.min(box1.min);
         let max = box0.max.max(box1.max);
@@ -45,17 +111,98 @@ impl AABB {
     }
 }
 
-pub struct HitRecord {
+// What a ray found on a surface. The material is borrowed from the object
+// that was hit, so records cost no reference counting however many
+// candidates a traversal turns up.
+#[derive(Clone, Copy)]
+pub struct HitRecord<'a> {
     pub point: DVec3,
     pub normal: DVec3,
-    pub material: Arc<dyn Material>,
+    pub material: &'a dyn Material,
     pub t: f64,
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    // Set by `Tagged` wrappers for the object ID pass; 0 means untagged.
+    pub object_id: u32,
+    // Error bound of `point` for primitives whose hits are only
+    // approximate, such as ray-marched ones; 0 for exact ones. `Tagged`
+    // raises it to suit the bounds of the whole object. Rays leaving the
+    // hit are pushed at least this far off the surface, and start this far
+    // along.
+    pub epsilon: f64,
+    // Direction of increasing `u` on the surface, for normal mapping; zero
+    // when the primitive has no UV parameterisation.
+    pub tangent: DVec3,
+    // Partial derivatives of the point and of the outward normal with
+    // respect to `u` and `v`, for bump mapping, anisotropic shading frames
+    // and texture filtering; zero when the primitive has no UV
+    // parameterisation. With interpolated normals, the normal derivatives
+    // follow the normalised shading normal.
+    pub dpdu: DVec3,
+    pub dpdv: DVec3,
+    pub dndu: DVec3,
+    pub dndv: DVec3,
+    // Index into the renderer's `LightSet` when the hit primitive is an
+    // emitter that light sampling can also reach.
+    pub light: Option<u32>,
+    // Barycentric coordinates of the hit within its triangle, for the
+    // wireframe view; `None` for other primitives.
+    pub barycentric: Option<DVec3>,
+    // Colour of the vertices or points around the hit, interpolated, for
+    // meshes and point clouds that carry their own; `None` elsewhere.
+    pub color: Option<DVec3>,
 }
 
-impl HitRecord {
+impl HitRecord<'_> {
+    // The base colour `texture` gives a material here, tinted by the
+    // vertex colour as glTF's COLOR_0 tints its base colour.
+    pub fn base_color(&self, texture: &dyn Texture) -> DVec3 {
+        let color = texture.value(self.u, self.v, self.point);
+        self.color.map_or(color, |tint| color * tint)
+    }
+
+    // Start of the interval to intersect rays from `spawn` over.
+    pub fn ray_epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    // Ray leaving this hit along `direction`, at the time and wavelength of
+    // `ray_in`, the ray that found it. Its origin is the hit point pushed
+    // off the surface along the normal, to the side `direction` leaves on,
+    // by more than the rounding error in the point: that grows with the
+    // magnitude of its coordinates and with the distance `ray_in`
+    // travelled. Unlike an offset along the ray, the push clears the
+    // surface even at grazing angles, and it stays far too small to
+    // detach shadows from contact points. Shadow rays spawn shadow rays,
+    // and any other ray an indirect one.
+    pub fn spawn(&self, ray_in: &Ray, direction: DVec3) -> Ray {
+        let travelled = (self.point - ray_in.origin).abs().max_element();
+        let magnitude = self.point.abs().max_element() + travelled;
+        let offset = scaled_epsilon(magnitude).max(self.epsilon);
+        let side = if direction.dot(self.normal) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        Ray::new(self.point + side * offset * self.normal, direction)
+            .with_time(ray_in.time)
+            .with_wavelength(ray_in.wavelength)
+            .with_kind(match ray_in.kind {
+                RayKind::Shadow => RayKind::Shadow,
+                _ => RayKind::Indirect,
+            })
+    }
+
+    // `spawn` towards a ray a material scattered. A wavelength the material
+    // gave it, such as the one dispersive glass picks for an RGB path,
+    // stays with the path from then on.
+    pub fn spawn_scattered(&self, ray_in: &Ray, scattered: &Ray) -> Ray {
+        let wavelength = scattered.wavelength.or(ray_in.wavelength);
+        self.spawn(ray_in, scattered.direction)
+            .with_wavelength(wavelength)
+    }
+
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: DVec3) {
         self.front_face = ray.direction.dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -64,22 +211,91 @@ impl HitRecord {
             -outward_normal
         };
     }
+
+    // Like `set_face_normal` for surfaces shaded with a normal other than
+    // their true one, such as smooth-shaded meshes. The side the ray hits
+    // is decided by `outward_normal`, and the shading normal is flipped
+    // with it, so that a shading normal tilted past the ray doesn't turn
+    // the surface inside out.
+    pub fn set_shading_normal(&mut self, ray: &Ray, outward_normal: DVec3, shading_normal: DVec3) {
+        self.set_face_normal(ray, outward_normal);
+        self.normal = if self.front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+    }
 }
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, ray: &Ray, interval: Range<f64>) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>>;
     fn bounding_box(&self) -> Option<AABB>;
+
+    // Smallest and largest `axis · p` over the points `p` of the object,
+    // for bounding volumes tighter than its box. By default the box's own
+    // extent; primitives that fit their box poorly give their exact one.
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        let bounds = self.bounding_box()?;
+        let center = 0.5 * (bounds.min + bounds.max).dot(axis);
+        let reach = 0.5 * (bounds.max - bounds.min).dot(axis.abs());
+        Some((center - reach, center + reach))
+    }
+
+    // Whether a surface that blocks light lies on `ray` within `interval`,
+    // for shadow rays that don't need to know which. Surfaces cut away
+    // where the ray meets them, and index-matched ones, let it through; see
+    // `blocks`. By default closest-hit queries from one surface to the
+    // next; hierarchies that can stop at the first object they find, and
+    // primitives that can skip building the record for materials that
+    // always block, override it.
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        let mut interval = interval;
+        while let Some(rec) = self.hit(ray, interval) {
+            if blocks(&rec) {
+                return true;
+            }
+            interval = interval.with_min(rec.t + rec.ray_epsilon());
+        }
+        false
+    }
+
+    // Closest hit of each of `rays` within `interval`, into `hits`, as
+    // `hit` would find it. By default one ray at a time; hierarchies
+    // override it to walk their nodes once for a whole bundle of coherent
+    // rays, such as a tile of camera rays.
+    fn hit_packet<'a>(
+        &'a self,
+        rays: &[Ray],
+        interval: Interval,
+        hits: &mut [Option<HitRecord<'a>>],
+    ) {
+        for (ray, hit) in rays.iter().zip(hits) {
+            *hit = self.hit(ray, interval);
+        }
+    }
+
+    // The object as a triangle mesh, for backends that intersect the
+    // triangles themselves, such as Embree.
+    fn as_mesh(&self) -> Option<&Mesh> {
+        None
+    }
+
+    // The object this one places in the world, and the transform it is
+    // placed with, for backends that instance shared geometry themselves.
+    fn as_instance(&self) -> Option<(&dyn Hittable, DAffine3)> {
+        None
+    }
 }
 
 pub type HittableList = Vec<Arc<dyn Hittable>>;
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, interval: Range<f64>) -> Option<HitRecord> {
-        let mut closest_so_far = interval.end;
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let mut closest_so_far = interval.max;
         let mut hit_record = None;
 
         for object in self.iter() {
-            if let Some(temp_rec) = object.hit(ray, interval.start..closest_so_far) {
+            if let Some(temp_rec) = object.hit(ray, interval.with_max(closest_so_far)) {
                 closest_so_far = temp_rec.t;
                 hit_record = Some(temp_rec);
             }
@@ -88,6 +304,10 @@ impl Hittable for HittableList {
         hit_record
     }
 
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        self.iter().any(|object| object.hit_any(ray, interval))
+    }
+
     fn bounding_box(&self) -> Option<AABB> {
         if self.is_empty() {
             return None;
@@ -109,3 +329,35 @@ impl Hittable for HittableList {
         output_box
     }
 }
+
+// Whether the surface at `rec` stops shadow rays, rather than being cut
+// away there or index-matched.
+pub fn blocks(rec: &HitRecord) -> bool {
+    !rec.material.is_index_matched() && !rec.material.is_cut_out(rec)
+}
+
+// The closest hit on `world` along `ray` within `interval`, passing through
+// surfaces that are cut away where the ray meets them, such as the
+// transparent parts of a `Cutout` leaf. Its `t` is along `ray`.
+pub fn hit_surface<'a>(
+    world: &'a dyn Hittable,
+    ray: &Ray,
+    interval: Interval,
+) -> Option<HitRecord<'a>> {
+    let mut through = *ray;
+    let mut interval = interval;
+    let mut travelled = 0.0;
+    loop {
+        let mut rec = world.hit(&through, interval)?;
+        if !rec.material.is_cut_out(&rec) {
+            rec.t += travelled;
+            return Some(rec);
+        }
+        // Carry on from the far side of the surface.
+        interval = Interval::new(rec.ray_epsilon(), interval.max - rec.t);
+        travelled += rec.t;
+        through = rec
+            .spawn(&through, through.direction)
+            .with_kind(through.kind);
+    }
+}
@@ -0,0 +1,202 @@
+use glam::DVec3;
+use std::error::Error;
+use std::f64::consts::PI;
+
+// The distribution of light from a real luminaire, read from an IES LM-63
+// photometric file as manufacturers publish them. Only type C photometry is
+// read, the kind used for building and street lighting: vertical angles run
+// from 0 along the fitting's axis, which points down as it hangs, to 180
+// straight back, and horizontal angles turn around that axis. Values are
+// scaled so that the brightest direction is 1, leaving the light's own
+// intensity to set the brightness.
+#[derive(Clone, Debug)]
+pub struct IesProfile {
+    // Both in degrees, ascending.
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    // One row of vertical samples per horizontal angle.
+    candela: Vec<Vec<f64>>,
+    // Mean of the profile over the sphere, for the light's total power.
+    average: f64,
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| format!("{path}: {e}").into())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut lines = text.lines();
+        // Keywords such as [MANUFAC] come first, up to the tilt.
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find(|line| line.starts_with("TILT="))
+            .ok_or("no TILT= line")?;
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                word.parse::<f64>()
+                    .map_err(|_| format!("'{word}' is not a number"))
+            });
+        let mut next = || -> Result<f64, String> {
+            numbers
+                .next()
+                .unwrap_or_else(|| Err("file ends early".into()))
+        };
+        // Lamp tilt factors only matter for lamps that can be tipped in
+        // their fitting; they are skipped.
+        if tilt == "TILT=INCLUDE" {
+            next()?;
+            let pairs = next()? as usize;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        // Units and luminous opening dimensions, ballast factor, a reserved
+        // value and input watts.
+        for _ in 0..7 {
+            next()?;
+        }
+        if photometric_type != 1.0 {
+            return Err("only type C photometry is supported".into());
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err("no angles".into());
+        }
+        let mut read = |count: usize| (0..count).map(|_| next()).collect::<Result<Vec<_>, _>>();
+        let vertical = read(vertical_count)?;
+        let horizontal = read(horizontal_count)?;
+        let mut candela = Vec::with_capacity(horizontal_count);
+        for _ in 0..horizontal_count {
+            candela.push(read(vertical_count)?);
+        }
+        let ascending = |angles: &[f64]| angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !ascending(&vertical) || !ascending(&horizontal) {
+            return Err("angles must be in ascending order".into());
+        }
+
+        let peak = candela
+            .iter()
+            .flatten()
+            .fold(0.0f64, |peak, &c| peak.max(c));
+        if !(peak * multiplier > 0.0) {
+            return Err("the luminaire gives no light".into());
+        }
+        for row in &mut candela {
+            for value in row {
+                *value = value.max(0.0) / peak;
+            }
+        }
+        let mut profile = Self {
+            vertical,
+            horizontal,
+            candela,
+            average: 0.0,
+        };
+        profile.average = profile.integrate();
+        Ok(profile)
+    }
+
+    // The profile towards `direction`, leaving a luminaire aimed along
+    // `axis`. Horizontal angles are measured from the first of
+    // `axis.any_orthonormal_pair()`, towards the second.
+    pub fn eval(&self, axis: DVec3, direction: DVec3) -> f64 {
+        let (Some(axis), Some(direction)) = (axis.try_normalize(), direction.try_normalize())
+        else {
+            return 0.0;
+        };
+        let (tangent, bitangent) = axis.any_orthonormal_pair();
+        let vertical = direction.dot(axis).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = direction
+            .dot(bitangent)
+            .atan2(direction.dot(tangent))
+            .to_degrees()
+            .rem_euclid(360.0);
+        self.lookup(vertical, horizontal)
+    }
+
+    // Fraction of the light of a uniform source of the same peak that the
+    // profile lets out.
+    pub fn average(&self) -> f64 {
+        self.average
+    }
+
+    // Bilinear lookup at angles in degrees. Files only give the part of the
+    // circle the fitting's symmetry doesn't repeat: a single horizontal
+    // angle for one that is the same all round, up to 90 for quadrants
+    // that mirror each other and up to 180 for mirrored halves.
+    fn lookup(&self, vertical: f64, horizontal: f64) -> f64 {
+        let last = *self.horizontal.last().unwrap();
+        let horizontal = if last == 0.0 {
+            0.0
+        } else if last == 90.0 {
+            let half = if horizontal > 180.0 {
+                360.0 - horizontal
+            } else {
+                horizontal
+            };
+            if half > 90.0 {
+                180.0 - half
+            } else {
+                half
+            }
+        } else if last == 180.0 && horizontal > 180.0 {
+            360.0 - horizontal
+        } else {
+            // Full circles may stop short of 360.
+            horizontal.min(last)
+        };
+        let Some((h0, h1, th)) = bracket(&self.horizontal, horizontal) else {
+            return 0.0;
+        };
+        let Some((v0, v1, tv)) = bracket(&self.vertical, vertical) else {
+            return 0.0;
+        };
+        let row = |h: usize| (1.0 - tv) * self.candela[h][v0] + tv * self.candela[h][v1];
+        (1.0 - th) * row(h0) + th * row(h1)
+    }
+
+    // Midpoint rule over a grid of the sphere, weighted by solid angle.
+    fn integrate(&self) -> f64 {
+        const STEPS: usize = 180;
+        let mut total = 0.0;
+        for i in 0..STEPS {
+            let theta = (i as f64 + 0.5) * PI / STEPS as f64;
+            for j in 0..2 * STEPS {
+                let phi = (j as f64 + 0.5) * 180.0 / STEPS as f64;
+                total += theta.sin() * self.lookup(theta.to_degrees(), phi);
+            }
+        }
+        let cell = (PI / STEPS as f64) * (PI / STEPS as f64);
+        total * cell / (4.0 * PI)
+    }
+}
+
+// The samples of `angles` around `angle`, and how far it is between them;
+// `None` outside them, where the fitting gives no light. A single angle
+// covers everything.
+fn bracket(angles: &[f64], angle: f64) -> Option<(usize, usize, f64)> {
+    if angles.len() == 1 {
+        return Some((0, 0, 0.0));
+    }
+    let last = angles.len() - 1;
+    if angle < angles[0] || angle > angles[last] {
+        return None;
+    }
+    let upper = angles.partition_point(|&a| a < angle).clamp(1, last);
+    let lower = upper - 1;
+    let t = (angle - angles[lower]) / (angles[upper] - angles[lower]);
+    Some((lower, upper, t.clamp(0.0, 1.0)))
+}
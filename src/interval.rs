@@ -0,0 +1,68 @@
+// Span of ray parameters, or of coordinates along an axis, from `min` to
+// `max`. Empty when `max` is below `min`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Interval {
+    pub const EMPTY: Self = Self::new(f64::INFINITY, f64::NEG_INFINITY);
+    pub const UNIVERSE: Self = Self::new(f64::NEG_INFINITY, f64::INFINITY);
+
+    pub const fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+
+    // Everything beyond `min`, for rays that may hit anything past their
+    // start.
+    pub const fn after(min: f64) -> Self {
+        Self::new(min, f64::INFINITY)
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max < self.min
+    }
+
+    // Whether `x` is in the interval, ends included.
+    pub fn contains(&self, x: f64) -> bool {
+        self.min <= x && x <= self.max
+    }
+
+    // Whether `x` is strictly inside, for hits that must lie beyond the
+    // start of a ray and before the closest one found so far.
+    pub fn surrounds(&self, x: f64) -> bool {
+        self.min < x && x < self.max
+    }
+
+    pub fn clamp(&self, x: f64) -> f64 {
+        x.max(self.min).min(self.max)
+    }
+
+    // Grown by `delta` in all, half at each end.
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = 0.5 * delta;
+        Self::new(self.min - padding, self.max + padding)
+    }
+
+    // The same start, ending at `max`: what is left to search once a hit
+    // at `max` has been found.
+    pub fn with_max(&self, max: f64) -> Self {
+        Self::new(self.min, max)
+    }
+
+    // The same end, starting at `min`: what is left to search beyond a
+    // surface at `min` that was passed through.
+    pub fn with_min(&self, min: f64) -> Self {
+        Self::new(min, self.max)
+    }
+
+    // Overlap of the two.
+    pub fn intersect(&self, other: Self) -> Self {
+        Self::new(self.min.max(other.min), self.max.min(other.max))
+    }
+}
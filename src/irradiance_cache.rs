@@ -0,0 +1,87 @@
+use crate::hittable::HitRecord;
+use crate::sampler::mix_hash;
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const SHARDS: usize = 64;
+
+// Hash-grid cache of the radiance leaving diffuse surfaces, keyed by a grid
+// cell and the dominant axis of the normal. Paths that reach a cell with
+// enough samples stop there and use the cached value, trading bias (the
+// cell's average stands in for the exact point) for far fewer bounces.
+// Cells are shared between render threads, so renders with the cache
+// enabled are not bit-for-bit reproducible.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IrradianceCacheSettings {
+    // Edge length of a grid cell in world units. Smaller cells blur less
+    // lighting detail but take longer to fill.
+    pub cell_size: f64,
+    // Samples a cell needs before it is used instead of tracing further.
+    pub min_samples: u32,
+}
+
+impl Default for IrradianceCacheSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 0.1,
+            min_samples: 32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct CacheEntry {
+    sum: DVec3,
+    count: u32,
+}
+
+pub struct IrradianceCache {
+    settings: IrradianceCacheSettings,
+    shards: Vec<Mutex<HashMap<u64, CacheEntry>>>,
+}
+
+impl IrradianceCache {
+    pub fn new(settings: IrradianceCacheSettings) -> Self {
+        Self {
+            settings,
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    pub fn key(&self, rec: &HitRecord) -> u64 {
+        let cell = (rec.point / self.settings.cell_size.max(1e-6)).floor();
+        let n = rec.normal.abs();
+        let axis = if n.x >= n.y && n.x >= n.z {
+            0
+        } else if n.y >= n.z {
+            1
+        } else {
+            2
+        };
+        let direction = 2 * axis as u64 + (rec.normal[axis] < 0.0) as u64;
+        let mut h = mix_hash(cell.x as i64 as u64);
+        h = mix_hash(h ^ cell.y as i64 as u64);
+        h = mix_hash(h ^ cell.z as i64 as u64);
+        mix_hash(h ^ direction)
+    }
+
+    // Cached outgoing radiance, once the cell has enough samples.
+    pub fn lookup(&self, key: u64) -> Option<DVec3> {
+        let shard = self.shards[key as usize % SHARDS].lock().unwrap();
+        let entry = shard.get(&key)?;
+        (entry.count >= self.settings.min_samples).then(|| entry.sum / entry.count as f64)
+    }
+
+    pub fn insert(&self, key: u64, radiance: DVec3) {
+        if !radiance.is_finite() {
+            return;
+        }
+        let mut shard = self.shards[key as usize % SHARDS].lock().unwrap();
+        let entry = shard.entry(key).or_default();
+        entry.sum += radiance;
+        entry.count += 1;
+    }
+}
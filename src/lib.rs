@@ -1,8 +1,63 @@
+pub mod accelerator;
+pub mod albedo_lut;
+pub mod animation;
+pub mod bake;
+pub mod bloom;
 pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
+pub mod color;
+pub mod color_space;
+pub mod contact_sheet;
+pub mod control;
+#[cfg(feature = "oidn")]
+pub mod denoise;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod distributed;
+#[cfg(feature = "embree")]
+pub mod embree;
+pub mod environment;
+pub mod error;
+pub mod filter;
+pub mod generators;
+#[cfg(feature = "gpu")]
+pub mod gpu;
 pub mod hittable;
+pub mod ies;
+pub mod interval;
+pub mod irradiance_cache;
+pub mod lights;
+pub mod lut;
 pub mod material;
+pub mod material_preview;
+pub mod metrics;
+pub mod mipmap;
 pub mod objects;
+pub mod output;
+pub mod path;
+pub mod photon_map;
+#[cfg(feature = "physics")]
+pub mod physics;
+pub mod polynomial;
+pub mod procedural;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod qbvh;
+pub mod query;
 pub mod ray;
+pub mod real;
+pub mod reference_scenes;
+pub mod renderer;
+pub mod sample_map;
+pub mod sampler;
+pub mod scatter;
 pub mod scene;
+pub mod spherical_harmonics;
+pub mod stats;
+mod stamp;
+pub mod temporal;
 pub mod texture;
+pub mod uv_transform;
+pub mod volume;
+pub mod web;
+pub mod wireframe;
@@ -0,0 +1,907 @@
+use crate::environment::Environment;
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::ies::IesProfile;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::triangle::Triangle;
+use crate::ray::Ray;
+use crate::renderer::luminance;
+use crate::sampler::Sampler;
+use glam::{DAffine3, DVec2, DVec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Emissive primitives the integrator samples directly. Lights are picked in
+// proportion to their emitted power, so a few bright windows aren't drowned
+// out by thousands of dim street lamps. Analytic lights have no surface to
+// hit, so every one of them is sampled at each vertex instead.
+#[derive(Default)]
+pub struct LightSet {
+    lights: Vec<AreaLight>,
+    // Running sum of light powers, for picking a light by binary search.
+    cdf: Vec<f64>,
+    analytic: Vec<AnalyticLight>,
+    portals: Vec<Portal>,
+    // Set when the scene is known to have no index-matched surfaces, so
+    // light that nothing blocks reaches a point unchanged.
+    without_media: bool,
+}
+
+struct AreaLight {
+    shape: LightShape,
+    material: Arc<dyn Material>,
+}
+
+enum LightShape {
+    Triangle {
+        vertices: [DVec3; 3],
+        uvs: [DVec2; 3],
+    },
+    Sphere {
+        center: DVec3,
+        radius: f64,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct LightSample {
+    pub point: DVec3,
+    pub normal: DVec3,
+    pub radiance: DVec3,
+    // Area density of `point`, including the choice of light.
+    pub pdf: f64,
+}
+
+impl LightSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Number of area lights.
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn add_analytic(&mut self, light: AnalyticLight) {
+        self.analytic.push(light);
+    }
+
+    pub fn analytic(&self) -> &[AnalyticLight] {
+        &self.analytic
+    }
+
+    pub fn add_portal(&mut self, portal: Portal) {
+        self.portals.push(portal);
+    }
+
+    pub fn portals(&self) -> &[Portal] {
+        &self.portals
+    }
+
+    pub fn mark_without_media(&mut self) {
+        self.without_media = true;
+    }
+
+    // Whether shadow rays may cross media behind index-matched surfaces.
+    pub fn has_media(&self) -> bool {
+        !self.without_media
+    }
+
+    // Registers an emissive triangle and returns its light index.
+    pub fn add_triangle(&mut self, triangle: &Triangle) -> u32 {
+        self.add_placed_triangle(triangle, DAffine3::IDENTITY)
+    }
+
+    // Registers an emissive triangle placed in the world by `transform`.
+    pub fn add_placed_triangle(&mut self, triangle: &Triangle, transform: DAffine3) -> u32 {
+        self.add(AreaLight {
+            shape: LightShape::Triangle {
+                vertices: triangle.vertices().map(|p| transform.transform_point3(p)),
+                uvs: triangle.uvs,
+            },
+            material: triangle.material.clone(),
+        })
+    }
+
+    pub fn add_sphere(&mut self, center: DVec3, radius: f64, material: Arc<dyn Material>) -> u32 {
+        self.add(AreaLight {
+            shape: LightShape::Sphere {
+                center,
+                radius: radius.abs(),
+            },
+            material,
+        })
+    }
+
+    fn add(&mut self, light: AreaLight) -> u32 {
+        let total = self.cdf.last().copied().unwrap_or(0.0);
+        self.cdf.push(total + light.power());
+        self.lights.push(light);
+        self.lights.len() as u32 - 1
+    }
+
+    fn total_power(&self) -> f64 {
+        self.cdf.last().copied().unwrap_or(0.0)
+    }
+
+    fn pick_probability(&self, index: usize) -> f64 {
+        let previous = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+        (self.cdf[index] - previous) / self.total_power()
+    }
+
+    // Picks a light with `u` and a point on it with `uv`, as seen from
+    // `reference`.
+    pub fn sample(&self, reference: DVec3, u: f64, uv: (f64, f64)) -> Option<LightSample> {
+        let total = self.total_power();
+        if total <= 0.0 {
+            return None;
+        }
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u * total)
+            .min(self.lights.len() - 1);
+        let light = &self.lights[index];
+        let (point, normal, (lu, lv), area_pdf) = light.shape.sample(reference, uv);
+        Some(LightSample {
+            point,
+            normal,
+            radiance: light.material.emitted(lu, lv, point),
+            pdf: self.pick_probability(index) * area_pdf,
+        })
+    }
+
+    // Starts a photon at a light picked in proportion to its power, for
+    // tracing light forwards. Returns the ray and the flux it carries
+    // divided by the densities it was sampled with. Directional lights
+    // shine through the sphere around `scene`, the world's bounds, and emit
+    // nothing when it is unbounded.
+    pub fn emit(
+        &self,
+        scene: Option<AABB>,
+        u: f64,
+        uv: (f64, f64),
+        (d1, d2): (f64, f64),
+    ) -> Option<(Ray, DVec3)> {
+        let area = self.total_power();
+        let total = area + self.analytic.iter().map(|l| l.power(scene)).sum::<f64>();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut target = u * total;
+        if target < area {
+            let index = self
+                .cdf
+                .partition_point(|&c| c <= target)
+                .min(self.lights.len() - 1);
+            let light = &self.lights[index];
+            let (point, normal, (lu, lv), area_pdf) = light.shape.sample_surface(uv);
+            // Triangles emit from both faces, spheres only outwards.
+            let (normal, d1, sides) = match light.shape {
+                LightShape::Triangle { .. } if d1 < 0.5 => (normal, 2.0 * d1, 2.0),
+                LightShape::Triangle { .. } => (-normal, 2.0 * d1 - 1.0, 2.0),
+                LightShape::Sphere { .. } => (normal, d1, 1.0),
+            };
+            let pick = self.pick_probability(index) * area / total;
+            let radiance = light.material.emitted(lu, lv, point);
+            let ray = Ray::new(point, cosine_direction(normal, (d1, d2)));
+            return Some((ray, radiance * PI * sides / (pick * area_pdf)));
+        }
+        target -= area;
+        let count = self.analytic.len();
+        for (i, light) in self.analytic.iter().enumerate() {
+            let power = light.power(scene);
+            if power > 0.0 && (target < power || i + 1 == count) {
+                let (ray, flux) = light.emit(scene, uv)?;
+                return Some((ray, flux * total / power));
+            }
+            target -= power;
+        }
+        None
+    }
+
+    // Area density with which `sample` picks `point` on light `index` from
+    // `reference`.
+    pub fn pdf(&self, index: u32, reference: DVec3) -> f64 {
+        let total = self.total_power();
+        let Some(light) = self.lights.get(index as usize).filter(|_| total > 0.0) else {
+            return 0.0;
+        };
+        self.pick_probability(index as usize) * light.shape.area_pdf(reference)
+    }
+}
+
+impl AreaLight {
+    fn power(&self) -> f64 {
+        let emission = self.shape.mean_emission(&*self.material);
+        luminance(emission).max(0.0) * self.shape.area()
+    }
+}
+
+// Emission of `material` averaged over a triangle's surface, which for a
+// textured light needn't be what it gives out at any one corner.
+pub fn triangle_emission(triangle: &Triangle, material: &dyn Material) -> DVec3 {
+    let shape = LightShape::Triangle {
+        vertices: triangle.vertices(),
+        uvs: triangle.uvs,
+    };
+    shape.mean_emission(material)
+}
+
+// Emission of `material` averaged over the sphere's surface.
+pub fn sphere_emission(center: DVec3, radius: f64, material: &dyn Material) -> DVec3 {
+    let radius = radius.abs();
+    LightShape::Sphere { center, radius }.mean_emission(material)
+}
+
+impl LightShape {
+    fn area(&self) -> f64 {
+        match self {
+            LightShape::Triangle {
+                vertices: [p0, p1, p2],
+                ..
+            } => 0.5 * (*p1 - *p0).cross(*p2 - *p0).length(),
+            LightShape::Sphere { radius, .. } => 4.0 * PI * radius * radius,
+        }
+    }
+
+    // Spheres are sampled over the half facing `reference`, which holds
+    // every point visible from there.
+    fn area_pdf(&self, reference: DVec3) -> f64 {
+        match self {
+            LightShape::Triangle { .. } => 1.0 / self.area(),
+            LightShape::Sphere { center, radius } => {
+                if reference.distance_squared(*center) > radius * radius {
+                    2.0 / self.area()
+                } else {
+                    1.0 / self.area()
+                }
+            }
+        }
+    }
+
+    // Emission averaged over a grid of points spread evenly by area.
+    fn mean_emission(&self, material: &dyn Material) -> DVec3 {
+        const GRID: usize = 8;
+        let mut sum = DVec3::ZERO;
+        for i in 0..GRID {
+            for j in 0..GRID {
+                let uv = (
+                    (i as f64 + 0.5) / GRID as f64,
+                    (j as f64 + 0.5) / GRID as f64,
+                );
+                let (point, _, (u, v), _) = self.sample_surface(uv);
+                sum += material.emitted(u, v, point);
+            }
+        }
+        sum / (GRID * GRID) as f64
+    }
+
+    // Uniform point over the whole surface, as `sample` returns it.
+    fn sample_surface(&self, uv: (f64, f64)) -> (DVec3, DVec3, (f64, f64), f64) {
+        let inside = match self {
+            LightShape::Triangle { vertices, .. } => vertices[0],
+            LightShape::Sphere { center, .. } => *center,
+        };
+        self.sample(inside, uv)
+    }
+
+    // Point, outward normal, texture coordinates and area density.
+    fn sample(&self, reference: DVec3, (u1, u2): (f64, f64)) -> (DVec3, DVec3, (f64, f64), f64) {
+        match self {
+            LightShape::Triangle {
+                vertices: [p0, p1, p2],
+                uvs,
+            } => {
+                let su = u1.sqrt();
+                let (b1, b2) = (su * (1.0 - u2), su * u2);
+                let b0 = 1.0 - b1 - b2;
+                let point = b0 * *p0 + b1 * *p1 + b2 * *p2;
+                let normal = (*p1 - *p0).cross(*p2 - *p0).normalize_or_zero();
+                let uv = b0 * uvs[0] + b1 * uvs[1] + b2 * uvs[2];
+                (point, normal, uv.into(), self.area_pdf(reference))
+            }
+            LightShape::Sphere { center, radius } => {
+                let outside = reference.distance_squared(*center) > radius * radius;
+                let z = if outside { u1 } else { 1.0 - 2.0 * u1 };
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * u2;
+                let axis = (reference - *center).try_normalize().unwrap_or(DVec3::Y);
+                let (tangent, bitangent) = axis.any_orthonormal_pair();
+                let normal = r * phi.cos() * tangent + r * phi.sin() * bitangent + z * axis;
+                let point = *center + *radius * normal;
+                (point, normal, sphere_uv(normal), self.area_pdf(reference))
+            }
+        }
+    }
+}
+
+// An opening, such as a window, that light from the environment reaches
+// the scene through: the parallelogram with a corner at `corner` and sides
+// `edge1` and `edge2`, with `edge1 × edge2` pointing out of the scene.
+// When a scene has portals, the environment is only sampled through them
+// from points they face, so interiors lit by the sky through small windows
+// don't waste samples on the walls around them. Light from elsewhere in
+// the environment is still found by BSDF sampling. Portals aren't part of
+// the world and rays pass through them.
+#[derive(Clone, Copy, Debug)]
+pub struct Portal {
+    pub corner: DVec3,
+    pub edge1: DVec3,
+    pub edge2: DVec3,
+}
+
+impl Portal {
+    fn normal(&self) -> DVec3 {
+        self.edge1.cross(self.edge2).normalize_or_zero()
+    }
+
+    // Distance along `direction` from `origin` to where it crosses the
+    // portal, if it does.
+    fn crossing(&self, origin: DVec3, direction: DVec3) -> Option<f64> {
+        let normal = self.edge1.cross(self.edge2);
+        let denominator = normal.dot(direction);
+        if denominator == 0.0 {
+            return None;
+        }
+        let t = normal.dot(self.corner - origin) / denominator;
+        let offset = origin + t * direction - self.corner;
+        // Coordinates of the crossing along the sides, from their dual
+        // basis in the portal's plane.
+        let a = self.edge2.cross(normal).dot(offset) / self.edge2.cross(normal).dot(self.edge1);
+        let b = normal.cross(self.edge1).dot(offset) / normal.cross(self.edge1).dot(self.edge2);
+        (t > 0.0 && (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b)).then_some(t)
+    }
+}
+
+// Cells along each side of a portal's direction image.
+const PORTAL_RESOLUTION: usize = 64;
+
+// Samples the environment through portals in proportion to the radiance
+// coming through them, as PBRT's portal light does. Each portal keeps an
+// image of the environment over the directions out through its plane,
+// parameterised by their angles across its two sides, in which everything
+// seen through the portal from a point is a rectangle. Points on the far
+// side of every portal aren't faced by any and sample the environment as
+// if there were none.
+pub struct PortalDistribution {
+    images: Vec<PortalImage>,
+}
+
+struct PortalImage {
+    portal: Portal,
+    // Orthonormal frame with `x` along `edge1` and `z` out of the scene.
+    x: DVec3,
+    y: DVec3,
+    z: DVec3,
+    // Summed-area table of luminance times the solid angle per unit of
+    // image, integrated over the cells below and left of each corner.
+    table: Vec<f64>,
+}
+
+impl PortalDistribution {
+    pub fn new(portals: &[Portal], environment: &dyn Environment) -> Self {
+        let images = portals
+            .iter()
+            .filter(|portal| portal.normal() != DVec3::ZERO)
+            .map(|portal| PortalImage::new(*portal, environment))
+            .collect();
+        Self { images }
+    }
+
+    // Whether any portal faces `reference`, so environment light reaching
+    // it should be sampled through the portals.
+    pub fn faces(&self, reference: DVec3) -> bool {
+        self.images.iter().any(|image| image.faces(reference))
+    }
+
+    // A direction from `reference` out through a portal and its
+    // solid-angle density. Portals are picked by the light they let
+    // through towards `reference`.
+    pub fn sample(&self, reference: DVec3, u: f64, (u1, u2): (f64, f64)) -> Option<(DVec3, f64)> {
+        let windows: Vec<f64> = self
+            .images
+            .iter()
+            .map(|image| image.window(reference).map_or(0.0, |w| image.integral(&w)))
+            .collect();
+        let total: f64 = windows.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut target = u * total;
+        let index = windows
+            .iter()
+            .position(|&w| {
+                target -= w;
+                target < 0.0
+            })
+            .unwrap_or(windows.len() - 1);
+        let image = &self.images[index];
+        let window = image.window(reference)?;
+        let direction = image.sample(&window, (u1, u2))?;
+        // Parallelograms cover only part of their window.
+        image.portal.crossing(reference, direction)?;
+        let pdf = self.pdf(reference, direction);
+        (pdf > 0.0).then_some((direction, pdf))
+    }
+
+    // Solid-angle density with which `sample` picks `direction` from
+    // `reference`: zero unless it leaves through a portal facing
+    // `reference`, and summed over the portals it leaves through.
+    pub fn pdf(&self, reference: DVec3, direction: DVec3) -> f64 {
+        let mut total = 0.0;
+        let mut density = 0.0;
+        for image in &self.images {
+            let Some(window) = image.window(reference) else {
+                continue;
+            };
+            total += image.integral(&window);
+            if image.portal.crossing(reference, direction).is_some() {
+                density += image.density(direction);
+            }
+        }
+        if total > 0.0 {
+            density / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl PortalImage {
+    fn new(portal: Portal, environment: &dyn Environment) -> Self {
+        let z = portal.normal();
+        let x = portal.edge1.normalize();
+        let y = z.cross(x);
+        let n = PORTAL_RESOLUTION;
+        let cell = 1.0 / (n * n) as f64;
+        let mut image = Self {
+            portal,
+            x,
+            y,
+            z,
+            table: vec![0.0; (n + 1) * (n + 1)],
+        };
+        for i in 0..n {
+            for j in 0..n {
+                let uv = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let (direction, jacobian) = image.direction(uv);
+                let value = luminance(environment.value(direction)).max(0.0) * jacobian;
+                image.table[(i + 1) * (n + 1) + j + 1] = value * cell
+                    + image.table[i * (n + 1) + j + 1]
+                    + image.table[(i + 1) * (n + 1) + j]
+                    - image.table[i * (n + 1) + j];
+            }
+        }
+        image
+    }
+
+    fn faces(&self, reference: DVec3) -> bool {
+        (reference - self.portal.corner).dot(self.z) < 0.0
+    }
+
+    // The direction at image coordinates `(u, v)` and the solid angle it
+    // covers per unit of image.
+    fn direction(&self, (u, v): (f64, f64)) -> (DVec3, f64) {
+        let local = DVec3::new((PI * (u - 0.5)).tan(), (PI * (v - 0.5)).tan(), 1.0).normalize();
+        let jacobian = PI * PI * (1.0 - local.x * local.x) * (1.0 - local.y * local.y) / local.z;
+        let direction = local.x * self.x + local.y * self.y + local.z * self.z;
+        (direction, jacobian.max(0.0))
+    }
+
+    // Image coordinates of `direction`, which must leave through the
+    // portal's plane.
+    fn coordinates(&self, direction: DVec3) -> (f64, f64) {
+        let z = direction.dot(self.z);
+        let u = direction.dot(self.x).atan2(z) / PI + 0.5;
+        let v = direction.dot(self.y).atan2(z) / PI + 0.5;
+        (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0))
+    }
+
+    // The rectangle of the image seen through the portal from `reference`,
+    // as ([u0, u1], [v0, v1]); none when the portal doesn't face it.
+    fn window(&self, reference: DVec3) -> Option<[(f64, f64); 2]> {
+        if !self.faces(reference) {
+            return None;
+        }
+        let Portal {
+            corner,
+            edge1,
+            edge2,
+        } = self.portal;
+        let mut window = [(1.0f64, 0.0f64); 2];
+        for point in [
+            corner,
+            corner + edge1,
+            corner + edge2,
+            corner + edge1 + edge2,
+        ] {
+            let (u, v) = self.coordinates(point - reference);
+            window[0] = (window[0].0.min(u), window[0].1.max(u));
+            window[1] = (window[1].0.min(v), window[1].1.max(v));
+        }
+        Some(window)
+    }
+
+    fn table(&self, i: usize, j: usize) -> f64 {
+        self.table[i * (PORTAL_RESOLUTION + 1) + j]
+    }
+
+    // The cell holding `x` along a side, and how far across it `x` is.
+    fn cell(x: f64) -> (usize, f64) {
+        let scaled = x * PORTAL_RESOLUTION as f64;
+        let i = (scaled as usize).min(PORTAL_RESOLUTION - 1);
+        (i, scaled - i as f64)
+    }
+
+    // Integral of the image over [0, u] × [0, v], bilinear within a cell.
+    fn cumulative(&self, u: f64, v: f64) -> f64 {
+        let ((i, fu), (j, fv)) = (Self::cell(u), Self::cell(v));
+        let (a, b) = (self.table(i, j), self.table(i + 1, j));
+        let (c, d) = (self.table(i, j + 1), self.table(i + 1, j + 1));
+        a + fu * (b - a) + fv * (c - a) + fu * fv * (d - b - c + a)
+    }
+
+    fn integral(&self, &[(u0, u1), (v0, v1)]: &[(f64, f64); 2]) -> f64 {
+        self.cumulative(u1, v1) - self.cumulative(u0, v1) - self.cumulative(u1, v0)
+            + self.cumulative(u0, v0)
+    }
+
+    // Image value at `(u, v)`: constant over each cell.
+    fn value(&self, (u, v): (f64, f64)) -> f64 {
+        let ((i, _), (j, _)) = (Self::cell(u), Self::cell(v));
+        let sum = self.table(i + 1, j + 1) - self.table(i + 1, j) - self.table(i, j + 1)
+            + self.table(i, j);
+        sum * (PORTAL_RESOLUTION * PORTAL_RESOLUTION) as f64
+    }
+
+    // Density over solid angle of `direction`, up to the integral over the
+    // window it was sampled in.
+    fn density(&self, direction: DVec3) -> f64 {
+        let uv = self.coordinates(direction);
+        let (_, jacobian) = self.direction(uv);
+        if jacobian > 0.0 {
+            self.value(uv) / jacobian
+        } else {
+            0.0
+        }
+    }
+
+    // A direction in `window` picked in proportion to the image, first
+    // along `u` by the marginal and then along `v` within its column.
+    fn sample(&self, window: &[(f64, f64); 2], (u1, u2): (f64, f64)) -> Option<DVec3> {
+        let [(u0, u_end), (v0, v_end)] = *window;
+        let marginal = |u: f64| self.cumulative(u, v_end) - self.cumulative(u, v0);
+        let u = invert(marginal, (u0, u_end), u1)?;
+        let (i, _) = Self::cell(u);
+        let column = |v: f64| {
+            let (j, fv) = Self::cell(v);
+            let below = self.table(i + 1, j) - self.table(i, j);
+            let above = self.table(i + 1, j + 1) - self.table(i, j + 1);
+            below + fv * (above - below)
+        };
+        let v = invert(column, (v0, v_end), u2)?;
+        Some(self.direction((u, v)).0)
+    }
+}
+
+// The point in `range` a fraction `u` of the way through the increasing
+// function `f`'s rise over it, by bisection.
+fn invert(f: impl Fn(f64) -> f64, (start, end): (f64, f64), u: f64) -> Option<f64> {
+    let (low, high) = (f(start), f(end));
+    if high <= low {
+        return None;
+    }
+    let target = low + u * (high - low);
+    let (mut a, mut b) = (start, end);
+    for _ in 0..48 {
+        let middle = 0.5 * (a + b);
+        if f(middle) < target {
+            a = middle;
+        } else {
+            b = middle;
+        }
+    }
+    Some(0.5 * (a + b))
+}
+
+// Point-like and distant lights. They can't be reached by BSDF sampling, so
+// their light samples need no MIS weight.
+pub enum AnalyticLight {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+// `intensity` is radiant intensity, in W/sr per colour channel, and falls
+// off with the square of the distance. A `radius` above 0 makes the light a
+// glowing sphere of that size with the same intensity, casting soft
+// shadows. A `profile` shapes the light as the luminaire it was measured
+// from, hanging straight down, with `intensity` its brightest direction.
+pub struct PointLight {
+    pub position: DVec3,
+    pub intensity: DVec3,
+    pub radius: f64,
+    pub profile: Option<Arc<IesProfile>>,
+}
+
+// A point light restricted to a cone around `direction`. The edge fades out
+// smoothly over the last `cone_delta` radians of `cone_angle`, the cone's
+// half-angle. `radius` and `profile` are as for `PointLight`, with the
+// profile aimed along `direction`.
+pub struct SpotLight {
+    pub position: DVec3,
+    pub direction: DVec3,
+    pub intensity: DVec3,
+    pub cone_angle: f64,
+    pub cone_delta: f64,
+    pub radius: f64,
+    pub profile: Option<Arc<IesProfile>>,
+}
+
+// Parallel light travelling along `direction`, like the sun. `irradiance`
+// is measured on a surface facing the light.
+pub struct DirectionalLight {
+    pub direction: DVec3,
+    pub irradiance: DVec3,
+}
+
+// Light arriving at a point from an analytic light, before the BSDF and
+// cosine are applied.
+pub struct Illumination {
+    // Unit vector from the shading point towards the light.
+    pub direction: DVec3,
+    // Infinite for directional lights.
+    pub distance: f64,
+    pub radiance: DVec3,
+}
+
+// Direction luminaire profiles of point lights are aimed along.
+const HANGING: DVec3 = DVec3::NEG_Y;
+
+impl AnalyticLight {
+    // Light reaching `point`. Lights with a radius draw a point on the
+    // part of their sphere facing it from `sampler`; others draw nothing.
+    pub fn illuminate(&self, point: DVec3, sampler: &mut dyn Sampler) -> Option<Illumination> {
+        match self {
+            AnalyticLight::Point(light) => {
+                let shape = profile(&light.profile, HANGING, point - light.position);
+                let intensity = shape * light.intensity;
+                sphere_illumination(point, light.position, light.radius, intensity, sampler)
+            }
+            AnalyticLight::Spot(light) => {
+                let (direction, _) = towards(point, light.position)?;
+                let cos_outer = light.cone_angle.cos();
+                let cos_inner = (light.cone_angle - light.cone_delta).max(0.0).cos();
+                let cosine = (-direction).dot(light.direction.normalize_or_zero());
+                let falloff = smoothstep(cos_outer, cos_inner, cosine)
+                    * profile(&light.profile, light.direction, -direction);
+                if falloff <= 0.0 {
+                    return None;
+                }
+                let intensity = falloff * light.intensity;
+                sphere_illumination(point, light.position, light.radius, intensity, sampler)
+            }
+            AnalyticLight::Directional(light) => Some(Illumination {
+                direction: -light.direction.try_normalize()?,
+                distance: f64::INFINITY,
+                radiance: light.irradiance,
+            }),
+        }
+    }
+
+    // Luminance of the total flux, for picking lights to emit photons
+    // from; see `LightSet::emit`.
+    fn power(&self, scene: Option<AABB>) -> f64 {
+        match self {
+            AnalyticLight::Point(light) => {
+                let spread = light.profile.as_ref().map_or(1.0, |p| p.average());
+                4.0 * PI * spread * luminance(light.intensity)
+            }
+            AnalyticLight::Spot(light) => {
+                let cone = 2.0 * PI * (1.0 - light.cone_angle.cos());
+                let spread = light
+                    .profile
+                    .as_ref()
+                    .map_or(cone, |p| cone.min(4.0 * PI * p.average()));
+                spread * luminance(light.intensity)
+            }
+            AnalyticLight::Directional(light) => match scene {
+                Some(scene) => {
+                    let radius = 0.5 * (scene.max - scene.min).length();
+                    PI * radius * radius * luminance(light.irradiance)
+                }
+                None => 0.0,
+            },
+        }
+        .max(0.0)
+    }
+
+    // A photon leaving the light in a direction picked with `uv`, and the
+    // flux it carries divided by the density of that direction.
+    fn emit(&self, scene: Option<AABB>, (u1, u2): (f64, f64)) -> Option<(Ray, DVec3)> {
+        let phi = 2.0 * PI * u2;
+        match self {
+            AnalyticLight::Point(light) => {
+                let z = 1.0 - 2.0 * u1;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let direction = DVec3::new(r * phi.cos(), r * phi.sin(), z);
+                let shape = profile(&light.profile, HANGING, direction);
+                let ray = Ray::new(light.position + light.radius * direction, direction);
+                Some((ray, 4.0 * PI * shape * light.intensity))
+            }
+            AnalyticLight::Spot(light) => {
+                let axis = light.direction.try_normalize()?;
+                let cos_outer = light.cone_angle.cos();
+                let cos_inner = (light.cone_angle - light.cone_delta).max(0.0).cos();
+                let cosine = 1.0 - u1 * (1.0 - cos_outer);
+                let r = (1.0 - cosine * cosine).max(0.0).sqrt();
+                let (tangent, bitangent) = axis.any_orthonormal_pair();
+                let direction = r * phi.cos() * tangent + r * phi.sin() * bitangent + cosine * axis;
+                let falloff = smoothstep(cos_outer, cos_inner, cosine)
+                    * profile(&light.profile, light.direction, direction);
+                let solid_angle = 2.0 * PI * (1.0 - cos_outer);
+                let ray = Ray::new(light.position + light.radius * direction, direction);
+                Some((ray, falloff * solid_angle * light.intensity))
+            }
+            // A disc as wide as the scene's bounding sphere, just outside it.
+            AnalyticLight::Directional(light) => {
+                let scene = scene?;
+                let direction = light.direction.try_normalize()?;
+                let center = 0.5 * (scene.min + scene.max);
+                let radius = 0.5 * (scene.max - scene.min).length();
+                let (tangent, bitangent) = direction.any_orthonormal_pair();
+                let r = radius * u1.sqrt();
+                let offset = r * phi.cos() * tangent + r * phi.sin() * bitangent;
+                let ray = Ray::new(center - radius * direction + offset, direction);
+                Some((ray, PI * radius * radius * light.irradiance))
+            }
+        }
+    }
+}
+
+// Cosine-weighted direction around the unit `normal`.
+pub(crate) fn cosine_direction(normal: DVec3, (u1, u2): (f64, f64)) -> DVec3 {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = normal.any_orthonormal_pair();
+    r * phi.cos() * tangent + r * phi.sin() * bitangent + (1.0 - u1).max(0.0).sqrt() * normal
+}
+
+// Light of `intensity` reaching `point` from a sphere of `radius` around
+// `position`, or from `position` itself for a radius of 0. Directions are
+// drawn uniformly over the cone the sphere fills, which it shines into
+// with radiance I / πr².
+fn sphere_illumination(
+    point: DVec3,
+    position: DVec3,
+    radius: f64,
+    intensity: DVec3,
+    sampler: &mut dyn Sampler,
+) -> Option<Illumination> {
+    let (centre, distance) = towards(point, position)?;
+    // Inside the sphere, the light is treated as a point at its centre.
+    if radius <= 0.0 || distance <= radius {
+        return Some(Illumination {
+            direction: centre,
+            distance,
+            radiance: intensity / (distance * distance),
+        });
+    }
+    let cos_max = (1.0 - (radius / distance).powi(2)).max(0.0).sqrt();
+    let (u1, u2) = sampler.next_2d();
+    let cosine = 1.0 - u1 * (1.0 - cos_max);
+    let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = centre.any_orthonormal_pair();
+    let direction = sine * phi.cos() * tangent + sine * phi.sin() * bitangent + cosine * centre;
+    // To the near side of the sphere.
+    let along = distance * cosine
+        - (radius * radius - (distance * sine).powi(2))
+            .max(0.0)
+            .sqrt();
+    let solid_angle = 2.0 * PI * (1.0 - cos_max);
+    Some(Illumination {
+        direction,
+        distance: along,
+        radiance: intensity * solid_angle / (PI * radius * radius),
+    })
+}
+
+// The profile of a luminaire aimed along `axis` towards `direction`; 1
+// without one.
+fn profile(profile: &Option<Arc<IesProfile>>, axis: DVec3, direction: DVec3) -> f64 {
+    profile.as_ref().map_or(1.0, |p| p.eval(axis, direction))
+}
+
+fn towards(point: DVec3, position: DVec3) -> Option<(DVec3, f64)> {
+    let offset = position - point;
+    let distance = offset.length();
+    (distance > 0.0).then(|| (offset / distance, distance))
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    if edge1 <= edge0 {
+        return if x >= edge0 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Same parameterisation as `Sphere` hits.
+fn sphere_uv(p: DVec3) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+// Marks hits on `object` as coming from light `index`, so the integrator
+// can weigh emission found by BSDF sampling against light sampling.
+pub struct Emitter {
+    pub object: Arc<dyn Hittable>,
+    pub index: u32,
+}
+
+impl Emitter {
+    pub fn new(object: Arc<dyn Hittable>, index: u32) -> Self {
+        Self { object, index }
+    }
+}
+
+impl Hittable for Emitter {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = self.object.hit(ray, interval)?;
+        rec.light = Some(self.index);
+        Some(rec)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        self.object.hit_any(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.object.bounding_box()
+    }
+
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        self.object.extent(axis)
+    }
+}
+
+// Weighted reservoir of light samples for resampled importance sampling.
+// Candidates are streamed through `update`; the survivor is distributed in
+// proportion to `target`, the unshadowed contribution at the shading point.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Reservoir {
+    pub sample: Option<LightSample>,
+    pub weight_sum: f64,
+    // Number of candidates the reservoir has seen.
+    pub count: u32,
+    // Target function value of `sample`.
+    pub target: f64,
+}
+
+impl Reservoir {
+    pub fn update(&mut self, sample: LightSample, weight: f64, target: f64, u: f64) {
+        if weight <= 0.0 || !weight.is_finite() {
+            return;
+        }
+        self.weight_sum += weight;
+        if u * self.weight_sum < weight {
+            self.sample = Some(sample);
+            self.target = target;
+        }
+    }
+
+    // The estimator weight of the kept sample, standing in for 1/pdf.
+    pub fn contribution_weight(&self) -> f64 {
+        if self.sample.is_none() || self.target <= 0.0 || self.count == 0 {
+            0.0
+        } else {
+            self.weight_sum / (self.count as f64 * self.target)
+        }
+    }
+}
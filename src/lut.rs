@@ -0,0 +1,146 @@
+use glam::DVec3;
+use std::error::Error;
+
+// A colour lookup table in the Adobe/Resolve `.cube` format, for matching a
+// show's grade. A file may hold a 1D table, a 3D table or both, in which
+// case the 1D table is a shaper applied first. Inputs are mapped from
+// [DOMAIN_MIN, DOMAIN_MAX] onto the table and clamped to it.
+pub struct Lut {
+    domain_min: DVec3,
+    domain_max: DVec3,
+    table_1d: Vec<DVec3>,
+    // Red varies fastest, then green, then blue.
+    table_3d: Vec<DVec3>,
+    size_3d: usize,
+}
+
+impl Lut {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| format!("{path}: {e}").into())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut domain_min = DVec3::ZERO;
+        let mut domain_max = DVec3::ONE;
+        let (mut size_1d, mut size_3d) = (0, 0);
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {message}", number + 1);
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let mut numbers = || -> Result<Vec<f64>, String> {
+                words
+                    .by_ref()
+                    .map(|w| w.parse().map_err(|_| error("expected a number")))
+                    .collect()
+            };
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let size = match numbers()?.as_slice() {
+                        [size] if *size >= 2.0 && size.fract() == 0.0 => *size as usize,
+                        _ => return Err(error("expected a table size of at least 2").into()),
+                    };
+                    if keyword == "LUT_1D_SIZE" {
+                        size_1d = size;
+                    } else {
+                        size_3d = size;
+                    }
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let value = match numbers()?.as_slice() {
+                        [r, g, b] => DVec3::new(*r, *g, *b),
+                        _ => return Err(error("expected three numbers").into()),
+                    };
+                    if keyword == "DOMAIN_MIN" {
+                        domain_min = value;
+                    } else {
+                        domain_max = value;
+                    }
+                }
+                // Resolve's older per-table ranges are taken as the domain
+                // of the whole LUT.
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => match numbers()?.as_slice() {
+                    [min, max] => {
+                        domain_min = DVec3::splat(*min);
+                        domain_max = DVec3::splat(*max);
+                    }
+                    _ => return Err(error("expected two numbers").into()),
+                },
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    return Err(error(&format!("unknown keyword '{keyword}'")).into());
+                }
+                _ => {
+                    let first = keyword.parse().map_err(|_| error("expected a number"))?;
+                    match numbers()?.as_slice() {
+                        [g, b] => entries.push(DVec3::new(first, *g, *b)),
+                        _ => return Err(error("expected three numbers").into()),
+                    }
+                }
+            }
+        }
+
+        if size_1d == 0 && size_3d == 0 {
+            return Err("missing LUT_1D_SIZE or LUT_3D_SIZE".into());
+        }
+        let expected = size_1d + size_3d.pow(3);
+        if entries.len() != expected {
+            return Err(format!("expected {expected} entries, found {}", entries.len()).into());
+        }
+        if (domain_max - domain_min).min_element() <= 0.0 {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".into());
+        }
+        let table_3d = entries.split_off(size_1d);
+        Ok(Self {
+            domain_min,
+            domain_max,
+            table_1d: entries,
+            table_3d,
+            size_3d,
+        })
+    }
+
+    pub fn apply(&self, color: DVec3) -> DVec3 {
+        let mut x = ((color - self.domain_min) / (self.domain_max - self.domain_min))
+            .clamp(DVec3::ZERO, DVec3::ONE);
+        if !self.table_1d.is_empty() {
+            let n = self.table_1d.len();
+            for channel in 0..3 {
+                let (i, f) = split(x[channel], n);
+                x[channel] =
+                    self.table_1d[i][channel] * (1.0 - f) + self.table_1d[i + 1][channel] * f;
+            }
+            // The shaper's output indexes the 3D table directly.
+            x = x.clamp(DVec3::ZERO, DVec3::ONE);
+        }
+        if self.table_3d.is_empty() {
+            return x;
+        }
+        let n = self.size_3d;
+        let (r, fr) = split(x.x, n);
+        let (g, fg) = split(x.y, n);
+        let (b, fb) = split(x.z, n);
+        let at = |dr: usize, dg: usize, db: usize| {
+            self.table_3d[(r + dr) + (g + dg) * n + (b + db) * n * n]
+        };
+        let lerp = |a: DVec3, b: DVec3, t: f64| a + (b - a) * t;
+        let c00 = lerp(at(0, 0, 0), at(1, 0, 0), fr);
+        let c10 = lerp(at(0, 1, 0), at(1, 1, 0), fr);
+        let c01 = lerp(at(0, 0, 1), at(1, 0, 1), fr);
+        let c11 = lerp(at(0, 1, 1), at(1, 1, 1), fr);
+        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+    }
+}
+
+// Index of the table entry below `x` in [0, 1] in a table of `size`
+// entries, and the fraction of the way to the next one.
+fn split(x: f64, size: usize) -> (usize, f64) {
+    let scaled = x * (size - 1) as f64;
+    let i = (scaled.floor() as usize).min(size - 2);
+    (i, scaled - i as f64)
+}
@@ -1,12 +1,87 @@
+use crate::albedo_lut::AlbedoLut;
 use crate::hittable::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::texture::Texture;
+use crate::volume::DensityGrid;
 use glam::DVec3;
-use rand::Rng;
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, DVec3)>;
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)>;
+
+    // BSDF times cosine for scattering towards `direction`, used by light
+    // sampling. Materials that return `None` (mirrors, smooth glass) are
+    // only reachable through `scatter`.
+    fn eval(&self, _ray_in: &Ray, _rec: &HitRecord, _direction: DVec3) -> Option<DVec3> {
+        None
+    }
+
+    // Solid-angle density with which `scatter` picks `direction`.
+    fn pdf(&self, _ray_in: &Ray, _rec: &HitRecord, _direction: DVec3) -> f64 {
+        0.0
+    }
+
+    // Surface colour for the albedo pass.
+    fn albedo(&self, _rec: &HitRecord) -> DVec3 {
+        DVec3::ONE
+    }
+
+    // Whether reflected light is close enough to view-independent for the
+    // irradiance cache to reuse it.
+    fn is_diffuse(&self) -> bool {
+        false
+    }
+
+    // Radiance emitted from the surface at (`u`, `v`, `point`), the same
+    // for both faces.
+    fn emitted(&self, _u: f64, _v: f64, _point: DVec3) -> DVec3 {
+        DVec3::ZERO
+    }
+
+    // Medium filling the object, entered by rays that `scatter` sends
+    // through the surface.
+    fn medium(&self) -> Option<Medium> {
+        None
+    }
+
+    // Whether light crosses the surface unchanged, so that shadow rays
+    // carry on through it, attenuated by its medium.
+    fn is_index_matched(&self) -> bool {
+        false
+    }
+
+    // Whether the surface is cut away at the hit, so that rays pass
+    // through as if it weren't there; see `hittable::hit_surface`.
+    fn is_cut_out(&self, _rec: &HitRecord) -> bool {
+        false
+    }
+
+    // Whether shadow rays may pass through the surface anywhere, cut away
+    // or index-matched. Any-hit queries only build hit records for
+    // surfaces that may, and count the rest as blocking outright.
+    fn is_see_through(&self) -> bool {
+        self.is_index_matched()
+    }
+
+    // Whether camera rays see only the shadows falling on the surface; see
+    // `ShadowCatcher`.
+    fn is_shadow_catcher(&self) -> bool {
+        false
+    }
+
+    // ID for the material ID pass and matte, the same in every render of
+    // the scene; 0 when it has none, and the renderer makes one up that
+    // only lasts the render. See `Identified`.
+    fn id(&self) -> u32 {
+        0
+    }
 }
 
 pub struct Lambertian {
@@ -20,20 +95,43 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, DVec3)> {
-        let mut scatter_direction = rec.normal + random_unit_vector();
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let mut scatter_direction = rec.normal + random_unit_vector(sampler);
         if scatter_direction.abs_diff_eq(DVec3::ZERO, 1e-8) {
             scatter_direction = rec.normal;
         }
         let scattered = Ray::new(rec.point, scatter_direction);
-        let attenuation = self.albedo.value(rec.u, rec.v, rec.point);
+        let attenuation = rec.base_color(&*self.albedo);
         Some((scattered, attenuation))
     }
+
+    fn eval(&self, _ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let cosine = rec.normal.dot(direction.normalize()).max(0.0);
+        Some(rec.base_color(&*self.albedo) * cosine / PI)
+    }
+
+    fn pdf(&self, _ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        rec.normal.dot(direction.normalize()).max(0.0) / PI
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        rec.base_color(&*self.albedo)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        true
+    }
 }
 
 pub struct Metal {
     pub albedo: Arc<dyn Texture>,
     pub fuzz: f64,
+    pub thin_film: Option<ThinFilm>,
 }
 
 impl Metal {
@@ -41,15 +139,30 @@ impl Metal {
         Self {
             albedo,
             fuzz: fuzz.clamp(0.0, 1.0),
+            thin_film: None,
         }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, DVec3)> {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
         let reflected = reflect(ray_in.direction.normalize(), rec.normal);
-        let scattered = Ray::new(rec.point, reflected + self.fuzz * random_in_unit_sphere());
-        let attenuation = self.albedo.value(rec.u, rec.v, rec.point);
+        let scattered = Ray::new(
+            rec.point,
+            reflected + self.fuzz * random_in_unit_sphere(sampler),
+        );
+        let mut attenuation = rec.base_color(&*self.albedo);
+        if let Some(film) = self.thin_film {
+            let cos_i = -ray_in.direction.normalize().dot(rec.normal);
+            attenuation = per_channel(ray_in.wavelength, |channel, wavelength| {
+                film.over_metal(cos_i, attenuation[channel], wavelength)
+            });
+        }
 
         if scattered.direction.dot(rec.normal) > 0.0 {
             Some((scattered, attenuation))
@@ -57,77 +170,1991 @@ impl Material for Metal {
             None
         }
     }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        rec.base_color(&*self.albedo)
+    }
+}
+
+// How a dielectric's index of refraction varies with wavelength. Cauchy
+// and Sellmeier coefficients take wavelengths in micrometres, as glass
+// catalogues list them.
+#[derive(Clone, Copy, Debug)]
+pub enum Dispersion {
+    // Cauchy fit through the d-line index and this Abbe number.
+    Abbe(f64),
+    // n = a + b / λ² + c / λ⁴.
+    Cauchy { a: f64, b: f64, c: f64 },
+    // n² = 1 + Σ b λ² / (λ² - c).
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
 }
 
+// Glass and other clear materials. With a `roughness` above 0 the surface
+// is frosted or ground: light reflects and refracts off GGX microfacets
+// rather than the surface itself, blurring what is seen through it.
+// Light inside is absorbed at `absorption` per world unit and RGB channel,
+// tinting thick glass and deep water more than thin; the object must be
+// closed.
 pub struct Dielectric {
     pub index_of_refraction: f64,
+    pub dispersion: Option<Dispersion>,
+    pub thin_film: Option<ThinFilm>,
+    pub roughness: f64,
+    pub absorption: DVec3,
 }
 
+// Wavelengths in nanometres of the Fraunhofer d, F and C lines, which
+// define the Abbe number.
+pub const LAMBDA_D: f64 = 587.6;
+const LAMBDA_F: f64 = 486.1;
+const LAMBDA_C: f64 = 656.3;
+
+// Representative wavelengths of the red, green and blue channels, for
+// wavelength-dependent effects in RGB renders.
+const LAMBDA_RGB: [f64; 3] = [650.0, 550.0, 450.0];
+
 impl Dielectric {
     pub fn new(index_of_refraction: f64) -> Self {
         Self {
             index_of_refraction,
+            dispersion: None,
+            thin_film: None,
+            roughness: 0.0,
+            absorption: DVec3::ZERO,
+        }
+    }
+
+    pub fn with_dispersion(index_of_refraction: f64, abbe_number: f64) -> Self {
+        Self {
+            dispersion: Some(Dispersion::Abbe(abbe_number)),
+            ..Self::new(index_of_refraction)
+        }
+    }
+
+    // Index of refraction at `wavelength` nanometres.
+    pub fn index_at(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match self.dispersion {
+            // Fits a Cauchy equation n(λ) = A + B/λ² through the d-line
+            // index and the Abbe number.
+            Some(Dispersion::Abbe(abbe_number)) if abbe_number > 0.0 => {
+                let um2 = |nm: f64| (nm / 1000.0).powi(2);
+                let n_d = self.index_of_refraction;
+                let b = (n_d - 1.0) / (abbe_number * (1.0 / um2(LAMBDA_F) - 1.0 / um2(LAMBDA_C)));
+                let a = n_d - b / um2(LAMBDA_D);
+                a + b / l2
+            }
+            Some(Dispersion::Cauchy { a, b, c }) => a + b / l2 + c / (l2 * l2),
+            Some(Dispersion::Sellmeier { b, c }) => {
+                let sum: f64 = b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1.0 + sum).max(1.0).sqrt()
+            }
+            _ => self.index_of_refraction,
+        }
+    }
+
+    // GGX width of the microfacets; `None` for a smooth surface.
+    fn alpha(&self) -> Option<f64> {
+        (self.roughness > 0.0).then(|| (self.roughness * self.roughness).clamp(1e-3, 1.0))
+    }
+
+    // Dispersion that actually changes the index with wavelength.
+    fn dispersion(&self) -> Option<Dispersion> {
+        self.dispersion
+            .filter(|d| !matches!(d, Dispersion::Abbe(abbe_number) if *abbe_number <= 0.0))
+    }
+
+    // Reflectance in each channel of a surface, or microfacet, of index
+    // `index` met at `cos_theta`, and the chance `scatter` reflects with.
+    // Light that can't refract is reflected entirely.
+    fn reflectance(
+        &self,
+        cos_theta: f64,
+        index: f64,
+        front_face: bool,
+        wavelength: Option<f64>,
+    ) -> (DVec3, f64) {
+        let (outer, inner) = if front_face {
+            (1.0, index)
+        } else {
+            (index, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        if outer / inner * sin_theta > 1.0 {
+            return (DVec3::ONE, 1.0);
+        }
+        match self.thin_film {
+            None => {
+                let chance = reflectance(cos_theta, outer / inner);
+                (DVec3::splat(chance), chance)
+            }
+            // A film's reflectance may differ between channels; reflect with
+            // their average chance.
+            Some(film) => {
+                let reflectance = per_channel(wavelength, |_, wavelength| {
+                    film.over_dielectric(cos_theta, outer, inner, wavelength)
+                });
+                (reflectance, reflectance.element_sum() / 3.0)
+            }
+        }
+    }
+
+    // BSDF times cosine of a rough surface of index `index`, for light
+    // leaving along `wo` that arrives against `wi`, and the density with
+    // which `scatter` picks `wo`; Walter et al.'s microfacet model, as
+    // transported from the camera. Reflections and refractions each have
+    // their own microfacet normal, which must face both directions as
+    // `scatter` requires.
+    fn rough(
+        &self,
+        rec: &HitRecord,
+        (wi, wo): (DVec3, DVec3),
+        alpha: f64,
+        index: f64,
+        wavelength: Option<f64>,
+    ) -> (DVec3, f64) {
+        let n = rec.normal;
+        let (cos_i, cos_o) = (n.dot(wi), n.dot(wo));
+        // Far side's index over the near side's.
+        let eta = if rec.front_face { index } else { 1.0 / index };
+        let refracted = cos_o < 0.0;
+        if cos_i <= 0.0 || cos_o == 0.0 || (refracted && (eta - 1.0).abs() < 1e-9) {
+            return (DVec3::ZERO, 0.0);
+        }
+        let m = if refracted { -(wi + eta * wo) } else { wi + wo }.normalize_or_zero();
+        let m = if m.dot(n) < 0.0 { -m } else { m };
+        let (cos_im, cos_om) = (wi.dot(m), wo.dot(m));
+        if cos_im <= 0.0 || cos_o * cos_om <= 0.0 {
+            return (DVec3::ZERO, 0.0);
+        }
+        let (reflectance, chance) = self.reflectance(cos_im, index, rec.front_face, wavelength);
+        let d = ggx_d(m.dot(n), alpha);
+        let g = smith_g1(cos_i, alpha) * smith_g1(cos_o.abs(), alpha);
+        if refracted {
+            // From the density of microfacet normals to that of the
+            // directions they refract into.
+            let jacobian = eta * eta * cos_om.abs() / (cos_im + eta * cos_om).powi(2);
+            let f = (DVec3::ONE - reflectance) * d * g * cos_im * jacobian / cos_i;
+            (f, (1.0 - chance) * d * m.dot(n) * jacobian)
+        } else {
+            let f = reflectance * d * g / (4.0 * cos_i);
+            (f, chance * d * m.dot(n) / (4.0 * cos_om))
+        }
+    }
+
+    // `rough` at the index `scatter` would refract `ray_in` at: its own
+    // wavelength's, or with dispersion on an RGB path that has yet to pick
+    // one, each channel's own, which is picked a third of the time.
+    fn rough_at(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        direction: DVec3,
+        alpha: f64,
+    ) -> (DVec3, f64) {
+        let directions = (-ray_in.direction.normalize(), direction.normalize());
+        match (self.dispersion(), ray_in.wavelength) {
+            (None, wavelength) => {
+                self.rough(rec, directions, alpha, self.index_of_refraction, wavelength)
+            }
+            (Some(_), Some(wavelength)) => {
+                let index = self.index_at(wavelength);
+                self.rough(rec, directions, alpha, index, Some(wavelength))
+            }
+            (Some(_), None) => {
+                let (mut f, mut pdf) = (DVec3::ZERO, 0.0);
+                for (channel, wavelength) in LAMBDA_RGB.into_iter().enumerate() {
+                    let index = self.index_at(wavelength);
+                    let (value, density) =
+                        self.rough(rec, directions, alpha, index, Some(wavelength));
+                    f[channel] = value[channel];
+                    pdf += density / 3.0;
+                }
+                (f, pdf)
+            }
         }
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, DVec3)> {
-        let attenuation = DVec3::ONE;
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        // Spectral rays refract at their own wavelength. An RGB path meeting
+        // dispersion follows a single randomly chosen channel, at a
+        // representative wavelength for it, which the scattered ray carries
+        // on so that later interfaces bend it the same way; the 3x weight
+        // keeps the estimate unbiased across channels.
+        let (index_of_refraction, attenuation, wavelength) =
+            match (self.dispersion(), ray_in.wavelength) {
+                (None, wavelength) => (self.index_of_refraction, DVec3::ONE, wavelength),
+                (Some(_), Some(wavelength)) => {
+                    (self.index_at(wavelength), DVec3::ONE, Some(wavelength))
+                }
+                (Some(_), None) => {
+                    let channel = ((sampler.next_1d() * 3.0) as usize).min(2);
+                    let mut attenuation = DVec3::ZERO;
+                    attenuation[channel] = 3.0;
+                    let wavelength = LAMBDA_RGB[channel];
+                    (self.index_at(wavelength), attenuation, Some(wavelength))
+                }
+            };
         let refraction_ratio = if rec.front_face {
-            1.0 / self.index_of_refraction
+            1.0 / index_of_refraction
         } else {
-            self.index_of_refraction
+            index_of_refraction
         };
 
         let unit_direction = ray_in.direction.normalize();
-        let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let alpha = self.alpha();
+        let normal = match alpha {
+            Some(alpha) => sample_ggx(rec.normal, alpha, sampler.next_2d()),
+            None => rec.normal,
+        };
+        let cos_theta = (-unit_direction).dot(normal).min(1.0);
+        if alpha.is_some() && cos_theta <= 0.0 {
+            return None;
+        }
+
+        // Each channel is weighted by its own reflectance over the chance.
+        let (reflectance, chance) =
+            self.reflectance(cos_theta, index_of_refraction, rec.front_face, wavelength);
+        let (direction, attenuation) = if chance > sampler.next_1d() {
+            let reflected = reflect(unit_direction, normal);
+            (reflected, attenuation * reflectance / chance)
+        } else {
+            let refracted = refract(unit_direction, normal, refraction_ratio);
+            let transmittance = DVec3::ONE - reflectance;
+            (refracted, attenuation * transmittance / (1.0 - chance))
+        };
+
+        let attenuation = match alpha {
+            None => attenuation,
+            Some(alpha) => {
+                // Reflections off a microfacet that head into the surface,
+                // and refractions that head out of it, are lost. With the
+                // microfacet drawn from D(m)(m.n) and the choice made by
+                // its reflectance, the rest keep Walter et al.'s weight
+                // |i.m| G / (|i.n| |m.n|).
+                let n = rec.normal;
+                let direction = direction.normalize();
+                if direction.dot(n) * direction.dot(normal) <= 0.0 {
+                    return None;
+                }
+                let cos_i = (-unit_direction).dot(n);
+                if cos_i <= 0.0 {
+                    return None;
+                }
+                let g = smith_g1(cos_i, alpha) * smith_g1(direction.dot(n).abs(), alpha);
+                attenuation * cos_theta * g / (cos_i * normal.dot(n))
+            }
+        };
+        let scattered = Ray::new(rec.point, direction).with_wavelength(wavelength);
+        Some((scattered, attenuation))
+    }
+
+    // Smooth glass is only reachable through `scatter`; rough glass can be
+    // lit through light samples on either side.
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let alpha = self.alpha()?;
+        Some(self.rough_at(ray_in, rec, direction, alpha).0)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        self.alpha()
+            .map_or(0.0, |alpha| self.rough_at(ray_in, rec, direction, alpha).1)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        (self.absorption != DVec3::ZERO).then(|| Medium::new(self.absorption, DVec3::ZERO))
+    }
+}
+
+// Transparent coating, such as a soap film or an oil slick, thin enough
+// that light reflected off its two sides interferes, tinting the surface
+// with colours that shift with the viewing angle. Reflectance follows the
+// Airy formula for a single layer, averaged over the two polarisations
+// rather than tracking them along the path.
+#[derive(Clone, Copy, Debug)]
+pub struct ThinFilm {
+    // In nanometres.
+    pub thickness: f64,
+    pub index_of_refraction: f64,
+}
+
+impl ThinFilm {
+    // Reflectance of the film over a dielectric of index `inner`, for light
+    // of `wavelength` nanometres arriving at `cos_i` through a medium of
+    // index `outer`. The coating is lossless, so the rest is transmitted.
+    // Light that cannot enter the film or leave it into the substrate is
+    // taken to be reflected entirely, ignoring the little that tunnels
+    // through a film this thin.
+    pub fn over_dielectric(&self, cos_i: f64, outer: f64, inner: f64, wavelength: f64) -> f64 {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let film = self.index_of_refraction;
+        let (Some(cos_f), Some(cos_t)) = (
+            snell_cosine(cos_i, outer, film),
+            snell_cosine(cos_i, outer, inner),
+        ) else {
+            return 1.0;
+        };
+        let above = amplitudes(outer, cos_i, film, cos_f);
+        let below = amplitudes(film, cos_f, inner, cos_t);
+        // Difference in phase between light reflected off the top and the
+        // bottom of the film.
+        let phase = 4.0 * PI * film * self.thickness.max(0.0) * cos_f / wavelength;
+        let airy = |r01: f64, r12: f64| {
+            let cross = 2.0 * r01 * r12 * phase.cos();
+            let denominator = 1.0 + r01 * r01 * r12 * r12 + cross;
+            if denominator <= 0.0 {
+                return 1.0;
+            }
+            ((r01 * r01 + r12 * r12 + cross) / denominator).clamp(0.0, 1.0)
+        };
+        0.5 * (airy(above[0], below[0]) + airy(above[1], below[1]))
+    }
+
+    // Reflectance of the film over a metal that reflects `reflectance` of
+    // the light head-on when bare, with air above. The metal is modelled as
+    // the dielectric with that head-on reflectance, so a film of no
+    // thickness leaves head-on reflections unchanged.
+    pub fn over_metal(&self, cos_i: f64, reflectance: f64, wavelength: f64) -> f64 {
+        let amplitude = reflectance.clamp(0.0, 1.0).sqrt();
+        if amplitude >= 1.0 {
+            return 1.0;
+        }
+        let index = (1.0 + amplitude) / (1.0 - amplitude);
+        self.over_dielectric(cos_i, 1.0, index, wavelength)
+    }
+}
+
+// Cosine of the angle of light refracted from index `n_i` into index `n_t`,
+// or `None` if it is totally reflected.
+fn snell_cosine(cos_i: f64, n_i: f64, n_t: f64) -> Option<f64> {
+    let sin_t2 = (n_i / n_t).powi(2) * (1.0 - cos_i * cos_i);
+    (sin_t2 < 1.0).then(|| (1.0 - sin_t2).sqrt())
+}
+
+// Fresnel amplitude reflection coefficients for s and p polarised light.
+fn amplitudes(n_i: f64, cos_i: f64, n_t: f64, cos_t: f64) -> [f64; 2] {
+    [
+        (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t),
+        (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t),
+    ]
+}
+
+// Evaluates `f(channel, wavelength)` for each colour channel: at the ray's
+// own wavelength when it has one, otherwise at the channel's
+// representative wavelength.
+fn per_channel(wavelength: Option<f64>, f: impl Fn(usize, f64) -> f64) -> DVec3 {
+    let at = |channel: usize| f(channel, wavelength.unwrap_or(LAMBDA_RGB[channel]));
+    DVec3::new(at(0), at(1), at(2))
+}
+
+// Metallic-roughness material in the spirit of the Disney/glTF model: a
+// diffuse base, a GGX specular lobe and GGX rough transmission, blended by
+// `metallic` and `transmission`. `specular` scales the dielectric Fresnel
+// reflectance, 0.5 being physically correct for `ior`. With
+// `energy_compensation` the specular lobe also reflects the light that
+// bounces between microfacets, which rough surfaces otherwise lose.
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub ior: f64,
+    pub specular: f64,
+    pub transmission: f64,
+    pub energy_compensation: Option<Arc<AlbedoLut>>,
+}
+
+// Probabilities of sampling each lobe at one shading point.
+struct LobeChoice {
+    diffuse: f64,
+    specular: f64,
+    transmission: f64,
+}
+
+impl Principled {
+    pub fn new(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: 0.0,
+            roughness: 0.5,
+            ior: 1.5,
+            specular: 0.5,
+            transmission: 0.0,
+            energy_compensation: None,
+        }
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).clamp(1e-3, 1.0)
+    }
+
+    // Ratio of indices across the surface, incident side over far side.
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            1.0 / self.ior
+        } else {
+            self.ior
+        }
+    }
+
+    fn dielectric_fresnel(&self, cosine: f64, eta: f64) -> f64 {
+        (fresnel_dielectric(cosine, eta) * 2.0 * self.specular).min(1.0)
+    }
+
+    fn fresnel(&self, cosine: f64, eta: f64, base: DVec3) -> DVec3 {
+        let dielectric = DVec3::splat(self.dielectric_fresnel(cosine, eta));
+        let metal = base + (DVec3::ONE - base) * (1.0 - cosine).clamp(0.0, 1.0).powi(5);
+        dielectric.lerp(metal, self.metallic)
+    }
+
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn lobes(&self, cos_i: f64, eta: f64, base: DVec3) -> LobeChoice {
+        let fresnel = luminance(self.fresnel(cos_i, eta, base));
+        let not_reflected = 1.0 - self.dielectric_fresnel(cos_i, eta);
+        let diffuse = self.diffuse_weight() * luminance(base) * not_reflected;
+        let transmission = (1.0 - self.metallic) * self.transmission * not_reflected;
+        let specular = fresnel.max(0.05);
+        let total = diffuse + specular + transmission;
+        LobeChoice {
+            diffuse: diffuse / total,
+            specular: specular / total,
+            transmission: transmission / total,
+        }
+    }
+
+    // BSDF times cosine for a reflected direction; zero below the surface.
+    fn eval_reflection(&self, rec: &HitRecord, wi: DVec3, wo: DVec3, base: DVec3) -> DVec3 {
+        let n = rec.normal;
+        let (cos_i, cos_o) = (n.dot(wi), n.dot(wo));
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return DVec3::ZERO;
+        }
+        let eta = self.eta(rec);
+        let alpha = self.alpha();
+        let h = (wi + wo).normalize();
+        let d = ggx_d(n.dot(h), alpha);
+        let g = smith_g1(cos_i, alpha) * smith_g1(cos_o, alpha);
+        let mut specular = self.fresnel(wi.dot(h), eta, base) * d * g / (4.0 * cos_i);
+        if let Some(lut) = &self.energy_compensation {
+            let f0 = self.fresnel(1.0, eta, base);
+            specular += lut.multiple_scattering(cos_i, cos_o, alpha, f0) * cos_o;
+        }
+        let diffuse =
+            self.diffuse_weight() * (1.0 - self.dielectric_fresnel(cos_i, eta)) * base / PI * cos_o;
+        specular + diffuse
+    }
+
+    fn reflection_pdf(&self, rec: &HitRecord, wi: DVec3, wo: DVec3, lobes: &LobeChoice) -> f64 {
+        let n = rec.normal;
+        let cos_o = n.dot(wo);
+        if cos_o <= 0.0 {
+            return 0.0;
+        }
+        let h = (wi + wo).normalize();
+        let specular = ggx_d(n.dot(h), self.alpha()) * n.dot(h) / (4.0 * wo.dot(h).abs());
+        lobes.diffuse * cos_o / PI + lobes.specular * specular
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let n = rec.normal;
+        let wi = -ray_in.direction.normalize();
+        let cos_i = n.dot(wi);
+        if cos_i <= 0.0 {
+            return None;
+        }
+        let base = rec.base_color(&*self.base_color);
+        let eta = self.eta(rec);
+        let lobes = self.lobes(cos_i, eta, base);
 
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction =
-            if cannot_refract || reflectance(cos_theta, refraction_ratio) > rand::random() {
-                reflect(unit_direction, rec.normal)
+        let choice = sampler.next_1d();
+        let sample = sampler.next_2d();
+        if choice < lobes.diffuse + lobes.specular || lobes.transmission <= 0.0 {
+            let wo = if choice < lobes.diffuse {
+                let direction = n + random_unit_vector(sampler);
+                if direction.abs_diff_eq(DVec3::ZERO, 1e-8) {
+                    n
+                } else {
+                    direction.normalize()
+                }
             } else {
-                refract(unit_direction, rec.normal, refraction_ratio)
+                reflect(-wi, sample_ggx(n, self.alpha(), sample))
             };
+            // Weighting by the combined pdf of both reflection lobes keeps
+            // the estimate smooth where they overlap.
+            let pdf = self.reflection_pdf(rec, wi, wo, &lobes);
+            if pdf <= 0.0 {
+                return None;
+            }
+            let attenuation = self.eval_reflection(rec, wi, wo, base) / pdf;
+            return Some((Ray::new(rec.point, wo), attenuation));
+        }
 
-        let scattered = Ray::new(rec.point, direction);
-        Some((scattered, attenuation))
+        // Rough refraction through a GGX microfacet. With the microfacet
+        // normal drawn from D(m)(m.n) the BTDF weight reduces to Walter et
+        // al.'s |i.m| G / (|i.n| |m.n|), times the refracted share 1 - F.
+        let alpha = self.alpha();
+        let m = sample_ggx(n, alpha, sample);
+        let cos_im = wi.dot(m);
+        if cos_im <= 0.0 || eta * eta * (1.0 - cos_im * cos_im) >= 1.0 {
+            return None;
+        }
+        let wo = refract(-wi, m, eta).normalize();
+        let g = smith_g1(cos_i, alpha) * smith_g1(n.dot(wo).abs(), alpha);
+        let weight = (1.0 - self.dielectric_fresnel(cos_im, eta)) * cos_im * g / (cos_i * n.dot(m));
+        let attenuation = base * weight / lobes.transmission;
+        Some((Ray::new(rec.point, wo), attenuation))
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let base = rec.base_color(&*self.base_color);
+        let wi = -ray_in.direction.normalize();
+        Some(self.eval_reflection(rec, wi, direction.normalize(), base))
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        let base = rec.base_color(&*self.base_color);
+        let wi = -ray_in.direction.normalize();
+        let lobes = self.lobes(rec.normal.dot(wi), self.eta(rec), base);
+        self.reflection_pdf(rec, wi, direction.normalize(), &lobes)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        rec.base_color(&*self.base_color)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        self.metallic == 0.0 && self.transmission == 0.0 && self.roughness >= 0.5
     }
 }
 
-fn reflect(v: DVec3, n: DVec3) -> DVec3 {
-    v - 2.0 * v.dot(n) * n
+// Wraps another material and bends the shading normal with a tangent-space
+// normal map (RGB in [0, 1] encoding XYZ in [-1, 1], +Z along the surface
+// normal). `strength` scales the tangent-plane part of the perturbation.
+pub struct NormalMapped {
+    pub material: Arc<dyn Material>,
+    pub normal_map: Arc<dyn Texture>,
+    pub strength: f64,
 }
 
-fn refract(uv: DVec3, n: DVec3, etai_over_etat: f64) -> DVec3 {
-    let cos_theta = (-uv).dot(n).min(1.0);
-    let r_out_perp = etai_over_etat * (uv + cos_theta * n);
-    let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
-    r_out_perp + r_out_parallel
+impl NormalMapped {
+    pub fn new(material: Arc<dyn Material>, normal_map: Arc<dyn Texture>) -> Self {
+        Self {
+            material,
+            normal_map,
+            strength: 1.0,
+        }
+    }
+
+    fn shading_record<'a>(&self, ray_in: &Ray, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let n = rec.normal;
+        let (tangent, bitangent) = tangent_frame(rec);
+
+        let encoded = 2.0 * self.normal_map.value(rec.u, rec.v, rec.point) - DVec3::ONE;
+        let normal = (self.strength * (encoded.x * tangent + encoded.y * bitangent)
+            + encoded.z * n)
+            .normalize_or_zero();
+        // A normal tilted away from the viewer would make the surface
+        // unreachable; fall back to the geometric one.
+        let normal = if normal.dot(ray_in.direction) < 0.0 {
+            normal
+        } else {
+            n
+        };
+        HitRecord {
+            normal,
+            tangent,
+            ..*rec
+        }
+    }
 }
 
-fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-    let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-    let r0 = r0 * r0;
-    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+impl Material for NormalMapped {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        self.material
+            .scatter(ray_in, &self.shading_record(ray_in, rec), sampler)
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        self.material
+            .eval(ray_in, &self.shading_record(ray_in, rec), direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        self.material
+            .pdf(ray_in, &self.shading_record(ray_in, rec), direction)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        self.material.albedo(rec)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        self.material.is_diffuse()
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: DVec3) -> DVec3 {
+        self.material.emitted(u, v, point)
+    }
+
+    fn is_cut_out(&self, rec: &HitRecord) -> bool {
+        self.material.is_cut_out(rec)
+    }
+
+    fn is_see_through(&self) -> bool {
+        self.material.is_see_through()
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.material.is_shadow_catcher()
+    }
 }
 
-fn random_in_unit_sphere() -> DVec3 {
-    let mut rng = rand::thread_rng();
-    loop {
-        let p = DVec3::new(
-            rng.gen_range(-1.0..1.0),
-            rng.gen_range(-1.0..1.0),
-            rng.gen_range(-1.0..1.0),
-        );
-        if p.length_squared() < 1.0 {
-            return p;
+// Cuts `material` away wherever the grey level of `alpha` is below
+// `threshold`, for foliage cards, fences and other shapes drawn into a
+// texture's transparency rather than modelled.
+pub struct Cutout {
+    pub material: Arc<dyn Material>,
+    pub alpha: Arc<dyn Texture>,
+    pub threshold: f64,
+}
+
+impl Cutout {
+    pub fn new(material: Arc<dyn Material>, alpha: Arc<dyn Texture>) -> Self {
+        Self {
+            material,
+            alpha,
+            threshold: 0.5,
+        }
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        self.material.scatter(ray_in, rec, sampler)
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        self.material.eval(ray_in, rec, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        self.material.pdf(ray_in, rec, direction)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        self.material.albedo(rec)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        self.material.is_diffuse()
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: DVec3) -> DVec3 {
+        self.material.emitted(u, v, point)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+
+    fn is_index_matched(&self) -> bool {
+        self.material.is_index_matched()
+    }
+
+    fn is_cut_out(&self, rec: &HitRecord) -> bool {
+        luminance(self.alpha.value(rec.u, rec.v, rec.point)) < self.threshold
+            || self.material.is_cut_out(rec)
+    }
+
+    fn is_see_through(&self) -> bool {
+        true
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.material.is_shadow_catcher()
+    }
+}
+// Gives `material` a stable `id`, such as one from its name in the scene,
+// and otherwise behaves exactly as it does.
+pub struct Identified {
+    pub material: Arc<dyn Material>,
+    pub id: u32,
+}
+
+impl Identified {
+    pub fn new(material: Arc<dyn Material>, id: u32) -> Self {
+        Self { material, id }
+    }
+}
+
+impl Material for Identified {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        self.material.scatter(ray_in, rec, sampler)
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        self.material.eval(ray_in, rec, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        self.material.pdf(ray_in, rec, direction)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        self.material.albedo(rec)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        self.material.is_diffuse()
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: DVec3) -> DVec3 {
+        self.material.emitted(u, v, point)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+
+    fn is_index_matched(&self) -> bool {
+        self.material.is_index_matched()
+    }
+
+    fn is_cut_out(&self, rec: &HitRecord) -> bool {
+        self.material.is_cut_out(rec)
+    }
+
+    fn is_see_through(&self) -> bool {
+        self.material.is_see_through()
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.material.is_shadow_catcher()
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+// Blends `a` into `b` by the grey level of `factor`, 0 giving all `a` and 1
+// all `b`: each bounce follows one of the two, picked by the blend, so
+// surfaces like worn paint over metal can mix lobes of any kind. The mix
+// has no medium of its own.
+pub struct Mix {
+    pub a: Arc<dyn Material>,
+    pub b: Arc<dyn Material>,
+    pub factor: Arc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, factor: Arc<dyn Texture>) -> Self {
+        Self { a, b, factor }
+    }
+
+    // Share of `b` at (`u`, `v`, `point`), in [0, 1].
+    fn weight(&self, u: f64, v: f64, point: DVec3) -> f64 {
+        luminance(self.factor.value(u, v, point)).clamp(0.0, 1.0)
+    }
+}
+
+impl Material for Mix {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let t = self.weight(rec.u, rec.v, rec.point);
+        let chosen = if sampler.next_1d() < t {
+            &self.b
+        } else {
+            &self.a
+        };
+        let (scattered, attenuation) = chosen.scatter(ray_in, rec, sampler)?;
+        // Reflections that both halves can evaluate are weighted by the
+        // blend as a whole, as if either half could have sampled them;
+        // anything else keeps the weight the chosen half gave it.
+        let direction = scattered.direction;
+        if rec.normal.dot(direction) > 0.0 {
+            if let Some(value) = self.eval(ray_in, rec, direction) {
+                let pdf = self.pdf(ray_in, rec, direction);
+                if pdf > 0.0 {
+                    return Some((scattered, value / pdf));
+                }
+            }
         }
+        Some((scattered, attenuation))
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let a = self.a.eval(ray_in, rec, direction)?;
+        let b = self.b.eval(ray_in, rec, direction)?;
+        Some(a.lerp(b, self.weight(rec.u, rec.v, rec.point)))
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        let t = self.weight(rec.u, rec.v, rec.point);
+        let a = self.a.pdf(ray_in, rec, direction);
+        let b = self.b.pdf(ray_in, rec, direction);
+        a + (b - a) * t
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        let t = self.weight(rec.u, rec.v, rec.point);
+        self.a.albedo(rec).lerp(self.b.albedo(rec), t)
     }
+
+    fn is_diffuse(&self) -> bool {
+        self.a.is_diffuse() && self.b.is_diffuse()
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: DVec3) -> DVec3 {
+        let t = self.weight(u, v, point);
+        self.a
+            .emitted(u, v, point)
+            .lerp(self.b.emitted(u, v, point), t)
+    }
+
+    // Cut away where the half that dominates is.
+    fn is_cut_out(&self, rec: &HitRecord) -> bool {
+        if self.weight(rec.u, rec.v, rec.point) < 0.5 {
+            self.a.is_cut_out(rec)
+        } else {
+            self.b.is_cut_out(rec)
+        }
+    }
+
+    fn is_see_through(&self) -> bool {
+        self.a.is_see_through() || self.b.is_see_through()
+    }
+}
+
+// A clear coat over `base`, for car paint and lacquered wood: a smooth GGX
+// dielectric interface of index `coat_ior` reflects part of the light, and
+// what it lets through, both in and back out, reaches the base. Light
+// bouncing between the coat and the base is left out, so the coat darkens
+// the base a little more than a real one would. Back faces see only the
+// base.
+pub struct Layered {
+    pub base: Arc<dyn Material>,
+    pub coat_ior: f64,
+    pub coat_roughness: f64,
+}
+
+impl Layered {
+    pub fn new(base: Arc<dyn Material>) -> Self {
+        Self {
+            base,
+            coat_ior: 1.5,
+            coat_roughness: 0.03,
+        }
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.coat_roughness * self.coat_roughness).clamp(1e-3, 1.0)
+    }
+
+    fn fresnel(&self, cosine: f64) -> f64 {
+        fresnel_dielectric(cosine, 1.0 / self.coat_ior)
+    }
+
+    // Probability of sampling the coat rather than the base.
+    fn coat_probability(&self, cos_i: f64) -> f64 {
+        self.fresnel(cos_i).clamp(0.1, 0.9)
+    }
+
+    // Share of the light the coat lets through on the way in and out.
+    fn transmitted(&self, cos_i: f64, cos_o: f64) -> f64 {
+        (1.0 - self.fresnel(cos_i)) * (1.0 - self.fresnel(cos_o.abs()))
+    }
+
+    // The coat's BSDF times cosine towards `wo` and its density of
+    // sampling `wo`; both zero below the surface.
+    fn coat(&self, n: DVec3, wi: DVec3, wo: DVec3) -> (f64, f64) {
+        let (cos_i, cos_o) = (n.dot(wi), n.dot(wo));
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let alpha = self.alpha();
+        let h = (wi + wo).normalize();
+        let d = ggx_d(n.dot(h), alpha);
+        let g = smith_g1(cos_i, alpha) * smith_g1(cos_o, alpha);
+        let value = self.fresnel(wi.dot(h)) * d * g / (4.0 * cos_i);
+        let pdf = d * n.dot(h) / (4.0 * wo.dot(h).abs());
+        (value, pdf)
+    }
+}
+
+impl Material for Layered {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let n = rec.normal;
+        let wi = -ray_in.direction.normalize();
+        let cos_i = n.dot(wi);
+        if !rec.front_face || cos_i <= 0.0 {
+            return self.base.scatter(ray_in, rec, sampler);
+        }
+        let p_coat = self.coat_probability(cos_i);
+        let on_coat = sampler.next_1d() < p_coat;
+        let (scattered, attenuation) = if on_coat {
+            let wo = reflect(-wi, sample_ggx(n, self.alpha(), sampler.next_2d()));
+            let (value, pdf) = self.coat(n, wi, wo);
+            if pdf <= 0.0 {
+                return None;
+            }
+            (
+                Ray::new(rec.point, wo),
+                DVec3::splat(value / (p_coat * pdf)),
+            )
+        } else {
+            let (scattered, attenuation) = self.base.scatter(ray_in, rec, sampler)?;
+            let cos_o = n.dot(scattered.direction.normalize());
+            let weight = self.transmitted(cos_i, cos_o) / (1.0 - p_coat);
+            (scattered, attenuation * weight)
+        };
+        // As for `Principled`, reflections that both layers can evaluate
+        // are weighted by their combined pdf.
+        let direction = scattered.direction;
+        if n.dot(direction) > 0.0 {
+            if let Some(value) = self.eval(ray_in, rec, direction) {
+                let pdf = self.pdf(ray_in, rec, direction);
+                if pdf > 0.0 {
+                    return Some((scattered, value / pdf));
+                }
+            }
+        }
+        Some((scattered, attenuation))
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let base = self.base.eval(ray_in, rec, direction)?;
+        let n = rec.normal;
+        let wi = -ray_in.direction.normalize();
+        let wo = direction.normalize();
+        if !rec.front_face || n.dot(wi) <= 0.0 {
+            return Some(base);
+        }
+        let (coat, _) = self.coat(n, wi, wo);
+        Some(DVec3::splat(coat) + self.transmitted(n.dot(wi), n.dot(wo)) * base)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        let base = self.base.pdf(ray_in, rec, direction);
+        let n = rec.normal;
+        let wi = -ray_in.direction.normalize();
+        let cos_i = n.dot(wi);
+        if !rec.front_face || cos_i <= 0.0 {
+            return base;
+        }
+        let p_coat = self.coat_probability(cos_i);
+        let (_, coat) = self.coat(n, wi, direction.normalize());
+        p_coat * coat + (1.0 - p_coat) * base
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        self.base.albedo(rec)
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: DVec3) -> DVec3 {
+        self.base.emitted(u, v, point)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.base.medium()
+    }
+
+    fn is_cut_out(&self, rec: &HitRecord) -> bool {
+        self.base.is_cut_out(rec)
+    }
+
+    fn is_see_through(&self) -> bool {
+        self.base.is_see_through()
+    }
+}
+
+// Stands in for the ground of a photograph that rendered objects are laid
+// over. Camera rays see nothing of it but the shadows cast onto it, as the
+// alpha of a black layer (see `RenderSettings::transparent`); everything
+// else bounces off it as from a Lambertian surface of `albedo`, so the
+// objects still pick up light from the ground beneath them.
+pub struct ShadowCatcher {
+    surface: Lambertian,
+}
+
+impl ShadowCatcher {
+    pub fn new(albedo: Arc<dyn Texture>) -> Self {
+        Self {
+            surface: Lambertian::new(albedo),
+        }
+    }
+}
+
+impl Material for ShadowCatcher {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        self.surface.scatter(ray_in, rec, sampler)
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        self.surface.eval(ray_in, rec, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        self.surface.pdf(ray_in, rec, direction)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        self.surface.albedo(rec)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        true
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        true
+    }
+}
+
+// Translucent material for skin, wax and marble. Light that gets past the
+// Fresnel boundary takes a random walk through a homogeneous medium inside
+// the object until it is absorbed or leaves again, which the renderer
+// traces. The boundary transmits diffusely so the exit point can use light
+// sampling. Coefficients are per world unit; the object must be closed.
+pub struct Subsurface {
+    pub medium: Medium,
+    pub ior: f64,
+}
+
+impl Subsurface {
+    pub fn new(sigma_a: DVec3, sigma_s: DVec3) -> Self {
+        Self {
+            medium: Medium::new(sigma_a, sigma_s),
+            ior: 1.5,
+        }
+    }
+
+    fn fresnel(&self, ray_in: &Ray, rec: &HitRecord) -> f64 {
+        let eta = if rec.front_face {
+            1.0 / self.ior
+        } else {
+            self.ior
+        };
+        fresnel_dielectric(-ray_in.direction.normalize().dot(rec.normal), eta)
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let direction = if sampler.next_1d() < self.fresnel(ray_in, rec) {
+            reflect(ray_in.direction.normalize(), rec.normal)
+        } else {
+            let mut direction = -rec.normal + random_unit_vector(sampler);
+            if direction.abs_diff_eq(DVec3::ZERO, 1e-8) {
+                direction = -rec.normal;
+            }
+            direction
+        };
+        Some((Ray::new(rec.point, direction), DVec3::ONE))
+    }
+
+    // Only the diffuse transmission can be evaluated; the specular
+    // reflection is left to `scatter`.
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        Some(DVec3::splat(self.pdf(ray_in, rec, direction)))
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        let cosine = (-rec.normal).dot(direction.normalize()).max(0.0);
+        (1.0 - self.fresnel(ray_in, rec)) * cosine / PI
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> DVec3 {
+        self.medium.single_scattering_albedo()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(self.medium.clone())
+    }
+}
+
+// Participating medium with an invisible boundary, for clouds, smoke and
+// fog: light crosses the surface unchanged and scatters inside according
+// to `medium`. The object must be closed.
+pub struct Volume {
+    pub medium: Medium,
+}
+
+impl Volume {
+    pub fn new(medium: Medium) -> Self {
+        Self { medium }
+    }
+}
+
+impl Material for Volume {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        Some((Ray::new(rec.point, ray_in.direction), DVec3::ONE))
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> DVec3 {
+        self.medium.single_scattering_albedo()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(self.medium.clone())
+    }
+
+    fn is_index_matched(&self) -> bool {
+        true
+    }
+}
+
+// Absorbing and scattering medium with isotropic phase function.
+// Coefficients are per world unit and per RGB channel, scaled through
+// space by `density` when the medium is heterogeneous.
+#[derive(Clone, Debug)]
+pub struct Medium {
+    pub sigma_a: DVec3,
+    pub sigma_s: DVec3,
+    pub density: Option<Arc<DensityGrid>>,
+}
+
+impl Medium {
+    pub fn new(sigma_a: DVec3, sigma_s: DVec3) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            density: None,
+        }
+    }
+
+    pub fn sigma_t(&self) -> DVec3 {
+        self.sigma_a + self.sigma_s
+    }
+
+    pub fn single_scattering_albedo(&self) -> DVec3 {
+        let sigma_t = self.sigma_t();
+        DVec3::select(
+            sigma_t.cmpgt(DVec3::ZERO),
+            self.sigma_s / sigma_t,
+            DVec3::ZERO,
+        )
+    }
+
+    // Samples how far light travels from `origin` along the unit
+    // `direction` before its next scattering event. Returns the distance
+    // and throughput weight; a distance of at least `max_distance` means
+    // the segment was crossed without scattering.
+    pub fn sample_distance(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        max_distance: f64,
+        sampler: &mut dyn Sampler,
+    ) -> (f64, DVec3) {
+        if let Some(grid) = &self.density {
+            return self.delta_track(grid, origin, direction, max_distance, sampler);
+        }
+        // Homogeneous media choose the extinction of a random channel and
+        // weight by the average density over all three. Purely absorbing
+        // ones, such as tinted glass, never scatter, so light crosses them
+        // with its exact transmittance.
+        let sigma_t = self.sigma_t();
+        if self.sigma_s == DVec3::ZERO {
+            return (max_distance, (-sigma_t * max_distance).exp());
+        }
+        let channel = ((sampler.next_1d() * 3.0) as usize).min(2);
+        let u = sampler.next_1d();
+        let distance = if sigma_t[channel] > 0.0 {
+            -(1.0 - u).ln() / sigma_t[channel]
+        } else {
+            f64::INFINITY
+        };
+        if distance < max_distance {
+            let transmittance = (-sigma_t * distance).exp();
+            let pdf = (sigma_t * transmittance).element_sum() / 3.0;
+            let weight = if pdf > 0.0 {
+                self.sigma_s * transmittance / pdf
+            } else {
+                DVec3::ZERO
+            };
+            (distance, weight)
+        } else {
+            let transmittance = (-sigma_t * max_distance).exp();
+            let probability = transmittance.element_sum() / 3.0;
+            let weight = if probability > 0.0 {
+                transmittance / probability
+            } else {
+                DVec3::ZERO
+            };
+            (max_distance, weight)
+        }
+    }
+
+    // Fraction of light per channel that crosses `max_distance` from
+    // `origin` along the unit `direction` without being absorbed or
+    // scattered away. Exact for homogeneous media and an unbiased ratio
+    // tracking estimate for heterogeneous ones.
+    pub fn transmittance(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        max_distance: f64,
+        sampler: &mut dyn Sampler,
+    ) -> DVec3 {
+        let sigma_t = self.sigma_t();
+        let Some(grid) = &self.density else {
+            return (-sigma_t * max_distance).exp();
+        };
+        let majorant = sigma_t.max_element() * grid.max_density();
+        let mut transmittance = DVec3::ONE;
+        if majorant <= 0.0 {
+            return transmittance;
+        }
+        let mut distance = 0.0;
+        loop {
+            distance -= (1.0 - sampler.next_1d()).ln() / majorant;
+            if distance >= max_distance {
+                return transmittance;
+            }
+            let density = grid.density(origin + distance * direction);
+            transmittance *= DVec3::ONE - sigma_t * density / majorant;
+        }
+    }
+
+    // Delta tracking against the largest extinction anywhere in the grid,
+    // choosing between real and null collisions by their average over the
+    // channels and weighting each channel by its own share, so that
+    // coloured media need no per-channel walks.
+    fn delta_track(
+        &self,
+        grid: &DensityGrid,
+        origin: DVec3,
+        direction: DVec3,
+        max_distance: f64,
+        sampler: &mut dyn Sampler,
+    ) -> (f64, DVec3) {
+        let sigma_t = self.sigma_t();
+        let majorant = sigma_t.max_element() * grid.max_density();
+        let mut weight = DVec3::ONE;
+        if majorant <= 0.0 {
+            return (max_distance, weight);
+        }
+        let mut distance = 0.0;
+        loop {
+            distance -= (1.0 - sampler.next_1d()).ln() / majorant;
+            if distance >= max_distance {
+                return (max_distance, weight);
+            }
+            let density = grid.density(origin + distance * direction);
+            let real = sigma_t * density;
+            let real_chance = real.element_sum() / (3.0 * majorant);
+            if sampler.next_1d() < real_chance {
+                let scattering = self.sigma_s * density;
+                return (distance, weight * scattering / (majorant * real_chance));
+            }
+            let null = DVec3::splat(majorant) - real;
+            weight *= null / (majorant * (1.0 - real_chance));
+        }
+    }
+}
+
+// An emitter that absorbs all incoming light. Its radiance is `color`,
+// times `texture` where there is one, as for a screen or stained glass.
+pub struct DiffuseLight {
+    pub color: DVec3,
+    pub texture: Option<Arc<dyn Texture>>,
+}
+
+impl DiffuseLight {
+    pub fn new(color: DVec3) -> Self {
+        Self {
+            color,
+            texture: None,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _rec: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        None
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> DVec3 {
+        DVec3::ZERO
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: DVec3) -> DVec3 {
+        match &self.texture {
+            Some(texture) => self.color * texture.value(u, v, point),
+            None => self.color,
+        }
+    }
+}
+
+// Metal with separate GGX roughness along the surface tangent and
+// bitangent, for brushed and machined finishes. `rotation` turns the
+// brushing direction around the normal, in radians. Energy compensation
+// works as for `Principled`, with the geometric mean of the two
+// roughnesses.
+pub struct AnisotropicMetal {
+    pub albedo: Arc<dyn Texture>,
+    pub roughness_u: f64,
+    pub roughness_v: f64,
+    pub rotation: f64,
+    pub energy_compensation: Option<Arc<AlbedoLut>>,
+}
+
+impl AnisotropicMetal {
+    pub fn new(albedo: Arc<dyn Texture>, roughness_u: f64, roughness_v: f64) -> Self {
+        Self {
+            albedo,
+            roughness_u: roughness_u.clamp(0.0, 1.0),
+            roughness_v: roughness_v.clamp(0.0, 1.0),
+            rotation: 0.0,
+            energy_compensation: None,
+        }
+    }
+
+    fn alphas(&self) -> (f64, f64) {
+        (
+            (self.roughness_u * self.roughness_u).clamp(1e-3, 1.0),
+            (self.roughness_v * self.roughness_v).clamp(1e-3, 1.0),
+        )
+    }
+
+    // Tangent, bitangent and normal, turned by `rotation`.
+    fn frame(&self, rec: &HitRecord) -> [DVec3; 3] {
+        let (t, b) = tangent_frame(rec);
+        let (sin, cos) = self.rotation.sin_cos();
+        [cos * t + sin * b, cos * b - sin * t, rec.normal]
+    }
+
+    // Incoming and outgoing directions in the local frame, +Z along the
+    // normal.
+    fn local_directions(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> (DVec3, DVec3) {
+        let [t, b, n] = self.frame(rec);
+        let local = |v: DVec3| {
+            let v = v.normalize();
+            DVec3::new(v.dot(t), v.dot(b), v.dot(n))
+        };
+        (local(-ray_in.direction), local(direction))
+    }
+
+    // BSDF times cosine and the sampling density, both in local space.
+    fn eval_local(&self, wi: DVec3, wo: DVec3, albedo: DVec3) -> (DVec3, f64) {
+        if wi.z <= 0.0 || wo.z <= 0.0 {
+            return (DVec3::ZERO, 0.0);
+        }
+        let (ax, ay) = self.alphas();
+        let h = (wi + wo).normalize();
+        let d = ggx_d_aniso(h, ax, ay);
+        let g = smith_g1_aniso(wi, ax, ay) * smith_g1_aniso(wo, ax, ay);
+        let f = schlick(albedo, wi.dot(h));
+        let value = f * d * g / (4.0 * wi.z) + self.multiple_scattering(wi, wo, albedo);
+        (value, d * h.z / (4.0 * wo.dot(h).abs()))
+    }
+
+    // The energy compensation lobe times cosine, if there is one.
+    fn multiple_scattering(&self, wi: DVec3, wo: DVec3, albedo: DVec3) -> DVec3 {
+        let Some(lut) = &self.energy_compensation else {
+            return DVec3::ZERO;
+        };
+        let (ax, ay) = self.alphas();
+        lut.multiple_scattering(wi.z, wo.z, (ax * ay).sqrt(), albedo) * wo.z
+    }
+}
+
+impl Material for AnisotropicMetal {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let [t, b, n] = self.frame(rec);
+        let v = -ray_in.direction.normalize();
+        let wi = DVec3::new(v.dot(t), v.dot(b), v.dot(n));
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let (ax, ay) = self.alphas();
+        let h = sample_ggx_aniso(ax, ay, sampler.next_2d());
+        let wo = 2.0 * wi.dot(h) * h - wi;
+        if wo.z <= 0.0 {
+            return None;
+        }
+        // With h drawn from D(h) h.z the weight reduces to
+        // F G |i.h| / (i.n h.n).
+        let albedo = rec.base_color(&*self.albedo);
+        let g = smith_g1_aniso(wi, ax, ay) * smith_g1_aniso(wo, ax, ay);
+        let mut attenuation = schlick(albedo, wi.dot(h)) * g * wi.dot(h) / (wi.z * h.z);
+        if self.energy_compensation.is_some() {
+            let pdf = ggx_d_aniso(h, ax, ay) * h.z / (4.0 * wi.dot(h));
+            attenuation += self.multiple_scattering(wi, wo, albedo) / pdf;
+        }
+        let direction = wo.x * t + wo.y * b + wo.z * n;
+        Some((Ray::new(rec.point, direction), attenuation))
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let (wi, wo) = self.local_directions(ray_in, rec, direction);
+        let albedo = rec.base_color(&*self.albedo);
+        Some(self.eval_local(wi, wo, albedo).0)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        let (wi, wo) = self.local_directions(ray_in, rec, direction);
+        self.eval_local(wi, wo, DVec3::ONE).1
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        rec.base_color(&*self.albedo)
+    }
+}
+
+// Complex index of refraction of a metal, `eta` + i`k`, for the red, green
+// and blue channels at `LAMBDA_RGB`. The constants are fitted to published
+// measurements of the pure metals.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComplexIor {
+    pub eta: DVec3,
+    pub k: DVec3,
+}
+
+impl ComplexIor {
+    pub const GOLD: Self = Self {
+        eta: DVec3::new(0.143, 0.375, 1.442),
+        k: DVec3::new(3.983, 2.386, 1.603),
+    };
+    pub const SILVER: Self = Self {
+        eta: DVec3::new(0.155, 0.117, 0.138),
+        k: DVec3::new(4.828, 3.122, 2.147),
+    };
+    pub const COPPER: Self = Self {
+        eta: DVec3::new(0.200, 0.924, 1.102),
+        k: DVec3::new(3.913, 2.453, 2.142),
+    };
+    pub const ALUMINIUM: Self = Self {
+        eta: DVec3::new(1.657, 0.880, 0.521),
+        k: DVec3::new(9.224, 6.270, 4.837),
+    };
+
+    // `eta` and `k` at `wavelength` nanometres, interpolated between the
+    // channels and held beyond them.
+    pub fn at(&self, wavelength: f64) -> (f64, f64) {
+        let [red, green, blue] = LAMBDA_RGB;
+        let (a, b, t) = if wavelength >= green {
+            (1, 0, ((wavelength - green) / (red - green)).min(1.0))
+        } else {
+            (1, 2, ((green - wavelength) / (green - blue)).min(1.0))
+        };
+        let lerp = |v: DVec3| v[a] + t * (v[b] - v[a]);
+        (lerp(self.eta), lerp(self.k))
+    }
+
+    // Unpolarised reflectance at an angle of incidence with cosine
+    // `cosine`, for rays of `wavelength` or, without one, each channel.
+    pub fn reflectance(&self, cosine: f64, wavelength: Option<f64>) -> DVec3 {
+        per_channel(wavelength, |_, wavelength| {
+            let (eta, k) = self.at(wavelength);
+            fresnel_conductor(cosine, eta, k)
+        })
+    }
+}
+
+// A polished or rough metal whose colour comes from its complex index of
+// refraction rather than a tint: reflectance follows the full Fresnel
+// equations for conductors, so gold and copper shift towards white at
+// grazing angles and aluminium dips slightly short of them, as the real
+// metals do. The GGX lobe and energy compensation are as for `Principled`.
+pub struct Conductor {
+    pub ior: ComplexIor,
+    pub roughness: f64,
+    pub energy_compensation: Option<Arc<AlbedoLut>>,
+}
+
+impl Conductor {
+    pub fn new(ior: ComplexIor, roughness: f64) -> Self {
+        Self {
+            ior,
+            roughness: roughness.clamp(0.0, 1.0),
+            energy_compensation: None,
+        }
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).clamp(1e-3, 1.0)
+    }
+
+    // BSDF times cosine and the sampling density.
+    fn eval_reflection(&self, ray_in: &Ray, rec: &HitRecord, wo: DVec3) -> (DVec3, f64) {
+        let n = rec.normal;
+        let wi = -ray_in.direction.normalize();
+        let wo = wo.normalize();
+        let (cos_i, cos_o) = (n.dot(wi), n.dot(wo));
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return (DVec3::ZERO, 0.0);
+        }
+        let alpha = self.alpha();
+        let h = (wi + wo).normalize();
+        let d = ggx_d(n.dot(h), alpha);
+        let g = smith_g1(cos_i, alpha) * smith_g1(cos_o, alpha);
+        let fresnel = self.ior.reflectance(wi.dot(h), ray_in.wavelength);
+        let mut value = fresnel * d * g / (4.0 * cos_i);
+        if let Some(lut) = &self.energy_compensation {
+            let f0 = self.ior.reflectance(1.0, ray_in.wavelength);
+            value += lut.multiple_scattering(cos_i, cos_o, alpha, f0) * cos_o;
+        }
+        (value, d * n.dot(h) / (4.0 * wo.dot(h).abs()))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let n = rec.normal;
+        let wi = -ray_in.direction.normalize();
+        let cos_i = n.dot(wi);
+        if cos_i <= 0.0 {
+            return None;
+        }
+        let alpha = self.alpha();
+        let h = sample_ggx(n, alpha, sampler.next_2d());
+        let wo = reflect(-wi, h);
+        let cos_o = n.dot(wo);
+        if cos_o <= 0.0 {
+            return None;
+        }
+        // With h drawn from D(h)(h.n) the weight reduces to
+        // F G |i.h| / (i.n h.n).
+        let g = smith_g1(cos_i, alpha) * smith_g1(cos_o, alpha);
+        let fresnel = self.ior.reflectance(wi.dot(h), ray_in.wavelength);
+        let mut attenuation = fresnel * g * wi.dot(h) / (cos_i * n.dot(h));
+        if let Some(lut) = &self.energy_compensation {
+            let f0 = self.ior.reflectance(1.0, ray_in.wavelength);
+            let pdf = ggx_d(n.dot(h), alpha) * n.dot(h) / (4.0 * wo.dot(h).abs());
+            attenuation += lut.multiple_scattering(cos_i, cos_o, alpha, f0) * cos_o / pdf;
+        }
+        Some((Ray::new(rec.point, wo), attenuation))
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        Some(self.eval_reflection(ray_in, rec, direction).0)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        self.eval_reflection(ray_in, rec, direction).1
+    }
+
+    // Reflectance head on, the colour the metal is known by.
+    fn albedo(&self, _rec: &HitRecord) -> DVec3 {
+        self.ior.reflectance(1.0, None)
+    }
+}
+
+// A simple fibre model for `Curve`s, after Marschner et al.: light
+// reflects off the surface of a hair (R), passes through it (TT) or
+// reflects once inside it (TRT). Each lobe is a trimmed logistic in the
+// angle along the fibre, mirrored about its normal plane and shifted by
+// the tilt of the cuticle scales, times one in the angle around it: TT
+// carries on straight through, spread by `azimuthal_roughness`, while R
+// and TRT come back broadly. Every pass through the fibre is filtered by
+// `color`, so dark hair is mostly its shine. Hits must carry the direction
+// of the fibre as their tangent.
+pub struct Hair {
+    pub color: Arc<dyn Texture>,
+    pub ior: f64,
+    // Spreads of the lobes along and around the fibre, in radians.
+    pub longitudinal_roughness: f64,
+    pub azimuthal_roughness: f64,
+    pub cuticle_angle: f64,
+}
+
+// Scale of the azimuthal spread of the R and TRT lobes.
+const BROAD_AZIMUTH: f64 = 1.0;
+
+// The fibre direction, the shading normal and the direction across the
+// fibre, around which azimuths are measured from the normal.
+struct FibreFrame {
+    fibre: DVec3,
+    normal: DVec3,
+    across: DVec3,
+}
+
+impl FibreFrame {
+    fn new(rec: &HitRecord) -> Self {
+        let (fibre, across) = tangent_frame(rec);
+        Self {
+            fibre,
+            normal: rec.normal,
+            across,
+        }
+    }
+
+    // Angle of `w` from the normal plane and around the fibre.
+    fn angles(&self, w: DVec3) -> (f64, f64) {
+        let w = w.normalize();
+        let theta = w.dot(self.fibre).clamp(-1.0, 1.0).asin();
+        (theta, w.dot(self.across).atan2(w.dot(self.normal)))
+    }
+
+    fn direction(&self, theta: f64, phi: f64) -> DVec3 {
+        let around = phi.cos() * self.normal + phi.sin() * self.across;
+        theta.sin() * self.fibre + theta.cos() * around
+    }
+}
+
+impl Hair {
+    pub fn new(color: Arc<dyn Texture>) -> Self {
+        Self {
+            color,
+            ior: 1.55,
+            longitudinal_roughness: 0.3,
+            azimuthal_roughness: 0.3,
+            cuticle_angle: 2f64.to_radians(),
+        }
+    }
+
+    // Energy each lobe carries away for light arriving along `wi`.
+    fn attenuations(&self, rec: &HitRecord, wi: DVec3) -> [DVec3; 3] {
+        let f = fresnel_dielectric(rec.normal.dot(wi).abs(), 1.0 / self.ior);
+        let t = self
+            .color
+            .value(rec.u, rec.v, rec.point)
+            .clamp(DVec3::ZERO, DVec3::ONE);
+        let through = (1.0 - f) * (1.0 - f);
+        [DVec3::splat(f), through * t, through * f * t * t]
+    }
+
+    // Longitudinal centre, longitudinal scale, azimuthal centre and
+    // azimuthal scale of lobe `p` for light arriving at `theta_i`.
+    fn lobe(&self, p: usize, theta_i: f64) -> (f64, f64, f64, f64) {
+        let alpha = self.cuticle_angle;
+        let beta = self.longitudinal_roughness.max(1e-3);
+        match p {
+            0 => (-theta_i - 2.0 * alpha, beta, PI, BROAD_AZIMUTH),
+            1 => (
+                -theta_i + alpha,
+                0.5 * beta,
+                0.0,
+                self.azimuthal_roughness.max(1e-3),
+            ),
+            _ => (-theta_i + 4.0 * alpha, 2.0 * beta, PI, BROAD_AZIMUTH),
+        }
+    }
+
+    // Product of lobe `p`'s angular densities towards `wo`, per unit
+    // longitudinal and azimuthal angle.
+    fn lobe_density(&self, p: usize, frame: &FibreFrame, wi: DVec3, wo: DVec3) -> f64 {
+        let (theta_i, phi_i) = frame.angles(wi);
+        let (theta_o, phi_o) = frame.angles(wo);
+        let (theta, v, mu, s) = self.lobe(p, theta_i);
+        let half_pi = 0.5 * PI;
+        let longitudinal = trimmed_logistic(theta_o - theta, v, -half_pi - theta, half_pi - theta);
+        let phi = wrap_angle(phi_o - phi_i - mu);
+        longitudinal * trimmed_logistic(phi, s, -PI, PI)
+    }
+
+    // BSDF times cosine and pdf towards `wo`, in solid angle.
+    fn evaluate(&self, rec: &HitRecord, wi: DVec3, wo: DVec3) -> (DVec3, f64) {
+        let frame = FibreFrame::new(rec);
+        let cos_o = frame.angles(wo).0.cos();
+        if cos_o < 1e-6 {
+            return (DVec3::ZERO, 0.0);
+        }
+        let attenuations = self.attenuations(rec, wi);
+        let weights = lobe_weights(&attenuations);
+        let (mut value, mut pdf) = (DVec3::ZERO, 0.0);
+        for (p, (attenuation, weight)) in attenuations.iter().zip(weights).enumerate() {
+            let density = self.lobe_density(p, &frame, wi, wo);
+            value += *attenuation * density;
+            pdf += weight * density;
+        }
+        (value / cos_o, pdf / cos_o)
+    }
+}
+
+// Probability of sampling each lobe, by how much light it carries.
+fn lobe_weights(attenuations: &[DVec3; 3]) -> [f64; 3] {
+    let weights = attenuations.map(luminance);
+    let total: f64 = weights.iter().sum();
+    if total > 0.0 {
+        weights.map(|w| w / total)
+    } else {
+        [0.0; 3]
+    }
+}
+
+impl Material for Hair {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, DVec3)> {
+        let wi = -ray_in.direction.normalize();
+        let frame = FibreFrame::new(rec);
+        let weights = lobe_weights(&self.attenuations(rec, wi));
+        let choice = sampler.next_1d();
+        let p = if choice < weights[0] {
+            0
+        } else if choice < weights[0] + weights[1] {
+            1
+        } else {
+            2
+        };
+        let (theta_i, phi_i) = frame.angles(wi);
+        let (theta, v, mu, s) = self.lobe(p, theta_i);
+        let (u1, u2) = sampler.next_2d();
+        let half_pi = 0.5 * PI;
+        let theta_o = theta + sample_trimmed_logistic(u1, v, -half_pi - theta, half_pi - theta);
+        let phi_o = phi_i + mu + sample_trimmed_logistic(u2, s, -PI, PI);
+        let wo = frame.direction(theta_o, phi_o);
+        // Weighted by all three lobes together, as if any could have
+        // sampled the direction.
+        let (value, pdf) = self.evaluate(rec, wi, wo);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((Ray::new(rec.point, wo), value / pdf))
+    }
+
+    fn eval(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> Option<DVec3> {
+        let wi = -ray_in.direction.normalize();
+        Some(self.evaluate(rec, wi, direction).0)
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &HitRecord, direction: DVec3) -> f64 {
+        let wi = -ray_in.direction.normalize();
+        self.evaluate(rec, wi, direction).1
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> DVec3 {
+        self.color.value(rec.u, rec.v, rec.point)
+    }
+}
+
+// Logistic density of scale `s` at `x`, and its cumulative distribution.
+fn logistic(x: f64, s: f64) -> f64 {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+// The logistic density renormalised to [`a`, `b`] and zero outside it.
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    if x < a || x > b {
+        return 0.0;
+    }
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let (low, high) = (logistic_cdf(a, s), logistic_cdf(b, s));
+    let k = low + u * (high - low);
+    (-s * (1.0 / k - 1.0).ln()).clamp(a, b)
+}
+
+// `angle` brought into [-π, π].
+fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+// Orthonormal tangent and bitangent around the shading normal. Primitives
+// without UVs get a tangent around the Y axis, which matches the usual
+// sphere parameterisation.
+fn tangent_frame(rec: &HitRecord) -> (DVec3, DVec3) {
+    let n = rec.normal;
+    let mut tangent = rec.tangent - n * n.dot(rec.tangent);
+    if tangent.length_squared() < 1e-12 {
+        tangent = DVec3::Y.cross(n);
+        if tangent.length_squared() < 1e-12 {
+            tangent = n.any_orthonormal_vector();
+        }
+    }
+    let tangent = tangent.normalize();
+    (tangent, n.cross(tangent))
+}
+
+fn ggx_d(cos_h: f64, alpha: f64) -> f64 {
+    if cos_h <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let t = cos_h * cos_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * t * t)
+}
+
+pub(crate) fn smith_g1(cosine: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    2.0 * cosine / (cosine + (a2 + (1.0 - a2) * cosine * cosine).sqrt())
+}
+
+// Microfacet normal distributed as D(m)(m.n) around `n`.
+pub(crate) fn sample_ggx(n: DVec3, alpha: f64, (u1, u2): (f64, f64)) -> DVec3 {
+    let cos2 = (1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1);
+    let cos_theta = cos2.sqrt();
+    let sin_theta = (1.0 - cos2).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (t, b) = n.any_orthonormal_pair();
+    (sin_theta * phi.cos() * t + sin_theta * phi.sin() * b + cos_theta * n).normalize()
+}
+
+// Anisotropic GGX terms for a local-space microfacet normal or direction,
+// with roughness `ax` along X and `ay` along Y.
+fn ggx_d_aniso(h: DVec3, ax: f64, ay: f64) -> f64 {
+    if h.z <= 0.0 {
+        return 0.0;
+    }
+    let t = (h.x / ax).powi(2) + (h.y / ay).powi(2) + h.z * h.z;
+    1.0 / (PI * ax * ay * t * t)
+}
+
+fn smith_g1_aniso(w: DVec3, ax: f64, ay: f64) -> f64 {
+    let tan2 = ((ax * w.x).powi(2) + (ay * w.y).powi(2)) / (w.z * w.z);
+    2.0 / (1.0 + (1.0 + tan2).sqrt())
+}
+
+// Local-space microfacet normal distributed as D(h)(h.z).
+fn sample_ggx_aniso(ax: f64, ay: f64, (u1, u2): (f64, f64)) -> DVec3 {
+    let phi = (ay * (2.0 * PI * u2).sin()).atan2(ax * (2.0 * PI * u2).cos());
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let inv_a2 = (cos_phi / ax).powi(2) + (sin_phi / ay).powi(2);
+    let tan2 = u1 / ((1.0 - u1) * inv_a2);
+    let cos_theta = 1.0 / (1.0 + tan2).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    DVec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta)
+}
+
+fn schlick(f0: DVec3, cosine: f64) -> DVec3 {
+    f0 + (DVec3::ONE - f0) * (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+// Unpolarised Fresnel reflectance; `eta` is incident over transmitted index.
+fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin_t2 = eta * eta * (1.0 - cos_i * cos_i);
+    if sin_t2 >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t2).sqrt();
+    let rs = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let rp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// Unpolarised reflectance of a conductor of complex index `eta` + i`k`
+// under air, from the exact Fresnel equations.
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+    let t1 = a2b2 + cos2;
+    let t2 = 2.0 * a * cos2.sqrt();
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rs + rp)
+}
+
+fn luminance(c: DVec3) -> f64 {
+    c.dot(DVec3::new(0.2126, 0.7152, 0.0722))
+}
+
+fn reflect(v: DVec3, n: DVec3) -> DVec3 {
+    v - 2.0 * v.dot(n) * n
+}
+
+fn refract(uv: DVec3, n: DVec3, etai_over_etat: f64) -> DVec3 {
+    let cos_theta = (-uv).dot(n).min(1.0);
+    let r_out_perp = etai_over_etat * (uv + cos_theta * n);
+    let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
+    r_out_perp + r_out_parallel
+}
+
+fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+    let r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+// Both helpers map sampler dimensions directly instead of rejection sampling so
+// that stratified and low-discrepancy samplers keep their structure.
+pub(crate) fn random_unit_vector(sampler: &mut dyn Sampler) -> DVec3 {
+    let (u1, u2) = sampler.next_2d();
+    let z = 1.0 - 2.0 * u1;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    DVec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
-fn random_unit_vector() -> DVec3 {
-    random_in_unit_sphere().normalize()
+pub(crate) fn random_in_unit_sphere(sampler: &mut dyn Sampler) -> DVec3 {
+    let radius = sampler.next_1d().cbrt();
+    radius * random_unit_vector(sampler)
 }
@@ -0,0 +1,104 @@
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::environment::{Environment, EnvironmentMap, SkyGradient};
+use crate::hittable::{Hittable, HittableList};
+use crate::material::{Lambertian, Material};
+use crate::objects::sphere::Sphere;
+use crate::objects::transform::Transformed;
+use crate::renderer::{ImageBuffer, RenderSettings, Renderer};
+use crate::scene::{parse_material, MaterialDef, MaterialLibrary};
+use crate::texture::{CheckerTexture, SolidColor};
+use glam::{DAffine3, DVec3};
+use std::collections::HashMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct PreviewOptions {
+    pub settings: RenderSettings,
+    pub hdri: Option<String>,
+    pub turntable_frames: Option<u32>,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            settings: RenderSettings {
+                width: 256,
+                height: 256,
+                samples_per_pixel: 64,
+                ..RenderSettings::default()
+            },
+            hdri: None,
+            turntable_frames: None,
+        }
+    }
+}
+
+// Renders `material` on a standard shader ball: a unit sphere resting on a
+// checkered floor with a large curved backdrop behind it. Returns one image,
+// or one per frame when a turntable is requested.
+pub fn render_material_preview(
+    material: &MaterialDef,
+    options: &PreviewOptions,
+) -> Result<Vec<ImageBuffer>, Box<dyn Error>> {
+    let checker: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(CheckerTexture::new(
+        0.25,
+        Arc::new(SolidColor::new(DVec3::splat(0.15))),
+        Arc::new(SolidColor::new(DVec3::splat(0.7))),
+    ))));
+    let backdrop: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+
+    // A lone material has no library to name entries from.
+    let (materials, textures) = (HashMap::new(), HashMap::new());
+    let ball: Arc<dyn Hittable> = Arc::new(Sphere::new(
+        DVec3::new(0.0, 1.0, 0.0),
+        1.0,
+        parse_material(material, &MaterialLibrary::new(&materials, &textures))?,
+    ));
+    let environment: Arc<dyn Environment> = match &options.hdri {
+        Some(path) => Arc::new(EnvironmentMap::new(path, 1.0)?),
+        None => Arc::new(SkyGradient::default()),
+    };
+
+    let settings = &options.settings;
+    let aspect_ratio = settings.aspect_ratio();
+    let lookat = DVec3::new(0.0, 0.9, 0.0);
+    let lookfrom = DVec3::new(0.0, 2.5, 4.5);
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        DVec3::Y,
+        30.0,
+        aspect_ratio,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+
+    let frames = options.turntable_frames.unwrap_or(1).max(1);
+    let mut images = Vec::with_capacity(frames as usize);
+    for frame in 0..frames {
+        let angle = 2.0 * PI * frame as f64 / frames as f64;
+        let mut world = HittableList::new();
+        world.push(Arc::new(Transformed::new(
+            ball.clone(),
+            DAffine3::from_rotation_y(angle),
+        )));
+        world.push(Arc::new(Sphere::new(
+            DVec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            checker.clone(),
+        )));
+        world.push(Arc::new(Sphere::new(
+            DVec3::new(0.0, 0.0, -1006.0),
+            1000.0,
+            backdrop.clone(),
+        )));
+
+        let renderer = Renderer::new(Arc::new(BvhNode::new(world)), environment.clone());
+        images.push(renderer.render(&camera, settings));
+    }
+    Ok(images)
+}
@@ -0,0 +1,298 @@
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Live counters of a render in progress, shared between the renderer and
+// the metrics endpoint. The renderer updates them once per finished tile,
+// so render threads don't contend on every sample, and tells any
+// listeners each time.
+#[derive(Default)]
+pub struct RenderProgress {
+    total_pixels: AtomicU64,
+    pixels_done: AtomicU64,
+    total_tiles: AtomicU64,
+    tiles_done: AtomicU64,
+    camera_rays: AtomicU64,
+    clock: Mutex<RenderClock>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+type Listener = Box<dyn Fn(&MetricsSnapshot) + Send + Sync>;
+
+#[derive(Default)]
+struct RenderClock {
+    started: Option<Instant>,
+    // Set once the render is done, freezing the elapsed time.
+    finished: Option<Duration>,
+}
+
+// Point-in-time view of a `RenderProgress`.
+#[derive(Clone, Debug, Serialize)]
+pub struct MetricsSnapshot {
+    // Fraction of pixels finished, in [0, 1].
+    pub progress: f64,
+    pub pixels_done: u64,
+    pub total_pixels: u64,
+    pub tiles_done: u64,
+    pub total_tiles: u64,
+    // Primary rays, one per pixel sample; bounces and shadow rays aren't
+    // counted.
+    pub camera_rays: u64,
+    pub camera_rays_per_second: f64,
+    pub elapsed_seconds: f64,
+    // Time left at the rate pixels have finished so far; unknown until the
+    // first tile finishes, and 0 once the render is done.
+    pub eta_seconds: Option<f64>,
+    // Resident set size of the process; only known on Linux.
+    pub resident_memory_bytes: Option<u64>,
+}
+
+impl RenderProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Resets the counters for a new image of `total_pixels` pixels, split
+    // into `total_tiles` tiles.
+    pub fn start(&self, total_pixels: u64, total_tiles: u64) {
+        self.total_pixels.store(total_pixels, Ordering::Relaxed);
+        self.pixels_done.store(0, Ordering::Relaxed);
+        self.total_tiles.store(total_tiles, Ordering::Relaxed);
+        self.tiles_done.store(0, Ordering::Relaxed);
+        self.camera_rays.store(0, Ordering::Relaxed);
+        *self.clock.lock().unwrap() = RenderClock {
+            started: Some(Instant::now()),
+            finished: None,
+        };
+    }
+
+    pub fn add_tile(&self, pixels: u64, camera_rays: u64) {
+        self.pixels_done.fetch_add(pixels, Ordering::Relaxed);
+        self.tiles_done.fetch_add(1, Ordering::Relaxed);
+        self.camera_rays.fetch_add(camera_rays, Ordering::Relaxed);
+        self.notify();
+    }
+
+    // Marks the render done, whether it finished or was cancelled.
+    pub fn finish(&self) {
+        {
+            let mut clock = self.clock.lock().unwrap();
+            clock.finished = clock.started.map(|started| started.elapsed());
+        }
+        self.notify();
+    }
+
+    // Calls `listener` with a snapshot whenever a tile finishes and when
+    // the render is done, on whichever render thread got there. Calls are
+    // never concurrent, but slow listeners hold up rendering.
+    pub fn on_update(&self, listener: impl Fn(&MetricsSnapshot) + Send + Sync + 'static) {
+        self.listeners.lock().unwrap().push(Box::new(listener));
+    }
+
+    // The snapshots `on_update` would give, for a thread of the caller's
+    // to receive, such as a GUI's event loop. Updates stop being sent once
+    // the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<MetricsSnapshot> {
+        let (sender, receiver) = mpsc::channel();
+        self.on_update(move |snapshot| {
+            let _ = sender.send(snapshot.clone());
+        });
+        receiver
+    }
+
+    fn notify(&self) {
+        let listeners = self.listeners.lock().unwrap();
+        if listeners.is_empty() {
+            return;
+        }
+        let snapshot = self.snapshot();
+        for listener in listeners.iter() {
+            listener(&snapshot);
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let (elapsed, finished) = {
+            let clock = self.clock.lock().unwrap();
+            let elapsed = clock
+                .finished
+                .or_else(|| clock.started.map(|started| started.elapsed()))
+                .unwrap_or_default();
+            (elapsed, clock.finished.is_some())
+        };
+        let total_pixels = self.total_pixels.load(Ordering::Relaxed);
+        let pixels_done = self.pixels_done.load(Ordering::Relaxed);
+        let camera_rays = self.camera_rays.load(Ordering::Relaxed);
+        let seconds = elapsed.as_secs_f64();
+        let eta_seconds = if finished {
+            Some(0.0)
+        } else if pixels_done > 0 {
+            let left = total_pixels.saturating_sub(pixels_done);
+            Some(seconds * left as f64 / pixels_done as f64)
+        } else {
+            None
+        };
+        MetricsSnapshot {
+            progress: if total_pixels == 0 {
+                0.0
+            } else {
+                pixels_done as f64 / total_pixels as f64
+            },
+            pixels_done,
+            total_pixels,
+            tiles_done: self.tiles_done.load(Ordering::Relaxed),
+            total_tiles: self.total_tiles.load(Ordering::Relaxed),
+            camera_rays,
+            camera_rays_per_second: if seconds > 0.0 {
+                camera_rays as f64 / seconds
+            } else {
+                0.0
+            },
+            elapsed_seconds: seconds,
+            eta_seconds,
+            resident_memory_bytes: resident_memory(),
+        }
+    }
+}
+
+impl MetricsSnapshot {
+    // Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(text, "# HELP raytracer_{name} {help}");
+            let _ = writeln!(text, "# TYPE raytracer_{name} {kind}");
+            let _ = writeln!(text, "raytracer_{name} {value}");
+        };
+        metric(
+            "progress_ratio",
+            "gauge",
+            "Fraction of pixels finished.",
+            self.progress,
+        );
+        metric(
+            "pixels_rendered_total",
+            "counter",
+            "Pixels finished so far.",
+            self.pixels_done as f64,
+        );
+        metric(
+            "pixels",
+            "gauge",
+            "Pixels in the image being rendered.",
+            self.total_pixels as f64,
+        );
+        metric(
+            "tiles_rendered_total",
+            "counter",
+            "Tiles finished so far.",
+            self.tiles_done as f64,
+        );
+        metric(
+            "tiles",
+            "gauge",
+            "Tiles in the image being rendered.",
+            self.total_tiles as f64,
+        );
+        metric(
+            "camera_rays_total",
+            "counter",
+            "Camera rays traced so far.",
+            self.camera_rays as f64,
+        );
+        metric(
+            "camera_rays_per_second",
+            "gauge",
+            "Average camera rays traced per second.",
+            self.camera_rays_per_second,
+        );
+        metric(
+            "elapsed_seconds",
+            "gauge",
+            "Time spent on the current render.",
+            self.elapsed_seconds,
+        );
+        if let Some(eta) = self.eta_seconds {
+            metric(
+                "eta_seconds",
+                "gauge",
+                "Estimated time left on the current render.",
+                eta,
+            );
+        }
+        if let Some(bytes) = self.resident_memory_bytes {
+            metric(
+                "resident_memory_bytes",
+                "gauge",
+                "Resident set size of the renderer.",
+                bytes as f64,
+            );
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("metrics serialise to JSON")
+    }
+}
+
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+// Serves `progress` over HTTP on a background thread: `/metrics` in the
+// Prometheus text format and `/metrics.json` as JSON. Returns the bound
+// address, which tells callers the port when `address` asks for port 0.
+pub fn serve(address: impl ToSocketAddrs, progress: Arc<RenderProgress>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let bound = listener.local_addr()?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A client that hangs up mid-request only affects itself.
+            let _ = respond(stream, &progress);
+        }
+    });
+    Ok(bound)
+}
+
+fn respond(stream: TcpStream, progress: &RenderProgress) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers; no route takes a body.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next(), parts.next());
+    let (status, content_type, body) = match (method, target) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            progress.snapshot().to_prometheus(),
+        ),
+        (Some("GET"), Some("/metrics.json")) => {
+            ("200 OK", "application/json", progress.snapshot().to_json())
+        }
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,341 @@
+use crate::color_space::ColorSpace;
+use crate::error::RenderError;
+use crate::texture::Texture;
+use glam::DVec3;
+use std::cell::Cell;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+// Image texture with bilinear filtering and a box-filtered mip pyramid. UVs
+// wrap, so tiled textures filter across the seam. `value` filters
+// trilinearly for the footprint of the hit being shaded, which the renderer
+// sets with a `FootprintScope`, and reads the full resolution level where
+// none is set; callers that know the footprint of a lookup themselves can
+// use `sample_footprint`.
+pub struct MipmappedTexture {
+    levels: Vec<MipLevel>,
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<DVec3>,
+}
+
+impl MipmappedTexture {
+    // The colours of the image at `path`, decoded from `color_space` to
+    // linear before the pyramid is filtered.
+    pub fn new(path: &str, color_space: ColorSpace) -> Result<Self, RenderError> {
+        Ok(Self::from_image(open(path)?, color_space))
+    }
+
+    // Like `new`, for an encoded image already in memory, such as one
+    // fetched by a page with no filesystem to read it from. The format is
+    // guessed from the bytes.
+    pub fn from_bytes(bytes: &[u8], color_space: ColorSpace) -> Result<Self, RenderError> {
+        Ok(Self::from_image(decode(bytes)?, color_space))
+    }
+
+    fn from_image(image: image::DynamicImage, color_space: ColorSpace) -> Self {
+        let image = image.into_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let decoded: Vec<f64> = (0..=255)
+            .map(|c| color_space.decode(c as f64 / 255.0))
+            .collect();
+        let texels = image
+            .pixels()
+            .map(|p| DVec3::from_array(p.0.map(|c| decoded[c as usize])))
+            .collect();
+        Self::from_texels(width, height, texels)
+    }
+
+    // The alpha channel of the image at `path` as grey, white where it has
+    // none, for cutouts. Alpha is always linear.
+    pub fn alpha(path: &str) -> Result<Self, RenderError> {
+        Ok(Self::alpha_of(open(path)?))
+    }
+
+    // Like `alpha`, for an encoded image already in memory.
+    pub fn alpha_from_bytes(bytes: &[u8]) -> Result<Self, RenderError> {
+        Ok(Self::alpha_of(decode(bytes)?))
+    }
+
+    fn alpha_of(image: image::DynamicImage) -> Self {
+        let image = image.into_rgba8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let texels = image
+            .pixels()
+            .map(|p| DVec3::splat(p[3] as f64 / 255.0))
+            .collect();
+        Self::from_texels(width, height, texels)
+    }
+
+    // `texels` are rows top to bottom.
+    pub fn from_texels(width: usize, height: usize, texels: Vec<DVec3>) -> Self {
+        assert!(
+            width > 0 && height > 0 && texels.len() == width * height,
+            "texture size doesn't match its texels"
+        );
+        let mut levels = vec![MipLevel {
+            width,
+            height,
+            texels,
+        }];
+        while let Some(next) = levels.last().unwrap().downsample() {
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    // Fractional mip level whose texels are `footprint` UV units wide.
+    pub fn level_for_footprint(&self, footprint: f64) -> f64 {
+        let base = &self.levels[0];
+        let texels = footprint * base.width.max(base.height) as f64;
+        texels.max(1.0).log2().min((self.levels.len() - 1) as f64)
+    }
+
+    // Trilinear lookup: bilinear in the two levels around `level`, blended.
+    pub fn sample(&self, u: f64, v: f64, level: f64) -> DVec3 {
+        let level = level.clamp(0.0, (self.levels.len() - 1) as f64);
+        let lower = level.floor() as usize;
+        let fine = self.levels[lower].bilinear(u, v);
+        if lower + 1 == self.levels.len() {
+            return fine;
+        }
+        let coarse = self.levels[lower + 1].bilinear(u, v);
+        fine.lerp(coarse, level - lower as f64)
+    }
+
+    pub fn sample_footprint(&self, u: f64, v: f64, footprint: f64) -> DVec3 {
+        self.sample(u, v, self.level_for_footprint(footprint))
+    }
+}
+
+fn open(path: &str) -> Result<image::DynamicImage, RenderError> {
+    image::open(path).map_err(|e| RenderError::image(path, e))
+}
+
+fn decode(bytes: &[u8]) -> Result<image::DynamicImage, RenderError> {
+    image::load_from_memory(bytes).map_err(|e| RenderError::image("<memory>", e))
+}
+
+impl MipLevel {
+    fn texel(&self, x: isize, y: isize) -> DVec3 {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    fn nearest(&self, u: f64, v: f64) -> DVec3 {
+        let x = (u * self.width as f64).floor() as isize;
+        let y = ((1.0 - v) * self.height as f64).floor() as isize;
+        self.texel(x, y)
+    }
+
+    fn bilinear(&self, u: f64, v: f64) -> DVec3 {
+        // Texel centres sit at half-integer coordinates; v runs bottom to
+        // top while rows are stored top to bottom.
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let top = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), fx);
+        let bottom = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+
+    // Halves each dimension that is still larger than one texel, averaging
+    // 2x2 (or 2x1) blocks. Odd sizes drop their last row or column.
+    fn downsample(&self) -> Option<MipLevel> {
+        if self.width == 1 && self.height == 1 {
+            return None;
+        }
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let (sx, sy) = (self.width / width, self.height / height);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = DVec3::ZERO;
+                for dy in 0..sy {
+                    for dx in 0..sx {
+                        sum += self.texels[(y * sy + dy) * self.width + x * sx + dx];
+                    }
+                }
+                texels.push(sum / (sx * sy) as f64);
+            }
+        }
+        Some(MipLevel {
+            width,
+            height,
+            texels,
+        })
+    }
+}
+
+impl Texture for MipmappedTexture {
+    fn value(&self, u: f64, v: f64, _p: DVec3) -> DVec3 {
+        let footprint = FOOTPRINT.with(Cell::get);
+        if footprint > 0.0 {
+            self.sample_footprint(u, v, footprint)
+        } else {
+            self.levels[0].bilinear(u, v)
+        }
+    }
+}
+
+// The full resolution level of an image texture read without filtering,
+// for `nearest` textures decoded from memory rather than read by
+// `ImageTexture`.
+pub struct NearestTexture {
+    texture: MipmappedTexture,
+}
+
+impl NearestTexture {
+    pub fn new(texture: MipmappedTexture) -> Self {
+        Self { texture }
+    }
+}
+
+impl Texture for NearestTexture {
+    fn value(&self, u: f64, v: f64, _p: DVec3) -> DVec3 {
+        self.texture.levels[0].nearest(u, v)
+    }
+}
+
+// Textures are looked up by materials, which only pass on the UV and point
+// of a hit, so the footprint of the hit being shaded is kept per thread.
+thread_local! {
+    static FOOTPRINT: Cell<f64> = const { Cell::new(0.0) };
+}
+
+// Sets the width, in UV units, of the lookups image textures make on this
+// thread until it is dropped, when the footprint outside it is restored.
+pub struct FootprintScope {
+    outer: f64,
+}
+
+impl FootprintScope {
+    pub fn enter(footprint: f64) -> Self {
+        Self {
+            outer: FOOTPRINT.with(|f| f.replace(footprint)),
+        }
+    }
+}
+
+impl Drop for FootprintScope {
+    fn drop(&mut self) {
+        FOOTPRINT.with(|f| f.set(self.outer));
+    }
+}
+
+// A `MipmappedTexture` that reads its file again when it changes, for
+// editing textures while a preview refines. Lookups see either the old
+// image or the new one, never part of each.
+pub struct ReloadingTexture {
+    path: String,
+    read: Box<Reader>,
+    loaded: RwLock<Loaded>,
+}
+
+type Reader = dyn Fn(&str) -> Result<MipmappedTexture, RenderError> + Send + Sync;
+
+struct Loaded {
+    // Of the file when it was last read, if it could be told.
+    modified: Option<SystemTime>,
+    texture: Arc<MipmappedTexture>,
+}
+
+impl ReloadingTexture {
+    // The image at `path` as `read` makes it, e.g. with
+    // `MipmappedTexture::alpha`, each time it is loaded.
+    pub fn new(
+        path: &str,
+        read: impl Fn(&str) -> Result<MipmappedTexture, RenderError> + Send + Sync + 'static,
+    ) -> Result<Self, RenderError> {
+        let modified = modified(path);
+        let texture = read(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            read: Box::new(read),
+            loaded: RwLock::new(Loaded {
+                modified,
+                texture: Arc::new(texture),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Reads the file again if it has changed since it was last read, and
+    // says whether it did. An image that fails to read is reported once,
+    // and the texture keeps the one it had until the file changes again.
+    pub fn reload(&self) -> Result<bool, RenderError> {
+        let modified = modified(&self.path);
+        if modified == self.loaded.read().unwrap().modified {
+            return Ok(false);
+        }
+        self.loaded.write().unwrap().modified = modified;
+        let texture = (self.read)(&self.path)?;
+        self.loaded.write().unwrap().texture = Arc::new(texture);
+        Ok(true)
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Texture for ReloadingTexture {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        let texture = self.loaded.read().unwrap().texture.clone();
+        texture.value(u, v, p)
+    }
+}
+
+// The reloading textures of a scene, for a progressive render to check
+// between passes. Clones share the same list.
+#[derive(Clone, Default)]
+pub struct TextureWatch {
+    textures: Arc<Mutex<Vec<Arc<ReloadingTexture>>>>,
+}
+
+impl TextureWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, texture: Arc<ReloadingTexture>) {
+        self.textures.lock().unwrap().push(texture);
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Reloads every texture whose file changed, handing the errors of those
+    // that couldn't be read to `report`. True when any image changed, so
+    // that what has been rendered so far is stale.
+    pub fn reload_changed(&self, mut report: impl FnMut(RenderError)) -> bool {
+        let textures = self.textures.lock().unwrap().clone();
+        let mut changed = false;
+        for texture in textures {
+            match texture.reload() {
+                Ok(reloaded) => changed |= reloaded,
+                Err(e) => report(e),
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,168 @@
+use crate::hittable::{HitRecord, Hittable, HittableList, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct Capsule {
+    pub start: DVec3,
+    pub end: DVec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(start: DVec3, end: DVec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            start,
+            end,
+            radius,
+            material,
+        }
+    }
+
+    fn closest_on_axis(&self, point: DVec3) -> (DVec3, f64) {
+        let axis = self.end - self.start;
+        let length_squared = axis.length_squared();
+        if length_squared == 0.0 {
+            return (self.start, 0.0);
+        }
+        let s = ((point - self.start).dot(axis) / length_squared).clamp(0.0, 1.0);
+        (self.start + s * axis, s)
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let axis = self.end - self.start;
+        let length = axis.length();
+        let n = if length > 0.0 {
+            axis / length
+        } else {
+            DVec3::Y
+        };
+        let r2 = self.radius * self.radius;
+
+        let mut candidates: Vec<f64> = Vec::with_capacity(6);
+
+        // Cylindrical body between the two end caps.
+        let oa = ray.origin - self.start;
+        let d_perp = ray.direction - ray.direction.dot(n) * n;
+        let o_perp = oa - oa.dot(n) * n;
+        let a = d_perp.length_squared();
+        if a > 0.0 {
+            let half_b = d_perp.dot(o_perp);
+            let c = o_perp.length_squared() - r2;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                    let y = (oa + t * ray.direction).dot(n);
+                    if (0.0..=length).contains(&y) {
+                        candidates.push(t);
+                    }
+                }
+            }
+        }
+
+        // Hemispherical caps; only the half facing away from the body counts.
+        for (center, sign) in [(self.start, -1.0), (self.end, 1.0)] {
+            let oc = ray.origin - center;
+            let a = ray.direction.length_squared();
+            let half_b = oc.dot(ray.direction);
+            let c = oc.length_squared() - r2;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            let sqrtd = discriminant.sqrt();
+            for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                if sign * (ray.at(t) - center).dot(n) >= 0.0 {
+                    candidates.push(t);
+                }
+            }
+        }
+
+        let t = candidates
+            .into_iter()
+            .filter(|&t| interval.surrounds(t))
+            .min_by(|a, b| a.total_cmp(b))?;
+
+        let point = ray.at(t);
+        let (closest, s) = self.closest_on_axis(point);
+        let outward_normal = (point - closest) / self.radius;
+
+        let reference = if n.x.abs() > 0.9 { DVec3::Y } else { DVec3::X };
+        let tangent = n.cross(reference).normalize();
+        let bitangent = n.cross(tangent);
+        let phi = outward_normal
+            .dot(bitangent)
+            .atan2(outward_normal.dot(tangent))
+            + PI;
+        // Turning the normal about the axis; `v` only moves along the body,
+        // not over the caps.
+        let around = 2.0 * PI * n.cross(outward_normal);
+        let dpdv = if s > 0.0 && s < 1.0 {
+            axis
+        } else {
+            DVec3::ZERO
+        };
+
+        let mut rec = HitRecord {
+            point,
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: phi / (2.0 * PI),
+            v: s,
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent: DVec3::ZERO,
+            dpdu: self.radius * around,
+            dpdv,
+            dndu: around,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = DVec3::splat(self.radius);
+        Some(AABB::new(
+            self.start.min(self.end) - r,
+            self.start.max(self.end) + r,
+        ))
+    }
+
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        let (start, end) = (axis.dot(self.start), axis.dot(self.end));
+        let reach = self.radius * axis.length();
+        Some((start.min(end) - reach, start.max(end) + reach))
+    }
+}
+
+pub fn polyline(points: &[DVec3], radius: f64, material: Arc<dyn Material>) -> HittableList {
+    let mut segments = HittableList::new();
+    if points.len() == 1 {
+        segments.push(Arc::new(Capsule::new(
+            points[0], points[0], radius, material,
+        )));
+        return segments;
+    }
+    for pair in points.windows(2) {
+        segments.push(Arc::new(Capsule::new(
+            pair[0],
+            pair[1],
+            radius,
+            material.clone(),
+        )));
+    }
+    segments
+}
@@ -0,0 +1,446 @@
+use glam::{DVec2, DVec3};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::AddAssign;
+
+// Positions closer than this fraction of a mesh's largest extent are taken
+// to be the same point.
+const WELD_TOLERANCE: f64 = 1e-7;
+
+// An indexed triangle mesh as it comes out of a file, with one normal,
+// texture coordinate and colour per position when present.
+pub struct MeshData {
+    pub positions: Vec<DVec3>,
+    // Empty, or one per position.
+    pub normals: Vec<DVec3>,
+    // Empty, or one per position.
+    pub texcoords: Vec<DVec2>,
+    // Empty, or one per position, in linear RGB; PLY scans carry them.
+    pub colors: Vec<DVec3>,
+    pub faces: Vec<[usize; 3]>,
+}
+
+// What `clean` changed in a mesh.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CleanupStats {
+    // Vertices merged into an identical one, which can join faces that the
+    // file left apart.
+    pub welded_vertices: usize,
+    // Faces dropped for having repeated corners or no area.
+    pub degenerate_triangles: usize,
+    // Faces turned around to wind the same way as their neighbours, or by
+    // `orient_outward`.
+    pub flipped_triangles: usize,
+    // Vertex normals that were zero or not finite, rebuilt from the faces
+    // around them.
+    pub repaired_normals: usize,
+}
+
+impl CleanupStats {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl AddAssign for CleanupStats {
+    fn add_assign(&mut self, other: Self) {
+        self.welded_vertices += other.welded_vertices;
+        self.degenerate_triangles += other.degenerate_triangles;
+        self.flipped_triangles += other.flipped_triangles;
+        self.repaired_normals += other.repaired_normals;
+    }
+}
+
+// Lists the repairs made, e.g. "12 vertices welded, 1 triangle flipped".
+impl fmt::Display for CleanupStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = [
+            (self.welded_vertices, "vertex", "vertices", "welded"),
+            (
+                self.degenerate_triangles,
+                "degenerate triangle",
+                "degenerate triangles",
+                "dropped",
+            ),
+            (self.flipped_triangles, "triangle", "triangles", "flipped"),
+            (self.repaired_normals, "normal", "normals", "repaired"),
+        ];
+        let mut first = true;
+        for (count, one, many, action) in counts {
+            if count == 0 {
+                continue;
+            }
+            let noun = if count == 1 { one } else { many };
+            let separator = if first { "" } else { ", " };
+            write!(f, "{separator}{count} {noun} {action}")?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+// Repairs the faces of `mesh` for rendering. Degenerate triangles have no
+// normal and shade as NaN, and a face wound against its neighbours has its
+// inside and outside swapped, which turns glass inside out; dirty exports
+// are full of both. Each connected piece keeps the winding most of its
+// faces already have.
+pub fn clean(mesh: &mut MeshData) -> CleanupStats {
+    let mut stats = CleanupStats::default();
+    let tolerance = WELD_TOLERANCE * extent(&mesh.positions);
+
+    // Vertices with the same position and attributes become one; faces
+    // are then related through their corners' positions alone, so a seam
+    // in the UVs or normals doesn't split a surface in two.
+    let point_of = point_ids(&mesh.positions, tolerance);
+    let mut vertices = HashMap::new();
+    let mut vertex_of = Vec::with_capacity(mesh.positions.len());
+    for (i, &position) in mesh.positions.iter().enumerate() {
+        if !position.is_finite() {
+            // Never welded; the faces using it are dropped below.
+            vertex_of.push(i);
+            continue;
+        }
+        let key = cell(position, tolerance);
+        let normal = mesh.normals.get(i).map(|n| n.to_array().map(f64::to_bits));
+        let texcoord = mesh
+            .texcoords
+            .get(i)
+            .map(|t| t.to_array().map(f64::to_bits));
+        let color = mesh.colors.get(i).map(|c| c.to_array().map(f64::to_bits));
+        let vertex = *vertices.entry((key, normal, texcoord, color)).or_insert(i);
+        if vertex != i {
+            stats.welded_vertices += 1;
+        }
+        vertex_of.push(vertex);
+    }
+    for face in &mut mesh.faces {
+        *face = face.map(|i| vertex_of[i]);
+    }
+
+    let positions = &mesh.positions;
+    let before = mesh.faces.len();
+    mesh.faces.retain(|&[a, b, c]| {
+        let (pa, pb, pc) = (point_of[a], point_of[b], point_of[c]);
+        let area = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        pa != pb
+            && pb != pc
+            && pc != pa
+            && area.is_finite()
+            && area.length() > tolerance * tolerance
+    });
+    stats.degenerate_triangles = before - mesh.faces.len();
+
+    stats.flipped_triangles = orient(&mut mesh.faces, &point_of);
+    stats.repaired_normals = repair_normals(mesh);
+    stats
+}
+
+// Turns closed pieces of `mesh` around so that their front faces look out
+// of the solid they bound and dielectrics refract into it. A piece is
+// closed when every edge joins exactly two of its faces; open pieces have
+// no inside and are left alone. A shell inside an odd number of others
+// bounds a cavity and faces into it instead, found by counting the shells
+// a ray from one of its corners crosses. The faces should already wind
+// consistently, as `clean` leaves them. Returns the number of faces
+// flipped.
+pub fn orient_outward(mesh: &mut MeshData) -> usize {
+    let p = &mesh.positions;
+    let point_of = welded_points(p);
+    let edges = edge_faces(&mesh.faces, &point_of);
+    let edges_of = |face: [usize; 3]| {
+        let [a, b, c] = face.map(|i| point_of[i]);
+        [(a, b), (b, c), (c, a)].map(|(p, q)| &edges[&(p.min(q), p.max(q))])
+    };
+
+    let mut seen = vec![false; mesh.faces.len()];
+    let mut shells = Vec::new();
+    for seed in 0..mesh.faces.len() {
+        if seen[seed] {
+            continue;
+        }
+        seen[seed] = true;
+        let mut piece = vec![seed];
+        let mut closed = true;
+        let mut next = 0;
+        while let Some(&f) = piece.get(next) {
+            next += 1;
+            for faces in edges_of(mesh.faces[f]) {
+                closed &= faces.len() == 2;
+                for &g in faces {
+                    if !seen[g] {
+                        seen[g] = true;
+                        piece.push(g);
+                    }
+                }
+            }
+        }
+        if closed {
+            shells.push(Shell::new(piece, &mesh.faces, p));
+        }
+    }
+
+    let mut turn = vec![false; mesh.faces.len()];
+    for (i, shell) in shells.iter().enumerate() {
+        let corner = p[mesh.faces[shell.faces[0]][0]];
+        let depth = shells
+            .iter()
+            .enumerate()
+            .filter(|&(j, other)| j != i && other.contains(corner, &mesh.faces, p))
+            .count();
+        if (shell.volume < 0.0) != (depth % 2 == 1) {
+            for &f in &shell.faces {
+                turn[f] = true;
+            }
+        }
+    }
+
+    let mut flipped = 0;
+    for (face, turn) in mesh.faces.iter_mut().zip(turn) {
+        if turn {
+            face.swap(1, 2);
+            flipped += 1;
+        }
+    }
+    flipped
+}
+
+// A closed piece of a mesh.
+struct Shell {
+    faces: Vec<usize>,
+    // Positive when the faces wind outwards.
+    volume: f64,
+    min: DVec3,
+    max: DVec3,
+}
+
+impl Shell {
+    fn new(faces: Vec<usize>, all_faces: &[[usize; 3]], positions: &[DVec3]) -> Self {
+        // Measured from a corner of the shell to keep the sum precise far
+        // from the origin.
+        let origin = positions[all_faces[faces[0]][0]];
+        let mut volume = 0.0;
+        let (mut min, mut max) = (origin, origin);
+        for &f in &faces {
+            let [a, b, c] = all_faces[f].map(|i| positions[i]);
+            volume += (a - origin).dot((b - origin).cross(c - origin)) / 6.0;
+            min = min.min(a).min(b).min(c);
+            max = max.max(a).max(b).max(c);
+        }
+        Self {
+            faces,
+            volume,
+            min,
+            max,
+        }
+    }
+
+    // Whether `point` is inside the shell, by the parity of the faces a ray
+    // from it crosses. The direction is skewed so the ray is unlikely to
+    // graze an edge of an axis-aligned model.
+    fn contains(&self, point: DVec3, all_faces: &[[usize; 3]], positions: &[DVec3]) -> bool {
+        if point.cmplt(self.min).any() || point.cmpgt(self.max).any() {
+            return false;
+        }
+        let direction = DVec3::new(0.5772, 0.6180, 0.5336);
+        let crossings = self
+            .faces
+            .iter()
+            .filter(|&&f| {
+                let [a, b, c] = all_faces[f].map(|i| positions[i]);
+                crosses(point, direction, a, b, c)
+            })
+            .count();
+        crossings % 2 == 1
+    }
+}
+
+// Möller-Trumbore: whether the ray from `origin` along `direction` passes
+// through the triangle `a`, `b`, `c`.
+fn crosses(origin: DVec3, direction: DVec3, a: DVec3, b: DVec3, c: DVec3) -> bool {
+    let (e1, e2) = (b - a, c - a);
+    let h = direction.cross(e2);
+    let det = e1.dot(h);
+    if det.abs() < 1e-300 {
+        return false;
+    }
+    let s = origin - a;
+    let u = s.dot(h) / det;
+    let q = s.cross(e1);
+    let v = direction.dot(q) / det;
+    u >= 0.0 && v >= 0.0 && u + v <= 1.0 && e2.dot(q) / det > 0.0
+}
+
+fn cell(position: DVec3, tolerance: f64) -> [i64; 3] {
+    (position / tolerance).round().to_array().map(|c| c as i64)
+}
+
+// Identifies each position with the first one in the same cell of size
+// `tolerance`. Positions that aren't finite get an identity of their own.
+fn point_ids(positions: &[DVec3], tolerance: f64) -> Vec<usize> {
+    let mut points = HashMap::new();
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, &position)| {
+            if !position.is_finite() {
+                return usize::MAX - i;
+            }
+            let next = points.len();
+            *points.entry(cell(position, tolerance)).or_insert(next)
+        })
+        .collect()
+}
+
+// `point_ids` with the tolerance vertices are welded with.
+pub(crate) fn welded_points(positions: &[DVec3]) -> Vec<usize> {
+    point_ids(positions, WELD_TOLERANCE * extent(positions))
+}
+
+// The faces around each edge, keyed by its end points in increasing order.
+fn edge_faces(faces: &[[usize; 3]], point_of: &[usize]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (f, face) in faces.iter().enumerate() {
+        let [a, b, c] = face.map(|i| point_of[i]);
+        for (p, q) in [(a, b), (b, c), (c, a)] {
+            edges.entry((p.min(q), p.max(q))).or_default().push(f);
+        }
+    }
+    edges
+}
+
+// Largest side of the bounding box of `positions`, or 1 for a point.
+fn extent(positions: &[DVec3]) -> f64 {
+    let finite = positions.iter().filter(|p| p.is_finite());
+    let (min, max) = finite.fold(
+        (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+        |(min, max), &p| (min.min(p), max.max(p)),
+    );
+    let extent = (max - min).max_element();
+    if extent.is_finite() && extent > 0.0 {
+        extent
+    } else {
+        1.0
+    }
+}
+
+// Flips faces so that neighbours sharing an edge run along it in opposite
+// directions, walking each connected piece from its first face. Edges with
+// more than two faces don't say which way is consistent and are not
+// followed. Returns the number of faces flipped.
+fn orient(faces: &mut [[usize; 3]], point_of: &[usize]) -> usize {
+    let points = |face: [usize; 3]| face.map(|i| point_of[i]);
+    let edges = edge_faces(faces, point_of);
+    // Whether face `f` runs from point `p` to point `q`.
+    let runs = |f: usize, p: usize, q: usize| {
+        let [a, b, c] = points(faces[f]);
+        [(a, b), (b, c), (c, a)].contains(&(p, q))
+    };
+
+    let mut flip: Vec<Option<bool>> = vec![None; faces.len()];
+    let mut turn = vec![false; faces.len()];
+    let mut queue = VecDeque::new();
+    for seed in 0..faces.len() {
+        if flip[seed].is_some() {
+            continue;
+        }
+        flip[seed] = Some(false);
+        queue.push_back(seed);
+        let mut piece = Vec::new();
+        while let Some(f) = queue.pop_front() {
+            piece.push(f);
+            let turned = flip[f] == Some(true);
+            let [a, b, c] = points(faces[f]);
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                let (p, q) = if turned { (q, p) } else { (p, q) };
+                let [f0, f1] = edges[&(p.min(q), p.max(q))][..] else {
+                    continue;
+                };
+                let g = if f0 == f { f1 } else { f0 };
+                if flip[g].is_none() {
+                    // `g` has to run from `q` to `p`.
+                    flip[g] = Some(runs(g, p, q));
+                    queue.push_back(g);
+                }
+            }
+        }
+        // Keep whichever winding most of the piece has.
+        let turned = piece.iter().filter(|&&f| flip[f] == Some(true)).count();
+        let invert = 2 * turned > piece.len();
+        for f in piece {
+            turn[f] = (flip[f] == Some(true)) != invert;
+        }
+    }
+
+    let mut flipped = 0;
+    for (face, turn) in faces.iter_mut().zip(turn) {
+        if turn {
+            face.swap(1, 2);
+            flipped += 1;
+        }
+    }
+    flipped
+}
+
+// Gives `mesh` vertex normals, if it has none, for smooth shading: the
+// area-weighted average of the normals of the faces around each position.
+// Corners at the same position share a normal, so seams in the texture
+// coordinates don't show as creases.
+pub fn generate_normals(mesh: &mut MeshData) {
+    if mesh.normals.is_empty() {
+        mesh.normals = point_normals(mesh);
+    }
+}
+
+// The area-weighted average of the normals of the faces around each
+// position, the same for every vertex there whatever normals it has.
+pub(crate) fn point_normals(mesh: &MeshData) -> Vec<DVec3> {
+    let point_of = welded_points(&mesh.positions);
+    let mut sums: HashMap<usize, DVec3> = HashMap::new();
+    for &[a, b, c] in &mesh.faces {
+        let p = &mesh.positions;
+        // The cross product's length is twice the area.
+        let normal = (p[b] - p[a]).cross(p[c] - p[a]);
+        for i in [a, b, c] {
+            *sums.entry(point_of[i]).or_default() += normal;
+        }
+    }
+    for normal in sums.values_mut() {
+        *normal = normal.normalize_or_zero();
+    }
+    // Positions no face uses are left with a zero normal.
+    point_of
+        .iter()
+        .map(|point| sums.get(point).copied().unwrap_or_default())
+        .collect()
+}
+
+// Replaces unusable vertex normals with the area-weighted average of the
+// normals of the faces using them. Returns how many were replaced.
+fn repair_normals(mesh: &mut MeshData) -> usize {
+    let broken: Vec<bool> = mesh
+        .normals
+        .iter()
+        .map(|n| !n.is_finite() || n.length_squared() < 1e-24)
+        .collect();
+    if !broken.contains(&true) {
+        return 0;
+    }
+    let mut sums = vec![DVec3::ZERO; mesh.normals.len()];
+    for &[a, b, c] in &mesh.faces {
+        let p = &mesh.positions;
+        // The cross product's length is twice the area.
+        let normal = (p[b] - p[a]).cross(p[c] - p[a]);
+        for i in [a, b, c] {
+            sums[i] += normal;
+        }
+    }
+    let mut repaired = 0;
+    for (i, normal) in mesh.normals.iter_mut().enumerate() {
+        // Vertices no face uses are left alone.
+        if broken[i] && sums[i] != DVec3::ZERO {
+            *normal = sums[i].normalize();
+            repaired += 1;
+        }
+    }
+    repaired
+}
@@ -0,0 +1,152 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::disk::disk_extent;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// A closed cone with a base of `radius` at `base` and its tip at `apex`.
+// UVs follow `Cylinder`: u wraps around the axis and v runs towards the
+// apex; the base cap uses disk UVs.
+pub struct Cone {
+    pub base: DVec3,
+    pub apex: DVec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(base: DVec3, apex: DVec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            base,
+            apex,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let axis = self.apex - self.base;
+        let height = axis.length();
+        if height == 0.0 || self.radius <= 0.0 {
+            return None;
+        }
+        let n = axis / height;
+        let (tangent, bitangent) = n.any_orthonormal_pair();
+        // Local frame with the axis along y.
+        let local = |v: DVec3| DVec3::new(v.dot(tangent), v.dot(n), v.dot(bitangent));
+        let o = local(ray.origin - self.base);
+        let d = local(ray.direction);
+        // Radius shrinks by `k` per unit of height.
+        let k2 = (self.radius / height).powi(2);
+
+        // x² + z² = k²(h - y)² along the ray.
+        let mut candidates: Vec<(f64, bool)> = Vec::with_capacity(3);
+        let h = height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let half_b = o.x * d.x + o.z * d.z + k2 * h * d.y;
+        let c = o.x * o.x + o.z * o.z - k2 * h * h;
+        let roots = if a.abs() < 1e-12 {
+            if half_b == 0.0 {
+                Vec::new()
+            } else {
+                vec![-c / (2.0 * half_b)]
+            }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                Vec::new()
+            } else {
+                let sqrtd = discriminant.sqrt();
+                vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+            }
+        };
+        for t in roots {
+            if (0.0..=height).contains(&(o.y + t * d.y)) {
+                candidates.push((t, true));
+            }
+        }
+        if d.y != 0.0 {
+            let t = -o.y / d.y;
+            let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+            if x * x + z * z <= self.radius * self.radius {
+                candidates.push((t, false));
+            }
+        }
+
+        let (t, side) = candidates
+            .into_iter()
+            .filter(|&(t, _)| interval.surrounds(t))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        let p = o + t * d;
+        let (outward_normal, u, v, surface_tangent) = if side {
+            let phi = p.z.atan2(p.x) + PI;
+            let normal = (p.x * tangent + k2 * (height - p.y) * n + p.z * bitangent)
+                .try_normalize()
+                .unwrap_or(n);
+            let around = (-p.z * tangent + p.x * bitangent).normalize_or_zero();
+            (normal, phi / (2.0 * PI), p.y / height, around)
+        } else {
+            let u = 0.5 * (p.x / self.radius + 1.0);
+            let v = 0.5 * (p.z / self.radius + 1.0);
+            (-n, u, v, tangent)
+        };
+        let [dpdu, dpdv, dndu] = if side {
+            // Distance from the axis; the normal only turns with `u`.
+            let rho = (p.x * p.x + p.z * p.z).sqrt();
+            let outward = if rho > 0.0 {
+                (p.x * tangent + p.z * bitangent) / rho
+            } else {
+                DVec3::ZERO
+            };
+            let slant = (1.0 + k2).sqrt();
+            [
+                2.0 * PI * rho * surface_tangent,
+                height * n - self.radius * outward,
+                2.0 * PI * surface_tangent / slant,
+            ]
+        } else {
+            [
+                2.0 * self.radius * tangent,
+                2.0 * self.radius * bitangent,
+                DVec3::ZERO,
+            ]
+        };
+
+        let mut rec = HitRecord {
+            point: ray.at(t),
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent: surface_tangent,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let n = (self.apex - self.base).normalize_or_zero();
+        let extent = disk_extent(n, self.radius);
+        Some(AABB::new(
+            (self.base - extent).min(self.apex),
+            (self.base + extent).max(self.apex),
+        ))
+    }
+}
@@ -0,0 +1,129 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use std::sync::Arc;
+
+// Crossings gathered per child before giving up on the rest of the ray.
+const MAX_CROSSINGS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // `left` with `right` carved out of it.
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// Boolean combination of two closed solids. Each child's surface crossings
+// along the ray are merged in order, tracking whether the ray is inside
+// either child; the first crossing that changes whether it is inside the
+// result is the hit. Children must be watertight and report `front_face`
+// consistently, or the inside tracking goes wrong.
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Arc<dyn Hittable>,
+    pub right: Arc<dyn Hittable>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+// Every surface crossing of `object` within `interval`, nearest first.
+fn crossings<'a>(object: &'a dyn Hittable, ray: &Ray, interval: Interval) -> Vec<HitRecord<'a>> {
+    let mut hits = Vec::new();
+    let mut start = interval.min;
+    while hits.len() < MAX_CROSSINGS {
+        let Some(rec) = object.hit(ray, Interval::new(start, interval.max)) else {
+            break;
+        };
+        start = rec.t + 1e-7 * rec.t.abs().max(1.0);
+        hits.push(rec);
+    }
+    hits
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let left = crossings(self.left.as_ref(), ray, interval);
+        let right = crossings(self.right.as_ref(), ray, interval);
+
+        // A ray whose first crossing of a child is an exit starts inside it.
+        let mut in_left = left.first().is_some_and(|rec| !rec.front_face);
+        let mut in_right = right.first().is_some_and(|rec| !rec.front_face);
+        let inside = self.operation.contains(in_left, in_right);
+
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        loop {
+            let take_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let mut rec = if take_left {
+                let rec = left.next()?;
+                in_left = rec.front_face;
+                rec
+            } else {
+                let rec = right.next()?;
+                in_right = rec.front_face;
+                rec
+            };
+            let now_inside = self.operation.contains(in_left, in_right);
+            if now_inside != inside {
+                // The stored normal already faces the ray; only which side
+                // of the result it is on changes.
+                rec.front_face = now_inside;
+                return Some(rec);
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.operation {
+            CsgOperation::Union => Some(AABB::surrounding_box(left?, right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(left), Some(right)) => {
+                    // Disjoint children leave an empty box at the overlap's
+                    // corner.
+                    let min = left.min.max(right.min);
+                    Some(AABB::new(min, left.max.min(right.max).max(min)))
+                }
+                (left, right) => left.or(right),
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::sync::Arc;
+
+// An axis-aligned box. Each face gets its own [0, 1]^2 UVs.
+pub struct Cuboid {
+    pub min: DVec3,
+    pub max: DVec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    pub fn new(a: DVec3, b: DVec3, material: Arc<dyn Material>) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+            material,
+        }
+    }
+}
+
+// Axes spanning u and v on the faces perpendicular to each axis.
+const FACE_AXES: [(usize, usize); 3] = [(2, 1), (0, 2), (0, 1)];
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        // Slab test, remembering which face the ray enters and leaves by.
+        let mut t_near = f64::NEG_INFINITY;
+        let mut t_far = f64::INFINITY;
+        let (mut near_face, mut far_face) = ((0, 0.0), (0, 0.0));
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.direction[a];
+            let t0 = (self.min[a] - ray.origin[a]) * inv_d;
+            let t1 = (self.max[a] - ray.origin[a]) * inv_d;
+            let (enter, exit, sign) = if inv_d < 0.0 {
+                (t1, t0, 1.0)
+            } else {
+                (t0, t1, -1.0)
+            };
+            if enter > t_near {
+                t_near = enter;
+                near_face = (a, sign);
+            }
+            if exit < t_far {
+                t_far = exit;
+                far_face = (a, -sign);
+            }
+        }
+        if t_far < t_near {
+            return None;
+        }
+        let (t, (axis, sign)) = if interval.surrounds(t_near) {
+            (t_near, near_face)
+        } else if interval.surrounds(t_far) {
+            (t_far, far_face)
+        } else {
+            return None;
+        };
+
+        let point = ray.at(t);
+        let mut outward_normal = DVec3::ZERO;
+        outward_normal[axis] = sign;
+        let (ua, va) = FACE_AXES[axis];
+        let size = self.max - self.min;
+        let local = (point - self.min) / size.max(DVec3::splat(1e-12));
+        let mut tangent = DVec3::ZERO;
+        tangent[ua] = 1.0;
+        let mut dpdv = DVec3::ZERO;
+        dpdv[va] = size[va];
+
+        let mut rec = HitRecord {
+            point,
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: local[ua],
+            v: local[va],
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent,
+            dpdu: size[ua] * tangent,
+            dpdv,
+            dndu: DVec3::ZERO,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let padding = DVec3::splat(1e-4);
+        Some(AABB::new(self.min - padding, self.max + padding))
+    }
+}
@@ -0,0 +1,344 @@
+use crate::error::RenderError;
+use crate::hittable::{HitRecord, Hittable, HittableList, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{DVec2, DVec3};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::SQRT_2;
+use std::path::Path;
+use std::sync::Arc;
+
+// How a curve is shaded across its width. Either way it is intersected as
+// a ribbon turned to face each ray, which is all a strand a pixel or less
+// wide needs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CurveShape {
+    // With one normal across the width, facing the ray.
+    #[default]
+    #[serde(rename = "flat")]
+    Flat,
+    // With the normal turning across the width as a tube's would, for
+    // strands seen close up.
+    #[serde(rename = "round")]
+    Round,
+}
+
+// A cubic Bezier strand through `control_points`, its width interpolated
+// linearly from `widths[0]` at the start to `widths[1]` at the end. Hits
+// carry the direction along the strand as their tangent, for hair
+// shading, with `u` along the strand and `v` across it.
+pub struct Curve {
+    pub control_points: [DVec3; 4],
+    pub widths: [f64; 2],
+    pub shape: CurveShape,
+    pub material: Arc<dyn Material>,
+}
+
+// Where a ray met a curve: along the ray, along the curve and across it.
+struct CurveHit {
+    t: f64,
+    u: f64,
+    v: f64,
+}
+
+impl Curve {
+    pub fn new(
+        control_points: [DVec3; 4],
+        widths: [f64; 2],
+        shape: CurveShape,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            control_points,
+            widths,
+            shape,
+            material,
+        }
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        self.widths[0] + (self.widths[1] - self.widths[0]) * u
+    }
+
+    // Subdivides the curve, in a frame with the ray running down +z from
+    // the origin, until its pieces are close enough to straight to test as
+    // line segments (after Pharr et al.'s `Curve::recursiveIntersect`).
+    // `z` is the stretch of the ray still to search, in distance along it.
+    fn intersect(
+        &self,
+        cp: [DVec3; 4],
+        u: (f64, f64),
+        z: Interval,
+        depth: u32,
+    ) -> Option<CurveHit> {
+        let half_width = 0.5 * self.width(u.0).max(self.width(u.1));
+        let min = cp.iter().fold(DVec3::INFINITY, |m, &p| m.min(p));
+        let max = cp.iter().fold(DVec3::NEG_INFINITY, |m, &p| m.max(p));
+        if min.x > half_width
+            || max.x < -half_width
+            || min.y > half_width
+            || max.y < -half_width
+            || min.z - half_width > z.max
+            || max.z + half_width < z.min
+        {
+            return None;
+        }
+        if depth > 0 {
+            let [first, second] = split(cp);
+            let middle = 0.5 * (u.0 + u.1);
+            let near = self.intersect(first, (u.0, middle), z, depth - 1);
+            let z = near.as_ref().map_or(z, |hit| z.with_max(hit.t));
+            return self.intersect(second, (middle, u.1), z, depth - 1).or(near);
+        }
+
+        // The ray must pass between the lines through the segment's ends
+        // square to its tangents there, so that neighbouring pieces don't
+        // both claim it.
+        let xy = cp.map(|p| DVec2::new(p.x, p.y));
+        if (xy[1] - xy[0]).dot(-xy[0]) < 0.0 || (xy[2] - xy[3]).dot(-xy[3]) < 0.0 {
+            return None;
+        }
+        let segment = xy[3] - xy[0];
+        let length_squared = segment.length_squared();
+        if length_squared == 0.0 {
+            return None;
+        }
+        let w = ((-xy[0]).dot(segment) / length_squared).clamp(0.0, 1.0);
+        let along = u.0 + (u.1 - u.0) * w;
+        let width = self.width(along);
+        let (point, derivative) = evaluate(cp, w);
+        let distance_squared = point.x * point.x + point.y * point.y;
+        if distance_squared > 0.25 * width * width || !z.surrounds(point.z) {
+            return None;
+        }
+        // Which side of the centre line the ray passes on.
+        let distance = distance_squared.sqrt();
+        let side = derivative.x * -point.y + point.x * derivative.y;
+        let v = if side > 0.0 {
+            0.5 + distance / width
+        } else {
+            0.5 - distance / width
+        };
+        Some(CurveHit {
+            t: point.z,
+            u: along,
+            v,
+        })
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let length = ray.direction.length();
+        if length == 0.0 {
+            return None;
+        }
+        let z_axis = ray.direction / length;
+        let (x_axis, y_axis) = z_axis.any_orthonormal_pair();
+        let cp = self.control_points.map(|p| {
+            let p = p - ray.origin;
+            DVec3::new(p.dot(x_axis), p.dot(y_axis), p.dot(z_axis))
+        });
+
+        // Enough halvings that the pieces bend by less than a twentieth of
+        // the width.
+        let bend = cp
+            .windows(3)
+            .map(|p| (p[0] - 2.0 * p[1] + p[2]).abs().max_element())
+            .fold(0.0, f64::max);
+        let tolerance = 0.05 * self.widths[0].max(self.widths[1]);
+        let depth = if bend > 0.0 && tolerance > 0.0 {
+            (0.5 * (SQRT_2 * 6.0 * bend / (8.0 * tolerance)).log2()).clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let z = Interval::new(interval.min * length, interval.max * length);
+        let found = self.intersect(cp, (0.0, 1.0), z, depth)?;
+        let t = found.t / length;
+        let point = ray.at(t);
+
+        let (center, dpdu) = evaluate(self.control_points, found.u);
+        let tangent = dpdu.normalize_or_zero();
+        let facing = -z_axis - tangent * (-z_axis).dot(tangent);
+        let facing = if facing.length_squared() > 1e-12 {
+            facing.normalize()
+        } else {
+            tangent.any_orthonormal_vector()
+        };
+        let across = tangent.cross(facing);
+        let width = self.width(found.u);
+        let normal = match self.shape {
+            CurveShape::Flat => facing,
+            CurveShape::Round => {
+                let s = ((point - center).dot(across) / (0.5 * width)).clamp(-1.0, 1.0);
+                (s * across + (1.0 - s * s).sqrt() * facing).normalize()
+            }
+        };
+
+        let mut rec = HitRecord {
+            point,
+            normal,
+            material: &*self.material,
+            t,
+            u: found.u,
+            v: found.v,
+            front_face: true,
+            object_id: 0,
+            // The ribbon turns to face every ray, so rays leaving it start
+            // a width along to keep from finding it again.
+            epsilon: width,
+            tangent,
+            dpdu,
+            dpdv: width * across,
+            dndu: DVec3::ZERO,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let half_width = DVec3::splat(0.5 * self.widths[0].max(self.widths[1]));
+        let min = self
+            .control_points
+            .iter()
+            .fold(DVec3::INFINITY, |m, &p| m.min(p));
+        let max = self
+            .control_points
+            .iter()
+            .fold(DVec3::NEG_INFINITY, |m, &p| m.max(p));
+        Some(AABB::new(min - half_width, max + half_width))
+    }
+}
+
+// Point and derivative of the Bezier curve through `cp` at `u`.
+fn evaluate(cp: [DVec3; 4], u: f64) -> (DVec3, DVec3) {
+    let lerp = |a: DVec3, b: DVec3| a + (b - a) * u;
+    let [a, b, c] = [lerp(cp[0], cp[1]), lerp(cp[1], cp[2]), lerp(cp[2], cp[3])];
+    let [d, e] = [lerp(a, b), lerp(b, c)];
+    (lerp(d, e), 3.0 * (e - d))
+}
+
+// The two halves of the Bezier curve through `cp`, by de Casteljau.
+fn split(cp: [DVec3; 4]) -> [[DVec3; 4]; 2] {
+    let mid = |a: DVec3, b: DVec3| 0.5 * (a + b);
+    let [a, b, c] = [mid(cp[0], cp[1]), mid(cp[1], cp[2]), mid(cp[2], cp[3])];
+    let [d, e] = [mid(a, b), mid(b, c)];
+    let f = mid(d, e);
+    [[cp[0], a, d, f], [f, e, c, cp[3]]]
+}
+
+// One hair: points along it and its width at each.
+pub struct Strand {
+    pub points: Vec<DVec3>,
+    pub widths: Vec<f64>,
+}
+
+impl Strand {
+    // A smooth curve through the points, one Bezier piece between each
+    // pair with the tangents of a Catmull-Rom spline.
+    pub fn curves(&self, shape: CurveShape, material: Arc<dyn Material>) -> HittableList {
+        let p = &self.points;
+        let last = p.len().saturating_sub(1);
+        let mut curves = HittableList::new();
+        for i in 0..last {
+            let before = p[i.saturating_sub(1)];
+            let after = p[(i + 2).min(last)];
+            let control_points = [
+                p[i],
+                p[i] + (p[i + 1] - before) / 6.0,
+                p[i + 1] - (after - p[i]) / 6.0,
+                p[i + 1],
+            ];
+            curves.push(Arc::new(Curve::new(
+                control_points,
+                [self.widths[i], self.widths[i + 1]],
+                shape,
+                material.clone(),
+            )));
+        }
+        curves
+    }
+}
+
+// Reads the strands of a Cem Yuksel .hair file: a 128-byte header, then
+// arrays of segment counts per strand, points, and thicknesses per point,
+// each left out when the header says to use a default instead. All
+// values are little-endian. Transparency and colour arrays are skipped;
+// the material decides how hair looks.
+pub fn read_hair(path: impl AsRef<Path>) -> Result<Vec<Strand>, RenderError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| RenderError::io(path, e))?;
+    parse_hair(&bytes).map_err(|message| RenderError::mesh(path, message))
+}
+
+const HAS_SEGMENTS: u32 = 1;
+const HAS_POINTS: u32 = 2;
+const HAS_THICKNESS: u32 = 4;
+
+fn parse_hair(bytes: &[u8]) -> Result<Vec<Strand>, String> {
+    if bytes.len() < 128 || &bytes[..4] != b"HAIR" {
+        return Err("not a .hair file".into());
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let f32_at = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let (strand_count, point_count, flags) = (u32_at(4) as usize, u32_at(8) as usize, u32_at(12));
+    let (default_segments, default_thickness) = (u32_at(16) as usize, f32_at(20) as f64);
+    if flags & HAS_POINTS == 0 {
+        return Err("has no points".into());
+    }
+
+    let mut offset = 128;
+    let mut take = |size: usize| {
+        let start = offset;
+        offset += size;
+        if offset > bytes.len() {
+            return Err(format!("is cut short after {} bytes", bytes.len()));
+        }
+        Ok(&bytes[start..offset])
+    };
+    let segments: Vec<usize> = if flags & HAS_SEGMENTS != 0 {
+        take(2 * strand_count)?
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]) as usize)
+            .collect()
+    } else {
+        vec![default_segments; strand_count]
+    };
+    let floats = |data: &[u8]| -> Vec<f64> {
+        data.chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64)
+            .collect()
+    };
+    let coordinates = floats(take(12 * point_count)?);
+    let thickness = if flags & HAS_THICKNESS != 0 {
+        floats(take(4 * point_count)?)
+    } else {
+        vec![default_thickness; point_count]
+    };
+
+    let needed: usize = segments.iter().map(|s| s + 1).sum();
+    if needed > point_count {
+        return Err(format!("strands need {needed} points, found {point_count}"));
+    }
+    let mut strands = Vec::with_capacity(strand_count);
+    let mut first = 0;
+    for count in segments {
+        let range = first..first + count + 1;
+        first = range.end;
+        let points = coordinates[3 * range.start..3 * range.end]
+            .chunks_exact(3)
+            .map(|c| DVec3::new(c[0], c[1], c[2]))
+            .collect();
+        strands.push(Strand {
+            points,
+            widths: thickness[range].to_vec(),
+        });
+    }
+    Ok(strands)
+}
@@ -0,0 +1,141 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::disk::disk_extent;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// A closed cylinder from `start` to `end`. The side's UVs wrap around the
+// axis (u) and run from `start` to `end` (v); the caps use disk UVs.
+pub struct Cylinder {
+    pub start: DVec3,
+    pub end: DVec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+#[derive(Clone, Copy)]
+enum Part {
+    Side,
+    Base,
+    Top,
+}
+
+impl Cylinder {
+    pub fn new(start: DVec3, end: DVec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            start,
+            end,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let axis = self.end - self.start;
+        let height = axis.length();
+        if height == 0.0 || self.radius <= 0.0 {
+            return None;
+        }
+        let n = axis / height;
+        let (tangent, bitangent) = n.any_orthonormal_pair();
+        // Local frame with the axis along y.
+        let local = |v: DVec3| DVec3::new(v.dot(tangent), v.dot(n), v.dot(bitangent));
+        let o = local(ray.origin - self.start);
+        let d = local(ray.direction);
+        let r2 = self.radius * self.radius;
+
+        let mut candidates: Vec<(f64, Part)> = Vec::with_capacity(4);
+        let a = d.x * d.x + d.z * d.z;
+        if a > 0.0 {
+            let half_b = o.x * d.x + o.z * d.z;
+            let c = o.x * o.x + o.z * o.z - r2;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                    if (0.0..=height).contains(&(o.y + t * d.y)) {
+                        candidates.push((t, Part::Side));
+                    }
+                }
+            }
+        }
+        if d.y != 0.0 {
+            for (y, part) in [(0.0, Part::Base), (height, Part::Top)] {
+                let t = (y - o.y) / d.y;
+                let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+                if x * x + z * z <= r2 {
+                    candidates.push((t, part));
+                }
+            }
+        }
+
+        let (t, part) = candidates
+            .into_iter()
+            .filter(|&(t, _)| interval.surrounds(t))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        let p = o + t * d;
+        let (outward_normal, u, v, surface_tangent) = match part {
+            Part::Side => {
+                let phi = p.z.atan2(p.x) + PI;
+                let normal = (p.x * tangent + p.z * bitangent) / self.radius;
+                let around = (-p.z * tangent + p.x * bitangent) / self.radius;
+                (normal, phi / (2.0 * PI), p.y / height, around)
+            }
+            Part::Base | Part::Top => {
+                let normal = if matches!(part, Part::Top) { n } else { -n };
+                let u = 0.5 * (p.x / self.radius + 1.0);
+                let v = 0.5 * (p.z / self.radius + 1.0);
+                (normal, u, v, tangent)
+            }
+        };
+        let [dpdu, dpdv, dndu] = match part {
+            Part::Side => [
+                2.0 * PI * self.radius * surface_tangent,
+                height * n,
+                2.0 * PI * surface_tangent,
+            ],
+            Part::Base | Part::Top => [
+                2.0 * self.radius * tangent,
+                2.0 * self.radius * bitangent,
+                DVec3::ZERO,
+            ],
+        };
+
+        let mut rec = HitRecord {
+            point: ray.at(t),
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent: surface_tangent,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let n = (self.end - self.start).normalize_or_zero();
+        let extent = disk_extent(n, self.radius);
+        Some(AABB::new(
+            self.start.min(self.end) - extent,
+            self.start.max(self.end) + extent,
+        ))
+    }
+}
@@ -0,0 +1,77 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::sync::Arc;
+
+// A flat circle facing `normal`; rays hit it from either side. UVs map the
+// square around the disk to [0, 1]^2.
+pub struct Disk {
+    pub center: DVec3,
+    pub normal: DVec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: DVec3, normal: DVec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let denominator = ray.direction.dot(self.normal);
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = (self.center - ray.origin).dot(self.normal) / denominator;
+        if !interval.surrounds(t) {
+            return None;
+        }
+        let point = ray.at(t);
+        let offset = point - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        let (tangent, bitangent) = self.normal.any_orthonormal_pair();
+        let mut rec = HitRecord {
+            point,
+            normal: self.normal,
+            material: &*self.material,
+            t,
+            u: 0.5 * (offset.dot(tangent) / self.radius + 1.0),
+            v: 0.5 * (offset.dot(bitangent) / self.radius + 1.0),
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent,
+            dpdu: 2.0 * self.radius * tangent,
+            dpdv: 2.0 * self.radius * bitangent,
+            dndu: DVec3::ZERO,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let extent = disk_extent(self.normal, self.radius);
+        Some(AABB::new(self.center - extent, self.center + extent).padded(2e-4))
+    }
+}
+
+// Half-size of the bounding box of a disk with unit `normal`.
+pub(crate) fn disk_extent(normal: DVec3, radius: f64) -> DVec3 {
+    radius * (DVec3::ONE - normal * normal).max(DVec3::ZERO).sqrt()
+}
@@ -0,0 +1,119 @@
+use crate::hittable::{HitRecord, Hittable, HittableList, AABB};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::sync::Arc;
+
+// Slab normals of a 14-DOP: the three axes of a box and the four diagonals
+// of a cube, which cut the corners boxes leave around diagonal triangles
+// and strands.
+const AXES: [DVec3; 7] = [
+    DVec3::X,
+    DVec3::Y,
+    DVec3::Z,
+    DVec3::new(1.0, 1.0, 1.0),
+    DVec3::new(1.0, 1.0, -1.0),
+    DVec3::new(1.0, -1.0, 1.0),
+    DVec3::new(-1.0, 1.0, 1.0),
+];
+
+// Discrete oriented polytope bounding an object between a pair of planes
+// along each of `AXES`.
+#[derive(Clone, Copy, Debug)]
+pub struct Dop14 {
+    pub min: [f64; 7],
+    pub max: [f64; 7],
+}
+
+impl Dop14 {
+    // Bounds of `object` from its extents, or `None` if it is unbounded.
+    // Slabs are widened slightly so that rounding in `hit` never culls a
+    // ray the object itself would report.
+    pub fn of(object: &dyn Hittable) -> Option<Self> {
+        let mut dop = Self {
+            min: [0.0; 7],
+            max: [0.0; 7],
+        };
+        for (i, axis) in AXES.iter().enumerate() {
+            let (min, max) = object.extent(*axis)?;
+            if !(min.is_finite() && max.is_finite()) {
+                return None;
+            }
+            let padding = 1e-9 * min.abs().max(max.abs()) + 1e-12;
+            dop.min[i] = min - padding;
+            dop.max[i] = max + padding;
+        }
+        Some(dop)
+    }
+
+    // Whether `ray` passes through the polytope within `interval`.
+    pub fn hit(&self, ray: &Ray, interval: Interval) -> bool {
+        let mut t_min = interval.min;
+        let mut t_max = interval.max;
+        for (i, axis) in AXES.iter().enumerate() {
+            let from = axis.dot(ray.origin);
+            let inv_d = 1.0 / axis.dot(ray.direction);
+            let mut t0 = (self.min[i] - from) * inv_d;
+            let mut t1 = (self.max[i] - from) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // `max` and `min` skip the NaN of a ray grazing a slab plane.
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Box of the three axis-aligned slabs.
+    pub fn bounding_box(&self) -> AABB {
+        AABB::new(
+            DVec3::new(self.min[0], self.min[1], self.min[2]),
+            DVec3::new(self.max[0], self.max[1], self.max[2]),
+        )
+    }
+}
+
+// `object` behind a 14-DOP, which rays must pass through before the object
+// itself is intersected. Hierarchies see the box of its axis-aligned
+// slabs, which for rotated objects is tighter than their own.
+pub struct DopBounded {
+    pub object: Arc<dyn Hittable>,
+    pub dop: Dop14,
+}
+
+impl Hittable for DopBounded {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        if !self.dop.hit(ray, interval) {
+            return None;
+        }
+        self.object.hit(ray, interval)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        self.dop.hit(ray, interval) && self.object.hit_any(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(self.dop.bounding_box())
+    }
+
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        self.object.extent(axis)
+    }
+}
+
+// Puts each bounded object of `objects` behind its 14-DOP, for the leaves
+// of a hierarchy; unbounded objects are kept as they are.
+pub fn tighten(objects: HittableList) -> HittableList {
+    objects
+        .into_iter()
+        .map(|object| match Dop14::of(&*object) {
+            Some(dop) => Arc::new(DopBounded { object, dop }) as Arc<dyn Hittable>,
+            None => object,
+        })
+        .collect()
+}
@@ -0,0 +1,280 @@
+use crate::error::RenderError;
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::raymarch::slab_interval;
+use crate::objects::triangle::uv_derivatives;
+use crate::ray::Ray;
+use glam::{DVec2, DVec3};
+use std::sync::Arc;
+
+// Terrain from a grid of height samples, centred on the origin in the XZ
+// plane with heights along +Y. Each grid cell holds two triangles with
+// smooth normals. Rays walk the cells they cross with a 2D DDA, skipping
+// cells whose height range they pass above or below, so the cost grows with
+// the grid's side rather than its area.
+pub struct Heightfield {
+    // Samples per row and number of rows; both at least 2.
+    columns: usize,
+    rows: usize,
+    // World-space heights, row by row from -Z to +Z.
+    heights: Vec<f64>,
+    normals: Vec<DVec3>,
+    // Lowest and highest corner of each cell.
+    cell_ranges: Vec<(f64, f64)>,
+    // Extent along X and Z.
+    size: DVec2,
+    bounds: AABB,
+    pub material: Arc<dyn Material>,
+}
+
+impl Heightfield {
+    // `samples` are `columns * rows` values in [0, 1], scaled by `height`.
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        samples: &[f64],
+        size: DVec2,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(
+            columns >= 2 && rows >= 2 && samples.len() == columns * rows,
+            "a heightfield needs at least 2x2 samples"
+        );
+        let heights: Vec<f64> = samples.iter().map(|s| s * height).collect();
+        let spacing = size / DVec2::new((columns - 1) as f64, (rows - 1) as f64);
+        let at = |i: usize, j: usize| heights[j * columns + i];
+
+        let mut normals = Vec::with_capacity(heights.len());
+        for j in 0..rows {
+            for i in 0..columns {
+                let (left, right) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (back, front) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let slope_x = (at(right, j) - at(left, j)) / ((right - left) as f64 * spacing.x);
+                let slope_z = (at(i, front) - at(i, back)) / ((front - back) as f64 * spacing.y);
+                normals.push(DVec3::new(-slope_x, 1.0, -slope_z).normalize());
+            }
+        }
+
+        let mut cell_ranges = Vec::with_capacity((columns - 1) * (rows - 1));
+        for j in 0..rows - 1 {
+            for i in 0..columns - 1 {
+                let corners = [at(i, j), at(i + 1, j), at(i, j + 1), at(i + 1, j + 1)];
+                let low = corners.iter().copied().fold(f64::INFINITY, f64::min);
+                let high = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                cell_ranges.push((low, high));
+            }
+        }
+
+        let low = heights.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = heights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let padding = 1e-4;
+        let bounds = AABB::new(
+            DVec3::new(-0.5 * size.x, low - padding, -0.5 * size.y),
+            DVec3::new(0.5 * size.x, high + padding, 0.5 * size.y),
+        );
+
+        Self {
+            columns,
+            rows,
+            heights,
+            normals,
+            cell_ranges,
+            size,
+            bounds,
+            material,
+        }
+    }
+
+    // Reads a grayscale image, one sample per pixel, with black at height 0
+    // and white at `height`. The top row of the image is the -Z edge.
+    pub fn from_image(
+        path: &str,
+        size: DVec2,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, RenderError> {
+        let image = image::open(path)
+            .map_err(|e| RenderError::image(path, e))?
+            .into_luma16();
+        let (columns, rows) = (image.width() as usize, image.height() as usize);
+        if columns < 2 || rows < 2 {
+            return Err(RenderError::mesh(path, "needs at least 2x2 pixels"));
+        }
+        let samples: Vec<f64> = image.pixels().map(|p| p[0] as f64 / 65535.0).collect();
+        Ok(Self::new(columns, rows, &samples, size, height, material))
+    }
+
+    fn spacing(&self) -> DVec2 {
+        self.size / DVec2::new((self.columns - 1) as f64, (self.rows - 1) as f64)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> DVec3 {
+        let xz = self.spacing() * DVec2::new(i as f64, j as f64) - 0.5 * self.size;
+        DVec3::new(xz.x, self.heights[j * self.columns + i], xz.y)
+    }
+
+    // Nearest hit on the two triangles of cell (i, j).
+    fn hit_cell(&self, i: usize, j: usize, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        // Corners as (column, row), wound so the geometric normal faces +Y.
+        let triangles = [
+            [(i, j), (i, j + 1), (i + 1, j)],
+            [(i + 1, j), (i, j + 1), (i + 1, j + 1)],
+        ];
+        let mut nearest: Option<(f64, [(usize, usize); 3], f64, f64)> = None;
+        for corners in triangles {
+            let [p0, p1, p2] = corners.map(|(i, j)| self.vertex(i, j));
+            let limit = nearest.map_or(interval.max, |n| n.0);
+            if let Some((t, b1, b2)) = intersect_triangle(ray, p0, p1, p2, interval.with_max(limit))
+            {
+                nearest = Some((t, corners, b1, b2));
+            }
+        }
+        let (t, corners, b1, b2) = nearest?;
+
+        let b0 = 1.0 - b1 - b2;
+        let [n0, n1, n2] = corners.map(|(i, j)| self.normals[j * self.columns + i]);
+        let outward_normal = (b0 * n0 + b1 * n1 + b2 * n2).normalize();
+        let point = ray.at(t);
+        let grid = (DVec2::new(point.x, point.z) + 0.5 * self.size) / self.size;
+        let last = DVec2::new((self.columns - 1) as f64, (self.rows - 1) as f64);
+        let [dpdu, dpdv, dndu, dndv] = uv_derivatives(
+            corners.map(|(i, j)| self.vertex(i, j)),
+            corners.map(|(i, j)| DVec2::new(i as f64 / last.x, 1.0 - j as f64 / last.y)),
+            Some([n0, n1, n2]),
+            (b1, b2),
+        );
+        let mut rec = HitRecord {
+            point,
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: grid.x.clamp(0.0, 1.0),
+            v: 1.0 - grid.y.clamp(0.0, 1.0),
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent: (DVec3::X - outward_normal.x * outward_normal).normalize_or_zero(),
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+}
+
+// Möller-Trumbore; returns the distance and the barycentrics of p1 and p2.
+fn intersect_triangle(
+    ray: &Ray,
+    p0: DVec3,
+    p1: DVec3,
+    p2: DVec3,
+    interval: Interval,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = ray.direction.cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin - p0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.direction.dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inv_det;
+    interval.surrounds(t).then_some((t, b1, b2))
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let (enter, exit) = slab_interval(&self.bounds, ray)?;
+        let t_start = enter.max(interval.min);
+        let t_end = exit.min(interval.max);
+        if t_start > t_end {
+            return None;
+        }
+
+        let spacing = self.spacing();
+        let last = (self.columns as isize - 2, self.rows as isize - 2);
+        let start = ray.at(t_start);
+        let grid = (DVec2::new(start.x, start.z) + 0.5 * self.size) / spacing;
+        let mut cell = (
+            (grid.x.floor() as isize).clamp(0, last.0),
+            (grid.y.floor() as isize).clamp(0, last.1),
+        );
+
+        // Per axis: cell step, ray distance between grid lines, and ray
+        // distance to the next grid line.
+        let axis = |direction: f64, origin: f64, index: isize, spacing: f64, half: f64| {
+            if direction == 0.0 {
+                return (0, f64::INFINITY, f64::INFINITY);
+            }
+            let step = if direction > 0.0 { 1 } else { -1 };
+            let line = (index + (step > 0) as isize) as f64 * spacing - half;
+            (step, spacing / direction.abs(), (line - origin) / direction)
+        };
+        let (step_x, delta_x, mut next_x) = axis(
+            ray.direction.x,
+            ray.origin.x,
+            cell.0,
+            spacing.x,
+            0.5 * self.size.x,
+        );
+        let (step_z, delta_z, mut next_z) = axis(
+            ray.direction.z,
+            ray.origin.z,
+            cell.1,
+            spacing.y,
+            0.5 * self.size.y,
+        );
+
+        let mut t = t_start;
+        loop {
+            let cell_exit = next_x.min(next_z).min(t_end);
+            let index = cell.1 as usize * (self.columns - 1) + cell.0 as usize;
+            let (low, high) = self.cell_ranges[index];
+            let (y0, y1) = (ray.at(t).y, ray.at(cell_exit).y);
+            // Any hit in a cell is the nearest, since cells are visited in
+            // order along the ray.
+            if y0.min(y1) <= high && y0.max(y1) >= low {
+                let hit = self.hit_cell(cell.0 as usize, cell.1 as usize, ray, interval);
+                if hit.is_some() {
+                    return hit;
+                }
+            }
+            if cell_exit >= t_end {
+                return None;
+            }
+            if next_x < next_z {
+                cell.0 += step_x;
+                t = next_x;
+                next_x += delta_x;
+            } else {
+                cell.1 += step_z;
+                t = next_z;
+                next_z += delta_z;
+            }
+            if cell.0 < 0 || cell.0 > last.0 || cell.1 < 0 || cell.1 > last.1 {
+                return None;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(self.bounds)
+    }
+}
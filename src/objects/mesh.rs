@@ -0,0 +1,190 @@
+use crate::hittable::{blocks, HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::triangle::{self, Triangle};
+use crate::qbvh::{BvhBuildStrategy, Hierarchy};
+use crate::ray::Ray;
+use crate::real::{from_real, to_real, Vec3};
+use glam::{DVec2, DVec3};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+// A triangle mesh in flat buffers: vertices shared between faces, faces
+// that index them, and a four-wide BVH over the faces by their position.
+// Big meshes take a handful of allocations rather than one per triangle,
+// and rays walk contiguous memory instead of following an `Arc` to every
+// triangle and BVH node; only the mesh as a whole is a `Hittable`.
+pub struct Mesh {
+    positions: Vec<Vec3>,
+    // One per position, or none when no face is smooth-shaded.
+    normals: Vec<Vec3>,
+    uvs: Vec<DVec2>,
+    // One per position, white where a face has none, or none when no face
+    // has vertex colours.
+    colors: Vec<Vec3>,
+    // In the order the hierarchy's leaves refer to them by.
+    faces: Vec<Face>,
+    materials: Vec<Arc<dyn Material>>,
+    hierarchy: Hierarchy,
+    bounds: Option<AABB>,
+}
+
+struct Face {
+    corners: [u32; 3],
+    material: u32,
+    // Interpolates the vertex normals; otherwise shaded with its true one.
+    smooth: bool,
+}
+
+impl Mesh {
+    // Packs `triangles` into the mesh's buffers, merging the corners that
+    // have the same position, normal, UV and colour and the materials that
+    // are shared.
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut colors = Vec::new();
+        let mut materials: Vec<Arc<dyn Material>> = Vec::new();
+        let mut vertex_indices = HashMap::new();
+        let mut material_indices = HashMap::new();
+        let mut faces = Vec::with_capacity(triangles.len());
+        let mut bounds = Vec::with_capacity(triangles.len());
+
+        for triangle in &triangles {
+            let (vertices, vertex_normals) = (triangle.vertices(), triangle.normals());
+            let vertex_colors = triangle.colors();
+            let corners = [0, 1, 2].map(|i| {
+                let (position, uv) = (vertices[i], triangle.uvs[i]);
+                let normal = vertex_normals.map_or(DVec3::ZERO, |n| n[i]);
+                let color = vertex_colors.map_or(DVec3::ONE, |c| c[i]);
+                let (p, n, c) = (position, normal, color);
+                let key =
+                    [p.x, p.y, p.z, n.x, n.y, n.z, uv.x, uv.y, c.x, c.y, c.z].map(f64::to_bits);
+                *vertex_indices.entry(key).or_insert_with(|| {
+                    positions.push(to_real(position));
+                    normals.push(to_real(normal));
+                    uvs.push(uv);
+                    colors.push(to_real(color));
+                    (positions.len() - 1) as u32
+                })
+            });
+            let material = *material_indices
+                .entry(Arc::as_ptr(&triangle.material) as *const () as usize)
+                .or_insert_with(|| {
+                    materials.push(triangle.material.clone());
+                    (materials.len() - 1) as u32
+                });
+            faces.push(Face {
+                corners,
+                material,
+                smooth: vertex_normals.is_some(),
+            });
+            bounds.extend(triangle.bounding_box());
+        }
+        if !faces.iter().any(|face| face.smooth) {
+            normals = Vec::new();
+        }
+        if !triangles.iter().any(|triangle| triangle.colors().is_some()) {
+            colors = Vec::new();
+        }
+
+        let (hierarchy, total, order) = Hierarchy::build(&bounds, BvhBuildStrategy::default());
+        let mut faces: Vec<Option<Face>> = faces.into_iter().map(Some).collect();
+        let faces = order.into_iter().filter_map(|i| faces[i].take()).collect();
+        Self {
+            positions,
+            normals,
+            uvs,
+            colors,
+            faces,
+            materials,
+            hierarchy,
+            bounds: total,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    // Whether shadow rays may pass through any of its faces.
+    pub fn is_see_through(&self) -> bool {
+        self.materials.iter().any(|m| m.is_see_through())
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    // The vertex positions, which `face_corners` index.
+    pub fn positions(&self) -> impl Iterator<Item = DVec3> + '_ {
+        self.positions.iter().map(|&p| from_real(p))
+    }
+
+    pub fn face_corners(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.faces.iter().map(|face| face.corners)
+    }
+
+    // The record of a hit on face `index` found by `triangle::intersect`.
+    fn surface(&self, ray: &Ray, index: usize, hit: (f64, (f64, f64))) -> HitRecord<'_> {
+        let face = &self.faces[index];
+        let corners = |buffer: &[Vec3]| face.corners.map(|i| from_real(buffer[i as usize]));
+        triangle::surface(
+            ray,
+            hit,
+            corners(&self.positions),
+            face.smooth.then(|| corners(&self.normals)),
+            face.corners.map(|i| self.uvs[i as usize]),
+            (!self.colors.is_empty()).then(|| corners(&self.colors)),
+            &*self.materials[face.material as usize],
+        )
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let (origin, direction) = (to_real(ray.origin), to_real(ray.direction));
+        // Only the closest face in a leaf gets a hit record.
+        let leaf = |faces: Range<usize>, interval: Interval| {
+            let mut closest = None;
+            let mut end = interval.max;
+            for index in faces {
+                let vertices = self.faces[index]
+                    .corners
+                    .map(|i| self.positions[i as usize]);
+                let range = interval.with_max(end);
+                if let Some(hit) = triangle::intersect(&vertices, origin, direction, range) {
+                    end = hit.0;
+                    closest = Some((index, hit));
+                }
+            }
+            closest.map(|(index, hit)| self.surface(ray, index, hit))
+        };
+        self.hierarchy.hit(ray, interval, leaf)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        let (origin, direction) = (to_real(ray.origin), to_real(ray.direction));
+        self.hierarchy.hit_any(ray, interval, |faces, interval| {
+            faces.into_iter().any(|index| {
+                let face = &self.faces[index];
+                let vertices = face.corners.map(|i| self.positions[i as usize]);
+                let Some(hit) = triangle::intersect(&vertices, origin, direction, interval) else {
+                    return false;
+                };
+                !self.materials[face.material as usize].is_see_through()
+                    || blocks(&self.surface(ray, index, hit))
+            })
+        })
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.bounds
+    }
+
+    fn as_mesh(&self) -> Option<&Mesh> {
+        Some(self)
+    }
+}
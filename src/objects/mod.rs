@@ -0,0 +1,26 @@
+pub mod capsule;
+pub mod cleanup;
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod dop;
+pub mod heightfield;
+pub mod mesh;
+pub mod motion;
+pub mod obj;
+pub mod ply;
+pub mod point_cloud;
+pub mod quadric;
+pub mod raymarch;
+pub mod sdf;
+pub mod sphere;
+pub mod stl;
+pub mod subdivision;
+pub mod tagged;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
+pub mod visibility;
@@ -0,0 +1,113 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::objects::transform::{hit_transformed, to_local, transform_box};
+use crate::ray::Ray;
+use glam::{DAffine3, DQuat, DVec3};
+use std::sync::Arc;
+
+// Sub-steps per motion segment used to bound the swept volume; rotation
+// between two keys can carry corners outside both key boxes.
+const BOUND_STEPS: usize = 16;
+
+// An object moving through N transform keys spread evenly over the shutter
+// interval. Two keys give plain linear motion blur; more keys follow curved
+// or fast rotating motion such as propellers.
+pub struct MotionTransformed {
+    pub object: Arc<dyn Hittable>,
+    keys: Vec<MotionKey>,
+    bbox: Option<AABB>,
+}
+
+#[derive(Clone, Copy)]
+struct MotionKey {
+    scale: DVec3,
+    rotation: DQuat,
+    translation: DVec3,
+}
+
+impl MotionTransformed {
+    pub fn new(object: Arc<dyn Hittable>, transforms: &[DAffine3]) -> Self {
+        assert!(
+            !transforms.is_empty(),
+            "motion needs at least one transform"
+        );
+        let keys = transforms
+            .iter()
+            .map(|t| {
+                let (scale, rotation, translation) = t.to_scale_rotation_translation();
+                MotionKey {
+                    scale,
+                    rotation,
+                    translation,
+                }
+            })
+            .collect();
+        let mut motion = Self {
+            object,
+            keys,
+            bbox: None,
+        };
+        motion.bbox = motion.swept_bounds();
+        motion
+    }
+
+    pub fn transform_at(&self, time: f64) -> DAffine3 {
+        let segments = self.keys.len() - 1;
+        if segments == 0 {
+            return self.keys[0].to_affine();
+        }
+        let position = time.clamp(0.0, 1.0) * segments as f64;
+        let i = (position as usize).min(segments - 1);
+        let f = position - i as f64;
+        let (a, b) = (self.keys[i], self.keys[i + 1]);
+        DAffine3::from_scale_rotation_translation(
+            a.scale.lerp(b.scale, f),
+            a.rotation.slerp(b.rotation, f),
+            a.translation.lerp(b.translation, f),
+        )
+    }
+
+    fn swept_bounds(&self) -> Option<AABB> {
+        let local = self.object.bounding_box()?;
+        let steps = (self.keys.len() - 1) * BOUND_STEPS;
+        let mut bbox = transform_box(&local, &self.transform_at(0.0));
+        for step in 1..=steps {
+            let time = step as f64 / steps as f64;
+            bbox = AABB::surrounding_box(bbox, transform_box(&local, &self.transform_at(time)));
+        }
+        // Cover the chord error between sub-steps.
+        let pad = 0.01 * (bbox.max - bbox.min).length();
+        Some(AABB::new(
+            bbox.min - DVec3::splat(pad),
+            bbox.max + DVec3::splat(pad),
+        ))
+    }
+}
+
+impl MotionKey {
+    fn to_affine(self) -> DAffine3 {
+        DAffine3::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Hittable for MotionTransformed {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let transform = self.transform_at(ray.time);
+        hit_transformed(
+            &*self.object,
+            &transform,
+            &transform.inverse(),
+            ray,
+            interval,
+        )
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        let inverse = self.transform_at(ray.time).inverse();
+        self.object.hit_any(&to_local(&inverse, ray), interval)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.bbox
+    }
+}
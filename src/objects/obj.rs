@@ -0,0 +1,273 @@
+use crate::color_space::{ColorSpace, DecodedTexture};
+use crate::error::RenderError;
+use crate::hittable::{Hittable, HittableList};
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::objects::cleanup::{self, CleanupStats, MeshData};
+use crate::objects::ply;
+use crate::objects::stl;
+use crate::objects::subdivision;
+use crate::objects::triangle::Triangle;
+use crate::texture::{ImageTexture, SolidColor, Texture};
+use glam::{DMat3, DVec2, DVec3};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Shading {
+    // With the true normal of each face, ignoring vertex normals.
+    #[serde(rename = "flat")]
+    Flat,
+    // With normals interpolated from the vertex normals, where there are
+    // any.
+    #[default]
+    #[serde(rename = "smooth")]
+    Smooth,
+}
+
+// How `load_with` reads an OBJ file.
+#[derive(Clone, Default)]
+pub struct ObjOptions {
+    // Turns closed objects to face outwards with `cleanup::orient_outward`.
+    pub orient_outward: bool,
+    pub shading: Shading,
+    // Gives objects without vertex normals some with
+    // `cleanup::generate_normals`, so that they shade smoothly too.
+    pub generate_normals: bool,
+    // Levels of `subdivision::subdivide`, each splitting every triangle in
+    // four. Subdivided meshes lose any PLY vertex colours.
+    pub subdivision: u32,
+    pub displacement: Option<Displacement>,
+    // Applied to the file's positions before anything else, to bring them
+    // from the file's units and axes to the scene's; normals are turned
+    // with them.
+    pub conversion: Option<DMat3>,
+    // Gives every triangle the fallback material instead of those of the
+    // file's MTL library, which is then not read at all.
+    pub ignore_mtl: bool,
+}
+
+// Detail added to a mesh after it is subdivided, with
+// `subdivision::displace`.
+#[derive(Clone)]
+pub struct Displacement {
+    // Grey levels, in distances along the normal before `scale`.
+    pub height: Arc<dyn Texture>,
+    pub scale: f64,
+}
+
+pub fn load_with_materials(
+    path: &str,
+    fallback: Arc<dyn Material>,
+) -> Result<HittableList, RenderError> {
+    let triangles = load_triangles(path, fallback)?;
+    Ok(triangles
+        .into_iter()
+        .map(|t| Arc::new(t) as Arc<dyn Hittable>)
+        .collect())
+}
+
+pub fn load_triangles(
+    path: &str,
+    fallback: Arc<dyn Material>,
+) -> Result<Vec<Triangle>, RenderError> {
+    Ok(load_cleaned(path, fallback)?.0)
+}
+
+// Loads the triangles of an OBJ file after running each of its objects
+// through `cleanup::clean`, and reports what that changed.
+pub fn load_cleaned(
+    path: &str,
+    fallback: Arc<dyn Material>,
+) -> Result<(Vec<Triangle>, CleanupStats), RenderError> {
+    load_with(path, fallback, &ObjOptions::default())
+}
+
+// Like `load_cleaned`, also turning closed objects to face outwards with
+// `cleanup::orient_outward`.
+pub fn load_outward(
+    path: &str,
+    fallback: Arc<dyn Material>,
+) -> Result<(Vec<Triangle>, CleanupStats), RenderError> {
+    let options = ObjOptions {
+        orient_outward: true,
+        ..ObjOptions::default()
+    };
+    load_with(path, fallback, &options)
+}
+
+// Like `load_cleaned`, with the repairs and shading of `options`. STL and
+// PLY files are read as well, told apart by their extension; the vertex
+// colours of PLY files tint the triangles' materials.
+pub fn load_with(
+    path: &str,
+    fallback: Arc<dyn Material>,
+    options: &ObjOptions,
+) -> Result<(Vec<Triangle>, CleanupStats), RenderError> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str());
+    let mut triangles = Vec::new();
+    let stats = match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("stl") => push_triangles(&mut triangles, stl::read(path)?, fallback, options),
+        Some("ply") => push_triangles(&mut triangles, ply::read(path)?, fallback, options),
+        _ => return load_obj(path, fallback, options),
+    };
+    Ok((triangles, stats))
+}
+
+fn load_obj(
+    path: &str,
+    fallback: Arc<dyn Material>,
+    options: &ObjOptions,
+) -> Result<(Vec<Triangle>, CleanupStats), RenderError> {
+    let load_options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+    // tobj reports a file it can't open without saying why.
+    std::fs::metadata(path).map_err(|e| RenderError::io(path, e))?;
+    let (models, mtl_result) =
+        tobj::load_obj(path, &load_options).map_err(|e| RenderError::mesh(path, e))?;
+
+    let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    // A library the file names but that can't be read is an error, rather
+    // than the fallback quietly standing in for all of its materials.
+    let materials: Vec<Arc<dyn Material>> = match mtl_result {
+        _ if options.ignore_mtl => Vec::new(),
+        Ok(mtl) => mtl
+            .iter()
+            .map(|m| convert_material(m, base_dir))
+            .collect::<Result<_, _>>()?,
+        Err(e) => {
+            let error = format!("couldn't read its MTL library: {e}");
+            return Err(RenderError::mesh(path, error));
+        }
+    };
+
+    let mut triangles = Vec::new();
+    let mut stats = CleanupStats::default();
+    for model in &models {
+        let mesh = &model.mesh;
+        let material = mesh
+            .material_id
+            .and_then(|id| materials.get(id).cloned())
+            .unwrap_or_else(|| fallback.clone());
+
+        let vec3s = |values: &[f32]| {
+            values
+                .chunks_exact(3)
+                .map(|v| DVec3::new(v[0] as f64, v[1] as f64, v[2] as f64))
+                .collect()
+        };
+        let data = MeshData {
+            positions: vec3s(&mesh.positions),
+            normals: vec3s(&mesh.normals),
+            texcoords: mesh
+                .texcoords
+                .chunks_exact(2)
+                .map(|t| DVec2::new(t[0] as f64, t[1] as f64))
+                .collect(),
+            faces: mesh
+                .indices
+                .chunks_exact(3)
+                .map(|face| [face[0] as usize, face[1] as usize, face[2] as usize])
+                .collect(),
+            colors: Vec::new(),
+        };
+        stats += push_triangles(&mut triangles, data, material, options);
+    }
+
+    Ok((triangles, stats))
+}
+
+// Repairs `data`, refines it as `options` ask, and adds its faces to
+// `triangles` with `material`, tinted by their corners' colours when the
+// mesh has them.
+fn push_triangles(
+    triangles: &mut Vec<Triangle>,
+    mut data: MeshData,
+    material: Arc<dyn Material>,
+    options: &ObjOptions,
+) -> CleanupStats {
+    if let Some(conversion) = options.conversion {
+        let normal_matrix = conversion.inverse().transpose();
+        for position in &mut data.positions {
+            *position = conversion * *position;
+        }
+        for normal in &mut data.normals {
+            *normal = (normal_matrix * *normal).normalize_or_zero();
+        }
+    }
+    let mut stats = cleanup::clean(&mut data);
+    if options.orient_outward {
+        stats.flipped_triangles += cleanup::orient_outward(&mut data);
+    }
+    subdivision::subdivide(&mut data, options.subdivision);
+    if let Some(displacement) = &options.displacement {
+        subdivision::displace(&mut data, &*displacement.height, displacement.scale);
+    }
+    if options.generate_normals {
+        cleanup::generate_normals(&mut data);
+    }
+    let smooth = options.shading == Shading::Smooth && !data.normals.is_empty();
+    let colored = !data.colors.is_empty();
+
+    for idx in &data.faces {
+        let mut triangle = Triangle::new(idx.map(|i| data.positions[i]), material.clone());
+        if smooth {
+            triangle = triangle.with_normals(idx.map(|i| data.normals[i]));
+        }
+        if colored {
+            triangle = triangle.with_colors(idx.map(|i| data.colors[i]));
+        }
+        if !data.texcoords.is_empty() {
+            triangle = triangle.with_uvs(idx.map(|i| data.texcoords[i]));
+        }
+        triangles.push(triangle);
+    }
+    stats
+}
+
+// Maps MTL illumination models onto the closest built-in material:
+// transparent/refractive entries become Dielectric, mirror-like entries
+// become Metal and everything else falls back to Lambertian. Fails when
+// the diffuse texture can't be read.
+fn convert_material(
+    mtl: &tobj::Material,
+    base_dir: &Path,
+) -> Result<Arc<dyn Material>, RenderError> {
+    let illum = mtl.illumination_model.unwrap_or(2);
+    let dissolve = mtl.dissolve.unwrap_or(1.0);
+
+    if matches!(illum, 4 | 6 | 7 | 9) || dissolve < 1.0 {
+        let ior = mtl.optical_density.unwrap_or(1.5) as f64;
+        return Ok(Arc::new(Dielectric::new(ior)));
+    }
+
+    if matches!(illum, 3 | 5 | 8) {
+        let specular = mtl.specular.map(to_dvec3).unwrap_or(DVec3::ONE);
+        let shininess = mtl.shininess.unwrap_or(1000.0) as f64;
+        let fuzz = 1.0 - (shininess / 1000.0).clamp(0.0, 1.0).sqrt();
+        let albedo = Arc::new(SolidColor::new(specular));
+        return Ok(Arc::new(Metal::new(albedo, fuzz)));
+    }
+
+    let albedo: Arc<dyn Texture> = match &mtl.diffuse_texture {
+        Some(texture) => {
+            let path = base_dir.join(texture);
+            // `ImageTexture` can't report a file it fails to read, and hands
+            // out its code values undecoded; diffuse maps are sRGB.
+            image::image_dimensions(&path).map_err(|e| RenderError::image(&path, e))?;
+            let texture = Arc::new(ImageTexture::new(path.to_string_lossy().as_ref()));
+            Arc::new(DecodedTexture::new(texture, ColorSpace::Srgb))
+        }
+        None => Arc::new(SolidColor::new(
+            mtl.diffuse.map(to_dvec3).unwrap_or(DVec3::splat(0.8)),
+        )),
+    };
+    Ok(Arc::new(Lambertian::new(albedo)))
+}
+
+fn to_dvec3(c: [f32; 3]) -> DVec3 {
+    DVec3::new(c[0] as f64, c[1] as f64, c[2] as f64)
+}
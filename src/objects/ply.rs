@@ -0,0 +1,322 @@
+use crate::color::srgb_to_linear;
+use crate::error::RenderError;
+use crate::objects::cleanup::MeshData;
+use glam::{DVec2, DVec3};
+use std::error::Error;
+use std::str::SplitAsciiWhitespace;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(format!("unknown PLY type '{name}'").into()),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Largest value of integer types, by which colours are divided; 1 for
+    // floating point ones.
+    fn full_scale(self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    // Name, then the types of the count and of the items.
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Values of the body, in file order.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    at: usize,
+    words: SplitAsciiWhitespace<'a>,
+}
+
+impl Body<'_> {
+    fn value(&mut self, scalar: Scalar) -> Result<f64, Box<dyn Error>> {
+        if self.format == Format::Ascii {
+            let word = self.words.next().ok_or("PLY file ends early")?;
+            return Ok(word.parse()?);
+        }
+        let size = scalar.size();
+        let bytes = self
+            .bytes
+            .get(self.at..self.at + size)
+            .ok_or("PLY file ends early")?;
+        self.at += size;
+        // Little-endian from here on.
+        let mut raw = [0; 8];
+        raw[..size].copy_from_slice(bytes);
+        if self.format == Format::BigEndian {
+            raw[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::I8 => raw[0] as i8 as f64,
+            Scalar::U8 => raw[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(raw),
+        })
+    }
+}
+
+// Reads an ASCII or binary PLY file: its `vertex` elements' positions,
+// normals, texture coordinates and colours, and its `face` elements'
+// polygons, split into fans of triangles. Other elements are skipped.
+// Colours come from `red`, `green` and `blue` properties, as 3D scans
+// usually have them, decoded to linear RGB.
+pub fn read(path: &str) -> Result<MeshData, RenderError> {
+    let bytes = std::fs::read(path).map_err(|e| RenderError::io(path, e))?;
+    parse(&bytes).map_err(|e| RenderError::mesh(path, e))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<MeshData, Box<dyn Error>> {
+    let end = bytes
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .ok_or("PLY header has no end_header")?;
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |i| end + i + 1);
+    let (format, elements) = parse_header(std::str::from_utf8(&bytes[..end])?)?;
+    let text = match format {
+        Format::Ascii => std::str::from_utf8(&bytes[body_start..])?,
+        _ => "",
+    };
+    let mut body = Body {
+        format,
+        bytes,
+        at: body_start,
+        words: text.split_ascii_whitespace(),
+    };
+
+    let mut mesh = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        colors: Vec::new(),
+        faces: Vec::new(),
+    };
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut body, &mut mesh)?,
+            "face" => read_faces(element, &mut body, &mut mesh.faces)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        read_property(property, &mut body, &mut Vec::new())?;
+                    }
+                }
+            }
+        }
+    }
+    let vertices = mesh.positions.len();
+    if let Some(face) = mesh.faces.iter().find(|f| f.iter().any(|&i| i >= vertices)) {
+        return Err(format!("PLY face {face:?} refers to a missing vertex").into());
+    }
+    Ok(mesh)
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), Box<dyn Error>> {
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("not a PLY file".into());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(format!("unknown PLY format '{name}'").into()),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or("PLY property before any element")?;
+                let (count, item) = (Scalar::parse(count)?, Scalar::parse(item)?);
+                element
+                    .properties
+                    .push(Property::List(name.to_string(), count, item));
+            }
+            ["property", scalar, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or("PLY property before any element")?;
+                let scalar = Scalar::parse(scalar)?;
+                element
+                    .properties
+                    .push(Property::Scalar(name.to_string(), scalar));
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(format!("unexpected PLY header line '{line}'").into()),
+        }
+    }
+    Ok((format.ok_or("PLY header has no format")?, elements))
+}
+
+// Reads one property into `values`: its value, or a list's items.
+fn read_property(
+    property: &Property,
+    body: &mut Body,
+    values: &mut Vec<f64>,
+) -> Result<(), Box<dyn Error>> {
+    values.clear();
+    match property {
+        Property::Scalar(_, scalar) => values.push(body.value(*scalar)?),
+        Property::List(_, count, item) => {
+            let count = body.value(*count)?;
+            if count < 0.0 {
+                return Err("PLY list has a negative length".into());
+            }
+            for _ in 0..count as usize {
+                values.push(body.value(*item)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_vertices(
+    element: &Element,
+    body: &mut Body,
+    mesh: &mut MeshData,
+) -> Result<(), Box<dyn Error>> {
+    let index = |names: &[&str]| {
+        element.properties.iter().position(|p| match p {
+            Property::Scalar(name, _) => names.contains(&name.as_str()),
+            Property::List(..) => false,
+        })
+    };
+    let all = |names: [&[&str]; 3]| {
+        let [a, b, c] = names.map(index);
+        Some([a?, b?, c?])
+    };
+    let position = all([&["x"], &["y"], &["z"]]).ok_or("PLY vertices have no position")?;
+    let normal = all([&["nx"], &["ny"], &["nz"]]);
+    let uv = [
+        index(&["u", "s", "texture_u", "texture_s"]),
+        index(&["v", "t", "texture_v", "texture_t"]),
+    ];
+    let color = all([
+        &["red", "diffuse_red"],
+        &["green", "diffuse_green"],
+        &["blue", "diffuse_blue"],
+    ]);
+    let color_scale = match color.map(|[red, ..]| &element.properties[red]) {
+        Some(Property::Scalar(_, scalar)) => scalar.full_scale(),
+        _ => 1.0,
+    };
+
+    let mut row = Vec::with_capacity(element.properties.len());
+    let mut values = Vec::new();
+    for _ in 0..element.count {
+        row.clear();
+        for property in &element.properties {
+            read_property(property, body, &mut values)?;
+            row.push(values.first().copied().unwrap_or(0.0));
+        }
+        let vector = |indices: [usize; 3]| DVec3::from_array(indices.map(|i| row[i]));
+        mesh.positions.push(vector(position));
+        if let Some(normal) = normal {
+            mesh.normals.push(vector(normal));
+        }
+        if let [Some(u), Some(v)] = uv {
+            mesh.texcoords.push(DVec2::new(row[u], row[v]));
+        }
+        if let Some(color) = color {
+            let encoded = vector(color) / color_scale;
+            let color = DVec3::from_array(encoded.to_array().map(srgb_to_linear));
+            mesh.colors.push(color);
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(
+    element: &Element,
+    body: &mut Body,
+    faces: &mut Vec<[usize; 3]>,
+) -> Result<(), Box<dyn Error>> {
+    let mut values = Vec::new();
+    for _ in 0..element.count {
+        for property in &element.properties {
+            read_property(property, body, &mut values)?;
+            let Property::List(name, ..) = property else {
+                continue;
+            };
+            if name != "vertex_indices" && name != "vertex_index" {
+                continue;
+            }
+            for i in 2..values.len() {
+                faces.push([values[0], values[i - 1], values[i]].map(|v| v as usize));
+            }
+        }
+    }
+    Ok(())
+}
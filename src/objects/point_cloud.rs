@@ -0,0 +1,289 @@
+use crate::color::srgb_to_linear;
+use crate::error::RenderError;
+use crate::hittable::{blocks, HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::ply;
+use crate::output::TransferFunction;
+use crate::qbvh::{BvhBuildStrategy, Hierarchy};
+use crate::ray::Ray;
+use glam::{DMat3, DVec3};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+// The shape each point of a `PointCloud` is drawn as.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Splat {
+    // A disk square to the point's normal, or facing the ray when it has
+    // none, as surfels from scans are usually drawn.
+    #[default]
+    #[serde(rename = "disk")]
+    Disk,
+    #[serde(rename = "sphere")]
+    Sphere,
+}
+
+// A point as read from a scan; `normal` and `color` are `None` when the
+// file doesn't give them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloudPoint {
+    pub position: DVec3,
+    pub normal: Option<DVec3>,
+    pub color: Option<DVec3>,
+}
+
+// Millions of points drawn as splats of one `radius`, in flat buffers
+// under a four-wide BVH of their own, as `Mesh` keeps its triangles. All
+// points share the cloud's material; those with a colour tint its base
+// colour with it, through their hit records.
+pub struct PointCloud {
+    // In the order the hierarchy's leaves refer to them by.
+    positions: Vec<DVec3>,
+    // One per position, zero for points without one; or none at all.
+    normals: Vec<DVec3>,
+    // One per position, sRGB encoded as scans store them, white for points
+    // without one; or none at all.
+    colors: Vec<[u8; 3]>,
+    material: Arc<dyn Material>,
+    radius: f64,
+    splat: Splat,
+    hierarchy: Hierarchy,
+    bounds: Option<AABB>,
+}
+
+impl PointCloud {
+    pub fn new(
+        points: &[CloudPoint],
+        radius: f64,
+        splat: Splat,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let reach = DVec3::splat(radius);
+        let bounds: Vec<AABB> = points
+            .iter()
+            .map(|point| AABB::new(point.position - reach, point.position + reach))
+            .collect();
+        let (hierarchy, total, order) = Hierarchy::build(&bounds, BvhBuildStrategy::default());
+        let has_normals = points.iter().any(|point| point.normal.is_some());
+        let normals = if has_normals {
+            order
+                .iter()
+                .map(|&i| {
+                    points[i]
+                        .normal
+                        .map_or(DVec3::ZERO, DVec3::normalize_or_zero)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let has_colors = points.iter().any(|point| point.color.is_some());
+        let colors = if has_colors {
+            let encode =
+                |c: f64| (TransferFunction::Srgb.encode(c.clamp(0.0, 1.0)) * 255.0).round();
+            order
+                .iter()
+                .map(|&i| {
+                    let color = points[i].color.unwrap_or(DVec3::ONE);
+                    color.to_array().map(|c| encode(c) as u8)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            positions: order.iter().map(|&i| points[i].position).collect(),
+            normals,
+            colors,
+            material,
+            radius,
+            splat,
+            hierarchy,
+            bounds: total,
+        }
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Where `ray` meets the splat of point `index` within `interval`, and
+    // the outward normal there.
+    fn intersect(&self, ray: &Ray, index: usize, interval: Interval) -> Option<(f64, DVec3)> {
+        let center = self.positions[index];
+        let to_center = center - ray.origin;
+        let r2 = self.radius * self.radius;
+        match self.splat {
+            Splat::Disk => {
+                let normal = self.normals.get(index).copied().unwrap_or(DVec3::ZERO);
+                let normal = if normal == DVec3::ZERO {
+                    -ray.direction.normalize()
+                } else {
+                    normal
+                };
+                let denominator = ray.direction.dot(normal);
+                if denominator == 0.0 {
+                    return None;
+                }
+                let t = to_center.dot(normal) / denominator;
+                if !interval.surrounds(t) || (ray.at(t) - center).length_squared() > r2 {
+                    return None;
+                }
+                Some((t, normal))
+            }
+            Splat::Sphere => {
+                let a = ray.direction.length_squared();
+                let half_b = -to_center.dot(ray.direction);
+                let c = to_center.length_squared() - r2;
+                let discriminant = half_b * half_b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let sqrtd = discriminant.sqrt();
+                let t = [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+                    .into_iter()
+                    .find(|&t| interval.surrounds(t))?;
+                Some((t, (ray.at(t) - center) / self.radius))
+            }
+        }
+    }
+
+    fn surface(&self, ray: &Ray, index: usize, t: f64, normal: DVec3) -> HitRecord<'_> {
+        // Disks without a normal turn to face every ray, so rays leaving
+        // one start past it to keep from finding it again.
+        let oriented = self.normals.get(index).is_some_and(|n| *n != DVec3::ZERO);
+        let epsilon = match self.splat {
+            Splat::Disk if !oriented => 2.0 * self.radius,
+            _ => 0.0,
+        };
+        let mut rec = HitRecord {
+            point: ray.at(t),
+            normal,
+            material: &*self.material,
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face: false,
+            object_id: 0,
+            epsilon,
+            tangent: DVec3::ZERO,
+            dpdu: DVec3::ZERO,
+            dpdv: DVec3::ZERO,
+            dndu: DVec3::ZERO,
+            dndv: DVec3::ZERO,
+            light: None,
+            barycentric: None,
+            color: self.colors.get(index).map(|&color| decode_color(color)),
+        };
+        rec.set_face_normal(ray, normal);
+        rec
+    }
+}
+
+impl Hittable for PointCloud {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        // Only the closest point in a leaf gets a hit record.
+        let leaf = |points: Range<usize>, interval: Interval| {
+            let mut closest = None;
+            let mut end = interval.max;
+            for index in points {
+                if let Some((t, normal)) = self.intersect(ray, index, interval.with_max(end)) {
+                    end = t;
+                    closest = Some((index, t, normal));
+                }
+            }
+            closest.map(|(index, t, normal)| self.surface(ray, index, t, normal))
+        };
+        self.hierarchy.hit(ray, interval, leaf)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        let see_through = self.material.is_see_through();
+        self.hierarchy.hit_any(ray, interval, |points, interval| {
+            points.into_iter().any(|index| {
+                self.intersect(ray, index, interval)
+                    .is_some_and(|(t, normal)| {
+                        !see_through || blocks(&self.surface(ray, index, t, normal))
+                    })
+            })
+        })
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.bounds
+    }
+}
+
+fn decode_color(color: [u8; 3]) -> DVec3 {
+    DVec3::from_array(color.map(|c| srgb_to_linear(c as f64 / 255.0)))
+}
+
+// Reads the points of a PLY file, whose faces are ignored, or of an XYZ
+// text file by its extension.
+pub fn read(path: &str) -> Result<Vec<CloudPoint>, RenderError> {
+    let is_ply = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ply"));
+    if is_ply {
+        let mesh = ply::read(path)?;
+        let points = mesh.positions.iter().enumerate();
+        return Ok(points
+            .map(|(i, &position)| CloudPoint {
+                position,
+                normal: mesh.normals.get(i).copied(),
+                color: mesh.colors.get(i).copied(),
+            })
+            .collect());
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| RenderError::io(path, e))?;
+    parse_xyz(&text).map_err(|e| RenderError::mesh(path, e))
+}
+
+// Brings `points` from a file's units and axes to the scene's by
+// `conversion`, turning their normals with them.
+pub fn convert(points: &mut [CloudPoint], conversion: DMat3) {
+    let normal_matrix = conversion.inverse().transpose();
+    for point in points {
+        point.position = conversion * point.position;
+        point.normal = point.normal.map(|n| normal_matrix * n);
+    }
+}
+
+// One point per line: `x y z`, `x y z r g b` or `x y z r g b nx ny nz`,
+// with sRGB colours from 0 to 255 as scanners export them. Blank lines
+// and lines starting with `#` or `//` are skipped.
+pub fn parse_xyz(text: &str) -> Result<Vec<CloudPoint>, String> {
+    let mut points = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let values = line
+            .split(|c: char| c.is_ascii_whitespace() || c == ',' || c == ';')
+            .filter(|value| !value.is_empty())
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("line {}: {e}", number + 1))?;
+        let vector = |at: usize| DVec3::new(values[at], values[at + 1], values[at + 2]);
+        let (normal, color) = match values.len() {
+            3 => (None, None),
+            6 => (None, Some(vector(3))),
+            9 => (Some(vector(6)), Some(vector(3))),
+            count => {
+                return Err(format!(
+                    "line {}: expected 3, 6 or 9 values, found {count}",
+                    number + 1
+                ))
+            }
+        };
+        points.push(CloudPoint {
+            position: vector(0),
+            normal,
+            color: color.map(|c| (c / 255.0).to_array().map(srgb_to_linear).into()),
+        });
+    }
+    Ok(points)
+}
@@ -0,0 +1,129 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{DMat3, DMat4, DVec3, DVec4};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// The surface A x² + B y² + C z² + D xy + E xz + F yz + G x + H y + I z + J
+// = 0, with `coefficients` in that order, clipped to `bounds` since most
+// quadrics (cylinders, paraboloids, hyperboloids) are infinite. UVs wrap
+// around the y axis of the bounds (u) and run up it (v).
+pub struct Quadric {
+    // Symmetric matrix Q with p^T Q p = 0 for homogeneous points p.
+    matrix: DMat4,
+    pub bounds: AABB,
+    pub material: Arc<dyn Material>,
+}
+
+impl Quadric {
+    pub fn new(coefficients: [f64; 10], bounds: AABB, material: Arc<dyn Material>) -> Self {
+        let [a, b, c, d, e, f, g, h, i, j] = coefficients;
+        let matrix = DMat4::from_cols(
+            DVec4::new(a, d / 2.0, e / 2.0, g / 2.0),
+            DVec4::new(d / 2.0, b, f / 2.0, h / 2.0),
+            DVec4::new(e / 2.0, f / 2.0, c, i / 2.0),
+            DVec4::new(g / 2.0, h / 2.0, i / 2.0, j),
+        );
+        Self {
+            matrix,
+            bounds,
+            material,
+        }
+    }
+
+    fn contains(&self, p: DVec3) -> bool {
+        let epsilon = DVec3::splat(1e-9);
+        p.cmpge(self.bounds.min - epsilon).all() && p.cmple(self.bounds.max + epsilon).all()
+    }
+}
+
+impl Hittable for Quadric {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let o = ray.origin.extend(1.0);
+        let d = ray.direction.extend(0.0);
+        let qd = self.matrix * d;
+        let a = d.dot(qd);
+        let half_b = o.dot(qd);
+        let c = o.dot(self.matrix * o);
+
+        let roots = if a.abs() < 1e-12 * (half_b.abs() + c.abs()).max(1e-300) {
+            // Lines parallel to a paraboloid's axis cross it only once.
+            if half_b == 0.0 {
+                return None;
+            }
+            [-c / (2.0 * half_b), f64::NAN]
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            // Avoids cancellation between -half_b and the square root.
+            let q = -(half_b + discriminant.sqrt().copysign(half_b));
+            let (t0, t1) = (q / a, c / q);
+            [t0.min(t1), t0.max(t1)]
+        };
+        let t = roots
+            .into_iter()
+            .find(|&t| interval.surrounds(t) && self.contains(ray.at(t)))?;
+
+        let point = ray.at(t);
+        let gradient = (self.matrix * point.extend(1.0)).truncate();
+        let outward_normal = gradient.try_normalize()?;
+        let size = (self.bounds.max - self.bounds.min).max(DVec3::splat(1e-12));
+        let center = (self.bounds.min + self.bounds.max) / 2.0;
+        let phi = (point.z - center.z).atan2(point.x - center.x) + PI;
+
+        // Along the level curve of `v`, u changes at the rate the surface
+        // turns around the bounds' axis; along the curve of constant `u`, v
+        // changes with height.
+        let around = DVec3::new(center.z - point.z, 0.0, point.x - center.x);
+        let level = outward_normal.cross(DVec3::Y);
+        let turn = level.dot(around);
+        let dpdu = if turn.abs() > 1e-12 {
+            2.0 * PI * around.length_squared() / turn * level
+        } else {
+            DVec3::ZERO
+        };
+        let meridian = outward_normal.cross(around);
+        let dpdv = if meridian.y.abs() > 1e-12 {
+            size.y / meridian.y * meridian
+        } else {
+            DVec3::ZERO
+        };
+        // Weingarten: the gradient's change, less its component along the
+        // normal, over the gradient's length.
+        let hessian = DMat3::from_mat4(self.matrix);
+        let normal_change = |dp: DVec3| {
+            let dg = hessian * dp;
+            (dg - dg.dot(outward_normal) * outward_normal) / gradient.length()
+        };
+
+        let mut rec = HitRecord {
+            point,
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: phi / (2.0 * PI),
+            v: ((point.y - self.bounds.min.y) / size.y).clamp(0.0, 1.0),
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent: DVec3::ZERO,
+            dpdu,
+            dpdv,
+            dndu: normal_change(dpdu),
+            dndv: normal_change(dpdv),
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(self.bounds)
+    }
+}
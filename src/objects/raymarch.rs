@@ -0,0 +1,228 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{DVec3, DVec4};
+use std::sync::Arc;
+
+pub trait DistanceField: Send + Sync {
+    // Conservative distance estimate from `p` to the surface; it may
+    // under-estimate but must never over-estimate.
+    fn distance(&self, p: DVec3) -> f64;
+    fn bounds(&self) -> AABB;
+}
+
+pub struct RayMarched<D: DistanceField> {
+    pub field: D,
+    pub material: Arc<dyn Material>,
+    pub max_steps: u32,
+    pub epsilon: f64,
+}
+
+impl<D: DistanceField> RayMarched<D> {
+    pub fn new(field: D, material: Arc<dyn Material>) -> Self {
+        Self {
+            field,
+            material,
+            max_steps: 256,
+            epsilon: 1e-4,
+        }
+    }
+
+    fn normal(&self, p: DVec3) -> DVec3 {
+        let h = self.epsilon;
+        let dx = DVec3::new(h, 0.0, 0.0);
+        let dy = DVec3::new(0.0, h, 0.0);
+        let dz = DVec3::new(0.0, 0.0, h);
+        DVec3::new(
+            self.field.distance(p + dx) - self.field.distance(p - dx),
+            self.field.distance(p + dy) - self.field.distance(p - dy),
+            self.field.distance(p + dz) - self.field.distance(p - dz),
+        )
+        .normalize_or_zero()
+    }
+}
+
+impl<D: DistanceField> Hittable for RayMarched<D> {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let (enter, exit) = slab_interval(&self.field.bounds(), ray)?;
+        let speed = ray.direction.length();
+        let mut t = enter.max(interval.min);
+        let t_max = exit.min(interval.max);
+
+        // Rays that start inside the surface march on |d| so that they find
+        // the exit point instead of stopping immediately.
+        let inside = self.field.distance(ray.at(t)) < 0.0;
+        for _ in 0..self.max_steps {
+            if t > t_max {
+                return None;
+            }
+            let d = self.field.distance(ray.at(t)).abs();
+            if d < self.epsilon && t > interval.min {
+                let point = ray.at(t);
+                let mut outward_normal = self.normal(point);
+                if outward_normal == DVec3::ZERO {
+                    outward_normal = -ray.direction.normalize();
+                }
+                let mut rec = HitRecord {
+                    point,
+                    normal: outward_normal,
+                    material: &*self.material,
+                    t,
+                    u: 0.0,
+                    v: 0.0,
+                    front_face: false,
+                    object_id: 0,
+                    // The hit lies up to `epsilon` from the surface; rays
+                    // leaving it have to start clear of that band.
+                    epsilon: 10.0 * self.epsilon,
+                    tangent: DVec3::ZERO,
+                    dpdu: DVec3::ZERO,
+                    dpdv: DVec3::ZERO,
+                    dndu: DVec3::ZERO,
+                    dndv: DVec3::ZERO,
+                    light: None,
+                    barycentric: None,
+                    color: None,
+                };
+                rec.set_face_normal(ray, outward_normal);
+                return Some(rec);
+            }
+            let step = if inside { d.max(self.epsilon) } else { d };
+            t += step / speed;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(self.field.bounds())
+    }
+}
+
+pub(crate) fn slab_interval(bbox: &AABB, ray: &Ray) -> Option<(f64, f64)> {
+    let mut t_min = f64::NEG_INFINITY;
+    let mut t_max = f64::INFINITY;
+    for a in 0..3 {
+        let inv_d = 1.0 / ray.direction[a];
+        let mut t0 = (bbox.min[a] - ray.origin[a]) * inv_d;
+        let mut t1 = (bbox.max[a] - ray.origin[a]) * inv_d;
+        if inv_d < 0.0 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        t_min = t_min.max(t0);
+        t_max = t_max.min(t1);
+    }
+    if t_max < t_min {
+        None
+    } else {
+        Some((t_min, t_max))
+    }
+}
+
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: u32,
+}
+
+impl DistanceField for Mandelbulb {
+    fn distance(&self, p: DVec3) -> f64 {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = 0.0;
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > 2.0 {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z =
+                zr * DVec3::new(
+                    theta.sin() * phi.cos(),
+                    phi.sin() * theta.sin(),
+                    theta.cos(),
+                ) + p;
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    fn bounds(&self) -> AABB {
+        AABB::new(DVec3::splat(-1.25), DVec3::splat(1.25))
+    }
+}
+
+pub struct MengerSponge {
+    pub iterations: u32,
+}
+
+impl DistanceField for MengerSponge {
+    fn distance(&self, p: DVec3) -> f64 {
+        let q = p.abs() - DVec3::ONE;
+        let mut d = q.max(DVec3::ZERO).length() + q.max_element().min(0.0);
+
+        let mut s = 1.0;
+        for _ in 0..self.iterations {
+            let a = DVec3::new(
+                (p.x * s).rem_euclid(2.0),
+                (p.y * s).rem_euclid(2.0),
+                (p.z * s).rem_euclid(2.0),
+            ) - DVec3::ONE;
+            s *= 3.0;
+            let r = (DVec3::ONE - 3.0 * a.abs()).abs();
+            let da = r.x.max(r.y);
+            let db = r.y.max(r.z);
+            let dc = r.z.max(r.x);
+            let c = (da.min(db).min(dc) - 1.0) / s;
+            d = d.max(c);
+        }
+        d
+    }
+
+    fn bounds(&self) -> AABB {
+        AABB::new(DVec3::splat(-1.0), DVec3::splat(1.0))
+    }
+}
+
+pub struct JuliaSet {
+    pub c: DVec4,
+    pub iterations: u32,
+}
+
+impl DistanceField for JuliaSet {
+    fn distance(&self, p: DVec3) -> f64 {
+        let mut z = DVec4::new(p.x, p.y, p.z, 0.0);
+        let mut dz = DVec4::new(1.0, 0.0, 0.0, 0.0);
+        for _ in 0..self.iterations {
+            dz = 2.0 * quat_mul(z, dz);
+            z = quat_mul(z, z) + self.c;
+            if z.length_squared() > 16.0 {
+                break;
+            }
+        }
+        let r = z.length();
+        let dr = dz.length();
+        if dr == 0.0 {
+            return 0.0;
+        }
+        0.5 * r * r.ln() / dr
+    }
+
+    fn bounds(&self) -> AABB {
+        AABB::new(DVec3::splat(-1.5), DVec3::splat(1.5))
+    }
+}
+
+// Quaternion product with the real part stored in `x`.
+fn quat_mul(a: DVec4, b: DVec4) -> DVec4 {
+    DVec4::new(
+        a.x * b.x - a.y * b.y - a.z * b.z - a.w * b.w,
+        a.x * b.y + a.y * b.x + a.z * b.w - a.w * b.z,
+        a.x * b.z - a.y * b.w + a.z * b.x + a.w * b.y,
+        a.x * b.w + a.y * b.z - a.z * b.y + a.w * b.x,
+    )
+}
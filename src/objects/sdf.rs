@@ -0,0 +1,150 @@
+use crate::hittable::AABB;
+use crate::objects::raymarch::{DistanceField, RayMarched};
+use glam::{DVec2, DVec3};
+
+// Implicit surface found by sphere tracing a distance field, with normals
+// from central differences. The field is usually an `SdfExpr`, but any
+// `DistanceField` works, including closures wrapped in `SdfFn`.
+pub type SdfObject<D = SdfExpr> = RayMarched<D>;
+
+// Distance field given by a closure. The closure must never over-estimate
+// the distance, and the surface must lie inside `bounds`.
+pub struct SdfFn<F> {
+    pub distance: F,
+    pub bounds: AABB,
+}
+
+impl<F: Fn(DVec3) -> f64 + Send + Sync> SdfFn<F> {
+    pub fn new(bounds: AABB, distance: F) -> Self {
+        Self { distance, bounds }
+    }
+}
+
+impl<F: Fn(DVec3) -> f64 + Send + Sync> DistanceField for SdfFn<F> {
+    fn distance(&self, p: DVec3) -> f64 {
+        (self.distance)(p)
+    }
+
+    fn bounds(&self) -> AABB {
+        self.bounds
+    }
+}
+
+// A small tree of primitives combined with CSG operations. Intersections
+// and differences give distance bounds rather than exact distances, which
+// is all sphere tracing needs.
+pub enum SdfExpr {
+    Sphere {
+        center: DVec3,
+        radius: f64,
+    },
+    Cuboid {
+        center: DVec3,
+        half_extents: DVec3,
+    },
+    // Lies in the XZ plane around `center`.
+    Torus {
+        center: DVec3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        start: DVec3,
+        end: DVec3,
+        radius: f64,
+    },
+    Union(Box<SdfExpr>, Box<SdfExpr>),
+    Intersection(Box<SdfExpr>, Box<SdfExpr>),
+    // The first shape with the second carved out of it.
+    Difference(Box<SdfExpr>, Box<SdfExpr>),
+    // Union with the seam rounded over a distance of about `k`.
+    SmoothUnion(Box<SdfExpr>, Box<SdfExpr>, f64),
+}
+
+impl DistanceField for SdfExpr {
+    fn distance(&self, p: DVec3) -> f64 {
+        match self {
+            SdfExpr::Sphere { center, radius } => p.distance(*center) - radius,
+            SdfExpr::Cuboid {
+                center,
+                half_extents,
+            } => {
+                let q = (p - *center).abs() - *half_extents;
+                q.max(DVec3::ZERO).length() + q.max_element().min(0.0)
+            }
+            SdfExpr::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let p = p - *center;
+                let q = DVec2::new(DVec2::new(p.x, p.z).length() - major_radius, p.y);
+                q.length() - minor_radius
+            }
+            SdfExpr::Capsule { start, end, radius } => {
+                let axis = *end - *start;
+                let length_squared = axis.length_squared();
+                let h = if length_squared > 0.0 {
+                    ((p - *start).dot(axis) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                p.distance(*start + h * axis) - radius
+            }
+            SdfExpr::Union(a, b) => a.distance(p).min(b.distance(p)),
+            SdfExpr::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            SdfExpr::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            SdfExpr::SmoothUnion(a, b, k) => smooth_min(a.distance(p), b.distance(p), *k),
+        }
+    }
+
+    fn bounds(&self) -> AABB {
+        match self {
+            SdfExpr::Sphere { center, radius } => {
+                let r = DVec3::splat(radius.abs());
+                AABB::new(*center - r, *center + r)
+            }
+            SdfExpr::Cuboid {
+                center,
+                half_extents,
+            } => AABB::new(*center - half_extents.abs(), *center + half_extents.abs()),
+            SdfExpr::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius.abs() + minor_radius.abs();
+                let r = DVec3::new(outer, minor_radius.abs(), outer);
+                AABB::new(*center - r, *center + r)
+            }
+            SdfExpr::Capsule { start, end, radius } => {
+                let r = DVec3::splat(radius.abs());
+                AABB::new(start.min(*end) - r, start.max(*end) + r)
+            }
+            SdfExpr::Union(a, b) => AABB::surrounding_box(a.bounds(), b.bounds()),
+            SdfExpr::Intersection(a, b) => {
+                let (a, b) = (a.bounds(), b.bounds());
+                let min = a.min.max(b.min);
+                // Disjoint shapes leave an empty box at the overlap's corner.
+                AABB::new(min, a.max.min(b.max).max(min))
+            }
+            SdfExpr::Difference(a, _) => a.bounds(),
+            // The blend can pull the surface out by up to k/4 past either
+            // shape.
+            SdfExpr::SmoothUnion(a, b, k) => {
+                let bounds = AABB::surrounding_box(a.bounds(), b.bounds());
+                let pad = DVec3::splat(0.25 * k.abs());
+                AABB::new(bounds.min - pad, bounds.max + pad)
+            }
+        }
+    }
+}
+
+// Polynomial smooth minimum; equals `min(a, b)` once they differ by `k`.
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
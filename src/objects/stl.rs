@@ -0,0 +1,74 @@
+use crate::error::RenderError;
+use crate::objects::cleanup::MeshData;
+use glam::DVec3;
+use std::error::Error;
+
+// Binary STL: an 80 byte header, the triangle count, then 50 bytes per
+// triangle.
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+// Reads a binary or ASCII STL file. STL stores every triangle with its own
+// three corners and no attributes, so the mesh has no normals or texture
+// coordinates, and corners are welded by `cleanup::clean`. The facet
+// normals in the file are ignored in favour of the winding.
+pub fn read(path: &str) -> Result<MeshData, RenderError> {
+    let bytes = std::fs::read(path).map_err(|e| RenderError::io(path, e))?;
+    parse(&bytes).map_err(|e| RenderError::mesh(path, e))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<MeshData, Box<dyn Error>> {
+    // Binary files may also start with "solid", so their size decides.
+    let binary = bytes.len() >= HEADER_SIZE && {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        bytes.len() == HEADER_SIZE + count * TRIANGLE_SIZE
+    };
+    let positions = if binary {
+        parse_binary(bytes)
+    } else {
+        parse_ascii(std::str::from_utf8(bytes)?)?
+    };
+    Ok(MeshData {
+        faces: (0..positions.len() / 3)
+            .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+            .collect(),
+        positions,
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        colors: Vec::new(),
+    })
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<DVec3> {
+    let mut positions = Vec::new();
+    for triangle in bytes[HEADER_SIZE..].chunks_exact(TRIANGLE_SIZE) {
+        let float = |at: usize| f32::from_le_bytes(triangle[at..at + 4].try_into().unwrap());
+        // The corners follow the facet normal.
+        for at in [12, 24, 36] {
+            let corner = [float(at), float(at + 4), float(at + 8)];
+            positions.push(DVec3::from_array(corner.map(f64::from)));
+        }
+    }
+    positions
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<DVec3>, Box<dyn Error>> {
+    let mut positions = Vec::new();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        if word != "vertex" {
+            continue;
+        }
+        let mut coordinate = || -> Result<f64, Box<dyn Error>> {
+            let word = words
+                .next()
+                .ok_or("STL vertex has fewer than 3 coordinates")?;
+            Ok(word.parse()?)
+        };
+        positions.push(DVec3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+    if positions.len() % 3 != 0 {
+        return Err("STL facet doesn't have 3 vertices".into());
+    }
+    Ok(positions)
+}
@@ -0,0 +1,138 @@
+use crate::objects::cleanup::{self, MeshData};
+use crate::renderer::luminance;
+use crate::texture::Texture;
+use glam::{DVec2, DVec3};
+use std::collections::HashMap;
+
+// Applies `levels` of Loop subdivision to `mesh`: each splits every
+// triangle into four and moves the corners towards a smooth surface
+// through them. Open edges, and edges shared by more than two faces, are
+// kept as creases. Texture coordinates are interpolated linearly, so seams
+// stay where they were. Vertex normals, if the mesh has any, are rebuilt
+// for the new shape with `cleanup::generate_normals`; vertex colours are
+// dropped.
+pub fn subdivide(mesh: &mut MeshData, levels: u32) {
+    if levels == 0 {
+        return;
+    }
+    let had_normals = !mesh.normals.is_empty();
+    mesh.normals.clear();
+    mesh.colors.clear();
+    for _ in 0..levels {
+        subdivide_once(mesh);
+    }
+    if had_normals {
+        cleanup::generate_normals(mesh);
+    }
+}
+
+// Moves each vertex of `mesh` along the surface normal by the grey level
+// of `height` times `scale`. The texture is read at the vertex's texture
+// coordinates, or at its position for meshes without any. Vertices at the
+// same position move along the same normal, so the surface only tears
+// where the texture itself differs across a seam in the coordinates.
+// Vertex normals, if the mesh has any, are rebuilt for the new shape.
+pub fn displace(mesh: &mut MeshData, height: &dyn Texture, scale: f64) {
+    let normals = cleanup::point_normals(mesh);
+    for (i, position) in mesh.positions.iter_mut().enumerate() {
+        let uv = mesh.texcoords.get(i).copied().unwrap_or(DVec2::ZERO);
+        let level = luminance(height.value(uv.x, uv.y, *position));
+        *position += normals[i] * level * scale;
+    }
+    if !mesh.normals.is_empty() {
+        mesh.normals = cleanup::point_normals(mesh);
+    }
+}
+
+fn edge(p: usize, q: usize) -> (usize, usize) {
+    (p.min(q), p.max(q))
+}
+
+fn subdivide_once(mesh: &mut MeshData) {
+    // The surface's shape is worked out on welded points, so that seams in
+    // the texture coordinates don't split it.
+    let point_of = cleanup::welded_points(&mesh.positions);
+    let mut position_of = HashMap::new();
+    // The third corner of each face along an edge of points.
+    let mut opposite: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    let mut neighbours: HashMap<usize, Vec<usize>> = HashMap::new();
+    for face in &mesh.faces {
+        for i in *face {
+            position_of.insert(point_of[i], mesh.positions[i]);
+        }
+        let [a, b, c] = face.map(|i| point_of[i]);
+        for (p, q, r) in [(a, b, c), (b, c, a), (c, a, b)] {
+            let corners = opposite.entry(edge(p, q)).or_default();
+            if corners.is_empty() {
+                neighbours.entry(p).or_default().push(q);
+                neighbours.entry(q).or_default().push(p);
+            }
+            corners.push(r);
+        }
+    }
+    let crease = |p: usize, q: usize| opposite[&edge(p, q)].len() != 2;
+
+    // Corners keep their points, moved by the even rules.
+    let mut moved = HashMap::with_capacity(position_of.len());
+    for (&point, around) in &neighbours {
+        let p = position_of[&point];
+        let creases: Vec<usize> = around
+            .iter()
+            .copied()
+            .filter(|&q| crease(point, q))
+            .collect();
+        let position = match creases.as_slice() {
+            [] => {
+                let n = around.len() as f64;
+                let beta = if around.len() == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * n)
+                };
+                let sum: DVec3 = around.iter().map(|q| position_of[q]).sum();
+                (1.0 - n * beta) * p + beta * sum
+            }
+            [a, b] => 0.75 * p + 0.125 * (position_of[a] + position_of[b]),
+            // Where creases meet or fan out, the point stays put.
+            _ => p,
+        };
+        moved.insert(point, position);
+    }
+    // And new vertices split every edge, placed by the odd rules.
+    let split = |p: usize, q: usize| match opposite[&edge(p, q)].as_slice() {
+        [r, s] => {
+            0.375 * (position_of[&p] + position_of[&q]) + 0.125 * (position_of[r] + position_of[s])
+        }
+        _ => 0.5 * (position_of[&p] + position_of[&q]),
+    };
+
+    let mut positions: Vec<DVec3> = (0..mesh.positions.len())
+        .map(|i| {
+            moved
+                .get(&point_of[i])
+                .copied()
+                .unwrap_or(mesh.positions[i])
+        })
+        .collect();
+    let mut texcoords = mesh.texcoords.clone();
+    // Faces sharing an edge share its new vertex, unless its two ends are
+    // different vertices for them, as at a seam.
+    let mut middles = HashMap::new();
+    let mut middle = |i: usize, j: usize| {
+        *middles.entry(edge(i, j)).or_insert_with(|| {
+            positions.push(split(point_of[i], point_of[j]));
+            if !mesh.texcoords.is_empty() {
+                texcoords.push((mesh.texcoords[i] + mesh.texcoords[j]) / 2.0);
+            }
+            positions.len() - 1
+        })
+    };
+    let mut faces = Vec::with_capacity(4 * mesh.faces.len());
+    for &[a, b, c] in &mesh.faces {
+        let (ab, bc, ca) = (middle(a, b), middle(b, c), middle(c, a));
+        faces.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+    }
+    mesh.positions = positions;
+    mesh.texcoords = texcoords;
+    mesh.faces = faces;
+}
@@ -0,0 +1,46 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::sync::Arc;
+
+// Stamps every hit on `object` with `id` for the object ID pass, and with a
+// ray offset scaled to the object's bounds so that planet-sized and
+// millimetre-sized objects can share a scene without acne or gaps.
+pub struct Tagged {
+    pub object: Arc<dyn Hittable>,
+    pub id: u32,
+    pub epsilon: f64,
+}
+
+impl Tagged {
+    pub fn new(object: Arc<dyn Hittable>, id: u32) -> Self {
+        let epsilon = object.bounding_box().map_or(0.0, |b| b.epsilon());
+        Self {
+            object,
+            id,
+            epsilon,
+        }
+    }
+}
+
+impl Hittable for Tagged {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = self.object.hit(ray, interval)?;
+        rec.object_id = self.id;
+        rec.epsilon = rec.epsilon.max(self.epsilon);
+        Some(rec)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        self.object.hit_any(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.object.bounding_box()
+    }
+
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        self.object.extent(axis)
+    }
+}
@@ -0,0 +1,111 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::objects::disk::disk_extent;
+use crate::polynomial::real_roots;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// A ring of radius `major_radius` around `axis`, swept by a tube of radius
+// `minor_radius`. u runs around the axis and v around the tube.
+pub struct Torus {
+    pub center: DVec3,
+    pub axis: DVec3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: DVec3,
+        axis: DVec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center,
+            axis: axis.normalize(),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let length = ray.direction.length();
+        if length == 0.0 {
+            return None;
+        }
+        let (tangent, bitangent) = self.axis.any_orthonormal_pair();
+        // Local frame with the axis along y and a unit direction. Starting
+        // from the point on the ray closest to the centre keeps the quartic's
+        // coefficients small.
+        let local = |v: DVec3| DVec3::new(v.dot(tangent), v.dot(self.axis), v.dot(bitangent));
+        let d = local(ray.direction / length);
+        let offset = local(self.center - ray.origin).dot(d);
+        let o = local(ray.origin - self.center) + offset * d;
+
+        // (|p|² + R² - r²)² = 4R²(x² + z²) along p = o + s d.
+        let r2 = self.major_radius * self.major_radius;
+        let k = o.length_squared() + r2 - self.minor_radius * self.minor_radius;
+        let b = 2.0 * o.dot(d);
+        let coefficients = [
+            k * k - 4.0 * r2 * (o.x * o.x + o.z * o.z),
+            2.0 * b * k - 8.0 * r2 * (o.x * d.x + o.z * d.z),
+            b * b + 2.0 * k - 4.0 * r2 * (d.x * d.x + d.z * d.z),
+            2.0 * b,
+            1.0,
+        ];
+        let s_range = interval.min * length - offset..interval.max * length - offset;
+        let s = *real_roots(&coefficients, s_range).first()?;
+        let t = (s + offset) / length;
+
+        let p = o + s * d;
+        let ring = DVec3::new(p.x, 0.0, p.z)
+            .try_normalize()
+            .unwrap_or(DVec3::X);
+        let local_normal = (p - self.major_radius * ring).normalize_or_zero();
+        let to_world = |v: DVec3| v.x * tangent + v.y * self.axis + v.z * bitangent;
+        let outward_normal = to_world(local_normal);
+        let phi = p.z.atan2(p.x) + PI;
+        let theta = local_normal.y.atan2(local_normal.dot(ring)) + PI;
+        // Directions of increasing `phi` around the axis and `theta` around
+        // the tube.
+        let around_axis = DVec3::new(-ring.z, 0.0, ring.x);
+        let around_tube = -local_normal.y * ring + local_normal.dot(ring) * DVec3::Y;
+        let ring_radius = self.major_radius + self.minor_radius * local_normal.dot(ring);
+
+        let mut rec = HitRecord {
+            point: ray.at(t),
+            normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: phi / (2.0 * PI),
+            v: theta / (2.0 * PI),
+            front_face: false,
+            object_id: 0,
+            epsilon: 0.0,
+            tangent: to_world(around_axis),
+            dpdu: 2.0 * PI * ring_radius * to_world(around_axis),
+            dpdv: 2.0 * PI * self.minor_radius * to_world(around_tube),
+            dndu: 2.0 * PI * local_normal.dot(ring) * to_world(around_axis),
+            dndv: 2.0 * PI * to_world(around_tube),
+            light: None,
+            barycentric: None,
+            color: None,
+        };
+        rec.set_face_normal(ray, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let extent = disk_extent(self.axis, self.major_radius) + DVec3::splat(self.minor_radius);
+        Some(AABB::new(self.center - extent, self.center + extent))
+    }
+}
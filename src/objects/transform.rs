@@ -0,0 +1,103 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use glam::{DAffine3, DVec3};
+use std::sync::Arc;
+
+pub struct Transformed {
+    pub object: Arc<dyn Hittable>,
+    transform: DAffine3,
+    inverse: DAffine3,
+}
+
+impl Transformed {
+    pub fn new(object: Arc<dyn Hittable>, transform: DAffine3) -> Self {
+        Self {
+            object,
+            transform,
+            inverse: transform.inverse(),
+        }
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        hit_transformed(&*self.object, &self.transform, &self.inverse, ray, interval)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        self.object.hit_any(&to_local(&self.inverse, ray), interval)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(transform_box(&self.object.bounding_box()?, &self.transform))
+    }
+
+    // Projecting the object onto the axis carried back into its own space
+    // keeps rotated objects as tight as they are unrotated.
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        let local = self.transform.matrix3.transpose().mul_vec3(axis);
+        let (min, max) = self.object.extent(local)?;
+        let offset = axis.dot(self.transform.translation);
+        Some((min + offset, max + offset))
+    }
+
+    fn as_instance(&self) -> Option<(&dyn Hittable, DAffine3)> {
+        Some((&*self.object, self.transform))
+    }
+}
+
+pub(crate) fn hit_transformed<'a>(
+    object: &'a dyn Hittable,
+    transform: &DAffine3,
+    inverse: &DAffine3,
+    ray: &Ray,
+    interval: Interval,
+) -> Option<HitRecord<'a>> {
+    let mut rec = object.hit(&to_local(inverse, ray), interval)?;
+    rec.point = transform.transform_point3(rec.point);
+    let normal_matrix = inverse.matrix3.transpose();
+    let normal = normal_matrix.mul_vec3(rec.normal);
+    let length = normal.length();
+    rec.normal = normal / length;
+    rec.tangent = transform.transform_vector3(rec.tangent).normalize_or_zero();
+    rec.dpdu = transform.transform_vector3(rec.dpdu);
+    rec.dpdv = transform.transform_vector3(rec.dpdv);
+    // The derivative of the renormalised normal.
+    let n = rec.normal;
+    let normal_change = |dn: DVec3| {
+        let dn = normal_matrix.mul_vec3(dn);
+        (dn - dn.dot(n) * n) / length
+    };
+    rec.dndu = normal_change(rec.dndu);
+    rec.dndv = normal_change(rec.dndv);
+    Some(rec)
+}
+
+// `ray` carried into an object's own space by `inverse`, at the same time
+// and of the same kind.
+pub(crate) fn to_local(inverse: &DAffine3, ray: &Ray) -> Ray {
+    Ray::new(
+        inverse.transform_point3(ray.origin),
+        inverse.transform_vector3(ray.direction),
+    )
+    .with_time(ray.time)
+    .with_kind(ray.kind)
+}
+
+// Box around the eight transformed corners of `bbox`.
+pub(crate) fn transform_box(bbox: &AABB, transform: &DAffine3) -> AABB {
+    let mut min = DVec3::splat(f64::INFINITY);
+    let mut max = DVec3::splat(f64::NEG_INFINITY);
+    for i in 0..8 {
+        let corner = DVec3::new(
+            if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+            if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+            if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+        );
+        let p = transform.transform_point3(corner);
+        min = min.min(p);
+        max = max.max(p);
+    }
+    AABB::new(min, max)
+}
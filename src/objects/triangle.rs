@@ -0,0 +1,245 @@
+use crate::hittable::{blocks, HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::real::{from_real, to_real, wide, Vec3};
+use crate::stats;
+use glam::{DVec2, DVec3};
+use std::sync::Arc;
+
+// Vertices and normals are kept at `Real` precision, since big meshes are
+// mostly made of them.
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    pub uvs: [DVec2; 3],
+    pub material: Arc<dyn Material>,
+    // Linear RGB of the corners, interpolated to tint the material's base
+    // colour; see `HitRecord::color`.
+    colors: Option<[Vec3; 3]>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [DVec3; 3], material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: vertices.map(to_real),
+            normals: None,
+            uvs: [
+                DVec2::new(0.0, 0.0),
+                DVec2::new(1.0, 0.0),
+                DVec2::new(0.0, 1.0),
+            ],
+            material,
+            colors: None,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [DVec3; 3]) -> Self {
+        self.normals = Some(normals.map(to_real));
+        self
+    }
+
+    pub fn vertices(&self) -> [DVec3; 3] {
+        self.vertices.map(from_real)
+    }
+
+    pub fn normals(&self) -> Option<[DVec3; 3]> {
+        self.normals.map(|normals| normals.map(from_real))
+    }
+
+    pub fn with_uvs(mut self, uvs: [DVec2; 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn with_colors(mut self, colors: [DVec3; 3]) -> Self {
+        self.colors = Some(colors.map(to_real));
+        self
+    }
+
+    pub fn colors(&self) -> Option<[DVec3; 3]> {
+        self.colors.map(|colors| colors.map(from_real))
+    }
+}
+
+// dp/du, dp/dv, dn/du and dn/dv of a triangle at barycentrics `b1`, `b2`,
+// solving edge = du * dp/du + dv * dp/dv for its position and normal edges.
+// The normal derivatives are those of the normalised interpolated normal,
+// and zero without per-vertex normals; everything is zero when the UVs are
+// degenerate.
+pub(crate) fn uv_derivatives(
+    vertices: [DVec3; 3],
+    uvs: [DVec2; 3],
+    normals: Option<[DVec3; 3]>,
+    (b1, b2): (f64, f64),
+) -> [DVec3; 4] {
+    let duv1 = uvs[1] - uvs[0];
+    let duv2 = uvs[2] - uvs[0];
+    let det = duv1.x * duv2.y - duv2.x * duv1.y;
+    if det.abs() < 1e-12 {
+        return [DVec3::ZERO; 4];
+    }
+    let solve = |e1: DVec3, e2: DVec3| {
+        (
+            (e1 * duv2.y - e2 * duv1.y) / det,
+            (e2 * duv1.x - e1 * duv2.x) / det,
+        )
+    };
+    let [p0, p1, p2] = vertices;
+    let (dpdu, dpdv) = solve(p1 - p0, p2 - p0);
+    let Some([n0, n1, n2]) = normals else {
+        return [dpdu, dpdv, DVec3::ZERO, DVec3::ZERO];
+    };
+    let (dndu, dndv) = solve(n1 - n0, n2 - n0);
+    let interpolated = (1.0 - b1 - b2) * n0 + b1 * n1 + b2 * n2;
+    let length = interpolated.length();
+    if length == 0.0 {
+        return [dpdu, dpdv, DVec3::ZERO, DVec3::ZERO];
+    }
+    let n = interpolated / length;
+    let normalised = |dn: DVec3| (dn - dn.dot(n) * n) / length;
+    [dpdu, dpdv, normalised(dndu), normalised(dndv)]
+}
+
+// Distance along a ray from `origin` along `direction` to the triangle
+// with `vertices`, and the barycentrics of the hit, when it's within
+// `interval`. This is the watertight test of Woop, Benthin and Wald: the
+// vertices are moved into a frame where the ray runs along +z from the
+// origin, and the signs of the 2D edge functions decide the hit. Edges
+// shared by two triangles are computed identically for both, so no ray
+// slips between them, and a ray exactly on an edge hits at least one.
+pub(crate) fn intersect(
+    vertices: &[Vec3; 3],
+    origin: Vec3,
+    direction: Vec3,
+    interval: Interval,
+) -> Option<(f64, (f64, f64))> {
+    stats::count(|stats| stats.triangle_tests += 1);
+    let abs = direction.abs();
+    let kz = if abs.x > abs.y && abs.x > abs.z {
+        0
+    } else if abs.y > abs.z {
+        1
+    } else {
+        2
+    };
+    let (kx, ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    let permute = |v: Vec3| Vec3::new(v[kx], v[ky], v[kz]);
+    let d = permute(direction);
+    let shear = Vec3::new(-d.x / d.z, -d.y / d.z, 1.0 / d.z);
+    let [a, b, c] = vertices.map(|p| {
+        let p = permute(p - origin);
+        Vec3::new(p.x + shear.x * p.z, p.y + shear.y * p.z, p.z * shear.z)
+    });
+
+    let e0 = b.x * c.y - b.y * c.x;
+    let e1 = c.x * a.y - c.y * a.x;
+    let e2 = a.x * b.y - a.y * b.x;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    // Zero when the ray runs in the triangle's plane.
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+    let t = wide((e0 * a.z + e1 * b.z + e2 * c.z) / det);
+    if !interval.surrounds(t) {
+        return None;
+    }
+    Some((t, (wide(e1 / det), wide(e2 / det))))
+}
+
+// The hit record for a hit found by `intersect` at `t` along `ray`.
+pub(crate) fn surface<'a>(
+    ray: &Ray,
+    (t, (b1, b2)): (f64, (f64, f64)),
+    vertices: [DVec3; 3],
+    normals: Option<[DVec3; 3]>,
+    uvs: [DVec2; 3],
+    colors: Option<[DVec3; 3]>,
+    material: &'a dyn Material,
+) -> HitRecord<'a> {
+    let [p0, p1, p2] = vertices;
+    let (edge1, edge2) = (p1 - p0, p2 - p0);
+    let b0 = 1.0 - b1 - b2;
+    let uv = b0 * uvs[0] + b1 * uvs[1] + b2 * uvs[2];
+    let geometric_normal = edge1.cross(edge2).normalize();
+    let (outward_normal, shading_normal) = match normals {
+        // The vertex normals say which side is out, whichever way the
+        // triangle winds.
+        Some([n0, n1, n2]) => (
+            geometric_normal * geometric_normal.dot(n0 + n1 + n2).signum(),
+            (b0 * n0 + b1 * n1 + b2 * n2).normalize(),
+        ),
+        None => (geometric_normal, geometric_normal),
+    };
+
+    let [dpdu, dpdv, dndu, dndv] = uv_derivatives(vertices, uvs, normals, (b1, b2));
+
+    let mut rec = HitRecord {
+        point: ray.at(t),
+        normal: shading_normal,
+        material,
+        t,
+        u: uv.x,
+        v: uv.y,
+        front_face: false,
+        object_id: 0,
+        epsilon: 0.0,
+        tangent: dpdu.normalize_or_zero(),
+        dpdu,
+        dpdv,
+        dndu,
+        dndv,
+        light: None,
+        barycentric: Some(DVec3::new(b0, b1, b2)),
+        color: colors.map(|[c0, c1, c2]| b0 * c0 + b1 * c1 + b2 * c2),
+    };
+    rec.set_shading_normal(ray, outward_normal, shading_normal);
+    rec
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let (origin, direction) = (to_real(ray.origin), to_real(ray.direction));
+        let hit = intersect(&self.vertices, origin, direction, interval)?;
+        Some(surface(
+            ray,
+            hit,
+            self.vertices(),
+            self.normals(),
+            self.uvs,
+            self.colors(),
+            &*self.material,
+        ))
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        if self.material.is_see_through() {
+            return self.hit(ray, interval).is_some_and(|rec| blocks(&rec));
+        }
+        let (origin, direction) = (to_real(ray.origin), to_real(ray.direction));
+        intersect(&self.vertices, origin, direction, interval).is_some()
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let [p0, p1, p2] = self.vertices();
+        let padding = DVec3::splat(1e-4);
+        Some(AABB::new(
+            p0.min(p1).min(p2) - padding,
+            p0.max(p1).max(p2) + padding,
+        ))
+    }
+
+    // Padded like the box.
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        let along = self.vertices().map(|p| axis.dot(p));
+        let padding = 1e-4 * axis.abs().element_sum();
+        Some((
+            along[0].min(along[1]).min(along[2]) - padding,
+            along[0].max(along[1]).max(along[2]) + padding,
+        ))
+    }
+}
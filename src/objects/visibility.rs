@@ -0,0 +1,70 @@
+use crate::hittable::{HitRecord, Hittable, AABB};
+use crate::interval::Interval;
+use crate::ray::{Ray, RayKind};
+use glam::DVec3;
+use std::sync::Arc;
+
+// Hides `object` from the kinds of ray it is turned off for: an object
+// the camera can't see still blocks light and shows in reflections, and
+// one that casts no shadows lets light samples straight through it.
+pub struct Visibility {
+    pub object: Arc<dyn Hittable>,
+    pub camera: bool,
+    pub shadows: bool,
+    pub reflections: bool,
+}
+
+impl Visibility {
+    pub fn new(object: Arc<dyn Hittable>) -> Self {
+        Self {
+            object,
+            camera: true,
+            shadows: true,
+            reflections: true,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: bool) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    pub fn with_shadows(mut self, shadows: bool) -> Self {
+        self.shadows = shadows;
+        self
+    }
+
+    pub fn with_reflections(mut self, reflections: bool) -> Self {
+        self.reflections = reflections;
+        self
+    }
+
+    fn visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Indirect => self.reflections,
+            RayKind::Shadow => self.shadows,
+        }
+    }
+}
+
+impl Hittable for Visibility {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        if !self.visible_to(ray.kind) {
+            return None;
+        }
+        self.object.hit(ray, interval)
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        self.visible_to(ray.kind) && self.object.hit_any(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.object.bounding_box()
+    }
+
+    fn extent(&self, axis: DVec3) -> Option<(f64, f64)> {
+        self.object.extent(axis)
+    }
+}
@@ -0,0 +1,730 @@
+use crate::camera::{Camera, CameraProjection};
+use crate::lut::Lut;
+use crate::renderer::{ImageBuffer, RenderPasses, RenderSettings};
+use crate::sampler::{mix_hash, to_unit};
+use crate::stamp::stamp_text;
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub enum ToneMapper {
+    // Clamps to [0, 1]; highlights burn out.
+    #[serde(rename = "linear")]
+    Linear,
+    #[serde(rename = "reinhard")]
+    Reinhard,
+    // Narkowicz's fit of the ACES reference rendering transform.
+    #[default]
+    #[serde(rename = "aces")]
+    AcesFilmic,
+}
+
+impl ToneMapper {
+    pub fn apply(self, color: DVec3) -> DVec3 {
+        let color = color.max(DVec3::ZERO);
+        match self {
+            ToneMapper::Linear => color.min(DVec3::ONE),
+            ToneMapper::Reinhard => color / (DVec3::ONE + color),
+            ToneMapper::AcesFilmic => {
+                let numerator = color * (2.51 * color + 0.03);
+                let denominator = color * (2.43 * color + 0.59) + 0.14;
+                (numerator / denominator).clamp(DVec3::ZERO, DVec3::ONE)
+            }
+        }
+    }
+}
+
+// Encoding from linear light to PNG code values. The matching colour
+// metadata is written alongside so viewers decode the file correctly.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum TransferFunction {
+    #[default]
+    #[serde(rename = "srgb")]
+    Srgb,
+    // Pure power law, as assumed by some older viewers.
+    #[serde(rename = "gamma_2.2")]
+    Gamma22,
+    #[serde(rename = "rec709")]
+    Rec709,
+    // SMPTE ST 2084 for HDR displays. Linear 1.0 maps to the 203 nit
+    // reference white of BT.2408, and the tone mapper is skipped so
+    // highlights keep their range up to 10000 nits.
+    #[serde(rename = "pq")]
+    Pq,
+    // No curve at all, for PNGs that are composited or graded further
+    // rather than viewed; wants 16 bits to avoid banding in the shadows.
+    #[serde(rename = "linear")]
+    Linear,
+}
+
+impl TransferFunction {
+    pub fn encode(self, c: f64) -> f64 {
+        match self {
+            TransferFunction::Srgb => linear_to_srgb(c),
+            TransferFunction::Gamma22 => c.clamp(0.0, 1.0).powf(1.0 / 2.2),
+            TransferFunction::Rec709 => {
+                let c = c.clamp(0.0, 1.0);
+                if c < 0.018 {
+                    4.5 * c
+                } else {
+                    1.099 * c.powf(0.45) - 0.099
+                }
+            }
+            TransferFunction::Pq => {
+                const M1: f64 = 2610.0 / 16384.0;
+                const M2: f64 = 2523.0 / 4096.0 * 128.0;
+                const C1: f64 = 3424.0 / 4096.0;
+                const C2: f64 = 2413.0 / 4096.0 * 32.0;
+                const C3: f64 = 2392.0 / 4096.0 * 32.0;
+                let y = (c * 203.0 / 10000.0).clamp(0.0, 1.0).powf(M1);
+                ((C1 + C2 * y) / (1.0 + C3 * y)).powf(M2)
+            }
+            TransferFunction::Linear => c.clamp(0.0, 1.0),
+        }
+    }
+
+    fn is_hdr(self) -> bool {
+        self == TransferFunction::Pq
+    }
+
+    // The cICP code point of the transfer characteristic (ITU-T H.273).
+    fn cicp_code(self) -> u8 {
+        match self {
+            TransferFunction::Srgb => 13,
+            TransferFunction::Gamma22 => 4,
+            TransferFunction::Rec709 => 1,
+            TransferFunction::Pq => 16,
+            TransferFunction::Linear => 8,
+        }
+    }
+
+    // PNG chunks describing the encoding, inserted after the header. cICP is
+    // authoritative for newer decoders; sRGB and gAMA cover older ones.
+    fn png_chunks(self) -> Vec<([u8; 4], Vec<u8>)> {
+        // BT.709 primaries, RGB (no matrix), full range.
+        let mut chunks = vec![(*b"cICP", vec![1, self.cicp_code(), 0, 1])];
+        match self {
+            // Perceptual rendering intent.
+            TransferFunction::Srgb => chunks.push((*b"sRGB", vec![0])),
+            TransferFunction::Gamma22 => chunks.push((*b"gAMA", 45455u32.to_be_bytes().to_vec())),
+            TransferFunction::Linear => chunks.push((*b"gAMA", 100000u32.to_be_bytes().to_vec())),
+            TransferFunction::Rec709 | TransferFunction::Pq => {}
+        }
+        chunks
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum BitDepth {
+    #[default]
+    #[serde(rename = "8")]
+    Eight,
+    #[serde(rename = "16")]
+    Sixteen,
+}
+
+impl BitDepth {
+    fn max_value(self) -> f64 {
+        match self {
+            BitDepth::Eight => 255.0,
+            BitDepth::Sixteen => 65535.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    // Only applied to low dynamic range formats; EXR and HDR keep the
+    // scene-referred values.
+    pub tone_mapper: ToneMapper,
+    // In stops; applied to every format.
+    pub exposure: f64,
+    // Further exposures, in stops from `exposure`, each written beside the
+    // output as `<stem>.ev<offset>.<ext>`, e.g. `frame.ev-2.png`, for
+    // picking an exposure later or merging into HDR.
+    pub brackets: Vec<f64>,
+    // Bits per channel of PNG output.
+    pub bit_depth: BitDepth,
+    pub transfer: TransferFunction,
+    // Adds triangular noise of one code value before quantising PNG output,
+    // trading the banding in smooth gradients for fine grain.
+    pub dither: bool,
+    // Burns the render metadata into the bottom-left corner of PNG output.
+    pub stamp: bool,
+    // A `.cube` file applied to PNG output after encoding, so it maps
+    // display code values to graded ones as a grading tool's LUT would.
+    pub lut: Option<String>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            tone_mapper: ToneMapper::default(),
+            exposure: 0.0,
+            brackets: Vec::new(),
+            bit_depth: BitDepth::default(),
+            transfer: TransferFunction::default(),
+            dither: true,
+            stamp: false,
+            lut: None,
+        }
+    }
+}
+
+// Provenance of a render, written into PNG text chunks and the HDR header
+// so farm outputs can be traced back to the scene and settings that made
+// them. EXR output has no metadata, as the image encoder can't write custom
+// attributes.
+#[derive(Clone, Debug)]
+pub struct RenderMetadata {
+    // `scene_hash` of the scene file's contents.
+    pub scene_hash: Option<u64>,
+    pub seed: u64,
+    pub samples_per_pixel: u32,
+    pub render_time: Duration,
+    // Pixel width over height; also written as a PNG pHYs chunk and the
+    // Radiance PIXASPECT header so viewers display the image unsqueezed.
+    pub pixel_aspect_ratio: f64,
+    // Set for 360° renders, which PNG output marks with the XMP tags of
+    // Google's photo sphere and spherical video formats, so viewers and
+    // YouTube's VR metadata tools wrap them around the viewer.
+    pub panorama: Option<Panorama>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Panorama {
+    Mono,
+    // Left eye above, right eye below.
+    TopBottom,
+}
+
+impl RenderMetadata {
+    pub fn new(settings: &RenderSettings, render_time: Duration) -> Self {
+        Self {
+            scene_hash: None,
+            seed: settings.seed,
+            samples_per_pixel: settings
+                .adaptive
+                .map_or(settings.samples_per_pixel, |a| a.max_samples),
+            render_time,
+            pixel_aspect_ratio: settings.pixel_aspect_ratio,
+            panorama: None,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: &Camera) -> Self {
+        self.panorama = match camera.projection {
+            CameraProjection::Equirectangular => Some(Panorama::Mono),
+            CameraProjection::OmniStereo { .. } => Some(Panorama::TopBottom),
+            _ => None,
+        };
+        self
+    }
+
+    fn square_pixels(&self) -> bool {
+        (self.pixel_aspect_ratio - 1.0).abs() < 1e-9
+    }
+
+    pub fn with_scene(mut self, scene: &[u8]) -> Self {
+        self.scene_hash = Some(scene_hash(scene));
+        self
+    }
+
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![(
+            "Software",
+            format!("raytracer {}", env!("CARGO_PKG_VERSION")),
+        )];
+        if let Some(hash) = self.scene_hash {
+            entries.push(("Scene hash", format!("{hash:016x}")));
+        }
+        entries.push(("Seed", self.seed.to_string()));
+        entries.push(("Samples", self.samples_per_pixel.to_string()));
+        entries.push((
+            "Render time",
+            format!("{:.1}s", self.render_time.as_secs_f64()),
+        ));
+        if !self.square_pixels() {
+            entries.push(("Pixel aspect ratio", self.pixel_aspect_ratio.to_string()));
+        }
+        entries
+    }
+
+    // One line for the visible stamp.
+    fn summary(&self) -> String {
+        self.entries()
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+// 64-bit FNV-1a; stable across platforms and releases, unlike `DefaultHasher`.
+pub fn scene_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+// Accumulates weighted samples per pixel; `resolve` produces the averaged
+// image. Films from separate passes can be merged before resolving.
+#[derive(Clone)]
+pub struct Film {
+    pub width: u32,
+    pub height: u32,
+    sum: Vec<DVec3>,
+    weight: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Self {
+            width,
+            height,
+            sum: vec![DVec3::ZERO; len],
+            weight: vec![0.0; len],
+        }
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, color: DVec3, weight: f64) {
+        let i = (y * self.width + x) as usize;
+        self.sum[i] += weight * color;
+        self.weight[i] += weight;
+    }
+
+    pub fn merge(&mut self, other: &Film) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "cannot merge films of different sizes"
+        );
+        for (sum, s) in self.sum.iter_mut().zip(&other.sum) {
+            *sum += *s;
+        }
+        for (weight, w) in self.weight.iter_mut().zip(&other.weight) {
+            *weight += *w;
+        }
+    }
+
+    pub fn resolve(&self) -> ImageBuffer {
+        let mut image = ImageBuffer::new(self.width, self.height);
+        for (pixel, (sum, weight)) in image
+            .pixels
+            .iter_mut()
+            .zip(self.sum.iter().zip(&self.weight))
+        {
+            if *weight > 0.0 {
+                *pixel = *sum / *weight;
+            }
+        }
+        image
+    }
+}
+
+// Writes `image` in the format implied by the file extension: `png` (8 or
+// 16-bit, tone mapped and encoded with `options.transfer`), `exr` (32-bit
+// float) or `hdr` (Radiance RGBE), then its `options.brackets`. PNG and EXR
+// keep the image's alpha channel; HDR has none.
+pub fn save(
+    image: &ImageBuffer,
+    path: &Path,
+    options: &OutputOptions,
+    metadata: Option<&RenderMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    save_exposure(image, path, options, metadata)?;
+    for offset in &options.brackets {
+        let bracket = OutputOptions {
+            exposure: options.exposure + offset,
+            ..options.clone()
+        };
+        save_exposure(image, &bracket_path(path, *offset)?, &bracket, metadata)?;
+    }
+    Ok(())
+}
+
+// `frame.png` becomes `frame.ev+2.png` for an offset of 2 stops.
+pub fn bracket_path(path: &Path, offset: f64) -> Result<PathBuf, Box<dyn Error>> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("output path has no file name")?;
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{stem}.ev{offset:+}.{extension}"),
+        None => format!("{stem}.ev{offset:+}"),
+    };
+    Ok(path.with_file_name(name))
+}
+
+fn save_exposure(
+    image: &ImageBuffer,
+    path: &Path,
+    options: &OutputOptions,
+    metadata: Option<&RenderMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => save_png(image, path, options, metadata),
+        Some("exr") => save_exr(image, path, options),
+        Some("hdr") => save_hdr(image, path, options, metadata),
+        _ => Err(format!("unsupported output format: {}", path.display()).into()),
+    }
+}
+
+// Saves the beauty image to `path` and every auxiliary pass next to it as
+// `<stem>.<pass>.exr`, e.g. `frame.png` and `frame.albedo.exr`. The passes
+// are data rather than pictures, so they skip exposure and tone mapping.
+pub fn save_passes(
+    passes: &RenderPasses,
+    path: &Path,
+    options: &OutputOptions,
+    metadata: Option<&RenderMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    save(&passes.beauty, path, options, metadata)?;
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("output path has no file name")?;
+    let raw = OutputOptions {
+        exposure: 0.0,
+        ..options.clone()
+    };
+    for (name, image) in passes.aovs() {
+        save_exr(
+            image,
+            &path.with_file_name(format!("{stem}.{name}.exr")),
+            &raw,
+        )?;
+    }
+    Ok(())
+}
+
+pub fn save_png(
+    image: &ImageBuffer,
+    path: &Path,
+    options: &OutputOptions,
+    metadata: Option<&RenderMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    let scale = exposure_scale(options.exposure);
+    let max = options.bit_depth.max_value();
+    let lut = options.lut.as_deref().map(Lut::load).transpose()?;
+    let mut values = Vec::with_capacity(image.pixels.len() * 4);
+    for i in 0..image.pixels.len() {
+        let mut encoded = display(image, i, scale, options);
+        if let Some(lut) = &lut {
+            encoded = lut.apply(encoded);
+        }
+        for (channel, c) in encoded.to_array().into_iter().enumerate() {
+            let noise = if options.dither {
+                triangular_noise(3 * i as u64 + channel as u64)
+            } else {
+                0.0
+            };
+            values.push((max * c + 0.5 + noise).clamp(0.0, max) as u16);
+        }
+    }
+    if let Some(metadata) = metadata.filter(|_| options.stamp) {
+        stamp_text(
+            &mut values,
+            image.width,
+            image.height,
+            max as u16,
+            &metadata.summary(),
+        );
+    }
+    if let Some(alpha) = &image.alpha {
+        values = values
+            .chunks(3)
+            .zip(alpha)
+            .flat_map(|(rgb, a)| {
+                [
+                    rgb[0],
+                    rgb[1],
+                    rgb[2],
+                    (max * a.clamp(0.0, 1.0) + 0.5) as u16,
+                ]
+            })
+            .collect();
+    }
+    let (width, height) = (image.width, image.height);
+    let wrong_size = "image buffer has the wrong size";
+    let buffer = match (options.bit_depth, image.alpha.is_some()) {
+        (BitDepth::Eight, false) => {
+            let bytes = values.into_iter().map(|v| v as u8).collect();
+            image::DynamicImage::ImageRgb8(
+                image::RgbImage::from_raw(width, height, bytes).ok_or(wrong_size)?,
+            )
+        }
+        (BitDepth::Eight, true) => {
+            let bytes = values.into_iter().map(|v| v as u8).collect();
+            image::DynamicImage::ImageRgba8(
+                image::RgbaImage::from_raw(width, height, bytes).ok_or(wrong_size)?,
+            )
+        }
+        (BitDepth::Sixteen, false) => image::DynamicImage::ImageRgb16(
+            image::ImageBuffer::from_raw(width, height, values).ok_or(wrong_size)?,
+        ),
+        (BitDepth::Sixteen, true) => image::DynamicImage::ImageRgba16(
+            image::ImageBuffer::from_raw(width, height, values).ok_or(wrong_size)?,
+        ),
+    };
+    let mut png = Cursor::new(Vec::new());
+    buffer.write_to(&mut png, image::ImageFormat::Png)?;
+    let mut chunks = options.transfer.png_chunks();
+    if let Some(metadata) = metadata.filter(|m| !m.square_pixels()) {
+        chunks.push((*b"pHYs", physical_pixel_chunk(metadata.pixel_aspect_ratio)));
+    }
+    for (key, value) in metadata.map(RenderMetadata::entries).unwrap_or_default() {
+        // tEXt: Latin-1 keyword, a null separator, then the text.
+        let mut data = key.as_bytes().to_vec();
+        data.push(0);
+        data.extend(
+            value
+                .chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'?' }),
+        );
+        chunks.push((*b"tEXt", data));
+    }
+    if let Some(panorama) = metadata.and_then(|m| m.panorama) {
+        // iTXt: keyword, null, no compression, empty language and
+        // translated keyword, then UTF-8 text.
+        let mut data = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+        data.extend(panorama_xmp(panorama, image.width, image.height).bytes());
+        chunks.push((*b"iTXt", data));
+    }
+    let png = insert_png_chunks(png.into_inner(), &chunks)?;
+    std::fs::write(path, png)?;
+    Ok(())
+}
+
+// XMP packet marking an equirectangular image, each eye's half of it for
+// stereo, as covering the full sphere.
+fn panorama_xmp(panorama: Panorama, width: u32, height: u32) -> String {
+    let (eye_height, stereo_mode) = match panorama {
+        Panorama::Mono => (height, "mono"),
+        Panorama::TopBottom => (height / 2, "top-bottom"),
+    };
+    format!(
+        concat!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            "<rdf:Description rdf:about=\"\"",
+            " xmlns:GPano=\"http://ns.google.com/photos/1.0/panorama/\"",
+            " xmlns:GSpherical=\"http://ns.google.com/videos/1.0/spherical/\"",
+            " GPano:ProjectionType=\"equirectangular\"",
+            " GPano:UsePanoramaViewer=\"True\"",
+            " GPano:FullPanoWidthPixels=\"{width}\"",
+            " GPano:FullPanoHeightPixels=\"{eye_height}\"",
+            " GPano:CroppedAreaImageWidthPixels=\"{width}\"",
+            " GPano:CroppedAreaImageHeightPixels=\"{eye_height}\"",
+            " GPano:CroppedAreaLeftPixels=\"0\"",
+            " GPano:CroppedAreaTopPixels=\"0\"",
+            " GSpherical:Spherical=\"true\"",
+            " GSpherical:Stitched=\"true\"",
+            " GSpherical:ProjectionType=\"equirectangular\"",
+            " GSpherical:StereoMode=\"{stereo_mode}\"/>",
+            "</rdf:RDF></x:xmpmeta>"
+        ),
+        width = width,
+        eye_height = eye_height,
+        stereo_mode = stereo_mode,
+    )
+}
+
+// pHYs with unit 0 only gives the pixel shape: pixels per unit along X and
+// Y, whose ratio Y/X is the pixel aspect ratio.
+fn physical_pixel_chunk(pixel_aspect_ratio: f64) -> Vec<u8> {
+    const X: u32 = 10_000;
+    let y = (X as f64 * pixel_aspect_ratio)
+        .round()
+        .clamp(1.0, u32::MAX as f64) as u32;
+    let mut data = X.to_be_bytes().to_vec();
+    data.extend_from_slice(&y.to_be_bytes());
+    data.push(0);
+    data
+}
+
+// Inserts ancillary chunks straight after IHDR, where colour metadata has to
+// come before the image data.
+fn insert_png_chunks(
+    png: Vec<u8>,
+    chunks: &[([u8; 4], Vec<u8>)],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // 8 byte signature, then IHDR: length, type, 13 bytes of data, CRC.
+    const HEADER_END: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < HEADER_END || &png[12..16] != b"IHDR" {
+        return Err("encoder produced an unexpected PNG layout".into());
+    }
+    let mut out = Vec::with_capacity(png.len() + 64);
+    out.extend_from_slice(&png[..HEADER_END]);
+    for (kind, data) in chunks {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+    out.extend_from_slice(&png[HEADER_END..]);
+    Ok(out)
+}
+
+// CRC-32 as used by PNG (reflected polynomial 0xEDB88320).
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+// Noise in (-1, 1) code values with a triangular distribution, the sum of
+// two uniforms. Hashed from the sample index so output is reproducible.
+fn triangular_noise(index: u64) -> f64 {
+    let h = mix_hash(index);
+    to_unit(h) + to_unit(mix_hash(h)) - 1.0
+}
+
+// 8-bit RGBA of `image`, rows top to bottom, as an HTML canvas takes it.
+// Pixels are exposed, tone mapped and encoded as `save_png` encodes them;
+// LUTs, which are read from files, dithering and stamps are left out.
+// Images rendered without transparency are opaque.
+pub fn to_rgba8(image: &ImageBuffer, options: &OutputOptions) -> Vec<u8> {
+    let scale = exposure_scale(options.exposure);
+    let mut bytes = Vec::with_capacity(image.pixels.len() * 4);
+    for i in 0..image.pixels.len() {
+        let coverage = image.alpha.as_ref().map_or(1.0, |alpha| alpha[i]);
+        let encoded = display(image, i, scale, options).extend(coverage.clamp(0.0, 1.0));
+        for c in encoded.to_array() {
+            bytes.push((255.0 * c + 0.5).clamp(0.0, 255.0) as u8);
+        }
+    }
+    bytes
+}
+
+// Pixel `i` of `image` encoded for display, before any LUT: unpremultiplied,
+// since PNG and canvas colours aren't premultiplied by alpha, then exposed
+// by `scale`, tone mapped and encoded with the transfer function.
+fn display(image: &ImageBuffer, i: usize, scale: f64, options: &OutputOptions) -> DVec3 {
+    let color = image.pixels[i];
+    let coverage = image.alpha.as_ref().map_or(1.0, |alpha| alpha[i]);
+    let straight = if coverage > 0.0 {
+        color / coverage
+    } else {
+        color
+    };
+    let exposed = finite_or_zero(straight) * scale;
+    let mapped = if options.transfer.is_hdr() {
+        exposed
+    } else {
+        options.tone_mapper.apply(exposed)
+    };
+    DVec3::from_array(mapped.to_array().map(|c| options.transfer.encode(c)))
+}
+
+pub fn save_exr(
+    image: &ImageBuffer,
+    path: &Path,
+    options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    let scale = exposure_scale(options.exposure);
+    let colors = image
+        .pixels
+        .iter()
+        .map(|c| (finite_or_zero(*c) * scale).as_vec3());
+    // EXR colours stay premultiplied by alpha.
+    match &image.alpha {
+        Some(alpha) => {
+            let data: Vec<f32> = colors
+                .zip(alpha)
+                .flat_map(|(c, a)| c.extend(*a as f32).to_array())
+                .collect();
+            let buffer = image::Rgba32FImage::from_raw(image.width, image.height, data)
+                .ok_or("image buffer has the wrong size")?;
+            buffer.save_with_format(path, image::ImageFormat::OpenExr)?;
+        }
+        None => {
+            let data: Vec<f32> = colors.flat_map(|c| c.to_array()).collect();
+            let buffer = image::Rgb32FImage::from_raw(image.width, image.height, data)
+                .ok_or("image buffer has the wrong size")?;
+            buffer.save_with_format(path, image::ImageFormat::OpenExr)?;
+        }
+    }
+    Ok(())
+}
+
+// Uncompressed RGBE scanlines, which every Radiance reader accepts.
+pub fn save_hdr(
+    image: &ImageBuffer,
+    path: &Path,
+    options: &OutputOptions,
+    metadata: Option<&RenderMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    let scale = exposure_scale(options.exposure);
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "#?RADIANCE")?;
+    // Readers skip header lines starting with '#'.
+    for (key, value) in metadata.map(RenderMetadata::entries).unwrap_or_default() {
+        writeln!(out, "# {key}: {value}")?;
+    }
+    // Radiance gives the pixel aspect as height over width.
+    if let Some(metadata) = metadata.filter(|m| !m.square_pixels()) {
+        writeln!(out, "PIXASPECT={}", 1.0 / metadata.pixel_aspect_ratio)?;
+    }
+    write!(
+        out,
+        "FORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height, image.width
+    )?;
+    for color in &image.pixels {
+        out.write_all(&to_rgbe(finite_or_zero(*color) * scale))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn to_rgbe(color: DVec3) -> [u8; 4] {
+    let max = color.max_element();
+    if max < 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1).
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let c = (color.max(DVec3::ZERO) * scale).min(DVec3::splat(255.0));
+    [c.x as u8, c.y as u8, c.z as u8, (exponent + 128) as u8]
+}
+
+pub(crate) fn exposure_scale(exposure: f64) -> f64 {
+    2f64.powf(exposure)
+}
+
+fn finite_or_zero(color: DVec3) -> DVec3 {
+    if color.is_finite() {
+        color
+    } else {
+        DVec3::ZERO
+    }
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
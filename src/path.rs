@@ -0,0 +1,115 @@
+use glam::DVec3;
+
+const SAMPLES_PER_SEGMENT: usize = 64;
+
+pub struct CatmullRom {
+    points: Vec<DVec3>,
+    closed: bool,
+    arc_lengths: Vec<f64>,
+}
+
+impl CatmullRom {
+    pub fn new(points: Vec<DVec3>, closed: bool) -> Self {
+        assert!(points.len() >= 2, "a path needs at least two points");
+        let mut path = Self {
+            points,
+            closed,
+            arc_lengths: Vec::new(),
+        };
+
+        let samples = path.segment_count() * SAMPLES_PER_SEGMENT;
+        let mut arc_lengths = Vec::with_capacity(samples + 1);
+        let mut previous = path.evaluate(0.0);
+        let mut total = 0.0;
+        arc_lengths.push(0.0);
+        for i in 1..=samples {
+            let p = path.evaluate(i as f64 / SAMPLES_PER_SEGMENT as f64);
+            total += (p - previous).length();
+            arc_lengths.push(total);
+            previous = p;
+        }
+        path.arc_lengths = arc_lengths;
+        path
+    }
+
+    pub fn length(&self) -> f64 {
+        *self.arc_lengths.last().unwrap()
+    }
+
+    // `s` is the normalized distance along the path, so equal steps in `s`
+    // cover equal distances regardless of how the control points are spaced.
+    pub fn position_at(&self, s: f64) -> DVec3 {
+        self.evaluate(self.parameter_at(s))
+    }
+
+    pub fn tangent_at(&self, s: f64) -> DVec3 {
+        let u = self.parameter_at(s);
+        let h = 1e-4;
+        let max = self.segment_count() as f64;
+        let (a, b) = if self.closed {
+            (u - h, u + h)
+        } else {
+            ((u - h).max(0.0), (u + h).min(max))
+        };
+        (self.evaluate(b) - self.evaluate(a)).normalize_or_zero()
+    }
+
+    fn segment_count(&self) -> usize {
+        if self.closed {
+            self.points.len()
+        } else {
+            self.points.len() - 1
+        }
+    }
+
+    fn parameter_at(&self, s: f64) -> f64 {
+        let s = if self.closed {
+            s.rem_euclid(1.0)
+        } else {
+            s.clamp(0.0, 1.0)
+        };
+        let target = s * self.length();
+        let i = self.arc_lengths.partition_point(|&l| l < target);
+        if i == 0 {
+            return 0.0;
+        }
+        let (l0, l1) = (self.arc_lengths[i - 1], self.arc_lengths[i]);
+        let fraction = if l1 > l0 {
+            (target - l0) / (l1 - l0)
+        } else {
+            0.0
+        };
+        (i - 1) as f64 / SAMPLES_PER_SEGMENT as f64 + fraction / SAMPLES_PER_SEGMENT as f64
+    }
+
+    fn control_point(&self, i: isize) -> DVec3 {
+        let n = self.points.len() as isize;
+        if self.closed {
+            self.points[i.rem_euclid(n) as usize]
+        } else if i < 0 {
+            2.0 * self.points[0] - self.points[1]
+        } else if i >= n {
+            2.0 * self.points[(n - 1) as usize] - self.points[(n - 2) as usize]
+        } else {
+            self.points[i as usize]
+        }
+    }
+
+    fn evaluate(&self, u: f64) -> DVec3 {
+        let segment = (u.floor() as isize)
+            .min(self.segment_count() as isize - 1)
+            .max(0);
+        let t = u - segment as f64;
+        let p0 = self.control_point(segment - 1);
+        let p1 = self.control_point(segment);
+        let p2 = self.control_point(segment + 1);
+        let p3 = self.control_point(segment + 2);
+
+        let t2 = t * t;
+        let t3 = t2 * t;
+        0.5 * ((2.0 * p1)
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+    }
+}
@@ -0,0 +1,175 @@
+use crate::hittable::{hit_surface, HitRecord, Hittable, DEFAULT_EPSILON};
+use crate::interval::Interval;
+use crate::lights::LightSet;
+use crate::ray::{Ray, RayKind};
+use crate::sampler::{mix_hash, IndependentSampler, Sampler};
+use glam::DVec3;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+// Photons traced per parallel batch, each batch with its own sampler.
+const BATCH: u32 = 4096;
+
+// Light that reached a diffuse surface through mirrors and glass, stored
+// where it landed.
+#[derive(Clone, Copy, Debug)]
+pub struct Photon {
+    pub position: DVec3,
+    // Unit direction the photon was travelling in.
+    pub direction: DVec3,
+    // Share of the lights' flux the photon carries.
+    pub power: DVec3,
+}
+
+// Caustic photon map: photons traced from the lights through specular
+// bounces to the first diffuse surface, binned in a hash grid of cells
+// `radius` wide. Camera paths gather them to light caustics that they
+// could only find by chance. Photons pass through index-matched surfaces
+// without entering their media, and are traced at the mean index of
+// dispersive glass.
+pub struct PhotonMap {
+    radius: f64,
+    cells: HashMap<[i64; 3], Vec<Photon>>,
+    len: usize,
+}
+
+impl PhotonMap {
+    // Emits `photons` photons from `lights` into `world`, following each
+    // through at most `max_depth` bounces. The same `seed` gives the same
+    // map.
+    pub fn build(
+        world: &dyn Hittable,
+        lights: &LightSet,
+        photons: u32,
+        radius: f64,
+        max_depth: u32,
+        seed: u64,
+    ) -> Self {
+        let scene = world.bounding_box();
+        let batches = photons.div_ceil(BATCH);
+        let trace_batch = |batch: u32| {
+            let mut sampler = IndependentSampler::new(mix_hash(seed ^ batch as u64));
+            let mut stored = Vec::new();
+            let count = BATCH.min(photons - batch * BATCH);
+            for _ in 0..count {
+                let u = sampler.next_1d();
+                let uv = sampler.next_2d();
+                let direction = sampler.next_2d();
+                if let Some((ray, flux)) = lights.emit(scene, u, uv, direction) {
+                    let power = flux / photons as f64;
+                    trace(world, ray, power, max_depth, &mut sampler, &mut stored);
+                }
+            }
+            stored
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let stored = (0..batches).into_par_iter().flat_map_iter(trace_batch);
+        #[cfg(target_arch = "wasm32")]
+        let stored = (0..batches).flat_map(trace_batch);
+        let stored: Vec<Photon> = stored.collect();
+
+        let radius = radius.max(1e-6);
+        let mut map = Self {
+            radius,
+            cells: HashMap::new(),
+            len: stored.len(),
+        };
+        for photon in stored {
+            let cell = map.cell(photon.position);
+            map.cells.entry(cell).or_default().push(photon);
+        }
+        map
+    }
+
+    // Number of photons stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Caustic radiance leaving the hit towards the start of `ray`: the
+    // photons within `radius` of it, weighted by the BSDF and spread over
+    // the disc they were gathered from.
+    pub fn radiance(&self, ray: &Ray, rec: &HitRecord) -> DVec3 {
+        let radius_squared = self.radius * self.radius;
+        let [x, y, z] = self.cell(rec.point);
+        let mut radiance = DVec3::ZERO;
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let Some(photons) = self.cells.get(&[x + dx, y + dy, z + dz]) else {
+                        continue;
+                    };
+                    for photon in photons {
+                        if photon.position.distance_squared(rec.point) >= radius_squared {
+                            continue;
+                        }
+                        // `eval` includes the cosine, which the density of
+                        // the photons already accounts for.
+                        let incoming = -photon.direction;
+                        let cosine = rec.normal.dot(incoming);
+                        if cosine <= 1e-6 {
+                            continue;
+                        }
+                        if let Some(f) = rec.material.eval(ray, rec, incoming) {
+                            radiance += f / cosine * photon.power;
+                        }
+                    }
+                }
+            }
+        }
+        radiance / (PI * radius_squared)
+    }
+
+    fn cell(&self, point: DVec3) -> [i64; 3] {
+        let cell = (point / self.radius).floor();
+        [cell.x as i64, cell.y as i64, cell.z as i64]
+    }
+}
+
+// Follows one photon through specular bounces, storing it at the first
+// diffuse surface if it got there by way of a mirror or glass.
+fn trace(
+    world: &dyn Hittable,
+    mut ray: Ray,
+    mut power: DVec3,
+    max_depth: u32,
+    sampler: &mut dyn Sampler,
+    stored: &mut Vec<Photon>,
+) {
+    ray.kind = RayKind::Indirect;
+    let mut t_min = DEFAULT_EPSILON;
+    let mut caustic = false;
+    for _ in 0..max_depth {
+        let Some(rec) = hit_surface(world, &ray, Interval::after(t_min)) else {
+            return;
+        };
+        let crossing = rec.material.is_index_matched();
+        let specular = rec.material.eval(&ray, &rec, rec.normal).is_none();
+        if !specular && !crossing {
+            if caustic {
+                stored.push(Photon {
+                    position: rec.point,
+                    direction: ray.direction.normalize(),
+                    power,
+                });
+            }
+            return;
+        }
+        let Some((scattered, attenuation)) = rec.material.scatter(&ray, &rec, sampler) else {
+            return;
+        };
+        power *= attenuation;
+        if power == DVec3::ZERO {
+            return;
+        }
+        caustic |= !crossing;
+        ray = rec.spawn_scattered(&ray, &scattered);
+        t_min = rec.ray_epsilon();
+    }
+}
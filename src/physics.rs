@@ -0,0 +1,128 @@
+use crate::hittable::AABB;
+use glam::{DAffine3, DQuat, DVec3};
+use rapier3d::prelude::*;
+
+pub enum BodyShape {
+    Ball(f64),
+    Cuboid(DVec3),
+}
+
+pub struct DropBody {
+    pub center: DVec3,
+    pub shape: BodyShape,
+}
+
+pub struct SettleSettings {
+    pub ground_height: f64,
+    pub max_steps: usize,
+}
+
+impl Default for SettleSettings {
+    fn default() -> Self {
+        Self {
+            ground_height: 0.0,
+            max_steps: 2000,
+        }
+    }
+}
+
+// Simulates `bodies` falling onto the ground plane and the static
+// `obstacles` until every body is asleep (or `max_steps` is reached), and
+// returns the world-space transform that moves each body from its declared
+// pose to its resting pose.
+pub fn settle(bodies: &[DropBody], obstacles: &[AABB], settings: &SettleSettings) -> Vec<DAffine3> {
+    let mut rigid_body_set = RigidBodySet::new();
+    let mut collider_set = ColliderSet::new();
+
+    collider_set.insert(
+        ColliderBuilder::halfspace(Vector::y_axis())
+            .translation(vector![0.0, settings.ground_height as Real, 0.0])
+            .build(),
+    );
+
+    for obstacle in obstacles {
+        let half = (obstacle.max - obstacle.min) / 2.0;
+        let center = (obstacle.min + obstacle.max) / 2.0;
+        collider_set.insert(
+            ColliderBuilder::cuboid(half.x as Real, half.y as Real, half.z as Real)
+                .translation(vector![
+                    center.x as Real,
+                    center.y as Real,
+                    center.z as Real
+                ])
+                .build(),
+        );
+    }
+
+    let handles: Vec<RigidBodyHandle> = bodies
+        .iter()
+        .map(|body| {
+            let rigid_body = RigidBodyBuilder::dynamic()
+                .translation(vector![
+                    body.center.x as Real,
+                    body.center.y as Real,
+                    body.center.z as Real
+                ])
+                .build();
+            let handle = rigid_body_set.insert(rigid_body);
+            let collider = match body.shape {
+                BodyShape::Ball(radius) => ColliderBuilder::ball(radius as Real),
+                BodyShape::Cuboid(half) => {
+                    ColliderBuilder::cuboid(half.x as Real, half.y as Real, half.z as Real)
+                }
+            }
+            .restitution(0.1)
+            .friction(0.8)
+            .build();
+            collider_set.insert_with_parent(collider, handle, &mut rigid_body_set);
+            handle
+        })
+        .collect();
+
+    let gravity = vector![0.0, -9.81, 0.0];
+    let integration_parameters = IntegrationParameters::default();
+    let mut physics_pipeline = PhysicsPipeline::new();
+    let mut island_manager = IslandManager::new();
+    let mut broad_phase = DefaultBroadPhase::new();
+    let mut narrow_phase = NarrowPhase::new();
+    let mut impulse_joint_set = ImpulseJointSet::new();
+    let mut multibody_joint_set = MultibodyJointSet::new();
+    let mut ccd_solver = CCDSolver::new();
+    let mut query_pipeline = QueryPipeline::new();
+
+    for _ in 0..settings.max_steps {
+        physics_pipeline.step(
+            &gravity,
+            &integration_parameters,
+            &mut island_manager,
+            &mut broad_phase,
+            &mut narrow_phase,
+            &mut rigid_body_set,
+            &mut collider_set,
+            &mut impulse_joint_set,
+            &mut multibody_joint_set,
+            &mut ccd_solver,
+            Some(&mut query_pipeline),
+            &(),
+            &(),
+        );
+
+        if handles.iter().all(|h| rigid_body_set[*h].is_sleeping()) {
+            break;
+        }
+    }
+
+    bodies
+        .iter()
+        .zip(&handles)
+        .map(|(body, handle)| {
+            let position = rigid_body_set[*handle].position();
+            let t = position.translation.vector;
+            let r = position.rotation;
+            let rotation = DQuat::from_xyzw(r.i as f64, r.j as f64, r.k as f64, r.w as f64);
+            let translation = DVec3::new(t.x as f64, t.y as f64, t.z as f64);
+            DAffine3::from_rotation_translation(rotation, translation)
+                * DAffine3::from_translation(-body.center)
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+use std::ops::Range;
+
+// Real roots of the polynomial sum(coefficients[i] * t^i) inside `interval`,
+// in ascending order. Roots are isolated between the critical points (the
+// roots of the derivative, found recursively) and refined by bisection, which
+// stays robust where closed-form quartic solutions lose precision, e.g. for
+// rays grazing a torus.
+pub fn real_roots(coefficients: &[f64], interval: Range<f64>) -> Vec<f64> {
+    let coefficients = trim(coefficients);
+    let degree = coefficients.len().saturating_sub(1);
+    if degree == 0 {
+        return Vec::new();
+    }
+    // Cauchy's bound keeps infinite intervals finite.
+    let lead = coefficients[degree];
+    let bound = 1.0
+        + coefficients[..degree]
+            .iter()
+            .map(|c| (c / lead).abs())
+            .fold(0.0, f64::max);
+    let (lo, hi) = (interval.start.max(-bound), interval.end.min(bound));
+    if lo >= hi {
+        return Vec::new();
+    }
+    if degree == 1 {
+        let t = -coefficients[0] / coefficients[1];
+        return if (lo..hi).contains(&t) {
+            vec![t]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let derivative: Vec<f64> = coefficients[1..]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i + 1) as f64 * c)
+        .collect();
+    let mut breakpoints = vec![lo];
+    breakpoints.extend(real_roots(&derivative, lo..hi));
+    breakpoints.push(hi);
+
+    let scale = coefficients.iter().map(|c| c.abs()).fold(0.0, f64::max);
+    let mut roots: Vec<f64> = Vec::with_capacity(degree);
+    for pair in breakpoints.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let (fa, fb) = (evaluate(coefficients, a), evaluate(coefficients, b));
+        let root = if fa == 0.0 {
+            Some(a)
+        } else if fa.signum() != fb.signum() {
+            Some(bisect(coefficients, a, b, fa))
+        } else if a > lo && fa.abs() <= 1e-12 * scale {
+            // A critical point touching zero: a double root.
+            Some(a)
+        } else {
+            None
+        };
+        if let Some(t) = root.filter(|t| (lo..hi).contains(t)) {
+            if !roots.last().is_some_and(|last| t <= *last) {
+                roots.push(t);
+            }
+        }
+    }
+    roots
+}
+
+pub fn evaluate(coefficients: &[f64], t: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |sum, c| sum * t + c)
+}
+
+// Drops leading coefficients that are negligible next to the largest one.
+fn trim(coefficients: &[f64]) -> &[f64] {
+    let scale = coefficients.iter().map(|c| c.abs()).fold(0.0, f64::max);
+    let mut end = coefficients.len();
+    while end > 0 && coefficients[end - 1].abs() <= 1e-14 * scale {
+        end -= 1;
+    }
+    &coefficients[..end]
+}
+
+fn bisect(coefficients: &[f64], mut a: f64, mut b: f64, mut fa: f64) -> f64 {
+    for _ in 0..100 {
+        let mid = 0.5 * (a + b);
+        if mid <= a || mid >= b {
+            break;
+        }
+        let fm = evaluate(coefficients, mid);
+        if fm == 0.0 {
+            return mid;
+        }
+        if fm.signum() == fa.signum() {
+            a = mid;
+            fa = fm;
+        } else {
+            b = mid;
+        }
+    }
+    0.5 * (a + b)
+}
@@ -0,0 +1,171 @@
+use crate::camera::Camera;
+use crate::mipmap::TextureWatch;
+use crate::output::OutputOptions;
+use crate::renderer::{ImageBuffer, RenderSettings, Renderer};
+use glam::{DQuat, DVec3};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use std::error::Error;
+use std::time::Duration;
+
+// Radians of orbit per pixel dragged.
+const ORBIT_SPEED: f64 = 0.01;
+// Fraction of the distance to the target zoomed per wheel step.
+const ZOOM_STEP: f64 = 0.1;
+// Closest the orbit gets to looking straight along the up vector.
+const MIN_POLAR_ANGLE: f64 = 0.01;
+// How often input is polled once the image has converged or is paused.
+const IDLE_INTERVAL: Duration = Duration::from_millis(16);
+
+// Position of the preview camera around the point it looks at. Left drag
+// orbits around the target, right drag pans both, and the wheel moves the
+// camera towards or away from the target.
+struct Orbit {
+    target: DVec3,
+    // From the target to the camera.
+    offset: DVec3,
+    vup: DVec3,
+}
+
+impl Orbit {
+    fn new(camera: &Camera) -> Self {
+        let offset = camera.w * camera.focus_distance();
+        Self {
+            target: camera.origin - offset,
+            offset,
+            vup: camera.v,
+        }
+    }
+
+    fn rotate(&mut self, dx: f64, dy: f64) {
+        if let Some(right) = self.vup.cross(self.offset).try_normalize() {
+            let polar = self.vup.angle_between(self.offset);
+            let pitch = (-dy * ORBIT_SPEED).clamp(
+                MIN_POLAR_ANGLE - polar,
+                std::f64::consts::PI - MIN_POLAR_ANGLE - polar,
+            );
+            self.offset = DQuat::from_axis_angle(right, pitch) * self.offset;
+        }
+        self.offset = DQuat::from_axis_angle(self.vup, -dx * ORBIT_SPEED) * self.offset;
+    }
+
+    fn pan(&mut self, camera: &Camera, dx: f64, dy: f64, height: f64) {
+        // The target follows the cursor: a pixel is this wide at its distance.
+        let pixel = camera.vertical.length() / height;
+        self.target += pixel * (-dx * camera.u + dy * camera.v);
+    }
+
+    fn zoom(&mut self, steps: f64) {
+        self.offset *= (1.0 - ZOOM_STEP).powf(steps);
+    }
+
+    fn camera(&self, base: &Camera) -> Camera {
+        base.looking_at(self.target + self.offset, self.target, self.vup)
+    }
+}
+
+// Opens a window showing a progressive render of the scene seen through
+// `camera`: one sample per pixel is added each pass until the settings'
+// samples per pixel are reached, and moving the camera restarts the
+// average. Space pauses and resumes refinement, leaving the CPU to other
+// work. With `textures`, textures whose files change are read again
+// between passes and restart the average too; the title shows the last
+// one that failed to read until another reloads. Returns when the window
+// is closed or Escape is pressed.
+pub fn run(
+    renderer: &Renderer,
+    camera: &Camera,
+    settings: &RenderSettings,
+    options: &OutputOptions,
+    textures: Option<&TextureWatch>,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (settings.width as usize, settings.height as usize);
+    let mut window = Window::new("raytracer preview", width, height, WindowOptions::default())?;
+    let mut orbit = Orbit::new(camera);
+    let mut view = orbit.camera(camera);
+    let mut accumulated = ImageBuffer::new(settings.width, settings.height);
+    let mut passes = 0;
+    let mut buffer = vec![0u32; width * height];
+    let mut last_mouse: Option<(f32, f32)> = None;
+    let mut paused = false;
+    let mut texture_error = None;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let mouse = window.get_mouse_pos(MouseMode::Discard);
+        let mut moved = false;
+        if let (Some((x, y)), Some((last_x, last_y))) = (mouse, last_mouse) {
+            let (dx, dy) = ((x - last_x) as f64, (y - last_y) as f64);
+            if dx != 0.0 || dy != 0.0 {
+                if window.get_mouse_down(MouseButton::Left) {
+                    orbit.rotate(dx, dy);
+                    moved = true;
+                } else if window.get_mouse_down(MouseButton::Right) {
+                    orbit.pan(&view, dx, dy, height as f64);
+                    moved = true;
+                }
+            }
+        }
+        last_mouse = mouse;
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            if scroll != 0.0 {
+                orbit.zoom(scroll.signum() as f64);
+                moved = true;
+            }
+        }
+        if moved {
+            view = orbit.camera(camera);
+            passes = 0;
+        }
+        if let Some(textures) = textures {
+            let mut failed = None;
+            if textures.reload_changed(|e| failed = Some(e)) {
+                passes = 0;
+                texture_error = None;
+            }
+            texture_error = failed.or(texture_error);
+        }
+
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            paused = !paused;
+        }
+        if paused || passes >= settings.samples_per_pixel.max(1) {
+            window.update();
+            std::thread::sleep(IDLE_INTERVAL);
+            continue;
+        }
+        let pass_settings = RenderSettings {
+            samples_per_pixel: 1,
+            adaptive: None,
+            seed: settings.seed.wrapping_add(passes as u64),
+            ..*settings
+        };
+        let image = renderer.render(&view, &pass_settings);
+        passes += 1;
+        let weight = 1.0 / passes as f64;
+        for (sum, color) in accumulated.pixels.iter_mut().zip(&image.pixels) {
+            *sum = sum.lerp(*color, weight);
+        }
+        for (pixel, color) in buffer.iter_mut().zip(&accumulated.pixels) {
+            *pixel = display_pixel(*color, options);
+        }
+        let mut title = format!(
+            "raytracer preview - {passes}/{} spp",
+            settings.samples_per_pixel.max(1)
+        );
+        if let Some(e) = &texture_error {
+            title += &format!(" - {e}");
+        }
+        window.set_title(&title);
+        window.update_with_buffer(&buffer, width, height)?;
+    }
+    Ok(())
+}
+
+// Tone maps and encodes `color` as the window's 0RGB pixel.
+fn display_pixel(color: DVec3, options: &OutputOptions) -> u32 {
+    let exposed = color * 2f64.powf(options.exposure);
+    let mapped = options.tone_mapper.apply(exposed);
+    mapped.to_array().into_iter().fold(0, |pixel, c| {
+        let encoded = options.transfer.encode(c);
+        pixel << 8 | (255.0 * encoded + 0.5).clamp(0.0, 255.0) as u32
+    })
+}
@@ -0,0 +1,230 @@
+use crate::texture::Texture;
+use glam::{DVec2, DVec3};
+use std::f64::consts::TAU;
+use std::sync::Arc;
+
+// Solid textures computed from the point of a lookup rather than its UVs,
+// so they carve through objects without seams. Each blends two other
+// textures, which may be procedural themselves.
+
+// Gradient noise in about [-1, 1], smooth everywhere and zero on the
+// integer lattice. Lattice gradients come from hashing the cell, so there
+// is no table to build and the pattern never repeats.
+pub fn noise(p: DVec3) -> f64 {
+    let cell = p.floor();
+    let f = p - cell;
+    let fade = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let [x, y, z] = cell.to_array().map(|c| c as i64);
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        let offset = f - DVec3::new(dx as f64, dy as f64, dz as f64);
+        gradient(hash(x + dx, y + dy, z + dz)).dot(offset)
+    };
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let along_x = |dy, dz| lerp(corner(0, dy, dz), corner(1, dy, dz), fade.x);
+    let along_y = |dz| lerp(along_x(0, dz), along_x(1, dz), fade.y);
+    lerp(along_y(0), along_y(1), fade.z)
+}
+
+// Sum of `octaves` of the magnitude of `noise`, each at twice the
+// frequency and half the weight of the one before; the sharp creases where
+// the noise crosses zero make veins.
+pub fn turbulence(p: DVec3, octaves: u32) -> f64 {
+    let (mut sum, mut weight, mut p) = (0.0, 1.0, p);
+    for _ in 0..octaves {
+        sum += weight * noise(p).abs();
+        weight *= 0.5;
+        p *= 2.0;
+    }
+    sum
+}
+
+fn hash(x: i64, y: i64, z: i64) -> u64 {
+    let mut h = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+    h ^= h >> 31;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^ (h >> 29)
+}
+
+// One of the twelve edge directions of a cube, as in Perlin's improved
+// noise.
+fn gradient(hash: u64) -> DVec3 {
+    const GRADIENTS: [[f64; 3]; 12] = [
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [-1.0, 0.0, 1.0],
+        [1.0, 0.0, -1.0],
+        [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, -1.0, 1.0],
+        [0.0, 1.0, -1.0],
+        [0.0, -1.0, -1.0],
+    ];
+    DVec3::from_array(GRADIENTS[(hash % 12) as usize])
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum GradientShape {
+    // From `from` to `to`, constant across that direction and clamped
+    // beyond its ends.
+    Linear { from: DVec3, to: DVec3 },
+    // Outwards from `center`, reaching the end colour at `radius`.
+    Radial { center: DVec3, radius: f64 },
+}
+
+impl GradientShape {
+    // How far along the gradient `p` is, in [0, 1].
+    pub fn position(&self, p: DVec3) -> f64 {
+        let t = match *self {
+            GradientShape::Linear { from, to } => {
+                let axis = to - from;
+                (p - from).dot(axis) / axis.length_squared()
+            }
+            GradientShape::Radial { center, radius } => p.distance(center) / radius,
+        };
+        if t.is_finite() {
+            t.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct GradientTexture {
+    pub start: Arc<dyn Texture>,
+    pub end: Arc<dyn Texture>,
+    pub shape: GradientShape,
+}
+
+impl GradientTexture {
+    pub fn new(start: Arc<dyn Texture>, end: Arc<dyn Texture>, shape: GradientShape) -> Self {
+        Self { start, end, shape }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        let t = self.shape.position(p);
+        self.start.value(u, v, p).lerp(self.end.value(u, v, p), t)
+    }
+}
+
+// Veins of `vein` through `base`: bands of a sine wave along x, `scale`
+// per unit, bent by `turbulence` times as much noise.
+pub struct MarbleTexture {
+    pub base: Arc<dyn Texture>,
+    pub vein: Arc<dyn Texture>,
+    pub scale: f64,
+    pub turbulence: f64,
+    pub octaves: u32,
+}
+
+impl MarbleTexture {
+    pub fn new(base: Arc<dyn Texture>, vein: Arc<dyn Texture>) -> Self {
+        Self {
+            base,
+            vein,
+            scale: 1.0,
+            turbulence: 5.0,
+            octaves: 7,
+        }
+    }
+
+    // How much of the vein shows at `p`, in [0, 1].
+    pub fn vein_weight(&self, p: DVec3) -> f64 {
+        let p = self.scale * p;
+        let phase = p.x + self.turbulence * turbulence(p, self.octaves);
+        0.5 * (1.0 + phase.sin())
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        let t = self.vein_weight(p);
+        self.base.value(u, v, p).lerp(self.vein.value(u, v, p), t)
+    }
+}
+
+// Growth rings of `dark` in `light` around the y axis, `ring_spacing`
+// apart, pushed in and out by up to `distortion` rings of noise.
+pub struct WoodTexture {
+    pub light: Arc<dyn Texture>,
+    pub dark: Arc<dyn Texture>,
+    pub ring_spacing: f64,
+    pub distortion: f64,
+}
+
+impl WoodTexture {
+    pub fn new(light: Arc<dyn Texture>, dark: Arc<dyn Texture>, ring_spacing: f64) -> Self {
+        Self {
+            light,
+            dark,
+            ring_spacing,
+            distortion: 0.0,
+        }
+    }
+
+    // How much of the dark ring shows at `p`, in [0, 1].
+    pub fn ring_weight(&self, p: DVec3) -> f64 {
+        let p = p / self.ring_spacing;
+        let rings = DVec2::new(p.x, p.z).length() + self.distortion * noise(p);
+        0.5 * (1.0 - (TAU * rings).cos())
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        let t = self.ring_weight(p);
+        self.light.value(u, v, p).lerp(self.dark.value(u, v, p), t)
+    }
+}
+
+// A wall of `brick` in the xy plane, the same through z: bricks `size`
+// wide and high, every other course shifted by half a brick, with
+// `mortar` in joints `mortar_width` wide between them.
+pub struct BrickTexture {
+    pub brick: Arc<dyn Texture>,
+    pub mortar: Arc<dyn Texture>,
+    pub size: DVec2,
+    pub mortar_width: f64,
+}
+
+impl BrickTexture {
+    pub fn new(
+        brick: Arc<dyn Texture>,
+        mortar: Arc<dyn Texture>,
+        size: DVec2,
+        mortar_width: f64,
+    ) -> Self {
+        Self {
+            brick,
+            mortar,
+            size,
+            mortar_width,
+        }
+    }
+
+    pub fn is_mortar(&self, p: DVec3) -> bool {
+        let y = p.y / self.size.y;
+        let shift = 0.5 * y.floor().rem_euclid(2.0);
+        let x = p.x / self.size.x + shift;
+        // Distance to the nearest joint, in the units of `p`.
+        let to_joint = |t: f64, size: f64| (t - t.round()).abs() * size;
+        let half = 0.5 * self.mortar_width;
+        to_joint(x, self.size.x) < half || to_joint(y, self.size.y) < half
+    }
+}
+
+impl Texture for BrickTexture {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        if self.is_mortar(p) {
+            self.mortar.value(u, v, p)
+        } else {
+            self.brick.value(u, v, p)
+        }
+    }
+}
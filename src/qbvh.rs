@@ -0,0 +1,1148 @@
+use crate::hittable::{HitRecord, Hittable, HittableList, AABB};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::stats;
+use glam::{DVec3, Vec4};
+use std::error::Error;
+use std::ops::Range;
+use std::path::Path;
+
+const MAX_LEAF_SIZE: usize = 4;
+const MAX_STACK: usize = 64;
+const EMPTY: u32 = u32::MAX;
+
+// Cache files hold "QBVH", a format version, the key they were saved under
+// and the origin, then little-endian u32 words: the primitive order, the
+// unbounded objects' order, and each node's children and counts. Boxes
+// aren't stored; loading refits them, which is linear in the primitives
+// and far quicker than a build.
+const CACHE_MAGIC: &[u8; 4] = b"QBVH";
+const CACHE_VERSION: u32 = 1;
+
+// Four child boxes stored as structure-of-arrays so one ray can be tested
+// against all of them with a handful of `Vec4` operations.
+#[derive(Clone, Copy)]
+struct Node4 {
+    min_x: Vec4,
+    min_y: Vec4,
+    min_z: Vec4,
+    max_x: Vec4,
+    max_y: Vec4,
+    max_z: Vec4,
+    // Interior children store a node index with a count of zero; leaves store
+    // the first primitive and a non-zero count. Unused slots hold `EMPTY`.
+    child: [u32; 4],
+    count: [u32; 4],
+}
+
+impl Node4 {
+    fn empty() -> Self {
+        Self {
+            min_x: Vec4::INFINITY,
+            min_y: Vec4::INFINITY,
+            min_z: Vec4::INFINITY,
+            max_x: Vec4::NEG_INFINITY,
+            max_y: Vec4::NEG_INFINITY,
+            max_z: Vec4::NEG_INFINITY,
+            child: [EMPTY; 4],
+            count: [0; 4],
+        }
+    }
+
+    // Boxes are stored relative to `origin`, where f32 is most precise.
+    fn set_bounds(&mut self, slot: usize, bounds: &AABB, origin: DVec3) {
+        // Boxes are rounded outwards so the f32 copy never clips the f64 one.
+        let (min, max) = (bounds.min - origin, bounds.max - origin);
+        let lo = min - min.abs() * 1e-6 - DVec3::splat(1e-7);
+        let hi = max + max.abs() * 1e-6 + DVec3::splat(1e-7);
+        self.min_x[slot] = lo.x as f32;
+        self.min_y[slot] = lo.y as f32;
+        self.min_z[slot] = lo.z as f32;
+        self.max_x[slot] = hi.x as f32;
+        self.max_y[slot] = hi.y as f32;
+        self.max_z[slot] = hi.z as f32;
+    }
+
+    fn slot_bounds(&self, slot: usize, origin: DVec3) -> AABB {
+        AABB::new(
+            origin
+                + DVec3::new(
+                    self.min_x[slot] as f64,
+                    self.min_y[slot] as f64,
+                    self.min_z[slot] as f64,
+                ),
+            origin
+                + DVec3::new(
+                    self.max_x[slot] as f64,
+                    self.max_y[slot] as f64,
+                    self.max_z[slot] as f64,
+                ),
+        )
+    }
+
+    fn intersect(&self, ray: &PreparedRay, t_min: f32, t_max: f32) -> (u32, Vec4) {
+        let tx0 = (self.min_x - ray.origin_x) * ray.inv_x;
+        let tx1 = (self.max_x - ray.origin_x) * ray.inv_x;
+        let ty0 = (self.min_y - ray.origin_y) * ray.inv_y;
+        let ty1 = (self.max_y - ray.origin_y) * ray.inv_y;
+        let tz0 = (self.min_z - ray.origin_z) * ray.inv_z;
+        let tz1 = (self.max_z - ray.origin_z) * ray.inv_z;
+
+        let t_near = tx0
+            .min(tx1)
+            .max(ty0.min(ty1))
+            .max(tz0.min(tz1))
+            .max(Vec4::splat(t_min));
+        let t_far = tx0
+            .max(tx1)
+            .min(ty0.max(ty1))
+            .min(tz0.max(tz1))
+            .min(Vec4::splat(t_max));
+        (t_near.cmple(t_far).bitmask(), t_near)
+    }
+
+    // Slots whose boxes some ray of `frustum` may meet within `t_min` and
+    // `t_max`.
+    fn cull(&self, frustum: &Frustum, t_min: f32, t_max: f32) -> u32 {
+        let mins = [self.min_x, self.min_y, self.min_z];
+        let maxs = [self.max_x, self.max_y, self.max_z];
+        let mut t_near = Vec4::splat(t_min);
+        let mut t_far = Vec4::splat(t_max);
+        for a in 0..3 {
+            let (near, far) = if frustum.positive[a] {
+                (mins[a], maxs[a])
+            } else {
+                (maxs[a], mins[a])
+            };
+            // The earliest any ray crosses the near slab and the latest
+            // any crosses the far one are at corners of the ranges.
+            let mut first = Vec4::INFINITY;
+            let mut last = Vec4::NEG_INFINITY;
+            for o in [frustum.origin_min[a], frustum.origin_max[a]] {
+                for inv in [frustum.inv_min[a], frustum.inv_max[a]] {
+                    first = first.min((near - Vec4::splat(o)) * inv);
+                    last = last.max((far - Vec4::splat(o)) * inv);
+                }
+            }
+            t_near = t_near.max(first);
+            t_far = t_far.min(last);
+        }
+        let t_far = t_far + t_far.abs() * (f32::EPSILON * 4.0);
+        t_near.cmple(t_far).bitmask()
+    }
+}
+
+struct PreparedRay {
+    origin_x: Vec4,
+    origin_y: Vec4,
+    origin_z: Vec4,
+    inv_x: Vec4,
+    inv_y: Vec4,
+    inv_z: Vec4,
+}
+
+impl PreparedRay {
+    // The origin is moved by `-origin` in f64 before rounding to f32.
+    fn new(ray: &Ray, origin: DVec3) -> Self {
+        let o = (ray.origin - origin).as_vec3();
+        let d = ray.direction.as_vec3();
+        Self {
+            origin_x: Vec4::splat(o.x),
+            origin_y: Vec4::splat(o.y),
+            origin_z: Vec4::splat(o.z),
+            inv_x: Vec4::splat(1.0 / d.x),
+            inv_y: Vec4::splat(1.0 / d.y),
+            inv_z: Vec4::splat(1.0 / d.z),
+        }
+    }
+}
+
+// A bundle of rays as the ranges of their origins and inverse directions
+// along each axis, relative to the tree's origin. The slab test over those
+// ranges bounds where any of the rays can enter and leave a box, so boxes
+// they all miss are skipped with one test rather than one per ray. Only
+// rays whose directions agree in sign along every axis, as the camera rays
+// of a tile do, make a frustum.
+struct Frustum {
+    origin_min: [f32; 3],
+    origin_max: [f32; 3],
+    inv_min: [f32; 3],
+    inv_max: [f32; 3],
+    // Whether the rays head towards +axis, so cross the `min` slab first.
+    positive: [bool; 3],
+}
+
+impl Frustum {
+    fn new(rays: &[Ray], origin: DVec3) -> Option<Self> {
+        let first = rays.first()?;
+        let mut frustum = Self {
+            origin_min: [f32::INFINITY; 3],
+            origin_max: [f32::NEG_INFINITY; 3],
+            inv_min: [f32::INFINITY; 3],
+            inv_max: [f32::NEG_INFINITY; 3],
+            positive: [0, 1, 2].map(|a| first.direction[a] > 0.0),
+        };
+        for ray in rays {
+            let o = (ray.origin - origin).as_vec3();
+            let d = ray.direction.as_vec3();
+            for a in 0..3 {
+                let inv = 1.0 / d[a];
+                if !inv.is_finite() || (inv > 0.0) != frustum.positive[a] {
+                    return None;
+                }
+                frustum.origin_min[a] = frustum.origin_min[a].min(o[a]);
+                frustum.origin_max[a] = frustum.origin_max[a].max(o[a]);
+                frustum.inv_min[a] = frustum.inv_min[a].min(inv);
+                frustum.inv_max[a] = frustum.inv_max[a].max(inv);
+            }
+        }
+        Some(frustum)
+    }
+}
+
+struct PrimInfo {
+    index: usize,
+    bounds: AABB,
+    centroid: DVec3,
+}
+
+enum BuildNode {
+    Leaf {
+        bounds: AABB,
+        start: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: AABB,
+        children: [Box<BuildNode>; 2],
+    },
+}
+
+impl BuildNode {
+    fn bounds(&self) -> &AABB {
+        match self {
+            BuildNode::Leaf { bounds, .. } | BuildNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BvhBuildStrategy {
+    Midpoint,
+    Sah { bins: usize },
+}
+
+impl Default for BvhBuildStrategy {
+    fn default() -> Self {
+        BvhBuildStrategy::Sah { bins: 12 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub min_leaf_size: usize,
+    pub max_leaf_size: usize,
+    pub average_leaf_size: f64,
+    // Expected node visits plus primitive tests per ray, relative to the root
+    // surface area; lower is better.
+    pub sah_cost: f64,
+}
+
+// Node boxes and rays are tested in f32 relative to `origin`. Far from the
+// world origin f32 can't resolve small objects, so scenes placed there
+// should build the hierarchy around the camera position, where precision
+// matters most; primitives are still intersected in f64 world space.
+pub struct Qbvh {
+    nodes: Vec<Node4>,
+    origin: DVec3,
+    primitives: HittableList,
+    unbounded: HittableList,
+    // Positions of `primitives` and `unbounded` in the list the hierarchy
+    // was built from, for `refit`.
+    order: Vec<usize>,
+    unbounded_order: Vec<usize>,
+    bounds: Option<AABB>,
+}
+
+impl Qbvh {
+    pub fn new(objects: HittableList) -> Self {
+        Self::with_strategy(objects, BvhBuildStrategy::default())
+    }
+
+    pub fn with_strategy(objects: HittableList, strategy: BvhBuildStrategy) -> Self {
+        Self::with_origin(objects, strategy, DVec3::ZERO)
+    }
+
+    pub fn with_origin(objects: HittableList, strategy: BvhBuildStrategy, origin: DVec3) -> Self {
+        let mut unbounded = HittableList::new();
+        let mut unbounded_order = Vec::new();
+        let mut infos = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => infos.push(PrimInfo {
+                    index,
+                    bounds,
+                    centroid: (bounds.min + bounds.max) * 0.5,
+                }),
+                None => {
+                    unbounded.push(object.clone());
+                    unbounded_order.push(index);
+                }
+            }
+        }
+
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            origin,
+            primitives: HittableList::new(),
+            unbounded,
+            order: Vec::new(),
+            unbounded_order,
+            bounds: None,
+        };
+        if infos.is_empty() {
+            return bvh;
+        }
+
+        let len = infos.len();
+        let root = build_binary(&mut infos, 0, strategy);
+        bvh.bounds = if bvh.unbounded.is_empty() {
+            Some(*root.bounds())
+        } else {
+            None
+        };
+        bvh.primitives = infos.iter().map(|p| objects[p.index].clone()).collect();
+        bvh.order = infos.iter().map(|p| p.index).collect();
+
+        bvh.nodes.push(Node4::empty());
+        match root {
+            BuildNode::Leaf { .. } => {
+                bvh.nodes[0].set_bounds(0, root.bounds(), origin);
+                bvh.nodes[0].child[0] = 0;
+                bvh.nodes[0].count[0] = len as u32;
+            }
+            BuildNode::Interior { .. } => collapse(&mut bvh.nodes, origin, root, 0),
+        }
+        bvh
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            min_leaf_size: usize::MAX,
+            ..BvhStats::default()
+        };
+        if self.nodes.is_empty() {
+            stats.min_leaf_size = 0;
+            return stats;
+        }
+
+        let root_area = self.bounds.as_ref().map_or(0.0, surface_area);
+        let mut stack = vec![(0usize, 1usize)];
+        let mut total_leaf_size = 0;
+        while let Some((index, depth)) = stack.pop() {
+            let node = &self.nodes[index];
+            stats.node_count += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            for slot in 0..4 {
+                if node.child[slot] == EMPTY {
+                    continue;
+                }
+                let bounds = node.slot_bounds(slot, self.origin);
+                let relative_area = if root_area > 0.0 {
+                    surface_area(&bounds) / root_area
+                } else {
+                    1.0
+                };
+                let count = node.count[slot] as usize;
+                if count > 0 {
+                    stats.leaf_count += 1;
+                    stats.min_leaf_size = stats.min_leaf_size.min(count);
+                    stats.max_leaf_size = stats.max_leaf_size.max(count);
+                    total_leaf_size += count;
+                    stats.sah_cost += relative_area * count as f64;
+                } else {
+                    stats.sah_cost += relative_area;
+                    stack.push((node.child[slot] as usize, depth + 1));
+                }
+            }
+        }
+        if stats.leaf_count > 0 {
+            stats.average_leaf_size = total_leaf_size as f64 / stats.leaf_count as f64;
+        } else {
+            stats.min_leaf_size = 0;
+        }
+        stats
+    }
+
+    // Boxes of the hierarchy `depth` levels below the root, whose bounds are
+    // depth 0. Leaves above `depth` are included so the boxes still cover
+    // every primitive.
+    pub fn boxes_at_depth(&self, depth: usize) -> Vec<AABB> {
+        if depth == 0 || self.nodes.is_empty() {
+            return self.bounds.into_iter().collect();
+        }
+        let mut boxes = Vec::new();
+        let mut stack = vec![(0usize, 1usize)];
+        while let Some((index, level)) = stack.pop() {
+            let node = &self.nodes[index];
+            for slot in 0..4 {
+                if node.child[slot] == EMPTY {
+                    continue;
+                }
+                if level == depth || node.count[slot] > 0 {
+                    boxes.push(node.slot_bounds(slot, self.origin));
+                } else {
+                    stack.push((node.child[slot] as usize, level + 1));
+                }
+            }
+        }
+        boxes
+    }
+
+    // Swaps in `objects`, which replace the ones the hierarchy was built
+    // over one for one and in the same order, and recomputes the node boxes
+    // around them without changing the tree. Much faster than a rebuild for
+    // animated objects, but traversal slows as they drift from where they
+    // were built. Objects that were bounded must stay bounded.
+    pub fn refit(&mut self, objects: HittableList) {
+        assert_eq!(
+            objects.len(),
+            self.order.len() + self.unbounded_order.len(),
+            "refit needs the objects the hierarchy was built over"
+        );
+        self.primitives = self.order.iter().map(|&i| objects[i].clone()).collect();
+        self.unbounded = self
+            .unbounded_order
+            .iter()
+            .map(|&i| objects[i].clone())
+            .collect();
+        if self.nodes.is_empty() {
+            return;
+        }
+        let root = self.refit_node(0);
+        self.bounds = root.filter(|_| self.unbounded.is_empty());
+    }
+
+    fn refit_node(&mut self, index: usize) -> Option<AABB> {
+        let mut bounds: Option<AABB> = None;
+        for slot in 0..4 {
+            let (child, count) = (self.nodes[index].child[slot], self.nodes[index].count[slot]);
+            if child == EMPTY {
+                continue;
+            }
+            let child_bounds = if count > 0 {
+                let first = child as usize;
+                self.primitives[first..first + count as usize]
+                    .iter()
+                    .filter_map(|p| p.bounding_box())
+                    .reduce(AABB::surrounding_box)
+            } else {
+                self.refit_node(child as usize)
+            };
+            let Some(child_bounds) = child_bounds else {
+                continue;
+            };
+            self.nodes[index].set_bounds(slot, &child_bounds, self.origin);
+            bounds = Some(match bounds {
+                Some(b) => AABB::surrounding_box(b, child_bounds),
+                None => child_bounds,
+            });
+        }
+        bounds
+    }
+
+    // Writes the hierarchy to a cache file at `path`, under a `key` that
+    // `load` checks, such as a hash of the file the objects came from.
+    pub fn save(&self, path: &Path, key: u64) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(48 + 4 * self.order.len() + 32 * self.nodes.len());
+        bytes.extend_from_slice(CACHE_MAGIC);
+        bytes.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&key.to_le_bytes());
+        for axis in 0..3 {
+            bytes.extend_from_slice(&self.origin[axis].to_le_bytes());
+        }
+        for list in [&self.order, &self.unbounded_order] {
+            bytes.extend_from_slice(&(list.len() as u64).to_le_bytes());
+            for &index in list {
+                bytes.extend_from_slice(&(index as u32).to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&(self.nodes.len() as u64).to_le_bytes());
+        for node in &self.nodes {
+            for word in node.child.iter().chain(&node.count) {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        std::fs::write(path, bytes)
+    }
+
+    // Rebuilds a hierarchy saved under `key` over `objects`, which must be
+    // the objects it was built over, in the same order. Files saved under
+    // another key, or that don't fit `objects`, are rejected.
+    pub fn load(path: &Path, objects: HittableList, key: u64) -> Result<Self, Box<dyn Error>> {
+        let bytes = std::fs::read(path)?;
+        let mut reader = CacheReader { bytes: &bytes };
+        if reader.take(4)? != CACHE_MAGIC || reader.u32()? != CACHE_VERSION {
+            return Err(format!("{}: not a BVH cache file", path.display()).into());
+        }
+        if reader.u64()? != key {
+            return Err(format!("{}: BVH cache is out of date", path.display()).into());
+        }
+        let origin = DVec3::new(reader.f64()?, reader.f64()?, reader.f64()?);
+        let order = reader.indices()?;
+        let unbounded_order = reader.indices()?;
+        let node_count = reader.u64()? as usize;
+        if node_count > reader.bytes.len() / 32 {
+            return Err(format!("{}: BVH cache is truncated", path.display()).into());
+        }
+        let mut nodes = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            let mut node = Node4::empty();
+            for word in node.child.iter_mut().chain(&mut node.count) {
+                *word = reader.u32()?;
+            }
+            nodes.push(node);
+        }
+
+        let mismatch = || format!("{}: BVH cache doesn't fit the objects", path.display());
+        let mut seen = vec![false; objects.len()];
+        let bounded = order.iter().map(|&i| (i, true));
+        let entries = bounded.chain(unbounded_order.iter().map(|&i| (i, false)));
+        for (index, bounded) in entries {
+            let fits = seen.get(index).is_some_and(|&seen| !seen)
+                && objects[index].bounding_box().is_some() == bounded;
+            if !fits {
+                return Err(mismatch().into());
+            }
+            seen[index] = true;
+        }
+        // Children come after their parents, so a walk always ends.
+        let well_formed = seen.iter().all(|&seen| seen)
+            && nodes.is_empty() == order.is_empty()
+            && nodes.iter().enumerate().all(|(index, node)| {
+                (0..4).all(|slot| {
+                    let (child, count) = (node.child[slot] as usize, node.count[slot] as usize);
+                    node.child[slot] == EMPTY
+                        || if count > 0 {
+                            child + count <= order.len()
+                        } else {
+                            child > index && child < nodes.len()
+                        }
+                })
+            });
+        if !well_formed {
+            return Err(mismatch().into());
+        }
+
+        let mut bvh = Self {
+            nodes,
+            origin,
+            primitives: HittableList::new(),
+            unbounded: HittableList::new(),
+            order,
+            unbounded_order,
+            bounds: None,
+        };
+        bvh.refit(objects);
+        Ok(bvh)
+    }
+
+    // Loads the hierarchy over `objects` cached at `path` under `key`, or
+    // builds it with `strategy` and saves it there for next time. A cache
+    // that can't be written only costs the next load a build.
+    pub fn cached(
+        objects: HittableList,
+        strategy: BvhBuildStrategy,
+        path: &Path,
+        key: u64,
+    ) -> Self {
+        if let Ok(bvh) = Self::load(path, objects.clone(), key) {
+            return bvh;
+        }
+        let bvh = Self::with_strategy(objects, strategy);
+        let _ = bvh.save(path, key);
+        bvh
+    }
+}
+
+impl Qbvh {
+    // Closest hit on the primitives of a leaf.
+    fn leaf_hit(&self, ray: &Ray, leaf: Range<usize>, interval: Interval) -> Option<HitRecord<'_>> {
+        let mut closest = interval.max;
+        let mut result = None;
+        for object in &self.primitives[leaf] {
+            if let Some(rec) = object.hit(ray, interval.with_max(closest)) {
+                closest = rec.t;
+                result = Some(rec);
+            }
+        }
+        result
+    }
+}
+
+impl Hittable for Qbvh {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let result = self.unbounded.hit(ray, interval);
+        let closest = result.as_ref().map_or(interval.max, |rec| rec.t);
+        closest_hit(
+            &self.nodes,
+            self.origin,
+            ray,
+            interval.with_max(closest),
+            |leaf, interval| self.leaf_hit(ray, leaf, interval),
+        )
+        .or(result)
+    }
+
+    fn hit_packet<'a>(
+        &'a self,
+        rays: &[Ray],
+        interval: Interval,
+        hits: &mut [Option<HitRecord<'a>>],
+    ) {
+        for (ray, hit) in rays.iter().zip(hits.iter_mut()) {
+            *hit = self.unbounded.hit(ray, interval);
+        }
+        closest_hits(
+            &self.nodes,
+            self.origin,
+            rays,
+            interval,
+            hits,
+            |ray, leaf, interval| self.leaf_hit(ray, leaf, interval),
+        );
+    }
+
+    fn hit_any(&self, ray: &Ray, interval: Interval) -> bool {
+        if self
+            .unbounded
+            .iter()
+            .any(|object| object.hit_any(ray, interval))
+        {
+            return true;
+        }
+        any_hit(&self.nodes, self.origin, ray, interval, |leaf, interval| {
+            self.primitives[leaf]
+                .iter()
+                .any(|object| object.hit_any(ray, interval))
+        })
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.bounds
+    }
+}
+
+// Whether anything lies on `ray` within `interval` in the tree of
+// `nodes`, given `hit_leaf(primitives, interval)` for whether anything in
+// a leaf does. Stops at the first leaf that answers yes rather than
+// looking for the closest.
+fn any_hit(
+    nodes: &[Node4],
+    origin: DVec3,
+    ray: &Ray,
+    interval: Interval,
+    mut hit_leaf: impl FnMut(Range<usize>, Interval) -> bool,
+) -> bool {
+    if nodes.is_empty() {
+        return false;
+    }
+    let prepared = PreparedRay::new(ray, origin);
+    let t_max = (interval.max as f32) * (1.0 + f32::EPSILON * 4.0);
+    let mut stack = [0u32; MAX_STACK];
+    let mut sp = 1;
+    while sp > 0 {
+        sp -= 1;
+        let node = &nodes[stack[sp] as usize];
+        stats::count(|stats| stats.node_tests += 1);
+        let (mask, _) = node.intersect(&prepared, interval.min as f32, t_max);
+        for slot in 0..4 {
+            if mask & (1 << slot) == 0 || node.child[slot] == EMPTY {
+                continue;
+            }
+            let count = node.count[slot] as usize;
+            if count == 0 {
+                if sp < MAX_STACK {
+                    stack[sp] = node.child[slot];
+                    sp += 1;
+                }
+                continue;
+            }
+            let first = node.child[slot] as usize;
+            if hit_leaf(first..first + count, interval) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// Closest hit within `interval` in the tree of `nodes`, given
+// `hit_leaf(primitives, interval)` for the closest hit on the primitives of
+// a leaf, which are known by their range in the order the tree was built
+// in.
+fn closest_hit<'a>(
+    nodes: &[Node4],
+    origin: DVec3,
+    ray: &Ray,
+    interval: Interval,
+    mut hit_leaf: impl FnMut(Range<usize>, Interval) -> Option<HitRecord<'a>>,
+) -> Option<HitRecord<'a>> {
+    if nodes.is_empty() {
+        return None;
+    }
+    let mut closest = interval.max;
+    let mut result = None;
+
+    let prepared = PreparedRay::new(ray, origin);
+    let mut stack = [0u32; MAX_STACK];
+    let mut sp = 1;
+
+    while sp > 0 {
+        sp -= 1;
+        let node = &nodes[stack[sp] as usize];
+        stats::count(|stats| stats.node_tests += 1);
+        let (mask, t_near) = node.intersect(
+            &prepared,
+            interval.min as f32,
+            (closest as f32) * (1.0 + f32::EPSILON * 4.0),
+        );
+        if mask == 0 {
+            continue;
+        }
+
+        let mut order: [(f32, usize); 4] = [(f32::INFINITY, 4); 4];
+        let mut hits = 0;
+        for slot in 0..4 {
+            if mask & (1 << slot) != 0 && node.child[slot] != EMPTY {
+                order[hits] = (t_near[slot], slot);
+                hits += 1;
+            }
+        }
+        order[..hits].sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Leaves are intersected near-to-far right away; interior nodes
+        // are pushed far-to-near so the nearest is popped first.
+        for &(_, slot) in &order[..hits] {
+            let count = node.count[slot] as usize;
+            if count > 0 {
+                let first = node.child[slot] as usize;
+                if let Some(rec) = hit_leaf(first..first + count, interval.with_max(closest)) {
+                    closest = rec.t;
+                    result = Some(rec);
+                }
+            }
+        }
+        for &(_, slot) in order[..hits].iter().rev() {
+            if node.count[slot] == 0 && sp < MAX_STACK {
+                stack[sp] = node.child[slot];
+                sp += 1;
+            }
+        }
+    }
+    result
+}
+
+// `closest_hit` for a bundle of rays, walking the tree once for them all.
+// Nodes are culled against the rays' frustum, when they make one, before
+// any ray is tested against them, and each ray keeps its own closest hit
+// in `hits`, where only a closer one replaces a hit already there.
+fn closest_hits<'a>(
+    nodes: &[Node4],
+    origin: DVec3,
+    rays: &[Ray],
+    interval: Interval,
+    hits: &mut [Option<HitRecord<'a>>],
+    mut hit_leaf: impl FnMut(&Ray, Range<usize>, Interval) -> Option<HitRecord<'a>>,
+) {
+    if nodes.is_empty() {
+        return;
+    }
+    let frustum = Frustum::new(rays, origin);
+    let prepared: Vec<PreparedRay> = rays
+        .iter()
+        .map(|ray| PreparedRay::new(ray, origin))
+        .collect();
+    let mut closest: Vec<f64> = hits
+        .iter()
+        .map(|hit| hit.as_ref().map_or(interval.max, |rec| rec.t))
+        .collect();
+    let slack = 1.0 + f32::EPSILON * 4.0;
+    // The slots of the current node each ray meets.
+    let mut masks = vec![0u32; rays.len()];
+    let mut stack = [0u32; MAX_STACK];
+    let mut sp = 1;
+
+    while sp > 0 {
+        sp -= 1;
+        let node = &nodes[stack[sp] as usize];
+        let mut candidates = 0b1111;
+        if let Some(frustum) = &frustum {
+            stats::count(|stats| stats.node_tests += 1);
+            let farthest = closest.iter().fold(interval.min, |a, &b| a.max(b));
+            candidates = node.cull(frustum, interval.min as f32, (farthest as f32) * slack);
+            if candidates == 0 {
+                continue;
+            }
+        }
+
+        let mut reached = 0;
+        let mut entry = [f32::INFINITY; 4];
+        for (r, ray) in prepared.iter().enumerate() {
+            stats::count(|stats| stats.node_tests += 1);
+            let t_max = (closest[r] as f32) * slack;
+            let (mask, t_near) = node.intersect(ray, interval.min as f32, t_max);
+            masks[r] = mask & candidates;
+            for (slot, entry) in entry.iter_mut().enumerate() {
+                if masks[r] & (1 << slot) != 0 {
+                    *entry = entry.min(t_near[slot]);
+                }
+            }
+            reached |= masks[r];
+        }
+
+        let mut order: [(f32, usize); 4] = [(f32::INFINITY, 4); 4];
+        let mut count = 0;
+        for slot in 0..4 {
+            if reached & (1 << slot) != 0 && node.child[slot] != EMPTY {
+                order[count] = (entry[slot], slot);
+                count += 1;
+            }
+        }
+        order[..count].sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        // As in `closest_hit`, by the nearest entry of any ray.
+        for &(_, slot) in &order[..count] {
+            let primitives = node.count[slot] as usize;
+            if primitives == 0 {
+                continue;
+            }
+            let first = node.child[slot] as usize;
+            for (r, ray) in rays.iter().enumerate() {
+                if masks[r] & (1 << slot) == 0 {
+                    continue;
+                }
+                let leaf = first..first + primitives;
+                if let Some(rec) = hit_leaf(ray, leaf, interval.with_max(closest[r])) {
+                    closest[r] = rec.t;
+                    hits[r] = Some(rec);
+                }
+            }
+        }
+        for &(_, slot) in order[..count].iter().rev() {
+            if node.count[slot] == 0 && sp < MAX_STACK {
+                stack[sp] = node.child[slot];
+                sp += 1;
+            }
+        }
+    }
+}
+
+// Pulls grandchildren up until a node has four children (or only leaves
+// remain), always opening the child with the largest surface area.
+fn collapse(nodes: &mut Vec<Node4>, origin: DVec3, node: BuildNode, node_index: usize) {
+    let mut children: Vec<BuildNode> = match node {
+        BuildNode::Interior { children, .. } => children.into_iter().map(|c| *c).collect(),
+        leaf => vec![leaf],
+    };
+
+    while children.len() < 4 {
+        let candidate = children
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| matches!(c, BuildNode::Interior { .. }))
+            .max_by(|(_, a), (_, b)| surface_area(a.bounds()).total_cmp(&surface_area(b.bounds())))
+            .map(|(i, _)| i);
+        let Some(i) = candidate else { break };
+        if let BuildNode::Interior { children: pair, .. } = children.swap_remove(i) {
+            let [a, b] = pair;
+            children.push(*a);
+            children.push(*b);
+        }
+    }
+
+    for (slot, child) in children.into_iter().enumerate() {
+        nodes[node_index].set_bounds(slot, child.bounds(), origin);
+        match child {
+            BuildNode::Leaf { start, count, .. } => {
+                nodes[node_index].child[slot] = start as u32;
+                nodes[node_index].count[slot] = count as u32;
+            }
+            interior => {
+                let child_index = nodes.len();
+                nodes.push(Node4::empty());
+                nodes[node_index].child[slot] = child_index as u32;
+                nodes[node_index].count[slot] = 0;
+                collapse(nodes, origin, interior, child_index);
+            }
+        }
+    }
+}
+
+// The tree of a `Qbvh` alone, for objects that store their primitives
+// themselves rather than as a list of objects, such as the triangles of a
+// `Mesh`. Its leaves refer to primitives by their index in the order it
+// was built in.
+pub(crate) struct Hierarchy {
+    nodes: Vec<Node4>,
+    origin: DVec3,
+}
+
+impl Hierarchy {
+    // Builds the tree over primitives with boxes `bounds`, and returns it
+    // with the bounds of them all and the primitives' positions in
+    // `bounds` in build order.
+    pub(crate) fn build(
+        bounds: &[AABB],
+        strategy: BvhBuildStrategy,
+    ) -> (Self, Option<AABB>, Vec<usize>) {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            origin: DVec3::ZERO,
+        };
+        let mut infos: Vec<PrimInfo> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, &bounds)| PrimInfo {
+                index,
+                bounds,
+                centroid: (bounds.min + bounds.max) * 0.5,
+            })
+            .collect();
+        if infos.is_empty() {
+            return (tree, None, Vec::new());
+        }
+
+        let root = build_binary(&mut infos, 0, strategy);
+        let total = *root.bounds();
+        tree.nodes.push(Node4::empty());
+        match root {
+            BuildNode::Leaf { .. } => {
+                tree.nodes[0].set_bounds(0, &total, tree.origin);
+                tree.nodes[0].child[0] = 0;
+                tree.nodes[0].count[0] = infos.len() as u32;
+            }
+            BuildNode::Interior { .. } => collapse(&mut tree.nodes, tree.origin, root, 0),
+        }
+        (tree, Some(total), infos.iter().map(|p| p.index).collect())
+    }
+
+    // Closest hit within `interval`, given `hit_leaf(primitives, interval)`
+    // for the closest hit on the primitives in a range of the build order.
+    pub(crate) fn hit<'a>(
+        &self,
+        ray: &Ray,
+        interval: Interval,
+        hit_leaf: impl FnMut(Range<usize>, Interval) -> Option<HitRecord<'a>>,
+    ) -> Option<HitRecord<'a>> {
+        closest_hit(&self.nodes, self.origin, ray, interval, hit_leaf)
+    }
+
+    // Whether anything lies on `ray` within `interval`, given
+    // `hit_leaf(primitives, interval)` for whether anything in a range of
+    // the build order does.
+    pub(crate) fn hit_any(
+        &self,
+        ray: &Ray,
+        interval: Interval,
+        hit_leaf: impl FnMut(Range<usize>, Interval) -> bool,
+    ) -> bool {
+        any_hit(&self.nodes, self.origin, ray, interval, hit_leaf)
+    }
+}
+
+// Reads cache files front to back.
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> CacheReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if len > self.bytes.len() {
+            return Err("BVH cache is truncated".into());
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, Box<dyn Error>> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // A u64 count, then that many u32 indices.
+    fn indices(&mut self) -> Result<Vec<usize>, Box<dyn Error>> {
+        let len = self.u64()? as usize;
+        let words = self.take(len.checked_mul(4).ok_or("BVH cache is truncated")?)?;
+        Ok(words
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .collect())
+    }
+}
+
+fn surface_area(b: &AABB) -> f64 {
+    let d = (b.max - b.min).max(DVec3::ZERO);
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+}
+
+fn union_bounds(infos: &[PrimInfo]) -> AABB {
+    infos
+        .iter()
+        .map(|p| p.bounds)
+        .reduce(AABB::surrounding_box)
+        .unwrap_or_default()
+}
+
+fn build_binary(infos: &mut [PrimInfo], offset: usize, strategy: BvhBuildStrategy) -> BuildNode {
+    let bounds = union_bounds(infos);
+    let count = infos.len();
+    let leaf = BuildNode::Leaf {
+        bounds,
+        start: offset,
+        count,
+    };
+    if count <= 1 {
+        return leaf;
+    }
+
+    let (c_min, c_max) = infos.iter().fold(
+        (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+        |(lo, hi), p| (lo.min(p.centroid), hi.max(p.centroid)),
+    );
+    let extent = c_max - c_min;
+    let axis = if extent.x > extent.y && extent.x > extent.z {
+        0
+    } else if extent.y > extent.z {
+        1
+    } else {
+        2
+    };
+
+    let mid = if extent[axis] <= 0.0 {
+        if count <= MAX_LEAF_SIZE {
+            return leaf;
+        }
+        count / 2
+    } else {
+        match strategy {
+            BvhBuildStrategy::Midpoint => {
+                if count <= MAX_LEAF_SIZE {
+                    return leaf;
+                }
+                let center = c_min[axis] + 0.5 * extent[axis];
+                partition(infos, |p| p.centroid[axis] < center)
+            }
+            BvhBuildStrategy::Sah { bins } => {
+                match sah_split(infos, &bounds, axis, c_min[axis], extent[axis], bins) {
+                    Some(mid) => mid,
+                    None => return leaf,
+                }
+            }
+        }
+    };
+
+    let mid = mid.clamp(1, count - 1);
+    let (left, right) = infos.split_at_mut(mid);
+    let left_node = build_binary(left, offset, strategy);
+    let right_node = build_binary(right, offset + mid, strategy);
+    BuildNode::Interior {
+        bounds,
+        children: [Box::new(left_node), Box::new(right_node)],
+    }
+}
+
+// Binned surface area heuristic: returns the partition point of the cheapest
+// split, or `None` when keeping the primitives in a leaf is cheaper.
+fn sah_split(
+    infos: &mut [PrimInfo],
+    bounds: &AABB,
+    axis: usize,
+    axis_min: f64,
+    axis_extent: f64,
+    bins: usize,
+) -> Option<usize> {
+    let bins = bins.max(2);
+    let count = infos.len();
+    let bin_of = |p: &PrimInfo| {
+        let b = ((p.centroid[axis] - axis_min) / axis_extent * bins as f64) as usize;
+        b.min(bins - 1)
+    };
+
+    let mut bin_bounds: Vec<Option<AABB>> = vec![None; bins];
+    let mut bin_counts = vec![0usize; bins];
+    for p in infos.iter() {
+        let b = bin_of(p);
+        bin_counts[b] += 1;
+        bin_bounds[b] = Some(match bin_bounds[b] {
+            Some(existing) => AABB::surrounding_box(existing, p.bounds),
+            None => p.bounds,
+        });
+    }
+
+    // Sweep from the right once so each split's right-hand side is known.
+    let mut right_area = vec![0.0; bins];
+    let mut right_count = vec![0usize; bins];
+    let mut acc: Option<AABB> = None;
+    let mut n = 0;
+    for b in (1..bins).rev() {
+        if let Some(bb) = bin_bounds[b] {
+            acc = Some(acc.map_or(bb, |a| AABB::surrounding_box(a, bb)));
+        }
+        n += bin_counts[b];
+        right_area[b] = acc.as_ref().map_or(0.0, surface_area);
+        right_count[b] = n;
+    }
+
+    let mut best_cost = f64::INFINITY;
+    let mut best_split = 0;
+    let mut acc: Option<AABB> = None;
+    let mut n_left = 0;
+    for split in 1..bins {
+        if let Some(bb) = bin_bounds[split - 1] {
+            acc = Some(acc.map_or(bb, |a| AABB::surrounding_box(a, bb)));
+        }
+        n_left += bin_counts[split - 1];
+        let n_right = right_count[split];
+        if n_left == 0 || n_right == 0 {
+            continue;
+        }
+        let cost = n_left as f64 * acc.as_ref().map_or(0.0, surface_area)
+            + n_right as f64 * right_area[split];
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = split;
+        }
+    }
+
+    let leaf_cost = count as f64 * surface_area(bounds);
+    if count <= MAX_LEAF_SIZE && leaf_cost <= best_cost {
+        return None;
+    }
+    if best_split == 0 {
+        return Some(count / 2);
+    }
+    Some(partition(infos, |p| bin_of(p) < best_split))
+}
+
+fn partition(infos: &mut [PrimInfo], predicate: impl Fn(&PrimInfo) -> bool) -> usize {
+    let mut first = 0;
+    for i in 0..infos.len() {
+        if predicate(&infos[i]) {
+            infos.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
@@ -0,0 +1,166 @@
+use crate::error::RenderError;
+use crate::hittable::{hit_surface, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::{Ray, RayKind};
+use crate::scene::Scene;
+use glam::{DVec2, DVec3};
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use std::sync::Arc;
+
+// Rays traced together through `Hittable::hit_packet`, and handed to a
+// thread at a time.
+const BATCH: usize = 64;
+
+// Ray casts against a built scene for uses other than rendering, such as
+// line-of-sight checks or simulating a lidar: whole slices of rays are
+// traced in parallel, with coherent ones sharing hierarchy traversals, and
+// the results come back in flat arrays, one entry per ray. Surfaces cut
+// away by their material, such as the clear parts of a leaf texture, are
+// passed through, as the renderer passes through them.
+#[derive(Clone)]
+pub struct RayQuery {
+    world: Arc<dyn Hittable>,
+}
+
+// Nearest hits of a batch of rays, by ray. Rays that miss have an infinite
+// `t` and zero everywhere else.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NearestHits {
+    // Distance along each ray, in multiples of its direction.
+    pub t: Vec<f64>,
+    pub points: Vec<DVec3>,
+    // Shading normals, facing back along the ray.
+    pub normals: Vec<DVec3>,
+    // Surface coordinates of the hits.
+    pub uvs: Vec<DVec2>,
+    // IDs of the scene objects hit, as the object ID pass gives them; 0
+    // for misses and untagged objects.
+    pub object_ids: Vec<u32>,
+}
+
+impl NearestHits {
+    pub fn len(&self) -> usize {
+        self.t.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.t.is_empty()
+    }
+
+    pub fn is_hit(&self, index: usize) -> bool {
+        self.t[index].is_finite()
+    }
+}
+
+impl RayQuery {
+    pub fn new(world: Arc<dyn Hittable>) -> Self {
+        Self { world }
+    }
+
+    // The world of the scene file at `path`, as it is at time 0.
+    pub fn from_file(path: &str) -> Result<Self, RenderError> {
+        let (_, _, world, _) = Scene::from_file(path)?;
+        Ok(Self::new(world))
+    }
+
+    // The closest surface along each of `rays` within `interval`.
+    pub fn nearest(&self, rays: &[Ray], interval: Interval) -> NearestHits {
+        let batch = |rays: &[Ray]| {
+            let mut hits = vec![None; rays.len()];
+            self.world.hit_packet(rays, interval, &mut hits);
+            for (ray, hit) in rays.iter().zip(&mut hits) {
+                if hit.is_some_and(|rec| rec.material.is_cut_out(&rec)) {
+                    *hit = hit_surface(&*self.world, ray, interval);
+                }
+            }
+            hits.into_iter().map(|hit| hit.map(Found::from))
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let found: Vec<Option<Found>> = rays.par_chunks(BATCH).flat_map_iter(batch).collect();
+        #[cfg(target_arch = "wasm32")]
+        let found: Vec<Option<Found>> = rays.chunks(BATCH).flat_map(batch).collect();
+
+        let mut hits = NearestHits {
+            t: Vec::with_capacity(rays.len()),
+            points: Vec::with_capacity(rays.len()),
+            normals: Vec::with_capacity(rays.len()),
+            uvs: Vec::with_capacity(rays.len()),
+            object_ids: Vec::with_capacity(rays.len()),
+        };
+        for found in found {
+            let found = found.unwrap_or(Found {
+                t: f64::INFINITY,
+                ..Found::default()
+            });
+            hits.t.push(found.t);
+            hits.points.push(found.point);
+            hits.normals.push(found.normal);
+            hits.uvs.push(found.uv);
+            hits.object_ids.push(found.object_id);
+        }
+        hits
+    }
+
+    // Whether anything blocks each of `rays` within `interval`. Rays are
+    // traced as shadow rays, so objects that cast no shadows don't block
+    // them, and none of them needs the closest hit.
+    pub fn occluded(&self, rays: &[Ray], interval: Interval) -> Vec<bool> {
+        let blocked = |ray: &Ray| {
+            let ray = ray.with_kind(RayKind::Shadow);
+            self.world.hit_any(&ray, interval)
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let occluded = rays.par_iter().with_min_len(BATCH).map(blocked).collect();
+        #[cfg(target_arch = "wasm32")]
+        let occluded = rays.iter().map(blocked).collect();
+        occluded
+    }
+
+    // Whether each pair of points can see each other: nothing lies on the
+    // segment between them, short of `epsilon` from either end, so points
+    // on surfaces don't block themselves.
+    pub fn visible(&self, pairs: &[(DVec3, DVec3)], epsilon: f64) -> Vec<bool> {
+        let rays: Vec<Ray> = pairs
+            .iter()
+            .map(|&(from, to)| Ray::new(from, to - from))
+            .collect();
+        let visible = |(ray, &(from, to)): (&Ray, &(DVec3, DVec3))| {
+            let length = from.distance(to);
+            if length <= 2.0 * epsilon {
+                return true;
+            }
+            // `t` runs along the unnormalised direction, so 1 is `to`.
+            let interval = Interval::new(epsilon / length, 1.0 - epsilon / length);
+            let ray = ray.with_kind(RayKind::Shadow);
+            !self.world.hit_any(&ray, interval)
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let visible = rays.par_iter().zip(pairs).map(visible).collect();
+        #[cfg(target_arch = "wasm32")]
+        let visible = rays.iter().zip(pairs).map(visible).collect();
+        visible
+    }
+}
+
+// What `nearest` keeps of a hit record, which borrows from the world.
+#[derive(Clone, Copy, Default)]
+struct Found {
+    t: f64,
+    point: DVec3,
+    normal: DVec3,
+    uv: DVec2,
+    object_id: u32,
+}
+
+impl From<HitRecord<'_>> for Found {
+    fn from(rec: HitRecord<'_>) -> Self {
+        Self {
+            t: rec.t,
+            point: rec.point,
+            normal: rec.normal,
+            uv: DVec2::new(rec.u, rec.v),
+            object_id: rec.object_id,
+        }
+    }
+}
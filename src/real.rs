@@ -0,0 +1,50 @@
+use glam::DVec3;
+
+// Precision of the vertices, normals and colours of triangles and meshes,
+// and of their intersection tests. Doubles by default; the `f32` feature
+// halves the memory big meshes take, at the cost of their hits drifting by
+// about one part in 10^7 of the distance from the origin. It touches
+// nothing else: rays, BVHs, the other primitives, textures and shading
+// all stay in f64, so it is a memory saving for mesh-heavy scenes rather
+// than a faster renderer. Values cross over with `to_real`, `from_real`
+// and `wide`, and tests of triangle geometry scale their tolerances by
+// `Real::EPSILON`.
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(not(feature = "f32"))]
+pub type Vec3 = glam::DVec3;
+
+#[cfg(feature = "f32")]
+pub type Real = f32;
+#[cfg(feature = "f32")]
+pub type Vec3 = glam::Vec3;
+
+#[cfg(not(feature = "f32"))]
+pub fn to_real(v: DVec3) -> Vec3 {
+    v
+}
+
+#[cfg(not(feature = "f32"))]
+pub fn from_real(v: Vec3) -> DVec3 {
+    v
+}
+
+#[cfg(not(feature = "f32"))]
+pub fn wide(x: Real) -> f64 {
+    x
+}
+
+#[cfg(feature = "f32")]
+pub fn to_real(v: DVec3) -> Vec3 {
+    v.as_vec3()
+}
+
+#[cfg(feature = "f32")]
+pub fn from_real(v: Vec3) -> DVec3 {
+    v.as_dvec3()
+}
+
+#[cfg(feature = "f32")]
+pub fn wide(x: Real) -> f64 {
+    f64::from(x)
+}
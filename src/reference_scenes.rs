@@ -0,0 +1,238 @@
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::environment::{Environment, SolidBackground};
+use crate::hittable::{Hittable, HittableList};
+use crate::lights::{Emitter, LightSet};
+use crate::material::{AnisotropicMetal, DiffuseLight, Lambertian, Material};
+use crate::objects::sphere::Sphere;
+use crate::objects::triangle::Triangle;
+use crate::renderer::Renderer;
+use crate::texture::SolidColor;
+use glam::DVec3;
+use std::sync::Arc;
+
+// A scene with everything a renderer needs, for comparing this one's
+// images against published ones.
+pub struct ReferenceScene {
+    pub camera: Camera,
+    pub world: Arc<dyn Hittable>,
+    pub lights: Arc<LightSet>,
+    pub environment: Arc<dyn Environment>,
+}
+
+impl ReferenceScene {
+    pub fn renderer(&self) -> Renderer {
+        Renderer::new(self.world.clone(), self.environment.clone()).with_lights(self.lights.clone())
+    }
+}
+
+// The Cornell box as measured by the Cornell Program of Computer Graphics,
+// in millimetres, with the reflectances and light commonly used for it in
+// RGB. The light is lowered by 0.1 mm so that it doesn't share its plane
+// with the ceiling. Square images match the published camera.
+pub fn cornell_box() -> ReferenceScene {
+    let white = lambertian(DVec3::new(0.725, 0.71, 0.68));
+    let red = lambertian(DVec3::new(0.63, 0.065, 0.05));
+    let green = lambertian(DVec3::new(0.14, 0.45, 0.091));
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(DVec3::new(17.0, 12.0, 4.0)));
+
+    let mut world = HittableList::new();
+    let mut add = |corners: [[f64; 3]; 4], material: &Arc<dyn Material>| {
+        for triangle in quad(corners, material) {
+            world.push(Arc::new(triangle));
+        }
+    };
+    // Floor, ceiling, back wall, then the red wall on the left and the green
+    // one on the right.
+    add(
+        [
+            [552.8, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 559.2],
+            [549.6, 0.0, 559.2],
+        ],
+        &white,
+    );
+    add(
+        [
+            [556.0, 548.8, 0.0],
+            [556.0, 548.8, 559.2],
+            [0.0, 548.8, 559.2],
+            [0.0, 548.8, 0.0],
+        ],
+        &white,
+    );
+    add(
+        [
+            [549.6, 0.0, 559.2],
+            [0.0, 0.0, 559.2],
+            [0.0, 548.8, 559.2],
+            [556.0, 548.8, 559.2],
+        ],
+        &white,
+    );
+    add(
+        [
+            [552.8, 0.0, 0.0],
+            [549.6, 0.0, 559.2],
+            [556.0, 548.8, 559.2],
+            [556.0, 548.8, 0.0],
+        ],
+        &red,
+    );
+    add(
+        [
+            [0.0, 0.0, 559.2],
+            [0.0, 0.0, 0.0],
+            [0.0, 548.8, 0.0],
+            [0.0, 548.8, 559.2],
+        ],
+        &green,
+    );
+    // The short block, then the tall one: the top and four sides of each.
+    let short = [[130.0, 65.0], [82.0, 225.0], [240.0, 272.0], [290.0, 114.0]];
+    let tall = [
+        [423.0, 247.0],
+        [265.0, 296.0],
+        [314.0, 456.0],
+        [472.0, 406.0],
+    ];
+    for (footprint, height) in [(short, 165.0), (tall, 330.0)] {
+        let at = |[x, z]: [f64; 2], y: f64| [x, y, z];
+        add(footprint.map(|corner| at(corner, height)), &white);
+        for i in 0..4 {
+            let (a, b) = (footprint[i], footprint[(i + 1) % 4]);
+            add(
+                [at(a, 0.0), at(a, height), at(b, height), at(b, 0.0)],
+                &white,
+            );
+        }
+    }
+
+    let mut lights = LightSet::new();
+    let lamp = [
+        [343.0, 548.7, 227.0],
+        [343.0, 548.7, 332.0],
+        [213.0, 548.7, 332.0],
+        [213.0, 548.7, 227.0],
+    ];
+    for triangle in quad(lamp, &light) {
+        let index = lights.add_triangle(&triangle);
+        world.push(Arc::new(Emitter::new(Arc::new(triangle), index)));
+    }
+
+    // A 35 mm lens over a 25 mm square of film.
+    let vfov = 2.0 * (12.5f64 / 35.0).atan().to_degrees();
+    let camera = Camera::new(
+        DVec3::new(278.0, 273.0, -800.0),
+        DVec3::new(278.0, 273.0, 0.0),
+        DVec3::Y,
+        vfov,
+        1.0,
+        0.0,
+        800.0,
+    );
+    ReferenceScene {
+        camera,
+        world: Arc::new(BvhNode::new(world)),
+        lights: Arc::new(lights),
+        environment: Arc::new(SolidBackground::new(DVec3::ZERO)),
+    }
+}
+
+// A unit sphere of `material` inside a uniform white environment, filling
+// the middle of the view. Every ray it scatters escapes and sees radiance
+// 1, so it shows exactly its albedo: a material that loses no energy
+// disappears into the background, and none may come out brighter.
+pub fn white_furnace(material: Arc<dyn Material>) -> ReferenceScene {
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        4.0,
+    );
+    ReferenceScene {
+        camera,
+        world: Arc::new(Sphere::new(DVec3::ZERO, 1.0, material)),
+        lights: Arc::new(LightSet::new()),
+        environment: Arc::new(SolidBackground::new(DVec3::ONE)),
+    }
+}
+
+// After the figure in Veach's thesis that motivated multiple importance
+// sampling: four glossy plates, smoothest at the back, reflecting a row of
+// four spherical lights of the same power but very different sizes. Light
+// sampling alone is noisy for the big lights on the smooth plates, BSDF
+// sampling alone for the small lights on the rough ones. Each plate is
+// turned so that the middle of the row shows in it from the camera.
+pub fn veach_mis(aspect_ratio: f64) -> ReferenceScene {
+    let eye = DVec3::new(0.0, 2.0, 15.0);
+    let mut world = HittableList::new();
+    let mut lights = LightSet::new();
+    // Radii and radiances; each light gives out the same power.
+    let lamps = [
+        (0.0333, 901.803),
+        (0.1, 100.0),
+        (0.3, 11.1111),
+        (0.9, 1.23457),
+    ];
+    for (i, (radius, radiance)) in lamps.into_iter().enumerate() {
+        let center = DVec3::new(-3.75 + 2.5 * i as f64, 0.0, 0.0);
+        let material: Arc<dyn Material> = Arc::new(DiffuseLight::new(DVec3::splat(radiance)));
+        let index = lights.add_sphere(center, radius, material.clone());
+        let sphere = Sphere::new(center, radius, material);
+        world.push(Arc::new(Emitter::new(Arc::new(sphere), index)));
+    }
+
+    let albedo = Arc::new(SolidColor::new(DVec3::splat(0.35)));
+    // GGX alphas of the plates, from the back.
+    for (i, alpha) in [0.005f64, 0.02, 0.05, 0.1].into_iter().enumerate() {
+        let center = DVec3::new(0.0, -2.0 - 0.35 * i as f64, 1.0 + 1.1 * i as f64);
+        let roughness = alpha.sqrt();
+        let material: Arc<dyn Material> =
+            Arc::new(AnisotropicMetal::new(albedo.clone(), roughness, roughness));
+        let normal = ((eye - center).normalize() - center.normalize()).normalize();
+        let across = normal.cross(DVec3::X).normalize();
+        let (half_width, half_depth) = (4.0 * DVec3::X, 0.5 * across);
+        let corners = [
+            center - half_width - half_depth,
+            center + half_width - half_depth,
+            center + half_width + half_depth,
+            center - half_width + half_depth,
+        ];
+        for triangle in quad(corners.map(DVec3::to_array), &material) {
+            world.push(Arc::new(triangle));
+        }
+    }
+
+    let camera = Camera::new(
+        eye,
+        DVec3::new(0.0, -2.0, 2.5),
+        DVec3::Y,
+        28.0,
+        aspect_ratio,
+        0.0,
+        13.0,
+    );
+    ReferenceScene {
+        camera,
+        world: Arc::new(BvhNode::new(world)),
+        lights: Arc::new(lights),
+        environment: Arc::new(SolidBackground::new(DVec3::ZERO)),
+    }
+}
+
+fn lambertian(albedo: DVec3) -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(albedo))))
+}
+
+fn quad(corners: [[f64; 3]; 4], material: &Arc<dyn Material>) -> [Triangle; 2] {
+    let [a, b, c, d] = corners.map(DVec3::from_array);
+    [
+        Triangle::new([a, b, c], material.clone()),
+        Triangle::new([a, c, d], material.clone()),
+    ]
+}
@@ -0,0 +1,2194 @@
+use crate::bloom::{self, BloomSettings};
+use crate::camera::Camera;
+use crate::checkpoint::RenderCheckpoint;
+use crate::color::{sample_wavelength, wavelength_weight};
+use crate::control::{CancellationToken, RenderControl};
+use crate::environment::Environment;
+use crate::filter::{Film, PixelFilter};
+use crate::hittable::{hit_surface, HitRecord, Hittable, DEFAULT_EPSILON};
+use crate::interval::Interval;
+use crate::irradiance_cache::{IrradianceCache, IrradianceCacheSettings};
+use crate::lights::{LightSample, LightSet, PortalDistribution, Reservoir};
+use crate::material::{random_unit_vector, Lambertian, Material, Medium};
+use crate::metrics::RenderProgress;
+use crate::mipmap::FootprintScope;
+use crate::output::scene_hash;
+use crate::photon_map::PhotonMap;
+use crate::ray::{Ray, RayKind};
+use crate::sample_map::SampleMap;
+use crate::sampler::{mix_hash, Sampler, SamplerKind};
+use crate::spherical_harmonics::{ShAmbientSettings, ShEnvironment};
+use crate::stats::{self, RenderStats};
+use crate::temporal::TemporalSettings;
+use crate::texture::SolidColor;
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    // Width of a pixel over its height on the display. Values other than 1
+    // render a stretched view into the stored image, for anamorphic and
+    // other non-square-pixel delivery formats.
+    pub pixel_aspect_ratio: f64,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub tile_size: u32,
+    pub sampler: SamplerKind,
+    pub filter: PixelFilter,
+    // Renders with the same seed and settings are bit-for-bit identical.
+    pub seed: u64,
+    // Keeps `seed` unchanged across animation frames. By default each frame
+    // gets its own seed, so the noise doesn't sit still while the image
+    // moves.
+    pub lock_seed: bool,
+    pub russian_roulette_start: u32,
+    pub russian_roulette: RouletteMode,
+    pub min_contribution: f64,
+    pub internal_reflection_boost: u32,
+    pub sample_clamp: SampleClamp,
+    pub debug: IntegratorDebug,
+    pub aovs: AovSelection,
+    // When set, replaces the fixed `samples_per_pixel` budget.
+    pub adaptive: Option<AdaptiveSampling>,
+    pub irradiance_cache: Option<IrradianceCacheSettings>,
+    pub sh_ambient: Option<ShAmbientSettings>,
+    pub direct_lighting: DirectLighting,
+    pub integrator: Integrator,
+    // Traces each camera sample at one wavelength, so that dispersive
+    // dielectrics split light into a continuous spectrum instead of three
+    // RGB images. Everything else stays RGB; the colour noise this adds
+    // averages out with more samples.
+    pub spectral: bool,
+    // Accumulates animation frames over time; see `TemporalAccumulator`.
+    pub temporal: Option<TemporalSettings>,
+    // Renders only this part of the frame, leaving the rest black.
+    pub crop: Option<CropWindow>,
+    // Leaves out the environment wherever the camera sees it and gives the
+    // beauty image an alpha channel, for laying the render over a
+    // photograph: opaque over objects, as dark as the shadows on shadow
+    // catchers, and clear elsewhere.
+    pub transparent: bool,
+    // Glow and diffraction spikes around highlights, added to the finished
+    // image by `finish`; the beauty pass of `render_passes` is left
+    // without them.
+    pub bloom: Option<BloomSettings>,
+}
+
+// A rectangle of the frame in pixels, from its top-left corner. Tiles keep
+// their places in the full frame, so the pixels inside come out as they
+// would in a render of all of it, up to filters and ReSTIR reuse reaching
+// across the edge.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct CropWindow {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Keeps sampling a pixel until the 95% confidence interval of its mean
+// luminance is narrower than `threshold` times the mean, within
+// [`min_samples`, `max_samples`].
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AdaptiveSampling {
+    pub threshold: f64,
+    pub min_samples: u32,
+    pub max_samples: u32,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            threshold: 0.05,
+            min_samples: 16,
+            max_samples: 1024,
+        }
+    }
+}
+
+impl AdaptiveSampling {
+    fn converged(&self, stats: &PixelStats) -> bool {
+        if stats.count < self.min_samples.max(2) {
+            return false;
+        }
+        let half_width = 1.96 * (stats.variance() / stats.count as f64).sqrt();
+        // Dark pixels would otherwise never satisfy a relative bound.
+        half_width <= self.threshold * stats.mean.max(1e-2)
+    }
+}
+
+// Limits on what one contribution may add to a camera sample. Clamping
+// removes fireflies, such as light found through long chains of glass, that
+// no number of samples averages away, at the cost of some energy in
+// bright caustics and highlights.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SampleClamp {
+    // Largest channel value of light that reached the camera after at most
+    // one bounce: emitters seen directly and direct lighting at the first
+    // hit.
+    pub direct: Option<f64>,
+    // The same for light that took two or more bounces.
+    pub indirect: Option<f64>,
+    // Drops NaN and infinite contributions, which would otherwise spoil
+    // the whole pixel.
+    pub discard_invalid: bool,
+}
+
+impl Default for SampleClamp {
+    fn default() -> Self {
+        Self {
+            direct: None,
+            indirect: None,
+            discard_invalid: true,
+        }
+    }
+}
+
+impl SampleClamp {
+    // `value` as it may be added after `bounces` bounces, scaled down to
+    // the limit with its hue kept.
+    fn apply(&self, value: DVec3, bounces: u32) -> DVec3 {
+        if self.discard_invalid && !value.is_finite() {
+            return DVec3::ZERO;
+        }
+        let limit = if bounces <= 1 {
+            self.direct
+        } else {
+            self.indirect
+        };
+        match limit {
+            Some(limit) if value.max_element() > limit => value * (limit / value.max_element()),
+            _ => value,
+        }
+    }
+}
+
+// Running mean and variance of a pixel's sample luminance (Welford).
+#[derive(Default)]
+struct PixelStats {
+    count: u32,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStats {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+}
+
+// Auxiliary first-hit passes to record alongside the beauty image, e.g. as
+// guides for an external denoiser or for compositing.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AovSelection {
+    pub albedo: bool,
+    // World-space shading normal, facing the camera.
+    pub normal: bool,
+    // Distance from the camera to the first hit; 0 for misses.
+    pub depth: bool,
+    pub object_id: bool,
+    // Scene materials keep their IDs from render to render; others, such
+    // as those of MTL files, only within one.
+    pub material_id: bool,
+    // Cryptomatte-style mattes: the IDs seen in each pixel with the
+    // fraction of its samples that hit each, so that objects or materials
+    // can be cut out with antialiased, motion-blurred and defocused edges.
+    pub object_matte: bool,
+    pub material_matte: bool,
+    pub utility_sampling: UtilitySampling,
+}
+
+// Which of a pixel's samples the depth and ID passes come from. Albedo and
+// normals are always averaged.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum UtilitySampling {
+    // Depth averaged over the samples, which blends foreground and
+    // background depths at edges; IDs from the first sample.
+    #[default]
+    #[serde(rename = "average")]
+    Average,
+    // Depth and IDs from the first sample, so that they always describe
+    // the same surface.
+    #[serde(rename = "first_sample")]
+    FirstSample,
+    // Depth and IDs from the nearest hit among the samples, so that thin
+    // foreground objects keep their mattes wherever they show at all, as
+    // they do in a beauty pass spread by a filter wider than a pixel.
+    #[serde(rename = "closest")]
+    Closest,
+}
+
+impl AovSelection {
+    pub fn any(&self) -> bool {
+        self.albedo
+            || self.normal
+            || self.depth
+            || self.object_id
+            || self.material_id
+            || self.object_matte
+            || self.material_matte
+    }
+}
+
+// ID for the ID passes and mattes from the name of an object or material,
+// the same in every render. IDs stay below 2^24 so they survive 32-bit
+// float output, and have bit 23 set so they never clash with the numbered
+// IDs of unnamed objects.
+pub fn name_id(name: &str) -> u32 {
+    (scene_hash(name.as_bytes()) & 0x7F_FFFF) as u32 | 0x80_0000
+}
+
+// IDs each matte keeps per pixel, most coverage first; pixels where more
+// meet lose the rest. They are written two to an RGBA layer, as
+// cryptomatte packs them: ID, coverage, ID, coverage.
+pub const MATTE_RANKS: usize = 4;
+pub const MATTE_LAYERS: usize = MATTE_RANKS / 2;
+
+// The IDs hit by a pixel's samples, counting the samples that hit each
+// until the pixel is finished, and then the fraction of them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct IdCoverage {
+    ids: [u32; MATTE_RANKS],
+    coverage: [f64; MATTE_RANKS],
+}
+
+impl IdCoverage {
+    // A sample hit `id`; 0, for misses and untagged objects, isn't kept.
+    fn add(&mut self, id: u32) {
+        if id == 0 {
+            return;
+        }
+        let slot = self.ids.iter().position(|&i| i == id);
+        let slot = slot.or_else(|| self.coverage.iter().position(|&c| c == 0.0));
+        if let Some(slot) = slot {
+            self.ids[slot] = id;
+            self.coverage[slot] += 1.0;
+        }
+    }
+
+    fn finish(&mut self, samples: f64) {
+        let mut ranked: Vec<(u32, f64)> = self.ids.into_iter().zip(self.coverage).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (rank, (id, count)) in ranked.into_iter().enumerate() {
+            self.ids[rank] = id;
+            self.coverage[rank] = count / samples;
+        }
+    }
+}
+
+// The coverage of `id` in each pixel of the matte `layers`, as
+// `RenderPasses::object_matte` and `material_matte` hold them.
+pub fn matte_coverage(layers: &[ImageBuffer], id: u32) -> Vec<f64> {
+    let Some(first) = layers.first() else {
+        return Vec::new();
+    };
+    let mut coverage = vec![0.0; first.pixels.len()];
+    for layer in layers {
+        let alpha = layer.alpha.as_deref().unwrap_or(&[]);
+        for (i, pixel) in layer.pixels.iter().enumerate() {
+            if pixel.x == id as f64 {
+                coverage[i] += pixel.y;
+            }
+            if pixel.z == id as f64 {
+                coverage[i] += alpha.get(i).copied().unwrap_or(0.0);
+            }
+        }
+    }
+    coverage
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum RouletteMode {
+    // Survival proportional to the accumulated path throughput.
+    #[default]
+    #[serde(rename = "throughput")]
+    Throughput,
+    // Survival proportional to the albedo of the current bounce only.
+    #[serde(rename = "albedo")]
+    Albedo,
+    #[serde(rename = "fixed")]
+    Fixed { probability: f64 },
+}
+
+// Replaces the beauty image with a view of how the integrator combined its
+// sampling techniques, for tracking down fireflies and double counting in
+// custom materials.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub enum IntegratorDebug {
+    #[default]
+    #[serde(rename = "off")]
+    Off,
+    // Red: radiance found by BSDF sampling; green: radiance found by
+    // sampling the environment.
+    #[serde(rename = "technique")]
+    Technique,
+    // Red: MIS weight of the BSDF sample leaving the first hit; green: MIS
+    // weight of the light sample taken there.
+    #[serde(rename = "mis_weights")]
+    MisWeights,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Integrator {
+    #[default]
+    #[serde(rename = "path")]
+    Path,
+    // Biased but fast, for interactive navigation: one light and one
+    // environment sample at the first non-specular hit, plus either a
+    // single cosine bounce that is lit the same way or, when `ambient` is
+    // set, that constant radiance times the surface albedo. Mirrors and
+    // glass are still followed up to `max_depth`.
+    #[serde(rename = "preview")]
+    Preview { ambient: Option<DVec3> },
+    // Path tracing with caustics (light reaching diffuse surfaces through
+    // mirrors and glass) taken from a photon map traced from the lights
+    // before rendering, instead of found by chance. `photons` are emitted
+    // in total and gathered within `radius` world units, which blurs the
+    // caustics slightly in exchange for far less noise.
+    #[serde(rename = "photon")]
+    Photon {
+        #[serde(default = "default_photons")]
+        photons: u32,
+        #[serde(default = "default_photon_radius")]
+        radius: f64,
+    },
+    // For checking shapes and contacts: white where the first hit is open
+    // to the sky, darker where nearby geometry hides it. Each sample
+    // follows one cosine-weighted ray from the first hit, which counts as
+    // blocked only by surfaces within `distance` world units, or at any
+    // distance without it. Lights and materials play no part.
+    #[serde(rename = "ambient_occlusion")]
+    AmbientOcclusion { distance: Option<f64> },
+    // Path tracing with every surface grey Lambertian of `albedo`, for
+    // judging the lighting before the materials. Lights still shine, and
+    // clouds and fog keep their media.
+    #[serde(rename = "clay")]
+    Clay {
+        #[serde(default = "default_clay_albedo")]
+        albedo: f64,
+    },
+    // The first hit's world-space shading normal, mapped from [-1, 1] to
+    // [0, 1] in RGB.
+    #[serde(rename = "normals")]
+    Normals,
+    // The first hit's texture coordinates, wrapped to [0, 1), in red and
+    // green.
+    #[serde(rename = "uv")]
+    Uv,
+    // Surfaces shaded grey by how squarely they face the camera, with the
+    // edges of triangles drawn black wherever one of the hit's barycentric
+    // coordinates is below `width`.
+    #[serde(rename = "wireframe")]
+    Wireframe {
+        #[serde(default = "default_wire_width")]
+        width: f64,
+    },
+    // BVH nodes tested by each camera ray on its way to the first hit,
+    // from black through red and yellow to white at `max_nodes`. Only the
+    // `stats` feature counts them; without it the image stays black.
+    #[serde(rename = "bvh_heat")]
+    BvhHeat {
+        #[serde(default = "default_heat_max_nodes")]
+        max_nodes: u32,
+    },
+}
+
+fn default_photons() -> u32 {
+    200_000
+}
+
+fn default_photon_radius() -> f64 {
+    0.05
+}
+
+fn default_clay_albedo() -> f64 {
+    0.5
+}
+
+fn default_wire_width() -> f64 {
+    0.02
+}
+
+fn default_heat_max_nodes() -> u32 {
+    100
+}
+
+// How next-event estimation picks a point on the scene's emitters.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum DirectLighting {
+    // One sample per vertex, with lights chosen in proportion to their
+    // power and MIS-weighted against BSDF sampling.
+    #[default]
+    #[serde(rename = "power")]
+    Power,
+    // Reservoir resampling (ReSTIR): `candidates` power samples are
+    // resampled towards their unshadowed contribution and only the survivor
+    // gets a shadow ray. At the first hit the reservoirs of up to
+    // `spatial_neighbours` nearby pixels in the same tile, and the pixel's
+    // previous sample, are merged in as well. Reuse ignores visibility
+    // between neighbours, which slightly biases soft shadow edges in
+    // exchange for far less noise with many lights.
+    #[serde(rename = "restir")]
+    Restir {
+        #[serde(default = "default_restir_candidates")]
+        candidates: u32,
+        #[serde(default = "default_restir_neighbours")]
+        spatial_neighbours: u32,
+    },
+}
+
+fn default_restir_candidates() -> u32 {
+    32
+}
+
+fn default_restir_neighbours() -> u32 {
+    4
+}
+
+// Random walks inside a subsurface medium longer than this are cut short,
+// losing a little energy in very thick, weakly absorbing objects.
+const MAX_WALK_STEPS: u32 = 1024;
+
+// Camera rays are traced in packets of this many pixels square.
+const RAY_PACKET_SIZE: u32 = 8;
+
+// Already shaded pixels that spatial reuse may draw from, nearest first.
+const NEIGHBOUR_OFFSETS: [(i32, i32); 8] = [
+    (-1, 0),
+    (0, -1),
+    (-1, -1),
+    (1, -1),
+    (-2, 0),
+    (0, -2),
+    (-2, -1),
+    (2, -1),
+];
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 400,
+            height: 225,
+            pixel_aspect_ratio: 1.0,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            tile_size: 32,
+            sampler: SamplerKind::default(),
+            filter: PixelFilter::default(),
+            seed: 0,
+            lock_seed: false,
+            russian_roulette_start: 3,
+            russian_roulette: RouletteMode::default(),
+            min_contribution: 0.0,
+            internal_reflection_boost: 16,
+            sample_clamp: SampleClamp::default(),
+            debug: IntegratorDebug::Off,
+            aovs: AovSelection::default(),
+            adaptive: None,
+            irradiance_cache: None,
+            sh_ambient: None,
+            direct_lighting: DirectLighting::default(),
+            integrator: Integrator::default(),
+            temporal: None,
+            spectral: false,
+            crop: None,
+            transparent: false,
+            bloom: None,
+        }
+    }
+}
+
+impl RenderSettings {
+    // Pixels rendered, as (x0, y0, x1, y1): the crop window clipped to the
+    // frame, or all of it.
+    pub fn render_window(&self) -> (u32, u32, u32, u32) {
+        match self.crop {
+            Some(crop) => {
+                let x0 = crop.x.min(self.width);
+                let y0 = crop.y.min(self.height);
+                let x1 = crop.x.saturating_add(crop.width).min(self.width);
+                let y1 = crop.y.saturating_add(crop.height).min(self.height);
+                (x0, y0, x1, y1)
+            }
+            None => (0, 0, self.width, self.height),
+        }
+    }
+
+    // Aspect ratio of the displayed image, allowing for non-square pixels.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width.max(1) as f64 * self.pixel_aspect_ratio / self.height.max(1) as f64
+    }
+
+    // Settings for frame `frame` of an animation. Frame 0 keeps `seed`, so
+    // it matches a still render of the same scene.
+    pub fn for_frame(&self, frame: u32) -> Self {
+        let mut settings = *self;
+        if !self.lock_seed {
+            settings.seed = self
+                .seed
+                .wrapping_add((frame as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        settings
+    }
+}
+
+#[derive(Clone)]
+pub struct ImageBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<DVec3>,
+    // Coverage of each pixel, which `pixels` are premultiplied by, for
+    // images rendered with `RenderSettings::transparent`.
+    pub alpha: Option<Vec<f64>>,
+}
+
+impl ImageBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![DVec3::ZERO; (width * height) as usize],
+            alpha: None,
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> DVec3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: DVec3) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    // Gamma-2 encoded 8-bit RGB, rows top to bottom.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for color in &self.pixels {
+            for c in color.to_array() {
+                let c = if c.is_finite() {
+                    c.max(0.0).sqrt()
+                } else {
+                    0.0
+                };
+                bytes.push((256.0 * c.clamp(0.0, 0.999)) as u8);
+            }
+        }
+        bytes
+    }
+}
+
+// The beauty image plus whichever passes `RenderSettings::aovs` asked for.
+pub struct RenderPasses {
+    pub beauty: ImageBuffer,
+    pub albedo: Option<ImageBuffer>,
+    pub normal: Option<ImageBuffer>,
+    pub depth: Option<ImageBuffer>,
+    pub object_id: Option<ImageBuffer>,
+    pub material_id: Option<ImageBuffer>,
+    // RGBA layers of ID and coverage pairs; see `AovSelection::object_matte`
+    // and `matte_coverage`.
+    pub object_matte: Option<[ImageBuffer; MATTE_LAYERS]>,
+    pub material_matte: Option<[ImageBuffer; MATTE_LAYERS]>,
+    // Summed over every tile; see `RenderStats`.
+    pub stats: RenderStats,
+}
+
+impl RenderPasses {
+    // Enabled auxiliary passes with their conventional names.
+    pub fn aovs(&self) -> Vec<(&'static str, &ImageBuffer)> {
+        let mut aovs: Vec<_> = [
+            ("albedo", &self.albedo),
+            ("normal", &self.normal),
+            ("depth", &self.depth),
+            ("object_id", &self.object_id),
+            ("material_id", &self.material_id),
+        ]
+        .into_iter()
+        .filter_map(|(name, image)| image.as_ref().map(|image| (name, image)))
+        .collect();
+        let mattes = [
+            (["object_matte00", "object_matte01"], &self.object_matte),
+            (
+                ["material_matte00", "material_matte01"],
+                &self.material_matte,
+            ),
+        ];
+        for (names, layers) in mattes {
+            let Some(layers) = layers else {
+                continue;
+            };
+            aovs.extend(names.into_iter().zip(layers));
+        }
+        aovs
+    }
+}
+
+// First-hit data for one pixel. Albedo and normal are averaged over the
+// pixel's samples; depth and IDs are picked by `UtilitySampling`, as IDs
+// can't be averaged.
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+pub(crate) struct AovSample {
+    albedo: DVec3,
+    normal: DVec3,
+    depth: f64,
+    object_id: u32,
+    material_id: u32,
+    // Averaged like albedo, and always recorded.
+    #[serde(default)]
+    alpha: f64,
+    #[serde(default)]
+    object_matte: IdCoverage,
+    #[serde(default)]
+    material_matte: IdCoverage,
+}
+
+// Box-filtered pixels of a tile, row by row, and the samples splatted by
+// a wider filter.
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct RenderedTile {
+    pub(crate) pixels: Vec<(DVec3, AovSample)>,
+    pub(crate) film: Option<Film>,
+    pub(crate) camera_rays: u64,
+    #[serde(default)]
+    pub(crate) stats: RenderStats,
+}
+
+impl RenderedTile {
+    // Zeroes values that have become infinite or NaN, which JSON can't
+    // carry.
+    pub(crate) fn clear_non_finite(&mut self) {
+        let finite = |v: DVec3| if v.is_finite() { v } else { DVec3::ZERO };
+        for (color, aov) in &mut self.pixels {
+            *color = finite(*color);
+            aov.albedo = finite(aov.albedo);
+            aov.normal = finite(aov.normal);
+            if !aov.depth.is_finite() {
+                aov.depth = 0.0;
+            }
+        }
+        if let Some(film) = &mut self.film {
+            film.clear_non_finite();
+        }
+    }
+}
+
+// A pixel of the packet being rendered, and what its samples have added
+// up to so far.
+struct PacketPixel {
+    x: u32,
+    y: u32,
+    samples: u32,
+    // The sample map's block the pixel is rendered over, in pixels.
+    block_size: (f64, f64),
+    color: DVec3,
+    aov: AovSample,
+    stats: PixelStats,
+    converged: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub(crate) struct Tile {
+    pub(crate) x0: u32,
+    pub(crate) y0: u32,
+    pub(crate) x1: u32,
+    pub(crate) y1: u32,
+}
+
+impl Tile {
+    pub(crate) fn pixels(&self) -> u64 {
+        (self.x1 - self.x0) as u64 * (self.y1 - self.y0) as u64
+    }
+
+    // Squared distance from pixel (`x`, `y`) to the tile, zero inside it.
+    fn distance_squared(&self, x: u32, y: u32) -> u64 {
+        let dx = self.x0.saturating_sub(x).max(x.saturating_sub(self.x1 - 1)) as u64;
+        let dy = self.y0.saturating_sub(y).max(y.saturating_sub(self.y1 - 1)) as u64;
+        dx * dx + dy * dy
+    }
+}
+
+// Takes the next of the `pending` tiles to render: the one nearest `focus`
+// when there is one, otherwise the first.
+fn next_tile(pending: &mut Vec<(usize, Tile)>, focus: Option<(u32, u32)>) -> Option<(usize, Tile)> {
+    if pending.is_empty() {
+        return None;
+    }
+    let position = match focus {
+        Some((x, y)) => (0..pending.len())
+            .min_by_key(|&i| pending[i].1.distance_squared(x, y))
+            .unwrap_or(0),
+        None => 0,
+    };
+    Some(pending.remove(position))
+}
+
+pub struct Renderer {
+    pub world: Arc<dyn Hittable>,
+    pub environment: Arc<dyn Environment>,
+    pub lights: Arc<LightSet>,
+    // Updated as tiles finish, for the live metrics endpoint.
+    pub progress: Option<Arc<RenderProgress>>,
+    // Lets another thread pause and resume renders between tiles.
+    pub control: Option<Arc<RenderControl>>,
+    // Lets another thread stop renders early, keeping the tiles finished.
+    pub cancellation: Option<CancellationToken>,
+    // Collects finished tiles, and supplies those of an earlier attempt.
+    pub checkpoint: Option<Arc<RenderCheckpoint>>,
+    // Spends fewer samples or pixels on parts of the image.
+    pub sample_map: Option<Arc<SampleMap>>,
+    // `environment` projected for `RenderSettings::sh_ambient` on first use.
+    ambient: OnceLock<ShEnvironment>,
+    // `environment` as seen through the portals of `lights`, on first use.
+    portals: OnceLock<PortalDistribution>,
+}
+
+impl Renderer {
+    pub fn new(world: Arc<dyn Hittable>, environment: Arc<dyn Environment>) -> Self {
+        Self {
+            world,
+            environment,
+            lights: Arc::new(LightSet::new()),
+            progress: None,
+            control: None,
+            cancellation: None,
+            checkpoint: None,
+            sample_map: None,
+            ambient: OnceLock::new(),
+            portals: OnceLock::new(),
+        }
+    }
+
+    // Emitters in `world` to sample directly; they should be wrapped in
+    // `Emitter` with matching indices.
+    pub fn with_lights(mut self, lights: Arc<LightSet>) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Arc<RenderProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn with_control(mut self, control: Arc<RenderControl>) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn with_checkpoint(mut self, checkpoint: Arc<RenderCheckpoint>) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub fn with_sample_map(mut self, sample_map: Arc<SampleMap>) -> Self {
+        self.sample_map = Some(sample_map);
+        self
+    }
+
+    // The finished image: the beauty pass with `finish` applied.
+    pub fn render(&self, camera: &Camera, settings: &RenderSettings) -> ImageBuffer {
+        let mut image = self.render_passes(camera, settings).beauty;
+        finish(&mut image, settings);
+        image
+    }
+
+    pub fn render_passes(&self, camera: &Camera, settings: &RenderSettings) -> RenderPasses {
+        let cache = settings.irradiance_cache.map(IrradianceCache::new);
+        let photons = self.photon_map(settings);
+        let tiles = split_tiles(settings);
+        if let Some(progress) = &self.progress {
+            let (x0, y0, x1, y1) = settings.render_window();
+            progress.start((x1 - x0) as u64 * (y1 - y0) as u64, tiles.len() as u64);
+        }
+        let rendered = self.render_tiles(camera, settings, cache.as_ref(), photons.as_ref(), tiles);
+        let passes = assemble(settings, rendered);
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+        passes
+    }
+
+    // Caustic photons for `Integrator::Photon`, traced from the lights.
+    pub fn photon_map(&self, settings: &RenderSettings) -> Option<PhotonMap> {
+        let Integrator::Photon { photons, radius } = settings.integrator else {
+            return None;
+        };
+        Some(PhotonMap::build(
+            &*self.world,
+            &self.lights,
+            photons,
+            radius,
+            settings.max_depth,
+            settings.seed,
+        ))
+    }
+
+    // Renders `tiles` in parallel, returning them in the order given. In a
+    // browser, where WebAssembly has no threads, they render one by one.
+    // Tiles start nearest the control's focus first, when it has one.
+    // Pausing lets the render threads go back to their pool, with only the
+    // calling thread waiting to resume. Once the render is cancelled, tiles
+    // not yet started are left out.
+    pub(crate) fn render_tiles(
+        &self,
+        camera: &Camera,
+        settings: &RenderSettings,
+        cache: Option<&IrradianceCache>,
+        photons: Option<&PhotonMap>,
+        tiles: Vec<Tile>,
+    ) -> Vec<(Tile, RenderedTile)> {
+        let checkpoint = self.checkpoint.as_deref();
+        let mut rendered = Vec::with_capacity(tiles.len());
+        let mut pending = Vec::new();
+        for (index, tile) in tiles.into_iter().enumerate() {
+            match checkpoint.and_then(|c| c.finished(&tile)) {
+                Some(finished) => {
+                    if let Some(progress) = &self.progress {
+                        progress.add_tile(tile.pixels(), finished.camera_rays);
+                    }
+                    rendered.push((index, tile, finished));
+                }
+                None => pending.push((index, tile)),
+            }
+        }
+        let cancelled = || self.cancellation.as_ref().is_some_and(|c| c.is_cancelled());
+        let paused = || self.control.as_ref().is_some_and(|c| c.is_paused());
+        let pending = Mutex::new(pending);
+        let rendered = Mutex::new(rendered);
+        // Renders tiles until none are left, or the render is paused or
+        // cancelled.
+        let work = || {
+            while !paused() && !cancelled() {
+                let focus = self.control.as_ref().and_then(|c| c.focus());
+                let Some((index, tile)) = next_tile(&mut pending.lock().unwrap(), focus) else {
+                    return;
+                };
+                let tile_rendered = self.render_tile(camera, settings, cache, photons, tile);
+                if let Some(checkpoint) = checkpoint {
+                    checkpoint.record(tile, &tile_rendered);
+                }
+                rendered.lock().unwrap().push((index, tile, tile_rendered));
+            }
+        };
+        loop {
+            if let Some(control) = &self.control {
+                control.wait_while_paused(self.cancellation.as_ref());
+            }
+            if cancelled() || pending.lock().unwrap().is_empty() {
+                break;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            rayon::scope(|scope| {
+                for _ in 0..rayon::current_num_threads() {
+                    scope.spawn(|_| work());
+                }
+            });
+            #[cfg(target_arch = "wasm32")]
+            work();
+        }
+        let mut rendered = rendered.into_inner().unwrap();
+        rendered.sort_by_key(|&(index, ..)| index);
+        rendered
+            .into_iter()
+            .map(|(_, tile, tile_rendered)| (tile, tile_rendered))
+            .collect()
+    }
+
+    fn render_tile(
+        &self,
+        camera: &Camera,
+        settings: &RenderSettings,
+        cache: Option<&IrradianceCache>,
+        photons: Option<&PhotonMap>,
+        tile: Tile,
+    ) -> RenderedTile {
+        // Tiles run start to finish on one thread; drop whatever it counted
+        // before this one.
+        stats::take();
+        let max_samples = match settings.adaptive {
+            Some(adaptive) => adaptive.max_samples.max(1),
+            None => settings.samples_per_pixel,
+        };
+        let mut sampler = settings.sampler.create(max_samples, settings.seed);
+        let tile_pixels = ((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize;
+        let mut pixels = vec![(DVec3::ZERO, AovSample::default()); tile_pixels];
+        let record_aovs = settings.aovs.any();
+        let spatial_neighbours = match settings.direct_lighting {
+            DirectLighting::Restir {
+                spatial_neighbours, ..
+            } if !self.lights.is_empty() => {
+                (spatial_neighbours as usize).min(NEIGHBOUR_OFFSETS.len())
+            }
+            _ => 0,
+        };
+        let reuse = spatial_neighbours > 0;
+        let tile_width = (tile.x1 - tile.x0) as i32;
+        // Latest first-hit reservoir of each pixel in the tile.
+        let mut reservoirs: Vec<Option<PrimaryReservoir>> = if reuse {
+            vec![None; tile_pixels]
+        } else {
+            Vec::new()
+        };
+        let mut neighbours = Vec::with_capacity(spatial_neighbours + 1);
+        let mut camera_rays = 0;
+        let filter = settings.filter;
+        let mut film = (!filter.is_box()).then(|| {
+            Film::for_tile(
+                &filter,
+                (tile.x0, tile.y0, tile.x1, tile.y1),
+                settings.width,
+                settings.height,
+            )
+        });
+
+        // Integrators that find first hits their own way, such as the BVH
+        // heat map counting each ray's node tests, trace them one by one.
+        let packets = !matches!(
+            settings.integrator,
+            Integrator::Preview { .. }
+                | Integrator::AmbientOcclusion { .. }
+                | Integrator::Normals
+                | Integrator::Uv
+                | Integrator::Wireframe { .. }
+                | Integrator::BvhHeat { .. }
+        );
+        // The camera's physical exposure, vignetting and colour fringes
+        // apply to light, not to the views of shapes and sampling
+        // decisions.
+        let film_light = !matches!(
+            settings.integrator,
+            Integrator::AmbientOcclusion { .. }
+                | Integrator::Normals
+                | Integrator::Uv
+                | Integrator::Wireframe { .. }
+                | Integrator::BvhHeat { .. }
+        ) && matches!(settings.debug, IntegratorDebug::Off);
+        let film_scale = if film_light { camera.film_scale() } else { 1.0 };
+        // Draws the same camera rays as `sampler` ahead of it, for packets.
+        let mut ray_sampler = settings.sampler.create(max_samples, settings.seed);
+        let mut rays = Vec::new();
+        let mut packet: Vec<PacketPixel> = Vec::new();
+        let mut copies = Vec::new();
+        let mut active = Vec::new();
+        let index_of = |x: u32, y: u32| ((y - tile.y0) * tile_width as u32 + x - tile.x0) as usize;
+
+        // Square packets of pixels, row by row, take each sample index in
+        // turn, so the camera rays traced together are coherent.
+        for py in (tile.y0..tile.y1).step_by(RAY_PACKET_SIZE as usize) {
+            for px in (tile.x0..tile.x1).step_by(RAY_PACKET_SIZE as usize) {
+                packet.clear();
+                copies.clear();
+                for y in py..(py + RAY_PACKET_SIZE).min(tile.y1) {
+                    for x in px..(px + RAY_PACKET_SIZE).min(tile.x1) {
+                        let (density, block) = match &self.sample_map {
+                            Some(map) => (
+                                map.density(x, y, settings.width, settings.height),
+                                map.block_size(x, y, settings.width, settings.height),
+                            ),
+                            None => (1.0, 1),
+                        };
+                        // Pixels after the first of a block copy it; it was
+                        // rendered over the whole block, in this packet or
+                        // an earlier one.
+                        let first = (
+                            (x / block * block).max(tile.x0),
+                            (y / block * block).max(tile.y0),
+                        );
+                        let in_block = |map: &Arc<SampleMap>| {
+                            map.block_size(first.0, first.1, settings.width, settings.height)
+                                == block
+                        };
+                        if first != (x, y) && self.sample_map.as_ref().is_some_and(in_block) {
+                            copies.push(((x, y), first));
+                            continue;
+                        }
+                        let block_size = if first == (x, y) {
+                            let end = |v: u32, limit: u32| ((v / block + 1) * block).min(limit) - v;
+                            (end(x, tile.x1) as f64, end(y, tile.y1) as f64)
+                        } else {
+                            (1.0, 1.0)
+                        };
+                        packet.push(PacketPixel {
+                            x,
+                            y,
+                            samples: (max_samples as f64 * density).ceil() as u32,
+                            block_size,
+                            color: DVec3::ZERO,
+                            aov: AovSample::default(),
+                            stats: PixelStats::default(),
+                            converged: false,
+                        });
+                    }
+                }
+
+                for index in 0..max_samples {
+                    active.clear();
+                    active.extend(
+                        (0..packet.len())
+                            .filter(|&p| !packet[p].converged && index < packet[p].samples),
+                    );
+                    if active.is_empty() {
+                        break;
+                    }
+                    let hits = if packets {
+                        rays.clear();
+                        for &p in &active {
+                            let sampler = ray_sampler.as_mut();
+                            rays.push(camera_ray(camera, settings, sampler, &packet[p], index).0);
+                        }
+                        self.primary_hits(&rays)
+                    } else {
+                        Vec::new()
+                    };
+                    for (i, &p) in active.iter().enumerate() {
+                        let pixel = &mut packet[p];
+                        let (tx, ty) = ((pixel.x - tile.x0) as i32, (pixel.y - tile.y0) as i32);
+                        let (ray, lens_weight, (fx, fy)) =
+                            camera_ray(camera, settings, sampler.as_mut(), pixel, index);
+                        let primary = hits.get(i).copied();
+                        if record_aovs {
+                            let sampling = settings.aovs.utility_sampling;
+                            let aov = &mut pixel.aov;
+                            self.accumulate_aovs(&ray, primary, index == 0, sampling, aov);
+                        }
+                        neighbours.clear();
+                        if reuse {
+                            let offsets = std::iter::once((0, 0))
+                                .chain(NEIGHBOUR_OFFSETS.into_iter().take(spatial_neighbours));
+                            for (dx, dy) in offsets {
+                                let (nx, ny) = (tx + dx, ty + dy);
+                                if nx >= 0 && nx < tile_width && ny >= 0 {
+                                    if let Some(r) = reservoirs[(ny * tile_width + nx) as usize] {
+                                        neighbours.push(r);
+                                    }
+                                }
+                            }
+                        }
+                        let first = FirstVertex {
+                            hit: primary,
+                            neighbours: &neighbours,
+                            cone: camera.pixel_cone(settings.height),
+                        };
+                        let path =
+                            self.trace(&ray, settings, sampler.as_mut(), cache, photons, first);
+                        if reuse && path.reservoir.is_some() {
+                            reservoirs[(ty * tile_width + tx) as usize] = path.reservoir;
+                        }
+                        pixel.aov.alpha += path.alpha;
+                        let mut sample = film_scale * path.debug_color(settings.debug);
+                        if film_light {
+                            sample *= lens_weight;
+                        }
+                        if let (Some(wavelength), IntegratorDebug::Off) =
+                            (ray.wavelength, settings.debug)
+                        {
+                            sample *= wavelength_weight(wavelength);
+                        }
+                        if let Some(film) = &mut film {
+                            film.splat(&filter, pixel.x as f64 + fx, pixel.y as f64 + fy, sample);
+                        }
+                        pixel.color += sample;
+                        pixel.stats.add(luminance(sample));
+                        pixel.converged =
+                            settings.adaptive.is_some_and(|a| a.converged(&pixel.stats));
+                    }
+                }
+
+                for pixel in &mut packet {
+                    camera_rays += pixel.stats.count as u64;
+                    let n = pixel.stats.count.max(1) as f64;
+                    let aov = &mut pixel.aov;
+                    aov.albedo /= n;
+                    aov.alpha /= n;
+                    aov.normal = aov.normal.normalize_or_zero();
+                    if settings.aovs.utility_sampling == UtilitySampling::Average {
+                        aov.depth /= n;
+                    }
+                    aov.object_matte.finish(n);
+                    aov.material_matte.finish(n);
+                    pixels[index_of(pixel.x, pixel.y)] = (pixel.color / n, *aov);
+                }
+                for &((x, y), first) in &copies {
+                    pixels[index_of(x, y)] = pixels[index_of(first.0, first.1)];
+                }
+            }
+        }
+        if let Some(progress) = &self.progress {
+            progress.add_tile(tile_pixels as u64, camera_rays);
+        }
+        RenderedTile {
+            pixels,
+            film,
+            camera_rays,
+            stats: stats::take(),
+        }
+    }
+
+    // First hits of the camera rays `rays`, traced through the world
+    // together; surfaces cut away by their materials are looked past one
+    // ray at a time.
+    fn primary_hits(&self, rays: &[Ray]) -> Vec<Option<HitRecord<'_>>> {
+        let interval = Interval::after(DEFAULT_EPSILON);
+        let mut hits = vec![None; rays.len()];
+        self.world.hit_packet(rays, interval, &mut hits);
+        for (ray, hit) in rays.iter().zip(&mut hits) {
+            if hit.is_some_and(|rec| rec.material.is_cut_out(&rec)) {
+                *hit = hit_surface(&*self.world, ray, interval);
+            }
+        }
+        hits
+    }
+
+    fn accumulate_aovs(
+        &self,
+        ray: &Ray,
+        primary: Option<Option<HitRecord>>,
+        first_sample: bool,
+        sampling: UtilitySampling,
+        aov: &mut AovSample,
+    ) {
+        stats::count(|stats| stats.rays += 1);
+        let hit = primary
+            .unwrap_or_else(|| hit_surface(&*self.world, ray, Interval::after(DEFAULT_EPSILON)));
+        let Some(rec) = hit else {
+            return;
+        };
+        aov.albedo += rec.material.albedo(&rec);
+        aov.normal += rec.normal;
+        let material_id = match rec.material.id() {
+            0 => {
+                let address = rec.material as *const dyn Material as *const () as usize as u64;
+                // Below 2^24, like `name_id`, and never 0.
+                (mix_hash(address) & 0xFF_FFFF) as u32 | 1
+            }
+            id => id,
+        };
+        aov.object_matte.add(rec.object_id);
+        aov.material_matte.add(material_id);
+        let depth = rec.t * ray.direction.length();
+        // Depth is 0 until a sample hits something.
+        let picked = match sampling {
+            UtilitySampling::Average => {
+                aov.depth += depth;
+                first_sample
+            }
+            UtilitySampling::FirstSample => first_sample,
+            UtilitySampling::Closest => aov.depth == 0.0 || depth < aov.depth,
+        };
+        if picked {
+            if sampling != UtilitySampling::Average {
+                aov.depth = depth;
+            }
+            aov.object_id = rec.object_id;
+            aov.material_id = material_id;
+        }
+    }
+
+    pub fn ray_color(
+        &self,
+        ray: &Ray,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+    ) -> DVec3 {
+        self.trace(ray, settings, sampler, None, None, FirstVertex::default())
+            .radiance()
+    }
+
+    fn trace(
+        &self,
+        ray: &Ray,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        cache: Option<&IrradianceCache>,
+        photons: Option<&PhotonMap>,
+        first: FirstVertex,
+    ) -> PathSample {
+        match settings.integrator {
+            Integrator::Preview { ambient } => {
+                return self.trace_preview(ray, settings, sampler, ambient)
+            }
+            Integrator::AmbientOcclusion { distance } => {
+                return self.trace_occlusion(ray, settings, sampler, distance)
+            }
+            Integrator::Normals
+            | Integrator::Uv
+            | Integrator::Wireframe { .. }
+            | Integrator::BvhHeat { .. } => return self.trace_first_hit(ray, settings),
+            _ => {}
+        }
+        let mut path = self.trace_path(ray, settings, sampler, cache, photons, first);
+        // Everything gathered after a cached vertex, divided by the
+        // throughput that reached it, is the radiance leaving that vertex.
+        if let Some(cache) = cache {
+            let total = path.radiance();
+            for vertex in std::mem::take(&mut path.vertices) {
+                let outgoing = (total - vertex.radiance_before) / vertex.throughput;
+                cache.insert(vertex.key, outgoing);
+            }
+        }
+        path
+    }
+
+    fn trace_path(
+        &self,
+        ray: &Ray,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        cache: Option<&IrradianceCache>,
+        photons: Option<&PhotonMap>,
+        first: FirstVertex,
+    ) -> PathSample {
+        let mut path = PathSample::default();
+        let mut primary = first.hit;
+        let (mut cone_width, cone_spread) = first.cone;
+        let clamp = settings.sample_clamp;
+        let mut ray = *ray;
+        // Start of the interval `ray` is intersected over: the offset of
+        // the surface it left, once it has left one.
+        let mut t_min = DEFAULT_EPSILON;
+        let mut throughput = DVec3::ONE;
+        // Density of the BSDF sample that produced `ray`, or `None` after a
+        // specular bounce, which light sampling cannot reach.
+        let mut bsdf_pdf: Option<f64> = None;
+        let mut previous_point = ray.origin;
+        // Set while the path travels through the inside of a subsurface,
+        // volume or absorbing glass object.
+        let mut medium: Option<Medium> = None;
+        let mut walk_steps = 0;
+        // With a photon map, lights reached through mirrors and glass from
+        // a surface that gathered photons are caustics it already counted.
+        let mut gathered = false;
+        let mut via_specular = false;
+        let clay: Option<Arc<dyn Material>> = match settings.integrator {
+            Integrator::Clay { albedo } => Some(Arc::new(Lambertian::new(Arc::new(
+                SolidColor::new(DVec3::splat(albedo)),
+            )))),
+            _ => None,
+        };
+
+        let mut depth_budget = settings.max_depth;
+        let mut boost_left = settings.internal_reflection_boost;
+        let mut depth = 0;
+
+        while depth < depth_budget {
+            stats::count(|stats| stats.rays += 1);
+            let hit = primary
+                .take()
+                .unwrap_or_else(|| hit_surface(&*self.world, &ray, Interval::after(t_min)));
+            let Some(mut rec) = hit else {
+                if depth == 0 && settings.transparent {
+                    return path;
+                }
+                let weight = match bsdf_pdf {
+                    Some(pdf) => {
+                        power_heuristic(pdf, self.environment_pdf(previous_point, ray.direction))
+                    }
+                    None => 1.0,
+                };
+                let sky = weight * throughput * self.environment.value(ray.direction);
+                path.bsdf += clamp.apply(sky, depth);
+                if depth == 1 && bsdf_pdf.is_some() {
+                    path.bsdf_weight = weight;
+                }
+                return path;
+            };
+            if depth == 0 {
+                if rec.material.is_shadow_catcher() {
+                    return self.catch_shadow(&ray, &rec, settings, sampler);
+                }
+                path.alpha = 1.0;
+            }
+
+            // Inside a medium the path may scatter before reaching the
+            // surface it is heading for. Walk steps don't count towards
+            // `max_depth`, which would otherwise darken translucent objects.
+            if let Some(inside) = &medium {
+                let length = ray.direction.length();
+                let direction = ray.direction / length;
+                let (distance, weight) =
+                    inside.sample_distance(ray.origin, direction, rec.t * length, sampler);
+                throughput *= weight;
+                if distance < rec.t * length {
+                    walk_steps += 1;
+                    if walk_steps > MAX_WALK_STEPS || throughput == DVec3::ZERO {
+                        return path;
+                    }
+                    let origin = ray.at(distance / length);
+                    ray = Ray::new(origin, random_unit_vector(sampler))
+                        .with_time(ray.time)
+                        .with_wavelength(ray.wavelength)
+                        .with_kind(RayKind::Indirect);
+                    bsdf_pdf = None;
+                    gathered = false;
+                    continue;
+                }
+            }
+
+            // The pixel's cone, widened over the distance to the hit, sets
+            // how finely image textures are filtered while it is shaded.
+            cone_width += cone_spread * rec.t * ray.direction.length();
+            let _footprint = FootprintScope::enter(uv_footprint(&ray, &rec, cone_width));
+
+            let emitted = rec.material.emitted(rec.u, rec.v, rec.point);
+            let caustic = gathered && via_specular && rec.light.is_some();
+            if emitted != DVec3::ZERO && !caustic {
+                let weight = match (rec.light, bsdf_pdf) {
+                    (Some(index), Some(pdf)) => {
+                        self.emission_weight(settings, index, pdf, previous_point, &ray, &rec)
+                    }
+                    _ => 1.0,
+                };
+                path.bsdf += clamp.apply(weight * throughput * emitted, depth);
+            }
+            if let Some(clay) = clay.as_ref().filter(|_| !rec.material.is_index_matched()) {
+                rec.material = &**clay;
+            }
+
+            // The first hit is always traced so directly visible lighting
+            // stays sharp; the cache only shortcuts indirect bounces.
+            if let Some(cache) = cache.filter(|_| depth > 0 && rec.material.is_diffuse()) {
+                let key = cache.key(&rec);
+                if let Some(radiance) = cache.lookup(key) {
+                    path.bsdf += clamp.apply(throughput * radiance, depth + 1);
+                    return path;
+                }
+                if throughput.min_element() > 0.0 {
+                    path.vertices.push(CacheVertex {
+                        key,
+                        throughput,
+                        radiance_before: path.radiance(),
+                    });
+                }
+            }
+
+            // Past `after_bounces` the environment's light at diffuse hits
+            // comes from its projection, and the path ends here.
+            let ambient = settings
+                .sh_ambient
+                .filter(|sh| depth >= sh.after_bounces && rec.material.is_diffuse());
+            if ambient.is_none() {
+                if let Some((radiance, weight)) = self.sample_environment(&ray, &rec, sampler) {
+                    path.light += clamp.apply(throughput * radiance, depth + 1);
+                    if depth == 0 {
+                        path.light_weight = weight;
+                    }
+                }
+            }
+
+            if !self.lights.is_empty() {
+                let reuse = if depth == 0 { first.neighbours } else { &[] };
+                let (radiance, reservoir) =
+                    self.sample_lights(&ray, &rec, settings, sampler, reuse);
+                path.light += clamp.apply(throughput * radiance, depth + 1);
+                if depth == 0 {
+                    path.reservoir = reservoir.map(|reservoir| PrimaryReservoir {
+                        reservoir,
+                        normal: rec.normal,
+                        depth: rec.t * ray.direction.length(),
+                    });
+                }
+            }
+            let analytic = throughput * self.sample_analytic(&ray, &rec, sampler).0;
+            path.light += clamp.apply(analytic, depth + 1);
+
+            let specular = rec.material.eval(&ray, &rec, rec.normal).is_none();
+            if let Some(photons) = photons.filter(|_| !specular) {
+                path.light += clamp.apply(throughput * photons.radiance(&ray, &rec), depth + 1);
+            }
+            if specular {
+                // Crossing into or out of a cloud isn't a specular bounce.
+                via_specular |= !rec.material.is_index_matched();
+            } else {
+                gathered = photons.is_some();
+                via_specular = false;
+            }
+            if ambient.is_some() {
+                let sh = self
+                    .ambient
+                    .get_or_init(|| ShEnvironment::project(&*self.environment));
+                let radiance = rec.material.albedo(&rec) / PI * sh.irradiance(rec.normal);
+                path.light += clamp.apply(throughput * radiance, depth + 1);
+                return path;
+            }
+
+            let bounce_albedo = match rec.material.scatter(&ray, &rec, sampler) {
+                Some((scattered, attenuation)) => {
+                    // A back-face hit whose scattered ray stays on the inner
+                    // side is bouncing around inside a dielectric; give it
+                    // extra depth so thick glass doesn't turn black.
+                    let internal = !rec.front_face && scattered.direction.dot(rec.normal) > 0.0;
+                    if internal && boost_left > 0 {
+                        depth_budget += 1;
+                        boost_left -= 1;
+                    }
+                    // Crossing into or out of a cloud isn't a bounce.
+                    if rec.material.is_index_matched() {
+                        depth_budget += 1;
+                    }
+                    // Directions light sampling can't produce (zero BSDF
+                    // value, e.g. transmission) keep their full weight.
+                    bsdf_pdf = rec
+                        .material
+                        .eval(&ray, &rec, scattered.direction)
+                        .filter(|f| *f != DVec3::ZERO)
+                        .map(|_| rec.material.pdf(&ray, &rec, scattered.direction));
+                    throughput *= attenuation;
+                    let outward = if rec.front_face {
+                        rec.normal
+                    } else {
+                        -rec.normal
+                    };
+                    medium = rec
+                        .material
+                        .medium()
+                        .filter(|_| scattered.direction.dot(outward) < 0.0);
+                    // Materials don't know about motion; keep the path at
+                    // the camera ray's instant.
+                    previous_point = rec.point;
+                    ray = rec.spawn_scattered(&ray, &scattered);
+                    t_min = rec.ray_epsilon();
+                    attenuation.max_element()
+                }
+                None => return path,
+            };
+
+            // Russian roulette: paths survive with a probability chosen by
+            // `settings.russian_roulette` and are reweighted so the estimate
+            // stays unbiased.
+            let max_component = throughput.max_element();
+            let mut survival = 1.0;
+            if depth + 1 >= settings.russian_roulette_start {
+                survival = match settings.russian_roulette {
+                    RouletteMode::Throughput => max_component,
+                    RouletteMode::Albedo => bounce_albedo,
+                    RouletteMode::Fixed { probability } => probability,
+                }
+                .clamp(0.05, 1.0);
+            }
+            if max_component < settings.min_contribution {
+                survival = survival.min(max_component / settings.min_contribution);
+            }
+            if survival < 1.0 {
+                if sampler.next_1d() >= survival {
+                    return path;
+                }
+                throughput /= survival;
+            }
+            depth += 1;
+        }
+        path
+    }
+
+    // Next-event estimation towards the environment, MIS-weighted against
+    // the BSDF sample that may hit the same direction. Returns the weighted
+    // radiance and the light-sample weight.
+    fn sample_environment(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(DVec3, f64)> {
+        let (direction, light_pdf) = self.sample_environment_direction(rec.point, sampler)?;
+        let f = rec.material.eval(ray, rec, direction)?;
+        if f == DVec3::ZERO || light_pdf <= 0.0 {
+            return None;
+        }
+        let shadow = rec.spawn(ray, direction);
+        let transmittance =
+            self.transmittance(&shadow, Interval::after(rec.ray_epsilon()), sampler);
+        if transmittance == DVec3::ZERO {
+            return None;
+        }
+        let weight = power_heuristic(light_pdf, rec.material.pdf(ray, rec, direction));
+        Some((
+            weight * f * transmittance * self.environment.value(direction) / light_pdf,
+            weight,
+        ))
+    }
+
+    // A direction from `point` towards the environment and its solid-angle
+    // density: through a portal when any faces `point`, otherwise by the
+    // environment's own sampling.
+    fn sample_environment_direction(
+        &self,
+        point: DVec3,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(DVec3, f64)> {
+        let Some(portals) = self.portals(point) else {
+            return self.environment.sample(sampler);
+        };
+        let u = sampler.next_1d();
+        portals.sample(point, u, sampler.next_2d())
+    }
+
+    // Density with which `sample_environment_direction` picks `direction`
+    // from `point`.
+    fn environment_pdf(&self, point: DVec3, direction: DVec3) -> f64 {
+        match self.portals(point) {
+            Some(portals) => portals.pdf(point, direction),
+            None => self.environment.pdf(direction),
+        }
+    }
+
+    // The portals environment light reaches `point` through, if any face
+    // it.
+    fn portals(&self, point: DVec3) -> Option<&PortalDistribution> {
+        if self.lights.portals().is_empty() {
+            return None;
+        }
+        let portals = self
+            .portals
+            .get_or_init(|| PortalDistribution::new(self.lights.portals(), &*self.environment));
+        portals.faces(point).then_some(portals)
+    }
+
+    // MIS weight of emission reached by BSDF sampling from a vertex that
+    // also sampled the lights. Resampled light samples have no closed-form
+    // density, so with ReSTIR the light technique alone covers emitters.
+    fn emission_weight(
+        &self,
+        settings: &RenderSettings,
+        index: u32,
+        bsdf_pdf: f64,
+        from: DVec3,
+        ray: &Ray,
+        rec: &HitRecord,
+    ) -> f64 {
+        if let DirectLighting::Restir { .. } = settings.direct_lighting {
+            return 0.0;
+        }
+        let distance_squared = from.distance_squared(rec.point);
+        let cosine = rec.normal.dot(ray.direction.normalize()).abs();
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        let light_pdf = self.lights.pdf(index, from) * distance_squared / cosine;
+        power_heuristic(bsdf_pdf, light_pdf)
+    }
+
+    // Next-event estimation towards `self.lights`. Returns the radiance
+    // arriving through the chosen sample and, for ReSTIR, the reservoir
+    // it was drawn from so later pixels can reuse it.
+    fn sample_lights(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        neighbours: &[PrimaryReservoir],
+    ) -> (DVec3, Option<Reservoir>) {
+        // Mirrors and smooth glass can't use light samples.
+        if rec.material.eval(ray, rec, rec.normal).is_none() {
+            return (DVec3::ZERO, None);
+        }
+        let candidates = match settings.direct_lighting {
+            DirectLighting::Power => 1,
+            DirectLighting::Restir { candidates, .. } => candidates.max(1),
+        };
+        let mut reservoir = Reservoir::default();
+        for _ in 0..candidates {
+            let u = sampler.next_1d();
+            let uv = sampler.next_2d();
+            let pick = sampler.next_1d();
+            if let Some(sample) = self.lights.sample(rec.point, u, uv) {
+                let target = luminance(self.unshadowed_light(ray, rec, &sample));
+                reservoir.update(sample, target / sample.pdf, target, pick);
+            }
+        }
+        reservoir.count = candidates;
+
+        // Cap each neighbour's history so one reservoir can't dominate.
+        let depth = rec.t * ray.direction.length();
+        let max_count = 20 * candidates;
+        for neighbour in neighbours {
+            let similar = neighbour.normal.dot(rec.normal) > 0.9
+                && (neighbour.depth - depth).abs() < 0.1 * depth;
+            let Some(sample) = neighbour.reservoir.sample.filter(|_| similar) else {
+                continue;
+            };
+            let count = neighbour.reservoir.count.min(max_count);
+            let target = luminance(self.unshadowed_light(ray, rec, &sample));
+            let weight = target * neighbour.reservoir.contribution_weight() * count as f64;
+            reservoir.update(sample, weight, target, sampler.next_1d());
+            reservoir.count += count;
+        }
+
+        let reuse = matches!(settings.direct_lighting, DirectLighting::Restir { .. });
+        let kept = reuse.then_some(reservoir);
+        let Some(sample) = reservoir.sample else {
+            return (DVec3::ZERO, kept);
+        };
+        let transmittance = self.visibility(ray, rec, sample.point, sampler);
+        if transmittance == DVec3::ZERO {
+            return (DVec3::ZERO, kept);
+        }
+        let mut radiance = transmittance
+            * self.unshadowed_light(ray, rec, &sample)
+            * reservoir.contribution_weight();
+        if !reuse {
+            let to_light = sample.point - rec.point;
+            let distance = to_light.length();
+            let cosine = sample.normal.dot(to_light / distance).abs();
+            let light_pdf = sample.pdf * distance * distance / cosine.max(1e-12);
+            radiance *= power_heuristic(light_pdf, rec.material.pdf(ray, rec, to_light));
+        }
+        (radiance, kept)
+    }
+
+    // Direct light from every analytic light, each with its own shadow ray,
+    // and what it would be without shadows.
+    fn sample_analytic(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> (DVec3, DVec3) {
+        let (mut lit, mut unshadowed) = (DVec3::ZERO, DVec3::ZERO);
+        for light in self.lights.analytic() {
+            let Some(illumination) = light.illuminate(rec.point, sampler) else {
+                continue;
+            };
+            let Some(f) = rec.material.eval(ray, rec, illumination.direction) else {
+                // Mirrors and smooth glass can't use light samples.
+                return (DVec3::ZERO, DVec3::ZERO);
+            };
+            if f == DVec3::ZERO {
+                continue;
+            }
+            let shadow = rec.spawn(ray, illumination.direction);
+            let max_distance = illumination.distance * (1.0 - 1e-4);
+            let transmittance = self.transmittance(
+                &shadow,
+                Interval::new(rec.ray_epsilon(), max_distance),
+                sampler,
+            );
+            lit += f * transmittance * illumination.radiance;
+            unshadowed += f * illumination.radiance;
+        }
+        (lit, unshadowed)
+    }
+
+    // Fraction of light that gets along the segment between `from`, found
+    // by `ray`, and a point; see `transmittance`.
+    fn visibility(
+        &self,
+        ray: &Ray,
+        from: &HitRecord,
+        to: DVec3,
+        sampler: &mut dyn Sampler,
+    ) -> DVec3 {
+        let mut shadow = from.spawn(ray, to - from.point);
+        let offset = to - shadow.origin;
+        let distance = offset.length();
+        shadow.direction = offset / distance;
+        let interval = Interval::new(from.ray_epsilon(), distance * (1.0 - 1e-4));
+        self.transmittance(&shadow, interval, sampler)
+    }
+
+    // Fraction of light that travels along `shadow` over `interval`: none
+    // if a surface blocks it, and what the media behind index-matched
+    // surfaces let through otherwise. Random numbers are only drawn inside
+    // heterogeneous media.
+    fn transmittance(&self, shadow: &Ray, interval: Interval, sampler: &mut dyn Sampler) -> DVec3 {
+        stats::count(|stats| stats.shadow_rays += 1);
+        let mut shadow = shadow.with_kind(RayKind::Shadow);
+        // An any-hit query settles whether anything blocks the ray without
+        // building a single record; only media are left to dim it.
+        if self.world.hit_any(&shadow, interval) {
+            return DVec3::ZERO;
+        }
+        if !self.lights.has_media() {
+            return DVec3::ONE;
+        }
+        let mut interval = interval;
+        let mut transmittance = DVec3::ONE;
+        while let Some(rec) = hit_surface(&*self.world, &shadow, interval) {
+            if !rec.material.is_index_matched() {
+                return DVec3::ZERO;
+            }
+            // Leaving through the surface, so the segment up to it was
+            // inside the medium.
+            if let Some(medium) = rec.material.medium().filter(|_| !rec.front_face) {
+                let length = shadow.direction.length();
+                transmittance *= medium.transmittance(
+                    shadow.at(interval.min),
+                    shadow.direction / length,
+                    (rec.t - interval.min) * length,
+                    sampler,
+                );
+                if transmittance == DVec3::ZERO {
+                    return transmittance;
+                }
+            }
+            // Carry on from the far side of the surface.
+            interval = Interval::new(rec.ray_epsilon(), interval.max - rec.t);
+            shadow = rec.spawn(&shadow, shadow.direction);
+        }
+        transmittance
+    }
+
+    // The `Integrator::AmbientOcclusion` estimate. Misses see open sky.
+    fn trace_occlusion(
+        &self,
+        ray: &Ray,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        distance: Option<f64>,
+    ) -> PathSample {
+        let mut path = PathSample::default();
+        stats::count(|stats| stats.rays += 1);
+        let Some(rec) = hit_surface(&*self.world, ray, Interval::after(DEFAULT_EPSILON)) else {
+            if !settings.transparent {
+                path.bsdf = DVec3::ONE;
+            }
+            return path;
+        };
+        path.alpha = 1.0;
+        let direction = (rec.normal + random_unit_vector(sampler)).normalize_or_zero();
+        if direction == DVec3::ZERO {
+            return path;
+        }
+        stats::count(|stats| stats.rays += 1);
+        let reach = distance.unwrap_or(f64::INFINITY);
+        let probe = rec.spawn(ray, direction);
+        let interval = Interval::new(rec.ray_epsilon(), reach);
+        if !self.world.hit_any(&probe, interval) {
+            path.bsdf = DVec3::ONE;
+        }
+        path
+    }
+
+    // The debug views of `Integrator::Normals`, `Uv`, `Wireframe` and
+    // `BvhHeat`, all of the first hit alone. Misses are black.
+    fn trace_first_hit(&self, ray: &Ray, settings: &RenderSettings) -> PathSample {
+        let mut path = PathSample::default();
+        stats::count(|stats| stats.rays += 1);
+        let nodes_before = stats::current().node_tests;
+        let hit = hit_surface(&*self.world, ray, Interval::after(DEFAULT_EPSILON));
+        let nodes = stats::current().node_tests - nodes_before;
+        if let Integrator::BvhHeat { max_nodes } = settings.integrator {
+            let heat = 3.0 * nodes as f64 / max_nodes.max(1) as f64;
+            path.bsdf = DVec3::new(heat, heat - 1.0, heat - 2.0).clamp(DVec3::ZERO, DVec3::ONE);
+        }
+        let Some(rec) = hit else {
+            return path;
+        };
+        path.alpha = 1.0;
+        match settings.integrator {
+            Integrator::Normals => path.bsdf = 0.5 * (rec.normal + DVec3::ONE),
+            Integrator::Uv => {
+                path.bsdf = DVec3::new(rec.u.rem_euclid(1.0), rec.v.rem_euclid(1.0), 0.0)
+            }
+            Integrator::Wireframe { width } => {
+                let on_edge = rec.barycentric.is_some_and(|b| b.min_element() < width);
+                if !on_edge {
+                    let facing = rec.normal.dot(ray.direction.normalize()).abs();
+                    path.bsdf = DVec3::splat(0.2 + 0.6 * facing);
+                }
+            }
+            _ => {}
+        }
+        path
+    }
+
+    // The `Integrator::Preview` estimate. Without MIS, emission and sky
+    // that direct lighting already sampled are skipped when the bounce
+    // finds them.
+    fn trace_preview(
+        &self,
+        ray: &Ray,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        ambient: Option<DVec3>,
+    ) -> PathSample {
+        let mut path = PathSample::default();
+        let mut ray = *ray;
+        let mut t_min = DEFAULT_EPSILON;
+        let mut throughput = DVec3::ONE;
+        let mut bounced = false;
+
+        for depth in 0..settings.max_depth {
+            stats::count(|stats| stats.rays += 1);
+            let Some(rec) = hit_surface(&*self.world, &ray, Interval::after(t_min)) else {
+                if depth == 0 && settings.transparent {
+                    return path;
+                }
+                if !bounced || self.environment_pdf(ray.origin, ray.direction) <= 0.0 {
+                    path.bsdf += throughput * self.environment.value(ray.direction);
+                }
+                return path;
+            };
+            if depth == 0 {
+                if rec.material.is_shadow_catcher() {
+                    return self.catch_shadow(&ray, &rec, settings, sampler);
+                }
+                path.alpha = 1.0;
+            }
+            if !bounced || rec.light.is_none() {
+                path.bsdf += throughput * rec.material.emitted(rec.u, rec.v, rec.point);
+            }
+
+            let specular = rec.material.eval(&ray, &rec, rec.normal).is_none();
+            if !specular {
+                path.light += throughput * self.direct_light(&ray, &rec, sampler).0;
+                if bounced {
+                    return path;
+                }
+                if let Some(ambient) = ambient {
+                    path.bsdf += throughput * ambient * rec.material.albedo(&rec);
+                    return path;
+                }
+                bounced = true;
+            }
+            let Some((scattered, attenuation)) = rec.material.scatter(&ray, &rec, sampler) else {
+                return path;
+            };
+            throughput *= attenuation;
+            ray = rec.spawn_scattered(&ray, &scattered);
+            t_min = rec.ray_epsilon();
+        }
+        path
+    }
+
+    // One unweighted environment sample, one power-sampled light and every
+    // analytic light, with shadows and without.
+    fn direct_light(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> (DVec3, DVec3) {
+        let (mut lit, mut unshadowed) = (DVec3::ZERO, DVec3::ZERO);
+        if let Some((direction, pdf)) = self.sample_environment_direction(rec.point, sampler) {
+            let f = rec
+                .material
+                .eval(ray, rec, direction)
+                .unwrap_or(DVec3::ZERO);
+            if pdf > 0.0 && f != DVec3::ZERO {
+                let shadow = rec.spawn(ray, direction);
+                let transmittance =
+                    self.transmittance(&shadow, Interval::after(rec.ray_epsilon()), sampler);
+                let radiance = f * self.environment.value(direction) / pdf;
+                lit += transmittance * radiance;
+                unshadowed += radiance;
+            }
+        }
+        if !self.lights.is_empty() {
+            let u = sampler.next_1d();
+            let uv = sampler.next_2d();
+            if let Some(sample) = self.lights.sample(rec.point, u, uv) {
+                let transmittance = self.visibility(ray, rec, sample.point, sampler);
+                let radiance = self.unshadowed_light(ray, rec, &sample) / sample.pdf;
+                lit += transmittance * radiance;
+                unshadowed += radiance;
+            }
+        }
+        let (analytic_lit, analytic_unshadowed) = self.sample_analytic(ray, rec, sampler);
+        (lit + analytic_lit, unshadowed + analytic_unshadowed)
+    }
+
+    // A camera ray's hit on a shadow catcher: the fraction of its direct
+    // light that is blocked, as alpha over black. An opaque film shows the
+    // environment behind the catcher, darkened by as much.
+    fn catch_shadow(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+    ) -> PathSample {
+        let (lit, unshadowed) = self.direct_light(ray, rec, sampler);
+        let shadow = if luminance(unshadowed) > 0.0 {
+            (1.0 - luminance(lit) / luminance(unshadowed)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let mut path = PathSample {
+            alpha: shadow,
+            ..PathSample::default()
+        };
+        if !settings.transparent {
+            path.bsdf = (1.0 - shadow) * self.environment.value(ray.direction);
+        }
+        path
+    }
+
+    // BSDF-weighted radiance from `sample` per unit area of the light,
+    // ignoring occlusion: the resampling target for ReSTIR.
+    fn unshadowed_light(&self, ray: &Ray, rec: &HitRecord, sample: &LightSample) -> DVec3 {
+        let to_light = sample.point - rec.point;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= 0.0 {
+            return DVec3::ZERO;
+        }
+        let direction = to_light / distance_squared.sqrt();
+        let Some(f) = rec.material.eval(ray, rec, direction) else {
+            return DVec3::ZERO;
+        };
+        let cosine = sample.normal.dot(direction).abs();
+        f * sample.radiance * cosine / distance_squared
+    }
+}
+
+// What is known of the first vertex of a camera path before it is traced:
+// its hit, when that was found with the rest of the ray's packet
+// (`Some(None)` for a miss), the first-hit reservoirs of neighbouring
+// pixels for spatial reuse, and the cone of the pixel as `Camera::
+// pixel_cone` gives it, which is zero, for unfiltered textures, where
+// there is no camera.
+#[derive(Clone, Copy, Default)]
+struct FirstVertex<'a> {
+    hit: Option<Option<HitRecord<'a>>>,
+    neighbours: &'a [PrimaryReservoir],
+    cone: (f64, f64),
+}
+
+// Width in UV units of a cone `width` wide where it meets the surface of
+// `rec`: the geometric mean of the axes of the ellipse it covers there,
+// tilted by the angle it meets it at. Zero, for unfiltered lookups, where
+// the surface has no UV parameterisation.
+fn uv_footprint(ray: &Ray, rec: &HitRecord, width: f64) -> f64 {
+    let area = rec.dpdu.cross(rec.dpdv).length();
+    if width == 0.0 || area == 0.0 {
+        return 0.0;
+    }
+    let cosine = ray.direction.normalize().dot(rec.normal).abs().max(1e-2);
+    width / (area * cosine).sqrt()
+}
+
+// A first-hit reservoir kept for spatial reuse by later pixels, with the
+// geometry used to reject neighbours across edges.
+#[derive(Clone, Copy)]
+struct PrimaryReservoir {
+    reservoir: Reservoir,
+    normal: DVec3,
+    depth: f64,
+}
+
+// A diffuse hit whose outgoing radiance is added to the irradiance cache
+// once the path has finished.
+struct CacheVertex {
+    key: u64,
+    throughput: DVec3,
+    radiance_before: DVec3,
+}
+
+// Contributions of one camera path split by the technique that produced
+// them, plus the MIS weights chosen at the first surface hit.
+#[derive(Default)]
+struct PathSample {
+    bsdf: DVec3,
+    light: DVec3,
+    bsdf_weight: f64,
+    light_weight: f64,
+    reservoir: Option<PrimaryReservoir>,
+    // Diffuse hits to add to the irradiance cache.
+    vertices: Vec<CacheVertex>,
+    // 1 if the camera ray hit an object, the shadow there if it hit a
+    // shadow catcher, and 0 if it missed.
+    alpha: f64,
+}
+
+impl PathSample {
+    fn radiance(&self) -> DVec3 {
+        self.bsdf + self.light
+    }
+
+    fn debug_color(&self, view: IntegratorDebug) -> DVec3 {
+        match view {
+            IntegratorDebug::Off => self.radiance(),
+            IntegratorDebug::Technique => {
+                DVec3::new(luminance(self.bsdf), luminance(self.light), 0.0)
+            }
+            IntegratorDebug::MisWeights => DVec3::new(self.bsdf_weight, self.light_weight, 0.0),
+        }
+    }
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+pub(crate) fn luminance(c: DVec3) -> f64 {
+    c.dot(DVec3::new(0.2126, 0.7152, 0.0722))
+}
+
+// Camera ray for sample `index` of `pixel`, spread over its sample-map
+// block, with the weight the lens gives its light and where it lands on
+// the film relative to the pixel's corner.
+fn camera_ray(
+    camera: &Camera,
+    settings: &RenderSettings,
+    sampler: &mut dyn Sampler,
+    pixel: &PacketPixel,
+    index: u32,
+) -> (Ray, DVec3, (f64, f64)) {
+    let width = (settings.width.max(2) - 1) as f64;
+    let height = (settings.height.max(2) - 1) as f64;
+    let (block_width, block_height) = pixel.block_size;
+    sampler.start_pixel(pixel.x, pixel.y, index);
+    let (jx, jy) = sampler.next_2d();
+    let s = (pixel.x as f64 + jx * block_width) / width;
+    let t = ((settings.height - pixel.y) as f64 - (1.0 - jy) * block_height) / height;
+    let (mut ray, weight) = camera.get_ray(s, t, sampler);
+    if settings.spectral {
+        let wavelength = sample_wavelength(sampler.next_1d());
+        ray = ray.with_wavelength(Some(wavelength));
+    }
+    (ray, weight, (jx * block_width, (1.0 - jy) * block_height))
+}
+
+// Merges rendered tiles into the image and its passes. Films are summed
+// in the order of `rendered`, which should be the order of `split_tiles`.
+pub(crate) fn assemble<R: Borrow<RenderedTile>>(
+    settings: &RenderSettings,
+    rendered: Vec<(Tile, R)>,
+) -> RenderPasses {
+    // Wide filters reach into neighbouring tiles; their margins are
+    // summed in tile order, never in completion order.
+    let mut film =
+        (!settings.filter.is_box()).then(|| Film::new(0, 0, settings.width, settings.height));
+    if let Some(film) = &mut film {
+        for (_, rendered) in &rendered {
+            if let Some(tile_film) = &rendered.borrow().film {
+                film.add(tile_film);
+            }
+        }
+    }
+
+    let aovs = settings.aovs;
+    let new_pass =
+        |enabled: bool| enabled.then(|| ImageBuffer::new(settings.width, settings.height));
+    let new_matte = |enabled: bool| {
+        enabled.then(|| {
+            [(); MATTE_LAYERS].map(|_| {
+                let mut layer = ImageBuffer::new(settings.width, settings.height);
+                layer.alpha = Some(vec![0.0; layer.pixels.len()]);
+                layer
+            })
+        })
+    };
+    let mut passes = RenderPasses {
+        beauty: ImageBuffer::new(settings.width, settings.height),
+        albedo: new_pass(aovs.albedo),
+        normal: new_pass(aovs.normal),
+        depth: new_pass(aovs.depth),
+        object_id: new_pass(aovs.object_id),
+        material_id: new_pass(aovs.material_id),
+        object_matte: new_matte(aovs.object_matte),
+        material_matte: new_matte(aovs.material_matte),
+        stats: RenderStats::default(),
+    };
+    if settings.transparent {
+        passes.beauty.alpha = Some(vec![0.0; passes.beauty.pixels.len()]);
+    }
+    for (tile, rendered) in &rendered {
+        let rendered = rendered.borrow();
+        passes.stats += rendered.stats;
+        let mut samples = rendered.pixels.iter();
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let &(color, aov) = samples.next().unwrap();
+                let color = film
+                    .as_ref()
+                    .and_then(|film| film.resolve(x, y))
+                    .unwrap_or(color);
+                passes.beauty.set(x, y, color);
+                if let Some(alpha) = &mut passes.beauty.alpha {
+                    alpha[(y * settings.width + x) as usize] = aov.alpha;
+                }
+                let mut write = |pass: &mut Option<ImageBuffer>, value: DVec3| {
+                    if let Some(image) = pass {
+                        image.set(x, y, value);
+                    }
+                };
+                write(&mut passes.albedo, aov.albedo);
+                write(&mut passes.normal, aov.normal);
+                write(&mut passes.depth, DVec3::splat(aov.depth));
+                write(&mut passes.object_id, DVec3::splat(aov.object_id as f64));
+                write(
+                    &mut passes.material_id,
+                    DVec3::splat(aov.material_id as f64),
+                );
+                let index = (y * settings.width + x) as usize;
+                for (matte, coverage) in [
+                    (&mut passes.object_matte, &aov.object_matte),
+                    (&mut passes.material_matte, &aov.material_matte),
+                ] {
+                    for (layer, image) in matte.iter_mut().flatten().enumerate() {
+                        let (ids, amounts) = (coverage.ids, coverage.coverage);
+                        let (a, b) = (2 * layer, 2 * layer + 1);
+                        image.pixels[index] = DVec3::new(ids[a] as f64, amounts[a], ids[b] as f64);
+                        if let Some(alpha) = &mut image.alpha {
+                            alpha[index] = amounts[b];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    passes
+}
+
+// Effects on the finished beauty image, to be applied after denoising and
+// temporal accumulation and just before tone mapping: bloom spreads
+// highlights over their neighbours, which a denoiser would take for noise
+// to smooth and a temporal accumulator would drag across frames.
+pub fn finish(image: &mut ImageBuffer, settings: &RenderSettings) {
+    if let Some(bloom) = &settings.bloom {
+        bloom::apply(image, bloom);
+    }
+}
+
+pub(crate) fn split_tiles(settings: &RenderSettings) -> Vec<Tile> {
+    let size = settings.tile_size.max(1);
+    let (left, top, right, bottom) = settings.render_window();
+    let mut tiles = Vec::new();
+    for y0 in (0..settings.height).step_by(size as usize) {
+        for x0 in (0..settings.width).step_by(size as usize) {
+            let tile = Tile {
+                x0: x0.max(left),
+                y0: y0.max(top),
+                x1: (x0 + size).min(right),
+                y1: (y0 + size).min(bottom),
+            };
+            if tile.x0 < tile.x1 && tile.y0 < tile.y1 {
+                tiles.push(tile);
+            }
+        }
+    }
+    tiles
+}
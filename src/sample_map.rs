@@ -0,0 +1,111 @@
+use glam::DVec2;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// A rectangle of the image, with corners as fractions of its width and
+// height from the top-left corner, that gets `samples` times the usual
+// samples per pixel and `resolution` times the pixels along each axis. At
+// a resolution of 0.25, say, blocks of 4x4 pixels are rendered as one and
+// share its colour. Blocks are laid out on a grid from the image's corner,
+// cut at the edges of render tiles.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SampleRegion {
+    pub min: DVec2,
+    pub max: DVec2,
+    #[serde(default = "one")]
+    pub samples: f64,
+    #[serde(default = "one")]
+    pub resolution: f64,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+impl SampleRegion {
+    fn contains(&self, p: DVec2) -> bool {
+        p.cmpge(self.min).all() && p.cmplt(self.max).all()
+    }
+}
+
+// Where the renderer spends its samples, for cinematic shots whose blurred
+// backgrounds and letterbox bars don't need as many as the subject. Each
+// pixel's share of the samples is the grey level of the mask, stretched
+// over the image, times the `samples` of every region it falls in; pixels
+// with none stay black. Its resolution comes from the last region it falls
+// in. Everything is relative to the image, so one map serves any render
+// size.
+pub struct SampleMap {
+    mask: Option<Mask>,
+    regions: Vec<SampleRegion>,
+}
+
+struct Mask {
+    width: u32,
+    height: u32,
+    levels: Vec<f64>,
+}
+
+impl SampleMap {
+    pub fn new(regions: Vec<SampleRegion>) -> Self {
+        Self {
+            mask: None,
+            regions,
+        }
+    }
+
+    // `levels` holds `width` x `height` fractions in [0, 1], by rows from
+    // the top.
+    pub fn with_mask(mut self, width: u32, height: u32, levels: Vec<f64>) -> Self {
+        assert_eq!(levels.len(), (width * height) as usize);
+        self.mask = Some(Mask {
+            width,
+            height,
+            levels,
+        });
+        self
+    }
+
+    // Reads the mask from a greyscale image, white for the full budget.
+    pub fn load_mask(self, path: &str) -> Result<Self, Box<dyn Error>> {
+        let image = image::open(path)?.to_luma32f();
+        let (width, height) = image.dimensions();
+        let levels = image
+            .pixels()
+            .map(|p| p[0].clamp(0.0, 1.0) as f64)
+            .collect();
+        Ok(self.with_mask(width, height, levels))
+    }
+
+    // Fraction of the samples per pixel that pixel (`x`, `y`) of a `width`
+    // x `height` image gets.
+    pub fn density(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        let p = centre(x, y, width, height);
+        let mask = self.mask.as_ref().map_or(1.0, |mask| {
+            let mx = ((p.x * mask.width as f64) as u32).min(mask.width - 1);
+            let my = ((p.y * mask.height as f64) as u32).min(mask.height - 1);
+            mask.levels[(my * mask.width + mx) as usize]
+        });
+        self.regions
+            .iter()
+            .filter(|r| r.contains(p))
+            .fold(mask, |density, r| density * r.samples.max(0.0))
+    }
+
+    // Side of the blocks of pixels rendered as one around pixel (`x`, `y`).
+    pub fn block_size(&self, x: u32, y: u32, width: u32, height: u32) -> u32 {
+        let p = centre(x, y, width, height);
+        self.regions
+            .iter()
+            .rev()
+            .find(|r| r.contains(p))
+            .map_or(1, |r| (1.0 / r.resolution.clamp(1e-3, 1.0)).round() as u32)
+    }
+}
+
+fn centre(x: u32, y: u32, width: u32, height: u32) -> DVec2 {
+    DVec2::new(
+        (x as f64 + 0.5) / width.max(1) as f64,
+        (y as f64 + 0.5) / height.max(1) as f64,
+    )
+}
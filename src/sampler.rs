@@ -0,0 +1,190 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+pub trait Sampler: Send {
+    // Starts sample `index` of pixel (x, y); dimensions restart from zero.
+    // The values that follow depend only on the sampler's seed, the pixel
+    // and the index, never on the samples drawn before, so renders come out
+    // the same however their pixels are split between tiles, threads and
+    // machines.
+    fn start_pixel(&mut self, x: u32, y: u32, index: u32);
+    fn next_1d(&mut self) -> f64;
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub enum SamplerKind {
+    #[serde(rename = "independent")]
+    Independent,
+    #[serde(rename = "stratified")]
+    Stratified,
+    #[default]
+    #[serde(rename = "halton")]
+    Halton,
+}
+
+impl SamplerKind {
+    pub fn create(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+        }
+    }
+}
+
+pub struct IndependentSampler {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = SmallRng::seed_from_u64(sample_seed(self.seed, x, y, index));
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+}
+
+// Jitters the first 2D sample of every pixel (the image-plane position) over
+// a sqrt(spp) x sqrt(spp) grid; the remaining dimensions are independent.
+pub struct StratifiedSampler {
+    seed: u64,
+    strata: u32,
+    index: u32,
+    dimension: u32,
+    rng: SmallRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            seed,
+            strata: ((samples_per_pixel as f64).sqrt() as u32).max(1),
+            index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, x: u32, y: u32, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+        self.rng = SmallRng::seed_from_u64(sample_seed(self.seed, x, y, index));
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        self.dimension += 1;
+        self.rng.gen()
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+        let cells = self.strata * self.strata;
+        if dimension != 0 || self.index >= cells {
+            return (self.rng.gen(), self.rng.gen());
+        }
+        let (sx, sy) = (self.index % self.strata, self.index / self.strata);
+        (
+            (sx as f64 + self.rng.gen::<f64>()) / self.strata as f64,
+            (sy as f64 + self.rng.gen::<f64>()) / self.strata as f64,
+        )
+    }
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// Halton sequence indexed by sample number, decorrelated between pixels with
+// a per-pixel Cranley-Patterson rotation. Dimensions beyond the prime table
+// fall back to independent random numbers.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel_hash: u64,
+    index: u32,
+    dimension: usize,
+    rng: SmallRng,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_hash: 0,
+            index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel_hash = mix_hash(((x as u64) << 32 | y as u64) ^ self.seed);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = SmallRng::seed_from_u64(sample_seed(self.seed, x, y, index));
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        if dimension >= PRIMES.len() {
+            return self.rng.gen();
+        }
+        let value = radical_inverse(PRIMES[dimension], self.index as u64 + 1);
+        let rotation = to_unit(mix_hash(self.pixel_hash ^ dimension as u64));
+        (value + rotation).fract()
+    }
+}
+
+// Seed of the random numbers for sample `index` of pixel (x, y).
+fn sample_seed(seed: u64, x: u32, y: u32, index: u32) -> u64 {
+    mix_hash(mix_hash(((x as u64) << 32 | y as u64) ^ seed) ^ index as u64)
+}
+
+pub fn radical_inverse(base: u32, mut index: u64) -> f64 {
+    let base = base as u64;
+    let inv_base = 1.0 / base as f64;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * factor;
+        index /= base;
+        factor *= inv_base;
+    }
+    result
+}
+
+pub(crate) fn mix_hash(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^= h >> 33;
+    h
+}
+
+pub(crate) fn to_unit(h: u64) -> f64 {
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,102 @@
+use crate::hittable::{Hittable, HittableList};
+use crate::objects::transform::Transformed;
+use crate::objects::triangle::Triangle;
+use crate::texture::Texture;
+use glam::{DAffine3, DMat3, DQuat, DVec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct ScatterSettings {
+    pub count: usize,
+    pub seed: u64,
+    pub align_to_normal: bool,
+    pub random_rotation: bool,
+    pub scale_range: (f64, f64),
+    pub density: Option<Arc<dyn Texture>>,
+}
+
+impl Default for ScatterSettings {
+    fn default() -> Self {
+        Self {
+            count: 100,
+            seed: 0,
+            align_to_normal: true,
+            random_rotation: true,
+            scale_range: (1.0, 1.0),
+            density: None,
+        }
+    }
+}
+
+// Places `settings.count` instances of `prototype` on the surface of
+// `target`, choosing triangles proportionally to their area (optionally
+// weighted by the luminance of a density texture at the triangle's centroid).
+// The prototype is shared between all instances; each one only stores its
+// own transform.
+pub fn scatter_on_surface(
+    target: &[Triangle],
+    prototype: Arc<dyn Hittable>,
+    settings: &ScatterSettings,
+) -> HittableList {
+    let mut cdf = Vec::with_capacity(target.len());
+    let mut total = 0.0;
+    for triangle in target {
+        let [p0, p1, p2] = triangle.vertices();
+        let mut weight = 0.5 * (p1 - p0).cross(p2 - p0).length();
+        if let Some(density) = &settings.density {
+            let uv = (triangle.uvs[0] + triangle.uvs[1] + triangle.uvs[2]) / 3.0;
+            let c = density.value(uv.x, uv.y, (p0 + p1 + p2) / 3.0);
+            weight *= (0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z).max(0.0);
+        }
+        total += weight;
+        cdf.push(total);
+    }
+
+    let mut instances = HittableList::new();
+    if total <= 0.0 {
+        return instances;
+    }
+
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    for _ in 0..settings.count {
+        let target_weight = rng.gen::<f64>() * total;
+        let index = cdf
+            .partition_point(|&c| c < target_weight)
+            .min(target.len() - 1);
+        let triangle = &target[index];
+        let [p0, p1, p2] = triangle.vertices();
+
+        let (mut b1, mut b2) = (rng.gen::<f64>(), rng.gen::<f64>());
+        if b1 + b2 > 1.0 {
+            b1 = 1.0 - b1;
+            b2 = 1.0 - b2;
+        }
+        let b0 = 1.0 - b1 - b2;
+        let position = b0 * p0 + b1 * p1 + b2 * p2;
+        let normal = match triangle.normals() {
+            Some([n0, n1, n2]) => (b0 * n0 + b1 * n1 + b2 * n2).normalize(),
+            None => (p1 - p0).cross(p2 - p0).normalize(),
+        };
+
+        let mut rotation = if settings.align_to_normal {
+            DMat3::from_quat(DQuat::from_rotation_arc(DVec3::Y, normal))
+        } else {
+            DMat3::IDENTITY
+        };
+        if settings.random_rotation {
+            rotation *= DMat3::from_rotation_y(rng.gen::<f64>() * 2.0 * PI);
+        }
+        let (min_scale, max_scale) = settings.scale_range;
+        let scale = if max_scale > min_scale {
+            rng.gen_range(min_scale..max_scale)
+        } else {
+            min_scale
+        };
+
+        let transform = DAffine3::from_mat3_translation(rotation * scale, position);
+        instances.push(Arc::new(Transformed::new(prototype.clone(), transform)));
+    }
+    instances
+}
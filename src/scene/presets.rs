@@ -0,0 +1,162 @@
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::hittable::{Hittable, HittableList};
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::objects::sphere::Sphere;
+use crate::reference_scenes;
+use crate::texture::{CheckerTexture, SolidColor};
+use glam::DVec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+// The classic test scenes, built in code rather than loaded, for examples
+// and benchmarks. Like the generators, they leave the environment to the
+// caller; a sky gradient or solid white shows them as they are usually
+// seen.
+
+pub struct RandomSpheresParams {
+    pub seed: u64,
+    // Small spheres around the three big ones.
+    pub spheres: u32,
+    pub aspect_ratio: f64,
+}
+
+impl Default for RandomSpheresParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            spheres: 484,
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+}
+
+pub struct CheckerParams {
+    // Size of the checks in world units.
+    pub scale: f64,
+    pub aspect_ratio: f64,
+}
+
+impl Default for CheckerParams {
+    fn default() -> Self {
+        Self {
+            scale: 0.32,
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+}
+
+// The cover of "Ray Tracing in One Weekend": glass, metal and diffuse
+// balls scattered on a grid around three big ones, one of each. The grid
+// is as square as `spheres` allows; spheres that would overlap a big one
+// are left out, so slightly fewer may appear.
+pub fn random_spheres(params: &RandomSpheresParams) -> (Camera, Arc<dyn Hittable>) {
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    let mut world = HittableList::new();
+    world.push(Arc::new(Sphere::new(
+        DVec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        lambertian(DVec3::splat(0.5)),
+    )));
+
+    let big = [
+        (
+            DVec3::new(0.0, 1.0, 0.0),
+            Arc::new(Dielectric::new(1.5)) as Arc<dyn Material>,
+        ),
+        (
+            DVec3::new(-4.0, 1.0, 0.0),
+            lambertian(DVec3::new(0.4, 0.2, 0.1)),
+        ),
+        (
+            DVec3::new(4.0, 1.0, 0.0),
+            metal(DVec3::new(0.7, 0.6, 0.5), 0.0),
+        ),
+    ];
+    let side = (params.spheres as f64).sqrt().ceil() as u32;
+    let mut small = HittableList::new();
+    for index in 0..params.spheres {
+        let (a, b) = ((index % side) as f64, (index / side) as f64);
+        let center = DVec3::new(
+            a - side as f64 / 2.0 + 0.9 * rng.gen::<f64>(),
+            0.2,
+            b - side as f64 / 2.0 + 0.9 * rng.gen::<f64>(),
+        );
+        let choice: f64 = rng.gen();
+        if big.iter().any(|(c, _)| (center - *c).length() < 1.2) {
+            continue;
+        }
+        let material = if choice < 0.8 {
+            let albedo = DVec3::from_array([(); 3].map(|_| rng.gen::<f64>() * rng.gen::<f64>()));
+            lambertian(albedo)
+        } else if choice < 0.95 {
+            let albedo = DVec3::from_array([(); 3].map(|_| rng.gen_range(0.5..1.0)));
+            metal(albedo, rng.gen_range(0.0..0.5))
+        } else {
+            Arc::new(Dielectric::new(1.5))
+        };
+        small.push(Arc::new(Sphere::new(center, 0.2, material)));
+    }
+    if !small.is_empty() {
+        world.push(Arc::new(BvhNode::new(small)));
+    }
+    for (center, material) in big {
+        world.push(Arc::new(Sphere::new(center, 1.0, material)));
+    }
+
+    let camera = Camera::new(
+        DVec3::new(13.0, 2.0, 3.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        20.0,
+        params.aspect_ratio,
+        0.1,
+        10.0,
+    );
+    (camera, Arc::new(BvhNode::new(world)))
+}
+
+// The Cornell box of `reference_scenes::cornell_box`, with its light in
+// the world; renderers given its `LightSet` as well converge faster.
+pub fn cornell_box() -> (Camera, Arc<dyn Hittable>) {
+    let scene = reference_scenes::cornell_box();
+    (scene.camera, scene.world)
+}
+
+// Two big checkered balls, one above the other, touching at the middle of
+// the view: the first test of solid textures in "Ray Tracing: The Next
+// Week".
+pub fn checkered_spheres(params: &CheckerParams) -> (Camera, Arc<dyn Hittable>) {
+    let checker: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(CheckerTexture::new(
+        params.scale,
+        Arc::new(SolidColor::new(DVec3::new(0.2, 0.3, 0.1))),
+        Arc::new(SolidColor::new(DVec3::splat(0.9))),
+    ))));
+    let mut world = HittableList::new();
+    for y in [-10.0, 10.0] {
+        world.push(Arc::new(Sphere::new(
+            DVec3::new(0.0, y, 0.0),
+            10.0,
+            checker.clone(),
+        )));
+    }
+    let camera = Camera::new(
+        DVec3::new(13.0, 2.0, 3.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        20.0,
+        params.aspect_ratio,
+        0.0,
+        10.0,
+    );
+    (camera, Arc::new(BvhNode::new(world)))
+}
+
+fn lambertian(albedo: DVec3) -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(albedo))))
+}
+
+fn metal(albedo: DVec3, fuzz: f64) -> Arc<dyn Material> {
+    Arc::new(Metal::new(Arc::new(SolidColor::new(albedo)), fuzz))
+}
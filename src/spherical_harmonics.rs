@@ -0,0 +1,108 @@
+use crate::environment::Environment;
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// Ends paths at diffuse hits `after_bounces` or more bounces from the
+// camera with the environment's light from its spherical harmonics
+// projection, ignoring occlusion, plus direct light from the scene's
+// emitters. Far fewer bounces for renders that can take soft, slightly
+// too bright ambient light: previews, and stylised looks.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShAmbientSettings {
+    pub after_bounces: u32,
+}
+
+impl Default for ShAmbientSettings {
+    fn default() -> Self {
+        Self { after_bounces: 1 }
+    }
+}
+
+// Polar steps of the grid of directions an environment is projected from;
+// there are twice as many azimuthal ones.
+const PROJECTION_STEPS: usize = 128;
+
+// Radiance of an environment projected onto the nine real spherical
+// harmonics of bands 0 to 2, which hold all but a few percent of the
+// irradiance it casts on diffuse surfaces (Ramamoorthi and Hanrahan 2001).
+#[derive(Clone, Copy, Debug)]
+pub struct ShEnvironment {
+    pub coefficients: [DVec3; 9],
+}
+
+impl ShEnvironment {
+    // Integrates `environment` over a grid of directions. Features much
+    // smaller than a grid cell, such as a sun in a photograph, may be
+    // missed or over-weighted.
+    pub fn project(environment: &dyn Environment) -> Self {
+        let (rows, columns) = (PROJECTION_STEPS, 2 * PROJECTION_STEPS);
+        let (d_theta, d_phi) = (PI / rows as f64, 2.0 * PI / columns as f64);
+        let mut coefficients = [DVec3::ZERO; 9];
+        for row in 0..rows {
+            let theta = (row as f64 + 0.5) * d_theta;
+            let solid_angle = theta.sin() * d_theta * d_phi;
+            for column in 0..columns {
+                let phi = (column as f64 + 0.5) * d_phi;
+                let direction = DVec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let radiance = environment.value(direction) * solid_angle;
+                for (c, y) in coefficients.iter_mut().zip(basis(direction)) {
+                    *c += radiance * y;
+                }
+            }
+        }
+        Self { coefficients }
+    }
+
+    // Radiance arriving from the unit `direction`, smoothed to the bands
+    // kept.
+    pub fn radiance(&self, direction: DVec3) -> DVec3 {
+        let mut radiance = DVec3::ZERO;
+        for (c, y) in self.coefficients.iter().zip(basis(direction)) {
+            radiance += *c * y;
+        }
+        radiance.max(DVec3::ZERO)
+    }
+
+    // Irradiance on a surface facing the unit `normal`: the radiance
+    // convolved with the clamped cosine, which scales each band by a
+    // constant.
+    pub fn irradiance(&self, normal: DVec3) -> DVec3 {
+        const BAND_SCALE: [f64; 9] = [
+            PI,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+        ];
+        let mut irradiance = DVec3::ZERO;
+        for ((c, y), scale) in self.coefficients.iter().zip(basis(normal)).zip(BAND_SCALE) {
+            irradiance += *c * y * scale;
+        }
+        irradiance.max(DVec3::ZERO)
+    }
+}
+
+// Real spherical harmonics of bands 0 to 2 at the unit `d`.
+fn basis(d: DVec3) -> [f64; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
@@ -0,0 +1,126 @@
+use crate::renderer::ImageBuffer;
+use glam::DVec3;
+
+// What `stamp` does to a pixel.
+enum Ink {
+    // Darkens the bar behind the text.
+    Shade,
+    Text,
+}
+
+// Burns a line of text into the bottom-left corner of an 8 or 16-bit RGB
+// image, using a 3x5 pixel font scaled with the image. Lower-case letters
+// are drawn as capitals; characters outside the font become '?'.
+pub(crate) fn stamp_text(values: &mut [u16], width: u32, height: u32, max: u16, text: &str) {
+    let row = 3 * width as usize;
+    stamp(width, height, text, |x, y, ink| {
+        let pixel = &mut values[y * row + 3 * x..y * row + 3 * x + 3];
+        match ink {
+            Ink::Shade => pixel.iter_mut().for_each(|c| *c /= 4),
+            Ink::Text => pixel.fill(max),
+        }
+    });
+}
+
+// The same for a linear image, with the text at 1.
+pub(crate) fn stamp_image(image: &mut ImageBuffer, text: &str) {
+    let width = image.width as usize;
+    stamp(image.width, image.height, text, |x, y, ink| {
+        let pixel = &mut image.pixels[y * width + x];
+        match ink {
+            Ink::Shade => *pixel /= 4.0,
+            Ink::Text => *pixel = DVec3::ONE,
+        }
+    });
+}
+
+fn stamp(width: u32, height: u32, text: &str, mut paint: impl FnMut(usize, usize, Ink)) {
+    let scale = (height / 360).max(1) as usize;
+    let (width, height) = (width as usize, height as usize);
+    let advance = 4 * scale;
+    let margin = scale;
+    let bar_height = (5 + 2) * scale;
+    let bar_width = (text.chars().count() * advance + margin).min(width);
+    if bar_height > height {
+        return;
+    }
+
+    // Darken a bar behind the text so it stays legible on bright images.
+    let top = height - bar_height;
+    for y in top..height {
+        for x in 0..bar_width {
+            paint(x, y, Ink::Shade);
+        }
+    }
+
+    for (i, ch) in text.chars().enumerate() {
+        let left = margin + i * advance;
+        for (row, bits) in glyph(ch).into_iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = left + column * scale + dx;
+                        let y = top + scale + row * scale + dy;
+                        if x < width {
+                            paint(x, y, Ink::Text);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Rows top to bottom, three bits each with the leftmost pixel highest.
+fn glyph(ch: char) -> [u8; 5] {
+    match ch.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ' ' => [0; 5],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+// Work done tracing a render, for comparing acceleration structures and
+// their build settings. Only counted with the `stats` feature; otherwise
+// every counter stays zero and counting compiles away.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct RenderStats {
+    // Closest-hit queries against the scene: camera rays and bounces.
+    pub rays: u64,
+    // Visibility queries towards lights and the environment.
+    pub shadow_rays: u64,
+    // BVH nodes whose children's boxes were tested against a ray.
+    pub node_tests: u64,
+    pub triangle_tests: u64,
+}
+
+impl RenderStats {
+    pub fn enabled() -> bool {
+        cfg!(feature = "stats")
+    }
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.rays += other.rays;
+        self.shadow_rays += other.shadow_rays;
+        self.node_tests += other.node_tests;
+        self.triangle_tests += other.triangle_tests;
+    }
+}
+
+// Counters are kept per thread, so tracing never contends on them, and
+// collected with `take` by whoever knows what the thread was working on.
+#[cfg(feature = "stats")]
+thread_local! {
+    static COUNTERS: std::cell::Cell<RenderStats> = const {
+        std::cell::Cell::new(RenderStats {
+            rays: 0,
+            shadow_rays: 0,
+            node_tests: 0,
+            triangle_tests: 0,
+        })
+    };
+}
+
+#[cfg(feature = "stats")]
+#[inline]
+pub(crate) fn count(update: impl FnOnce(&mut RenderStats)) {
+    COUNTERS.with(|counters| {
+        let mut stats = counters.get();
+        update(&mut stats);
+        counters.set(stats);
+    });
+}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub(crate) fn count(_update: impl FnOnce(&mut RenderStats)) {}
+
+// Counts on this thread since the last call, resetting them.
+#[cfg(feature = "stats")]
+pub(crate) fn take() -> RenderStats {
+    COUNTERS.with(|counters| counters.take())
+}
+
+#[cfg(not(feature = "stats"))]
+pub(crate) fn take() -> RenderStats {
+    RenderStats::default()
+}
+
+// Counts on this thread since the last `take`, leaving them to it.
+#[cfg(feature = "stats")]
+pub(crate) fn current() -> RenderStats {
+    COUNTERS.with(|counters| counters.get())
+}
+
+#[cfg(not(feature = "stats"))]
+pub(crate) fn current() -> RenderStats {
+    RenderStats::default()
+}
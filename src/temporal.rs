@@ -0,0 +1,183 @@
+use crate::camera::Camera;
+use crate::renderer::{ImageBuffer, RenderPasses};
+use glam::{DVec2, DVec3};
+use serde::{Deserialize, Serialize};
+
+// Accumulation of animation frames into a running average, so preview
+// sequences converge over time at a fraction of the samples per frame.
+// Each pixel finds where it was in the previous frame through its depth and
+// the two cameras; history whose depth doesn't match there (disocclusions,
+// pixels that were off-screen) is dropped. Objects are assumed not to move
+// between frames, so they may leave faint trails when they do.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TemporalSettings {
+    // Weight of the new frame in the average. Lower values reuse more
+    // history, for smoother images with longer trails.
+    pub blend: f64,
+    // Largest relative difference between the expected and the stored depth
+    // for history to be reused.
+    pub depth_tolerance: f64,
+}
+
+impl Default for TemporalSettings {
+    fn default() -> Self {
+        Self {
+            blend: 0.2,
+            depth_tolerance: 0.05,
+        }
+    }
+}
+
+// Distance at which pixels that only see the background are placed, far
+// enough that they follow camera rotation but not translation.
+const BACKGROUND_DISTANCE: f64 = 1e6;
+
+// The last accumulated frame.
+struct History {
+    camera: Camera,
+    color: ImageBuffer,
+    depth: ImageBuffer,
+}
+
+pub struct TemporalAccumulator {
+    settings: TemporalSettings,
+    history: Option<History>,
+}
+
+impl TemporalAccumulator {
+    pub fn new(settings: TemporalSettings) -> Self {
+        Self {
+            settings,
+            history: None,
+        }
+    }
+
+    // Forgets the history, e.g. at a cut.
+    pub fn reset(&mut self) {
+        self.history = None;
+    }
+
+    // Blends `passes` into the history and returns the result. The passes
+    // need the depth AOV; without it the beauty image is returned as is
+    // and the history is cleared.
+    pub fn accumulate(&mut self, camera: &Camera, passes: &RenderPasses) -> ImageBuffer {
+        let current = &passes.beauty;
+        let Some(depth) = &passes.depth else {
+            self.history = None;
+            return current.clone();
+        };
+        let mut output = current.clone();
+        let history = self
+            .history
+            .as_ref()
+            .filter(|h| h.color.width == current.width && h.color.height == current.height);
+        if let Some(history) = history {
+            let blend = self.settings.blend.clamp(0.0, 1.0);
+            for y in 0..current.height {
+                for x in 0..current.width {
+                    let Some((position, expected)) =
+                        reproject(&history.camera, camera, x, y, depth.get(x, y).x, current)
+                    else {
+                        continue;
+                    };
+                    if let Some(previous) =
+                        history.sample(position, expected, self.settings.depth_tolerance)
+                    {
+                        output.set(x, y, previous.lerp(current.get(x, y), blend));
+                    }
+                }
+            }
+        }
+        self.history = Some(History {
+            camera: camera.clone(),
+            color: output.clone(),
+            depth: depth.clone(),
+        });
+        output
+    }
+}
+
+impl History {
+    // Bilinear lookup at pixel `position`, using only the taps whose depth
+    // matches `expected`.
+    fn sample(&self, position: DVec2, expected: f64, tolerance: f64) -> Option<DVec3> {
+        let base = position.floor();
+        let f = position - base;
+        let mut color = DVec3::ZERO;
+        let mut weight = 0.0;
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let (x, y) = (base.x as i64 + dx, base.y as i64 + dy);
+            if x < 0 || y < 0 || x >= self.color.width as i64 || y >= self.color.height as i64 {
+                continue;
+            }
+            let w = if dx == 0 { 1.0 - f.x } else { f.x } * if dy == 0 { 1.0 - f.y } else { f.y };
+            let depth = self.depth.get(x as u32, y as u32).x;
+            let matches = if expected == 0.0 {
+                depth == 0.0
+            } else {
+                (depth - expected).abs() <= tolerance * expected
+            };
+            if matches && w > 0.0 {
+                color += w * self.color.get(x as u32, y as u32);
+                weight += w;
+            }
+        }
+        (weight > 1e-3).then(|| color / weight)
+    }
+}
+
+// Screen-space motion of each pixel since the previous frame, in pixels:
+// x and y hold the offset from the current position back to the previous
+// one, and z is 1 where the previous camera saw the point and 0 elsewhere.
+// Only camera motion is captured.
+pub fn motion_vectors(previous: &Camera, current: &Camera, depth: &ImageBuffer) -> ImageBuffer {
+    let mut vectors = ImageBuffer::new(depth.width, depth.height);
+    for y in 0..depth.height {
+        for x in 0..depth.width {
+            if let Some((position, _)) =
+                reproject(previous, current, x, y, depth.get(x, y).x, depth)
+            {
+                let offset = position - DVec2::new(x as f64, y as f64);
+                vectors.set(x, y, offset.extend(1.0));
+            }
+        }
+    }
+    vectors
+}
+
+// Position in the previous frame of pixel (`x`, `y`), whose first hit is
+// `depth` from the current camera, with its expected depth there (0 for
+// the background).
+fn reproject(
+    previous: &Camera,
+    current: &Camera,
+    x: u32,
+    y: u32,
+    depth: f64,
+    image: &ImageBuffer,
+) -> Option<(DVec2, f64)> {
+    // The renderer's mapping between pixel centres and NDC.
+    let width = (image.width.max(2) - 1) as f64;
+    let height = (image.height.max(2) - 1) as f64;
+    let s = (x as f64 + 0.5) / width;
+    let t = ((image.height - 1 - y) as f64 + 0.5) / height;
+
+    let distance = if depth > 0.0 {
+        depth
+    } else {
+        BACKGROUND_DISTANCE
+    };
+    let point = current.unproject(s, t, distance);
+    let ndc = previous.project(point)?;
+    let position = DVec2::new(
+        ndc.x * width - 0.5,
+        (image.height - 1) as f64 + 0.5 - ndc.y * height,
+    );
+    let expected = if depth > 0.0 {
+        (point - previous.origin).length()
+    } else {
+        0.0
+    };
+    Some((position, expected))
+}
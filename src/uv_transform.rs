@@ -0,0 +1,38 @@
+use crate::texture::Texture;
+use glam::{DVec2, DVec3};
+use std::sync::Arc;
+
+// Remaps the UVs of a lookup before passing it on, so a texture can be tiled,
+// shifted or turned without editing the asset's coordinates. UVs are scaled,
+// then rotated about the origin, then offset.
+pub struct UvTransform {
+    pub texture: Arc<dyn Texture>,
+    // Repetitions across the surface; 2 tiles the texture twice.
+    pub scale: DVec2,
+    pub offset: DVec2,
+    // Radians, counter-clockwise.
+    pub rotation: f64,
+}
+
+impl UvTransform {
+    pub fn new(texture: Arc<dyn Texture>) -> Self {
+        Self {
+            texture,
+            scale: DVec2::ONE,
+            offset: DVec2::ZERO,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn apply(&self, u: f64, v: f64) -> DVec2 {
+        let rotation = DVec2::from_angle(self.rotation);
+        rotation.rotate(DVec2::new(u, v) * self.scale) + self.offset
+    }
+}
+
+impl Texture for UvTransform {
+    fn value(&self, u: f64, v: f64, p: DVec3) -> DVec3 {
+        let uv = self.apply(u, v);
+        self.texture.value(uv.x, uv.y, p)
+    }
+}
@@ -0,0 +1,160 @@
+use crate::hittable::AABB;
+use glam::DVec3;
+use std::error::Error;
+use std::path::Path;
+
+// Scalar density on a regular grid of voxels filling `bounds`, for media
+// whose density varies through space, such as clouds and smoke. Densities
+// are interpolated trilinearly between voxel centres and are zero outside
+// the bounds.
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    // x varies fastest, then y, then z.
+    values: Vec<f32>,
+    max: f64,
+    // World-space box the grid fills.
+    pub bounds: AABB,
+}
+
+impl std::fmt::Debug for DensityGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DensityGrid")
+            .field("resolution", &self.resolution)
+            .field("max", &self.max)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DensityGrid {
+    // Grid filling the unit cube. Negative and non-finite densities are
+    // taken as empty space.
+    pub fn new(resolution: [usize; 3], values: Vec<f32>) -> Result<Self, Box<dyn Error>> {
+        let count = resolution.iter().product::<usize>();
+        if count == 0 || values.len() != count {
+            return Err(format!(
+                "a {}x{}x{} density grid needs {count} values, not {}",
+                resolution[0],
+                resolution[1],
+                resolution[2],
+                values.len()
+            )
+            .into());
+        }
+        let values: Vec<f32> = values
+            .into_iter()
+            .map(|v| if v.is_finite() { v.max(0.0) } else { 0.0 })
+            .collect();
+        let max = values.iter().fold(0.0f32, |max, &v| max.max(v)) as f64;
+        Ok(Self {
+            resolution,
+            values,
+            max,
+            bounds: AABB::new(DVec3::ZERO, DVec3::ONE),
+        })
+    }
+
+    // Loads a grid by the extension of `path`: Mitsuba's `.vol` format,
+    // which records its own resolution and bounds, or otherwise raw
+    // little-endian f32 voxels in `resolution`, x varying fastest. OpenVDB
+    // and NanoVDB files must be converted to one of these first.
+    pub fn load(path: &str, resolution: Option<[usize; 3]>) -> Result<Self, Box<dyn Error>> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("vdb" | "nvdb") => {
+                Err(format!("{path}: convert VDB grids to .vol or raw f32 voxels").into())
+            }
+            Some("vol") => Self::from_vol(&std::fs::read(path)?),
+            _ => {
+                let resolution =
+                    resolution.ok_or_else(|| format!("{path}: raw grids need a resolution"))?;
+                Self::from_raw(&std::fs::read(path)?, resolution)
+            }
+        }
+    }
+
+    pub fn from_raw(bytes: &[u8], resolution: [usize; 3]) -> Result<Self, Box<dyn Error>> {
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Self::new(resolution, values)
+    }
+
+    // Mitsuba's volume format: "VOL", version 3, the encoding (1 for f32,
+    // 3 for u8), the resolution, the channel count and the bounds, then
+    // the voxels with channels interleaved. Only the first channel is kept.
+    pub fn from_vol(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        const HEADER: usize = 48;
+        if bytes.len() < HEADER || &bytes[..3] != b"VOL" || bytes[3] != 3 {
+            return Err("not a version 3 .vol file".into());
+        }
+        let word = |i: usize| <[u8; 4]>::try_from(&bytes[4 * i..4 * i + 4]).unwrap();
+        let int = |i: usize| i32::from_le_bytes(word(i)).max(0) as usize;
+        let float = |i: usize| f32::from_le_bytes(word(i)) as f64;
+        let (encoding, channels) = (int(1), int(5).max(1));
+        let resolution = [int(2), int(3), int(4)];
+        let count = resolution.iter().product::<usize>();
+        let data = &bytes[HEADER..];
+        let values = match encoding {
+            1 if data.len() >= 4 * count * channels => data
+                .chunks_exact(4 * channels)
+                .take(count)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            3 if data.len() >= count * channels => data
+                .chunks_exact(channels)
+                .take(count)
+                .map(|b| b[0] as f32 / 255.0)
+                .collect(),
+            1 | 3 => return Err(".vol file is shorter than its header says".into()),
+            _ => return Err(format!("unsupported .vol encoding {encoding}").into()),
+        };
+        let mut grid = Self::new(resolution, values)?;
+        grid.bounds = AABB::new(
+            DVec3::new(float(6), float(7), float(8)),
+            DVec3::new(float(9), float(10), float(11)),
+        );
+        Ok(grid)
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    // Largest density anywhere in the grid, which bounds the extinction
+    // for delta and ratio tracking.
+    pub fn max_density(&self) -> f64 {
+        self.max
+    }
+
+    // Density at the world-space `point`.
+    pub fn density(&self, point: DVec3) -> f64 {
+        let size = self.bounds.max - self.bounds.min;
+        let local = (point - self.bounds.min) / size;
+        if !(local.cmpge(DVec3::ZERO).all() && local.cmple(DVec3::ONE).all()) {
+            return 0.0;
+        }
+        let [nx, ny, nz] = self.resolution;
+        // Voxel centres sit at half-integer coordinates.
+        let g = local * DVec3::new(nx as f64, ny as f64, nz as f64) - DVec3::splat(0.5);
+        let base = g.floor();
+        let f = g - base;
+        let index = |axis: usize, offset: i64| {
+            let n = self.resolution[axis] as i64;
+            (base[axis] as i64 + offset).clamp(0, n - 1) as usize
+        };
+        let mut density = 0.0;
+        for corner in 0..8 {
+            let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = (if dx == 1 { f.x } else { 1.0 - f.x })
+                * (if dy == 1 { f.y } else { 1.0 - f.y })
+                * (if dz == 1 { f.z } else { 1.0 - f.z });
+            let (x, y, z) = (index(0, dx), index(1, dy), index(2, dz));
+            density += weight * self.values[(z * ny + y) * nx + x] as f64;
+        }
+        density
+    }
+}
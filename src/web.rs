@@ -0,0 +1,28 @@
+use crate::output::{self, OutputOptions};
+use crate::renderer::Renderer;
+use crate::scene::{ImageFiles, Scene, SceneFormat};
+use std::error::Error;
+
+// Renders the JSON scene `source` at `width` x `height`, whatever size the
+// scene asks for, and returns its pixels as `output::to_rgba8` encodes
+// them, ready for a canvas's `ImageData`. Everything the scene refers to
+// must load without a filesystem when built for a browser: its `image`
+// textures are read from `images`, by their paths, which the page fetches
+// beforehand, and meshes are out.
+pub fn render_to_rgba_buffer(
+    source: &str,
+    images: &ImageFiles,
+    width: u32,
+    height: u32,
+    options: &OutputOptions,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut config = SceneFormat::Json.parse(source)?;
+    config.resolution = None;
+    config.render.width = width;
+    config.render.height = height;
+    let (config, camera, world, lights) = Scene::from_config_with_images(config, 0.0, images)?;
+    let image = Renderer::new(world, config.environment()?)
+        .with_lights(lights)
+        .render(&camera, &config.render);
+    Ok(output::to_rgba8(&image, options))
+}
@@ -0,0 +1,49 @@
+use crate::hittable::AABB;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Writes boxes as an OBJ of line elements, one named object per group, for
+// inspecting bounding volumes in Blender or any other OBJ viewer.
+pub fn write_boxes_obj(path: &Path, groups: &[(&str, Vec<AABB>)]) -> Result<(), Box<dyn Error>> {
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1),
+        (1, 3),
+        (3, 2),
+        (2, 0),
+        (4, 5),
+        (5, 7),
+        (7, 6),
+        (6, 4),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+    let mut out = BufWriter::new(File::create(path)?);
+    // OBJ indices are 1-based and global across objects.
+    let mut next_vertex = 1;
+    for (name, boxes) in groups {
+        writeln!(out, "o {name}")?;
+        for bbox in boxes {
+            // Corner i takes max along x, y and z for bits 0, 1 and 2.
+            for i in 0..8 {
+                let pick = |bit: usize, min: f64, max: f64| if i & bit == 0 { min } else { max };
+                writeln!(
+                    out,
+                    "v {} {} {}",
+                    pick(1, bbox.min.x, bbox.max.x),
+                    pick(2, bbox.min.y, bbox.max.y),
+                    pick(4, bbox.min.z, bbox.max.z)
+                )?;
+            }
+            for (a, b) in EDGES {
+                writeln!(out, "l {} {}", next_vertex + a, next_vertex + b)?;
+            }
+            next_vertex += 8;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
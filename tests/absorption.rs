@@ -0,0 +1,100 @@
+use glam::DVec3;
+use raytracer::environment::SolidBackground;
+use raytracer::material::{Dielectric, Medium};
+use raytracer::objects::sphere::Sphere;
+use raytracer::ray::Ray;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::sampler::IndependentSampler;
+use raytracer::scene::{Scene, SceneFormat, SceneValidationError};
+use std::sync::Arc;
+
+#[test]
+fn tinted_glass_follows_beer_lambert() {
+    // An index of 1 neither reflects nor bends, leaving only the tint.
+    let absorption = DVec3::new(0.1, 0.5, 2.0);
+    let glass = Arc::new(Dielectric {
+        absorption,
+        ..Dielectric::new(1.0)
+    });
+    let settings = RenderSettings::default();
+    let mut sampler = IndependentSampler::new(3);
+
+    // Straight through the middle of balls of two sizes, head on so that
+    // nothing is reflected.
+    for radius in [1.0, 0.25] {
+        let world = Arc::new(Sphere::new(DVec3::ZERO, radius, glass.clone()));
+        let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)));
+        let ray = Ray::new(DVec3::new(0.0, 0.0, 3.0), DVec3::NEG_Z);
+        let color = renderer.ray_color(&ray, &settings, &mut sampler);
+        let expected = (-absorption * 2.0 * radius).exp();
+        assert!(
+            color.abs_diff_eq(expected, 1e-6),
+            "{color}, expected {expected}"
+        );
+    }
+}
+
+#[test]
+fn purely_absorbing_media_are_crossed_without_scattering() {
+    let medium = Medium::new(DVec3::new(1.0, 0.5, 0.0), DVec3::ZERO);
+    let mut sampler = IndependentSampler::new(5);
+    for _ in 0..100 {
+        let (distance, weight) = medium.sample_distance(DVec3::ZERO, DVec3::X, 2.0, &mut sampler);
+        assert_eq!(distance, 2.0);
+        assert!(weight.abs_diff_eq((-2.0 * medium.sigma_a).exp(), 1e-12));
+    }
+}
+
+const YAML: &str = "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: dielectric
+      index_of_refraction: 1.33
+      absorption: ABSORPTION
+";
+
+#[test]
+fn scenes_tint_glass_by_colour_and_density() {
+    for absorption in [
+        "{ color: [0.6, 0.9, 1] }",
+        "{ color: [0.6, 0.9, 1], density: 4 }",
+    ] {
+        let source = YAML.replace("ABSORPTION", absorption);
+        assert!(
+            Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).is_ok(),
+            "{absorption}"
+        );
+    }
+
+    for (absorption, problem) in [
+        (
+            "{ color: [0, 0.9, 1] }",
+            "objects[0].material.absorption.color: must be in (0, 1]",
+        ),
+        (
+            "{ color: [0.6, 0.9, 1.5] }",
+            "objects[0].material.absorption.color: must be in (0, 1]",
+        ),
+        (
+            "{ color: [0.6, 0.9, 1], density: -1 }",
+            "objects[0].material.absorption.density: must not be negative",
+        ),
+    ] {
+        let source = YAML.replace("ABSORPTION", absorption);
+        let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+            .err()
+            .unwrap();
+        let error = error.downcast_ref::<SceneValidationError>().unwrap();
+        assert_eq!(error.problems, [problem]);
+    }
+}
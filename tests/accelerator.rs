@@ -0,0 +1,208 @@
+use glam::{DAffine3, DVec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use raytracer::accelerator::{Accelerator, UniformGrid};
+use raytracer::bvh::BvhNode;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::mesh::Mesh;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::transform::Transformed;
+use raytracer::objects::triangle::Triangle;
+use raytracer::qbvh::{BvhBuildStrategy, Qbvh};
+use raytracer::ray::Ray;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+// Spheres of mixed sizes scattered through a box, offset by `shift`.
+fn spheres(shift: DVec3) -> HittableList {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let mut rng = StdRng::seed_from_u64(3);
+    (0..300)
+        .map(|_| {
+            let center = DVec3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-2.0..2.0),
+                rng.gen_range(-10.0..10.0),
+            );
+            let radius = rng.gen_range(0.05..0.6);
+            Arc::new(Sphere::new(center + shift, radius, material.clone())) as Arc<dyn Hittable>
+        })
+        .collect()
+}
+
+// A bumpy grid of triangles over the rays' box.
+fn bumpy_grid() -> Mesh {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let height = |x: f64, z: f64| (0.7 * x).sin() * (0.5 * z).cos();
+    let mut triangles = Vec::new();
+    for i in -10..10 {
+        for j in -10..10 {
+            let at = |x: i32, z: i32| DVec3::new(x as f64, height(x as f64, z as f64), z as f64);
+            let (a, b, c, d) = (at(i, j), at(i + 1, j), at(i + 1, j + 1), at(i, j + 1));
+            triangles.push(Triangle::new([a, b, c], material.clone()));
+            triangles.push(Triangle::new([a, c, d], material.clone()));
+        }
+    }
+    Mesh::new(triangles)
+}
+
+fn rays() -> Vec<Ray> {
+    let mut rng = StdRng::seed_from_u64(5);
+    (0..2000)
+        .map(|i| {
+            let origin = DVec3::new(
+                rng.gen_range(-14.0..14.0),
+                rng.gen_range(-4.0..4.0),
+                rng.gen_range(-14.0..14.0),
+            );
+            let target = DVec3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-2.0..2.0),
+                rng.gen_range(-10.0..10.0),
+            );
+            // Some rays run along the grid's axes.
+            let direction = match i % 10 {
+                0 => DVec3::X,
+                1 => -DVec3::Z,
+                _ => target - origin,
+            };
+            Ray::new(origin, direction)
+        })
+        .collect()
+}
+
+fn assert_matches(expected: &dyn Hittable, actual: &dyn Accelerator) {
+    for ray in rays() {
+        for interval in [Interval::after(0.001), Interval::new(0.001, 3.0)] {
+            let want = expected.hit(&ray, interval).map(|rec| rec.t);
+            let got = actual.hit(&ray, interval).map(|rec| rec.t);
+            assert_eq!(got, want);
+            assert_eq!(actual.hit_any(&ray, interval), want.is_some());
+        }
+    }
+}
+
+#[test]
+fn accelerators_agree_with_testing_every_object() {
+    let objects = spheres(DVec3::ZERO);
+    assert_matches(&objects, &HittableList::build(objects.clone()));
+    assert_matches(&objects, &BvhNode::build(objects.clone()));
+    assert_matches(&objects, &Qbvh::build(objects.clone()));
+    let grid = UniformGrid::build(objects.clone());
+    assert!(grid.resolution().iter().all(|&n| n > 1));
+    assert_matches(&objects, &grid);
+}
+
+#[test]
+fn refitted_accelerators_follow_moved_objects() {
+    let moved = spheres(DVec3::new(0.5, 1.0, -0.3));
+    let mut qbvh = Qbvh::build(spheres(DVec3::ZERO));
+    qbvh.refit(moved.clone());
+    assert_matches(&moved, &qbvh);
+    let mut grid = UniformGrid::build(spheres(DVec3::ZERO));
+    grid.refit(moved.clone());
+    assert_matches(&moved, &grid);
+}
+
+#[test]
+fn cached_hierarchies_load_for_the_same_objects() {
+    let dir = std::env::temp_dir().join("raytracer-qbvh-cache");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("spheres.qbvh");
+    let _ = std::fs::remove_file(&path);
+
+    let objects = spheres(DVec3::ZERO);
+    let built = Qbvh::cached(objects.clone(), BvhBuildStrategy::default(), &path, 7);
+    assert!(path.exists());
+    let loaded = Qbvh::load(&path, objects.clone(), 7).unwrap();
+    assert_eq!(loaded.stats().node_count, built.stats().node_count);
+    assert_matches(&objects, &loaded);
+
+    assert!(Qbvh::load(&path, objects.clone(), 8).is_err());
+    let fewer: HittableList = objects[..10].to_vec();
+    assert!(Qbvh::load(&path, fewer, 7).is_err());
+    std::fs::write(&path, b"QBVH").unwrap();
+    assert!(Qbvh::load(&path, objects, 7).is_err());
+}
+
+#[test]
+fn meshes_and_transforms_answer_any_hit_queries() {
+    let objects: Vec<Arc<dyn Hittable>> = vec![
+        Arc::new(bumpy_grid()),
+        Arc::new(Transformed::new(
+            Arc::new(spheres(DVec3::ZERO)),
+            DAffine3::from_rotation_y(0.4),
+        )),
+    ];
+    for object in &objects {
+        for ray in rays() {
+            for interval in [Interval::after(0.001), Interval::new(0.001, 3.0)] {
+                let want = object.hit(&ray, interval).is_some();
+                assert_eq!(object.hit_any(&ray, interval), want);
+            }
+        }
+    }
+}
+
+#[test]
+fn packets_find_the_same_hits_as_single_rays() {
+    let objects = spheres(DVec3::ZERO);
+    let qbvh = Qbvh::build(objects.clone());
+    // Tiles of 8 x 8 rays from one eye, as a pinhole camera traces them,
+    // and bundles of the scattered rays, which make no frustum.
+    let eye = DVec3::new(0.0, 1.0, 16.0);
+    let mut packets: Vec<Vec<Ray>> = (0..16)
+        .map(|tile| {
+            let corner = (8 * (tile % 4), 8 * (tile / 4));
+            (0..64)
+                .map(|i| {
+                    let u = (corner.0 + i % 8) as f64 / 32.0 - 0.5;
+                    let v = (corner.1 + i / 8) as f64 / 32.0 - 0.5;
+                    Ray::new(eye, DVec3::new(24.0 * u, 6.0 * v - 1.0, -16.0))
+                })
+                .collect()
+        })
+        .collect();
+    packets.extend(rays().chunks(64).map(<[Ray]>::to_vec));
+
+    for packet in &packets {
+        for interval in [Interval::after(0.001), Interval::new(0.001, 20.0)] {
+            let mut hits = vec![None; packet.len()];
+            qbvh.hit_packet(packet, interval, &mut hits);
+            for (ray, hit) in packet.iter().zip(&hits) {
+                let want = objects.hit(ray, interval).map(|rec| rec.t);
+                assert_eq!(hit.map(|rec| rec.t), want);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "embree")]
+#[test]
+fn embree_agrees_with_testing_every_object() {
+    use raytracer::embree::EmbreeScene;
+    let objects = spheres(DVec3::ZERO);
+    assert_matches(&objects, &EmbreeScene::build(objects.clone()));
+}
+
+#[cfg(feature = "embree")]
+#[test]
+fn embree_traces_meshes_and_their_instances() {
+    use raytracer::embree::EmbreeScene;
+    // The grid where it was built, and a copy tilted and raised through
+    // the spheres, which Embree instances from the same triangles.
+    let grid: Arc<Mesh> = Arc::new(bumpy_grid());
+    let mut objects = spheres(DVec3::ZERO);
+    objects.push(grid.clone());
+    objects.push(Arc::new(Transformed::new(
+        grid.clone(),
+        DAffine3::from_translation(DVec3::Y) * DAffine3::from_rotation_x(0.1),
+    )));
+    let embree = EmbreeScene::build(objects.clone());
+    assert_eq!(embree.triangle_count(), 2 * grid.triangle_count());
+    assert_matches(&objects, &embree);
+}
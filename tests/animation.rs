@@ -0,0 +1,173 @@
+use glam::{DVec2, DVec3};
+use raytracer::animation::render_animation;
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::Hittable;
+use raytracer::material::Lambertian;
+use raytracer::objects::sphere::Sphere;
+use raytracer::renderer::{ImageBuffer, RenderSettings, Renderer};
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn render(settings: &RenderSettings) -> ImageBuffer {
+    let material = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.5,
+    )))));
+    let world = Arc::new(Sphere::new(DVec3::ZERO, 1.0, material));
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)));
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        settings.aspect_ratio(),
+        0.0,
+        4.0,
+    );
+    renderer.render(&camera, settings)
+}
+
+#[test]
+fn frames_get_their_own_noise_unless_locked() {
+    let settings = RenderSettings {
+        width: 16,
+        height: 16,
+        samples_per_pixel: 4,
+        seed: 3,
+        ..RenderSettings::default()
+    };
+    assert_eq!(settings.for_frame(0).seed, settings.seed);
+    let first = render(&settings.for_frame(0));
+    let second = render(&settings.for_frame(1));
+    assert_ne!(first.pixels, second.pixels);
+
+    let locked = RenderSettings {
+        lock_seed: true,
+        ..settings
+    };
+    assert_eq!(locked.for_frame(7).seed, settings.seed);
+    assert_eq!(render(&locked.for_frame(7)).pixels, first.pixels);
+}
+
+const SCENE: &str = "
+camera:
+  lookfrom: [0, 0, 10]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 10
+  animation:
+    keys:
+      - { time: 0 }
+      - { time: 1, rotate: [0, 360, 0] }
+objects:
+  - type: animated
+    object:
+      type: sphere
+      center: [0, 0, 0]
+      radius: 1
+      material: { type: lambertian, texture: { type: solid_color, color: [0.5, 0.5, 0.5] } }
+    animation:
+      interpolation: linear
+      keys:
+        - { time: 0 }
+        - { time: 0.5, translate: [1, 0, 0] }
+        - { time: 1, translate: [4, 0, 0] }
+render:
+  width: 8
+  height: 8
+  samples_per_pixel: 1
+";
+
+fn sphere_x(source: &str, time: f64) -> f64 {
+    let (_, _, world, _) = Scene::from_source_at(source, SceneFormat::Yaml, time).unwrap();
+    let bbox = world.bounding_box().unwrap();
+    (bbox.min.x + bbox.max.x) / 2.0
+}
+
+#[test]
+fn keyframes_move_objects_and_the_camera() {
+    assert!((sphere_x(SCENE, 0.25) - 0.5).abs() < 1e-9);
+    assert!((sphere_x(SCENE, 0.75) - 2.5).abs() < 1e-9);
+    // Held after the last key.
+    assert!((sphere_x(SCENE, 2.0) - 4.0).abs() < 1e-9);
+
+    let cubic = SCENE.replace("interpolation: linear", "interpolation: cubic");
+    assert!((sphere_x(&cubic, 0.5) - 1.0).abs() < 1e-9);
+    assert!((sphere_x(&cubic, 0.75) - 2.5).abs() > 1e-3);
+
+    // A quarter of the turntable puts the camera on the +X axis.
+    let (_, camera, _, _) = Scene::from_source_at(SCENE, SceneFormat::Yaml, 0.25).unwrap();
+    let ray = camera.generate_ray(0.5, 0.5, DVec2::ZERO, 0.0);
+    assert!(
+        ray.origin.abs_diff_eq(DVec3::new(10.0, 0.0, 0.0), 1e-9),
+        "{}",
+        ray.origin
+    );
+}
+
+#[test]
+fn frame_ranges_render_numbered_frames() {
+    let dir = std::env::temp_dir().join("raytracer-animation");
+    std::fs::create_dir_all(&dir).unwrap();
+    let scene = dir.join("turntable.yaml");
+    std::fs::write(&scene, SCENE).unwrap();
+    let output = dir.join("frame-###.png");
+    let mut reported = Vec::new();
+    let written = render_animation(
+        scene.to_str().unwrap(),
+        5,
+        2..4,
+        output.to_str().unwrap(),
+        |frame, _| reported.push(frame),
+    )
+    .unwrap();
+    assert_eq!(reported, [2, 3]);
+    assert_eq!(
+        written,
+        [dir.join("frame-002.png"), dir.join("frame-003.png")]
+    );
+    assert!(written.iter().all(|path| path.exists()));
+}
+
+const CAR: &str = "
+camera:
+  lookfrom: [0, 0, 10]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 10
+objects:
+  - type: node
+    animation:
+      keys:
+        - { time: 0 }
+        - { time: 1, translate: [10, 0, 0] }
+    children:
+      - type: node
+        transform: { translate: [0, 0, 2], rotate: [0, 90, 0] }
+        children:
+          - type: node
+            transform: { translate: [1, 0, 0], scale: 0.5 }
+            children:
+              - type: sphere
+                center: [0, 0, 0]
+                radius: 1
+                material: { type: lambertian, texture: { type: solid_color, color: [0.5, 0.5, 0.5] } }
+";
+
+#[test]
+fn nodes_carry_their_children_with_them() {
+    let (_, _, world, _) = Scene::from_source_at(CAR, SceneFormat::Yaml, 0.5).unwrap();
+    let bbox = world.bounding_box().unwrap();
+    // Local +X turned to -Z by the middle node, then carried by the root.
+    let center = (bbox.min + bbox.max) / 2.0;
+    assert!(
+        center.abs_diff_eq(DVec3::new(5.0, 0.0, 1.0), 1e-9),
+        "{center}"
+    );
+    assert!((bbox.max - bbox.min).abs_diff_eq(DVec3::ONE, 1e-9));
+}
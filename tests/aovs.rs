@@ -0,0 +1,161 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::disk::Disk;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::tagged::Tagged;
+use raytracer::ray::Ray;
+use raytracer::renderer::{
+    matte_coverage, name_id, AovSelection, RenderPasses, RenderSettings, Renderer, UtilitySampling,
+};
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+const SIZE: u32 = 12;
+// Between the sphere's depths, about 3, and the wall's, 6.
+const SPLIT: f64 = 4.5;
+
+fn render(aovs: AovSelection) -> RenderPasses {
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let sphere = Sphere::new(DVec3::new(0.0, 0.0, 1.0), 0.5, material.clone());
+    let wall = Disk::new(DVec3::new(0.0, 0.0, -2.0), DVec3::Z, 100.0, material);
+    let world: HittableList = vec![
+        Arc::new(Tagged::new(Arc::new(sphere), 1)) as Arc<dyn Hittable>,
+        Arc::new(Tagged::new(Arc::new(wall), 2)),
+    ];
+    let renderer = Renderer::new(Arc::new(world), Arc::new(SolidBackground::new(DVec3::ONE)));
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        30.0,
+        1.0,
+        0.0,
+        4.0,
+    );
+    let settings = RenderSettings {
+        width: SIZE,
+        height: SIZE,
+        samples_per_pixel: 16,
+        max_depth: 1,
+        aovs,
+        ..RenderSettings::default()
+    };
+    renderer.render_passes(&camera, &settings)
+}
+
+fn render_ids(utility_sampling: UtilitySampling) -> RenderPasses {
+    render(AovSelection {
+        depth: true,
+        object_id: true,
+        utility_sampling,
+        ..AovSelection::default()
+    })
+}
+
+// Pixels whose depth and ID disagree about which object they show.
+fn mismatches(passes: &RenderPasses) -> usize {
+    let (depth, ids) = (
+        passes.depth.as_ref().unwrap(),
+        passes.object_id.as_ref().unwrap(),
+    );
+    let mut count = 0;
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let near = depth.get(x, y).x < SPLIT;
+            if near != (ids.get(x, y).x == 1.0) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn depth_and_ids_come_from_the_same_sample() {
+    // Averaged depth blends the sphere's edge with the wall behind it.
+    let average = render_ids(UtilitySampling::Average);
+    let closest = render_ids(UtilitySampling::Closest);
+    assert_eq!(mismatches(&render_ids(UtilitySampling::FirstSample)), 0);
+    assert_eq!(mismatches(&closest), 0);
+
+    let (average, closest) = (average.depth.unwrap(), closest.depth.unwrap());
+    let mut nearer = 0;
+    for (a, c) in average.pixels.iter().zip(&closest.pixels) {
+        assert!(c.x <= a.x + 1e-9, "{} > {}", c.x, a.x);
+        if c.x < a.x - 1e-3 {
+            nearer += 1;
+        }
+    }
+    // Some pixels along the edge see the sphere in only a few samples.
+    assert!(nearer > 0);
+}
+
+#[test]
+fn mattes_split_edge_pixels_between_objects() {
+    let passes = render(AovSelection {
+        object_matte: true,
+        ..AovSelection::default()
+    });
+    let layers = passes.object_matte.as_ref().unwrap();
+    let names: Vec<_> = passes.aovs().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["object_matte00", "object_matte01"]);
+
+    let (sphere, wall) = (matte_coverage(layers, 1), matte_coverage(layers, 2));
+    let mut edges = 0;
+    for (s, w) in sphere.iter().zip(&wall) {
+        // Every sample hits one or the other.
+        assert!((s + w - 1.0).abs() < 1e-9, "{s} + {w}");
+        if *s > 0.0 && *w > 0.0 {
+            edges += 1;
+        }
+    }
+    assert!(edges > 0);
+    let centre = (SIZE / 2 * SIZE + SIZE / 2) as usize;
+    assert_eq!(sphere[centre], 1.0);
+    // The ID with the most coverage comes first.
+    let first = layers[0].get(SIZE / 2, SIZE / 2);
+    assert_eq!((first.x, first.y), (1.0, 1.0));
+}
+
+#[test]
+fn names_give_stable_ids() {
+    let scene = "
+camera: { lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }
+materials:
+  clay: { type: lambertian, texture: { type: solid_color, color: [0.5, 0.5, 0.5] } }
+objects:
+  - name: ball
+    type: sphere
+    center: [-1, 0, 0]
+    radius: 0.5
+    material: clay
+  - type: sphere
+    center: [1, 0, 0]
+    radius: 0.5
+    material: { type: metal, texture: { type: solid_color, color: [0.9, 0.9, 0.9] }, fuzz: 0 }
+  - type: sphere
+    center: [3, 0, -3]
+    radius: 0.5
+    material: { type: metal, texture: { type: solid_color, color: [0.9, 0.9, 0.9] }, fuzz: 0 }
+";
+    let (_, _, world, _) = Scene::from_source_at(scene, SceneFormat::Yaml, 0.0).unwrap();
+    let hit = |x: f64, z: f64| {
+        let ray = Ray::new(DVec3::new(x, 0.0, z + 5.0), -DVec3::Z);
+        world.hit(&ray, Interval::after(1e-3)).unwrap()
+    };
+    let ball = hit(-1.0, 0.0);
+    assert_eq!(ball.object_id, name_id("ball"));
+    assert_eq!(ball.material.id(), name_id("clay"));
+    // Unnamed objects are numbered; identical inline materials share an ID.
+    let (near, far) = (hit(1.0, 0.0), hit(3.0, -3.0));
+    assert_eq!(near.object_id, 2);
+    assert_ne!(near.material.id(), 0);
+    assert_eq!(near.material.id(), far.material.id());
+}
@@ -0,0 +1,98 @@
+use glam::{DVec2, DVec3};
+use raytracer::bake::{bake, BakeMode, BakeOptions};
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::triangle::Triangle;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+fn grey() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.5,
+    )))))
+}
+
+// The unit square in z = 0, facing up, with UVs of its x and y.
+fn square() -> Vec<Triangle> {
+    let [a, b, c, d] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    let corner = |(x, y): (f64, f64)| (DVec3::new(x, y, 0.0), DVec2::new(x, y));
+    [[a, b, c], [a, c, d]]
+        .into_iter()
+        .map(|corners| {
+            let corners = corners.map(corner);
+            Triangle::new(corners.map(|c| c.0), grey()).with_uvs(corners.map(|c| c.1))
+        })
+        .collect()
+}
+
+fn renderer(extra: Vec<Triangle>) -> Renderer {
+    let mut world = HittableList::new();
+    for triangle in square().into_iter().chain(extra) {
+        world.push(Arc::new(triangle));
+    }
+    let world: Arc<dyn Hittable> = Arc::new(world);
+    Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)))
+}
+
+fn options(mode: BakeMode) -> BakeOptions {
+    BakeOptions {
+        width: 16,
+        height: 16,
+        mode,
+        samples: 32,
+        ..BakeOptions::default()
+    }
+}
+
+#[test]
+fn open_surfaces_bake_to_white_and_uniform_irradiance() {
+    let renderer = renderer(Vec::new());
+    let settings = RenderSettings::default();
+    let occlusion = BakeMode::AmbientOcclusion { distance: None };
+    let ao = bake(&square(), &renderer, &settings, &options(occlusion));
+    assert_eq!((ao.width, ao.height), (16, 16));
+    assert!(ao.pixels.iter().all(|p| *p == DVec3::ONE));
+    assert!(ao.alpha.unwrap().iter().all(|&a| a == 1.0));
+
+    // A white sky all round gives π from the hemisphere above.
+    let irradiance = bake(
+        &square(),
+        &renderer,
+        &settings,
+        &options(BakeMode::Irradiance),
+    );
+    for pixel in &irradiance.pixels {
+        assert!(pixel.abs_diff_eq(DVec3::splat(PI), 1e-9), "{pixel}");
+    }
+}
+
+#[test]
+fn covered_texels_are_occluded_and_empty_ones_padded() {
+    // A roof over the half of the square with x < 0.5, just above it.
+    let [a, b, c, d] =
+        [(-1.0, -1.0), (0.5, -1.0), (0.5, 2.0), (-1.0, 2.0)].map(|(x, y)| DVec3::new(x, y, 0.05));
+    let roof = vec![
+        Triangle::new([a, c, b], grey()),
+        Triangle::new([a, d, c], grey()),
+    ];
+    let renderer = renderer(roof);
+    let settings = RenderSettings::default();
+    let occlusion = BakeMode::AmbientOcclusion { distance: None };
+
+    // Only the lower triangle, x > y in UV space, is baked.
+    let triangles = &square()[..1];
+    let ao = bake(triangles, &renderer, &settings, &options(occlusion));
+    let at = |x: u32, y: u32| ao.get(x, 15 - y).x;
+    assert!(at(2, 1) < 0.2, "{}", at(2, 1));
+    assert!(at(14, 1) > 0.5, "{}", at(14, 1));
+
+    // Texels a little past the diagonal are padding; the far corner is
+    // left empty.
+    let alpha = ao.alpha.as_ref().unwrap();
+    assert_eq!(alpha[(15 - 9) * 16 + 7], 1.0);
+    assert_eq!(alpha[0], 0.0);
+    assert_eq!(ao.get(0, 0), DVec3::ZERO);
+}
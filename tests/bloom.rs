@@ -0,0 +1,127 @@
+use glam::DVec3;
+use raytracer::bloom::{self, BloomSettings, GlareSettings};
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::material::Lambertian;
+use raytracer::objects::sphere::Sphere;
+use raytracer::renderer::{self, ImageBuffer, RenderSettings, Renderer};
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+// A dim 41 x 41 image with one pixel of 100 in the middle.
+fn highlight() -> ImageBuffer {
+    let mut image = ImageBuffer::new(41, 41);
+    image.pixels.fill(DVec3::splat(0.2));
+    image.set(20, 20, DVec3::splat(100.0));
+    image
+}
+
+fn total(image: &ImageBuffer) -> DVec3 {
+    image.pixels.iter().sum()
+}
+
+#[test]
+fn only_highlights_bloom_and_light_is_kept() {
+    let settings = BloomSettings {
+        threshold: 1.0,
+        intensity: 0.5,
+        radius: 0.05,
+        glare: None,
+    };
+    let mut dim = ImageBuffer::new(8, 8);
+    dim.pixels.fill(DVec3::new(0.9, 0.5, 0.1));
+    let before = dim.pixels.clone();
+    bloom::apply(&mut dim, &settings);
+    assert_eq!(dim.pixels, before);
+
+    let mut image = highlight();
+    let before = total(&image);
+    bloom::apply(&mut image, &settings);
+    assert!(total(&image).abs_diff_eq(before, 1e-9));
+    // Half the excess over the threshold leaves the highlight...
+    assert!((50.0..60.0).contains(&image.get(20, 20).x));
+    // ...and falls off with distance, evenly all round.
+    let near = image.get(21, 20);
+    assert!(near.x > 0.2 + 1.0);
+    assert!(near.abs_diff_eq(image.get(20, 19), 1e-12));
+    assert!(image.get(23, 20).x < near.x);
+    assert_eq!(image.get(0, 0), DVec3::splat(0.2));
+}
+
+#[test]
+fn glare_draws_spikes_along_the_blades() {
+    let settings = BloomSettings {
+        threshold: 1.0,
+        intensity: 0.0,
+        radius: 0.0,
+        glare: Some(GlareSettings {
+            spikes: 4,
+            length: 0.25,
+            intensity: 0.5,
+            rotation: 0.0,
+        }),
+    };
+    let mut image = highlight();
+    let before = total(&image);
+    bloom::apply(&mut image, &settings);
+    assert!(total(&image).abs_diff_eq(before, 1e-9));
+    for (x, y) in [(25, 20), (15, 20), (20, 25), (20, 15)] {
+        assert!(image.get(x, y).x > 0.5, "{:?}", (x, y));
+    }
+    assert!(image.get(22, 20).x > image.get(28, 20).x);
+    assert_eq!(image.get(23, 23), DVec3::splat(0.2));
+    // Spikes end at their length.
+    assert_eq!(image.get(32, 20), DVec3::splat(0.2));
+}
+
+#[test]
+fn scenes_set_bloom_in_render_settings() {
+    let scene = "
+camera: { lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }
+render: { width: 8, height: 8, bloom: { threshold: 2, glare: { spikes: 8 } } }
+objects: []
+";
+    let (config, _, _, _) = Scene::from_source_at(scene, SceneFormat::Yaml, 0.0).unwrap();
+    let bloom = config.render.bloom.unwrap();
+    assert_eq!(bloom.threshold, 2.0);
+    assert_eq!(bloom.radius, BloomSettings::default().radius);
+    assert_eq!(bloom.glare.unwrap().spikes, 8);
+}
+
+#[test]
+fn bloom_is_left_out_of_the_passes_until_the_image_is_finished() {
+    // A dark ball against a sky bright enough to bloom over it.
+    let material = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.1,
+    )))));
+    let world = Arc::new(Sphere::new(DVec3::ZERO, 1.0, material));
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::splat(10.0))));
+    let settings = RenderSettings {
+        width: 16,
+        height: 16,
+        samples_per_pixel: 2,
+        bloom: Some(BloomSettings {
+            threshold: 2.0,
+            ..BloomSettings::default()
+        }),
+        ..RenderSettings::default()
+    };
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        4.0,
+    );
+
+    // Denoisers and temporal accumulation get the beauty pass as rendered;
+    // `finish` adds the bloom afterwards, as `render` does.
+    let mut beauty = renderer.render_passes(&camera, &settings).beauty;
+    let finished = renderer.render(&camera, &settings);
+    assert_ne!(beauty.pixels, finished.pixels);
+    renderer::finish(&mut beauty, &settings);
+    assert_eq!(beauty.pixels, finished.pixels);
+}
@@ -0,0 +1,402 @@
+use glam::{DVec2, DVec3};
+use raytracer::camera::{
+    Camera, CameraProjection, LensEffects, PhysicalExposure, ShutterCurve, Stereo, StereoLayout,
+};
+use raytracer::hittable::AABB;
+use raytracer::renderer::Renderer;
+use raytracer::sampler::{IndependentSampler, Sampler};
+use raytracer::scene::{Scene, SceneFormat};
+
+fn camera(projection: CameraProjection) -> Camera {
+    Camera::new(
+        DVec3::new(1.0, 2.0, 5.0),
+        DVec3::new(0.0, 0.5, 0.0),
+        DVec3::Y,
+        40.0,
+        16.0 / 9.0,
+        0.0,
+        4.0,
+    )
+    .with_projection(projection)
+}
+
+// Projecting a point unprojected from NDC must land back on the same NDC
+// position for every projection model.
+#[test]
+fn project_inverts_unproject() {
+    let projections = [
+        CameraProjection::Perspective,
+        CameraProjection::Orthographic { height: 3.0 },
+        CameraProjection::Fisheye { fov: 180.0 },
+        CameraProjection::Equirectangular,
+    ];
+    for projection in projections {
+        let camera = camera(projection);
+        for ndc in [
+            DVec2::new(0.5, 0.5),
+            DVec2::new(0.1, 0.8),
+            DVec2::new(0.9, 0.2),
+        ] {
+            let point = camera.unproject(ndc.x, ndc.y, 7.0);
+            let projected = camera.project(point).expect("point should be visible");
+            assert!(
+                projected.abs_diff_eq(ndc, 1e-9),
+                "{projection:?}: {ndc} came back as {projected}"
+            );
+        }
+    }
+}
+
+#[test]
+fn ndc_origin_is_bottom_left() {
+    let camera = camera(CameraProjection::Perspective);
+    let center = camera.generate_ray(0.5, 0.5, DVec2::ZERO, 0.0).direction;
+    let corner = camera.generate_ray(0.0, 0.0, DVec2::ZERO, 0.0).direction;
+    let right = center.cross(DVec3::Y);
+    assert!(corner.dot(right) < 0.0, "px = 0 should be on the left");
+    assert!(
+        corner.normalize().y < center.normalize().y,
+        "py = 0 should be at the bottom"
+    );
+}
+
+#[test]
+fn points_behind_a_perspective_camera_are_not_projected() {
+    let camera = camera(CameraProjection::Perspective);
+    assert!(camera.project(DVec3::new(2.0, 3.5, 10.0)).is_none());
+}
+
+#[test]
+fn shutter_curves_cover_the_exposure_monotonically() {
+    let curves = [
+        ShutterCurve::Box,
+        ShutterCurve::Triangle,
+        ShutterCurve::Ramp {
+            open: 0.2,
+            close: 0.4,
+        },
+    ];
+    for curve in curves {
+        assert!(curve.sample(0.0).abs() < 1e-12, "{curve:?}");
+        assert!((curve.sample(1.0) - 1.0).abs() < 1e-12, "{curve:?}");
+        let mut previous = 0.0;
+        for i in 1..=100 {
+            let t = curve.sample(i as f64 / 100.0);
+            assert!(t >= previous, "{curve:?} is not monotonic");
+            previous = t;
+        }
+    }
+    // Symmetric curves put half the samples before the midpoint.
+    assert!((ShutterCurve::Triangle.sample(0.5) - 0.5).abs() < 1e-12);
+}
+
+#[test]
+fn looking_at_keeps_the_lens() {
+    let (lookfrom, lookat) = (DVec3::new(-3.0, 1.0, 2.0), DVec3::new(0.5, 0.0, -1.0));
+    let moved = camera(CameraProjection::Perspective).looking_at(lookfrom, lookat, DVec3::Y);
+    let focus_dist = (lookfrom - lookat).length();
+    let expected = Camera::new(
+        lookfrom,
+        lookat,
+        DVec3::Y,
+        40.0,
+        16.0 / 9.0,
+        0.0,
+        focus_dist,
+    );
+    assert!((moved.focus_distance() - focus_dist).abs() < 1e-12);
+    for ndc in [DVec2::ZERO, DVec2::new(0.3, 0.8), DVec2::ONE] {
+        let a = moved.generate_ray(ndc.x, ndc.y, DVec2::ZERO, 0.0);
+        let b = expected.generate_ray(ndc.x, ndc.y, DVec2::ZERO, 0.0);
+        assert!(a.origin.abs_diff_eq(b.origin, 1e-12));
+        assert!(a
+            .direction
+            .normalize()
+            .abs_diff_eq(b.direction.normalize(), 1e-12));
+    }
+}
+
+#[test]
+fn omni_stereo_eyes_share_directions_from_either_side() {
+    let camera = camera(CameraProjection::OmniStereo { ipd: 0.064 });
+    for (px, py) in [(0.5, 0.5), (0.1, 0.8), (0.9, 0.3), (0.3, 0.02)] {
+        let left = camera.generate_ray(px, 0.5 + 0.5 * py, DVec2::ZERO, 0.0);
+        let right = camera.generate_ray(px, 0.5 * py, DVec2::ZERO, 0.0);
+        assert!(left.direction.abs_diff_eq(right.direction, 1e-12));
+        let baseline = right.origin - left.origin;
+        assert!((baseline.length() - 0.064).abs() < 1e-12);
+        // The right eye is to the right of the view direction.
+        let side = left.direction.cross(DVec3::Y);
+        assert!(baseline.dot(side) > 0.0, "{px}, {py}");
+
+        let ndc = DVec2::new(px, 0.5 + 0.5 * py);
+        let point = camera.unproject(ndc.x, ndc.y, 7.0);
+        let projected = camera.project(point).expect("point should be visible");
+        assert!(
+            projected.abs_diff_eq(ndc, 1e-9),
+            "{ndc} came back as {projected}"
+        );
+    }
+}
+
+#[test]
+fn framed_bounds_fill_the_view() {
+    let bounds = AABB {
+        min: DVec3::new(10.0, -1.0, 3.0),
+        max: DVec3::new(14.0, 1.0, 4.0),
+    };
+    let corners = (0..8).map(|i| {
+        let pick = |bit: i32, min: f64, max: f64| if i & bit == 0 { min } else { max };
+        DVec3::new(
+            pick(1, bounds.min.x, bounds.max.x),
+            pick(2, bounds.min.y, bounds.max.y),
+            pick(4, bounds.min.z, bounds.max.z),
+        )
+    });
+    for projection in [
+        CameraProjection::Perspective,
+        CameraProjection::Orthographic { height: 1.0 },
+        CameraProjection::Fisheye { fov: 120.0 },
+    ] {
+        let before = camera(projection);
+        let framed = before.frame_bounds(&bounds, 0.1);
+        let center = framed.generate_ray(0.5, 0.5, DVec2::ZERO, 0.0);
+        let direction = center.direction.normalize();
+        let towards = (bounds.min + bounds.max) / 2.0 - center.origin;
+        assert!(direction.abs_diff_eq(towards.normalize(), 1e-9));
+        // Seen from the same side as before.
+        let forward = before.generate_ray(0.5, 0.5, DVec2::ZERO, 0.0).direction;
+        assert!(direction.abs_diff_eq(forward.normalize(), 1e-9));
+        for corner in corners.clone() {
+            let ndc = framed.project(corner).expect("corner should be in front");
+            assert!(
+                ndc.cmpgt(DVec2::ZERO).all() && ndc.cmplt(DVec2::ONE).all(),
+                "{projection:?}: {corner} at {ndc}"
+            );
+        }
+    }
+}
+
+#[test]
+fn shift_slides_the_image_and_tilt_turns_the_focal_plane() {
+    let level = Camera::new(DVec3::ZERO, DVec3::NEG_Z, DVec3::Y, 60.0, 1.0, 0.5, 4.0);
+    let shifted = level.clone().with_shift(DVec2::new(0.0, 0.25));
+    let a = shifted.generate_ray(0.5, 0.5, DVec2::ZERO, 0.0);
+    let b = level.generate_ray(0.5, 0.75, DVec2::ZERO, 0.0);
+    assert!(a.direction.abs_diff_eq(b.direction, 1e-12));
+    // Looking level, a vertical edge stays vertical in the image.
+    let top = shifted.project(DVec3::new(1.0, 3.0, -5.0)).unwrap();
+    let bottom = shifted.project(DVec3::new(1.0, 0.0, -5.0)).unwrap();
+    assert!((top.x - bottom.x).abs() < 1e-12);
+    let point = shifted.unproject(0.2, 0.9, 7.0);
+    assert!(shifted
+        .project(point)
+        .unwrap()
+        .abs_diff_eq(DVec2::new(0.2, 0.9), 1e-9));
+
+    // Rays from across the lens meet on the tilted plane, which still
+    // passes through the focus distance straight ahead.
+    let angle = 0.3f64;
+    let tilted = level.with_tilt(DVec2::new(0.0, angle));
+    let normal = DVec3::new(0.0, angle.tan(), 1.0);
+    for (px, py) in [(0.5, 0.5), (0.2, 0.9), (0.7, 0.1)] {
+        let focus = |lens: DVec2| {
+            let ray = tilted.generate_ray(px, py, lens, 0.0);
+            ray.origin + ray.direction
+        };
+        let point = focus(DVec2::new(0.9, 0.2));
+        assert!(point.abs_diff_eq(focus(DVec2::new(0.1, 0.7)), 1e-9));
+        assert!((point - DVec3::new(0.0, 0.0, -4.0)).dot(normal).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn stereo_eyes_converge_on_the_shared_window() {
+    let eye = DVec3::new(1.0, 2.0, 5.0);
+    let forward = (DVec3::new(0.0, 0.5, 0.0) - eye).normalize();
+    for layout in [StereoLayout::SideBySide, StereoLayout::TopBottom] {
+        let camera = camera(CameraProjection::Stereo(Stereo {
+            ipd: 0.064,
+            convergence: Some(3.0),
+            layout,
+        }));
+        // The same spot in each eye's part of the image.
+        let (left, right) = match layout {
+            StereoLayout::SideBySide => (DVec2::new(0.2, 0.6), DVec2::new(0.7, 0.6)),
+            StereoLayout::TopBottom => (DVec2::new(0.4, 0.8), DVec2::new(0.4, 0.3)),
+        };
+        let left_ray = camera.generate_ray(left.x, left.y, DVec2::ZERO, 0.0);
+        let right_ray = camera.generate_ray(right.x, right.y, DVec2::ZERO, 0.0);
+        let baseline = right_ray.origin - left_ray.origin;
+        assert!((baseline.length() - 0.064).abs() < 1e-12);
+        let side = forward.cross(DVec3::Y);
+        assert!(baseline.dot(side) > 0.0, "{layout:?}");
+
+        // Both rays pass through the same point of the window at the
+        // convergence distance.
+        let on_window = |origin: DVec3, direction: DVec3| {
+            let t = (3.0 - (origin - eye).dot(forward)) / direction.dot(forward);
+            origin + t * direction
+        };
+        assert!(
+            on_window(left_ray.origin, left_ray.direction)
+                .abs_diff_eq(on_window(right_ray.origin, right_ray.direction), 1e-9),
+            "{layout:?}"
+        );
+
+        let point = camera.unproject(left.x, left.y, 7.0);
+        let projected = camera.project(point).expect("point should be visible");
+        assert!(
+            projected.abs_diff_eq(left, 1e-9),
+            "{layout:?}: {left} came back as {projected}"
+        );
+    }
+}
+
+#[test]
+fn exposure_follows_the_f_stop_shutter_and_iso() {
+    let base = PhysicalExposure {
+        iso: 100.0,
+        f_stop: 1.0,
+        shutter_speed: 1.0,
+    };
+    assert!(base.ev100().abs() < 1e-12);
+    assert!((base.scale() - 1.0 / 1.2).abs() < 1e-12);
+    let sunny_16 = PhysicalExposure {
+        f_stop: 16.0,
+        shutter_speed: 1.0 / 100.0,
+        ..base
+    };
+    assert!((sunny_16.ev100() - 14.64).abs() < 0.01);
+
+    // A stop down quarters the light; double the ISO or the time doubles it.
+    let scale = |exposure: PhysicalExposure| exposure.scale() / base.scale();
+    assert!(
+        (scale(PhysicalExposure {
+            f_stop: 2.0,
+            ..base
+        }) - 0.25)
+            .abs()
+            < 1e-12
+    );
+    assert!((scale(PhysicalExposure { iso: 200.0, ..base }) - 2.0).abs() < 1e-12);
+    let long = PhysicalExposure {
+        shutter_speed: 2.0,
+        ..base
+    };
+    assert!((scale(long) - 2.0).abs() < 1e-12);
+
+    let camera = Camera::new(DVec3::ZERO, DVec3::NEG_Z, DVec3::Y, 40.0, 1.0, 0.0, 4.0);
+    assert_eq!(camera.film_scale(), 1.0);
+    let exposed = camera.clone().with_exposure(base);
+    assert!((exposed.film_scale() - base.scale()).abs() < 1e-12);
+    // A shutter that takes time to open lets less light in.
+    let ramped = exposed.with_shutter_curve(ShutterCurve::Ramp {
+        open: 0.25,
+        close: 0.25,
+    });
+    assert!((ramped.film_scale() - 0.75 * base.scale()).abs() < 1e-12);
+}
+
+// A 2 x 2 image of a uniform 10 nit sky through `exposure`, with its
+// camera.
+fn expose(exposure: &str) -> (DVec3, Camera) {
+    let scene = format!(
+        "
+camera: {{ lookfrom: [0, 0, 0], lookat: [0, 0, -1], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 4, exposure: {exposure} }}
+render: {{ width: 2, height: 2, samples_per_pixel: 4 }}
+background: {{ type: solid, color: [10, 10, 10] }}
+objects: []
+"
+    );
+    let (config, camera, world, lights) =
+        Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+    let image = Renderer::new(world, config.environment().unwrap())
+        .with_lights(lights)
+        .render(&camera, &config.render);
+    (image.pixels[0], camera)
+}
+
+#[test]
+fn scenes_stop_down_for_brightness_and_depth_of_field() {
+    let (open, wide) = expose("{ f_stop: 2, shutter_speed: 0.01 }");
+    let (stopped, narrow) = expose("{ f_stop: 4, shutter_speed: 0.01 }");
+    let expected = 10.0 / (1.2 * (4.0f64 / 0.01).log2().exp2());
+    assert!((open.x - expected).abs() < 1e-9, "{open}");
+    assert!((stopped.x - 0.25 * expected).abs() < 1e-9, "{stopped}");
+
+    // Coupled, the lens opens to the focal length of a 24 mm tall film
+    // over the f-number.
+    let focal_length = 0.012 / 20f64.to_radians().tan();
+    let lens_radius = |camera: &Camera| {
+        let centre = camera.generate_ray(0.5, 0.5, DVec2::ZERO, 0.0);
+        let edge = camera.generate_ray(0.5, 0.5, DVec2::new(1.0, 0.0), 0.0);
+        centre.origin.distance(edge.origin)
+    };
+    assert!((lens_radius(&wide) - focal_length / 4.0).abs() < 1e-12);
+    assert!((lens_radius(&narrow) - focal_length / 8.0).abs() < 1e-12);
+
+    // Decoupled, `aperture` keeps the pinhole and only the light changes.
+    let (decoupled, pinhole) = expose("{ f_stop: 2, shutter_speed: 0.01, couple_aperture: false }");
+    assert!((decoupled.x - expected).abs() < 1e-9);
+    assert_eq!(lens_radius(&pinhole), 0.0);
+}
+
+#[test]
+fn lens_effects_bend_fringe_and_darken_the_image() {
+    let ideal = Camera::new(DVec3::ZERO, DVec3::NEG_Z, DVec3::Y, 60.0, 1.5, 0.0, 4.0);
+    let lens = |effects: LensEffects| ideal.clone().with_lens_effects(effects);
+
+    // Barrel distortion pulls the corners in from further out; the centre
+    // stays put.
+    let barrel = lens(LensEffects {
+        distortion: -0.2,
+        ..Default::default()
+    });
+    assert!(barrel
+        .distort(0.5, 0.5, None)
+        .abs_diff_eq(DVec2::splat(0.5), 1e-12));
+    let corner = barrel.distort(1.0, 1.0, None);
+    assert!(corner.abs_diff_eq(DVec2::splat(0.5 + 0.5 / 0.8), 1e-12));
+    let pincushion = lens(LensEffects {
+        distortion: 0.2,
+        ..Default::default()
+    });
+    assert!(pincushion.distort(1.0, 0.5, None).x < 1.0);
+
+    // Red is magnified and blue shrunk, so red at a corner comes from
+    // nearer the centre.
+    let fringed = lens(LensEffects {
+        chromatic_aberration: 0.01,
+        ..Default::default()
+    });
+    let [red, green, blue] = [0, 1, 2].map(|c| fringed.distort(0.9, 0.2, Some(c)));
+    assert!(green.abs_diff_eq(DVec2::new(0.9, 0.2), 1e-12));
+    assert!(red.distance(DVec2::splat(0.5)) < green.distance(DVec2::splat(0.5)));
+    assert!(blue.distance(DVec2::splat(0.5)) > green.distance(DVec2::splat(0.5)));
+
+    // Each ray carries one channel, weighted to keep the average white.
+    let mut sampler = IndependentSampler::new(7);
+    let mut total = DVec3::ZERO;
+    for i in 0..3000 {
+        sampler.start_pixel(0, 0, i);
+        let (_, weight) = fringed.get_ray(0.5, 0.5, &mut sampler);
+        assert_eq!(weight.element_sum(), 3.0);
+        total += weight;
+    }
+    assert!((total / 3000.0).abs_diff_eq(DVec3::ONE, 0.1), "{total}");
+
+    // Vignetting follows cos⁴ off the line of sight; the ideal lens gives
+    // every ray full weight.
+    let vignetted = lens(LensEffects {
+        vignetting: 1.0,
+        ..Default::default()
+    });
+    let (centre, weight) = vignetted.get_ray(0.5, 0.5, &mut sampler);
+    assert!(weight.abs_diff_eq(DVec3::ONE, 1e-12));
+    let (edge, weight) = vignetted.get_ray(1.0, 1.0, &mut sampler);
+    let cosine = edge.direction.normalize().dot(centre.direction.normalize());
+    assert!(weight.abs_diff_eq(DVec3::splat(cosine.powi(4)), 1e-12));
+    assert!(cosine < 0.9);
+    assert_eq!(ideal.get_ray(1.0, 1.0, &mut sampler).1, DVec3::ONE);
+}
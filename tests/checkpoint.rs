@@ -0,0 +1,111 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::checkpoint::RenderCheckpoint;
+use raytracer::environment::SolidBackground;
+use raytracer::material::Lambertian;
+use raytracer::objects::sphere::Sphere;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn renderer(albedo: f64) -> Renderer {
+    let material = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        albedo,
+    )))));
+    let world = Arc::new(Sphere::new(DVec3::ZERO, 1.0, material));
+    Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)))
+}
+
+#[test]
+fn checkpointed_tiles_are_not_rendered_again() {
+    let settings = RenderSettings {
+        width: 24,
+        height: 16,
+        samples_per_pixel: 2,
+        tile_size: 8,
+        ..RenderSettings::default()
+    };
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        settings.aspect_ratio(),
+        0.0,
+        4.0,
+    );
+    let scene = b"sphere";
+    let checkpoint = Arc::new(RenderCheckpoint::new(scene, &[], &settings));
+    let expected = renderer(0.5)
+        .with_checkpoint(checkpoint.clone())
+        .render(&camera, &settings);
+    assert!(checkpoint.tiles_done() > 1);
+    assert_eq!(checkpoint.passes(&settings).beauty.pixels, expected.pixels);
+
+    let path = std::env::temp_dir().join("raytracer-checkpoint.json");
+    checkpoint.save(&path).unwrap();
+    // A darker sphere shows which pixels were rendered again.
+    let resumed = Arc::new(RenderCheckpoint::load(&path, scene, &[], &settings).unwrap());
+    let image = renderer(0.1)
+        .with_checkpoint(resumed)
+        .render(&camera, &settings);
+    // Through JSON, which may round the last bit.
+    for (a, b) in image.pixels.iter().zip(&expected.pixels) {
+        assert!(a.abs_diff_eq(*b, 1e-12), "{a} != {b}");
+    }
+
+    // Not for this render.
+    let other = RenderSettings {
+        seed: 1,
+        ..settings
+    };
+    let stale = RenderCheckpoint::load(&path, scene, &[], &other).unwrap();
+    assert_eq!(stale.tiles_done(), 0);
+    let missing = std::env::temp_dir().join("raytracer-no-checkpoint.json");
+    let _ = std::fs::remove_file(&missing);
+    let fresh = RenderCheckpoint::load(&missing, scene, &[], &settings).unwrap();
+    assert_eq!(fresh.tiles_done(), 0);
+}
+
+// A mesh or texture edited since the checkpoint was saved changes what its
+// tiles would show, though the scene file is the same.
+#[test]
+fn edited_assets_make_checkpoints_stale() {
+    let settings = RenderSettings {
+        width: 8,
+        height: 8,
+        samples_per_pixel: 1,
+        ..RenderSettings::default()
+    };
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        settings.aspect_ratio(),
+        0.0,
+        4.0,
+    );
+    let dir = std::env::temp_dir();
+    let asset = dir.join("raytracer-checkpoint-asset.obj");
+    std::fs::write(&asset, "v 0 0 0\n").unwrap();
+    let assets = [asset.to_str().unwrap().to_string()];
+    let scene = b"mesh";
+    let checkpoint = Arc::new(RenderCheckpoint::new(scene, &assets, &settings));
+    renderer(0.5)
+        .with_checkpoint(checkpoint.clone())
+        .render(&camera, &settings);
+    let path = dir.join("raytracer-checkpoint-assets.json");
+    checkpoint.save(&path).unwrap();
+    let done = checkpoint.tiles_done();
+    assert!(done > 0);
+
+    let same = RenderCheckpoint::load(&path, scene, &assets, &settings).unwrap();
+    assert_eq!(same.tiles_done(), done);
+    std::fs::write(&asset, "v 1 0 0\n").unwrap();
+    let edited = RenderCheckpoint::load(&path, scene, &assets, &settings).unwrap();
+    assert_eq!(edited.tiles_done(), 0);
+    std::fs::remove_file(&asset).unwrap();
+    let missing = RenderCheckpoint::load(&path, scene, &assets, &settings).unwrap();
+    assert_eq!(missing.tiles_done(), 0);
+}
@@ -0,0 +1,34 @@
+// Fixtures shared by the integration tests. Each test crate uses only some
+// of them.
+#![allow(dead_code)]
+
+use glam::DVec3;
+use raytracer::ray::Ray;
+
+// `count` rays from a ring of viewpoints `radius` from the y axis, bobbing
+// `swing` above and below `height`, each towards `target` of its index, so
+// that objects near the centre are seen from every side.
+pub fn ring_of_rays(
+    count: usize,
+    radius: f64,
+    height: f64,
+    swing: f64,
+    target: impl Fn(f64) -> DVec3,
+) -> impl Iterator<Item = Ray> {
+    (0..count).map(move |i| {
+        let i = i as f64;
+        let angle = i * 0.37;
+        let origin = DVec3::new(
+            radius * angle.cos(),
+            height + swing * (i * 0.61).sin(),
+            radius * angle.sin(),
+        );
+        Ray::new(origin, target(i) - origin)
+    })
+}
+
+// Points jittered up to `spread` from the centre in x and y, as targets for
+// `ring_of_rays`.
+pub fn near_centre(spread: f64) -> impl Fn(f64) -> DVec3 {
+    move |i| spread * DVec3::new(i.sin(), (i * 1.3).cos(), 0.0)
+}
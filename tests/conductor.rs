@@ -0,0 +1,93 @@
+use glam::DVec3;
+use raytracer::material::{fresnel_conductor, ComplexIor};
+use raytracer::scene::{Scene, SceneFormat, SceneValidationError};
+
+#[test]
+fn conductor_fresnel_matches_its_limits() {
+    // Head on, ((n - 1)² + k²) / ((n + 1)² + k²).
+    let (eta, k) = (0.2, 3.9);
+    let head_on = fresnel_conductor(1.0, eta, k);
+    let expected = ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+    assert!((head_on - expected).abs() < 1e-12, "{head_on}");
+    assert!((fresnel_conductor(0.0, eta, k) - 1.0).abs() < 1e-12);
+    // Without absorption, the Fresnel reflectance of glass.
+    assert!((fresnel_conductor(1.0, 1.5, 0.0) - 0.04).abs() < 1e-12);
+    let oblique = fresnel_conductor(0.5, 1.5, 0.0);
+    assert!((oblique - 0.0891867).abs() < 1e-6, "{oblique}");
+}
+
+#[test]
+fn measured_metals_keep_their_edge_tints() {
+    let gold = ComplexIor::GOLD.reflectance(1.0, None);
+    assert!(gold.x > 0.9 && gold.x > gold.y && gold.y > gold.z, "{gold}");
+    // Towards grazing, the blue that gold absorbs head on comes back.
+    let grazing = ComplexIor::GOLD.reflectance(0.1, None);
+    assert!(grazing.z > gold.z + 0.2, "{grazing}");
+    assert!(grazing.max_element() - grazing.min_element() < gold.x - gold.z);
+
+    let silver = ComplexIor::SILVER.reflectance(1.0, None);
+    assert!(silver.min_element() > 0.9, "{silver}");
+    let copper = ComplexIor::COPPER.reflectance(1.0, None);
+    assert!(copper.x > copper.y && copper.y > copper.z, "{copper}");
+
+    // Aluminium dips before it climbs to 1 at grazing angles.
+    let aluminium = |cosine: f64| ComplexIor::ALUMINIUM.reflectance(cosine, None).x;
+    assert!(aluminium(0.15) < aluminium(1.0) - 0.02);
+    assert!(aluminium(0.01) > aluminium(1.0));
+
+    // Spectral rays at a channel's wavelength see that channel.
+    let red = ComplexIor::GOLD.reflectance(1.0, Some(650.0));
+    assert!(red.abs_diff_eq(DVec3::splat(gold.x), 1e-12), "{red}");
+}
+
+const YAML: &str = "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material: { type: conductor, METAL, roughness: 0.2 }
+";
+
+#[test]
+fn scenes_pick_conductors_by_name() {
+    for metal in [
+        "metal: gold",
+        "metal: aluminum",
+        "eta: [0.2, 0.9, 1.1], k: [3.9, 2.5, 2.1]",
+    ] {
+        let source = YAML.replace("METAL", metal);
+        assert!(
+            Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).is_ok(),
+            "{metal}"
+        );
+    }
+
+    for (metal, problem) in [
+        (
+            "metal: gold, eta: [0.2, 0.9, 1.1], k: [3.9, 2.5, 2.1]",
+            "objects[0].material: give either metal, or both eta and k",
+        ),
+        (
+            "eta: [0.2, 0.9, 1.1]",
+            "objects[0].material: give either metal, or both eta and k",
+        ),
+        (
+            "eta: [0.2, 0.9, 1.1], k: [3.9, -2.5, 2.1]",
+            "objects[0].material.k: must not be negative",
+        ),
+    ] {
+        let source = YAML.replace("METAL", metal);
+        let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+            .err()
+            .unwrap();
+        let error = error.downcast_ref::<SceneValidationError>().unwrap();
+        assert_eq!(error.problems, [problem]);
+    }
+}
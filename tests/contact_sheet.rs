@@ -0,0 +1,100 @@
+use glam::DVec3;
+use raytracer::contact_sheet::{
+    render_contact_sheet, render_sweep, ContactSheetOptions, SweepAxis,
+};
+use serde_json::json;
+use std::path::PathBuf;
+
+const SCENE: &str = "
+render: { width: 200, height: 100 }
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: lambertian
+      texture: { type: solid_color, color: [0.5, 0.5, 0.5] }
+";
+
+#[test]
+fn sheets_show_every_scene_that_loads() {
+    let dir = std::env::temp_dir().join("raytracer-contact-sheet");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("b.yaml"), SCENE).unwrap();
+    std::fs::write(dir.join("a.yml"), SCENE.replace("200", "50")).unwrap();
+    std::fs::write(dir.join("broken.json"), "{").unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a scene").unwrap();
+
+    let options = ContactSheetOptions {
+        cell_size: 20,
+        samples_per_pixel: 1,
+        columns: 4,
+        gap: 2,
+        ..ContactSheetOptions::default()
+    };
+    let mut skipped = Vec::new();
+    let sheet =
+        render_contact_sheet(&dir, &options, |path, _| skipped.push(path.to_path_buf())).unwrap();
+
+    let names = |paths: &[PathBuf]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+    assert_eq!(names(&sheet.scenes), ["a.yml", "b.yaml"]);
+    assert_eq!(names(&skipped), ["broken.json"]);
+    // Two cells side by side, with gaps around them.
+    assert_eq!((sheet.image.width, sheet.image.height), (46, 24));
+    // The wide scene is letterboxed in its cell; the tall one pillarboxed.
+    let background = sheet.image.get(24, 2);
+    assert_ne!(sheet.image.get(24, 12), background);
+    assert_eq!(sheet.image.get(2, 12), background);
+    assert_ne!(sheet.image.get(12, 12), background);
+}
+
+#[test]
+fn sweeps_vary_fields_across_and_down_the_sheet() {
+    let path = std::env::temp_dir().join("raytracer-sweep.yaml");
+    std::fs::write(&path, SCENE).unwrap();
+    let path = path.to_str().unwrap();
+    let colors = SweepAxis {
+        field: "objects.0.material.texture.color".to_string(),
+        values: vec![json!([0.9, 0.1, 0.1]), json!([0.1, 0.1, 0.9])],
+    };
+    let radii = SweepAxis {
+        field: "objects.0.radius".to_string(),
+        values: vec![json!(1.0), json!(0.2)],
+    };
+    let options = ContactSheetOptions {
+        cell_size: 40,
+        samples_per_pixel: 4,
+        gap: 2,
+        ..ContactSheetOptions::default()
+    };
+    let image = render_sweep(path, &colors, Some(&radii), &options).unwrap();
+    assert_eq!((image.width, image.height), (86, 86));
+
+    // The middle of each cell, where the sphere is.
+    let centre = |column: u32, row: u32| image.get(22 + 42 * column, 22 + 42 * row);
+    let (red, blue) = (centre(0, 0), centre(1, 0));
+    assert!(red.x > red.z && blue.z > blue.x, "{red} vs {blue}");
+    // The small spheres leave a gap beside the middle.
+    assert_ne!(image.get(30, 22), image.get(30, 64));
+    // Each cell is labelled along its bottom.
+    assert!((12..20).any(|y| (2..40).any(|x| image.get(x, 2 + 10 + y) == DVec3::ONE)));
+
+    let missing = SweepAxis {
+        field: "objects.0.roughness".to_string(),
+        values: vec![json!(0.5)],
+    };
+    assert!(render_sweep(path, &missing, None, &options).is_err());
+}
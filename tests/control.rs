@@ -0,0 +1,190 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::control::{CancellationToken, RenderControl};
+use raytracer::environment::SolidBackground;
+use raytracer::material::Lambertian;
+use raytracer::metrics::RenderProgress;
+use raytracer::objects::sphere::Sphere;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn renderer() -> Renderer {
+    let material = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.5,
+    )))));
+    let world = Arc::new(Sphere::new(DVec3::ZERO, 1.0, material));
+    Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)))
+}
+
+#[test]
+fn paused_renders_wait_and_resume_where_they_left_off() {
+    let settings = RenderSettings {
+        width: 32,
+        height: 32,
+        samples_per_pixel: 4,
+        tile_size: 8,
+        ..RenderSettings::default()
+    };
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        settings.aspect_ratio(),
+        0.0,
+        4.0,
+    );
+    let expected = renderer().render(&camera, &settings);
+
+    let control = Arc::new(RenderControl::new());
+    let progress = Arc::new(RenderProgress::new());
+    let paused = renderer()
+        .with_control(control.clone())
+        .with_progress(progress.clone());
+    control.pause();
+    let image = std::thread::scope(|scope| {
+        let render = scope.spawn(|| paused.render(&camera, &settings));
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(control.is_paused());
+        assert_eq!(progress.snapshot().pixels_done, 0);
+        assert!(!render.is_finished());
+        // The render threads are free for other work meanwhile.
+        let other = scope.spawn(|| {
+            use rayon::prelude::*;
+            (0..1000u64).into_par_iter().sum::<u64>()
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(other.is_finished());
+        assert_eq!(other.join().unwrap(), 499500);
+        control.resume();
+        render.join().unwrap()
+    });
+    assert_eq!(image.pixels, expected.pixels);
+}
+
+#[test]
+fn cancelled_renders_return_the_tiles_finished() {
+    let settings = RenderSettings {
+        width: 32,
+        height: 32,
+        samples_per_pixel: 4,
+        tile_size: 8,
+        ..RenderSettings::default()
+    };
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        settings.aspect_ratio(),
+        0.0,
+        4.0,
+    );
+    let expected = renderer().render(&camera, &settings);
+
+    // Cancelling a paused render wakes it, without starting another tile.
+    let control = Arc::new(RenderControl::new());
+    let cancellation = CancellationToken::new();
+    let progress = Arc::new(RenderProgress::new());
+    let cancelled = renderer()
+        .with_control(control.clone())
+        .with_cancellation(cancellation.clone())
+        .with_progress(progress.clone());
+    control.pause();
+    let image = std::thread::scope(|scope| {
+        let render = scope.spawn(|| cancelled.render(&camera, &settings));
+        std::thread::sleep(Duration::from_millis(100));
+        cancellation.cancel();
+        render.join().unwrap()
+    });
+    assert!(cancellation.is_cancelled());
+    assert_eq!(progress.snapshot().tiles_done, 0);
+    assert!(image.pixels.iter().all(|&p| p == DVec3::ZERO));
+
+    // Tiles that finish before the cancellation are kept as they are. On
+    // one thread, no tile starts alongside the one that cancels.
+    let cancellation = CancellationToken::new();
+    let progress = Arc::new(RenderProgress::new());
+    let stop = cancellation.clone();
+    progress.on_update(move |snapshot| {
+        if snapshot.tiles_done == 3 {
+            stop.cancel();
+        }
+    });
+    let renderer = renderer()
+        .with_cancellation(cancellation)
+        .with_progress(progress.clone());
+    let image = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(|| renderer.render(&camera, &settings));
+    let snapshot = progress.snapshot();
+    assert_eq!((snapshot.tiles_done, snapshot.total_tiles), (3, 16));
+    assert_eq!(snapshot.eta_seconds, Some(0.0));
+    let (mut kept, mut missing) = (0, 0);
+    for (pixel, expected) in image.pixels.iter().zip(&expected.pixels) {
+        if pixel == expected {
+            kept += 1;
+        } else {
+            assert_eq!(*pixel, DVec3::ZERO);
+            missing += 1;
+        }
+    }
+    assert_eq!((kept, missing), (3 * 64, 13 * 64));
+}
+
+#[test]
+fn tiles_nearest_the_focus_start_first() {
+    let settings = RenderSettings {
+        width: 32,
+        height: 32,
+        samples_per_pixel: 4,
+        tile_size: 8,
+        ..RenderSettings::default()
+    };
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        settings.aspect_ratio(),
+        0.0,
+        4.0,
+    );
+    let expected = renderer().render(&camera, &settings);
+
+    // On one thread, stopping after the first tile leaves just the tile
+    // around the focus.
+    let control = Arc::new(RenderControl::new());
+    control.set_focus(Some((30, 5)));
+    let cancellation = CancellationToken::new();
+    let progress = Arc::new(RenderProgress::new());
+    let stop = cancellation.clone();
+    progress.on_update(move |snapshot| {
+        if snapshot.tiles_done == 1 {
+            stop.cancel();
+        }
+    });
+    let renderer = renderer()
+        .with_control(control)
+        .with_cancellation(cancellation)
+        .with_progress(progress);
+    let image = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(|| renderer.render(&camera, &settings));
+    for y in 0..32 {
+        for x in 0..32 {
+            let i = (y * 32 + x) as usize;
+            if (24..32).contains(&x) && y < 8 {
+                assert_eq!(image.pixels[i], expected.pixels[i]);
+            } else {
+                assert_eq!(image.pixels[i], DVec3::ZERO);
+            }
+        }
+    }
+}
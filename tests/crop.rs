@@ -0,0 +1,41 @@
+use glam::DVec3;
+use raytracer::reference_scenes;
+use raytracer::renderer::{CropWindow, RenderSettings};
+
+#[test]
+fn crop_window_renders_its_pixels_as_in_the_full_frame() {
+    let scene = reference_scenes::cornell_box();
+    let full = RenderSettings {
+        width: 24,
+        height: 24,
+        samples_per_pixel: 4,
+        tile_size: 8,
+        ..RenderSettings::default()
+    };
+    let crop = CropWindow {
+        x: 5,
+        y: 10,
+        width: 9,
+        height: 30,
+    };
+    let cropped = RenderSettings {
+        crop: Some(crop),
+        ..full
+    };
+    assert_eq!(cropped.render_window(), (5, 10, 14, 24));
+
+    let renderer = scene.renderer();
+    let expected = renderer.render(&scene.camera, &full);
+    let image = renderer.render(&scene.camera, &cropped);
+    for y in 0..24 {
+        for x in 0..24 {
+            let inside = (5..14).contains(&x) && y >= 10;
+            let want = if inside {
+                expected.get(x, y)
+            } else {
+                DVec3::ZERO
+            };
+            assert_eq!(image.get(x, y), want, "({x}, {y})");
+        }
+    }
+}
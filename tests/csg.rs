@@ -0,0 +1,105 @@
+use glam::DVec3;
+use raytracer::hittable::Hittable;
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::csg::Csg;
+use raytracer::objects::cylinder::Cylinder;
+use raytracer::objects::sphere::Sphere;
+use raytracer::ray::Ray;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))))
+}
+
+// Two unit spheres overlapping between x = -0.5 and 0.5.
+fn lens() -> Csg {
+    Csg::intersection(
+        Arc::new(Sphere::new(DVec3::new(-0.5, 0.0, 0.0), 1.0, material())),
+        Arc::new(Sphere::new(DVec3::new(0.5, 0.0, 0.0), 1.0, material())),
+    )
+}
+
+// A tube of radius 1 around the Y axis with a hole of radius 0.5.
+fn pipe() -> Csg {
+    Csg::difference(
+        Arc::new(Cylinder::new(-DVec3::Y, DVec3::Y, 1.0, material())),
+        Arc::new(Cylinder::new(
+            -2.0 * DVec3::Y,
+            2.0 * DVec3::Y,
+            0.5,
+            material(),
+        )),
+    )
+}
+
+#[test]
+fn intersection_keeps_only_the_overlap() {
+    let lens = lens();
+    let rec = lens
+        .hit(
+            &Ray::new(DVec3::new(-5.0, 0.0, 0.0), DVec3::X),
+            Interval::after(0.001),
+        )
+        .expect("ray hits the lens");
+    assert!((rec.t - 4.5).abs() < 1e-9, "t = {}", rec.t);
+    assert!(rec.front_face);
+    assert!(rec.normal.abs_diff_eq(-DVec3::X, 1e-9));
+
+    // From inside, the first crossing is where the lens ends.
+    let rec = lens
+        .hit(&Ray::new(DVec3::ZERO, DVec3::X), Interval::after(0.001))
+        .expect("ray leaves the lens");
+    assert!((rec.t - 0.5).abs() < 1e-9, "t = {}", rec.t);
+    assert!(!rec.front_face);
+
+    // Misses the overlap while passing through one sphere.
+    let ray = Ray::new(DVec3::new(-1.0, 5.0, 0.0), -DVec3::Y);
+    assert!(lens.hit(&ray, Interval::after(0.001)).is_none());
+}
+
+#[test]
+fn difference_carves_out_the_hole() {
+    let pipe = pipe();
+    // Straight down the hole.
+    assert!(pipe
+        .hit(&Ray::new(5.0 * DVec3::Y, -DVec3::Y), Interval::after(0.001))
+        .is_none());
+
+    // Onto the annular top face.
+    let rec = pipe
+        .hit(
+            &Ray::new(DVec3::new(0.75, 5.0, 0.0), -DVec3::Y),
+            Interval::after(0.001),
+        )
+        .expect("ray hits the rim");
+    assert!((rec.t - 4.0).abs() < 1e-9, "t = {}", rec.t);
+
+    // Across the wall: in at x = -1, out into the hole at x = -0.5.
+    let ray = Ray::new(DVec3::new(-5.0, 0.0, 0.0), DVec3::X);
+    let rec = pipe
+        .hit(&ray, Interval::after(0.001))
+        .expect("ray hits the wall");
+    assert!((rec.t - 4.0).abs() < 1e-9 && rec.front_face);
+    let rec = pipe
+        .hit(&ray, Interval::after(4.1))
+        .expect("ray leaves the wall");
+    assert!((rec.t - 4.5).abs() < 1e-9, "t = {}", rec.t);
+    assert!(!rec.front_face);
+    assert!(rec.normal.abs_diff_eq(-DVec3::X, 1e-9));
+}
+
+#[test]
+fn bounds_follow_the_operation() {
+    let lens = lens().bounding_box().expect("bounded");
+    assert!(lens.min.abs_diff_eq(DVec3::new(-0.5, -1.0, -1.0), 1e-9));
+    assert!(lens.max.abs_diff_eq(DVec3::new(0.5, 1.0, 1.0), 1e-9));
+
+    let sphere = Arc::new(Sphere::new(DVec3::new(3.0, 0.0, 0.0), 1.0, material()));
+    let union = Csg::union(Arc::new(pipe()), sphere)
+        .bounding_box()
+        .expect("bounded");
+    assert!(union.min.abs_diff_eq(DVec3::new(-1.0, -1.0, -1.0), 1e-9));
+    assert!(union.max.abs_diff_eq(DVec3::new(4.0, 1.0, 1.0), 1e-9));
+}
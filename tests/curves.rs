@@ -0,0 +1,126 @@
+use glam::DVec3;
+use raytracer::hittable::Hittable;
+use raytracer::interval::Interval;
+use raytracer::material::{Hair, Lambertian, Material};
+use raytracer::objects::curve::{read_hair, Curve, CurveShape};
+use raytracer::ray::Ray;
+use raytracer::sampler::{IndependentSampler, Sampler};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn white() -> Arc<SolidColor> {
+    Arc::new(SolidColor::new(DVec3::ONE))
+}
+
+// Along x from -1 to 1, 0.2 wide.
+fn straight(shape: CurveShape, material: Arc<dyn Material>) -> Curve {
+    let control_points = [-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0].map(|x| DVec3::new(x, 0.0, 0.0));
+    Curve::new(control_points, [0.2, 0.2], shape, material)
+}
+
+#[test]
+fn curves_are_hit_within_their_width() {
+    let curve = straight(CurveShape::Round, Arc::new(Lambertian::new(white())));
+    let down = |x: f64, y: f64| Ray::new(DVec3::new(x, y, 5.0), -DVec3::Z);
+    let interval = Interval::after(1e-3);
+
+    let rec = curve.hit(&down(0.5, 0.0), interval).unwrap();
+    assert!((rec.t - 5.0).abs() < 1e-6, "{}", rec.t);
+    assert!((rec.u - 0.75).abs() < 1e-3, "{}", rec.u);
+    assert!((rec.v - 0.5).abs() < 1e-6, "{}", rec.v);
+    assert!(rec.tangent.abs_diff_eq(DVec3::X, 1e-9));
+    assert!(rec.normal.abs_diff_eq(DVec3::Z, 1e-9));
+
+    // Round curves turn their normals towards the edges.
+    let rec = curve.hit(&down(0.0, 0.08), interval).unwrap();
+    assert!(rec.normal.y > 0.5, "{}", rec.normal);
+    assert!(curve.hit(&down(0.0, 0.11), interval).is_none());
+    assert!(curve.hit(&down(1.2, 0.0), interval).is_none());
+    let short = Interval::new(1e-3, 4.0);
+    assert!(curve.hit(&down(0.0, 0.0), short).is_none());
+}
+
+#[test]
+fn bent_curves_are_hit_along_their_length() {
+    let material = Arc::new(Lambertian::new(white()));
+    let control_points = [
+        DVec3::new(0.0, 0.0, 0.0),
+        DVec3::new(0.0, 1.0, 0.0),
+        DVec3::new(1.0, 2.0, 0.0),
+        DVec3::new(2.0, 2.0, 0.0),
+    ];
+    let curve = Curve::new(control_points, [0.05, 0.01], CurveShape::Flat, material);
+    for i in 0..=10 {
+        let u = i as f64 / 10.0;
+        let point = (1.0 - u).powi(3) * control_points[0]
+            + 3.0 * (1.0 - u).powi(2) * u * control_points[1]
+            + 3.0 * (1.0 - u) * u * u * control_points[2]
+            + u.powi(3) * control_points[3];
+        let ray = Ray::new(point + DVec3::new(0.0, 0.0, 3.0), -DVec3::Z);
+        let rec = curve.hit(&ray, Interval::after(1e-3)).unwrap();
+        assert!((rec.u - u).abs() < 0.02, "{} at {u}", rec.u);
+        assert!(rec.point.distance(point) < 0.03, "{} at {u}", rec.point);
+    }
+}
+
+// Each lobe takes its share of the light away from the fibre, and white
+// hair absorbs none of it, so the scattered weights average to what the
+// three lobes carry: almost everything.
+#[test]
+fn white_hair_scatters_nearly_all_light() {
+    let curve = straight(CurveShape::Round, Arc::new(Hair::new(white())));
+    let mut sampler = IndependentSampler::new(5);
+    let mut total = DVec3::ZERO;
+    let samples = 20_000;
+    for i in 0..samples {
+        sampler.start_pixel(0, 0, i);
+        let origin = DVec3::new(0.3, 0.1 * sampler.next_1d() - 0.05, 2.0);
+        let ray = Ray::new(origin, DVec3::new(-0.2, 0.0, -1.0));
+        let rec = curve.hit(&ray, Interval::after(1e-3)).unwrap();
+        if let Some((_, attenuation)) = rec.material.scatter(&ray, &rec, &mut sampler) {
+            total += attenuation;
+        }
+    }
+    let mean = total / samples as f64;
+    assert!((0.93..1.02).contains(&mean.x), "{mean}");
+}
+
+#[test]
+fn hair_files_load_as_strands() {
+    let mut bytes = b"HAIR".to_vec();
+    // Two strands, five points, with segment and thickness arrays.
+    for value in [2u32, 5, 1 | 2 | 4, 0] {
+        bytes.extend(value.to_le_bytes());
+    }
+    for value in [0.1f32, 1.0, 1.0, 1.0, 1.0] {
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.resize(128, 0);
+    for segments in [2u16, 1] {
+        bytes.extend(segments.to_le_bytes());
+    }
+    for i in 0..5 {
+        for coordinate in [i as f32, 0.0, 0.0] {
+            bytes.extend(coordinate.to_le_bytes());
+        }
+    }
+    for thickness in [0.3f32, 0.2, 0.1, 0.05, 0.05] {
+        bytes.extend(thickness.to_le_bytes());
+    }
+    let path = std::env::temp_dir().join("raytracer-strands.hair");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let strands = read_hair(&path).unwrap();
+    assert_eq!(strands.len(), 2);
+    assert_eq!(strands[0].points.len(), 3);
+    assert_eq!(strands[1].points[0], DVec3::new(3.0, 0.0, 0.0));
+    assert!((strands[0].widths[1] - 0.2).abs() < 1e-6);
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(white()));
+    assert_eq!(strands[0].curves(CurveShape::Flat, material).len(), 2);
+
+    // Strands that need more points than the file has.
+    std::fs::write(&path, &bytes[..bytes.len() - 30]).unwrap();
+    let error = read_hair(&path).err().unwrap().to_string();
+    assert!(error.contains("raytracer-strands.hair"), "{error}");
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,85 @@
+use glam::DVec3;
+use raytracer::color::{cie_xyz, wavelength_weight, WAVELENGTH_MAX, WAVELENGTH_MIN};
+use raytracer::hittable::Hittable;
+use raytracer::interval::Interval;
+use raytracer::material::{Dielectric, Dispersion, Material, LAMBDA_D};
+use raytracer::objects::sphere::Sphere;
+use raytracer::ray::Ray;
+use raytracer::sampler::IndependentSampler;
+use std::sync::Arc;
+
+#[test]
+fn glass_indices_follow_their_coefficients() {
+    // Schott N-BK7: n_d = 1.5168, Abbe number 64.17.
+    let bk7 = Dielectric {
+        dispersion: Some(Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }),
+        ..Dielectric::new(1.0)
+    };
+    assert!((bk7.index_at(LAMBDA_D) - 1.5168).abs() < 1e-4);
+    let abbe = (bk7.index_at(LAMBDA_D) - 1.0) / (bk7.index_at(486.1) - bk7.index_at(656.3));
+    assert!((abbe - 64.17).abs() < 0.1, "{abbe}");
+
+    let fitted = Dielectric::with_dispersion(1.5168, 64.17);
+    assert!((fitted.index_at(LAMBDA_D) - 1.5168).abs() < 1e-12);
+    assert!((fitted.index_at(450.0) - bk7.index_at(450.0)).abs() < 1e-3);
+
+    let cauchy = Dielectric {
+        dispersion: Some(Dispersion::Cauchy {
+            a: 1.5,
+            b: 0.01,
+            c: 0.0,
+        }),
+        ..Dielectric::new(1.0)
+    };
+    assert!((cauchy.index_at(500.0) - 1.54).abs() < 1e-12);
+    // Blue bends more than red.
+    assert!(cauchy.index_at(450.0) > cauchy.index_at(650.0));
+}
+
+#[test]
+fn wavelength_weights_average_to_white() {
+    let steps = 4000;
+    let width = (WAVELENGTH_MAX - WAVELENGTH_MIN) / steps as f64;
+    let mean = (0..steps).fold(DVec3::ZERO, |sum, i| {
+        sum + wavelength_weight(WAVELENGTH_MIN + (i as f64 + 0.5) * width)
+    }) / steps as f64;
+    assert!(mean.abs_diff_eq(DVec3::ONE, 1e-3), "{mean}");
+
+    // The luminance curve peaks near 555 nm at 1.
+    assert!((cie_xyz(555.0).y - 1.0).abs() < 0.02);
+    assert!(cie_xyz(450.0).z > cie_xyz(650.0).z);
+}
+
+#[test]
+fn rgb_paths_keep_the_channel_dispersion_picked() {
+    let glass = Arc::new(Dielectric::with_dispersion(1.5, 30.0));
+    let ball = Sphere::new(DVec3::ZERO, 1.0, glass);
+    let mut sampler = IndependentSampler::new(3);
+    for _ in 0..50 {
+        // Into the ball, where an RGB ray picks a channel.
+        let ray = Ray::new(DVec3::new(0.3, 0.2, 3.0), DVec3::NEG_Z);
+        let rec = ball.hit(&ray, Interval::after(1e-9)).unwrap();
+        let (scattered, attenuation) = rec.material.scatter(&ray, &rec, &mut sampler).unwrap();
+        let wavelength = scattered.wavelength.expect("a channel's wavelength");
+        let channel = [650.0, 550.0, 450.0]
+            .iter()
+            .position(|&w| w == wavelength)
+            .unwrap();
+        assert_eq!(attenuation.max_element(), attenuation[channel]);
+        assert_eq!(attenuation.min_element(), 0.0);
+
+        // Wherever it goes next, the path bends at that wavelength alone
+        // rather than picking again.
+        let next = rec.spawn_scattered(&ray, &scattered);
+        assert_eq!(next.wavelength, Some(wavelength));
+        let Some(rec) = ball.hit(&next, Interval::after(rec.ray_epsilon())) else {
+            continue;
+        };
+        let (scattered, attenuation) = rec.material.scatter(&next, &rec, &mut sampler).unwrap();
+        assert_eq!(scattered.wavelength, Some(wavelength));
+        assert!(attenuation.abs_diff_eq(DVec3::ONE, 1e-12), "{attenuation}");
+    }
+}
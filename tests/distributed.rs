@@ -0,0 +1,102 @@
+use raytracer::distributed;
+use raytracer::renderer::Renderer;
+use raytracer::scene::Scene;
+use std::net::TcpListener;
+use std::sync::Mutex;
+
+const SCENE: &str = "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material: { type: lambertian, texture: { type: solid_color, color: [0.5, 0.5, 0.5] } }
+render:
+  width: 12
+  height: 8
+  samples_per_pixel: 2
+  tile_size: 4
+  filter: { type: tent }
+";
+
+fn write_scene(name: &str, source: &str) -> String {
+    let dir = std::env::temp_dir().join("raytracer-distributed");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, source).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn worker(path: &str) -> String {
+    let address = distributed::serve("127.0.0.1:0", path).unwrap();
+    address.to_string()
+}
+
+#[test]
+fn workers_render_the_same_image() {
+    let path = write_scene("scene.yaml", SCENE);
+    let (config, camera, world, lights) = Scene::from_file(&path).unwrap();
+    let local = Renderer::new(world, config.environment().unwrap())
+        .with_lights(lights)
+        .render(&camera, &config.render);
+
+    // Nothing listens on a port that was just freed, so that worker fails
+    // and the others take over its tiles.
+    let closed = TcpListener::bind("127.0.0.1:0").and_then(|l| l.local_addr());
+    let closed = closed.unwrap().to_string();
+    let workers = vec![worker(&path), closed.clone(), worker(&path)];
+    let reports = Mutex::new(Vec::new());
+    let passes = distributed::render(
+        SCENE.as_bytes(),
+        &config.render,
+        &workers,
+        None,
+        |message: &str| reports.lock().unwrap().push(message.to_string()),
+    )
+    .unwrap();
+    for (a, b) in passes.beauty.pixels.iter().zip(&local.pixels) {
+        assert!(a.abs_diff_eq(*b, 1e-12), "{a} != {b}");
+    }
+    let reports = reports.into_inner().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].starts_with(&format!("worker {closed}")));
+}
+
+#[test]
+fn workers_must_have_the_same_scene() {
+    let path = write_scene("other.yaml", &SCENE.replace("radius: 1", "radius: 2"));
+    let (config, ..) = Scene::from_file(&path).unwrap();
+    let workers = vec![worker(&path)];
+    let reports = Mutex::new(Vec::new());
+    let error = distributed::render(
+        SCENE.as_bytes(),
+        &config.render,
+        &workers,
+        None,
+        |message: &str| reports.lock().unwrap().push(message.to_string()),
+    )
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("6 tiles"), "{error}");
+    let reports = reports.into_inner().unwrap();
+    assert!(reports[0].ends_with("its scene file differs from this one"));
+}
+
+#[test]
+fn tile_layout_does_not_change_the_image() {
+    // Without the tent filter's splats, which sum across tile edges.
+    let source = SCENE.replace("  filter: { type: tent }\n", "");
+    let path = write_scene("untiled.yaml", &source);
+    let (mut config, camera, world, lights) = Scene::from_file(&path).unwrap();
+    let renderer = Renderer::new(world, config.environment().unwrap()).with_lights(lights);
+    let tiled = renderer.render(&camera, &config.render);
+    config.render.tile_size = 5;
+    let retiled = renderer.render(&camera, &config.render);
+    assert_eq!(tiled.pixels, retiled.pixels);
+}
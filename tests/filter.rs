@@ -0,0 +1,81 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::environment::{SkyGradient, SolidBackground};
+use raytracer::filter::PixelFilter;
+use raytracer::material::Lambertian;
+use raytracer::objects::sphere::Sphere;
+use raytracer::renderer::{ImageBuffer, RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn camera() -> Camera {
+    Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        4.0,
+    )
+}
+
+fn settings(filter: PixelFilter) -> RenderSettings {
+    RenderSettings {
+        width: 24,
+        height: 24,
+        samples_per_pixel: 4,
+        tile_size: 5,
+        filter,
+        ..RenderSettings::default()
+    }
+}
+
+fn render_with_threads(
+    renderer: &Renderer,
+    settings: &RenderSettings,
+    threads: usize,
+) -> ImageBuffer {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(|| renderer.render(&camera(), settings))
+}
+
+#[test]
+fn wide_filters_are_independent_of_thread_count() {
+    let material = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.5,
+    )))));
+    let world = Arc::new(Sphere::new(DVec3::ZERO, 1.0, material));
+    let sky = SkyGradient::new(DVec3::ONE, DVec3::new(0.5, 0.7, 1.0));
+    let renderer = Renderer::new(world, Arc::new(sky));
+    for filter in [
+        PixelFilter::Tent { radius: 1.5 },
+        PixelFilter::Gaussian {
+            radius: 2.0,
+            alpha: 2.0,
+        },
+        PixelFilter::Mitchell { radius: 2.5 },
+    ] {
+        let settings = settings(filter);
+        let single = render_with_threads(&renderer, &settings, 1);
+        let parallel = render_with_threads(&renderer, &settings, 4);
+        assert_eq!(single.pixels, parallel.pixels, "{filter:?}");
+    }
+}
+
+#[test]
+fn constant_image_has_no_tile_seams() {
+    let world = Arc::new(Sphere::new(
+        DVec3::new(0.0, 0.0, 100.0),
+        1.0,
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ZERO)))),
+    ));
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)));
+    let image = renderer.render(&camera(), &settings(PixelFilter::Tent { radius: 2.0 }));
+    for pixel in &image.pixels {
+        assert!(pixel.abs_diff_eq(DVec3::ONE, 1e-12), "{pixel}");
+    }
+}
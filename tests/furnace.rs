@@ -0,0 +1,241 @@
+use glam::DVec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use raytracer::albedo_lut::AlbedoLut;
+use raytracer::color::{sample_wavelength, wavelength_weight};
+use raytracer::environment::SolidBackground;
+use raytracer::material::{
+    AnisotropicMetal, Dielectric, Dispersion, Lambertian, Layered, Material, Metal, Mix,
+    Principled, Subsurface,
+};
+use raytracer::objects::sphere::Sphere;
+use raytracer::ray::Ray;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::sampler::IndependentSampler;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+const SAMPLES: usize = 20_000;
+// For materials whose paths follow one colour channel, which leaves each
+// channel a third of the paths.
+const CHANNEL_SAMPLES: usize = 100_000;
+const TOLERANCE: f64 = 0.02;
+
+// A convex object inside a uniform white environment reflects exactly its
+// albedo back: every scattered ray escapes and sees radiance 1.
+fn furnace(material: Arc<dyn Material>) -> DVec3 {
+    furnace_with(material, false, SAMPLES)
+}
+
+// The furnace with each ray at a random wavelength, weighted as the
+// spectral integrator does.
+fn spectral_furnace(material: Arc<dyn Material>) -> DVec3 {
+    furnace_with(material, true, SAMPLES)
+}
+
+fn furnace_with(material: Arc<dyn Material>, spectral: bool, samples: usize) -> DVec3 {
+    let world = Arc::new(Sphere::new(DVec3::ZERO, 1.0, material));
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)));
+    let settings = RenderSettings {
+        max_depth: 64,
+        ..RenderSettings::default()
+    };
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut sampler = IndependentSampler::new(11);
+    let mut total = DVec3::ZERO;
+    for _ in 0..samples {
+        let origin = 3.0 * random_unit_vector(&mut rng);
+        let target = 0.99 * random_unit_vector(&mut rng);
+        let ray = Ray::new(origin, target - origin);
+        if spectral {
+            let wavelength = sample_wavelength(rng.gen());
+            let ray = ray.with_wavelength(Some(wavelength));
+            total +=
+                renderer.ray_color(&ray, &settings, &mut sampler) * wavelength_weight(wavelength);
+        } else {
+            total += renderer.ray_color(&ray, &settings, &mut sampler);
+        }
+    }
+    total / samples as f64
+}
+
+fn random_unit_vector(rng: &mut StdRng) -> DVec3 {
+    loop {
+        let p = DVec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let len = p.length_squared();
+        if len > 1e-6 && len < 1.0 {
+            return p / len.sqrt();
+        }
+    }
+}
+
+fn assert_close(actual: DVec3, expected: DVec3) {
+    assert_close_within(actual, expected, TOLERANCE);
+}
+
+fn assert_close_within(actual: DVec3, expected: DVec3, tolerance: f64) {
+    assert!(
+        (actual - expected).abs().max_element() < tolerance,
+        "furnace mismatch: got {actual}, expected {expected}"
+    );
+}
+
+fn solid(color: DVec3) -> Arc<SolidColor> {
+    Arc::new(SolidColor::new(color))
+}
+
+#[test]
+fn lambertian_reflects_its_albedo() {
+    let albedo = DVec3::new(0.8, 0.5, 0.2);
+    assert_close(furnace(Arc::new(Lambertian::new(solid(albedo)))), albedo);
+}
+
+#[test]
+fn white_lambertian_is_energy_preserving() {
+    assert_close(
+        furnace(Arc::new(Lambertian::new(solid(DVec3::ONE)))),
+        DVec3::ONE,
+    );
+}
+
+#[test]
+fn smooth_metal_reflects_its_albedo() {
+    let albedo = DVec3::new(0.9, 0.7, 0.4);
+    assert_close(furnace(Arc::new(Metal::new(solid(albedo), 0.0))), albedo);
+}
+
+// `Metal` absorbs fuzzed reflections that end up below the surface, so
+// rough metals lose energy by design and this fails; it is ignored, and
+// run with `--ignored` to see how much a rough metal loses.
+#[test]
+#[ignore]
+fn rough_metal_reflects_its_albedo() {
+    let albedo = DVec3::ONE;
+    assert_close(furnace(Arc::new(Metal::new(solid(albedo), 0.8))), albedo);
+}
+
+#[test]
+fn dielectric_is_energy_preserving() {
+    assert_close(furnace(Arc::new(Dielectric::new(1.5))), DVec3::ONE);
+}
+
+// Dispersion has each path follow one colour channel with a 3x weight,
+// which is unbiased but noisier than the plain dielectric.
+#[test]
+fn dispersive_dielectric_is_energy_preserving() {
+    let glass = Arc::new(Dielectric::with_dispersion(1.5, 30.0));
+    assert_close(furnace_with(glass, false, CHANNEL_SAMPLES), DVec3::ONE);
+}
+
+#[test]
+fn spectral_dispersion_is_energy_preserving() {
+    let sellmeier = Dielectric {
+        dispersion: Some(Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }),
+        ..Dielectric::new(1.5)
+    };
+    assert_close_within(spectral_furnace(Arc::new(sellmeier)), DVec3::ONE, 0.1);
+}
+
+// Schlick's Fresnel brightens coloured metals towards grazing angles, so
+// only a white metal has an exact furnace value.
+#[test]
+fn smooth_principled_white_metal_is_energy_preserving() {
+    let material = Principled {
+        metallic: 1.0,
+        roughness: 0.05,
+        ..Principled::new(solid(DVec3::ONE))
+    };
+    assert_close(furnace(Arc::new(material)), DVec3::ONE);
+}
+
+#[test]
+fn smooth_principled_glass_is_invisible() {
+    let material = Principled {
+        roughness: 0.05,
+        transmission: 1.0,
+        ..Principled::new(solid(DVec3::ONE))
+    };
+    assert_close_within(furnace(Arc::new(material)), DVec3::ONE, 0.05);
+}
+
+#[test]
+fn frosted_glass_is_nearly_energy_preserving() {
+    let material = Dielectric {
+        roughness: 0.2,
+        ..Dielectric::new(1.5)
+    };
+    assert_close_within(furnace(Arc::new(material)), DVec3::ONE, 0.05);
+}
+
+#[test]
+fn smooth_anisotropic_white_metal_is_energy_preserving() {
+    let material = AnisotropicMetal {
+        rotation: 0.5,
+        ..AnisotropicMetal::new(solid(DVec3::ONE), 0.1, 0.3)
+    };
+    assert_close_within(furnace(Arc::new(material)), DVec3::ONE, 0.05);
+}
+
+// A single GGX bounce loses a good part of the light on rough surfaces;
+// the baked albedo tables put it back.
+#[test]
+fn rough_white_metals_are_energy_preserving_with_compensation() {
+    let lut = Some(AlbedoLut::shared());
+    let principled = |energy_compensation| Principled {
+        metallic: 1.0,
+        roughness: 1.0,
+        energy_compensation,
+        ..Principled::new(solid(DVec3::ONE))
+    };
+    assert!(furnace(Arc::new(principled(None))).x < 0.9);
+    assert_close(furnace(Arc::new(principled(lut.clone()))), DVec3::ONE);
+
+    let anisotropic = AnisotropicMetal {
+        energy_compensation: lut,
+        ..AnisotropicMetal::new(solid(DVec3::ONE), 0.8, 0.8)
+    };
+    assert_close(furnace(Arc::new(anisotropic)), DVec3::ONE);
+}
+
+// Without absorption every random walk eventually leaves the object again.
+// Channel-dependent scattering exercises the chromatic distance sampling.
+#[test]
+fn non_absorbing_subsurface_is_energy_preserving() {
+    let material = Subsurface::new(DVec3::ZERO, DVec3::new(1.0, 4.0, 16.0));
+    assert_close_within(furnace(Arc::new(material)), DVec3::ONE, 0.05);
+}
+
+// Each bounce follows one half of the blend, so the furnace sees the blend
+// of their albedos.
+#[test]
+fn mix_reflects_the_blend_of_its_halves() {
+    let (a, b) = (DVec3::new(0.8, 0.5, 0.2), DVec3::new(0.2, 0.4, 0.9));
+    let material = Mix::new(
+        Arc::new(Lambertian::new(solid(a))),
+        Arc::new(Metal::new(solid(b), 0.0)),
+        solid(DVec3::splat(0.25)),
+    );
+    assert_close(furnace(Arc::new(material)), a.lerp(b, 0.25));
+}
+
+// The coat reflects a few percent on its own and, leaving out the light
+// bouncing under it, takes a little from a white base.
+#[test]
+fn clear_coat_reflects_part_of_the_light() {
+    let coated = |albedo| {
+        let base = Arc::new(Lambertian::new(solid(albedo)));
+        furnace(Arc::new(Layered::new(base)))
+    };
+    let black = coated(DVec3::ZERO).x;
+    assert!((0.03..0.2).contains(&black), "{black}");
+    let white = coated(DVec3::ONE).x;
+    assert!((0.8..1.0).contains(&white), "{white}");
+}
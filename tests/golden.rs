@@ -0,0 +1,87 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::environment::SkyGradient;
+use raytracer::hittable::Hittable;
+use raytracer::output::{self, OutputOptions};
+use raytracer::renderer::{ImageBuffer, RenderSettings, Renderer};
+use raytracer::scene::presets::{self, CheckerParams, RandomSpheresParams};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Tiny renders of the presets at fixed seeds, compared with the images in
+// `tests/golden`. A missing reference fails the test; set `UPDATE_GOLDEN=1`
+// to write them, after a change that is meant to alter the images, and
+// check them in.
+fn check(name: &str, (camera, world): (Camera, Arc<dyn Hittable>)) {
+    let settings = RenderSettings {
+        width: 32,
+        height: 18,
+        samples_per_pixel: 16,
+        max_depth: 8,
+        seed: 7,
+        ..RenderSettings::default()
+    };
+    let image = Renderer::new(world, Arc::new(SkyGradient::default())).render(&camera, &settings);
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.exr"));
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|update| update == "1") {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        output::save_exr(&image, &path, &OutputOptions::default()).unwrap();
+        eprintln!("wrote {}", path.display());
+        return;
+    }
+    assert!(
+        path.exists(),
+        "{} is missing; run with UPDATE_GOLDEN=1 to write it",
+        path.display()
+    );
+    let reference = load(&path);
+    assert_eq!(
+        (reference.width, reference.height),
+        (image.width, image.height),
+        "{name}"
+    );
+    // Loose enough for floating-point differences between platforms, tight
+    // enough to catch a missing bounce or a shifted camera.
+    let squared: f64 = image
+        .pixels
+        .iter()
+        .zip(&reference.pixels)
+        .map(|(a, b)| (*a - *b).length_squared() / 3.0)
+        .sum();
+    let rmse = (squared / image.pixels.len() as f64).sqrt();
+    assert!(rmse < 0.01, "{name} differs from its reference by {rmse}");
+}
+
+fn load(path: &Path) -> ImageBuffer {
+    let decoded = image::open(path).unwrap().to_rgb32f();
+    let mut image = ImageBuffer::new(decoded.width(), decoded.height());
+    for (pixel, rgb) in image.pixels.iter_mut().zip(decoded.pixels()) {
+        *pixel = DVec3::from_array(rgb.0.map(f64::from));
+    }
+    image
+}
+
+#[test]
+fn random_spheres_match_their_reference() {
+    let params = RandomSpheresParams {
+        seed: 3,
+        spheres: 64,
+        ..RandomSpheresParams::default()
+    };
+    check("random_spheres", presets::random_spheres(&params));
+}
+
+#[test]
+fn cornell_box_matches_its_reference() {
+    check("cornell_box", presets::cornell_box());
+}
+
+#[test]
+fn checkered_spheres_match_their_reference() {
+    check(
+        "checkered_spheres",
+        presets::checkered_spheres(&CheckerParams::default()),
+    );
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "gpu")]
+
+use glam::DVec3;
+use raytracer::gpu::GpuDevices;
+use raytracer::renderer::{ImageBuffer, Renderer};
+use raytracer::scene::{Scene, SceneFormat};
+
+const SCENE: &str = "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material: { type: lambertian, texture: { type: solid_color, color: [0.5, 0.5, 0.5] } }
+  - type: sphere
+    center: [0, -101, 0]
+    radius: 100
+    material: { type: metal, texture: { type: solid_color, color: [0.8, 0.8, 0.8] }, fuzz: 0.2 }
+render:
+  width: 160
+  height: 96
+  samples_per_pixel: 16
+  tile_size: 16
+";
+
+// Side of the tiles the devices take in turn.
+const TILE_SIZE: u32 = 64;
+
+#[test]
+fn cpu_takes_tiles_alongside_the_gpus() {
+    let (config, camera, world, lights) =
+        Scene::from_source_at(SCENE, SceneFormat::Yaml, 0.0).unwrap();
+    let renderer = Renderer::new(world, config.environment().unwrap()).with_lights(lights);
+    let settings = config.render;
+    let expected = renderer.render(&camera, &settings);
+    let image = config
+        .to_gpu_scene()
+        .unwrap()
+        .render_on(&camera, &settings, GpuDevices::AllGpus, Some(&renderer))
+        .unwrap();
+
+    // Without a GPU the CPU renders every tile. Its tiles match the CPU
+    // render exactly; a GPU's trace the same scene in f32 with their own
+    // random numbers, so only their averages agree.
+    let mut cpu_tiles = 0;
+    for y0 in (0..settings.height).step_by(TILE_SIZE as usize) {
+        for x0 in (0..settings.width).step_by(TILE_SIZE as usize) {
+            let pixels: Vec<(u32, u32)> = (y0..(y0 + TILE_SIZE).min(settings.height))
+                .flat_map(|y| (x0..(x0 + TILE_SIZE).min(settings.width)).map(move |x| (x, y)))
+                .collect();
+            if pixels
+                .iter()
+                .all(|&(x, y)| image.get(x, y) == expected.get(x, y))
+            {
+                cpu_tiles += 1;
+                continue;
+            }
+            let mean = |image: &ImageBuffer| {
+                pixels.iter().map(|&(x, y)| image.get(x, y)).sum::<DVec3>() / pixels.len() as f64
+            };
+            let (gpu, cpu) = (mean(&image), mean(&expected));
+            assert!(
+                gpu.abs_diff_eq(cpu, 0.05),
+                "({x0}, {y0}): {gpu}, expected {cpu}"
+            );
+        }
+    }
+    // The GPUs set their devices up first, so the CPU, starting straight
+    // away, always gets some of the tiles.
+    assert!(cpu_tiles > 0);
+}
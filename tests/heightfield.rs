@@ -0,0 +1,107 @@
+mod common;
+
+use common::ring_of_rays;
+use glam::{DVec2, DVec3};
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::heightfield::Heightfield;
+use raytracer::objects::triangle::Triangle;
+use raytracer::ray::Ray;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+const COLUMNS: usize = 9;
+const ROWS: usize = 7;
+const SIZE: DVec2 = DVec2::new(4.0, 3.0);
+const HEIGHT: f64 = 1.5;
+
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))))
+}
+
+fn sample(i: usize, j: usize) -> f64 {
+    0.5 + 0.5 * (i as f64 * 0.9).sin() * (j as f64 * 1.3).cos()
+}
+
+// The same terrain as loose triangles, split along the same diagonals.
+fn brute_force() -> HittableList {
+    let vertex = |i: usize, j: usize| {
+        let x = i as f64 / (COLUMNS - 1) as f64 * SIZE.x - 0.5 * SIZE.x;
+        let z = j as f64 / (ROWS - 1) as f64 * SIZE.y - 0.5 * SIZE.y;
+        DVec3::new(x, sample(i, j) * HEIGHT, z)
+    };
+    let mut mesh = HittableList::new();
+    for j in 0..ROWS - 1 {
+        for i in 0..COLUMNS - 1 {
+            let (a, b) = (vertex(i, j), vertex(i + 1, j));
+            let (c, d) = (vertex(i, j + 1), vertex(i + 1, j + 1));
+            mesh.push(Arc::new(Triangle::new([a, c, b], material())));
+            mesh.push(Arc::new(Triangle::new([b, c, d], material())));
+        }
+    }
+    mesh
+}
+
+#[test]
+fn grid_traversal_matches_brute_force() {
+    let samples: Vec<f64> = (0..ROWS)
+        .flat_map(|j| (0..COLUMNS).map(move |i| sample(i, j)))
+        .collect();
+    let terrain = Heightfield::new(COLUMNS, ROWS, &samples, SIZE, HEIGHT, material());
+    let mesh = brute_force();
+
+    let mut hits = 0;
+    // Towards points all over the terrain, not just its centre.
+    let target = |k: f64| {
+        DVec3::new(
+            1.8 * (k * 1.7).sin(),
+            0.3 * (k * 2.3).cos(),
+            1.4 * (k * 0.9).cos(),
+        )
+    };
+    for (k, ray) in ring_of_rays(500, 3.0, 2.0, 1.0, target).enumerate() {
+        let expected = mesh.hit(&ray, Interval::after(0.001));
+        let actual = terrain.hit(&ray, Interval::after(0.001));
+        match (expected, actual) {
+            (Some(expected), Some(actual)) => {
+                hits += 1;
+                assert!((expected.t - actual.t).abs() < 1e-9, "ray {k}");
+                assert!(actual.normal.dot(expected.normal) > 0.5, "ray {k}");
+            }
+            (None, None) => {}
+            (expected, actual) => panic!(
+                "ray {k}: brute force hit {}, traversal hit {}",
+                expected.is_some(),
+                actual.is_some()
+            ),
+        }
+    }
+    assert!(hits > 250, "only {hits} rays hit the terrain");
+}
+
+#[test]
+fn flat_terrain_is_hit_from_both_sides() {
+    let samples = vec![0.25; 4];
+    let terrain = Heightfield::new(2, 2, &samples, DVec2::splat(2.0), 1.0, material());
+    let down = terrain
+        .hit(
+            &Ray::new(DVec3::new(0.3, 5.0, -0.2), -DVec3::Y),
+            Interval::after(0.001),
+        )
+        .expect("ray hits the flat terrain");
+    assert!((down.t - 4.75).abs() < 1e-9);
+    assert!(down.front_face && down.normal.abs_diff_eq(DVec3::Y, 1e-9));
+
+    let up = terrain
+        .hit(
+            &Ray::new(DVec3::new(0.3, -5.0, -0.2), DVec3::Y),
+            Interval::after(0.001),
+        )
+        .expect("ray hits the underside");
+    assert!((up.t - 5.25).abs() < 1e-9);
+    assert!(!up.front_face && up.normal.abs_diff_eq(-DVec3::Y, 1e-9));
+
+    let beside = Ray::new(DVec3::new(1.5, 5.0, 0.0), -DVec3::Y);
+    assert!(terrain.hit(&beside, Interval::after(0.001)).is_none());
+}
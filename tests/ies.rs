@@ -0,0 +1,118 @@
+use glam::DVec3;
+use raytracer::ies::IesProfile;
+use raytracer::lights::{AnalyticLight, SpotLight};
+use raytracer::sampler::IndependentSampler;
+use raytracer::scene::{Scene, SceneFormat};
+use std::sync::Arc;
+
+// A downlight, the same all round, at full strength straight down and
+// fading to nothing at 90°.
+const DOWNLIGHT: &str = "IESNA:LM-63-2002
+[MANUFAC] Test
+TILT=NONE
+1 1000 1 4 1 1 2 0 0 0
+1 1 50
+0 30 60 90
+0
+1000 800 400 0
+";
+
+// Half as bright towards one side as the other: quadrant-symmetric, with
+// the horizontal angles split over two lines and commas.
+const OVAL: &str = "TILT=INCLUDE
+1
+2 0 90 1 1
+1 1000 2 2 3 1 1 0 0 0
+1 1 40
+0 90
+0, 45,
+90
+100 100 75 75 50 50
+";
+
+#[test]
+fn profiles_interpolate_between_measured_angles() {
+    let profile = IesProfile::parse(DOWNLIGHT).unwrap();
+    let down = DVec3::NEG_Y;
+    assert!((profile.eval(down, down) - 1.0).abs() < 1e-12);
+    let at = |degrees: f64| {
+        let angle = degrees.to_radians();
+        profile.eval(down, DVec3::new(angle.sin(), -angle.cos(), 0.0))
+    };
+    assert!((at(30.0) - 0.8).abs() < 1e-9);
+    assert!((at(45.0) - 0.6).abs() < 1e-9);
+    assert_eq!(at(120.0), 0.0);
+    // The same all round.
+    let turned = DVec3::new(0.0, -30f64.to_radians().cos(), 30f64.to_radians().sin());
+    assert!((profile.eval(down, turned) - 0.8).abs() < 1e-9);
+    // About a fifth of a uniform source's light, shining only downwards
+    // and fading towards the horizon.
+    assert!(
+        (0.15..0.3).contains(&profile.average()),
+        "{}",
+        profile.average()
+    );
+}
+
+#[test]
+fn symmetric_profiles_mirror_their_quadrant() {
+    let profile = IesProfile::parse(OVAL).unwrap();
+    let axis = DVec3::NEG_Y;
+    let (tangent, bitangent) = axis.any_orthonormal_pair();
+    let sideways = |degrees: f64| {
+        let angle = degrees.to_radians();
+        let across = angle.cos() * tangent + angle.sin() * bitangent;
+        profile.eval(axis, across + axis)
+    };
+    assert!((sideways(0.0) - 1.0).abs() < 1e-9);
+    assert!((sideways(90.0) - 0.5).abs() < 1e-9);
+    for degrees in [30.0, 60.0] {
+        let value = sideways(degrees);
+        assert!((sideways(180.0 - degrees) - value).abs() < 1e-9);
+        assert!((sideways(180.0 + degrees) - value).abs() < 1e-9);
+        assert!((sideways(360.0 - degrees) - value).abs() < 1e-9);
+    }
+
+    let error = IesProfile::parse("TILT=NONE\n1 1000 1 2 1 2 2 0 0 0\n").unwrap_err();
+    assert_eq!(error.to_string(), "file ends early");
+}
+
+#[test]
+fn spotlights_take_the_shape_of_their_profile() {
+    let profile = Arc::new(IesProfile::parse(DOWNLIGHT).unwrap());
+    let light = AnalyticLight::Spot(SpotLight {
+        position: DVec3::new(0.0, 2.0, 0.0),
+        direction: DVec3::NEG_Y,
+        intensity: DVec3::splat(8.0),
+        cone_angle: 80f64.to_radians(),
+        cone_delta: 0.0,
+        radius: 0.0,
+        profile: Some(profile),
+    });
+    let mut sampler = IndependentSampler::new(0);
+    let below = light.illuminate(DVec3::ZERO, &mut sampler).unwrap();
+    assert!(below.radiance.abs_diff_eq(DVec3::splat(2.0), 1e-9));
+    // 30° off the axis, at the same distance.
+    let angle = 30f64.to_radians();
+    let point = DVec3::new(0.0, 2.0, 0.0) + 2.0 * DVec3::new(angle.sin(), -angle.cos(), 0.0);
+    let aside = light.illuminate(point, &mut sampler).unwrap();
+    assert!(aside.radiance.abs_diff_eq(DVec3::splat(1.6), 1e-9));
+
+    let path = std::env::temp_dir().join("raytracer-downlight.ies");
+    std::fs::write(&path, DOWNLIGHT).unwrap();
+    let scene = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects: []
+lights:
+  - {{ type: spot, position: [0, 2, 0], target: [0, 0, 0], intensity: [8, 8, 8], cone_angle: 80, cone_delta: 0, ies: '{}' }}
+",
+        path.display()
+    );
+    let (_, _, _, lights) = Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+    let aside = lights.analytic()[0]
+        .illuminate(point, &mut sampler)
+        .unwrap();
+    assert!(aside.radiance.abs_diff_eq(DVec3::splat(1.6), 1e-9));
+    std::fs::remove_file(&path).unwrap();
+}
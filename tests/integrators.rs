@@ -0,0 +1,83 @@
+use glam::DVec3;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::material::{Lambertian, Material, Metal};
+use raytracer::objects::triangle::Triangle;
+use raytracer::ray::Ray;
+use raytracer::reference_scenes;
+use raytracer::renderer::{Integrator, RenderSettings, Renderer};
+use raytracer::sampler::IndependentSampler;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+// Average of `samples` estimates along a camera ray between a floor at
+// y = 0 and a ceiling at y = 1, looking down at the floor.
+fn between_floor_and_ceiling(integrator: Integrator, samples: u32) -> DVec3 {
+    let grey: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let mut world = HittableList::new();
+    for y in [0.0, 1.0] {
+        let [a, b, c, d] =
+            [(-1e3, -1e3), (1e3, -1e3), (1e3, 1e3), (-1e3, 1e3)].map(|(x, z)| DVec3::new(x, y, z));
+        world.push(Arc::new(Triangle::new([a, b, c], grey.clone())));
+        world.push(Arc::new(Triangle::new([a, c, d], grey.clone())));
+    }
+    let world: Arc<dyn Hittable> = Arc::new(world);
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)));
+    let settings = RenderSettings {
+        integrator,
+        ..RenderSettings::default()
+    };
+    let mut sampler = IndependentSampler::new(5);
+    let ray = Ray::new(DVec3::new(0.0, 0.5, -2.0), DVec3::new(0.0, -0.5, 2.0));
+    let mut total = DVec3::ZERO;
+    for _ in 0..samples {
+        total += renderer.ray_color(&ray, &settings, &mut sampler);
+    }
+    total / samples as f64
+}
+
+#[test]
+fn ambient_occlusion_counts_blockers_within_reach() {
+    // The ceiling hides the whole sky from the floor, but is always more
+    // than its height away.
+    let unlimited = Integrator::AmbientOcclusion { distance: None };
+    assert_eq!(between_floor_and_ceiling(unlimited, 64), DVec3::ZERO);
+    let near = Integrator::AmbientOcclusion {
+        distance: Some(0.9),
+    };
+    assert_eq!(between_floor_and_ceiling(near, 64), DVec3::ONE);
+}
+
+// Inside a white furnace every surface shows its albedo, which clay sets
+// for all of them; a mirror ball would otherwise disappear.
+#[test]
+fn clay_replaces_every_material() {
+    let mirror: Arc<dyn Material> =
+        Arc::new(Metal::new(Arc::new(SolidColor::new(DVec3::ONE)), 0.0));
+    let scene = reference_scenes::white_furnace(mirror);
+    let settings = RenderSettings {
+        width: 8,
+        height: 8,
+        samples_per_pixel: 256,
+        integrator: Integrator::Clay { albedo: 0.3 },
+        ..RenderSettings::default()
+    };
+    let centre = scene.renderer().render(&scene.camera, &settings).get(4, 4);
+    assert!(
+        (centre - DVec3::splat(0.3)).abs().max_element() < 0.05,
+        "{centre}"
+    );
+}
+
+#[test]
+fn debug_views_show_the_first_hit() {
+    let normal = between_floor_and_ceiling(Integrator::Normals, 1);
+    assert_eq!(normal, DVec3::new(0.5, 1.0, 0.5));
+    // The ray lands on the diagonal the floor's two triangles share.
+    let wireframe = Integrator::Wireframe { width: 0.01 };
+    assert_eq!(between_floor_and_ceiling(wireframe, 1), DVec3::ZERO);
+    let no_lines = Integrator::Wireframe { width: 0.0 };
+    assert!(between_floor_and_ceiling(no_lines, 1).x > 0.0);
+}
@@ -0,0 +1,441 @@
+use glam::DVec3;
+use raytracer::environment::{Environment, SolidBackground, SunSky};
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::lights::{AnalyticLight, Emitter, LightSet, PointLight, Portal, PortalDistribution};
+use raytracer::material::{DiffuseLight, Lambertian, Material};
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::visibility::Visibility;
+use raytracer::ray::{Ray, RayKind};
+use raytracer::renderer::{DirectLighting, Integrator, RenderSettings, Renderer};
+use raytracer::sampler::{IndependentSampler, Sampler};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+const SAMPLES: u32 = 40_000;
+
+fn quad(corners: [DVec3; 4], material: Arc<dyn Material>) -> [Triangle; 2] {
+    let [a, b, c, d] = corners;
+    [
+        Triangle::new([a, b, c], material.clone()),
+        Triangle::new([a, c, d], material),
+    ]
+}
+
+// A grey floor under a small square light in a black environment, seen
+// from below the light. Returns the average radiance of one camera ray.
+fn lit_floor(register_lights: bool, settings: RenderSettings) -> DVec3 {
+    let floor: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let emitter: Arc<dyn Material> = Arc::new(DiffuseLight::new(DVec3::splat(4.0)));
+    let mut world = HittableList::new();
+    let mut lights = LightSet::new();
+    for triangle in quad(
+        [
+            DVec3::new(-10.0, 0.0, -10.0),
+            DVec3::new(-10.0, 0.0, 10.0),
+            DVec3::new(10.0, 0.0, 10.0),
+            DVec3::new(10.0, 0.0, -10.0),
+        ],
+        floor,
+    ) {
+        world.push(Arc::new(triangle));
+    }
+    for triangle in quad(
+        [
+            DVec3::new(-0.5, 1.0, -0.5),
+            DVec3::new(0.5, 1.0, -0.5),
+            DVec3::new(0.5, 1.0, 0.5),
+            DVec3::new(-0.5, 1.0, 0.5),
+        ],
+        emitter,
+    ) {
+        if register_lights {
+            let index = lights.add_triangle(&triangle);
+            world.push(Arc::new(Emitter::new(Arc::new(triangle), index)));
+        } else {
+            world.push(Arc::new(triangle));
+        }
+    }
+
+    let world: Arc<dyn Hittable> = Arc::new(world);
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ZERO)))
+        .with_lights(Arc::new(lights));
+    let mut sampler = IndependentSampler::new(5);
+    let ray = Ray::new(DVec3::new(0.0, 0.5, -2.0), DVec3::new(0.0, -0.5, 2.0));
+    let mut total = DVec3::ZERO;
+    for _ in 0..SAMPLES {
+        total += renderer.ray_color(&ray, &settings, &mut sampler);
+    }
+    total / SAMPLES as f64
+}
+
+fn with_direct_lighting(direct_lighting: DirectLighting) -> RenderSettings {
+    RenderSettings {
+        direct_lighting,
+        ..RenderSettings::default()
+    }
+}
+
+fn assert_close(actual: DVec3, expected: DVec3) {
+    assert!(
+        (actual - expected).abs().max_element() < 0.03,
+        "got {actual}, expected {expected}"
+    );
+}
+
+// Light sampling must converge to the same image as plain BSDF sampling;
+// only the noise should change.
+#[test]
+fn power_light_sampling_matches_bsdf_sampling() {
+    let reference = lit_floor(false, RenderSettings::default());
+    assert!(reference.x > 0.1, "the floor should be lit: {reference}");
+    assert_close(lit_floor(true, RenderSettings::default()), reference);
+}
+
+#[test]
+fn restir_without_reuse_matches_bsdf_sampling() {
+    let restir = DirectLighting::Restir {
+        candidates: 8,
+        spatial_neighbours: 0,
+    };
+    assert_close(
+        lit_floor(true, with_direct_lighting(restir)),
+        lit_floor(false, RenderSettings::default()),
+    );
+}
+
+// The floor only sees the light directly, so the preview's direct lighting
+// plus one bounce is exact here.
+#[test]
+fn preview_matches_the_path_tracer_for_direct_light() {
+    let preview = RenderSettings {
+        integrator: Integrator::Preview { ambient: None },
+        ..RenderSettings::default()
+    };
+    assert_close(
+        lit_floor(true, preview),
+        lit_floor(true, RenderSettings::default()),
+    );
+}
+
+// A point light straight above a diffuse floor gives L = albedo / pi * I / d^2
+// with no noise, since analytic lights are sampled deterministically.
+#[test]
+fn point_light_follows_the_inverse_square_law() {
+    let floor: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let mut world = HittableList::new();
+    for triangle in quad(
+        [
+            DVec3::new(-10.0, 0.0, -10.0),
+            DVec3::new(-10.0, 0.0, 10.0),
+            DVec3::new(10.0, 0.0, 10.0),
+            DVec3::new(10.0, 0.0, -10.0),
+        ],
+        floor,
+    ) {
+        world.push(Arc::new(triangle));
+    }
+    let mut lights = LightSet::new();
+    lights.add_analytic(AnalyticLight::Point(PointLight {
+        position: DVec3::new(0.0, 2.0, 0.0),
+        intensity: DVec3::splat(8.0),
+        radius: 0.0,
+        profile: None,
+    }));
+    let world: Arc<dyn Hittable> = Arc::new(world);
+    let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ZERO)))
+        .with_lights(Arc::new(lights));
+    let mut sampler = IndependentSampler::new(5);
+    let ray = Ray::new(DVec3::new(0.0, 0.5, -2.0), DVec3::new(0.0, -0.5, 2.0));
+    let color = renderer.ray_color(&ray, &RenderSettings::default(), &mut sampler);
+    let expected = DVec3::splat(0.5 / std::f64::consts::PI * 8.0 / 4.0);
+    assert!(
+        color.abs_diff_eq(expected, 1e-9),
+        "got {color}, expected {expected}"
+    );
+}
+
+// The floor under the point light above, with a black ball between them
+// and another in front of the camera. Hidden from the camera, the near ball
+// leaves the exact lighting; it's the shadow one that darkens the floor
+// until it stops casting shadows.
+// Lights with a radius are lit from all over the side of their sphere
+// facing the point, as brightly as a point light when far away.
+#[test]
+fn sized_point_lights_spread_over_their_sphere() {
+    let centre = DVec3::new(0.0, 2.0, 0.0);
+    let light = AnalyticLight::Point(PointLight {
+        position: centre,
+        intensity: DVec3::splat(8.0),
+        radius: 0.5,
+        profile: None,
+    });
+    let mut sampler = IndependentSampler::new(3);
+    let expected = 2.0 * 8.0 * (1.0 - (1.0 - 0.0625f64).sqrt()) / 0.25;
+    let mut spread = 0.0f64;
+    for i in 0..1000 {
+        sampler.start_pixel(0, 0, i);
+        let illumination = light.illuminate(DVec3::ZERO, &mut sampler).unwrap();
+        let on_sphere = illumination.distance * illumination.direction;
+        assert!((on_sphere.distance(centre) - 0.5).abs() < 1e-9);
+        assert!(on_sphere.y < 2.0);
+        assert!(illumination
+            .radiance
+            .abs_diff_eq(DVec3::splat(expected), 1e-9));
+        spread = spread.max(illumination.direction.x.abs());
+    }
+    assert!(spread > 0.2, "{spread}");
+    assert!((expected - 2.0).abs() < 0.05);
+}
+
+#[test]
+fn visibility_flags_hide_objects_from_kinds_of_ray() {
+    let floor: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let black: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ZERO))));
+    let ray = Ray::new(DVec3::new(0.0, 0.5, -2.0), DVec3::new(0.0, -0.5, 2.0));
+    let color = |casts_shadows: bool| {
+        let mut world = HittableList::new();
+        for triangle in quad(
+            [
+                DVec3::new(-10.0, 0.0, -10.0),
+                DVec3::new(-10.0, 0.0, 10.0),
+                DVec3::new(10.0, 0.0, 10.0),
+                DVec3::new(10.0, 0.0, -10.0),
+            ],
+            floor.clone(),
+        ) {
+            world.push(Arc::new(triangle));
+        }
+        let near = Sphere::new(ray.at(0.5), 0.1, black.clone());
+        world.push(Arc::new(Visibility::new(Arc::new(near)).with_camera(false)));
+        let blocker = Sphere::new(DVec3::new(0.0, 1.0, 0.0), 0.2, black.clone());
+        world.push(Arc::new(
+            Visibility::new(Arc::new(blocker)).with_shadows(casts_shadows),
+        ));
+        let mut lights = LightSet::new();
+        lights.add_analytic(AnalyticLight::Point(PointLight {
+            position: DVec3::new(0.0, 2.0, 0.0),
+            intensity: DVec3::splat(8.0),
+            radius: 0.0,
+            profile: None,
+        }));
+        let world: Arc<dyn Hittable> = Arc::new(world);
+        let renderer = Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ZERO)))
+            .with_lights(Arc::new(lights));
+        let mut sampler = IndependentSampler::new(5);
+        renderer.ray_color(&ray, &RenderSettings::default(), &mut sampler)
+    };
+    assert_eq!(color(true), DVec3::ZERO);
+    let expected = DVec3::splat(0.5 / std::f64::consts::PI * 8.0 / 4.0);
+    let unshadowed = color(false);
+    assert!(
+        unshadowed.abs_diff_eq(expected, 1e-9),
+        "got {unshadowed}, expected {expected}"
+    );
+
+    let hidden =
+        Visibility::new(Arc::new(Sphere::new(DVec3::ZERO, 1.0, black))).with_reflections(false);
+    let through = Ray::new(DVec3::new(0.0, 0.0, -2.0), DVec3::Z);
+    assert!(hidden.hit(&through, Interval::new(0.0, 10.0)).is_some());
+    assert!(hidden
+        .hit(
+            &through.with_kind(RayKind::Indirect),
+            Interval::new(0.0, 10.0)
+        )
+        .is_none());
+}
+
+// Irradiance on the ground from the sun and sky, estimated by importance
+// sampling, must match a uniform estimate of the sky plus the sun's disc
+// added on exactly.
+#[test]
+fn sun_and_sky_sampling_is_unbiased() {
+    let (elevation, azimuth) = (30f64.to_radians(), 60f64.to_radians());
+    let sky = SunSky::new(elevation, azimuth, 3.0, 1.0);
+    let sun = DVec3::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        -elevation.cos() * azimuth.cos(),
+    );
+    let sun_cos_radius = 0.00465f64.cos();
+    let mut sampler = IndependentSampler::new(3);
+    sampler.start_pixel(0, 0, 0);
+
+    let mut sampled = DVec3::ZERO;
+    for _ in 0..SAMPLES {
+        let Some((direction, pdf)) = sky.sample(&mut sampler) else {
+            continue;
+        };
+        assert!((pdf - sky.pdf(direction)).abs() <= 1e-9 * pdf);
+        sampled += sky.value(direction) * direction.y.max(0.0) / pdf;
+    }
+    sampled /= SAMPLES as f64;
+
+    let mut uniform = DVec3::ZERO;
+    for _ in 0..SAMPLES {
+        let (u1, u2) = sampler.next_2d();
+        let r = (1.0 - u1 * u1).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u2;
+        let direction = DVec3::new(r * phi.cos(), u1, r * phi.sin());
+        if direction.dot(sun) < sun_cos_radius {
+            uniform += sky.value(direction) * u1 * 2.0 * std::f64::consts::PI;
+        }
+    }
+    uniform /= SAMPLES as f64;
+    let disc = 2.0 * std::f64::consts::PI * (1.0 - sun_cos_radius);
+    uniform += sky.value(sun) * disc * sun.y;
+
+    assert!(sampled.min_element() > 0.0);
+    assert!(
+        (sampled - uniform).abs().max_element() < 0.02 * uniform.max_element(),
+        "sampled {sampled}, uniform {uniform}"
+    );
+    // Sunlight through the air is warm.
+    assert!(sky.value(sun).x > sky.value(sun).z);
+}
+
+// A unit square one unit above the origin, opening upwards.
+const SKYLIGHT: Portal = Portal {
+    corner: DVec3::new(-0.5, 1.0, -0.5),
+    edge1: DVec3::Z,
+    edge2: DVec3::X,
+};
+
+// The average of 1 / pdf over directions sampled through a portal is the
+// solid angle it subtends: 4 asin(1/5) for the skylight.
+#[test]
+fn portal_sampling_covers_the_opening() {
+    let portals = PortalDistribution::new(&[SKYLIGHT], &SolidBackground::new(DVec3::ONE));
+    let mut sampler = IndependentSampler::new(7);
+    sampler.start_pixel(0, 0, 0);
+    let mut solid_angle = 0.0;
+    for _ in 0..SAMPLES {
+        let u = sampler.next_1d();
+        let (direction, pdf) = portals.sample(DVec3::ZERO, u, sampler.next_2d()).unwrap();
+        assert!((pdf - portals.pdf(DVec3::ZERO, direction)).abs() <= 1e-9 * pdf);
+        solid_angle += 1.0 / pdf;
+    }
+    solid_angle /= SAMPLES as f64;
+    let expected = 4.0 * (0.2f64).asin();
+    assert!(
+        (solid_angle - expected).abs() < 0.01 * expected,
+        "{solid_angle} vs {expected}"
+    );
+    assert_eq!(portals.pdf(DVec3::ZERO, DVec3::X), 0.0);
+    // Points above the skylight aren't faced by it.
+    assert!(portals.faces(DVec3::ZERO));
+    assert!(!portals.faces(DVec3::new(0.0, 2.0, 0.0)));
+}
+
+// Bright in the east and dim elsewhere.
+struct EasternSky;
+
+impl Environment for EasternSky {
+    fn value(&self, direction: DVec3) -> DVec3 {
+        DVec3::splat(if direction.x > 0.0 { 10.0 } else { 0.1 })
+    }
+}
+
+// Portal samples head for the bright half of the sky, and still average
+// to the light coming through.
+#[test]
+fn portal_sampling_follows_the_radiance() {
+    let portals = PortalDistribution::new(&[SKYLIGHT], &EasternSky);
+    let mut sampler = IndependentSampler::new(9);
+    sampler.start_pixel(0, 0, 0);
+    let (mut estimate, mut east) = (0.0, 0);
+    for _ in 0..SAMPLES {
+        let u = sampler.next_1d();
+        let (direction, pdf) = portals.sample(DVec3::ZERO, u, sampler.next_2d()).unwrap();
+        estimate += EasternSky.value(direction).x / pdf;
+        east += (direction.x > 0.0) as u32;
+    }
+    estimate /= SAMPLES as f64;
+    assert!(east as f64 > 0.95 * SAMPLES as f64, "{east}");
+    // Half the opening's solid angle at each radiance.
+    let expected = 2.0 * (0.2f64).asin() * (10.0 + 0.1);
+    assert!(
+        (estimate - expected).abs() < 0.01 * expected,
+        "{estimate} vs {expected}"
+    );
+}
+
+// A floor under a ceiling with a hole in it, lit by a white sky: sampling
+// the sky through a portal over the hole must converge to the same
+// radiance as sampling it without.
+#[test]
+fn portals_leave_the_image_unchanged() {
+    let grey: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let at = |x: f64, y: f64, z: f64| DVec3::new(x, y, z);
+    let mut world = HittableList::new();
+    let mut add = |corners: [DVec3; 4]| {
+        for triangle in quad(corners, grey.clone()) {
+            world.push(Arc::new(triangle) as Arc<dyn Hittable>);
+        }
+    };
+    add([
+        at(-10.0, 0.0, -10.0),
+        at(-10.0, 0.0, 10.0),
+        at(10.0, 0.0, 10.0),
+        at(10.0, 0.0, -10.0),
+    ]);
+    // The ceiling around a unit hole above the origin.
+    add([
+        at(-10.0, 1.0, -10.0),
+        at(10.0, 1.0, -10.0),
+        at(10.0, 1.0, -0.5),
+        at(-10.0, 1.0, -0.5),
+    ]);
+    add([
+        at(-10.0, 1.0, 0.5),
+        at(10.0, 1.0, 0.5),
+        at(10.0, 1.0, 10.0),
+        at(-10.0, 1.0, 10.0),
+    ]);
+    add([
+        at(-10.0, 1.0, -0.5),
+        at(-0.5, 1.0, -0.5),
+        at(-0.5, 1.0, 0.5),
+        at(-10.0, 1.0, 0.5),
+    ]);
+    add([
+        at(0.5, 1.0, -0.5),
+        at(10.0, 1.0, -0.5),
+        at(10.0, 1.0, 0.5),
+        at(0.5, 1.0, 0.5),
+    ]);
+    let world: Arc<dyn Hittable> = Arc::new(world);
+
+    let radiance = |portals: &[Portal]| {
+        let mut lights = LightSet::new();
+        for portal in portals {
+            lights.add_portal(*portal);
+        }
+        let renderer = Renderer::new(world.clone(), Arc::new(SolidBackground::new(DVec3::ONE)))
+            .with_lights(Arc::new(lights));
+        let mut sampler = IndependentSampler::new(5);
+        let ray = Ray::new(DVec3::new(0.0, 0.5, -2.0), DVec3::new(0.0, -0.5, 2.0));
+        let settings = RenderSettings {
+            max_depth: 2,
+            ..RenderSettings::default()
+        };
+        let mut total = DVec3::ZERO;
+        for _ in 0..SAMPLES {
+            total += renderer.ray_color(&ray, &settings, &mut sampler);
+        }
+        total / SAMPLES as f64
+    };
+    let through_portal = radiance(&[SKYLIGHT]);
+    assert!(through_portal.x > 0.05, "{through_portal}");
+    assert_close(through_portal, radiance(&[]));
+}
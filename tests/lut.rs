@@ -0,0 +1,54 @@
+use glam::DVec3;
+use raytracer::lut::Lut;
+
+// A 2x2x2 table that inverts every channel, red varying fastest.
+const INVERT: &str = "TITLE \"invert\"
+# corners
+LUT_3D_SIZE 2
+1 1 1
+0 1 1
+1 0 1
+0 0 1
+1 1 0
+0 1 0
+1 0 0
+0 0 0
+";
+
+#[test]
+fn tables_are_interpolated() {
+    let invert = Lut::parse(INVERT).unwrap();
+    let color = invert.apply(DVec3::new(0.25, 0.5, 1.0));
+    assert!(
+        color.abs_diff_eq(DVec3::new(0.75, 0.5, 0.0), 1e-12),
+        "{color}"
+    );
+    // Inputs outside the domain are clamped to it.
+    let color = invert.apply(DVec3::new(-1.0, 2.0, 0.5));
+    assert!(
+        color.abs_diff_eq(DVec3::new(1.0, 0.0, 0.5), 1e-12),
+        "{color}"
+    );
+
+    let shaper = "LUT_1D_SIZE 3\nDOMAIN_MAX 2 2 2\n0 0 0\n0.25 0.5 0.5\n1 1 1\n";
+    let color = Lut::parse(shaper).unwrap().apply(DVec3::new(1.0, 1.0, 1.5));
+    assert!(
+        color.abs_diff_eq(DVec3::new(0.25, 0.5, 0.75), 1e-12),
+        "{color}"
+    );
+
+    // A shaper in front of a 3D table feeds it its output.
+    let both = format!("LUT_1D_SIZE 2\n0 0 0\n0.5 0.5 0.5\n{INVERT}");
+    let color = Lut::parse(&both).unwrap().apply(DVec3::ONE);
+    assert!(color.abs_diff_eq(DVec3::splat(0.5), 1e-12), "{color}");
+}
+
+#[test]
+fn malformed_files_are_rejected() {
+    let error = Lut::parse("LUT_3D_SIZE 2\n0 0 0\n0 0\n").err().unwrap();
+    assert_eq!(error.to_string(), "line 3: expected three numbers");
+    let error = Lut::parse("LUT_3D_SIZE 2\n0 0 0\n").err().unwrap();
+    assert_eq!(error.to_string(), "expected 8 entries, found 1");
+    assert!(Lut::parse("LUT_4D_SIZE 2\n").is_err());
+    assert!(Lut::parse("0 0 0\n1 1 1\n").is_err());
+}
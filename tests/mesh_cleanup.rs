@@ -0,0 +1,183 @@
+use glam::DVec3;
+use raytracer::material::Lambertian;
+use raytracer::objects::cleanup::{self, CleanupStats, MeshData};
+use raytracer::objects::obj;
+use raytracer::real::{wide, Real};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+#[test]
+fn dirty_meshes_are_repaired() {
+    let mut normals = vec![DVec3::Z; 7];
+    normals[3] = DVec3::NAN;
+    let mut mesh = MeshData {
+        positions: vec![
+            DVec3::new(0.0, 0.0, 0.0),
+            DVec3::new(1.0, 0.0, 0.0),
+            DVec3::new(1.0, 1.0, 0.0),
+            DVec3::new(0.0, 1.0, 0.0),
+            // Copies of the first and third corners.
+            DVec3::new(0.0, 0.0, 0.0),
+            DVec3::new(1.0, 1.0, 0.0),
+            DVec3::new(2.0, 0.0, 0.0),
+        ],
+        normals,
+        texcoords: Vec::new(),
+        colors: Vec::new(),
+        // The second face is wound backwards and the third has no area.
+        faces: vec![[0, 1, 2], [4, 3, 5], [0, 1, 4], [1, 6, 2]],
+    };
+    let stats = cleanup::clean(&mut mesh);
+    assert_eq!(
+        stats,
+        CleanupStats {
+            welded_vertices: 2,
+            degenerate_triangles: 1,
+            flipped_triangles: 1,
+            repaired_normals: 1,
+        }
+    );
+    assert_eq!(mesh.faces, [[0, 1, 2], [0, 2, 3], [1, 6, 2]]);
+    assert!(mesh.normals[3].abs_diff_eq(DVec3::Z, 1e-12));
+    assert_eq!(
+        stats.to_string(),
+        "2 vertices welded, 1 degenerate triangle dropped, 1 triangle flipped, 1 normal repaired"
+    );
+}
+
+#[test]
+fn vertices_of_different_colours_stay_apart() {
+    // Two triangles of a square, each with its own copies of the shared
+    // corners; the copies of the first corner differ in colour.
+    let mut mesh = MeshData {
+        positions: vec![
+            DVec3::new(0.0, 0.0, 0.0),
+            DVec3::new(1.0, 0.0, 0.0),
+            DVec3::new(1.0, 1.0, 0.0),
+            DVec3::new(0.0, 0.0, 0.0),
+            DVec3::new(1.0, 1.0, 0.0),
+            DVec3::new(0.0, 1.0, 0.0),
+        ],
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        colors: vec![
+            DVec3::X,
+            DVec3::ONE,
+            DVec3::ONE,
+            DVec3::Y,
+            DVec3::ONE,
+            DVec3::ONE,
+        ],
+        faces: vec![[0, 1, 2], [3, 4, 5]],
+    };
+    let stats = cleanup::clean(&mut mesh);
+    assert_eq!(stats.welded_vertices, 1);
+    assert_eq!(mesh.faces, [[0, 1, 2], [3, 2, 5]]);
+}
+
+#[test]
+fn obj_files_are_cleaned_on_import() {
+    let dir = std::env::temp_dir().join("raytracer-mesh-cleanup");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("dirty.obj");
+    let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\nf 1 2 4\nf 3 5 4\n";
+    std::fs::write(&path, source).unwrap();
+    let fallback = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let (triangles, stats) = obj::load_cleaned(path.to_str().unwrap(), fallback).unwrap();
+    assert_eq!(triangles.len(), 2);
+    assert_eq!(stats.degenerate_triangles, 1);
+    assert_eq!(stats.flipped_triangles, 1);
+    let normal = |t: &raytracer::objects::triangle::Triangle| {
+        let [a, b, c] = t.vertices();
+        (b - a).cross(c - a).normalize()
+    };
+    // Triangles keep their vertices at `Real` precision.
+    let tolerance = 1e4 * wide(Real::EPSILON);
+    assert!(normal(&triangles[0]).abs_diff_eq(normal(&triangles[1]), tolerance));
+}
+
+// A cube of half-size `size` centred on the origin, with its faces wound
+// inwards.
+fn inward_cube(mesh: &mut MeshData, size: f64) {
+    let first = mesh.positions.len();
+    for i in 0..8 {
+        let corner = |bit: usize| if i & bit == 0 { -size } else { size };
+        let position = DVec3::new(corner(1), corner(2), corner(4));
+        mesh.positions.push(position);
+    }
+    let quads = [
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+    ];
+    for [a, b, c, d] in quads {
+        mesh.faces.push([a, b, c].map(|i| first + i));
+        mesh.faces.push([a, c, d].map(|i| first + i));
+    }
+}
+
+fn faces_outward(mesh: &MeshData, face: [usize; 3]) -> bool {
+    let [a, b, c] = face.map(|i| mesh.positions[i]);
+    (b - a).cross(c - a).dot((a + b + c) / 3.0) > 0.0
+}
+
+#[test]
+fn closed_meshes_are_turned_outwards() {
+    let mut mesh = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        colors: Vec::new(),
+        faces: Vec::new(),
+    };
+    inward_cube(&mut mesh, 2.0);
+    inward_cube(&mut mesh, 1.0);
+    assert!(!faces_outward(&mesh, mesh.faces[0]));
+    // Only the outer cube turns; the inner one bounds a cavity.
+    assert_eq!(cleanup::orient_outward(&mut mesh), 12);
+    assert!(mesh.faces[..12].iter().all(|f| faces_outward(&mesh, *f)));
+    assert!(mesh.faces[12..].iter().all(|f| !faces_outward(&mesh, *f)));
+    assert_eq!(cleanup::orient_outward(&mut mesh), 0);
+}
+
+#[test]
+fn normals_are_generated_for_smooth_shading() {
+    let dir = std::env::temp_dir().join("raytracer-mesh-cleanup");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ridge.obj");
+    // Two faces meeting at a right angle along the ridge from vertex 1 to
+    // vertex 2, the second twice the size of the first.
+    let source = "v 0 0 0\nv 0 0 1\nv 1 0 0\nv 0 2 0\nf 1 3 2\nf 1 2 4\n";
+    std::fs::write(&path, source).unwrap();
+    let path = path.to_str().unwrap();
+    let fallback = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+
+    let (triangles, _) = obj::load_cleaned(path, fallback.clone()).unwrap();
+    assert!(triangles.iter().all(|t| t.normals().is_none()));
+
+    let options = obj::ObjOptions {
+        generate_normals: true,
+        ..obj::ObjOptions::default()
+    };
+    let (triangles, _) = obj::load_with(path, fallback.clone(), &options).unwrap();
+    let normals = triangles[0].normals().unwrap();
+    let ridge = DVec3::new(-2.0, -1.0, 0.0).normalize();
+    let tolerance = 1e4 * wide(Real::EPSILON);
+    assert!(normals[0].abs_diff_eq(ridge, tolerance), "{}", normals[0]);
+    assert!(
+        normals[1].abs_diff_eq(-DVec3::Y, tolerance),
+        "{}",
+        normals[1]
+    );
+    assert_eq!(triangles[1].normals().unwrap()[0], normals[0]);
+
+    let flat = obj::ObjOptions {
+        shading: obj::Shading::Flat,
+        ..options
+    };
+    let (triangles, _) = obj::load_with(path, fallback, &flat).unwrap();
+    assert!(triangles.iter().all(|t| t.normals().is_none()));
+}
@@ -0,0 +1,262 @@
+use glam::{DMat3, DVec3};
+use raytracer::hittable::Hittable;
+use raytracer::interval::Interval;
+use raytracer::material::Lambertian;
+use raytracer::objects::{obj, ply, stl};
+use raytracer::ray::Ray;
+use raytracer::real::{wide, Real};
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+const CORNERS: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+#[test]
+fn stl_files_are_read_in_both_encodings() {
+    let ascii = "solid tri
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid tri
+";
+    // A binary header that starts like an ASCII file.
+    let mut binary = b"solid but binary".to_vec();
+    binary.resize(80, 0);
+    binary.extend(1u32.to_le_bytes());
+    for value in [0.0, 0.0, 1.0].iter().chain(CORNERS.iter().flatten()) {
+        binary.extend(f32::to_le_bytes(*value));
+    }
+    binary.extend([0, 0]);
+
+    for bytes in [ascii.as_bytes(), &binary] {
+        let mesh = stl::parse(bytes).unwrap();
+        assert_eq!(mesh.faces, [[0, 1, 2]]);
+        let expected = CORNERS.map(|c| DVec3::from_array(c.map(f64::from)));
+        assert_eq!(mesh.positions, expected);
+    }
+}
+
+#[test]
+fn ply_files_keep_vertex_colours() {
+    let ascii = "ply
+format ascii 1.0
+comment a unit square with a red and a white corner
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 255 255 255
+1 1 0 255 255 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+    let mut binary = b"ply
+format binary_big_endian 1.0
+element vertex 3
+property double x
+property double y
+property double z
+element material 1
+property float shininess
+element face 1
+property list uchar uint vertex_index
+end_header
+"
+    .to_vec();
+    for value in CORNERS.iter().flatten() {
+        binary.extend(f64::from(*value).to_be_bytes());
+    }
+    binary.extend(0.5f32.to_be_bytes());
+    binary.push(3);
+    for index in [0u32, 1, 2] {
+        binary.extend(index.to_be_bytes());
+    }
+
+    let square = ply::parse(ascii.as_bytes()).unwrap();
+    assert_eq!(square.faces, [[0, 1, 2], [0, 2, 3]]);
+    assert_eq!(square.colors.len(), 4);
+    assert_eq!(square.colors[0], DVec3::X);
+    assert_eq!(square.colors[1], DVec3::ONE);
+
+    let triangle = ply::parse(&binary).unwrap();
+    assert_eq!(triangle.faces, [[0, 1, 2]]);
+    assert_eq!(triangle.positions[2], DVec3::Y);
+    assert!(triangle.colors.is_empty());
+
+    let dir = std::env::temp_dir().join("raytracer-mesh-formats");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("square.ply");
+    std::fs::write(&path, ascii).unwrap();
+    let fallback = Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let (triangles, _) = obj::load_cleaned(path.to_str().unwrap(), fallback).unwrap();
+    assert_eq!(triangles.len(), 2);
+
+    // The colours tint the scene's material, interpolated across each
+    // triangle: halfway from the red corner, in a grey material.
+    let scene = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - {{ type: mesh, path: '{}', material: {{ type: lambertian, texture: {{ type: solid_color, color: [0.5, 0.5, 0.5] }} }} }}
+",
+        path.display()
+    );
+    let (_, _, world, _) = Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+    let ray = Ray::new(DVec3::new(0.5, 0.25, 5.0), DVec3::NEG_Z);
+    let rec = world.hit(&ray, Interval::after(1e-3)).unwrap();
+    let albedo = rec.material.albedo(&rec);
+    let expected = DVec3::new(0.5, 0.25, 0.25);
+    let tolerance = 1e3 * wide(Real::EPSILON);
+    assert!(albedo.abs_diff_eq(expected, tolerance), "{albedo}");
+}
+
+#[test]
+fn scenes_convert_mesh_units_and_up_axes() {
+    // A 1000 mm square standing up in Z, as CAD tools write them, with its
+    // normals pointing along -Y.
+    let path = std::env::temp_dir().join("raytracer-z-up.obj");
+    let obj = "v 0 0 0\nv 1000 0 0\nv 1000 0 1000\nv 0 0 1000\n\
+               vn 0 -1 0\nf 1//1 2//1 3//1\nf 1//1 3//1 4//1\n";
+    std::fs::write(&path, obj).unwrap();
+    let load = |scene_import: &str, mesh_import: &str| {
+        let scene = format!(
+            "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+{scene_import}
+objects:
+  - {{ type: mesh, path: '{}', material: {{ type: lambertian, texture: {{ type: solid_color, color: [1, 1, 1] }} }} {mesh_import} }}
+",
+            path.display()
+        );
+        let (_, _, world, _) = Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+        world
+    };
+    let interval = Interval::after(1e-3);
+    // Triangles keep their vertices at `Real` precision.
+    let tolerance = 1e3 * wide(Real::EPSILON);
+    let toward_floor = Ray::new(DVec3::new(0.5, 5.0, 0.5), DVec3::NEG_Y);
+    let toward_wall = Ray::new(DVec3::new(0.5, 0.5, 5.0), DVec3::NEG_Z);
+
+    // In metres and Y up, the file would be a wall a kilometre across.
+    let world = load("units: mm\nup_axis: z", "");
+    let rec = world.hit(&toward_wall, interval).unwrap();
+    assert!((rec.t - 5.0).abs() < tolerance * 5.0, "{}", rec.t);
+    assert!(
+        rec.normal.abs_diff_eq(DVec3::Z, tolerance),
+        "{}",
+        rec.normal
+    );
+    let bounds = world.bounding_box().unwrap();
+    assert!((bounds.max.x - 1.0).abs() < 1e-6 && (bounds.max.y - 1.0).abs() < 1e-6);
+    assert!(world.hit(&toward_floor, interval).is_none());
+
+    // Meshes override the scene.
+    let world = load("units: mm\nup_axis: z", ", up_axis: y");
+    let rec = world.hit(&toward_floor, interval).unwrap();
+    assert!((rec.t - 5.0).abs() < tolerance * 5.0, "{}", rec.t);
+    let world = load("", ", units: millimetres, up_axis: z");
+    assert!(world.hit(&toward_wall, interval).is_some());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn meshes_outside_the_scene_are_read_in_its_units() {
+    // What `bake_scene` reads its mesh with.
+    let scene = "
+camera: { lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }
+units: mm
+up_axis: z
+objects:
+  - { type: mesh, path: 'prop.obj', up_axis: y, material: { type: lambertian, texture: { type: solid_color, color: [1, 1, 1] } } }
+";
+    let config = SceneFormat::Yaml.parse(scene).unwrap();
+    let millimetres = DMat3::from_diagonal(DVec3::splat(0.001));
+
+    // As the object that loads it reads it, or else as the scene reads
+    // meshes.
+    let options = config.mesh_options("prop.obj").unwrap();
+    assert!(options.conversion.unwrap().abs_diff_eq(millimetres, 1e-15));
+    let options = config.mesh_options("unplaced.obj").unwrap();
+    let z_up = millimetres * DMat3::from_cols(DVec3::X, DVec3::NEG_Z, DVec3::Y);
+    assert!(options.conversion.unwrap().abs_diff_eq(z_up, 1e-15));
+}
+
+#[test]
+fn copies_of_a_mesh_keep_their_own_materials() {
+    // A square whose upper-left half is green in its MTL library and whose
+    // other half takes the object's material.
+    let dir = std::env::temp_dir().join("raytracer-shared-mesh");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("square.mtl"), "newmtl green\nKd 0 1 0\n").unwrap();
+    let obj = "mtllib square.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+               f 1 2 3\nusemtl green\nf 1 3 4\n";
+    let path = dir.join("square.obj");
+    std::fs::write(&path, obj).unwrap();
+    let scene = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - {{ type: mesh, path: '{path}', material: {{ type: lambertian, texture: {{ type: solid_color, color: [1, 0, 0] }} }} }}
+  - type: node
+    transform: {{ translate: [10, 0, 0] }}
+    children:
+      - {{ type: mesh, path: '{path}', material: {{ type: lambertian, texture: {{ type: solid_color, color: [0, 0, 1] }} }} }}
+",
+        path = path.display()
+    );
+    let (_, _, world, _) = Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+    let albedo = |x: f64, y: f64| {
+        let ray = Ray::new(DVec3::new(x, y, 5.0), DVec3::NEG_Z);
+        let rec = world.hit(&ray, Interval::after(1e-3)).unwrap();
+        rec.material.albedo(&rec)
+    };
+    assert!(albedo(0.75, 0.25).abs_diff_eq(DVec3::X, 1e-9));
+    assert!(albedo(10.75, 0.25).abs_diff_eq(DVec3::Z, 1e-9));
+    for x in [0.25, 10.25] {
+        assert!(albedo(x, 0.75).abs_diff_eq(DVec3::Y, 1e-9));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_mtl_libraries_fail_unless_ignored() {
+    let dir = std::env::temp_dir().join("raytracer-missing-mtl");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("square.obj");
+    let obj = "mtllib gone.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl green\nf 1 2 3\n";
+    std::fs::write(&path, obj).unwrap();
+    let scene = |use_mtl: bool| {
+        format!(
+            "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - {{ type: mesh, path: '{}', use_mtl: {use_mtl}, material: {{ type: lambertian, texture: {{ type: solid_color, color: [1, 0, 0] }} }} }}
+",
+            path.display()
+        )
+    };
+    let error = Scene::from_source_at(&scene(true), SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    assert!(
+        error.to_string().contains("couldn't read its MTL library"),
+        "{error}"
+    );
+
+    let (_, _, world, _) = Scene::from_source_at(&scene(false), SceneFormat::Yaml, 0.0).unwrap();
+    let ray = Ray::new(DVec3::new(0.75, 0.25, 5.0), DVec3::NEG_Z);
+    let rec = world.hit(&ray, Interval::after(1e-3)).unwrap();
+    assert!(rec.material.albedo(&rec).abs_diff_eq(DVec3::X, 1e-9));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,66 @@
+use raytracer::metrics::{self, RenderProgress};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn get(address: SocketAddr, target: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn endpoint_serves_prometheus_and_json() {
+    let progress = Arc::new(RenderProgress::new());
+    progress.start(100, 4);
+    progress.add_tile(25, 400);
+    let address = metrics::serve("127.0.0.1:0", progress.clone()).unwrap();
+
+    let text = get(address, "/metrics");
+    assert!(text.starts_with("HTTP/1.1 200 OK"), "{text}");
+    assert!(text.contains("\nraytracer_progress_ratio 0.25\n"), "{text}");
+    assert!(
+        text.contains("\nraytracer_camera_rays_total 400\n"),
+        "{text}"
+    );
+    assert!(text.contains("# TYPE raytracer_pixels_rendered_total counter"));
+
+    progress.add_tile(75, 600);
+    progress.finish();
+    let json = get(address, "/metrics.json");
+    let body = json.split("\r\n\r\n").nth(1).unwrap();
+    let value: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(value["progress"], 1.0);
+    assert_eq!(value["camera_rays"], 1000);
+
+    assert!(get(address, "/other").starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn listeners_hear_of_every_tile() {
+    let progress = RenderProgress::new();
+    let updates = progress.subscribe();
+    progress.start(100, 4);
+    assert_eq!(progress.snapshot().eta_seconds, None);
+    std::thread::sleep(Duration::from_millis(20));
+    progress.add_tile(25, 100);
+    progress.add_tile(25, 100);
+
+    let update = updates.try_recv().unwrap();
+    assert_eq!((update.tiles_done, update.total_tiles), (1, 4));
+    assert_eq!(update.pixels_done, 25);
+    // Three times as long again for the other three quarters.
+    let eta = update.eta_seconds.unwrap();
+    assert!((eta - 3.0 * update.elapsed_seconds).abs() < 1e-9, "{eta}");
+    let update = updates.try_recv().unwrap();
+    assert_eq!(update.tiles_done, 2);
+    assert!(updates.try_recv().is_err());
+
+    progress.finish();
+    let update = updates.try_recv().unwrap();
+    assert_eq!(update.eta_seconds, Some(0.0));
+    assert!(update.progress < 1.0);
+}
@@ -0,0 +1,297 @@
+use glam::DVec3;
+use raytracer::color_space::ColorSpace;
+use raytracer::error::RenderError;
+use raytracer::mipmap::{FootprintScope, MipmappedTexture, ReloadingTexture, TextureWatch};
+use raytracer::output::TransferFunction;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::texture::Texture;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+// A 4x2 texture whose left half is black and right half white.
+fn split_texture() -> MipmappedTexture {
+    let texels = (0..8)
+        .map(|i| if i % 4 < 2 { DVec3::ZERO } else { DVec3::ONE })
+        .collect();
+    MipmappedTexture::from_texels(4, 2, texels)
+}
+
+#[test]
+fn pyramid_halves_down_to_one_texel() {
+    let texture = split_texture();
+    assert_eq!(texture.levels(), 3);
+    // The last level is the average of the whole image.
+    assert!(texture
+        .sample(0.3, 0.7, 2.0)
+        .abs_diff_eq(DVec3::splat(0.5), 1e-12));
+}
+
+#[test]
+fn bilinear_blends_between_texel_centres() {
+    let texture = split_texture();
+    // Texel centres at u = 0.375 and 0.625 straddle the edge.
+    let edge = texture.value(0.5, 0.5, DVec3::ZERO);
+    assert!(edge.abs_diff_eq(DVec3::splat(0.5), 1e-12), "{edge}");
+    let inside = texture.value(0.625, 0.5, DVec3::ZERO);
+    assert!(inside.abs_diff_eq(DVec3::ONE, 1e-12), "{inside}");
+}
+
+#[test]
+fn wide_footprints_select_coarser_levels() {
+    let texture = split_texture();
+    assert_eq!(texture.level_for_footprint(0.0), 0.0);
+    assert!((texture.level_for_footprint(0.5) - 1.0).abs() < 1e-12);
+    assert_eq!(texture.level_for_footprint(10.0), 2.0);
+}
+
+// Writes a 1x1 image of `grey` to `path`, dated `age` seconds in the past
+// so that rewrites are seen as changes whatever the clock's resolution.
+fn write_grey(path: &Path, grey: u8, age: u64) {
+    image::RgbImage::from_pixel(1, 1, image::Rgb([grey; 3]))
+        .save(path)
+        .unwrap();
+    backdate(path, age);
+}
+
+fn backdate(path: &Path, age: u64) {
+    let modified = SystemTime::now() - Duration::from_secs(age);
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(modified).unwrap();
+}
+
+#[test]
+fn unreadable_files_are_typed_errors() {
+    let dir = std::env::temp_dir().join("raytracer-texture-errors");
+    std::fs::create_dir_all(&dir).unwrap();
+    let missing = dir.join("missing.png");
+    let error = MipmappedTexture::new(missing.to_str().unwrap(), ColorSpace::Srgb)
+        .err()
+        .unwrap();
+    assert!(matches!(error, RenderError::Io { .. }), "{error}");
+    assert_eq!(error.path(), missing);
+
+    // An image texture that isn't an image fails the scene that uses it.
+    let corrupt = dir.join("corrupt.png");
+    std::fs::write(&corrupt, "not a png").unwrap();
+    let scene = dir.join("scene.yaml");
+    let source = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: lambertian
+      texture: {{ type: image, path: '{}', filter: bilinear }}
+",
+        corrupt.display()
+    );
+    std::fs::write(&scene, source).unwrap();
+    let error = Scene::from_file(scene.to_str().unwrap()).err().unwrap();
+    assert!(matches!(error, RenderError::Image { .. }), "{error}");
+    assert_eq!(error.path(), corrupt);
+
+    let error = Scene::from_file(dir.join("missing.yaml").to_str().unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(error, RenderError::Io { .. }), "{error}");
+}
+
+#[test]
+fn watched_textures_reload_when_their_files_change() {
+    let path = std::env::temp_dir().join("raytracer-reloading.png");
+    write_grey(&path, 0, 20);
+    let read = |path: &str| MipmappedTexture::new(path, ColorSpace::Srgb);
+    let texture = Arc::new(ReloadingTexture::new(path.to_str().unwrap(), read).unwrap());
+    let watch = TextureWatch::new();
+    watch.add(texture.clone());
+    let mut errors = Vec::new();
+    assert!(!watch.reload_changed(|e| errors.push(e)));
+
+    write_grey(&path, 255, 10);
+    assert!(watch.reload_changed(|e| errors.push(e)));
+    assert_eq!(texture.value(0.5, 0.5, DVec3::ZERO), DVec3::ONE);
+
+    // A broken rewrite is reported once and the last image kept.
+    std::fs::write(&path, "not a png").unwrap();
+    backdate(&path, 5);
+    assert!(!watch.reload_changed(|e| errors.push(e)));
+    assert!(!watch.reload_changed(|e| errors.push(e)));
+    assert_eq!(errors.len(), 1);
+    assert!(
+        matches!(errors[0], RenderError::Image { .. }),
+        "{}",
+        errors[0]
+    );
+    assert_eq!(texture.value(0.5, 0.5, DVec3::ZERO), DVec3::ONE);
+}
+
+#[test]
+fn images_are_decoded_to_linear_light() {
+    let path = std::env::temp_dir().join("raytracer-mid-grey.png");
+    write_grey(&path, 128, 0);
+    let path = path.to_str().unwrap();
+    let value = |color_space| {
+        let texture = MipmappedTexture::new(path, color_space).unwrap();
+        texture.value(0.5, 0.5, DVec3::ZERO).x
+    };
+    let srgb = value(ColorSpace::Srgb);
+    assert!((srgb - 0.2158).abs() < 1e-4, "{srgb}");
+    assert_eq!(value(ColorSpace::Linear), 128.0 / 255.0);
+    assert!(value(ColorSpace::Rec709) < 128.0 / 255.0);
+
+    // Encoding for display gives back the code value.
+    let encoded = TransferFunction::Srgb.encode(srgb) * 255.0;
+    assert!((encoded - 128.0).abs() < 1e-6, "{encoded}");
+    assert_eq!(TransferFunction::Linear.encode(0.25), 0.25);
+}
+
+#[test]
+fn textures_decode_from_bytes() {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]))
+        .write_to(&mut bytes, image::ImageFormat::Png)
+        .unwrap();
+    let texture = MipmappedTexture::from_bytes(bytes.get_ref(), ColorSpace::Srgb).unwrap();
+    assert_eq!(texture.levels(), 2);
+    let red = texture.value(0.5, 0.5, DVec3::ZERO);
+    assert!(red.abs_diff_eq(DVec3::X, 1e-9), "{red}");
+
+    let error = MipmappedTexture::from_bytes(b"not an image", ColorSpace::Srgb);
+    assert!(matches!(error, Err(RenderError::Image { .. })));
+}
+
+#[test]
+fn scenes_decode_each_image_once() {
+    let dir = std::env::temp_dir().join("raytracer-shared-images");
+    std::fs::create_dir_all(&dir).unwrap();
+    let image = dir.join("grey.png");
+    write_grey(&image, 128, 0);
+    let scene = dir.join("scene.yaml");
+    // Three textures read the file alike, two of them inline; the linear
+    // one needs a copy of its own.
+    let source = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+textures:
+  grey: {{ type: image, path: '{path}', filter: bilinear }}
+objects:
+  - type: sphere
+    center: [-2, 0, 0]
+    radius: 1
+    material: {{ type: lambertian, texture: {{ type: image, path: '{path}', filter: bilinear }} }}
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material: {{ type: metal, texture: {{ type: image, path: '{path}', filter: bilinear }}, fuzz: 0 }}
+  - type: sphere
+    center: [2, 0, 0]
+    radius: 1
+    material: {{ type: lambertian, texture: grey }}
+  - type: sphere
+    center: [0, 2, 0]
+    radius: 1
+    material:
+      type: lambertian
+      texture: {{ type: image, path: '{path}', filter: bilinear, color_space: linear }}
+",
+        path = image.display()
+    );
+    std::fs::write(&scene, source).unwrap();
+    let watch = TextureWatch::new();
+    Scene::from_file_watching(scene.to_str().unwrap(), &watch).unwrap();
+    assert_eq!(watch.len(), 2);
+}
+
+// Normal maps are data: without a `color_space` they are read linear, as
+// they were before images were decoded, not as sRGB colours.
+#[test]
+fn normal_maps_render_linear_by_default() {
+    let path = std::env::temp_dir().join("raytracer-tilted-normals.png");
+    image::RgbImage::from_pixel(1, 1, image::Rgb([200, 128, 230]))
+        .save(&path)
+        .unwrap();
+    let render = |color_space: &str| {
+        let source = format!(
+            "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 30, aperture: 0, focus_dist: 5 }}
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: normal_mapped
+      material: {{ type: lambertian, texture: {{ type: solid_color, color: [0.8, 0.8, 0.8] }} }}
+      normal_map: {{ type: image, path: '{}', filter: bilinear{color_space} }}
+",
+            path.display()
+        );
+        let (config, camera, world, lights) =
+            Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+        let settings = RenderSettings {
+            width: 8,
+            height: 8,
+            samples_per_pixel: 4,
+            ..config.render
+        };
+        Renderer::new(world, config.environment().unwrap())
+            .with_lights(lights)
+            .render(&camera, &settings)
+            .pixels
+    };
+    let default = render("");
+    assert_eq!(default, render(", color_space: linear"));
+    assert_ne!(default, render(", color_space: srgb"));
+}
+
+// A ball glowing with a checker of single texels, far finer than the
+// pixels it is seen through: each camera ray looks up a level coarse
+// enough to average the checker, where the finest level would give black
+// or white.
+#[test]
+fn renders_filter_textures_by_pixel_footprint() {
+    let path = std::env::temp_dir().join("raytracer-fine-checker.png");
+    image::RgbImage::from_fn(256, 256, |x, y| image::Rgb([((x + y) % 2 * 255) as u8; 3]))
+        .save(&path)
+        .unwrap();
+    let source = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 30, aperture: 0, focus_dist: 5 }}
+background: {{ type: solid, color: [0, 0, 0] }}
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: diffuse_light
+      texture: {{ type: image, path: '{}', filter: bilinear, color_space: linear }}
+",
+        path.display()
+    );
+    let (config, camera, world, lights) =
+        Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    let settings = RenderSettings {
+        width: 8,
+        height: 8,
+        samples_per_pixel: 1,
+        ..config.render
+    };
+    let image = Renderer::new(world, config.environment().unwrap())
+        .with_lights(lights)
+        .render(&camera, &settings);
+    for (x, y) in [(3, 3), (4, 3), (3, 4), (4, 4)] {
+        let pixel = image.pixels[y * 8 + x];
+        assert!(pixel.abs_diff_eq(DVec3::splat(0.5), 0.05), "{pixel}");
+    }
+
+    // Lookups outside a render still read the full resolution.
+    let texture = MipmappedTexture::new(path.to_str().unwrap(), ColorSpace::Linear).unwrap();
+    let texel = texture.value(0.5 / 256.0, 1.0 - 0.5 / 256.0, DVec3::ZERO);
+    assert_eq!(texel, DVec3::ZERO);
+    let _scope = FootprintScope::enter(0.5);
+    let average = texture.value(0.5 / 256.0, 1.0 - 0.5 / 256.0, DVec3::ZERO);
+    assert!(average.abs_diff_eq(DVec3::splat(0.5), 1e-9), "{average}");
+}
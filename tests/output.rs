@@ -0,0 +1,76 @@
+use glam::DVec3;
+use raytracer::output::{self, bracket_path, OutputOptions};
+use raytracer::renderer::ImageBuffer;
+use std::path::Path;
+
+#[test]
+fn brackets_are_named_by_their_offset() {
+    let path = Path::new("renders/frame.png");
+    assert_eq!(
+        bracket_path(path, -2.0).unwrap(),
+        Path::new("renders/frame.ev-2.png")
+    );
+    assert_eq!(
+        bracket_path(path, 0.0).unwrap(),
+        Path::new("renders/frame.ev+0.png")
+    );
+    assert_eq!(
+        bracket_path(path, 1.5).unwrap(),
+        Path::new("renders/frame.ev+1.5.png")
+    );
+}
+
+#[test]
+fn brackets_share_one_image() {
+    let dir = std::env::temp_dir().join("raytracer-brackets");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut image = ImageBuffer::new(2, 1);
+    image.pixels.fill(DVec3::splat(0.25));
+    let options = OutputOptions {
+        exposure: 1.0,
+        brackets: vec![-2.0, 0.0, 2.0],
+        ..OutputOptions::default()
+    };
+    let path = dir.join("frame.hdr");
+    output::save(&image, &path, &options, None).unwrap();
+    for (offset, expected) in [(-2.0, 0.125), (0.0, 0.5), (2.0, 2.0)] {
+        let bracket = image::open(bracket_path(&path, offset).unwrap())
+            .unwrap()
+            .to_rgb32f();
+        assert!(bracket.pixels().all(|p| p.0 == [expected; 3]), "{offset}");
+    }
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        std::fs::read(bracket_path(&path, 0.0).unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn png_alpha_is_straight() {
+    let dir = std::env::temp_dir().join("raytracer-alpha");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut image = ImageBuffer::new(2, 1);
+    // Half-covered mid grey, premultiplied, next to a clear pixel.
+    image.pixels[0] = DVec3::splat(0.25);
+    image.alpha = Some(vec![0.5, 0.0]);
+    let options = OutputOptions {
+        dither: false,
+        ..OutputOptions::default()
+    };
+    let path = dir.join("frame.png");
+    output::save(&image, &path, &options, None).unwrap();
+    let png = image::open(&path).unwrap().to_rgba32f();
+    let (covered, clear) = (png.get_pixel(0, 0).0, png.get_pixel(1, 0).0);
+    assert!((covered[3] - 0.5).abs() < 0.01, "{covered:?}");
+    assert_eq!(clear[3], 0.0);
+    let unpremultiplied = image::open(&path).unwrap().to_rgba8();
+    let mut opaque = ImageBuffer::new(1, 1);
+    opaque.pixels[0] = DVec3::splat(0.5);
+    let opaque_path = dir.join("opaque.png");
+    output::save(&opaque, &opaque_path, &options, None).unwrap();
+    let expected = image::open(&opaque_path).unwrap().to_rgb8();
+    assert_eq!(
+        unpremultiplied.get_pixel(0, 0).0[..3],
+        expected.get_pixel(0, 0).0
+    );
+}
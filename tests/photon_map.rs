@@ -0,0 +1,100 @@
+use glam::DVec3;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::lights::{AnalyticLight, LightSet, PointLight};
+use raytracer::material::{Lambertian, Material, Metal};
+use raytracer::objects::triangle::Triangle;
+use raytracer::photon_map::PhotonMap;
+use raytracer::ray::Ray;
+use raytracer::renderer::{Integrator, RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+fn quad(height: f64, material: Arc<dyn Material>) -> [Triangle; 2] {
+    let [a, b, c, d] = [(-10.0, -10.0), (-10.0, 10.0), (10.0, 10.0), (10.0, -10.0)]
+        .map(|(x, z)| DVec3::new(x, height, z));
+    [
+        Triangle::new([a, b, c], material.clone()),
+        Triangle::new([a, c, d], material),
+    ]
+}
+
+// A grey floor with a point light one unit above it, under a mirror at
+// height 2 when `mirrored` is set. The light's reflection lights the floor
+// as if from height 3.
+fn room(mirrored: bool) -> (Arc<dyn Hittable>, Arc<LightSet>) {
+    let floor: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let mirror: Arc<dyn Material> =
+        Arc::new(Metal::new(Arc::new(SolidColor::new(DVec3::ONE)), 0.0));
+    let mut world = HittableList::new();
+    for triangle in quad(0.0, floor) {
+        world.push(Arc::new(triangle));
+    }
+    if mirrored {
+        for triangle in quad(2.0, mirror) {
+            world.push(Arc::new(triangle));
+        }
+    }
+    let mut lights = LightSet::new();
+    lights.add_analytic(AnalyticLight::Point(PointLight {
+        position: DVec3::new(0.0, 1.0, 0.0),
+        intensity: DVec3::splat(18.0 * PI),
+        radius: 0.0,
+        profile: None,
+    }));
+    (Arc::new(world), Arc::new(lights))
+}
+
+fn floor_radiance(world: &dyn Hittable, photons: &PhotonMap, x: f64) -> DVec3 {
+    let ray = Ray::new(DVec3::new(x, 0.5, 0.0), -DVec3::Y);
+    let rec = world.hit(&ray, Interval::after(1e-4)).unwrap();
+    photons.radiance(&ray, &rec)
+}
+
+#[test]
+fn mirrors_cast_caustics() {
+    let (world, lights) = room(true);
+    let photons = PhotonMap::build(&*world, &lights, 400_000, 0.25, 8, 1);
+    assert!(!photons.is_empty());
+    // Irradiance of 2π from the reflection, on a floor of albedo 0.5.
+    let radiance = floor_radiance(&*world, &photons, 0.0);
+    assert!(
+        (radiance - DVec3::ONE).abs().max_element() < 0.15,
+        "{radiance}"
+    );
+    // Further out the reflection arrives at a grazing angle.
+    assert!(floor_radiance(&*world, &photons, 6.0).max_element() < 0.15);
+
+    let again = PhotonMap::build(&*world, &lights, 400_000, 0.25, 8, 1);
+    assert_eq!(again.len(), photons.len());
+}
+
+#[test]
+fn direct_light_is_not_a_caustic() {
+    let (world, lights) = room(false);
+    let photons = PhotonMap::build(&*world, &lights, 10_000, 0.25, 8, 1);
+    assert!(photons.is_empty());
+    assert_eq!(floor_radiance(&*world, &photons, 0.0), DVec3::ZERO);
+}
+
+#[test]
+fn only_the_photon_integrator_traces_photons() {
+    let (world, lights) = room(true);
+    let renderer =
+        Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ZERO))).with_lights(lights);
+    assert!(renderer.photon_map(&RenderSettings::default()).is_none());
+    let settings = RenderSettings {
+        integrator: Integrator::Photon {
+            photons: 1000,
+            radius: 0.1,
+        },
+        ..RenderSettings::default()
+    };
+    assert!(renderer
+        .photon_map(&settings)
+        .is_some_and(|map| !map.is_empty()));
+}
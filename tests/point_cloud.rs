@@ -0,0 +1,153 @@
+use glam::DVec3;
+use raytracer::hittable::Hittable;
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::point_cloud::{parse_xyz, CloudPoint, PointCloud, Splat};
+use raytracer::ray::Ray;
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn grey() -> Arc<dyn Material> {
+    let texture = Arc::new(SolidColor::new(DVec3::splat(0.5)));
+    Arc::new(Lambertian::new(texture))
+}
+
+// A 100 x 100 grid of points in the z = 0 plane, 0.1 apart.
+fn grid(normal: Option<DVec3>) -> Vec<CloudPoint> {
+    (0..10_000)
+        .map(|i| CloudPoint {
+            position: DVec3::new((i % 100) as f64 * 0.1, (i / 100) as f64 * 0.1, 0.0),
+            normal,
+            color: None,
+        })
+        .collect()
+}
+
+#[test]
+fn xyz_lines_give_positions_colours_and_normals() {
+    let points = parse_xyz("# scan\n1 2 3\n\n0,0,0, 255,0,0\n0 0 1 0 255 0 0 0 2\n").unwrap();
+    assert_eq!(points.len(), 3);
+    assert_eq!(points[0].position, DVec3::new(1.0, 2.0, 3.0));
+    assert_eq!(points[0].color, None);
+    assert!(points[1].color.unwrap().abs_diff_eq(DVec3::X, 1e-9));
+    assert!(points[2].color.unwrap().abs_diff_eq(DVec3::Y, 1e-9));
+    assert_eq!(points[2].normal, Some(DVec3::new(0.0, 0.0, 2.0)));
+
+    let error = parse_xyz("1 2 3\n1 2 3 4\n").err().unwrap();
+    assert_eq!(error, "line 2: expected 3, 6 or 9 values, found 4");
+}
+
+#[test]
+fn splats_are_hit_within_their_radius() {
+    let interval = Interval::after(1e-3);
+    for splat in [Splat::Disk, Splat::Sphere] {
+        let cloud = PointCloud::new(&grid(Some(DVec3::Z)), 0.03, splat, grey());
+        assert_eq!(cloud.point_count(), 10_000);
+        let down = |x: f64, y: f64| Ray::new(DVec3::new(x, y, 2.0), -DVec3::Z);
+
+        let rec = cloud.hit(&down(4.52, 7.3), interval).unwrap();
+        assert!(rec.point.distance(DVec3::new(4.5, 7.3, 0.0)) < 0.031);
+        assert!(rec.normal.z > 0.0);
+        assert!(cloud.hit(&down(4.55, 7.3), interval).is_none());
+        assert!(!cloud.hit_any(&down(4.55, 7.3), interval));
+        assert!(cloud.hit_any(&down(4.5, 7.3), interval));
+    }
+
+    // Disks without normals face the ray, even edge-on to the grid.
+    let cloud = PointCloud::new(&grid(None), 0.03, Splat::Disk, grey());
+    let along = Ray::new(DVec3::new(-1.0, 2.01, 0.0), DVec3::X);
+    let rec = cloud.hit(&along, interval).unwrap();
+    assert!((rec.t - 1.0).abs() < 1e-9, "{}", rec.t);
+    assert!(rec.normal.abs_diff_eq(-DVec3::X, 1e-9));
+}
+
+// Colours are kept as the bytes scans store them and tint the cloud's own
+// material, which every point shares.
+#[test]
+fn coloured_points_tint_the_cloud_material() {
+    let mut points = grid(Some(DVec3::Z));
+    let red = DVec3::new(0.8, 0.1, 0.1);
+    for point in &mut points[..5000] {
+        point.color = Some(red);
+    }
+    let cloud = PointCloud::new(&points, 0.03, Splat::Disk, grey());
+    let down = |x: f64, y: f64| Ray::new(DVec3::new(x, y, 2.0), -DVec3::Z);
+    let interval = Interval::after(1e-3);
+    let a = cloud.hit(&down(1.0, 1.0), interval).unwrap();
+    let b = cloud.hit(&down(5.0, 8.0), interval).unwrap();
+    let tinted = a.material.albedo(&a);
+    assert!(tinted.abs_diff_eq(0.5 * red, 2e-3), "{tinted}");
+    assert_eq!(b.color, Some(DVec3::ONE));
+    assert_eq!(b.material.albedo(&b), DVec3::splat(0.5));
+    assert!(std::ptr::eq(
+        a.material as *const dyn Material as *const (),
+        b.material as *const dyn Material as *const ()
+    ));
+
+    // Without colours the points have none to tint with.
+    let plain = PointCloud::new(&grid(Some(DVec3::Z)), 0.03, Splat::Disk, grey());
+    assert_eq!(plain.hit(&down(1.0, 1.0), interval).unwrap().color, None);
+}
+
+#[test]
+fn point_clouds_load_from_scenes() {
+    let path = std::env::temp_dir().join("raytracer-scan.xyz");
+    let scan = "0 0 0 255 255 255\n0.1 0 0 255 0 0\n0 0.1 0 0 0 255\n";
+    std::fs::write(&path, scan).unwrap();
+    let scene = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - type: point_cloud
+    path: '{}'
+    radius: 0.05
+    splat: sphere
+    point_colors: true
+    material: {{ type: lambertian, texture: {{ type: solid_color, color: [1, 1, 1] }} }}
+",
+        path.display()
+    );
+    let (_, _, world, _) = Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+    let ray = Ray::new(DVec3::new(0.1, 0.0, 5.0), -DVec3::Z);
+    let rec = world.hit(&ray, Interval::after(1e-3)).unwrap();
+    assert!(rec.material.albedo(&rec).abs_diff_eq(DVec3::X, 1e-9));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn point_clouds_follow_the_scene_units() {
+    // A scan in millimetres with Z up.
+    let path = std::env::temp_dir().join("raytracer-scan-mm.xyz");
+    std::fs::write(&path, "100 0 0\n0 0 100\n").unwrap();
+    let load = |scene: &str, object: &str| {
+        let scene = format!(
+            "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+{scene}
+objects:
+  - {{ type: point_cloud, path: '{}', radius: 0.02, splat: sphere{object}, material: {{ type: lambertian, texture: {{ type: solid_color, color: [1, 1, 1] }} }} }}
+",
+            path.display()
+        );
+        let (_, _, world, _) = Scene::from_source_at(&scene, SceneFormat::Yaml, 0.0).unwrap();
+        world
+    };
+    let hits = |world: &Arc<dyn Hittable>, x: f64, y: f64| {
+        let ray = Ray::new(DVec3::new(x, y, 5.0), -DVec3::Z);
+        world.hit(&ray, Interval::after(1e-3)).is_some()
+    };
+
+    // Scaled to metres and turned Y up, by the scene or by the object.
+    for world in [
+        load("units: mm\nup_axis: z", ""),
+        load("", ", units: mm, up_axis: z"),
+    ] {
+        assert!(hits(&world, 0.1, 0.0));
+        assert!(hits(&world, 0.0, 0.1));
+        assert!(!hits(&world, 100.0, 0.0));
+    }
+    // Read as they are otherwise.
+    assert!(hits(&load("", ""), 100.0, 0.0));
+    std::fs::remove_file(&path).unwrap();
+}
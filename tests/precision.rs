@@ -0,0 +1,137 @@
+use glam::{BVec3, DVec3};
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::cuboid::Cuboid;
+use raytracer::objects::mesh::Mesh;
+use raytracer::objects::triangle::Triangle;
+use raytracer::ray::Ray;
+use raytracer::real::{wide, Real};
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.6,
+    )))))
+}
+
+#[test]
+fn triangle_hits_match_double_precision() {
+    // The back wall of the Cornell box, in millimetres, seen from its camera.
+    let [a, b, c] = [
+        DVec3::new(549.6, 0.0, 559.2),
+        DVec3::new(0.0, 0.0, 559.2),
+        DVec3::new(0.0, 548.8, 559.2),
+    ];
+    let triangle = Triangle::new([a, b, c], material());
+    let eye = DVec3::new(278.0, 273.0, -800.0);
+    let normal = (b - a).cross(c - a).normalize();
+    let tolerance = 1e3 * wide(Real::EPSILON);
+    for i in 1..20 {
+        for j in 1..20 - i {
+            let target = a + (b - a) * i as f64 / 20.0 + (c - a) * j as f64 / 20.0;
+            let ray = Ray::new(eye, (target - eye).normalize());
+            let exact = (a - eye).dot(normal) / ray.direction.dot(normal);
+            let hit = triangle.hit(&ray, Interval::after(0.0)).expect("inside");
+            assert!(
+                (hit.t - exact).abs() <= tolerance * exact,
+                "{} vs {exact}",
+                hit.t
+            );
+        }
+    }
+}
+
+// The cube from -1 to 1 on each axis, as twelve triangles.
+fn cube_triangles() -> Vec<Triangle> {
+    let (min, max) = (DVec3::splat(-1.0), DVec3::splat(1.0));
+    let corner = |i: usize| DVec3::select(BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), max, min);
+    let mut triangles = Vec::new();
+    // The faces at -x, +x, -y, +y, -z and +z.
+    for [p, q, r, s] in [
+        [0, 2, 6, 4],
+        [1, 3, 7, 5],
+        [0, 1, 5, 4],
+        [2, 3, 7, 6],
+        [0, 1, 3, 2],
+        [4, 5, 7, 6],
+    ] {
+        let [p, q, r, s] = [p, q, r, s].map(corner);
+        triangles.push(Triangle::new([p, q, r], material()));
+        triangles.push(Triangle::new([p, r, s], material()));
+    }
+    triangles
+}
+
+#[test]
+fn mesh_hits_match_double_precision_shapes() {
+    // The mesh stores and intersects the cube at `Real` precision, the
+    // cuboid always in doubles; with the `f32` feature this compares the
+    // two precisions hit by hit.
+    let (min, max) = (DVec3::splat(-1.0), DVec3::splat(1.0));
+    let mesh = Mesh::new(cube_triangles());
+    let cuboid = Cuboid::new(min, max, material());
+    let eye = DVec3::new(3.0, 2.5, 4.0);
+    let tolerance = 1e3 * wide(Real::EPSILON);
+    for i in 0..20 {
+        for j in 0..20 {
+            let target = DVec3::new(i as f64 / 10.0 - 0.95, j as f64 / 10.0 - 0.95, 0.3);
+            let ray = Ray::new(eye, (target - eye).normalize());
+            let expected = cuboid.hit(&ray, Interval::after(0.0)).expect("inside");
+            let hit = mesh.hit(&ray, Interval::after(0.0)).expect("inside");
+            assert!(
+                (hit.t - expected.t).abs() <= tolerance * expected.t,
+                "{} vs {}",
+                hit.t,
+                expected.t
+            );
+            assert!(hit
+                .point
+                .abs_diff_eq(expected.point, tolerance * expected.t));
+            assert!(hit.normal.abs_diff_eq(expected.normal, tolerance));
+        }
+    }
+}
+
+#[test]
+fn meshes_render_like_the_shapes_they_tessellate() {
+    let (min, max) = (DVec3::splat(-1.0), DVec3::splat(1.0));
+    let mut mesh = HittableList::new();
+    for triangle in cube_triangles() {
+        mesh.push(Arc::new(triangle));
+    }
+    let camera = Camera::new(
+        DVec3::new(3.0, 2.5, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        5.0,
+    );
+    let settings = RenderSettings {
+        width: 24,
+        height: 24,
+        samples_per_pixel: 8,
+        max_depth: 4,
+        ..RenderSettings::default()
+    };
+    let render = |world: Arc<dyn Hittable>| {
+        Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE))).render(&camera, &settings)
+    };
+    let tessellated = render(Arc::new(mesh));
+    let analytic = render(Arc::new(Cuboid::new(min, max, material())));
+
+    let difference: f64 = tessellated
+        .pixels
+        .iter()
+        .zip(&analytic.pixels)
+        .map(|(a, b)| (*a - *b).abs().max_element())
+        .sum::<f64>()
+        / tessellated.pixels.len() as f64;
+    assert!(difference < 0.01, "mean difference {difference}");
+}
@@ -0,0 +1,45 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::Hittable;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::scene::presets::{self, CheckerParams, RandomSpheresParams};
+use std::sync::Arc;
+
+fn render(scene: (Camera, Arc<dyn Hittable>)) -> Vec<DVec3> {
+    let (camera, world) = scene;
+    let settings = RenderSettings {
+        width: 16,
+        height: 9,
+        samples_per_pixel: 2,
+        max_depth: 4,
+        ..RenderSettings::default()
+    };
+    Renderer::new(world, Arc::new(SolidBackground::new(DVec3::ONE)))
+        .render(&camera, &settings)
+        .pixels
+}
+
+#[test]
+fn random_spheres_follow_the_seed() {
+    let params = |seed| RandomSpheresParams {
+        seed,
+        spheres: 100,
+        ..RandomSpheresParams::default()
+    };
+    let first = render(presets::random_spheres(&params(1)));
+    assert_eq!(first, render(presets::random_spheres(&params(1))));
+    assert_ne!(first, render(presets::random_spheres(&params(2))));
+}
+
+#[test]
+fn presets_fill_the_view() {
+    for scene in [
+        presets::cornell_box(),
+        presets::checkered_spheres(&CheckerParams::default()),
+    ] {
+        let pixels = render(scene);
+        assert!(pixels.iter().all(|p| p.is_finite()));
+        assert!(pixels.iter().any(|p| *p != DVec3::ONE));
+    }
+}
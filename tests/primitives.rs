@@ -0,0 +1,571 @@
+mod common;
+
+use common::{near_centre, ring_of_rays};
+use glam::{DAffine3, DVec2, DVec3};
+use raytracer::hittable::{self, Hittable, HittableList, AABB};
+use raytracer::interval::Interval;
+use raytracer::material::{Cutout, Lambertian, Material};
+use raytracer::objects::capsule::Capsule;
+use raytracer::objects::cone::Cone;
+use raytracer::objects::cuboid::Cuboid;
+use raytracer::objects::cylinder::Cylinder;
+use raytracer::objects::disk::Disk;
+use raytracer::objects::dop::{self, Dop14};
+use raytracer::objects::mesh::Mesh;
+use raytracer::objects::quadric::Quadric;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::tagged::Tagged;
+use raytracer::objects::torus::Torus;
+use raytracer::objects::transform::Transformed;
+use raytracer::objects::triangle::Triangle;
+use raytracer::qbvh::{BvhBuildStrategy, Qbvh};
+use raytracer::ray::Ray;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn primitives() -> [(&'static str, Arc<dyn Hittable>); 4] {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let start = DVec3::new(-0.3, -0.5, 0.2);
+    let end = DVec3::new(0.4, 0.6, -0.1);
+    [
+        (
+            "cylinder",
+            Arc::new(Cylinder::new(start, end, 0.5, material.clone())),
+        ),
+        (
+            "cone",
+            Arc::new(Cone::new(start, end, 0.5, material.clone())),
+        ),
+        (
+            "disk",
+            Arc::new(Disk::new(DVec3::ZERO, end - start, 0.7, material.clone())),
+        ),
+        ("box", Arc::new(Cuboid::new(start, end, material))),
+    ]
+}
+
+// Rays from a ring of viewpoints towards jittered points near the centre.
+// Every hit must lie inside the bounding box, with a unit normal facing the
+// ray and UVs in [0, 1].
+#[test]
+fn hits_are_bounded_and_well_formed() {
+    for (name, object) in primitives() {
+        let bbox = object.bounding_box().expect("primitives are bounded");
+        let mut hits = 0;
+        for ray in ring_of_rays(400, 3.0, 0.0, 2.0, near_centre(0.3)) {
+            let Some(rec) = object.hit(&ray, Interval::after(0.001)) else {
+                continue;
+            };
+            hits += 1;
+            let slack = DVec3::splat(1e-6);
+            assert!(
+                rec.point.cmpge(bbox.min - slack).all() && rec.point.cmple(bbox.max + slack).all(),
+                "{name}: hit {} outside its bounds",
+                rec.point
+            );
+            assert!((rec.normal.length() - 1.0).abs() < 1e-9, "{name}: normal");
+            assert!(
+                rec.normal.dot(ray.direction) <= 0.0,
+                "{name}: normal faces away"
+            );
+            assert!(
+                (-1e-9..=1.0 + 1e-9).contains(&rec.u) && (-1e-9..=1.0 + 1e-9).contains(&rec.v),
+                "{name}: uv ({}, {})",
+                rec.u,
+                rec.v
+            );
+        }
+        assert!(hits > 100, "{name}: only {hits} rays hit");
+    }
+}
+
+// Rays starting inside a closed primitive must find its surface.
+#[test]
+fn closed_primitives_are_hit_from_inside() {
+    for (name, object) in primitives() {
+        if name == "disk" {
+            continue;
+        }
+        let inside = Ray::new(DVec3::new(0.0, 0.0, 0.05), DVec3::new(0.3, 0.2, -0.1));
+        let rec = object
+            .hit(&inside, Interval::after(0.001))
+            .unwrap_or_else(|| panic!("{name}: missed from inside"));
+        assert!(!rec.front_face, "{name}: inside hit reported as front face");
+    }
+}
+
+// Every primitive with UVs, in a general position.
+fn parameterised() -> Vec<(&'static str, Arc<dyn Hittable>)> {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let start = DVec3::new(-0.3, -0.5, 0.2);
+    let end = DVec3::new(0.4, 0.6, -0.1);
+    let triangle = Triangle::new(
+        [
+            DVec3::new(-1.0, -0.8, 0.1),
+            DVec3::new(1.2, -0.5, -0.2),
+            DVec3::new(0.1, 1.1, 0.3),
+        ],
+        material.clone(),
+    )
+    .with_normals([
+        DVec3::new(0.2, 0.1, 1.0).normalize(),
+        DVec3::new(-0.1, 0.3, 1.0).normalize(),
+        DVec3::new(0.0, -0.2, 1.0).normalize(),
+    ])
+    .with_uvs([
+        DVec2::new(0.1, 0.2),
+        DVec2::new(0.9, 0.1),
+        DVec2::new(0.4, 0.8),
+    ]);
+    let ellipsoid = Quadric::new(
+        [1.0, 2.0, 1.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+        AABB::new(DVec3::splat(-1.0), DVec3::splat(1.0)),
+        material.clone(),
+    );
+    let mut objects = primitives().to_vec();
+    objects.extend([
+        (
+            "torus",
+            Arc::new(Torus::new(
+                DVec3::ZERO,
+                end - start,
+                0.6,
+                0.25,
+                material.clone(),
+            )) as Arc<dyn Hittable>,
+        ),
+        (
+            "capsule",
+            Arc::new(Capsule::new(start, end, 0.3, material.clone())),
+        ),
+        ("triangle", Arc::new(triangle)),
+        ("quadric", Arc::new(ellipsoid)),
+        (
+            "transformed cylinder",
+            Arc::new(Transformed::new(
+                Arc::new(Cylinder::new(start, end, 0.5, material)),
+                DAffine3::from_scale_rotation_translation(
+                    DVec3::new(1.5, 0.7, 1.0),
+                    glam::DQuat::from_rotation_z(0.4),
+                    DVec3::X,
+                ),
+            )),
+        ),
+    ]);
+    objects
+}
+
+// Neighbouring hits on the same patch must agree with the UV derivatives
+// to first order: dp = dp/du du + dp/dv dv, and likewise for the normal.
+#[test]
+fn uv_derivatives_match_neighbouring_hits() {
+    let outward = |rec: &raytracer::hittable::HitRecord| {
+        if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        }
+    };
+    for (name, object) in parameterised() {
+        let mut checked = 0;
+        let rays = ring_of_rays(400, 3.0, 0.0, 2.0, near_centre(0.3));
+        for (i, ray) in rays.enumerate() {
+            let i = i as f64;
+            let nudge = 1e-5 * DVec3::new((i * 2.1).cos(), (i * 0.7).sin(), 0.3);
+            let nearby = Ray::new(ray.origin, ray.direction + nudge);
+            let (Some(a), Some(b)) = (
+                object.hit(&ray, Interval::after(0.001)),
+                object.hit(&nearby, Interval::after(0.001)),
+            ) else {
+                continue;
+            };
+            let (du, dv) = (b.u - a.u, b.v - a.v);
+            // Skip seams and edges between faces.
+            if du.abs() > 1e-3 || dv.abs() > 1e-3 || a.normal.dot(b.normal) < 0.99 {
+                continue;
+            }
+            let dp = b.point - a.point;
+            let predicted = du * a.dpdu + dv * a.dpdv;
+            assert!(
+                (dp - predicted).length() < 1e-3 * dp.length() + 1e-12,
+                "{name}: dp {dp} but derivatives give {predicted}"
+            );
+            let dn = outward(&b) - outward(&a);
+            let predicted = du * a.dndu + dv * a.dndv;
+            assert!(
+                (dn - predicted).length() < 1e-3 * dp.length().max(dn.length()) + 1e-9,
+                "{name}: dn {dn} but derivatives give {predicted}"
+            );
+            checked += 1;
+        }
+        assert!(checked > 50, "{name}: only {checked} hits checked");
+    }
+}
+
+#[test]
+fn spawned_rays_clear_surfaces_of_any_size() {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let ground: Arc<dyn Hittable> = Arc::new(Tagged::new(
+        Arc::new(Cuboid::new(
+            DVec3::new(-1e6, -1e3, -1e6),
+            DVec3::new(1e6, 0.0, 1e6),
+            material.clone(),
+        )),
+        1,
+    ));
+    let grain: Arc<dyn Hittable> = Arc::new(Tagged::new(
+        Arc::new(Cuboid::new(DVec3::ZERO, DVec3::splat(1e-3), material)),
+        2,
+    ));
+    let down = Ray::new(DVec3::new(5.0, 1.0, 5.0), -DVec3::Y);
+    let on_ground = ground.hit(&down, Interval::after(0.0)).unwrap();
+    // Rays leave the ground from a hair above it, by the offset its bounds
+    // call for, and don't find it again even at grazing angles.
+    let grazing = DVec3::new(1.0, 1e-6, 0.0).normalize();
+    let leaving = on_ground.spawn(&down, grazing);
+    let bound = ground.bounding_box().unwrap().epsilon();
+    assert!(
+        leaving.origin.y - on_ground.point.y >= bound && leaving.origin.y < 1e-5,
+        "{}",
+        leaving.origin
+    );
+    assert!(ground
+        .hit(&leaving, Interval::after(on_ground.ray_epsilon()))
+        .is_none());
+
+    // A ray leaving the side of the grain lands on the ground half a
+    // millimetre away, closer than a fixed offset would allow.
+    let towards_side = Ray::new(DVec3::new(1.0, 0.5e-3, 0.5e-3), -DVec3::X);
+    let on_side = grain.hit(&towards_side, Interval::after(0.0)).unwrap();
+    let world: HittableList = vec![ground, grain];
+    let bounce = on_side.spawn(&towards_side, DVec3::new(1.0, -1.0, 0.0));
+    let rec = world
+        .hit(&bounce, Interval::after(on_side.ray_epsilon()))
+        .unwrap();
+    assert_eq!(rec.object_id, 1);
+    assert!(rec.point.y.abs() < 1e-9, "{}", rec.point);
+}
+
+#[test]
+fn qbvh_is_precise_around_a_distant_camera() {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let camera = DVec3::new(3e7, 1.0, -3e7);
+    let mut objects = HittableList::new();
+    for i in 0..64 {
+        let corner = camera + DVec3::new((i % 8) as f64, (i / 8) as f64, -5.0) * 2e-3;
+        objects.push(Arc::new(Tagged::new(
+            Arc::new(Cuboid::new(
+                corner,
+                corner + DVec3::splat(1e-3),
+                material.clone(),
+            )),
+            i + 1,
+        )));
+    }
+    let bvh = Qbvh::with_origin(objects.clone(), BvhBuildStrategy::default(), camera);
+
+    // f32 can't tell these boxes apart at 3e7 from the world origin, but
+    // can next to the camera.
+    for bounds in bvh.boxes_at_depth(2) {
+        let size = bounds.max - bounds.min;
+        assert!(size.max_element() < 0.1, "{size}");
+    }
+    for i in 0..64 {
+        let target = camera + DVec3::new((i % 8) as f64, (i / 8) as f64, -5.0) * 2e-3;
+        let ray = Ray::new(camera, target + DVec3::splat(5e-4) - camera);
+        let expected = objects
+            .hit(&ray, Interval::after(0.0))
+            .map(|rec| rec.object_id);
+        let actual = bvh.hit(&ray, Interval::after(0.0)).map(|rec| rec.object_id);
+        assert_eq!(actual, expected);
+        assert!(actual.is_some());
+    }
+}
+
+// Long diagonal primitives, which fill little of their boxes. Behind their
+// 14-DOPs they must be hit exactly as before, while many rays through the
+// boxes are turned away by the DOPs alone.
+#[test]
+fn dops_cull_only_misses() {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let rotation = DAffine3::from_axis_angle(DVec3::new(1.0, 1.0, 0.0).normalize(), 0.8);
+    let plank = Cuboid::new(
+        DVec3::new(-2.0, -0.1, -0.1),
+        DVec3::new(2.0, 0.1, 0.1),
+        material.clone(),
+    );
+    let objects: HittableList = vec![
+        Arc::new(Triangle::new(
+            [
+                DVec3::new(-1.0, -1.0, -1.0),
+                DVec3::new(1.0, 1.0, 1.0),
+                DVec3::new(1.05, 0.95, 1.0),
+            ],
+            material.clone(),
+        )),
+        Arc::new(Capsule::new(
+            DVec3::splat(-1.0),
+            DVec3::splat(1.0),
+            0.05,
+            material,
+        )),
+        Arc::new(Transformed::new(Arc::new(plank), rotation)),
+    ];
+    for (object, bounded) in objects.iter().zip(dop::tighten(objects.clone())) {
+        let dop = Dop14::of(&**object).unwrap();
+        let mut culled = 0;
+        for i in 0..400 {
+            let eye = DVec3::new(0.3, -0.2, 5.0);
+            let (x, y) = ((i % 20) as f64, (i / 20) as f64);
+            let target = DVec3::new(x / 9.5 - 1.0, y / 9.5 - 1.0, 0.0);
+            let ray = Ray::new(eye, target - eye);
+            let expected = object.hit(&ray, Interval::after(0.0)).map(|rec| rec.t);
+            let actual = bounded.hit(&ray, Interval::after(0.0)).map(|rec| rec.t);
+            assert_eq!(actual, expected);
+            let in_box = object
+                .bounding_box()
+                .unwrap()
+                .hit(&ray, Interval::after(0.0));
+            if in_box && !dop.hit(&ray, Interval::after(0.0)) {
+                culled += 1;
+            }
+        }
+        assert!(culled > 20, "{culled}");
+    }
+}
+
+// A 2x2 grid of quads, each split along a diagonal, in a tilted plane.
+// Rays from scattered eyes aimed exactly at shared edges and vertices must
+// hit one of the triangles meeting there.
+#[test]
+fn rays_do_not_leak_between_triangles() {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let frame = DAffine3::from_rotation_x(0.3) * DAffine3::from_rotation_y(-0.7);
+    let point = |x: f64, y: f64| frame.transform_point3(DVec3::new(x, y, 0.0));
+    let mut mesh = HittableList::new();
+    for i in 0..2 {
+        for j in 0..2 {
+            let (x, y) = (i as f64, j as f64);
+            let [a, b, c, d] = [
+                point(x, y),
+                point(x + 1.0, y),
+                point(x + 1.0, y + 1.0),
+                point(x, y + 1.0),
+            ];
+            mesh.push(Arc::new(Triangle::new([a, b, c], material.clone())));
+            mesh.push(Arc::new(Triangle::new([a, c, d], material.clone())));
+        }
+    }
+
+    let mut targets = vec![point(1.0, 1.0)];
+    for k in 1..200 {
+        let s = 2.0 * k as f64 / 200.0;
+        // The inner edges, and the diagonals of the lower-left and
+        // upper-right quads.
+        targets.extend([point(1.0, s), point(s, 1.0), point(s / 2.0, s / 2.0)]);
+        targets.push(point(1.0 + s / 2.0, 1.0 + s / 2.0));
+    }
+    for (n, target) in targets.into_iter().enumerate() {
+        let angle = n as f64 * 2.399;
+        let eye = point(1.0, 1.0)
+            + frame.transform_vector3(DVec3::new(angle.cos() * 3.0, angle.sin() * 2.0, 4.0));
+        let ray = Ray::new(eye, target - eye);
+        assert!(mesh.hit(&ray, Interval::after(0.0)).is_some(), "{target}");
+    }
+}
+
+#[test]
+fn boxes_catch_rays_along_faces_and_edges() {
+    let unit = AABB::new(DVec3::ZERO, DVec3::ONE);
+    let hits = |bounds: &AABB, origin: DVec3, direction: DVec3| {
+        bounds.hit(&Ray::new(origin, direction), Interval::after(0.0))
+    };
+    // Along the face y = 0, and along its edges.
+    assert!(hits(&unit, DVec3::new(-1.0, 0.0, 0.5), DVec3::X));
+    assert!(hits(&unit, DVec3::new(-1.0, 0.0, 0.0), DVec3::X));
+    assert!(hits(
+        &unit,
+        DVec3::new(2.0, 1.0, 1.0),
+        DVec3::new(-1.0, -0.0, 0.0)
+    ));
+    assert!(hits(&unit, DVec3::new(0.5, 2.0, 1.0), -DVec3::Y));
+    // Parallel to a face just outside it.
+    assert!(!hits(&unit, DVec3::new(-1.0, 1.0 + 1e-9, 0.5), DVec3::X));
+    assert!(!hits(&unit, DVec3::new(0.5, -1e-9, -1.0), DVec3::Z));
+
+    // Boxes of axis-aligned triangles have no thickness.
+    let flat = AABB::new(DVec3::ZERO, DVec3::new(1.0, 1.0, 0.0));
+    assert!(hits(&flat, DVec3::new(0.5, 0.5, 1.0), -DVec3::Z));
+    assert!(hits(
+        &flat,
+        DVec3::new(0.3, 0.6, -2.0),
+        DVec3::new(0.1, -0.1, 1.0)
+    ));
+    assert!(hits(&flat, DVec3::new(-1.0, 0.5, 0.0), DVec3::X));
+    assert!(!hits(&flat, DVec3::new(1.5, 0.5, 1.0), -DVec3::Z));
+}
+
+#[test]
+fn smooth_triangles_take_their_side_from_the_true_normal() {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let vertices = [
+        DVec3::new(-1.0, -1.0, 0.0),
+        DVec3::new(1.0, -1.0, 0.0),
+        DVec3::new(0.0, 1.0, 0.0),
+    ];
+    // Shading normals tilted far over, and a ray from the front that
+    // meets them at more than a right angle.
+    let tilted = DVec3::new(1.0, 0.0, 0.1).normalize();
+    let ray = Ray::new(DVec3::new(-1.0, 0.0, 2.0), DVec3::new(0.5, 0.0, -1.0));
+    for triangle in [
+        Triangle::new(vertices, material.clone()).with_normals([tilted; 3]),
+        // Wound the other way; the normals still say which side is out.
+        Triangle::new([vertices[0], vertices[2], vertices[1]], material.clone())
+            .with_normals([tilted; 3]),
+    ] {
+        let rec = triangle.hit(&ray, Interval::after(0.0)).unwrap();
+        assert!(rec.front_face);
+        assert!(rec.normal.abs_diff_eq(tilted, 1e-12), "{}", rec.normal);
+        let behind = Ray::new(DVec3::new(-1.0, 0.0, -2.0), DVec3::new(0.5, 0.0, 1.0));
+        let rec = triangle.hit(&behind, Interval::after(0.0)).unwrap();
+        assert!(!rec.front_face);
+        assert!(rec.normal.abs_diff_eq(-tilted, 1e-12), "{}", rec.normal);
+    }
+}
+
+#[test]
+fn rays_pass_through_cutouts() {
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let card = |alpha: f64| -> Arc<dyn Hittable> {
+        let alpha = Arc::new(SolidColor::new(DVec3::splat(alpha)));
+        let cutout = Arc::new(Cutout::new(material.clone(), alpha));
+        Arc::new(Disk::new(DVec3::Z, DVec3::Z, 1.0, cutout))
+    };
+    let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(DVec3::ZERO, 0.5, material.clone()));
+    let ray = Ray::new(DVec3::new(0.0, 0.0, 5.0), DVec3::new(0.0, 0.0, -2.0));
+
+    let world: HittableList = vec![card(0.8), sphere.clone()];
+    let rec = hittable::hit_surface(&world, &ray, Interval::after(0.0)).unwrap();
+    assert!((rec.t - 2.0).abs() < 1e-9, "{}", rec.t);
+
+    let world: HittableList = vec![card(0.2), sphere];
+    let rec = hittable::hit_surface(&world, &ray, Interval::after(0.0)).unwrap();
+    assert!((rec.t - 2.25).abs() < 1e-6, "{}", rec.t);
+    assert!(rec.point.abs_diff_eq(DVec3::new(0.0, 0.0, 0.5), 1e-6));
+    assert!(hittable::hit_surface(&world, &ray, Interval::new(0.0, 2.2)).is_none());
+
+    // Shadow rays find the same surfaces in a single any-hit query,
+    // triangles and meshes included.
+    assert!(!world.hit_any(&ray, Interval::new(0.0, 2.2)));
+    assert!(world.hit_any(&ray, Interval::after(0.0)));
+    let world: HittableList = vec![card(0.8)];
+    assert!(world.hit_any(&ray, Interval::new(0.0, 2.2)));
+    let clear = Arc::new(SolidColor::new(DVec3::ZERO));
+    let cutout: Arc<dyn Material> = Arc::new(Cutout::new(material.clone(), clear));
+    let corners = [
+        DVec3::new(-1.0, -1.0, 1.0),
+        DVec3::new(1.0, -1.0, 1.0),
+        DVec3::new(0.0, 1.0, 1.0),
+    ];
+    let triangle = Triangle::new(corners, cutout.clone());
+    assert!(triangle.hit(&ray, Interval::after(0.0)).is_some());
+    assert!(!triangle.hit_any(&ray, Interval::after(0.0)));
+    let mesh = Mesh::new(vec![Triangle::new(corners, cutout)]);
+    assert!(!mesh.hit_any(&ray, Interval::after(0.0)));
+    let mesh = Mesh::new(vec![Triangle::new(corners, material)]);
+    assert!(mesh.hit_any(&ray, Interval::after(0.0)));
+}
+
+#[test]
+fn meshes_hit_like_their_triangles() {
+    let materials: [Arc<dyn Material>; 2] = [0.2, 0.8].map(|albedo| {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+            albedo,
+        ))))) as Arc<dyn Material>
+    });
+    // A bumpy 8x8 grid, smooth-shaded on its left half.
+    let point = |i: usize, j: usize| {
+        let (x, y) = (i as f64 / 8.0, j as f64 / 8.0);
+        DVec3::new(x, y, 0.2 * (5.0 * x).sin() * (3.0 * y).cos())
+    };
+    let mut triangles = Vec::new();
+    for i in 0..8 {
+        for j in 0..8 {
+            let [a, b, c, d] = [
+                point(i, j),
+                point(i + 1, j),
+                point(i + 1, j + 1),
+                point(i, j + 1),
+            ];
+            let material = &materials[(i + j) % 2];
+            for vertices in [[a, b, c], [a, c, d]] {
+                let triangle = Triangle::new(vertices, material.clone())
+                    .with_uvs(vertices.map(DVec3::truncate));
+                triangles.push(if i < 4 {
+                    triangle.with_normals(vertices.map(|p| DVec3::new(-p.z, 0.1, 1.0).normalize()))
+                } else {
+                    triangle
+                });
+            }
+        }
+    }
+    let list: HittableList = triangles
+        .iter()
+        .map(|t| {
+            let copy = Triangle::new(t.vertices(), t.material.clone()).with_uvs(t.uvs);
+            Arc::new(match t.normals() {
+                Some(normals) => copy.with_normals(normals),
+                None => copy,
+            }) as Arc<dyn Hittable>
+        })
+        .collect();
+    let mesh = Mesh::new(triangles);
+    assert_eq!(mesh.triangle_count(), 128);
+    // Corners are shared within each half; the middle column is split by
+    // its normals.
+    assert_eq!(mesh.vertex_count(), 90);
+
+    for n in 0..500 {
+        let angle = n as f64 * 2.399;
+        let target = DVec3::new(0.5 + 0.6 * angle.cos(), 0.5 + 0.6 * angle.sin(), 0.0);
+        let eye = DVec3::new(0.5, 0.5, 3.0) + DVec3::new(angle.sin(), 0.3 * angle.cos(), 0.0);
+        let ray = Ray::new(eye, target - eye);
+        let expected = list.hit(&ray, Interval::after(0.0));
+        let rec = mesh.hit(&ray, Interval::after(0.0));
+        assert_eq!(rec.is_some(), expected.is_some(), "{target}");
+        if let (Some(rec), Some(expected)) = (rec, expected) {
+            assert_eq!(rec.t, expected.t);
+            assert_eq!(rec.normal, expected.normal);
+            assert!(std::ptr::addr_eq(
+                rec.material,
+                Arc::as_ptr(&expected.material)
+            ));
+        }
+    }
+}
+
+#[test]
+fn intervals_bound_hits_and_pad_flat_boxes() {
+    let interval = Interval::new(1.0, 3.0);
+    assert!(interval.contains(1.0) && !interval.surrounds(1.0));
+    assert!(interval.surrounds(2.0) && !interval.contains(3.5));
+    assert_eq!(interval.clamp(5.0), 3.0);
+    assert_eq!(interval.expand(1.0), Interval::new(0.5, 3.5));
+    assert!(Interval::EMPTY.is_empty() && !Interval::UNIVERSE.is_empty());
+
+    // A disk facing up has no height of its own, but its box does.
+    let material: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))));
+    let disk = Disk::new(DVec3::ZERO, DVec3::Y, 1.0, material);
+    let bounds = disk.bounding_box().unwrap();
+    assert!(bounds.axis(1).size() >= 2e-4);
+    assert!((bounds.axis(0).size() - 2.0).abs() < 1e-9);
+}
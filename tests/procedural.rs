@@ -0,0 +1,120 @@
+use glam::{DVec2, DVec3};
+use raytracer::procedural::{
+    noise, BrickTexture, GradientShape, GradientTexture, MarbleTexture, WoodTexture,
+};
+use raytracer::scene::{Scene, SceneFormat, SceneValidationError};
+use raytracer::texture::{SolidColor, Texture};
+use std::sync::Arc;
+
+fn solid(value: f64) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::new(DVec3::splat(value)))
+}
+
+#[test]
+fn noise_vanishes_on_the_lattice_and_stays_bounded() {
+    assert_eq!(noise(DVec3::new(3.0, -2.0, 7.0)), 0.0);
+    let mut varies = false;
+    for i in 0..1000 {
+        let p = DVec3::new(i as f64 * 0.37, i as f64 * 0.11, -(i as f64) * 0.23);
+        let n = noise(p);
+        assert!(n.abs() <= 1.5, "{n} at {p}");
+        varies |= n.abs() > 0.1;
+    }
+    assert!(varies);
+    // Smooth: nearby points give nearby values.
+    let p = DVec3::new(0.3, 0.6, 0.9);
+    assert!((noise(p) - noise(p + DVec3::splat(1e-6))).abs() < 1e-4);
+}
+
+#[test]
+fn gradients_blend_by_position_and_clamp() {
+    let linear = GradientTexture::new(
+        solid(0.0),
+        solid(1.0),
+        GradientShape::Linear {
+            from: DVec3::ZERO,
+            to: DVec3::new(2.0, 0.0, 0.0),
+        },
+    );
+    let at = |texture: &dyn Texture, p: DVec3| texture.value(0.0, 0.0, p).x;
+    assert_eq!(at(&linear, DVec3::new(1.0, 5.0, -3.0)), 0.5);
+    assert_eq!(at(&linear, DVec3::new(-4.0, 0.0, 0.0)), 0.0);
+    assert_eq!(at(&linear, DVec3::new(9.0, 0.0, 0.0)), 1.0);
+
+    let radial = GradientTexture::new(
+        solid(0.0),
+        solid(1.0),
+        GradientShape::Radial {
+            center: DVec3::Y,
+            radius: 4.0,
+        },
+    );
+    assert_eq!(at(&radial, DVec3::new(0.0, 1.0, 1.0)), 0.25);
+    assert_eq!(at(&radial, DVec3::new(0.0, 9.0, 0.0)), 1.0);
+}
+
+#[test]
+fn bricks_are_staggered_between_courses() {
+    let bricks = BrickTexture::new(solid(1.0), solid(0.0), DVec2::new(2.0, 1.0), 0.1);
+    // The middle of a brick, and the joint above it.
+    assert!(!bricks.is_mortar(DVec3::new(1.0, 0.5, 3.0)));
+    assert!(bricks.is_mortar(DVec3::new(1.0, 1.0, 3.0)));
+    // The head joint at x = 2 in the first course falls mid-brick in the
+    // second.
+    assert!(bricks.is_mortar(DVec3::new(2.0, 0.5, 0.0)));
+    assert!(!bricks.is_mortar(DVec3::new(2.0, 1.5, 0.0)));
+    assert!(bricks.is_mortar(DVec3::new(1.0, 1.5, 0.0)));
+}
+
+#[test]
+fn marble_and_wood_stay_between_their_colours() {
+    let marble = MarbleTexture::new(solid(0.2), solid(0.8));
+    let wood = WoodTexture {
+        distortion: 0.5,
+        ..WoodTexture::new(solid(0.2), solid(0.8), 0.1)
+    };
+    for i in 0..200 {
+        let p = DVec3::new(i as f64 * 0.13, i as f64 * 0.07, i as f64 * 0.05);
+        for value in [marble.value(0.0, 0.0, p).x, wood.value(0.0, 0.0, p).x] {
+            assert!((0.2 - 1e-12..=0.8 + 1e-12).contains(&value), "{value}");
+        }
+    }
+    // Without distortion, rings are exactly `ring_spacing` apart.
+    let rings = WoodTexture::new(solid(0.0), solid(1.0), 0.1);
+    assert!(rings.ring_weight(DVec3::new(0.3, 7.0, 0.0)) < 1e-12);
+    assert!((rings.ring_weight(DVec3::new(0.0, 0.0, 0.35)) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn procedural_textures_load_from_scenes() {
+    let scene = "
+camera: { lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }
+textures:
+  stone:
+    type: marble
+    base: { type: solid_color, color: [0.9, 0.9, 0.9] }
+    vein: { type: radial_gradient, start: { type: solid_color, color: [0, 0, 0] }, end: { type: solid_color, color: [0.3, 0.3, 0.3] }, center: [0, 0, 0], radius: 2 }
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: lambertian
+      texture:
+        type: bricks
+        brick: stone
+        mortar: { type: wood, light: { type: solid_color, color: [1, 1, 1] }, dark: stone, ring_spacing: RING }
+        size: [0.5, 0.2]
+        mortar_width: 0.02
+";
+    assert!(Scene::from_source_at(&scene.replace("RING", "0.1"), SceneFormat::Yaml, 0.0).is_ok());
+
+    let error = Scene::from_source_at(&scene.replace("RING", "0"), SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        ["objects[0].material.texture.mortar.ring_spacing: must be positive"]
+    );
+}
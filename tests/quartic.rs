@@ -0,0 +1,126 @@
+mod common;
+
+use common::{near_centre, ring_of_rays};
+use glam::DVec3;
+use raytracer::hittable::{Hittable, HittableList, AABB};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::quadric::Quadric;
+use raytracer::objects::torus::Torus;
+use raytracer::objects::triangle::Triangle;
+use raytracer::polynomial::real_roots;
+use raytracer::texture::SolidColor;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))))
+}
+
+// Triangulates a closed parametric surface on a `columns` x `rows` grid over
+// [0, 1]^2.
+fn tessellate(columns: usize, rows: usize, surface: impl Fn(f64, f64) -> DVec3) -> HittableList {
+    let mut mesh = HittableList::new();
+    let material = material();
+    for i in 0..columns {
+        for j in 0..rows {
+            let corner = |di: usize, dj: usize| {
+                surface(
+                    (i + di) as f64 / columns as f64,
+                    (j + dj) as f64 / rows as f64,
+                )
+            };
+            let (a, b, c, d) = (corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1));
+            mesh.push(Arc::new(Triangle::new([a, b, c], material.clone())));
+            mesh.push(Arc::new(Triangle::new([a, c, d], material.clone())));
+        }
+    }
+    mesh
+}
+
+// Rays from a ring of viewpoints must hit the analytic surface and its fine
+// tessellation alike, at nearly the same distance. Grazing rays may slip
+// between the two, so a few disagreements are allowed.
+fn assert_matches_mesh(name: &str, analytic: &dyn Hittable, mesh: &dyn Hittable) {
+    let (mut hits, mut disagreements) = (0, 0);
+    for (i, ray) in ring_of_rays(400, 4.0, 0.0, 2.5, near_centre(1.2)).enumerate() {
+        let exact = analytic.hit(&ray, Interval::after(0.001));
+        let approximate = mesh.hit(&ray, Interval::after(0.001));
+        match (exact, approximate) {
+            (Some(exact), Some(approximate)) => {
+                hits += 1;
+                let error = (exact.point - approximate.point).length();
+                assert!(error < 1e-2, "{name}: hits {error} apart for ray {i}");
+                assert!(
+                    exact.normal.dot(approximate.normal) > 0.9,
+                    "{name}: normals disagree for ray {i}"
+                );
+            }
+            (None, None) => {}
+            _ => disagreements += 1,
+        }
+    }
+    assert!(hits > 100, "{name}: only {hits} rays hit");
+    assert!(
+        disagreements * 50 < hits,
+        "{name}: {disagreements} rays hit only one of the surfaces"
+    );
+}
+
+#[test]
+fn torus_matches_its_tessellation() {
+    let (center, axis) = (
+        DVec3::new(0.2, -0.1, 0.3),
+        DVec3::new(0.3, 1.0, 0.2).normalize(),
+    );
+    let (major, minor) = (1.0, 0.3);
+    let torus = Torus::new(center, axis, major, minor, material());
+    let (tangent, bitangent) = axis.any_orthonormal_pair();
+    let mesh = tessellate(128, 64, |u, v| {
+        let (phi, theta) = (2.0 * PI * u, 2.0 * PI * v);
+        let ring = phi.cos() * tangent + phi.sin() * bitangent;
+        center + (major + minor * theta.cos()) * ring + minor * theta.sin() * axis
+    });
+    assert_matches_mesh("torus", &torus, &mesh);
+}
+
+#[test]
+fn ellipsoid_quadric_matches_its_tessellation() {
+    let radii = DVec3::new(1.5, 1.0, 0.7);
+    let inverse = 1.0 / (radii * radii);
+    let coefficients = [
+        inverse.x, inverse.y, inverse.z, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0,
+    ];
+    let quadric = Quadric::new(coefficients, AABB::new(-radii, radii), material());
+    let mesh = tessellate(128, 64, |u, v| {
+        let (phi, theta) = (2.0 * PI * u, PI * v);
+        radii
+            * DVec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            )
+    });
+    assert_matches_mesh("ellipsoid", &quadric, &mesh);
+}
+
+#[test]
+fn real_roots_finds_simple_and_double_roots() {
+    // (t - 1)(t - 2)(t - 3)(t - 4)
+    let roots = real_roots(
+        &[24.0, -50.0, 35.0, -10.0, 1.0],
+        f64::NEG_INFINITY..f64::INFINITY,
+    );
+    assert_eq!(roots.len(), 4, "{roots:?}");
+    for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+        assert!((root - expected).abs() < 1e-9, "{roots:?}");
+    }
+
+    // (t - 1)²(t + 2), restricted to positive t.
+    let roots = real_roots(&[2.0, -3.0, 0.0, 1.0], 0.0..f64::INFINITY);
+    assert_eq!(roots.len(), 1, "{roots:?}");
+    assert!((roots[0] - 1.0).abs() < 1e-6, "{roots:?}");
+
+    // t² + 1 has no real roots.
+    assert!(real_roots(&[1.0, 0.0, 1.0], f64::NEG_INFINITY..f64::INFINITY).is_empty());
+}
@@ -0,0 +1,104 @@
+use glam::DVec3;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::tagged::Tagged;
+use raytracer::objects::visibility::Visibility;
+use raytracer::query::RayQuery;
+use raytracer::ray::Ray;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn grey() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+        0.5,
+    )))))
+}
+
+// A unit sphere at the origin tagged 7, and one at x = 4 that casts no
+// shadows.
+fn query() -> RayQuery {
+    let mut world = HittableList::new();
+    world.push(Arc::new(Tagged::new(
+        Arc::new(Sphere::new(DVec3::ZERO, 1.0, grey())),
+        7,
+    )));
+    world.push(Arc::new(
+        Visibility::new(Arc::new(Sphere::new(
+            DVec3::new(4.0, 0.0, 0.0),
+            1.0,
+            grey(),
+        )))
+        .with_shadows(false),
+    ));
+    let world: Arc<dyn Hittable> = Arc::new(world);
+    RayQuery::new(world)
+}
+
+#[test]
+fn nearest_hits_come_back_by_ray() {
+    // A scan line across the first sphere, as a lidar would sweep it, more
+    // than one batch long.
+    let rays: Vec<Ray> = (0..200)
+        .map(|i| {
+            let x = -2.0 + 4.0 * i as f64 / 199.0;
+            Ray::new(DVec3::new(x, 0.0, 5.0), DVec3::NEG_Z)
+        })
+        .collect();
+    let hits = query().nearest(&rays, Interval::after(1e-6));
+    assert_eq!(hits.len(), rays.len());
+    for (i, ray) in rays.iter().enumerate() {
+        let x = ray.origin.x;
+        if x.abs() < 0.99 {
+            assert!(hits.is_hit(i));
+            let expected = 5.0 - (1.0 - x * x).sqrt();
+            assert!((hits.t[i] - expected).abs() < 1e-9);
+            assert!((hits.points[i] - ray.at(hits.t[i])).length() < 1e-9);
+            assert!((hits.normals[i] - hits.points[i]).length() < 1e-9);
+            assert_eq!(hits.object_ids[i], 7);
+        } else if x.abs() > 1.01 {
+            assert!(!hits.is_hit(i));
+            assert_eq!(hits.t[i], f64::INFINITY);
+            assert_eq!(hits.object_ids[i], 0);
+        }
+    }
+}
+
+#[test]
+fn occlusion_queries_follow_shadow_visibility() {
+    let rays = [
+        Ray::new(DVec3::new(0.0, 0.0, 5.0), DVec3::NEG_Z),
+        Ray::new(DVec3::new(0.0, 3.0, 5.0), DVec3::NEG_Z),
+        // Only through the sphere that casts no shadows.
+        Ray::new(DVec3::new(4.0, 0.0, 5.0), DVec3::NEG_Z),
+    ];
+    let query = query();
+    assert_eq!(
+        query.occluded(&rays, Interval::after(1e-6)),
+        vec![true, false, false]
+    );
+    // Stopping short of the sphere sees nothing.
+    assert_eq!(
+        query.occluded(&rays[..1], Interval::new(1e-6, 3.0)),
+        vec![false]
+    );
+    assert!(query.nearest(&rays[2..], Interval::after(1e-6)).is_hit(0));
+}
+
+#[test]
+fn line_of_sight_between_points() {
+    let query = query();
+    let visible = query.visible(
+        &[
+            (DVec3::new(-3.0, 0.0, 0.0), DVec3::new(-3.0, 0.0, 2.0)),
+            (DVec3::new(-3.0, 0.0, 0.0), DVec3::new(2.0, 0.0, 0.0)),
+            // From a point on the sphere's surface, away from it.
+            (DVec3::new(0.0, 1.0, 0.0), DVec3::new(0.0, 3.0, 0.0)),
+            // Ends that stop short of the sphere.
+            (DVec3::new(-3.0, 0.0, 0.0), DVec3::new(-1.5, 0.0, 0.0)),
+        ],
+        1e-6,
+    );
+    assert_eq!(visible, vec![true, false, true, true]);
+}
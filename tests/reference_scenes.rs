@@ -0,0 +1,50 @@
+use glam::DVec3;
+use raytracer::material::{Lambertian, Material};
+use raytracer::reference_scenes;
+use raytracer::renderer::RenderSettings;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn settings(size: u32, samples_per_pixel: u32) -> RenderSettings {
+    RenderSettings {
+        width: size,
+        height: size,
+        samples_per_pixel,
+        ..RenderSettings::default()
+    }
+}
+
+#[test]
+fn cornell_box_walls_tint_their_sides() {
+    let scene = reference_scenes::cornell_box();
+    let image = scene.renderer().render(&scene.camera, &settings(16, 16));
+    // Halfway up the left and right walls.
+    let (left, right) = (image.get(0, 8), image.get(15, 8));
+    assert!(left.x > left.y && left.x > left.z, "left wall {left}");
+    assert!(right.y > right.x && right.y > right.z, "right wall {right}");
+    assert!(image.pixels.iter().all(|p| p.is_finite()));
+}
+
+#[test]
+fn white_furnace_shows_the_albedo() {
+    let albedo = DVec3::new(0.2, 0.5, 0.8);
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(albedo))));
+    let scene = reference_scenes::white_furnace(material);
+    let image = scene.renderer().render(&scene.camera, &settings(8, 256));
+    let centre = image.get(4, 4);
+    assert!(
+        (centre - albedo).abs().max_element() < 0.05,
+        "{centre} vs {albedo}"
+    );
+    assert_eq!(image.get(0, 0), DVec3::ONE);
+}
+
+#[test]
+fn veach_plates_reflect_the_lights() {
+    let scene = reference_scenes::veach_mis(1.0);
+    let image = scene.renderer().render(&scene.camera, &settings(32, 4));
+    assert!(image.pixels.iter().all(|p| p.is_finite()));
+    // Below the lights, where the plates are.
+    let lower_half = &image.pixels[(16 * 32) as usize..];
+    assert!(lower_half.iter().any(|p| p.max_element() > 0.0));
+}
@@ -0,0 +1,72 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::HittableList;
+use raytracer::lights::{AnalyticLight, LightSet, PointLight};
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::triangle::Triangle;
+use raytracer::qbvh::Qbvh;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::stats::RenderStats;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+// A floor of many small triangles under a point light, seen from above.
+fn render(settings: &RenderSettings) -> RenderStats {
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let mut world = HittableList::new();
+    for i in -8..8 {
+        for j in -8..8 {
+            let corner = |di: i32, dj: i32| DVec3::new((i + di) as f64, 0.0, (j + dj) as f64);
+            let [a, b, c, d] = [corner(0, 0), corner(0, 1), corner(1, 1), corner(1, 0)];
+            world.push(Arc::new(Triangle::new([a, b, c], material.clone())));
+            world.push(Arc::new(Triangle::new([a, c, d], material.clone())));
+        }
+    }
+    let mut lights = LightSet::new();
+    lights.add_analytic(AnalyticLight::Point(PointLight {
+        position: DVec3::new(0.0, 3.0, 0.0),
+        intensity: DVec3::splat(10.0),
+        radius: 0.0,
+        profile: None,
+    }));
+    let renderer = Renderer::new(
+        Arc::new(Qbvh::new(world)),
+        Arc::new(SolidBackground::new(DVec3::ZERO)),
+    )
+    .with_lights(Arc::new(lights));
+    let camera = Camera::new(
+        DVec3::new(0.0, 6.0, 0.1),
+        DVec3::ZERO,
+        DVec3::Y,
+        60.0,
+        settings.aspect_ratio(),
+        0.0,
+        6.0,
+    );
+    renderer.render_passes(&camera, settings).stats
+}
+
+#[test]
+fn renders_count_their_work_with_the_stats_feature() {
+    let settings = RenderSettings {
+        width: 16,
+        height: 16,
+        samples_per_pixel: 2,
+        max_depth: 3,
+        ..RenderSettings::default()
+    };
+    let stats = render(&settings);
+    if !RenderStats::enabled() {
+        assert_eq!(stats, RenderStats::default());
+        return;
+    }
+    assert!(stats.rays >= 16 * 16 * 2, "{stats:?}");
+    assert!(stats.shadow_rays > 0, "{stats:?}");
+    assert!(stats.node_tests > 0, "{stats:?}");
+    assert!(stats.triangle_tests > 0, "{stats:?}");
+    // Tracing is deterministic, and so are the counts.
+    assert_eq!(render(&settings), stats);
+}
@@ -0,0 +1,63 @@
+use glam::DVec3;
+use raytracer::environment::SolidBackground;
+use raytracer::material::{DiffuseLight, Lambertian};
+use raytracer::objects::sphere::Sphere;
+use raytracer::ray::Ray;
+use raytracer::renderer::{RenderSettings, Renderer, SampleClamp};
+use raytracer::sampler::IndependentSampler;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn settings(sample_clamp: SampleClamp) -> RenderSettings {
+    RenderSettings {
+        sample_clamp,
+        ..RenderSettings::default()
+    }
+}
+
+#[test]
+fn direct_and_indirect_light_are_clamped_separately() {
+    let light = Arc::new(Sphere::new(
+        DVec3::ZERO,
+        1.0,
+        Arc::new(DiffuseLight::new(DVec3::new(10.0, 5.0, 0.0))),
+    ));
+    let renderer = Renderer::new(light, Arc::new(SolidBackground::new(DVec3::ZERO)));
+    let ray = Ray::new(DVec3::new(0.0, 0.0, 5.0), -DVec3::Z);
+    let mut sampler = IndependentSampler::new(1);
+    let mut color = |clamp| renderer.ray_color(&ray, &settings(clamp), &mut sampler);
+
+    assert_eq!(color(SampleClamp::default()), DVec3::new(10.0, 5.0, 0.0));
+    let direct = SampleClamp {
+        direct: Some(2.0),
+        ..SampleClamp::default()
+    };
+    // Scaled down with the hue kept.
+    assert_eq!(color(direct), DVec3::new(2.0, 1.0, 0.0));
+    let indirect = SampleClamp {
+        indirect: Some(2.0),
+        ..SampleClamp::default()
+    };
+    assert_eq!(color(indirect), DVec3::new(10.0, 5.0, 0.0));
+}
+
+#[test]
+fn invalid_samples_are_discarded() {
+    let ball = Arc::new(Sphere::new(
+        DVec3::ZERO,
+        1.0,
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE)))),
+    ));
+    let renderer = Renderer::new(ball, Arc::new(SolidBackground::new(DVec3::NAN)));
+    let ray = Ray::new(DVec3::new(0.0, 0.0, 5.0), -DVec3::Z);
+    let mut sampler = IndependentSampler::new(1);
+    let color = renderer.ray_color(&ray, &RenderSettings::default(), &mut sampler);
+    assert_eq!(color, DVec3::ZERO);
+
+    let keep = SampleClamp {
+        discard_invalid: false,
+        ..SampleClamp::default()
+    };
+    let color = renderer.ray_color(&ray, &settings(keep), &mut sampler);
+    assert!(color.is_nan());
+}
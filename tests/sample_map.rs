@@ -0,0 +1,99 @@
+use glam::{DVec2, DVec3};
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::sphere::Sphere;
+use raytracer::renderer::{RenderPasses, RenderSettings, Renderer};
+use raytracer::sample_map::{SampleMap, SampleRegion};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+const SIZE: u32 = 16;
+
+fn render(sample_map: Option<SampleMap>) -> RenderPasses {
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let sphere = Sphere::new(DVec3::ZERO, 1.0, material);
+    let mut renderer = Renderer::new(Arc::new(sphere), Arc::new(SolidBackground::new(DVec3::ONE)));
+    if let Some(sample_map) = sample_map {
+        renderer = renderer.with_sample_map(Arc::new(sample_map));
+    }
+    let camera = Camera::new(
+        DVec3::new(0.0, 0.0, 4.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        4.0,
+    );
+    let settings = RenderSettings {
+        width: SIZE,
+        height: SIZE,
+        samples_per_pixel: 8,
+        max_depth: 4,
+        ..RenderSettings::default()
+    };
+    renderer.render_passes(&camera, &settings)
+}
+
+fn region(min: (f64, f64), max: (f64, f64), samples: f64, resolution: f64) -> SampleRegion {
+    SampleRegion {
+        min: DVec2::new(min.0, min.1),
+        max: DVec2::new(max.0, max.1),
+        samples,
+        resolution,
+    }
+}
+
+#[test]
+fn masks_and_regions_scale_the_samples() {
+    let map = SampleMap::new(vec![region((0.0, 0.0), (0.5, 1.0), 0.5, 1.0)]).with_mask(
+        2,
+        1,
+        vec![1.0, 0.25],
+    );
+    assert_eq!(map.density(0, 0, SIZE, SIZE), 0.5);
+    assert_eq!(map.density(SIZE - 1, 0, SIZE, SIZE), 0.25);
+    assert_eq!(map.block_size(0, 0, SIZE, SIZE), 1);
+}
+
+#[test]
+fn letterbox_bars_get_no_samples() {
+    let full = render(None);
+    // Bars over the top and bottom quarters.
+    let passes = render(Some(SampleMap::new(vec![
+        region((0.0, 0.0), (1.0, 0.25), 0.0, 1.0),
+        region((0.0, 0.75), (1.0, 1.0), 0.0, 1.0),
+    ])));
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let color = passes.beauty.get(x, y);
+            if !(SIZE / 4..3 * SIZE / 4).contains(&y) {
+                assert_eq!(color, DVec3::ZERO, "({x}, {y})");
+            } else {
+                assert_eq!(color, full.beauty.get(x, y), "({x}, {y})");
+            }
+        }
+    }
+}
+
+#[test]
+fn low_resolution_regions_render_blocks_of_pixels() {
+    let passes = render(Some(SampleMap::new(vec![region(
+        (0.0, 0.0),
+        (1.0, 1.0),
+        1.0,
+        0.25,
+    )])));
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let first = passes.beauty.get(x / 4 * 4, y / 4 * 4);
+            assert_eq!(passes.beauty.get(x, y), first, "({x}, {y})");
+        }
+    }
+    // The sphere still shows, darker than the background, in the middle.
+    let (middle, corner) = (passes.beauty.get(8, 8), passes.beauty.get(0, 0));
+    assert!(middle.x < corner.x, "{middle} vs {corner}");
+}
@@ -0,0 +1,668 @@
+use glam::DVec3;
+use raytracer::color::{blackbody, parse_css};
+use raytracer::hittable::Hittable;
+use raytracer::interval::Interval;
+use raytracer::ray::Ray;
+use raytracer::scene::{
+    ColorDef, EnvironmentDef, MaterialDef, ObjectDef, Reference, Scene, SceneFormat,
+    SceneObjectDef, SceneValidationError, SphereDef, TextureDef,
+};
+use std::f64::consts::PI;
+
+const YAML: &str = "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: lambertian
+      texture: { type: solid_color, color: [0.5, 0.5, 0.5] }
+";
+
+#[test]
+fn format_follows_the_extension() {
+    assert_eq!(SceneFormat::from_path("a/b.yml"), SceneFormat::Yaml);
+    assert_eq!(SceneFormat::from_path("scene.YAML"), SceneFormat::Yaml);
+    assert_eq!(SceneFormat::from_path("scene.toml"), SceneFormat::Toml);
+    assert_eq!(SceneFormat::from_path("scene.ron"), SceneFormat::Ron);
+    assert_eq!(SceneFormat::from_path("scene.json"), SceneFormat::Json);
+    assert_eq!(SceneFormat::from_path("scene"), SceneFormat::Json);
+}
+
+#[test]
+fn yaml_scene_loads() {
+    let (config, _, objects, _) = Scene::from_source_at(YAML, SceneFormat::Yaml, 0.0).unwrap();
+    assert_eq!(config.objects.len(), 1);
+    assert!(objects.bounding_box().is_some());
+}
+
+#[test]
+fn parse_errors_name_the_field() {
+    let source = YAML.replace("type: lambertian", "type: velvet");
+    let error = SceneFormat::Yaml.parse(&source).err().unwrap().to_string();
+    assert!(error.starts_with("objects[0]"), "{error}");
+    assert!(error.contains("velvet"), "{error}");
+}
+
+#[test]
+fn validation_lists_every_problem() {
+    let source = YAML.replace("radius: 1", "radius: 0")
+        + "background: { type: hdr, path: missing/sky.hdr }\n";
+    let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        [
+            "background.path: file 'missing/sky.hdr' not found",
+            "objects[0].radius: must be non-zero",
+        ]
+    );
+}
+
+#[test]
+fn scenes_built_in_code_are_saved_and_loaded() {
+    let mut config = SceneFormat::Yaml.parse(YAML).unwrap();
+    let texture = TextureDef::SolidColor {
+        color: ColorDef::Rgb(DVec3::new(0.2, 0.4, 0.8)),
+    };
+    config.objects.push(SceneObjectDef {
+        name: Some("added".to_string()),
+        object: ObjectDef::Sphere(SphereDef {
+            center: DVec3::new(3.0, 0.0, 0.0),
+            radius: 0.5,
+            material: Reference::Inline(MaterialDef::Lambertian {
+                texture: Reference::Inline(texture),
+            }),
+        }),
+        visible_to_camera: true,
+        casts_shadows: true,
+        visible_in_reflections: true,
+    });
+    config.camera.lookfrom = DVec3::new(0.0, 0.0, 8.0);
+
+    let path = std::env::temp_dir().join("raytracer-built-scene.json");
+    let path = path.to_str().unwrap();
+    Scene::to_file(&config, path).unwrap();
+    let (saved, _, world, _) = Scene::from_file(path).unwrap();
+    assert_eq!(saved.camera.lookfrom, DVec3::new(0.0, 0.0, 8.0));
+    let ray = Ray::new(DVec3::new(3.0, 0.0, 5.0), DVec3::NEG_Z);
+    let rec = world.hit(&ray, Interval::after(1e-3)).unwrap();
+    assert!((rec.t - 4.5).abs() < 1e-9, "{}", rec.t);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn scenes_round_trip_through_every_format() {
+    let config = SceneFormat::Yaml.parse(YAML).unwrap();
+    let expected = serde_json::to_value(&config).unwrap();
+    for format in [
+        SceneFormat::Json,
+        SceneFormat::Yaml,
+        SceneFormat::Toml,
+        SceneFormat::Ron,
+    ] {
+        let text = format.write(&config).unwrap();
+        let reloaded = format.parse(&text).unwrap();
+        assert_eq!(
+            serde_json::to_value(&reloaded).unwrap(),
+            expected,
+            "{format:?}"
+        );
+    }
+}
+
+#[test]
+fn colors_can_be_given_as_temperatures() {
+    let warm = blackbody(3200.0);
+    assert_eq!(warm.x, 1.0);
+    assert!(warm.y > warm.z && warm.z > 0.0, "{warm}");
+    assert!(blackbody(6500.0).abs_diff_eq(DVec3::ONE, 0.1));
+
+    let source = YAML.replace(
+        "color: [0.5, 0.5, 0.5]",
+        "color: { color_temperature: 3200 }",
+    )
+        + "background: { type: solid, color: { color_temperature: 3200, intensity: 2 } }\n";
+    let config = SceneFormat::Yaml.parse(&source).unwrap();
+    let Some(EnvironmentDef::Solid { color }) = &config.background else {
+        panic!("expected a solid background");
+    };
+    assert_eq!(color.rgb(), 2.0 * warm);
+    assert!(Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).is_ok());
+}
+
+#[test]
+fn colors_can_be_given_as_css() {
+    // sRGB code values are linearised.
+    let orange = parse_css("#ffcc00").unwrap();
+    assert!(
+        orange.abs_diff_eq(DVec3::new(1.0, 0.6038, 0.0), 1e-4),
+        "{orange}"
+    );
+    assert_eq!(parse_css("#fc0"), Some(orange));
+    assert_eq!(parse_css("White"), Some(DVec3::ONE));
+    assert_eq!(parse_css("rebeccapurple"), parse_css("#663399"));
+    assert_eq!(parse_css("#ffcc0"), None);
+
+    let source = YAML.replace("[0.5, 0.5, 0.5]", "'#ffcc00'")
+        + "background: { type: gradient, horizon: white, zenith: skyblue }\n";
+    assert!(Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).is_ok());
+    let error = SceneFormat::Yaml
+        .parse(&source.replace("skyblue", "sky"))
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.starts_with("background.zenith"), "{error}");
+    assert!(error.contains("unknown colour 'sky'"), "{error}");
+}
+
+#[test]
+fn values_can_carry_units() {
+    let with_units = |vfov: &str, fuzz: &str| {
+        let source = YAML.replace("vfov: 40", &format!("vfov: {vfov}")).replace(
+            "type: lambertian",
+            &format!("type: metal\n      fuzz: {fuzz}"),
+        );
+        let config = SceneFormat::Yaml.parse(&source)?;
+        Ok::<_, Box<dyn std::error::Error>>(serde_json::to_value(&config)?)
+    };
+    let plain = with_units("40", "0.25").unwrap();
+    for (vfov, fuzz) in [
+        ("40deg", "25%"),
+        ("'40 °'", "2.5e-1"),
+        ("0.6981317007977318rad", "'25 %'"),
+    ] {
+        let value = with_units(vfov, fuzz).unwrap();
+        let vfov = value["camera"]["vfov"].as_f64().unwrap();
+        assert!((vfov - 40.0).abs() < 1e-9, "{vfov}");
+        assert_eq!(
+            value["objects"][0]["material"],
+            plain["objects"][0]["material"]
+        );
+    }
+
+    let error = with_units("40%", "0.25").err().unwrap().to_string();
+    assert!(error.starts_with("camera.vfov"), "{error}");
+    assert!(error.contains("expected an angle"), "{error}");
+    let error = with_units("40", "25deg").err().unwrap().to_string();
+    assert!(error.starts_with("objects[0]"), "{error}");
+    assert!(error.contains("expected a fraction"), "{error}");
+}
+
+#[test]
+fn objects_use_library_materials_by_name() {
+    let library = "
+materials:
+  gold:
+    type: metal
+    texture: paint
+    fuzz: 0.1
+  bumpy:
+    type: normal_mapped
+    material: bumpy
+    normal_map: { type: solid_color, color: [0.5, 0.5, 1] }
+textures:
+  paint: { type: solid_color, color: [1, 0.8, 0.3] }
+";
+    let source = YAML.replace(
+        "    material:\n      type: lambertian\n      texture: { type: solid_color, color: [0.5, 0.5, 0.5] }\n",
+        "    material: gold\n",
+    ) + library;
+    let config = SceneFormat::Yaml.parse(&source).unwrap();
+    assert!(config.materials.contains_key("gold"));
+    let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(error.problems, ["materials.bumpy: refers to itself"]);
+
+    let source = source.replace("material: bumpy", "material: gold");
+    let (_, _, objects, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    assert!(objects.bounding_box().is_some());
+
+    let source = source.replace("material: gold\n", "material: silver\n");
+    let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        [
+            "materials.bumpy.material: unknown material 'silver'",
+            "objects[0].material: unknown material 'silver'",
+        ]
+    );
+}
+
+#[test]
+fn includes_merge_objects_and_libraries() {
+    let dir = std::env::temp_dir().join("raytracer-scene-includes");
+    std::fs::create_dir_all(&dir).unwrap();
+    let parts = dir.join("parts.yaml");
+    std::fs::write(
+        &parts,
+        "
+objects:
+  - type: sphere
+    name: ball
+    center: [0, 0, 0]
+    radius: 1
+    material: gold
+materials:
+  gold: { type: metal, texture: { type: solid_color, color: [1, 0.8, 0.3] }, fuzz: 0 }
+",
+    )
+    .unwrap();
+    let parts = parts.to_str().unwrap();
+    let source = format!(
+        "{YAML}include:\n  - path: {parts}\n  - path: {parts}\n    transform: {{ translate: [10, 0, 0] }}\n"
+    );
+    let (config, _, world, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    assert_eq!(config.objects.len(), 3);
+    assert!(config.include.is_empty());
+    assert!(config.materials.contains_key("gold"));
+    assert!(world.bounding_box().unwrap().max.x >= 11.0 - 1e-9);
+
+    let looping = dir.join("looping.yaml");
+    let looping_source = format!("include:\n  - path: {}\n", looping.to_str().unwrap());
+    std::fs::write(&looping, &looping_source).unwrap();
+    let error = Scene::from_source_at(&format!("{YAML}{looping_source}"), SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("includes itself"), "{error}");
+}
+
+#[test]
+fn includes_are_read_relative_to_their_files_and_added_once() {
+    let dir = std::env::temp_dir().join("raytracer-relative-includes");
+    std::fs::create_dir_all(dir.join("parts")).unwrap();
+    let ball = "
+objects:
+  - { type: sphere, name: ball, center: [0, 0, 0], radius: 1, material: gold }
+materials:
+  gold: { type: metal, texture: { type: solid_color, color: [1, 0.8, 0.3] }, fuzz: 0 }
+";
+    std::fs::write(dir.join("parts/ball.yaml"), ball).unwrap();
+    // Beside the file that includes it, not the scene or the working
+    // directory.
+    std::fs::write(
+        dir.join("parts/table.yaml"),
+        "include: [{ path: ball.yaml }, { path: ball.yaml, transform: { translate: [5, 0, 0] } }]\n",
+    )
+    .unwrap();
+    let scene = dir.join("scene.yaml");
+    let write_scene = |includes: &str| {
+        std::fs::write(&scene, format!("{YAML}include: {includes}\n")).unwrap();
+        Scene::from_file(scene.to_str().unwrap())
+    };
+
+    // The ball the table adds where it is, and the scene again, is added
+    // once; the table's placed copy stays.
+    let (config, _, _, _) =
+        write_scene("[{ path: parts/table.yaml }, { path: parts/ball.yaml }]").unwrap();
+    assert_eq!(config.objects.len(), 3);
+    assert_eq!(config.objects[1].name.as_deref(), Some("ball"));
+
+    // Two includes can't give one name to different materials.
+    std::fs::write(
+        dir.join("parts/copper.yaml"),
+        ball.replace("[1, 0.8, 0.3]", "[0.95, 0.64, 0.54]"),
+    )
+    .unwrap();
+    let error = write_scene("[{ path: parts/ball.yaml }, { path: parts/copper.yaml, transform: { translate: [0, 5, 0] } }]")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("materials.gold"), "{error}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lights_in_groups_and_nodes_are_sampled_at_their_placed_power() {
+    let source = "
+camera: { lookfrom: [0, 0, 10], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 10 }
+objects:
+  - type: node
+    transform: { scale: 2 }
+    children:
+      - { type: sphere, center: [0, 0, 0], radius: 0.5, material: bulb }
+  - type: group
+    transform: { translate: [5, 0, 0] }
+    objects:
+      - { type: sphere, center: [0, 0, 0], radius: 1, material: bulb }
+materials:
+  bulb: { type: diffuse_light, power: 100 }
+";
+    let (_, _, world, lights) = Scene::from_source_at(source, SceneFormat::Yaml, 0.0).unwrap();
+    assert_eq!(lights.len(), 2);
+    // The node doubles the ball to a radius of 1, over which the power is
+    // shared out.
+    for x in [0.0, 5.0] {
+        let eye = DVec3::new(x, 0.0, 10.0);
+        let ray = Ray::new(eye, DVec3::NEG_Z);
+        let rec = world.hit(&ray, Interval::after(0.001)).expect("ray hits");
+        assert!((rec.t - 9.0).abs() < 1e-9, "{}", rec.t);
+        assert!(rec.light.is_some());
+        let radiance = rec.material.emitted(rec.u, rec.v, rec.point);
+        let power = radiance.x * PI * 4.0 * PI;
+        assert!((power - 100.0).abs() < 1e-9, "{power}");
+    }
+}
+
+#[test]
+fn lights_in_watts_keep_their_power_at_any_size() {
+    let source = "
+camera: { lookfrom: [0, 0, 10], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 10 }
+objects:
+  - { type: sphere, center: [-5, 0, 0], radius: 1, material: bulb }
+  - { type: sphere, center: [5, 0, 0], radius: 2, material: bulb }
+  - type: sphere
+    center: [0, 5, 0]
+    radius: 1
+    material:
+      type: diffuse_light
+      color: [2, 2, 2]
+      texture: { type: solid_color, color: [0.5, 0.25, 0] }
+materials:
+  bulb: { type: diffuse_light, power: 100 }
+";
+    let (_, _, world, _) = Scene::from_source_at(source, SceneFormat::Yaml, 0.0).unwrap();
+    let radiance = |target: DVec3| {
+        let ray = Ray::new(
+            DVec3::new(0.0, 0.0, 10.0),
+            target - DVec3::new(0.0, 0.0, 10.0),
+        );
+        let rec = world.hit(&ray, Interval::after(0.001)).expect("ray hits");
+        rec.material.emitted(rec.u, rec.v, rec.point)
+    };
+    // Radiance times π and the area is the power.
+    for (x, radius) in [(-5.0, 1.0), (5.0, 2.0)] {
+        let area = 4.0 * PI * radius * radius;
+        let power = radiance(DVec3::new(x, 0.0, 0.0)).x * PI * area;
+        assert!((power - 100.0).abs() < 1e-9, "{power}");
+    }
+    let tinted = radiance(DVec3::new(0.0, 5.0, 0.0));
+    assert!(
+        tinted.abs_diff_eq(DVec3::new(1.0, 0.5, 0.0), 1e-12),
+        "{tinted}"
+    );
+
+    let error = Scene::from_source_at(
+        &source.replace(
+            "type: sphere, center: [-5",
+            "type: disk, normal: [0, 0, 1], center: [-5",
+        ),
+        SceneFormat::Yaml,
+        0.0,
+    )
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("power"), "{error}");
+}
+
+#[test]
+fn lights_glowing_away_from_their_centres_are_still_sampled() {
+    // A triangle centred on the origin, dark there but lit further out.
+    let path = std::env::temp_dir().join("raytracer-ring-light.obj");
+    std::fs::write(&path, "v -1 -1 0\nv 2 -1 0\nv -1 2 0\nf 1 2 3\n").unwrap();
+    let source = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - type: mesh
+    path: '{}'
+    material:
+      type: diffuse_light
+      texture:
+        type: radial_gradient
+        start: {{ type: solid_color, color: [0, 0, 0] }}
+        end: {{ type: solid_color, color: [1, 1, 1] }}
+        center: [0, 0, 0]
+        radius: 0.5
+",
+        path.display()
+    );
+    let (_, _, _, lights) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    assert_eq!(lights.len(), 1);
+}
+
+#[test]
+fn textured_lights_in_watts_are_sized_by_their_average() {
+    // Black at the bottom of the ball, white at the top and half lit on
+    // average, so the top shines twice as brightly as a white ball would.
+    let source = "
+camera: { lookfrom: [0, 0, 10], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 10 }
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: diffuse_light
+      power: 100
+      texture:
+        type: linear_gradient
+        start: { type: solid_color, color: [0, 0, 0] }
+        end: { type: solid_color, color: [1, 1, 1] }
+        from: [0, -1, 0]
+        to: [0, 1, 0]
+";
+    let (_, _, world, _) = Scene::from_source_at(source, SceneFormat::Yaml, 0.0).unwrap();
+    let ray = Ray::new(DVec3::new(0.0, 10.0, 0.0), DVec3::NEG_Y);
+    let rec = world.hit(&ray, Interval::after(0.001)).expect("ray hits");
+    let top = rec.material.emitted(rec.u, rec.v, rec.point).x;
+    let power = top * PI * 4.0 * PI;
+    assert!((power - 200.0).abs() < 1e-6, "{power}");
+}
+
+#[test]
+fn autofocus_measures_the_focus_distance() {
+    let (_, before, _, _) = Scene::from_source_at(YAML, SceneFormat::Yaml, 0.0).unwrap();
+    let source = YAML.replace("focus_dist: 5", "focus_dist: 5\n  focus: auto");
+    let (_, camera, _, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    // The sphere's near side, along the view axis.
+    let expected = DVec3::new(0.0, 1.0, 5.0).length() - 1.0;
+    assert!((camera.focus_distance() - expected).abs() < 1e-6);
+    // Focusing leaves the framing alone.
+    let point = DVec3::new(0.3, 0.2, 0.1);
+    let ndc = camera.project(point).unwrap();
+    assert!(ndc.abs_diff_eq(before.project(point).unwrap(), 1e-9));
+
+    let source = YAML.replace("focus_dist: 5", "focus_dist: 5\n  focus: { object: far }")
+        + "  - { type: sphere, name: far, center: [0, 1, -5], radius: 1, material: { type: lambertian, texture: { type: solid_color, color: [1, 1, 1] } } }\n";
+    let (_, camera, _, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    // The ray at its centre meets it at [0, 1, -4], 9 units straight ahead.
+    let view = DVec3::new(0.0, -1.0, -5.0).normalize();
+    let expected = DVec3::new(0.0, 0.0, -9.0).dot(view);
+    assert!(
+        (camera.focus_distance() - expected).abs() < 1e-6,
+        "{}",
+        camera.focus_distance()
+    );
+}
+
+#[test]
+fn materials_can_be_mixed_and_coated() {
+    let material = "
+    material:
+      type: mix
+      a: { type: layered, base: paint, coat_roughness: 0.05 }
+      b: { type: metal, texture: { type: solid_color, color: [0.9, 0.9, 0.9] }, fuzz: 0.3 }
+      factor: FACTOR
+materials:
+  paint: { type: lambertian, texture: { type: solid_color, color: [0.6, 0.1, 0.1] } }
+";
+    let with_factor = |factor: &str| {
+        let source = YAML.replace(
+            "    material:\n      type: lambertian\n      texture: { type: solid_color, color: [0.5, 0.5, 0.5] }\n",
+            &material.replace("FACTOR", factor),
+        );
+        Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+    };
+    assert!(with_factor("0.3").is_ok());
+    assert!(with_factor("{ type: solid_color, color: [0.2, 0.2, 0.2] }").is_ok());
+
+    let error = with_factor("1.5").err().unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        ["objects[0].material.factor: must be between 0 and 1"]
+    );
+    let error = with_factor("rust").err().unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        ["objects[0].material.factor: unknown texture 'rust'"]
+    );
+}
+
+#[test]
+fn instances_place_copies_of_one_mesh_by_their_transforms() {
+    // A triangle filling the lower left half of a square, so turns show.
+    let path = std::env::temp_dir().join("raytracer-instanced.obj");
+    std::fs::write(&path, "v -1 -1 0\nv 1 -1 0\nv -1 1 0\nf 1 2 3\n").unwrap();
+    let source = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+meshes:
+  shard:
+    path: '{}'
+    material: {{ type: lambertian, texture: {{ type: solid_color, color: [0.5, 0.5, 0.5] }} }}
+objects:
+  - type: instance
+    mesh: shard
+    transforms:
+      - {{ translate: [-3, 0, 0], scale: 0.5 }}
+      - {{ translate: [3, 0, -1], rotate: [0, 0, 180] }}
+",
+        path.display()
+    );
+    let (_, _, world, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    let hit = |x: f64, y: f64| {
+        let ray = Ray::new(DVec3::new(x, y, 5.0), DVec3::NEG_Z);
+        world.hit(&ray, Interval::after(1e-3)).map(|rec| rec.point)
+    };
+    // The first copy is halved about its new centre.
+    let point = hit(-3.4, -0.4).unwrap();
+    assert!(
+        point.abs_diff_eq(DVec3::new(-3.4, -0.4, 0.0), 1e-9),
+        "{point}"
+    );
+    assert!(hit(-2.6, 0.4).is_none());
+    assert!(hit(-3.8, 0.0).is_none());
+    // The second is turned half way round, to fill the upper right, and
+    // moved back.
+    let point = hit(3.8, 0.8).unwrap();
+    assert!(
+        point.abs_diff_eq(DVec3::new(3.8, 0.8, -1.0), 1e-9),
+        "{point}"
+    );
+    assert!(hit(2.2, -0.8).is_none());
+    // Nothing is left where the file put the mesh.
+    assert!(hit(-0.5, -0.5).is_none());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn distant_instances_use_coarser_meshes() {
+    // A square, and a triangle missing its upper right half.
+    let dir = std::env::temp_dir();
+    let fine = dir.join("raytracer-lod-fine.obj");
+    let coarse = dir.join("raytracer-lod-coarse.obj");
+    let square = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nf 1 2 3\nf 1 3 4\n";
+    std::fs::write(&fine, square).unwrap();
+    std::fs::write(&coarse, "v -1 -1 0\nv 1 -1 0\nv -1 1 0\nf 1 2 3\n").unwrap();
+    let scene = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+meshes:
+  rock:
+    path: '{}'
+    material: {{ type: lambertian, texture: {{ type: solid_color, color: [0.5, 0.5, 0.5] }} }}
+    lods: [{{ path: '{}', distance: DISTANCE }}]
+objects:
+  - type: instance
+    mesh: rock
+    transforms: [{{ translate: [0, 0, 0] }}, {{ translate: [10, 0, -30] }}]
+",
+        fine.display(),
+        coarse.display()
+    );
+    let source = scene.replace("DISTANCE", "20");
+    let (_, _, world, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    let eye = DVec3::new(0.0, 0.0, 5.0);
+    let hits = |target: DVec3| {
+        let ray = Ray::new(eye, target - eye);
+        world.hit(&ray, Interval::after(1e-3)).is_some()
+    };
+    assert!(hits(DVec3::new(0.8, 0.8, 0.0)));
+    assert!(hits(DVec3::new(9.2, -0.8, -30.0)));
+    assert!(!hits(DVec3::new(10.8, 0.8, -30.0)));
+
+    let source = scene.replace("DISTANCE", "-1");
+    let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        ["meshes.rock.lods[0].distance: must be positive"]
+    );
+    std::fs::remove_file(&fine).unwrap();
+    std::fs::remove_file(&coarse).unwrap();
+}
+
+// Meshes moved away by the groups and nodes around them are judged by where
+// they end up, not by where their file puts them.
+#[test]
+fn transformed_meshes_pick_their_level_where_they_are_placed() {
+    let dir = std::env::temp_dir();
+    let fine = dir.join("raytracer-placed-lod-fine.obj");
+    let coarse = dir.join("raytracer-placed-lod-coarse.obj");
+    let square = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nf 1 2 3\nf 1 3 4\n";
+    std::fs::write(&fine, square).unwrap();
+    std::fs::write(&coarse, "v -1 -1 0\nv 1 -1 0\nv -1 1 0\nf 1 2 3\n").unwrap();
+    let mesh = format!(
+        "{{ type: mesh, path: '{}', material: {{ type: lambertian, texture: {{ type: solid_color, color: [0.5, 0.5, 0.5] }} }}, lods: [{{ path: '{}', distance: 20 }}] }}",
+        fine.display(),
+        coarse.display()
+    );
+    let source = format!(
+        "
+camera: {{ lookfrom: [0, 0, 5], lookat: [0, 0, 0], vup: [0, 1, 0], vfov: 40, aperture: 0, focus_dist: 5 }}
+objects:
+  - {mesh}
+  - type: group
+    transform: {{ translate: [10, 0, -30] }}
+    objects: [{mesh}]
+  - type: node
+    transform: {{ translate: [0, 5, 0] }}
+    children:
+      - type: node
+        transform: {{ translate: [-10, 0, -30] }}
+        children: [{mesh}]
+"
+    );
+    let (_, _, world, _) = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).unwrap();
+    let eye = DVec3::new(0.0, 0.0, 5.0);
+    let hits = |target: DVec3| {
+        let ray = Ray::new(eye, target - eye);
+        world.hit(&ray, Interval::after(1e-3)).is_some()
+    };
+    // The mesh left in place keeps its square; the placed copies lose the
+    // upper right half of theirs.
+    assert!(hits(DVec3::new(0.8, 0.8, 0.0)));
+    assert!(hits(DVec3::new(9.2, -0.8, -30.0)));
+    assert!(!hits(DVec3::new(10.8, 0.8, -30.0)));
+    assert!(hits(DVec3::new(-10.8, 4.2, -30.0)));
+    assert!(!hits(DVec3::new(-9.2, 5.8, -30.0)));
+    std::fs::remove_file(&fine).unwrap();
+    std::fs::remove_file(&coarse).unwrap();
+}
@@ -0,0 +1,69 @@
+use glam::DVec3;
+use raytracer::hittable::{Hittable, AABB};
+use raytracer::interval::Interval;
+use raytracer::material::{Lambertian, Material};
+use raytracer::objects::raymarch::DistanceField;
+use raytracer::objects::sdf::{SdfExpr, SdfFn, SdfObject};
+use raytracer::ray::Ray;
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::ONE))))
+}
+
+fn sphere(x: f64) -> Box<SdfExpr> {
+    Box::new(SdfExpr::Sphere {
+        center: DVec3::new(x, 0.0, 0.0),
+        radius: 0.5,
+    })
+}
+
+#[test]
+fn expression_and_closure_spheres_match_the_analytic_hit() {
+    let ray = Ray::new(DVec3::new(0.2, 0.1, 3.0), -DVec3::Z);
+    // Analytic entry point of the ray into the unit sphere.
+    let expected = 3.0 - (1.0f64 - 0.2 * 0.2 - 0.1 * 0.1).sqrt();
+    let bounds = AABB::new(DVec3::splat(-1.0), DVec3::splat(1.0));
+    let objects: [Arc<dyn Hittable>; 2] = [
+        Arc::new(SdfObject::new(
+            SdfExpr::Sphere {
+                center: DVec3::ZERO,
+                radius: 1.0,
+            },
+            material(),
+        )),
+        Arc::new(SdfObject::new(
+            SdfFn::new(bounds, |p: DVec3| p.length() - 1.0),
+            material(),
+        )),
+    ];
+    for object in objects {
+        let rec = object.hit(&ray, Interval::after(0.001)).expect("ray hits");
+        assert!((rec.t - expected).abs() < 1e-3, "t = {}", rec.t);
+        let normal = rec.point.normalize();
+        assert!(rec.normal.abs_diff_eq(normal, 1e-3), "{}", rec.normal);
+    }
+}
+
+#[test]
+fn csg_operations_combine_distances() {
+    let union = SdfExpr::Union(sphere(-0.4), sphere(0.4));
+    let intersection = SdfExpr::Intersection(sphere(-0.4), sphere(0.4));
+    let difference = SdfExpr::Difference(sphere(-0.4), sphere(0.4));
+    let blended = SdfExpr::SmoothUnion(sphere(-0.4), sphere(0.4), 0.5);
+
+    let origin = DVec3::ZERO;
+    assert!(union.distance(origin) < 0.0);
+    assert!(intersection.distance(origin) < 0.0);
+    assert!(difference.distance(origin) > 0.0);
+    assert!(difference.distance(DVec3::new(-0.7, 0.0, 0.0)) < 0.0);
+
+    // The blend fills the waist between the spheres, and stays inside its
+    // padded bounds.
+    let waist = DVec3::new(0.0, 0.35, 0.0);
+    assert!(union.distance(waist) > 0.0);
+    assert!(blended.distance(waist) < 0.0);
+    let bounds = blended.bounds();
+    assert!(bounds.max.y > 0.5 && bounds.min.x < -0.9);
+}
@@ -0,0 +1,92 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::environment::SolidBackground;
+use raytracer::hittable::{Hittable, HittableList};
+use raytracer::lights::{AnalyticLight, LightSet, PointLight};
+use raytracer::material::{Lambertian, Material, ShadowCatcher};
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::renderer::{RenderPasses, RenderSettings, Renderer};
+use raytracer::texture::SolidColor;
+use std::sync::Arc;
+
+// A ball over a shadow-catching ground, lit from straight above by a point
+// light. The camera looks down at the middle of the ball's shadow, with
+// the ball itself above it in the frame.
+fn render(transparent: bool, sky: DVec3) -> RenderPasses {
+    let grey = Arc::new(SolidColor::new(DVec3::splat(0.5)));
+    let ground: Arc<dyn Material> = Arc::new(ShadowCatcher::new(grey.clone()));
+    let ball: Arc<dyn Material> = Arc::new(Lambertian::new(grey));
+    let mut world = HittableList::new();
+    let corners = [
+        (-100.0, -100.0),
+        (100.0, -100.0),
+        (100.0, 100.0),
+        (-100.0, 100.0),
+    ]
+    .map(|(x, z)| DVec3::new(x, 0.0, z));
+    for vertices in [
+        [corners[0], corners[2], corners[1]],
+        [corners[0], corners[3], corners[2]],
+    ] {
+        world.push(Arc::new(Triangle::new(vertices, ground.clone())));
+    }
+    world.push(Arc::new(Sphere::new(DVec3::new(0.0, 1.0, 0.0), 0.5, ball)));
+    let mut lights = LightSet::new();
+    lights.add_analytic(AnalyticLight::Point(PointLight {
+        position: DVec3::new(0.0, 5.0, 0.0),
+        intensity: DVec3::splat(20.0),
+        radius: 0.0,
+        profile: None,
+    }));
+    let world: Arc<dyn Hittable> = Arc::new(world);
+    let renderer =
+        Renderer::new(world, Arc::new(SolidBackground::new(sky))).with_lights(Arc::new(lights));
+    let camera = Camera::new(
+        DVec3::new(0.0, 2.0, 5.0),
+        DVec3::ZERO,
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        5.0,
+    );
+    let settings = RenderSettings {
+        width: 9,
+        height: 9,
+        samples_per_pixel: 16,
+        max_depth: 4,
+        transparent,
+        ..RenderSettings::default()
+    };
+    renderer.render_passes(&camera, &settings)
+}
+
+#[test]
+fn shadow_catchers_show_only_their_shadows() {
+    let beauty = render(true, DVec3::ZERO).beauty;
+    let alpha = beauty
+        .alpha
+        .as_ref()
+        .expect("transparent renders have alpha");
+    let alpha_at = |x: u32, y: u32| alpha[(y * beauty.width + x) as usize];
+    // The shadow, seen as black.
+    assert!(alpha_at(4, 4) > 0.9, "shadow alpha {}", alpha_at(4, 4));
+    assert_eq!(beauty.get(4, 4), DVec3::ZERO);
+    // Open ground, clear.
+    assert!(alpha_at(0, 8) < 0.1, "ground alpha {}", alpha_at(0, 8));
+    // The ball, opaque and lit.
+    assert_eq!(alpha_at(4, 2), 1.0);
+    assert!(beauty.get(4, 2).x > 0.0);
+}
+
+#[test]
+fn opaque_films_show_the_environment_through_catchers() {
+    let beauty = render(false, DVec3::ONE).beauty;
+    assert!(beauty.alpha.is_none());
+    // The white sky, a little darker where the ball blocks some of it, and
+    // much darker in the shadow of the light.
+    let (ground, shadow) = (beauty.get(0, 8), beauty.get(4, 4));
+    assert!(ground.x > 0.8 && ground.x <= 1.0, "{ground}");
+    assert!(shadow.x < 0.7, "{shadow}");
+}
@@ -0,0 +1,65 @@
+use glam::DVec3;
+use raytracer::environment::{SkyGradient, SolidBackground};
+use raytracer::material::Lambertian;
+use raytracer::objects::sphere::Sphere;
+use raytracer::ray::Ray;
+use raytracer::renderer::{RenderSettings, Renderer};
+use raytracer::sampler::IndependentSampler;
+use raytracer::spherical_harmonics::{ShAmbientSettings, ShEnvironment};
+use raytracer::texture::SolidColor;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+fn assert_close(actual: DVec3, expected: DVec3) {
+    assert!(
+        (actual - expected).abs().max_element() < 1e-3,
+        "{actual} != {expected}"
+    );
+}
+
+#[test]
+fn uniform_environments_project_exactly() {
+    let color = DVec3::new(0.2, 0.5, 1.0);
+    let sh = ShEnvironment::project(&SolidBackground::new(color));
+    for normal in [DVec3::Y, -DVec3::X, DVec3::new(1.0, 2.0, -3.0).normalize()] {
+        assert_close(sh.radiance(normal), color);
+        assert_close(sh.irradiance(normal), PI * color);
+    }
+}
+
+#[test]
+fn gradients_light_surfaces_facing_them() {
+    let (horizon, zenith) = (DVec3::ONE, DVec3::new(0.5, 0.7, 1.0));
+    let sh = ShEnvironment::project(&SkyGradient::new(horizon, zenith));
+    // The sky is linear in height, which the first two bands hold exactly:
+    // a constant term lighting every surface and a linear one facing up.
+    let (constant, linear) = ((horizon + zenith) / 2.0, (zenith - horizon) / 2.0);
+    assert_close(
+        sh.irradiance(DVec3::Y),
+        PI * constant + 2.0 * PI / 3.0 * linear,
+    );
+    assert_close(sh.irradiance(DVec3::X), PI * constant);
+    assert_close(sh.radiance(-DVec3::Y), horizon);
+}
+
+#[test]
+fn ambient_ends_paths_without_noise() {
+    let ball = Arc::new(Sphere::new(
+        DVec3::ZERO,
+        1.0,
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(DVec3::splat(
+            0.5,
+        ))))),
+    ));
+    let renderer = Renderer::new(ball, Arc::new(SolidBackground::new(DVec3::ONE)));
+    let settings = RenderSettings {
+        sh_ambient: Some(ShAmbientSettings { after_bounces: 0 }),
+        ..RenderSettings::default()
+    };
+    let ray = Ray::new(DVec3::new(0.0, 0.0, 5.0), -DVec3::Z);
+    let mut sampler = IndependentSampler::new(1);
+    for _ in 0..4 {
+        let color = renderer.ray_color(&ray, &settings, &mut sampler);
+        assert_close(color, DVec3::splat(0.5));
+    }
+}
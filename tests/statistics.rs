@@ -0,0 +1,331 @@
+// Statistical checks on the samplers and BSDF sampling routines. They draw
+// hundreds of thousands of samples, so they only run with
+// `cargo test --features slow-tests`.
+#![cfg(feature = "slow-tests")]
+
+use glam::DVec3;
+use raytracer::hittable::HitRecord;
+use raytracer::material::{Dielectric, Lambertian, Material};
+use raytracer::ray::Ray;
+use raytracer::sampler::{Sampler, SamplerKind};
+use raytracer::texture::SolidColor;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+const KINDS: [SamplerKind; 3] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+];
+
+// Pearson's chi-square statistic against a uniform expectation.
+fn chi_square(counts: &[u64]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    let expected = total as f64 / counts.len() as f64;
+    counts
+        .iter()
+        .map(|&c| (c as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+// Upper 0.1% critical value of the chi-square distribution, using the
+// Wilson-Hilferty approximation.
+fn chi_square_critical(degrees_of_freedom: usize) -> f64 {
+    const Z: f64 = 3.09;
+    let k = degrees_of_freedom as f64;
+    let h = 2.0 / (9.0 * k);
+    k * (1.0 - h + Z * h.sqrt()).powi(3)
+}
+
+fn assert_uniform(name: &str, counts: &[u64]) {
+    let statistic = chi_square(counts);
+    let critical = chi_square_critical(counts.len() - 1);
+    assert!(
+        statistic < critical,
+        "{name}: chi-square {statistic:.1} exceeds {critical:.1}"
+    );
+}
+
+fn bin(value: f64, bins: usize) -> usize {
+    ((value * bins as f64) as usize).min(bins - 1)
+}
+
+// Draws `spp` samples per pixel over a 64x64 image and bins the 2D sample
+// taken after skipping `skip` dimensions.
+fn sampler_histogram(kind: SamplerKind, spp: u32, skip: u32) -> Vec<u64> {
+    const BINS: usize = 16;
+    let mut counts = vec![0; BINS * BINS];
+    let mut sampler = kind.create(spp, 3);
+    for y in 0..64 {
+        for x in 0..64 {
+            for index in 0..spp {
+                sampler.start_pixel(x, y, index);
+                for _ in 0..skip {
+                    sampler.next_1d();
+                }
+                let (u, v) = sampler.next_2d();
+                assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                counts[bin(v, BINS) * BINS + bin(u, BINS)] += 1;
+            }
+        }
+    }
+    counts
+}
+
+#[test]
+fn samplers_are_uniform_on_the_image_plane() {
+    for kind in KINDS {
+        assert_uniform(&format!("{kind:?}"), &sampler_histogram(kind, 16, 0));
+    }
+}
+
+#[test]
+fn samplers_are_uniform_in_higher_dimensions() {
+    for kind in KINDS {
+        for skip in [2, 5, 31] {
+            let name = format!("{kind:?} after {skip} dimensions");
+            assert_uniform(&name, &sampler_histogram(kind, 16, skip));
+        }
+    }
+}
+
+// Variance of a smooth 2D integrand estimated with `spp` samples, measured
+// across pixels (each pixel is an independent trial).
+fn estimator_variance(kind: SamplerKind, spp: u32) -> f64 {
+    const TRIALS: u32 = 2048;
+    let integrand = |u: f64, v: f64| (PI * u).sin() * (1.0 + v * v);
+    let mut sampler = kind.create(spp, 5);
+    let estimates: Vec<f64> = (0..TRIALS)
+        .map(|trial| {
+            let mut sum = 0.0;
+            for index in 0..spp {
+                sampler.start_pixel(trial, 0, index);
+                let (u, v) = sampler.next_2d();
+                sum += integrand(u, v);
+            }
+            sum / spp as f64
+        })
+        .collect();
+    let mean = estimates.iter().sum::<f64>() / TRIALS as f64;
+    estimates.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / (TRIALS - 1) as f64
+}
+
+// Least-squares slope of log(variance) against log(samples).
+fn variance_slope(kind: SamplerKind) -> f64 {
+    let points: Vec<(f64, f64)> = [16u32, 64, 256, 1024]
+        .iter()
+        .map(|&spp| ((spp as f64).ln(), estimator_variance(kind, spp).ln()))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let spread: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    covariance / spread
+}
+
+#[test]
+fn independent_variance_falls_as_one_over_n() {
+    let slope = variance_slope(SamplerKind::Independent);
+    assert!((-1.2..-0.8).contains(&slope), "slope {slope:.2}");
+}
+
+#[test]
+fn stratified_and_halton_converge_faster_than_random() {
+    for kind in [SamplerKind::Stratified, SamplerKind::Halton] {
+        let slope = variance_slope(kind);
+        assert!(slope < -1.5, "{kind:?} slope {slope:.2}");
+    }
+}
+
+fn hit_facing_up(material: &dyn Material) -> HitRecord<'_> {
+    HitRecord {
+        point: DVec3::ZERO,
+        normal: DVec3::Z,
+        material,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        front_face: true,
+        object_id: 0,
+        epsilon: 0.0,
+        tangent: DVec3::ZERO,
+        dpdu: DVec3::X,
+        dpdv: DVec3::Y,
+        dndu: DVec3::ZERO,
+        dndv: DVec3::ZERO,
+        light: None,
+        barycentric: None,
+        color: None,
+    }
+}
+
+// A cosine-weighted hemisphere is uniform in (cos²θ, φ), so its directions
+// bin evenly on that grid.
+#[test]
+fn lambertian_samples_a_cosine_weighted_hemisphere() {
+    const BINS: usize = 16;
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+        DVec3::splat(0.5),
+    ))));
+    let rec = hit_facing_up(&*material);
+    let incoming = Ray::new(DVec3::new(0.3, 0.0, 1.0), DVec3::new(-0.3, 0.0, -1.0));
+
+    for kind in KINDS {
+        let mut counts = vec![0; BINS * BINS];
+        let mut sampler = kind.create(64, 9);
+        for pixel in 0..4096 {
+            for index in 0..64 {
+                sampler.start_pixel(pixel, 0, index);
+                let (scattered, _) = material.scatter(&incoming, &rec, sampler.as_mut()).unwrap();
+                let direction = scattered.direction.normalize();
+                assert!(direction.z >= 0.0, "{kind:?}: direction below the surface");
+                let cos2 = direction.z * direction.z;
+                let phi = direction.y.atan2(direction.x).rem_euclid(2.0 * PI) / (2.0 * PI);
+                counts[bin(cos2, BINS) * BINS + bin(phi, BINS)] += 1;
+            }
+        }
+        assert_uniform(&format!("Lambertian with {kind:?}"), &counts);
+    }
+}
+
+// At normal incidence a dielectric should reflect Schlick's R0 of the time.
+#[test]
+fn dielectric_reflects_at_the_fresnel_rate() {
+    const SAMPLES: u32 = 200_000;
+    let ior = 1.5;
+    let material: Arc<dyn Material> = Arc::new(Dielectric::new(ior));
+    let rec = hit_facing_up(&*material);
+    let incoming = Ray::new(DVec3::Z, -DVec3::Z);
+    let expected = ((1.0 - ior) / (1.0 + ior)).powi(2);
+
+    let mut sampler = SamplerKind::Independent.create(1, 13);
+    let mut reflected = 0;
+    for index in 0..SAMPLES {
+        sampler.start_pixel(index, 0, 0);
+        let (scattered, _) = material.scatter(&incoming, &rec, sampler.as_mut()).unwrap();
+        if scattered.direction.z > 0.0 {
+            reflected += 1;
+        }
+    }
+
+    let rate = reflected as f64 / SAMPLES as f64;
+    let sigma = (expected * (1.0 - expected) / SAMPLES as f64).sqrt();
+    assert!(
+        (rate - expected).abs() < 4.0 * sigma,
+        "reflected {rate:.4}, expected {expected:.4}"
+    );
+}
+
+// Frosting spreads the light a dielectric transmits around the refracted
+// direction, the more so the rougher it is, while losing little of it.
+#[test]
+fn frosted_glass_spreads_with_roughness() {
+    const SAMPLES: u32 = 100_000;
+    let incoming = Ray::new(DVec3::Z, -DVec3::Z);
+    // Mean sine of the transmitted directions' angle from straight through,
+    // and the mean weight.
+    let spread = |roughness: f64| {
+        let material: Arc<dyn Material> = Arc::new(Dielectric {
+            roughness,
+            ..Dielectric::new(1.5)
+        });
+        let rec = hit_facing_up(&*material);
+        let mut sampler = SamplerKind::Independent.create(1, 17);
+        let (mut sines, mut transmitted, mut energy) = (0.0, 0, 0.0);
+        for index in 0..SAMPLES {
+            sampler.start_pixel(index, 0, 0);
+            let Some((scattered, weight)) = material.scatter(&incoming, &rec, sampler.as_mut())
+            else {
+                continue;
+            };
+            energy += weight.x;
+            let direction = scattered.direction.normalize();
+            if direction.z < 0.0 {
+                sines += direction.truncate().length();
+                transmitted += 1;
+            }
+        }
+        (sines / transmitted as f64, energy / SAMPLES as f64)
+    };
+
+    let (smooth, smooth_energy) = spread(0.0);
+    assert!(smooth < 1e-12, "{smooth}");
+    assert!((smooth_energy - 1.0).abs() < 1e-12, "{smooth_energy}");
+    let (frosted, frosted_energy) = spread(0.2);
+    let (ground, ground_energy) = spread(0.6);
+    assert!(0.0 < frosted && frosted < ground, "{frosted} {ground}");
+    assert!(frosted_energy > 0.95, "{frosted_energy}");
+    assert!(ground_energy > 0.8, "{ground_energy}");
+}
+
+// Rough glass weights what it samples by its BSDF over its density, so
+// light samples through it can be weighed against its own.
+#[test]
+fn frosted_glass_evaluates_what_it_samples() {
+    let glass = Dielectric {
+        roughness: 0.4,
+        ..Dielectric::new(1.5)
+    };
+    let incoming = Ray::new(DVec3::new(1.0, 0.0, 1.0), DVec3::new(-1.0, 0.0, -1.0));
+    // Entering from outside and leaving from inside.
+    for front_face in [true, false] {
+        let rec = HitRecord {
+            front_face,
+            ..hit_facing_up(&glass)
+        };
+        let mut sampler = SamplerKind::Independent.create(1, 23);
+        let (mut reflected, mut refracted) = (0, 0);
+        for index in 0..10_000 {
+            sampler.start_pixel(index, 0, 0);
+            let Some((scattered, weight)) = glass.scatter(&incoming, &rec, sampler.as_mut()) else {
+                continue;
+            };
+            let f = glass.eval(&incoming, &rec, scattered.direction).unwrap();
+            let pdf = glass.pdf(&incoming, &rec, scattered.direction);
+            assert!(pdf > 0.0, "{}", scattered.direction);
+            assert!(
+                (f / pdf).abs_diff_eq(weight, 1e-9 * weight.max_element()),
+                "{f} over {pdf}, expected {weight}"
+            );
+            if scattered.direction.z > 0.0 {
+                reflected += 1;
+            } else {
+                refracted += 1;
+            }
+        }
+        assert!(reflected > 0 && refracted > 0, "{reflected} {refracted}");
+    }
+}
+
+// Over the sphere, the density rough glass reports integrates to the
+// chance that it scatters at all.
+#[test]
+fn frosted_glass_densities_are_normalised() {
+    const SAMPLES: u32 = 400_000;
+    let glass = Dielectric {
+        roughness: 0.5,
+        ..Dielectric::new(1.5)
+    };
+    let rec = hit_facing_up(&glass);
+    let incoming = Ray::new(DVec3::new(0.5, 0.0, 1.0), DVec3::new(-0.5, 0.0, -1.0));
+    let mut sampler = SamplerKind::Independent.create(1, 29);
+    let (mut integral, mut scattered) = (0.0, 0);
+    for index in 0..SAMPLES {
+        sampler.start_pixel(index, 0, 0);
+        // Uniformly over the sphere.
+        let (u1, u2) = sampler.next_2d();
+        let z = 1.0 - 2.0 * u1;
+        let r = (1.0 - z * z).sqrt();
+        let direction = DVec3::new(r * (2.0 * PI * u2).cos(), r * (2.0 * PI * u2).sin(), z);
+        integral += glass.pdf(&incoming, &rec, direction) * 4.0 * PI;
+        if glass.scatter(&incoming, &rec, sampler.as_mut()).is_some() {
+            scattered += 1;
+        }
+    }
+    let (integral, expected) = (integral / SAMPLES as f64, scattered as f64 / SAMPLES as f64);
+    assert!(
+        (integral - expected).abs() < 0.02,
+        "{integral}, expected {expected}"
+    );
+}
@@ -0,0 +1,75 @@
+use glam::{DVec2, DVec3};
+use raytracer::objects::cleanup::MeshData;
+use raytracer::objects::subdivision;
+use raytracer::texture::SolidColor;
+
+fn tetrahedron() -> MeshData {
+    MeshData {
+        positions: vec![
+            DVec3::new(1.0, 1.0, 1.0),
+            DVec3::new(1.0, -1.0, -1.0),
+            DVec3::new(-1.0, 1.0, -1.0),
+            DVec3::new(-1.0, -1.0, 1.0),
+        ],
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        colors: Vec::new(),
+        faces: vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]],
+    }
+}
+
+// The unit square in the XY plane, facing +Z, with texture coordinates
+// matching its positions.
+fn square() -> MeshData {
+    let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    MeshData {
+        positions: corners.map(|(x, y)| DVec3::new(x, y, 0.0)).to_vec(),
+        normals: vec![DVec3::Z; 4],
+        texcoords: corners.map(|(x, y)| DVec2::new(x, y)).to_vec(),
+        colors: Vec::new(),
+        faces: vec![[0, 1, 2], [0, 2, 3]],
+    }
+}
+
+#[test]
+fn loop_subdivision_smooths_closed_meshes() {
+    let mut mesh = tetrahedron();
+    subdivision::subdivide(&mut mesh, 1);
+    assert_eq!(mesh.faces.len(), 16);
+    // The four corners and a vertex on each of the six edges.
+    assert_eq!(mesh.positions.len(), 10);
+    // Corners pull in to a quarter of the way out, and edge vertices sit
+    // at half the distance of the corners' midpoints.
+    assert!(mesh.positions[0].abs_diff_eq(DVec3::splat(0.25), 1e-12));
+    for p in &mesh.positions[4..] {
+        assert!((p.length() - 0.5).abs() < 1e-12, "{p}");
+    }
+
+    subdivision::subdivide(&mut mesh, 2);
+    assert_eq!(mesh.faces.len(), 256);
+}
+
+#[test]
+fn open_edges_stay_put_and_texture_coordinates_follow() {
+    let mut mesh = square();
+    subdivision::subdivide(&mut mesh, 1);
+    assert_eq!(mesh.faces.len(), 8);
+    assert_eq!(mesh.texcoords.len(), mesh.positions.len());
+    assert!(mesh.positions.iter().all(|p| p.z == 0.0));
+    // The diagonal is the only edge inside the square.
+    let centre = mesh
+        .texcoords
+        .iter()
+        .position(|&uv| uv == DVec2::splat(0.5))
+        .unwrap();
+    assert!(mesh.positions[centre].abs_diff_eq(DVec3::new(0.5, 0.5, 0.0), 1e-12));
+    assert!(mesh.normals.iter().all(|n| n.abs_diff_eq(DVec3::Z, 1e-12)));
+}
+
+#[test]
+fn displacement_moves_vertices_along_their_normals() {
+    let mut mesh = square();
+    subdivision::displace(&mut mesh, &SolidColor::new(DVec3::splat(0.5)), 2.0);
+    assert!(mesh.positions.iter().all(|p| (p.z - 1.0).abs() < 1e-12));
+    assert!(mesh.normals.iter().all(|n| n.abs_diff_eq(DVec3::Z, 1e-12)));
+}
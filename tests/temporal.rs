@@ -0,0 +1,76 @@
+use glam::DVec3;
+use raytracer::camera::Camera;
+use raytracer::renderer::{ImageBuffer, RenderPasses};
+use raytracer::stats::RenderStats;
+use raytracer::temporal::{motion_vectors, TemporalAccumulator, TemporalSettings};
+
+const SIZE: u32 = 8;
+
+fn camera(x: f64) -> Camera {
+    Camera::new(
+        DVec3::new(x, 0.0, 4.0),
+        DVec3::new(x, 0.0, 0.0),
+        DVec3::Y,
+        40.0,
+        1.0,
+        0.0,
+        4.0,
+    )
+}
+
+fn image(value: f64) -> ImageBuffer {
+    let mut image = ImageBuffer::new(SIZE, SIZE);
+    image.pixels.fill(DVec3::splat(value));
+    image
+}
+
+fn passes(color: f64, depth: f64) -> RenderPasses {
+    RenderPasses {
+        beauty: image(color),
+        albedo: None,
+        normal: None,
+        depth: Some(image(depth)),
+        object_id: None,
+        material_id: None,
+        object_matte: None,
+        material_matte: None,
+        stats: RenderStats::default(),
+    }
+}
+
+#[test]
+fn static_frames_are_averaged() {
+    let mut temporal = TemporalAccumulator::new(TemporalSettings {
+        blend: 0.25,
+        ..TemporalSettings::default()
+    });
+    let first = temporal.accumulate(&camera(0.0), &passes(1.0, 4.0));
+    assert_eq!(first.get(3, 3), DVec3::ONE);
+    let second = temporal.accumulate(&camera(0.0), &passes(0.0, 4.0));
+    assert!(second.get(3, 3).abs_diff_eq(DVec3::splat(0.75), 1e-9));
+    assert!(second.get(0, 7).abs_diff_eq(DVec3::splat(0.75), 1e-9));
+
+    // Something now sits in front of the old surface: no history to reuse.
+    let occluded = temporal.accumulate(&camera(0.0), &passes(0.5, 2.0));
+    assert_eq!(occluded.get(3, 3), DVec3::splat(0.5));
+
+    temporal.reset();
+    let after_cut = temporal.accumulate(&camera(0.0), &passes(0.5, 2.0));
+    assert_eq!(after_cut.get(3, 3), DVec3::splat(0.5));
+}
+
+#[test]
+fn motion_follows_the_camera() {
+    let depth = image(4.0);
+    let still = motion_vectors(&camera(0.0), &camera(0.0), &depth);
+    assert!(still.get(4, 4).abs_diff_eq(DVec3::Z, 1e-9));
+
+    // The camera moved right, so the scene was further right before.
+    let panned = motion_vectors(&camera(-0.1), &camera(0.0), &depth);
+    let motion = panned.get(4, 4);
+    assert!(motion.x > 0.1 && motion.y.abs() < 1e-9 && motion.z == 1.0);
+
+    // The background barely moves with the camera's translation.
+    let background = motion_vectors(&camera(-0.1), &camera(0.0), &image(0.0));
+    assert!(background.get(4, 4).x.abs() < 1e-3);
+}
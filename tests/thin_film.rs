@@ -0,0 +1,79 @@
+use raytracer::material::ThinFilm;
+use raytracer::scene::{Scene, SceneFormat, SceneValidationError};
+
+#[test]
+fn bare_films_leave_fresnel_reflectance() {
+    for index_of_refraction in [1.33, 2.0] {
+        let film = ThinFilm {
+            thickness: 0.0,
+            index_of_refraction,
+        };
+        let head_on = film.over_dielectric(1.0, 1.0, 1.5, 500.0);
+        assert!((head_on - 0.04).abs() < 1e-12, "{head_on}");
+        // Fresnel reflectance of glass at 60 degrees.
+        let oblique = film.over_dielectric(0.5, 1.0, 1.5, 500.0);
+        assert!((oblique - 0.0891867).abs() < 1e-6, "{oblique}");
+        let metal = film.over_metal(1.0, 0.6, 500.0);
+        assert!((metal - 0.6).abs() < 1e-12, "{metal}");
+    }
+    // Leaving glass past the critical angle.
+    let film = ThinFilm {
+        thickness: 100.0,
+        index_of_refraction: 1.33,
+    };
+    assert_eq!(film.over_dielectric(0.2, 1.5, 1.0, 500.0), 1.0);
+}
+
+#[test]
+fn films_interfere() {
+    // A quarter-wave coating cancels reflections at its wavelength only.
+    let coating = ThinFilm {
+        thickness: 550.0 / (4.0 * 1.5f64.sqrt()),
+        index_of_refraction: 1.5f64.sqrt(),
+    };
+    assert!(coating.over_dielectric(1.0, 1.0, 1.5, 550.0) < 1e-12);
+    assert!(coating.over_dielectric(1.0, 1.0, 1.5, 450.0) > 0.004);
+
+    // A soap bubble 200 nm thick is dark at 532 nm head-on, and the
+    // colour shifts as it is seen from an angle.
+    let bubble = ThinFilm {
+        thickness: 200.0,
+        index_of_refraction: 1.33,
+    };
+    assert!(bubble.over_dielectric(1.0, 1.0, 1.0, 532.0) < 1e-12);
+    assert!(bubble.over_dielectric(1.0, 1.0, 1.0, 450.0) > 0.02);
+    assert!(bubble.over_dielectric(0.6, 1.0, 1.0, 532.0) > 0.05);
+}
+
+const YAML: &str = "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: dielectric
+      thin_film: { thickness: THICKNESS }
+";
+
+#[test]
+fn scenes_can_coat_materials() {
+    let source = YAML.replace("THICKNESS", "350");
+    assert!(Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).is_ok());
+
+    let source = YAML.replace("THICKNESS", "-1");
+    let error = Scene::from_source_at(&source, SceneFormat::Yaml, 0.0)
+        .err()
+        .unwrap();
+    let error = error.downcast_ref::<SceneValidationError>().unwrap();
+    assert_eq!(
+        error.problems,
+        ["objects[0].material.thin_film.thickness: must not be negative"]
+    );
+}
@@ -0,0 +1,133 @@
+use glam::DVec3;
+use raytracer::hittable::AABB;
+use raytracer::material::Medium;
+use raytracer::sampler::IndependentSampler;
+use raytracer::scene::{Scene, SceneFormat};
+use raytracer::volume::DensityGrid;
+use std::sync::Arc;
+
+const SAMPLES: usize = 20_000;
+
+#[test]
+fn densities_are_interpolated_between_voxel_centres() {
+    let grid = DensityGrid::new([2, 1, 1], vec![0.0, 1.0]).unwrap();
+    assert_eq!(grid.max_density(), 1.0);
+    let at = |x: f64| grid.density(DVec3::new(x, 0.5, 0.5));
+    assert_eq!(at(0.25), 0.0);
+    assert_eq!(at(0.75), 1.0);
+    assert!((at(0.5) - 0.5).abs() < 1e-12);
+    // Past the outer voxel centres the edge values are held.
+    assert_eq!(at(0.9), 1.0);
+    assert_eq!(at(1.5), 0.0);
+
+    assert!(DensityGrid::new([2, 2, 1], vec![0.0, 1.0]).is_err());
+    let cleaned = DensityGrid::new([2, 1, 1], vec![-1.0, f32::NAN]).unwrap();
+    assert_eq!(cleaned.max_density(), 0.0);
+}
+
+#[test]
+fn vol_files_record_their_resolution_and_bounds() {
+    let mut bytes = b"VOL\x03".to_vec();
+    for int in [1, 2, 1, 1, 1] {
+        bytes.extend(i32::to_le_bytes(int));
+    }
+    for float in [-1.0f32, 0.0, 0.0, 1.0, 2.0, 4.0, 0.25, 0.5] {
+        bytes.extend(float.to_le_bytes());
+    }
+    let grid = DensityGrid::from_vol(&bytes).unwrap();
+    assert_eq!(grid.resolution(), [2, 1, 1]);
+    assert_eq!(grid.bounds.min, DVec3::new(-1.0, 0.0, 0.0));
+    assert_eq!(grid.bounds.max, DVec3::new(1.0, 2.0, 4.0));
+    assert_eq!(grid.density(DVec3::new(0.5, 1.0, 2.0)), 0.5);
+
+    assert!(DensityGrid::from_vol(&bytes[..50]).is_err());
+    assert!(DensityGrid::load("smoke.vdb", None).is_err());
+}
+
+// A medium of uniform unit density on a grid, which must behave as the
+// homogeneous medium with the same coefficients.
+fn uniform(sigma_a: DVec3, sigma_s: DVec3) -> Medium {
+    let mut grid = DensityGrid::new([1, 1, 1], vec![1.0]).unwrap();
+    grid.bounds = AABB::new(DVec3::splat(-10.0), DVec3::splat(10.0));
+    Medium {
+        density: Some(Arc::new(grid)),
+        ..Medium::new(sigma_a, sigma_s)
+    }
+}
+
+#[test]
+fn ratio_tracking_estimates_transmittance() {
+    let medium = uniform(DVec3::new(1.0, 0.2, 0.0), DVec3::new(1.0, 0.3, 0.5));
+    let mut sampler = IndependentSampler::new(3);
+    let mut total = DVec3::ZERO;
+    for _ in 0..SAMPLES {
+        total += medium.transmittance(DVec3::ZERO, DVec3::X, 1.0, &mut sampler);
+    }
+    let expected = (-medium.sigma_t()).exp();
+    let estimate = total / SAMPLES as f64;
+    assert!(
+        (estimate - expected).abs().max_element() < 0.02,
+        "got {estimate}, expected {expected}"
+    );
+}
+
+#[test]
+fn delta_tracking_escapes_with_the_transmittance() {
+    let medium = uniform(DVec3::ZERO, DVec3::new(1.0, 0.5, 0.25));
+    let mut sampler = IndependentSampler::new(5);
+    let mut escaped = DVec3::ZERO;
+    for _ in 0..SAMPLES {
+        let (distance, weight) = medium.sample_distance(DVec3::ZERO, DVec3::X, 1.0, &mut sampler);
+        if distance >= 1.0 {
+            escaped += weight;
+        }
+    }
+    let expected = (-medium.sigma_t()).exp();
+    let estimate = escaped / SAMPLES as f64;
+    assert!(
+        (estimate - expected).abs().max_element() < 0.02,
+        "got {estimate}, expected {expected}"
+    );
+}
+
+#[test]
+fn scenes_can_fill_objects_with_density_grids() {
+    let dir = std::env::temp_dir().join("raytracer-volume");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("puff.raw");
+    let voxels: Vec<u8> = [0.0f32, 1.0, 2.0, 1.0, 0.0, 1.0, 2.0, 1.0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    std::fs::write(&path, voxels).unwrap();
+
+    let source = format!(
+        "
+camera:
+  lookfrom: [0, 1, 5]
+  lookat: [0, 0, 0]
+  vup: [0, 1, 0]
+  vfov: 40
+  aperture: 0
+  focus_dist: 5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material:
+      type: volume
+      sigma_a: [0.1, 0.1, 0.1]
+      sigma_s: [2, 2, 2]
+      density:
+        path: {}
+        resolution: [2, 2, 2]
+        min: [-1, -1, -1]
+        max: [1, 1, 1]
+",
+        path.display()
+    );
+    assert!(Scene::from_source_at(&source, SceneFormat::Yaml, 0.0).is_ok());
+
+    let missing = source.replace("puff.raw", "missing.raw");
+    assert!(Scene::from_source_at(&missing, SceneFormat::Yaml, 0.0).is_err());
+}
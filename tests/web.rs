@@ -0,0 +1,70 @@
+use raytracer::output::OutputOptions;
+use raytracer::scene::{ImageFiles, Scene};
+use raytracer::web::render_to_rgba_buffer;
+
+const SCENE: &str = r#"{
+  "camera": {
+    "lookfrom": [0, 0, 5], "lookat": [0, 0, 0], "vup": [0, 1, 0],
+    "vfov": 30, "aperture": 0, "focus_dist": 5
+  },
+  "render": { "width": 640, "height": 480, "samples_per_pixel": 4 },
+  "objects": [
+    {
+      "type": "sphere", "center": [0, 0, 0], "radius": 1,
+      "material": {
+        "type": "lambertian",
+        "texture": { "type": "solid_color", "color": [0.8, 0.1, 0.1] }
+      }
+    }
+  ]
+}"#;
+
+#[test]
+fn scenes_load_from_json_text() {
+    let (config, _, world, _) = Scene::from_json_str(SCENE, &ImageFiles::new()).unwrap();
+    assert_eq!(config.render.width, 640);
+    assert!(world.bounding_box().is_some());
+    assert!(Scene::from_json_str("objects: []", &ImageFiles::new()).is_err());
+}
+
+#[test]
+fn canvas_buffers_are_opaque_rgba_at_the_size_asked_for() {
+    let (width, height) = (16, 12);
+    let options = OutputOptions::default();
+    let rgba = render_to_rgba_buffer(SCENE, &ImageFiles::new(), width, height, &options).unwrap();
+    assert_eq!(rgba.len(), (width * height * 4) as usize);
+    assert!(rgba.chunks(4).all(|pixel| pixel[3] == 255));
+
+    // The sphere fills the middle and is red there.
+    let centre = ((height / 2 * width + width / 2) * 4) as usize;
+    let [r, g, b] = [rgba[centre], rgba[centre + 1], rgba[centre + 2]];
+    assert!(r > g && r > b, "{r} {g} {b}");
+}
+
+#[test]
+fn image_textures_are_read_from_memory() {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(4, 4, image::Rgb([0, 255, 0]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let images = ImageFiles::from([("textures/grass.png".to_string(), png.into_inner())]);
+
+    // Whichever way they are filtered, and with no such file on disk.
+    for filter in ["bilinear", "nearest"] {
+        let texture =
+            format!(r#"{{ "type": "image", "path": "textures/grass.png", "filter": "{filter}" }}"#);
+        let scene = SCENE.replace(
+            r#"{ "type": "solid_color", "color": [0.8, 0.1, 0.1] }"#,
+            &texture,
+        );
+        let (width, height) = (16, 12);
+        let options = OutputOptions::default();
+        let rgba = render_to_rgba_buffer(&scene, &images, width, height, &options).unwrap();
+        let centre = ((height / 2 * width + width / 2) * 4) as usize;
+        let [r, g, b] = [rgba[centre], rgba[centre + 1], rgba[centre + 2]];
+        assert!(g > r && g > b, "{filter}: {r} {g} {b}");
+
+        // Images missing from memory are still looked for as files.
+        assert!(Scene::from_json_str(&scene, &ImageFiles::new()).is_err());
+    }
+}